@@ -15,6 +15,15 @@ pub enum SendError<T> {
     /// The channel is closed and the message could not be sent.
     #[error("Channel is closed and the message could not be sent")]
     Closed(T),
+
+    /// The message could not be sent to some of the downstream channels of a broadcast.
+    #[error("The message could not be sent to the downstream channels {failed:?}")]
+    Broadcast {
+        /// The message that could not be sent.
+        msg: T,
+        /// The ids of the downstream channels the message could not be sent to.
+        failed: Vec<usize>,
+    },
 }
 
 /// Errors that can occur when consuming messages from a channel.
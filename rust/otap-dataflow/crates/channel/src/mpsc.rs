@@ -8,7 +8,10 @@ use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 
 struct ChannelState<T> {
     buffer: VecDeque<T>,
@@ -121,6 +124,29 @@ impl<T> Sender<T> {
         Ok(())
     }
 
+    /// Sends a value to the channel, evicting the oldest buffered value if the channel is full.
+    /// Returns the evicted value, if any.
+    pub fn force_send(&self, value: T) -> Result<Option<T>, SendError<T>> {
+        let mut state = self.channel.state.borrow_mut();
+
+        if state.is_closed || !state.has_receiver {
+            return Err(SendError::Closed(value));
+        }
+
+        let evicted = if state.buffer.len() >= state.capacity {
+            state.buffer.pop_front()
+        } else {
+            None
+        };
+        state.buffer.push_back(value);
+
+        if let Some(waker) = state.receiver_waker.take() {
+            waker.wake();
+        }
+
+        Ok(evicted)
+    }
+
     /// Sends a value to the channel asynchronously.
     pub async fn send_async(&self, value: T) -> Result<(), SendError<T>> {
         SendFuture {
@@ -130,6 +156,52 @@ impl<T> Sender<T> {
         .await
     }
 
+    /// Sends a value to the channel asynchronously, giving up once the timeout elapsed. The value
+    /// is returned back in a [`SendError::Full`] if the channel stayed full until then.
+    pub async fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendError<T>> {
+        // The value is kept out of the send future, to be given back if the timeout elapses.
+        let mut value = Some(value);
+        let send = std::future::poll_fn(|cx| match value.take() {
+            Some(pending) => match self.send(pending) {
+                Err(SendError::Full(pending)) => {
+                    value = Some(pending);
+                    let mut state = self.channel.state.borrow_mut();
+                    state.sender_wakers.push_back(cx.waker().clone());
+                    Poll::Pending
+                }
+                result => Poll::Ready(result),
+            },
+            // The future completes with the value sent, it isn't polled again.
+            None => Poll::Ready(Ok(())),
+        });
+        match tokio::time::timeout(timeout, send).await {
+            Ok(result) => result,
+            Err(_) => match value.take() {
+                Some(value) => Err(SendError::Full(value)),
+                // A pending send always holds its value, the timeout can't elapse without it.
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Returns the number of values currently buffered in the channel.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.channel.state.borrow().buffer.len()
+    }
+
+    /// Returns true if no value is currently buffered in the channel.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the channel.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.channel.state.borrow().capacity
+    }
+
     /// Closes the channel.
     pub fn close(&self) {
         let mut state = self.channel.state.borrow_mut();
@@ -169,6 +241,294 @@ impl<T> Receiver<T> {
     pub async fn recv(&self) -> Result<T, RecvError> {
         RecvFuture { receiver: self }.await
     }
+
+    /// Polls to receive a value from the channel, registering the waker of the given context to
+    /// be notified when a value is sent if the channel is empty.
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(RecvError::Empty) => {
+                let mut state = self.channel.state.borrow_mut();
+                state.receiver_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// A lossy variant of the [`Channel`] for high-volume data where dropping the oldest values is
+/// preferable to blocking the producers: sending never blocks, the oldest unconsumed value is
+/// overwritten when the channel is full.
+pub struct RingChannel;
+
+impl RingChannel {
+    /// Creates a new ring channel with the given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is zero, as a ring channel always buffers the last value sent.
+    #[allow(clippy::new_ret_no_self)]
+    #[must_use]
+    pub fn new<T>(capacity: usize) -> (RingSender<T>, Receiver<T>) {
+        assert!(
+            capacity > 0,
+            "A ring channel needs a capacity of at least 1"
+        );
+        let (sender, receiver) = Channel::new(capacity);
+        (RingSender { sender }, receiver)
+    }
+}
+
+/// The outcome of a value sent to a [`RingChannel`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum RingSendResult<T> {
+    /// The value was buffered without overwriting any other value.
+    Sent,
+    /// The value was buffered, overwriting the given oldest unconsumed value.
+    Dropped(T),
+}
+
+/// A sender for the ring channel.
+pub struct RingSender<T> {
+    sender: Sender<T>,
+}
+
+impl<T> Clone for RingSender<T> {
+    fn clone(&self) -> Self {
+        RingSender {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> RingSender<T> {
+    /// Sends a value to the channel, overwriting the oldest unconsumed value if the channel is
+    /// full.
+    pub fn send(&self, value: T) -> Result<RingSendResult<T>, SendError<T>> {
+        Ok(match self.sender.force_send(value)? {
+            Some(dropped) => RingSendResult::Dropped(dropped),
+            None => RingSendResult::Sent,
+        })
+    }
+
+    /// Returns the number of values currently buffered in the channel.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Returns true if no value is currently buffered in the channel.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sender.is_empty()
+    }
+
+    /// Returns the capacity of the channel.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.sender.capacity()
+    }
+}
+
+/// Creates a [`Channel`] with the given capacity whose traffic is measured (see
+/// [`ChannelMetrics`]), e.g. to find out where the back-pressure of a pipeline originates. The
+/// name identifies the channel in the snapshots of its metrics.
+#[must_use]
+pub fn instrumented_channel<T>(
+    capacity: usize,
+    name: impl Into<String>,
+) -> (InstrumentedSender<T>, InstrumentedReceiver<T>) {
+    let (sender, receiver) = Channel::new(capacity);
+    let metrics = Arc::new(ChannelMetrics {
+        name: name.into(),
+        capacity,
+        enqueued: AtomicU64::new(0),
+        dequeued: AtomicU64::new(0),
+        depth: AtomicUsize::new(0),
+        high_water_mark: AtomicUsize::new(0),
+    });
+    (
+        InstrumentedSender {
+            sender,
+            metrics: metrics.clone(),
+        },
+        InstrumentedReceiver { receiver, metrics },
+    )
+}
+
+/// The metrics of an instrumented channel (see [`instrumented_channel`]), shared by its sender(s)
+/// and its receiver.
+///
+/// Note: Unlike the channel itself, the metrics are `Send` and can be read from another thread.
+#[derive(Debug)]
+pub struct ChannelMetrics {
+    name: String,
+    capacity: usize,
+    enqueued: AtomicU64,
+    dequeued: AtomicU64,
+    depth: AtomicUsize,
+    high_water_mark: AtomicUsize,
+}
+
+/// A point-in-time copy of the [`ChannelMetrics`] of a channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelMetricsSnapshot {
+    /// The name of the channel.
+    pub name: String,
+    /// The capacity of the channel.
+    pub capacity: usize,
+    /// The number of values sent to the channel since it was created.
+    pub enqueued: u64,
+    /// The number of values received from the channel since it was created.
+    pub dequeued: u64,
+    /// The number of values currently buffered in the channel.
+    pub depth: usize,
+    /// The highest number of values buffered at once in the channel since it was created.
+    pub high_water_mark: usize,
+}
+
+impl ChannelMetrics {
+    /// Returns a snapshot of the metrics.
+    #[must_use]
+    pub fn snapshot(&self) -> ChannelMetricsSnapshot {
+        ChannelMetricsSnapshot {
+            name: self.name.clone(),
+            capacity: self.capacity,
+            enqueued: self.enqueued.load(Ordering::Relaxed),
+            dequeued: self.dequeued.load(Ordering::Relaxed),
+            depth: self.depth.load(Ordering::Relaxed),
+            high_water_mark: self.high_water_mark.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_enqueued(&self) {
+        _ = self.enqueued.fetch_add(1, Ordering::Relaxed);
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        _ = self.high_water_mark.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    fn record_dequeued(&self) {
+        _ = self.dequeued.fetch_add(1, Ordering::Relaxed);
+        _ = self.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A sender for an instrumented channel (see [`instrumented_channel`]).
+pub struct InstrumentedSender<T> {
+    sender: Sender<T>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+/// A receiver for an instrumented channel (see [`instrumented_channel`]).
+pub struct InstrumentedReceiver<T> {
+    receiver: Receiver<T>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> Clone for InstrumentedSender<T> {
+    fn clone(&self) -> Self {
+        InstrumentedSender {
+            sender: self.sender.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T> InstrumentedSender<T> {
+    /// Sends a value to the channel.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.send(value)?;
+        self.metrics.record_enqueued();
+        Ok(())
+    }
+
+    /// Sends a value to the channel asynchronously.
+    pub async fn send_async(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.send_async(value).await?;
+        self.metrics.record_enqueued();
+        Ok(())
+    }
+
+    /// Sends a value to the channel asynchronously, giving up once the timeout elapsed (see
+    /// [`Sender::send_timeout`]).
+    pub async fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendError<T>> {
+        self.sender.send_timeout(value, timeout).await?;
+        self.metrics.record_enqueued();
+        Ok(())
+    }
+
+    /// Returns the number of values currently buffered in the channel.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Returns true if no value is currently buffered in the channel.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sender.is_empty()
+    }
+
+    /// Returns the capacity of the channel.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.sender.capacity()
+    }
+
+    /// Closes the channel.
+    pub fn close(&self) {
+        self.sender.close();
+    }
+
+    /// Returns a snapshot of the metrics of the channel.
+    #[must_use]
+    pub fn snapshot(&self) -> ChannelMetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the metrics of the channel, e.g. to read them from another thread.
+    #[must_use]
+    pub fn metrics(&self) -> Arc<ChannelMetrics> {
+        self.metrics.clone()
+    }
+}
+
+impl<T> InstrumentedReceiver<T> {
+    /// Tries to receive a value from the channel.
+    pub fn try_recv(&self) -> Result<T, RecvError> {
+        let value = self.receiver.try_recv()?;
+        self.metrics.record_dequeued();
+        Ok(value)
+    }
+
+    /// Receives a value from the channel asynchronously.
+    pub async fn recv(&self) -> Result<T, RecvError> {
+        let value = self.receiver.recv().await?;
+        self.metrics.record_dequeued();
+        Ok(value)
+    }
+
+    /// Polls to receive a value from the channel (see [`Receiver::poll_recv`]).
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let poll = self.receiver.poll_recv(cx);
+        if let Poll::Ready(Ok(_)) = poll {
+            self.metrics.record_dequeued();
+        }
+        poll
+    }
+
+    /// Returns a snapshot of the metrics of the channel.
+    #[must_use]
+    pub fn snapshot(&self) -> ChannelMetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the metrics of the channel, e.g. to read them from another thread.
+    #[must_use]
+    pub fn metrics(&self) -> Arc<ChannelMetrics> {
+        self.metrics.clone()
+    }
 }
 
 struct SendFuture<T> {
@@ -208,15 +568,7 @@ impl<T> Future for RecvFuture<'_, T> {
     type Output = Result<T, RecvError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
-        match self.receiver.try_recv() {
-            Ok(value) => Poll::Ready(Ok(value)),
-            Err(RecvError::Empty) => {
-                let mut state = self.receiver.channel.state.borrow_mut();
-                state.receiver_waker = Some(cx.waker().clone());
-                Poll::Pending
-            }
-            Err(e) => Poll::Ready(Err(e)),
-        }
+        self.receiver.poll_recv(cx)
     }
 }
 
@@ -283,6 +635,60 @@ mod tests {
         rt.block_on(handle).expect("Test task failed");
     }
 
+    #[test]
+    fn test_force_send_evicts_oldest() {
+        let rt = create_test_runtime();
+        let local = tokio::task::LocalSet::new();
+
+        let handle = local.spawn_local(async {
+            let (tx, rx) = Channel::new(2);
+
+            assert!(matches!(tx.force_send(1), Ok(None)));
+            assert!(matches!(tx.force_send(2), Ok(None)));
+            assert!(matches!(tx.force_send(3), Ok(Some(1))));
+            assert_eq!(rx.try_recv().unwrap(), 2);
+            assert_eq!(rx.try_recv().unwrap(), 3);
+
+            drop(rx);
+            assert!(matches!(tx.force_send(4), Err(SendError::Closed(4))));
+        });
+
+        rt.block_on(local);
+        rt.block_on(handle).expect("Test task failed");
+    }
+
+    #[test]
+    fn test_ring_channel_overwrites_oldest() {
+        let rt = create_test_runtime();
+        let local = tokio::task::LocalSet::new();
+
+        let handle = local.spawn_local(async {
+            let (tx, rx) = RingChannel::new(2);
+
+            assert_eq!(tx.send(1).unwrap(), RingSendResult::Sent);
+            assert_eq!(tx.send(2).unwrap(), RingSendResult::Sent);
+            assert_eq!(tx.send(3).unwrap(), RingSendResult::Dropped(1));
+            assert_eq!(tx.send(4).unwrap(), RingSendResult::Dropped(2));
+            assert_eq!(tx.len(), 2);
+            assert_eq!(rx.recv().await.unwrap(), 3);
+            assert_eq!(tx.send(5).unwrap(), RingSendResult::Sent);
+            assert_eq!(rx.recv().await.unwrap(), 4);
+            assert_eq!(rx.recv().await.unwrap(), 5);
+
+            drop(tx);
+            assert!(matches!(rx.recv().await, Err(RecvError::Closed)));
+        });
+
+        rt.block_on(local);
+        rt.block_on(handle).expect("Test task failed");
+    }
+
+    #[test]
+    #[should_panic(expected = "A ring channel needs a capacity of at least 1")]
+    fn test_ring_channel_zero_capacity() {
+        let _channel = RingChannel::new::<u64>(0);
+    }
+
     #[test]
     fn test_multiple_producers() {
         let rt = create_test_runtime();
@@ -487,4 +893,77 @@ mod tests {
         rt.block_on(local);
         rt.block_on(handle).expect("Test task failed");
     }
+
+    #[test]
+    fn test_instrumented_channel_high_water_mark() {
+        let rt = create_test_runtime();
+        let local = tokio::task::LocalSet::new();
+
+        let handle = local.spawn_local(async {
+            let (tx, rx) = instrumented_channel(8, "test_channel");
+
+            // A burst of 5 values, partially drained, then a smaller burst
+            for i in 0..5 {
+                assert!(tx.send(i).is_ok());
+            }
+            assert_eq!(rx.try_recv().unwrap(), 0);
+            assert_eq!(rx.recv().await.unwrap(), 1);
+            for i in 5..7 {
+                assert!(tx.send_async(i).await.is_ok());
+            }
+            let metrics = tx.metrics();
+            assert_eq!(
+                rx.snapshot(),
+                ChannelMetricsSnapshot {
+                    name: "test_channel".to_owned(),
+                    capacity: 8,
+                    enqueued: 7,
+                    dequeued: 2,
+                    depth: 5,
+                    high_water_mark: 5,
+                }
+            );
+
+            // A burst of 3 more values fills the channel
+            for i in 7..10 {
+                assert!(tx.send(i).is_ok());
+            }
+            // The value rejected by the full channel is not counted
+            assert!(matches!(tx.send(10), Err(SendError::Full(10))));
+            assert_eq!(metrics.snapshot().high_water_mark, 8);
+            assert_eq!(metrics.snapshot().enqueued, 10);
+        });
+
+        rt.block_on(local);
+        rt.block_on(handle).expect("Test task failed");
+    }
+
+    #[test]
+    fn test_instrumented_channel_drained() {
+        let rt = create_test_runtime();
+        let local = tokio::task::LocalSet::new();
+
+        let handle = local.spawn_local(async {
+            let (tx, rx) = instrumented_channel(4, "test_channel");
+
+            for _ in 0..3 {
+                for i in 0..4 {
+                    assert!(tx.send(i).is_ok());
+                }
+                assert_eq!(tx.snapshot().depth, 4);
+                while rx.try_recv().is_ok() {}
+                assert_eq!(tx.snapshot().depth, 0);
+            }
+
+            let snapshot = rx.snapshot();
+            assert_eq!(snapshot.enqueued, 12);
+            assert_eq!(snapshot.dequeued, 12);
+            assert_eq!(snapshot.depth, 0);
+            assert_eq!(snapshot.high_water_mark, 4);
+            assert_eq!(tx.len(), 0);
+        });
+
+        rt.block_on(local);
+        rt.block_on(handle).expect("Test task failed");
+    }
 }
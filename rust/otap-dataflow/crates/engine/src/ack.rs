@@ -0,0 +1,285 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Routing of Ack/Nack control messages back to the receiver that emitted the acknowledged pdata.
+//!
+//! Receivers tag the pdata they emit with a message id obtained from their effect handler (see
+//! `next_message_id`). The upper 16 bits of an id identify the route of the originating receiver
+//! and the lower 48 bits carry a per-receiver sequence number. Downstream nodes (e.g. exporters)
+//! only have to echo the id back, the [`AckRouter`] takes care of delivering the corresponding
+//! [`ControlMsg::Ack`] or [`ControlMsg::Nack`] to the control channel of the right receiver, even
+//! when multiple receivers share a pipeline.
+
+use crate::error::Error;
+use crate::message::{ControlMsg, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of bits of a message id reserved for the per-receiver sequence number.
+const SEQ_BITS: u32 = 48;
+const SEQ_MASK: u64 = (1 << SEQ_BITS) - 1;
+
+/// Route used by effect handlers that are not registered with an [`AckRouter`]. Ids allocated on
+/// this route can't be routed.
+pub(crate) const UNROUTED: u16 = 0;
+
+/// Allocates the ids of the messages emitted by a node.
+///
+/// Note: This implementation is `Send`.
+pub(crate) struct MessageIdGenerator {
+    route: u16,
+    next_seq: AtomicU64,
+}
+
+impl MessageIdGenerator {
+    /// Creates a new generator for the given route.
+    pub(crate) fn new(route: u16) -> Self {
+        MessageIdGenerator {
+            route,
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the next message id for this route.
+    pub(crate) fn next_id(&self) -> u64 {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed) & SEQ_MASK;
+        (u64::from(self.route) << SEQ_BITS) | seq
+    }
+}
+
+/// Extracts the route from a message id.
+#[must_use]
+pub fn route_of(id: u64) -> u16 {
+    (id >> SEQ_BITS) as u16
+}
+
+/// Routes Ack/Nack control messages to the control channel of the receiver that emitted the
+/// acknowledged message.
+pub struct AckRouter {
+    /// Control senders indexed by route - 1 (route 0 is reserved for unrouted ids).
+    routes: Vec<Sender<ControlMsg>>,
+}
+
+impl Default for AckRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AckRouter {
+    /// Creates a new router without any registered route.
+    #[must_use]
+    pub fn new() -> Self {
+        AckRouter { routes: Vec::new() }
+    }
+
+    /// Registers the control sender of a receiver and returns the route assigned to it.
+    ///
+    /// Returns `None` if all the routes are already in use.
+    pub fn register(&mut self, control_sender: Sender<ControlMsg>) -> Option<u16> {
+        let route = u16::try_from(self.routes.len() + 1).ok()?;
+        self.routes.push(control_sender);
+        Some(route)
+    }
+
+    /// Sends an Ack for the given message id to the receiver that emitted it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::UnknownAckRoute`] if no receiver is registered for the id, or an
+    /// [`Error::ChannelSendError`] if the receiver control channel is closed.
+    pub async fn route_ack(&self, id: u64) -> Result<(), Error<ControlMsg>> {
+        self.route(id, ControlMsg::Ack { id }).await
+    }
+
+    /// Sends a Nack for the given message id to the receiver that emitted it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::UnknownAckRoute`] if no receiver is registered for the id, or an
+    /// [`Error::ChannelSendError`] if the receiver control channel is closed.
+    pub async fn route_nack(&self, id: u64, reason: &str) -> Result<(), Error<ControlMsg>> {
+        self.route(
+            id,
+            ControlMsg::Nack {
+                id,
+                reason: reason.to_owned(),
+            },
+        )
+        .await
+    }
+
+    async fn route(&self, id: u64, msg: ControlMsg) -> Result<(), Error<ControlMsg>> {
+        let route = route_of(id);
+        let sender = (route != UNROUTED)
+            .then(|| self.routes.get(usize::from(route) - 1))
+            .flatten()
+            .ok_or(Error::UnknownAckRoute { id })?;
+        sender.send(msg).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AckRouter, route_of};
+    use crate::config::ReceiverConfig;
+    use crate::error::Error;
+    use crate::local::receiver as local;
+    use crate::message::ControlMsg;
+    use crate::receiver::ReceiverWrapper;
+    use crate::shared::receiver as shared;
+    use crate::testing::{CtrlMsgCounters, TestMsg, setup_test_runtime};
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    /// A receiver emitting a single tagged message and then counting the control messages it gets.
+    struct AckAwareReceiver {
+        counters: CtrlMsgCounters,
+    }
+
+    #[async_trait(?Send)]
+    impl local::Receiver<TestMsg> for AckAwareReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_chan: local::ControlChannel,
+            effect_handler: local::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            let id = effect_handler.next_message_id();
+            effect_handler.send_message(TestMsg(id.to_string())).await?;
+            loop {
+                let msg = ctrl_chan.recv().await?;
+                self.counters.update_with(&msg);
+                if msg.is_shutdown() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Receiver<TestMsg> for AckAwareReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_chan: shared::ControlChannel,
+            effect_handler: shared::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            let id = effect_handler.next_message_id();
+            effect_handler.send_message(TestMsg(id.to_string())).await?;
+            loop {
+                let msg = ctrl_chan.recv().await?;
+                self.counters.update_with(&msg);
+                if msg.is_shutdown() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_nack_reaches_originating_receiver() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let local_counters = CtrlMsgCounters::new();
+        let shared_counters = CtrlMsgCounters::new();
+        let mut router = AckRouter::new();
+
+        let mut local_receiver = ReceiverWrapper::local(
+            AckAwareReceiver {
+                counters: local_counters.clone(),
+            },
+            &ReceiverConfig::new("local_receiver"),
+        );
+        let mut shared_receiver = ReceiverWrapper::shared(
+            AckAwareReceiver {
+                counters: shared_counters.clone(),
+            },
+            &ReceiverConfig::new("shared_receiver"),
+        );
+        let local_route = local_receiver
+            .register_ack_route(&mut router)
+            .expect("Failed to register route");
+        let shared_route = shared_receiver
+            .register_ack_route(&mut router)
+            .expect("Failed to register route");
+        assert_ne!(local_route, shared_route);
+
+        let local_ctrl = local_receiver.control_sender();
+        let shared_ctrl = shared_receiver.control_sender();
+        let mut local_pdata = local_receiver
+            .take_pdata_receiver()
+            .expect("Failed to take the pdata receiver");
+        let mut shared_pdata = shared_receiver
+            .take_pdata_receiver()
+            .expect("Failed to take the pdata receiver");
+
+        let (observed_local, observed_shared) = (local_counters.clone(), shared_counters.clone());
+        rt.block_on(local_tasks.run_until(async move {
+            let local_handle = tokio::task::spawn_local(local_receiver.start());
+            let shared_handle = tokio::task::spawn_local(shared_receiver.start());
+
+            // The "exporter" side: acknowledge the local message, reject the shared one.
+            let local_id: u64 = local_pdata
+                .recv()
+                .await
+                .expect("No pdata")
+                .0
+                .parse()
+                .unwrap();
+            let shared_id: u64 = shared_pdata
+                .recv()
+                .await
+                .expect("No pdata")
+                .0
+                .parse()
+                .unwrap();
+            assert_eq!(route_of(local_id), local_route);
+            assert_eq!(route_of(shared_id), shared_route);
+
+            router
+                .route_ack(local_id)
+                .await
+                .expect("Failed to route Ack");
+            router
+                .route_nack(shared_id, "export failed")
+                .await
+                .expect("Failed to route Nack");
+
+            // Shutdown preempts the pending control messages, wait for the Ack/Nack to be observed.
+            while observed_local.get_ack_count() + observed_shared.get_nack_count() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            for ctrl in [local_ctrl, shared_ctrl] {
+                ctrl.send(ControlMsg::Shutdown {
+                    deadline: Duration::ZERO,
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+            }
+
+            local_handle
+                .await
+                .expect("Receiver task failed")
+                .expect("Receiver failed");
+            shared_handle
+                .await
+                .expect("Receiver task failed")
+                .expect("Receiver failed");
+        }));
+
+        assert_eq!(local_counters.get_ack_count(), 1);
+        assert_eq!(local_counters.get_nack_count(), 0);
+        assert_eq!(shared_counters.get_ack_count(), 0);
+        assert_eq!(shared_counters.get_nack_count(), 1);
+    }
+
+    #[test]
+    fn test_unrouted_id_is_rejected() {
+        let (rt, _local_tasks) = setup_test_runtime();
+        let router = AckRouter::new();
+
+        let result = rt.block_on(router.route_ack(42));
+        assert!(matches!(result, Err(Error::UnknownAckRoute { id: 42 })));
+    }
+}
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Back-pressure signaling to receivers.
+//!
+//! When enabled (see [`BackpressureConfig`]), the engine periodically checks the number of pdata
+//! messages buffered in the output channel of a receiver. A `Pause` control message is sent
+//! to the receiver once the channel stays above the high watermark for a number of consecutive
+//! checks, and a `Resume` once it drops below the low watermark. This lets receivers stop
+//! accepting new data instead of blocking on a full pdata channel.
+
+use crate::config::BackpressureConfig;
+use crate::message::{ControlMsg, PrioritySender};
+use std::future::Future;
+
+/// Runs the node future to completion while monitoring the output pdata channel of the node, if a
+/// back-pressure configuration is given.
+///
+/// `buffered` returns the number of pdata messages currently buffered in the output channel.
+pub(crate) async fn with_backpressure<T>(
+    config: Option<BackpressureConfig>,
+    buffered: impl Fn() -> usize,
+    control_sender: PrioritySender<ControlMsg>,
+    node_future: impl Future<Output = T>,
+) -> T {
+    let Some(config) = config else {
+        return node_future.await;
+    };
+    tokio::pin!(node_future);
+    tokio::select! {
+        biased;
+        output = &mut node_future => output,
+        // The monitor only returns once the control channel is closed.
+        () = monitor(config, buffered, control_sender) => node_future.await,
+    }
+}
+
+/// Sends `Pause` and `Resume` to the given control channel according to the
+/// watermarks of the configuration, until the control channel is closed.
+async fn monitor(
+    config: BackpressureConfig,
+    buffered: impl Fn() -> usize,
+    control_sender: PrioritySender<ControlMsg>,
+) {
+    let mut interval = tokio::time::interval(config.check_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut congested_checks = 0;
+    let mut paused = false;
+
+    loop {
+        _ = interval.tick().await;
+        let buffered = buffered();
+        let signal = if paused {
+            (buffered < config.low_watermark).then_some(ControlMsg::Resume)
+        } else if buffered > config.high_watermark {
+            congested_checks += 1;
+            (congested_checks >= config.consecutive_checks).then_some(ControlMsg::Pause)
+        } else {
+            congested_checks = 0;
+            None
+        };
+
+        if let Some(signal) = signal {
+            paused = signal.is_pause();
+            congested_checks = 0;
+            if control_sender.send(signal).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::monitor;
+    use crate::config::BackpressureConfig;
+    use crate::message::{ControlMsg, priority_channel};
+    use crate::testing::setup_test_runtime;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::time::{Instant, timeout};
+
+    #[test]
+    fn test_pause_and_resume_on_watermarks() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let config = BackpressureConfig {
+            high_watermark: 8,
+            low_watermark: 2,
+            consecutive_checks: 3,
+            check_interval: Duration::from_millis(10),
+        };
+        let buffered = Arc::new(AtomicUsize::new(10));
+        let (control_tx, control_rx) = priority_channel(8, ControlMsg::is_high_priority);
+
+        rt.block_on(local_tasks.run_until(async move {
+            let monitored = buffered.clone();
+            let started = Instant::now();
+            _ = tokio::task::spawn_local(monitor(
+                config,
+                move || monitored.load(Ordering::Relaxed),
+                control_tx,
+            ));
+
+            let msg = timeout(Duration::from_secs(1), control_rx.recv())
+                .await
+                .expect("Timed out waiting for Pause")
+                .expect("Control channel closed");
+            assert!(msg.is_pause());
+            // The first tick completes immediately, the third one after two intervals.
+            assert!(started.elapsed() >= 2 * config.check_interval);
+
+            // Still above the low watermark, the receiver stays paused.
+            buffered.store(5, Ordering::Relaxed);
+            assert!(
+                timeout(Duration::from_millis(50), control_rx.recv())
+                    .await
+                    .is_err()
+            );
+
+            buffered.store(1, Ordering::Relaxed);
+            let msg = timeout(Duration::from_secs(1), control_rx.recv())
+                .await
+                .expect("Timed out waiting for Resume")
+                .expect("Control channel closed");
+            assert!(msg.is_resume());
+        }));
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Budget of the bytes in flight between a receiver and the downstream node(s).
+//!
+//! The capacity of a pdata channel is measured in messages, a receiver reading arbitrary-size
+//! network payloads can then exhaust the memory before its output channel is full. When enabled
+//! (see the `max_inflight_bytes` field of the [`ReceiverConfig`](crate::config::ReceiverConfig)),
+//! the receiver effect handler charges the size of each pdata message sent to the output channel
+//! to an [`InflightBudget`], and waits while the budget is exhausted. The size is refunded when
+//! the message is received from the channel (see [`BudgetedReceiver`](crate::message::BudgetedReceiver)).
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+
+/// The bytes in flight in the output channel of a receiver, shared by its effect handler and by
+/// the receiving side of the channel.
+///
+/// Note: This implementation is `Send`.
+#[derive(Debug)]
+pub(crate) struct InflightBudget {
+    max_bytes: usize,
+    inflight_bytes: AtomicUsize,
+    /// Notified when some bytes are refunded.
+    released: Notify,
+}
+
+impl InflightBudget {
+    /// Creates a budget of `max_bytes` bytes in flight.
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        InflightBudget {
+            max_bytes,
+            inflight_bytes: AtomicUsize::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Charges `bytes` to the budget if it has room for them. A message larger than the whole
+    /// budget is accepted once nothing else is in flight, so that it can't block forever.
+    pub(crate) fn try_acquire(&self, bytes: usize) -> bool {
+        if bytes == 0 {
+            return true;
+        }
+        self.inflight_bytes
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |inflight| {
+                (inflight == 0 || inflight.saturating_add(bytes) <= self.max_bytes)
+                    .then(|| inflight.saturating_add(bytes))
+            })
+            .is_ok()
+    }
+
+    /// Charges `bytes` to the budget, waiting for some bytes to be refunded while it has no room
+    /// for them.
+    pub(crate) async fn acquire(&self, bytes: usize) {
+        loop {
+            // Registered before the check so that a refund in between isn't missed.
+            let released = self.released.notified();
+            if self.try_acquire(bytes) {
+                return;
+            }
+            released.await;
+        }
+    }
+
+    /// Refunds `bytes` to the budget.
+    pub(crate) fn release(&self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        _ = self
+            .inflight_bytes
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |inflight| {
+                Some(inflight.saturating_sub(bytes))
+            });
+        self.released.notify_waiters();
+    }
+
+    /// Returns the number of bytes currently in flight.
+    pub(crate) fn inflight_bytes(&self) -> usize {
+        self.inflight_bytes.load(Ordering::Acquire)
+    }
+}
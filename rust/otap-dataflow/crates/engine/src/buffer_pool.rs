@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Capacity shared by the output pdata channels of several receivers.
+//!
+//! The capacity of the output channel of a receiver only bounds the messages buffered by that
+//! receiver, with many receivers the worst case adds up. A [`GlobalBufferPool`] registered on a
+//! pipeline (see [`PipelineBuilder::buffer_pool`](crate::pipeline::PipelineBuilder::buffer_pool))
+//! bounds the total number of messages buffered in the output channels of the receivers naming it
+//! (see the `pool` field of [`PdataChannelConfig`](crate::config::PdataChannelConfig)). Once the
+//! pool is exhausted, a receiver sending a message is handled as if its output channel was full
+//! (see [`OverflowPolicy`](crate::config::OverflowPolicy)), even if the channel has some capacity
+//! left.
+//!
+//! Every member of the pool is guaranteed a number of reserved slots, the rest of the pool being
+//! shared on a first-come basis, so that a chatty receiver can't starve the other ones.
+//!
+//! The messages buffered by a member are counted from the length of its output channel, refreshed
+//! when the member sends a message and periodically while the receiver runs. A slot released by
+//! the downstream node is therefore only observed by the other members within
+//! [`POOL_REFRESH_INTERVAL`].
+//!
+//! Note: Only the default output channel of a receiver borrows from its pool, the channels of its
+//! named output ports don't.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Interval between two refreshes of the number of messages buffered by a member of a pool.
+pub const POOL_REFRESH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A budget of pdata messages shared by the output channels of several receivers.
+///
+/// Note: This implementation is `Send`.
+#[derive(Debug)]
+pub struct GlobalBufferPool {
+    capacity: usize,
+    reserved_per_member: usize,
+    members: Mutex<Vec<MemberUsage>>,
+    /// Notified when a member buffers fewer messages.
+    released: Notify,
+}
+
+/// The slots used by a member of a pool.
+#[derive(Debug, Default)]
+struct MemberUsage {
+    /// The number of messages buffered in the output channel of the member.
+    buffered: usize,
+    /// The number of slots taken by messages being sent to the output channel.
+    pending: usize,
+}
+
+impl MemberUsage {
+    fn used(&self) -> usize {
+        self.buffered + self.pending
+    }
+}
+
+impl GlobalBufferPool {
+    /// Creates a pool of `capacity` messages, reserving one slot to each member.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        GlobalBufferPool {
+            capacity,
+            reserved_per_member: 1,
+            members: Mutex::default(),
+            released: Notify::new(),
+        }
+    }
+
+    /// Sets the number of slots reserved to each member of the pool, which the other members
+    /// can't take.
+    #[must_use]
+    pub fn with_reservation(mut self, reserved_per_member: usize) -> Self {
+        self.reserved_per_member = reserved_per_member;
+        self
+    }
+
+    /// Returns the total number of messages of the pool.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of slots reserved to each member of the pool.
+    #[must_use]
+    pub fn reserved_per_member(&self) -> usize {
+        self.reserved_per_member
+    }
+
+    /// Returns the number of slots currently used by all the members of the pool.
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.lock().iter().map(MemberUsage::used).sum()
+    }
+
+    /// Adds a member to the pool, or returns `None` if the pool can't reserve its slots. Every
+    /// call adds a new member, which stays in the pool for its whole lifetime.
+    pub(crate) fn join(self: &Arc<Self>) -> Option<PoolMember> {
+        let mut members = self.lock();
+        let reserved = (members.len() + 1).checked_mul(self.reserved_per_member)?;
+        if reserved > self.capacity {
+            return None;
+        }
+        members.push(MemberUsage::default());
+        Some(PoolMember {
+            pool: self.clone(),
+            index: members.len() - 1,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<MemberUsage>> {
+        // The usages stay consistent even if a thread panicked while holding the lock.
+        self.members
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Returns true if the given member can take one more slot, i.e. if it has a reserved slot
+    /// left or if some slot beyond the reservations is left.
+    fn has_room(&self, members: &[MemberUsage], index: usize) -> bool {
+        if members[index].used() < self.reserved_per_member {
+            return true;
+        }
+        let beyond_reservations: usize = members
+            .iter()
+            .map(|member| member.used().saturating_sub(self.reserved_per_member))
+            .sum();
+        let shared = self.capacity - members.len() * self.reserved_per_member;
+        beyond_reservations < shared
+    }
+}
+
+/// The membership of a receiver in a [`GlobalBufferPool`], shared by the clones of its effect
+/// handler.
+#[derive(Clone, Debug)]
+pub(crate) struct PoolMember {
+    pool: Arc<GlobalBufferPool>,
+    index: usize,
+}
+
+impl PoolMember {
+    /// Returns true if the member belongs to the given pool.
+    pub(crate) fn belongs_to(&self, pool: &Arc<GlobalBufferPool>) -> bool {
+        Arc::ptr_eq(&self.pool, pool)
+    }
+
+    /// Takes a slot of the pool if it has room for the member, given the number of messages
+    /// currently buffered in the output channel of the member.
+    pub(crate) fn try_acquire(&self, buffered: usize) -> Option<PoolPermit<'_>> {
+        let mut members = self.pool.lock();
+        members[self.index].buffered = buffered;
+        if !self.pool.has_room(&members, self.index) {
+            return None;
+        }
+        members[self.index].pending += 1;
+        Some(PoolPermit { member: Some(self) })
+    }
+
+    /// Takes a slot of the pool, waiting while the pool has no room for the member.
+    /// `buffered` returns the number of messages buffered in the output channel of the member.
+    pub(crate) async fn acquire(&self, buffered: impl Fn() -> usize) -> PoolPermit<'_> {
+        loop {
+            // Registered before the check so that a release in between isn't missed.
+            let released = self.pool.released.notified();
+            if let Some(permit) = self.try_acquire(buffered()) {
+                return permit;
+            }
+            // The releases of the other members are only observed when they are refreshed.
+            _ = tokio::time::timeout(POOL_REFRESH_INTERVAL, released).await;
+        }
+    }
+
+    /// Records the number of messages buffered in the output channel of the member.
+    pub(crate) fn refresh(&self, buffered: usize) {
+        let released = {
+            let mut members = self.pool.lock();
+            let usage = &mut members[self.index];
+            let released = buffered < usage.buffered;
+            usage.buffered = buffered;
+            released
+        };
+        if released {
+            self.pool.released.notify_waiters();
+        }
+    }
+
+    /// Returns the slot of a message whose sending completed, or was abandoned.
+    fn settle(&self, buffered: Option<usize>) {
+        {
+            let mut members = self.pool.lock();
+            let usage = &mut members[self.index];
+            usage.pending = usage.pending.saturating_sub(1);
+            if let Some(buffered) = buffered {
+                usage.buffered = buffered;
+            }
+        }
+        self.pool.released.notify_waiters();
+    }
+}
+
+/// A slot taken from a pool for a message being sent to the output channel of a member. The slot
+/// is returned to the pool when the permit is dropped, and counted again from the length of the
+/// output channel once the sending completed (see [`PoolPermit::complete`]).
+#[must_use]
+pub(crate) struct PoolPermit<'a> {
+    member: Option<&'a PoolMember>,
+}
+
+impl PoolPermit<'_> {
+    /// A permit of a receiver that doesn't belong to any pool.
+    pub(crate) fn unpooled() -> Self {
+        PoolPermit { member: None }
+    }
+
+    /// Completes the sending of the message, given the number of messages now buffered in the
+    /// output channel of the member (whether the message made it to the channel or not).
+    pub(crate) fn complete(mut self, buffered: usize) {
+        if let Some(member) = self.member.take() {
+            member.settle(Some(buffered));
+        }
+    }
+}
+
+impl Drop for PoolPermit<'_> {
+    fn drop(&mut self) {
+        // The sending was cancelled, the length of the channel is refreshed later on.
+        if let Some(member) = self.member.take() {
+            member.settle(None);
+        }
+    }
+}
+
+/// Runs the node future to completion while refreshing the number of messages buffered in the
+/// output channel of the node, if it belongs to a pool. The slots of the node are returned to the
+/// pool once it completed.
+///
+/// `buffered` returns the number of pdata messages currently buffered in the output channel.
+pub(crate) async fn with_buffer_pool<T>(
+    member: Option<PoolMember>,
+    buffered: impl Fn() -> usize,
+    node_future: impl Future<Output = T>,
+) -> T {
+    let Some(member) = member else {
+        return node_future.await;
+    };
+    tokio::pin!(node_future);
+    let output = tokio::select! {
+        biased;
+        output = &mut node_future => output,
+        () = refresh(&member, buffered) => unreachable!("the refresh of a pool member never completes"),
+    };
+    // The messages left in the output channel are drained without counting against the pool.
+    member.refresh(0);
+    output
+}
+
+/// Refreshes the number of messages buffered by the given member every
+/// [`POOL_REFRESH_INTERVAL`].
+async fn refresh(member: &PoolMember, buffered: impl Fn() -> usize) {
+    let mut interval = tokio::time::interval(POOL_REFRESH_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        _ = interval.tick().await;
+        member.refresh(buffered());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlobalBufferPool;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_reserved_slots() {
+        let pool = Arc::new(GlobalBufferPool::new(10).with_reservation(2));
+        let chatty = pool.join().expect("Failed to join the pool");
+        let quiet = pool.join().expect("Failed to join the pool");
+
+        // The chatty member takes its reserved slots and all the shared ones.
+        let mut buffered = 0;
+        while let Some(permit) = chatty.try_acquire(buffered) {
+            buffered += 1;
+            permit.complete(buffered);
+        }
+        assert_eq!(buffered, 8);
+        assert_eq!(pool.used(), 8);
+
+        // The quiet member still gets its reserved slots, and nothing more.
+        let first = quiet.try_acquire(0).expect("No reserved slot left");
+        let second = quiet.try_acquire(0).expect("No reserved slot left");
+        assert!(quiet.try_acquire(0).is_none());
+        assert_eq!(pool.used(), 10);
+
+        // A cancelled sending returns its slot, a consumed message frees a shared slot.
+        drop(second);
+        first.complete(1);
+        assert!(chatty.try_acquire(8).is_none());
+        chatty.refresh(7);
+        assert!(chatty.try_acquire(7).is_some());
+
+        // No room is left for the reservations of a sixth member.
+        assert!(pool.join().is_some());
+        assert!(pool.join().is_some());
+        assert!(pool.join().is_some());
+        assert!(pool.join().is_none());
+    }
+}
@@ -6,7 +6,15 @@
 //! focuses instead on defining the interconnection of nodes within the DAG and each node’s specific
 //! settings.
 
+use crate::message::TypedConfig;
+use crate::read_buffer::DEFAULT_READ_BUFFER_SIZE;
+use crate::telemetry::MetricsSink;
 use otap_df_config::node::NodeName;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 /// For now, the channel capacity is set to 256 (a power of two). This value is currently somewhat
 /// arbitrary and will likely be adjusted (and made configurable) in the future once we have more
@@ -27,20 +35,189 @@ use otap_df_config::node::NodeName;
 /// ToDo: Make this default value configurable and based on performance testing.
 const DEFAULT_CONTROL_CHANNEL_CAPACITY: usize = 32;
 const DEFAULT_PDATA_CHANNEL_CAPACITY: usize = 256;
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Generic configuration for a control channel.
+#[derive(Clone)]
 pub struct ControlChannelConfig {
-    /// Max capacity of the channel.
+    /// Max capacity of the channel, ignored by an unbounded channel.
     pub capacity: usize,
+    /// Whether the channel is bounded by its capacity. Only applied to the control channel of
+    /// receivers.
+    pub kind: ControlChannelKind,
+}
+
+/// The kind of a control channel (see [`ControlChannelConfig`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ControlChannelKind {
+    /// The low-priority control messages (see [`crate::message::ControlMsg::is_high_priority`])
+    /// are bounded by the capacity of the channel, a sender waits for some capacity when the
+    /// channel is full.
+    #[default]
+    Bounded,
+    /// The channel never blocks its senders, e.g. so that a node sending control messages to a
+    /// receiver can't deadlock with a receiver waiting on that node to send its pdata.
+    ///
+    /// Note: Nothing bounds the memory used by the channel, a receiver that stops consuming its
+    /// control messages lets them pile up (e.g. the `TimerTick`s or the `Ack`s of its messages).
+    /// Only the control channel is unbounded, the pdata channels stay bounded.
+    Unbounded,
 }
 
 /// Generic configuration for a pdata channel.
+#[derive(Clone)]
 pub struct PdataChannelConfig {
     /// Max capacity of the channel.
     pub capacity: usize,
+    /// What to do with the pdata messages sent to the channel when it is full. Only applied to
+    /// the output pdata channel of receivers.
+    pub overflow_policy: OverflowPolicy,
+    /// The name of the buffer pool of the pipeline the channel borrows its capacity from, in
+    /// addition to its own capacity (see [`crate::buffer_pool`]). Only applied to the output pdata
+    /// channel of receivers.
+    pub pool: Option<String>,
+}
+
+/// The behavior of a receiver sending pdata messages to its full output channel (see the
+/// `send_message` method of the receiver effect handlers). Also applied per downstream by a
+/// [`crate::message::BroadcastSender`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Waits for some capacity in the channel.
+    #[default]
+    Block,
+    /// Drops the oldest message buffered in the channel to make room for the new one.
+    ///
+    /// Note: The Tokio channels used by shared receivers can't evict a buffered message, the new
+    /// message is dropped instead.
+    DropOldest,
+    /// Drops the new message.
+    DropNewest,
+}
+
+/// Watermarks on the output pdata channel of a receiver driving the `Pause` and
+/// `Resume` control messages sent to the receiver.
+#[derive(Clone, Copy, Debug)]
+pub struct BackpressureConfig {
+    /// Number of buffered pdata messages above which the channel is considered congested.
+    pub high_watermark: usize,
+    /// Number of buffered pdata messages below which a paused receiver is resumed.
+    pub low_watermark: usize,
+    /// Number of consecutive checks above the high watermark before the receiver is paused.
+    pub consecutive_checks: usize,
+    /// Interval between two checks of the channel.
+    pub check_interval: Duration,
+}
+
+/// Periodic liveness probing of a node (see [`crate::health`]).
+#[derive(Clone, Copy, Debug)]
+pub struct HealthCheckConfig {
+    /// Interval between two health checks sent to the node.
+    pub interval: Duration,
+    /// Time given to the node to answer a health check before it is marked as stalled.
+    pub timeout: Duration,
+}
+
+/// The cadence of the `TimerTick` control messages delivered to a node.
+#[derive(Clone, Copy, Debug)]
+pub struct TimerConfig {
+    /// Interval between two ticks.
+    pub interval: Duration,
+    /// Maximum random delay added to each interval, e.g. to spread the ticks of several nodes
+    /// sharing the same interval.
+    pub jitter: Option<Duration>,
+}
+
+/// What a receiver does with the pdata messages still buffered in its output channel once it
+/// completed (e.g. after a `Shutdown`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrainPolicy {
+    /// Returns as soon as the receiver completed, the buffered messages are left to the
+    /// downstream node.
+    Immediate,
+    /// Waits until the downstream node consumed the buffered messages, or until the timeout
+    /// expires.
+    Flush {
+        /// Maximum time to wait for the output channel to be drained.
+        timeout: Duration,
+    },
+}
+
+impl Default for DrainPolicy {
+    fn default() -> Self {
+        DrainPolicy::Flush {
+            timeout: DEFAULT_DRAIN_TIMEOUT,
+        }
+    }
+}
+
+/// How many times, and how often, an operation is retried: the restarts of a failed receiver (see
+/// the `restart` field of the [`ReceiverConfig`] and the `restart_with_retry` method of the
+/// receiver wrapper) or the connection attempts of an exporter (see [`crate::outbound`]).
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, the operation is given up on beyond it.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled at each subsequent attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the given retry attempt (starting at 1).
+    #[must_use]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Stopping a receiver with a cancellation token rather than a `Shutdown` control message, e.g.
+/// to tie the receiver to the shutdown signal of the service embedding the engine (see the
+/// `cancellation` field of the [`ReceiverConfig`]).
+#[derive(Clone, Debug)]
+pub struct CancellationConfig {
+    /// The token stopping the receiver once cancelled.
+    pub token: CancellationToken,
+    /// The shutdown deadline given to the receiver once the token is cancelled, after which it is
+    /// dropped.
+    pub grace_period: Duration,
+}
+
+/// Decodes the payload of a configuration update into the configuration type of a receiver (see
+/// the `config_schema` field of the [`ReceiverConfig`]).
+#[derive(Clone)]
+pub struct ConfigSchema {
+    decode: Arc<dyn Fn(&Value) -> Result<TypedConfig, serde_json::Error> + Send + Sync>,
+}
+
+impl ConfigSchema {
+    /// Creates the schema of the configuration type `C`.
+    #[must_use]
+    pub fn of<C>() -> Self
+    where
+        C: DeserializeOwned + Send + Sync + 'static,
+    {
+        ConfigSchema {
+            decode: Arc::new(|payload| C::deserialize(payload).map(TypedConfig::new)),
+        }
+    }
+
+    /// Decodes the given payload of a configuration update.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`serde_json::Error`] if the payload does not match the configuration type.
+    pub fn decode(&self, payload: &Value) -> Result<TypedConfig, serde_json::Error> {
+        (self.decode)(payload)
+    }
 }
 
 /// Generic configuration for a receiver.
+#[derive(Clone)]
 pub struct ReceiverConfig {
     /// Name of the receiver.
     pub name: NodeName,
@@ -48,6 +225,45 @@ pub struct ReceiverConfig {
     pub control_channel: ControlChannelConfig,
     /// Configuration for output pdata channel.
     pub output_pdata_channel: PdataChannelConfig,
+    /// Back-pressure signaling on the output pdata channel, disabled if `None`.
+    pub backpressure: Option<BackpressureConfig>,
+    /// Maximum number of connections served concurrently by a socket receiver (see the
+    /// `serve_connections` method of the receiver effect handlers), unbounded if `None`.
+    pub max_concurrent_connections: Option<usize>,
+    /// Size in bytes of the read buffers of the connections served by a socket receiver (see the
+    /// `read_buffer` method of the receiver effect handlers and [`crate::read_buffer`]).
+    pub read_buffer_size: usize,
+    /// Periodic liveness probing of the receiver, disabled if `None`.
+    pub health_check: Option<HealthCheckConfig>,
+    /// Cadence of the `TimerTick` control messages delivered to the receiver, no ticks if `None`.
+    pub timer: Option<TimerConfig>,
+    /// What the receiver wrapper does with the pdata messages buffered in the output channel once
+    /// the receiver completed.
+    pub drain_policy: DrainPolicy,
+    /// Names of the output ports of the receiver, in addition to its default output channel (see
+    /// the `with_outputs` method of the receiver wrapper).
+    pub out_ports: Vec<String>,
+    /// Sink the send counters of the receiver are pushed to, in addition to the telemetry
+    /// collected with `CollectTelemetry`, none if `None` (see [`crate::telemetry::MetricsSink`]).
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+    /// Maximum number of bytes in flight in the output pdata channel of the receiver, unbounded
+    /// if `None`. The size of the messages is given by the pdata size function of the receiver
+    /// (see the `set_pdata_size` and `take_budgeted_pdata_receiver` methods of the receiver
+    /// wrapper).
+    pub max_inflight_bytes: Option<usize>,
+    /// Restarts of the receiver by its wrapper when it fails, reusing the same control and pdata
+    /// channels, no restart if `None`. Only honored by the wrappers created from a factory (see
+    /// the `local_restartable` and `shared_restartable` methods of the receiver wrapper).
+    pub restart: Option<RetryPolicy>,
+    /// Stops the receiver when its token is cancelled, in addition to the `Shutdown` control
+    /// message, no cancellation if `None` (see the `start` method of the receiver wrapper).
+    pub cancellation: Option<CancellationConfig>,
+    /// The configuration type of the receiver. If set, the engine decodes the `Config` updates
+    /// addressed to the receiver before delivering them (see
+    /// [`crate::message::NodeConfigUpdate::decoded`]), the updates that don't match the type are
+    /// rejected without reaching the receiver (see [`crate::config_ack`]). The `Config` updates
+    /// are delivered undecoded if `None`.
+    pub config_schema: Option<ConfigSchema>,
 }
 
 /// Generic configuration for a processor.
@@ -60,6 +276,10 @@ pub struct ProcessorConfig {
     pub input_pdata_channel: PdataChannelConfig,
     /// Configuration for output pdata channel.
     pub output_pdata_channel: PdataChannelConfig,
+    /// Periodic liveness probing of the processor, disabled if `None`.
+    pub health_check: Option<HealthCheckConfig>,
+    /// Cadence of the `TimerTick` control messages delivered to the processor, no ticks if `None`.
+    pub timer: Option<TimerConfig>,
 }
 
 /// Generic configuration for an exporter.
@@ -70,6 +290,10 @@ pub struct ExporterConfig {
     pub control_channel: ControlChannelConfig,
     /// Configuration for input pdata channel.
     pub input_pdata_channel: PdataChannelConfig,
+    /// Periodic liveness probing of the exporter, disabled if `None`.
+    pub health_check: Option<HealthCheckConfig>,
+    /// Cadence of the `TimerTick` control messages delivered to the exporter, no ticks if `None`.
+    pub timer: Option<TimerConfig>,
 }
 
 impl ReceiverConfig {
@@ -82,12 +306,360 @@ impl ReceiverConfig {
             name: name.into(),
             control_channel: ControlChannelConfig {
                 capacity: DEFAULT_CONTROL_CHANNEL_CAPACITY,
+                kind: ControlChannelKind::Bounded,
             },
             output_pdata_channel: PdataChannelConfig {
                 capacity: DEFAULT_PDATA_CHANNEL_CAPACITY,
+                overflow_policy: OverflowPolicy::Block,
+                pool: None,
             },
+            backpressure: None,
+            max_concurrent_connections: None,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            health_check: None,
+            timer: None,
+            drain_policy: DrainPolicy::default(),
+            out_ports: Vec::new(),
+            metrics_sink: None,
+            max_inflight_bytes: None,
+            restart: None,
+            cancellation: None,
+            config_schema: None,
+        }
+    }
+}
+
+impl ReceiverConfig {
+    /// Returns a builder of receiver configurations validating the configuration once built.
+    #[must_use]
+    pub fn builder() -> ReceiverConfigBuilder {
+        ReceiverConfigBuilder::new()
+    }
+
+    /// Checks that the configuration can be used to create a receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first [`ConfigError`] found, e.g. a [`ConfigError::ZeroCapacity`] for a channel
+    /// that can't buffer any message.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        validate_node_name(&self.name)?;
+        if self.control_channel.kind == ControlChannelKind::Bounded
+            && self.control_channel.capacity == 0
+        {
+            return Err(ConfigError::ZeroCapacity { channel: "control" });
+        }
+        let capacity = self.output_pdata_channel.capacity;
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity {
+                channel: "output pdata",
+            });
+        }
+        if let Some(backpressure) = &self.backpressure {
+            let (low, high) = (backpressure.low_watermark, backpressure.high_watermark);
+            if low > high || high > capacity {
+                return Err(ConfigError::InvalidWatermarks {
+                    low,
+                    high,
+                    capacity,
+                });
+            }
+            check_non_zero("backpressure check interval", backpressure.check_interval)?;
+        }
+        if self.max_concurrent_connections == Some(0) {
+            return Err(ConfigError::ZeroValue {
+                field: "maximum number of concurrent connections",
+            });
+        }
+        if self.read_buffer_size == 0 {
+            return Err(ConfigError::ZeroValue {
+                field: "read buffer size",
+            });
+        }
+        if let Some(health_check) = &self.health_check {
+            check_non_zero("health check interval", health_check.interval)?;
+        }
+        if let Some(timer) = &self.timer {
+            check_non_zero("timer interval", timer.interval)?;
+        }
+        if self.max_inflight_bytes == Some(0) {
+            return Err(ConfigError::ZeroValue {
+                field: "maximum number of bytes in flight",
+            });
+        }
+        Ok(())
+    }
+}
+
+impl ReceiverConfig {
+    /// Normalizes the name of the receiver by trimming the surrounding whitespace, and lowercasing
+    /// it if `lowercase` is set, e.g. for names coming from user-provided configurations.
+    pub fn normalize_name(&mut self, lowercase: bool) {
+        let trimmed = self.name.trim();
+        if lowercase {
+            self.name = trimmed.to_lowercase().into();
+        } else if trimmed.len() != self.name.len() {
+            self.name = trimmed.to_owned().into();
+        }
+    }
+}
+
+/// Checks that a node name can identify the node, i.e. in the metric labels and the logs of the
+/// node: it must not be empty, and only contain ASCII letters, digits, `_`, `-` and `.`.
+///
+/// # Errors
+///
+/// Returns a [`ConfigError::EmptyName`] for an empty or whitespace-only name, and a
+/// [`ConfigError::InvalidName`] for a name containing other characters.
+pub fn validate_node_name(name: &str) -> Result<(), ConfigError> {
+    if name.trim().is_empty() {
+        return Err(ConfigError::EmptyName);
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(ConfigError::InvalidName {
+            name: name.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Returns a [`ConfigError::ZeroValue`] if the given duration is zero.
+fn check_non_zero(field: &'static str, duration: Duration) -> Result<(), ConfigError> {
+    if duration.is_zero() {
+        Err(ConfigError::ZeroValue { field })
+    } else {
+        Ok(())
+    }
+}
+
+/// Errors reported when validating the configuration of a node (see [`ReceiverConfig::validate`]).
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The node has no name.
+    #[error("The node name is empty")]
+    EmptyName,
+
+    /// The node name contains characters other than ASCII letters, digits, `_`, `-` and `.`.
+    #[error("The node name `{name}` must only contain ASCII letters, digits, '_', '-' and '.'")]
+    InvalidName {
+        /// The invalid name.
+        name: String,
+    },
+
+    /// A channel of the node can't buffer any message.
+    #[error("The capacity of the {channel} channel must be greater than 0")]
+    ZeroCapacity {
+        /// The channel with a zero capacity.
+        channel: &'static str,
+    },
+
+    /// The back-pressure watermarks are not ordered, or exceed the capacity of the channel.
+    #[error(
+        "The back-pressure watermarks must satisfy low ({low}) <= high ({high}) <= capacity ({capacity})"
+    )]
+    InvalidWatermarks {
+        /// The low watermark.
+        low: usize,
+        /// The high watermark.
+        high: usize,
+        /// The capacity of the output pdata channel.
+        capacity: usize,
+    },
+
+    /// A limit or an interval of the node is zero.
+    #[error("The {field} must be greater than 0")]
+    ZeroValue {
+        /// The field with a zero value.
+        field: &'static str,
+    },
+}
+
+/// A builder of [`ReceiverConfig`] validating the configuration on [`ReceiverConfigBuilder::build`].
+///
+/// The name must be set, and is trimmed (and lowercased with
+/// [`ReceiverConfigBuilder::with_lowercase_name`]) before being validated. The other fields default
+/// to the values of [`ReceiverConfig::new`]: a control channel capacity of 32 messages, an output
+/// pdata channel capacity of 256 messages with the [`OverflowPolicy::Block`] policy, a
+/// [`DrainPolicy::Flush`] with a 5s timeout, and all the optional features disabled.
+#[derive(Clone)]
+pub struct ReceiverConfigBuilder {
+    config: ReceiverConfig,
+    lowercase_name: bool,
+}
+
+impl Default for ReceiverConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReceiverConfigBuilder {
+    /// Creates a builder with the default values, but no name.
+    #[must_use]
+    pub fn new() -> Self {
+        ReceiverConfigBuilder {
+            config: ReceiverConfig::new(""),
+            lowercase_name: false,
         }
     }
+
+    /// Sets the name of the receiver.
+    #[must_use]
+    pub fn with_name<T: Into<NodeName>>(mut self, name: T) -> Self {
+        self.config.name = name.into();
+        self
+    }
+
+    /// Lowercases the name of the receiver when the configuration is built.
+    #[must_use]
+    pub fn with_lowercase_name(mut self) -> Self {
+        self.lowercase_name = true;
+        self
+    }
+
+    /// Sets the capacity of the control channel.
+    #[must_use]
+    pub fn with_control_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.control_channel.capacity = capacity;
+        self
+    }
+
+    /// Makes the control channel unbounded (see [`ControlChannelKind::Unbounded`] for the memory
+    /// risk).
+    #[must_use]
+    pub fn with_unbounded_control_channel(mut self) -> Self {
+        self.config.control_channel.kind = ControlChannelKind::Unbounded;
+        self
+    }
+
+    /// Sets the capacity of the output pdata channel.
+    #[must_use]
+    pub fn with_output_pdata_capacity(mut self, capacity: usize) -> Self {
+        self.config.output_pdata_channel.capacity = capacity;
+        self
+    }
+
+    /// Sets the policy applied when the output pdata channel is full.
+    #[must_use]
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.config.output_pdata_channel.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Makes the output pdata channel borrow its capacity from the buffer pool of the pipeline
+    /// with the given name (see [`crate::buffer_pool`]).
+    #[must_use]
+    pub fn with_buffer_pool(mut self, pool: impl Into<String>) -> Self {
+        self.config.output_pdata_channel.pool = Some(pool.into());
+        self
+    }
+
+    /// Enables the back-pressure signaling on the output pdata channel.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: BackpressureConfig) -> Self {
+        self.config.backpressure = Some(backpressure);
+        self
+    }
+
+    /// Limits the number of connections served concurrently.
+    #[must_use]
+    pub fn with_max_concurrent_connections(mut self, max_connections: usize) -> Self {
+        self.config.max_concurrent_connections = Some(max_connections);
+        self
+    }
+
+    /// Sets the size in bytes of the read buffers of the connections served by the receiver.
+    #[must_use]
+    pub fn with_read_buffer_size(mut self, size: usize) -> Self {
+        self.config.read_buffer_size = size;
+        self
+    }
+
+    /// Enables the periodic liveness probing of the receiver.
+    #[must_use]
+    pub fn with_health_check(mut self, health_check: HealthCheckConfig) -> Self {
+        self.config.health_check = Some(health_check);
+        self
+    }
+
+    /// Enables the `TimerTick` control messages.
+    #[must_use]
+    pub fn with_timer(mut self, timer: TimerConfig) -> Self {
+        self.config.timer = Some(timer);
+        self
+    }
+
+    /// Sets what is done with the buffered pdata messages once the receiver completed.
+    #[must_use]
+    pub fn with_drain_policy(mut self, drain_policy: DrainPolicy) -> Self {
+        self.config.drain_policy = drain_policy;
+        self
+    }
+
+    /// Adds an output port to the receiver.
+    #[must_use]
+    pub fn with_out_port<T: Into<String>>(mut self, port: T) -> Self {
+        self.config.out_ports.push(port.into());
+        self
+    }
+
+    /// Sets the sink the send counters of the receiver are pushed to.
+    #[must_use]
+    pub fn with_metrics_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.config.metrics_sink = Some(sink);
+        self
+    }
+
+    /// Limits the number of bytes in flight in the output pdata channel.
+    #[must_use]
+    pub fn with_max_inflight_bytes(mut self, max_bytes: usize) -> Self {
+        self.config.max_inflight_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Enables the restarts of the receiver when it fails.
+    #[must_use]
+    pub fn with_restart(mut self, restart: RetryPolicy) -> Self {
+        self.config.restart = Some(restart);
+        self
+    }
+
+    /// Stops the receiver when the given token is cancelled, giving it the given grace period to
+    /// shut down.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken, grace_period: Duration) -> Self {
+        self.config.cancellation = Some(CancellationConfig {
+            token,
+            grace_period,
+        });
+        self
+    }
+
+    /// Sets the configuration type of the receiver, decoded by the engine from the configuration
+    /// updates.
+    #[must_use]
+    pub fn with_config_schema<C>(mut self) -> Self
+    where
+        C: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.config.config_schema = Some(ConfigSchema::of::<C>());
+        self
+    }
+
+    /// Validates and returns the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConfigError`] if the configuration is invalid (see
+    /// [`ReceiverConfig::validate`]).
+    pub fn build(mut self) -> Result<ReceiverConfig, ConfigError> {
+        self.config.normalize_name(self.lowercase_name);
+        self.config.validate()?;
+        Ok(self.config)
+    }
 }
 
 impl ProcessorConfig {
@@ -101,13 +673,20 @@ impl ProcessorConfig {
             name: name.into(),
             control_channel: ControlChannelConfig {
                 capacity: DEFAULT_CONTROL_CHANNEL_CAPACITY,
+                kind: ControlChannelKind::Bounded,
             },
             input_pdata_channel: PdataChannelConfig {
                 capacity: DEFAULT_PDATA_CHANNEL_CAPACITY,
+                overflow_policy: OverflowPolicy::Block,
+                pool: None,
             },
             output_pdata_channel: PdataChannelConfig {
                 capacity: DEFAULT_PDATA_CHANNEL_CAPACITY,
+                overflow_policy: OverflowPolicy::Block,
+                pool: None,
             },
+            health_check: None,
+            timer: None,
         }
     }
 }
@@ -123,10 +702,261 @@ impl ExporterConfig {
             name: name.into(),
             control_channel: ControlChannelConfig {
                 capacity: DEFAULT_CONTROL_CHANNEL_CAPACITY,
+                kind: ControlChannelKind::Bounded,
             },
             input_pdata_channel: PdataChannelConfig {
                 capacity: DEFAULT_PDATA_CHANNEL_CAPACITY,
+                overflow_policy: OverflowPolicy::Block,
+                pool: None,
             },
+            health_check: None,
+            timer: None,
+        }
+    }
+}
+
+/// Applies a JSON merge patch ([RFC 7386](https://www.rfc-editor.org/rfc/rfc7386)) to the given
+/// configuration: the members of a patch object are merged recursively into the configuration, a
+/// `null` member removes the corresponding member of the configuration, and a patch that isn't
+/// an object replaces the whole configuration.
+pub fn apply_patch(config: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *config = patch.clone();
+        return;
+    };
+    if !config.is_object() {
+        *config = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(config) = config {
+        for (key, value) in patch {
+            if value.is_null() {
+                _ = config.remove(key);
+            } else {
+                apply_patch(config.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BackpressureConfig, ConfigError, ControlChannelKind, OverflowPolicy, ReceiverConfig,
+        RetryPolicy, TimerConfig, apply_patch,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        let backoffs: Vec<_> = (1..=6).map(|attempt| policy.backoff(attempt)).collect();
+        assert_eq!(
+            backoffs,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_receiver_config_builder() {
+        let config = ReceiverConfig::builder()
+            .with_name("receiver")
+            .with_control_channel_capacity(8)
+            .with_output_pdata_capacity(16)
+            .with_overflow_policy(OverflowPolicy::DropNewest)
+            .with_out_port("errors")
+            .with_read_buffer_size(1024)
+            .with_buffer_pool("receivers")
+            .build()
+            .expect("Valid configuration rejected");
+        assert_eq!(config.name, "receiver");
+        assert_eq!(config.control_channel.capacity, 8);
+        assert_eq!(config.output_pdata_channel.capacity, 16);
+        assert_eq!(
+            config.output_pdata_channel.overflow_policy,
+            OverflowPolicy::DropNewest
+        );
+        assert_eq!(config.out_ports, ["errors"]);
+        assert_eq!(config.read_buffer_size, 1024);
+        assert_eq!(
+            config.output_pdata_channel.pool.as_deref(),
+            Some("receivers")
+        );
+
+        // The defaults are those of `ReceiverConfig::new`.
+        let config = ReceiverConfig::builder()
+            .with_name("receiver")
+            .build()
+            .expect("Valid configuration rejected");
+        let defaults = ReceiverConfig::new("receiver");
+        assert_eq!(
+            config.control_channel.capacity,
+            defaults.control_channel.capacity
+        );
+        assert_eq!(
+            config.output_pdata_channel.capacity,
+            defaults.output_pdata_channel.capacity
+        );
+        assert_eq!(config.drain_policy, defaults.drain_policy);
+        assert_eq!(config.read_buffer_size, defaults.read_buffer_size);
+        assert!(config.output_pdata_channel.pool.is_none());
+        assert_eq!(config.control_channel.kind, ControlChannelKind::Bounded);
+
+        // The capacity of an unbounded control channel is ignored.
+        let config = ReceiverConfig::builder()
+            .with_name("receiver")
+            .with_control_channel_capacity(0)
+            .with_unbounded_control_channel()
+            .build()
+            .expect("Valid configuration rejected");
+        assert_eq!(config.control_channel.kind, ControlChannelKind::Unbounded);
+    }
+
+    #[test]
+    fn test_receiver_config_validation() {
+        let backpressure = |low_watermark, high_watermark| BackpressureConfig {
+            high_watermark,
+            low_watermark,
+            consecutive_checks: 1,
+            check_interval: Duration::from_millis(10),
+        };
+        let named = || ReceiverConfig::builder().with_name("receiver");
+        for (builder, expected) in [
+            (ReceiverConfig::builder(), ConfigError::EmptyName),
+            (
+                named().with_control_channel_capacity(0),
+                ConfigError::ZeroCapacity { channel: "control" },
+            ),
+            (
+                named().with_output_pdata_capacity(0),
+                ConfigError::ZeroCapacity {
+                    channel: "output pdata",
+                },
+            ),
+            (
+                named().with_backpressure(backpressure(8, 4)),
+                ConfigError::InvalidWatermarks {
+                    low: 8,
+                    high: 4,
+                    capacity: 256,
+                },
+            ),
+            (
+                named()
+                    .with_output_pdata_capacity(4)
+                    .with_backpressure(backpressure(2, 8)),
+                ConfigError::InvalidWatermarks {
+                    low: 2,
+                    high: 8,
+                    capacity: 4,
+                },
+            ),
+            (
+                named().with_max_concurrent_connections(0),
+                ConfigError::ZeroValue {
+                    field: "maximum number of concurrent connections",
+                },
+            ),
+            (
+                named().with_read_buffer_size(0),
+                ConfigError::ZeroValue {
+                    field: "read buffer size",
+                },
+            ),
+            (
+                named().with_timer(TimerConfig {
+                    interval: Duration::ZERO,
+                    jitter: None,
+                }),
+                ConfigError::ZeroValue {
+                    field: "timer interval",
+                },
+            ),
+        ] {
+            assert_eq!(builder.build().err(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_receiver_name_validation() {
+        for (name, expected) in [
+            ("", ConfigError::EmptyName),
+            ("  \t", ConfigError::EmptyName),
+            (
+                "otlp receiver",
+                ConfigError::InvalidName {
+                    name: "otlp receiver".to_owned(),
+                },
+            ),
+            (
+                "otlp{grpc}",
+                ConfigError::InvalidName {
+                    name: "otlp{grpc}".to_owned(),
+                },
+            ),
+        ] {
+            assert_eq!(ReceiverConfig::new(name).validate().err(), Some(expected));
+        }
+
+        // Valid names round trip, the builder normalizes the surrounding whitespace.
+        for name in ["receiver", "otlp-grpc_1.0"] {
+            let config = ReceiverConfig::builder()
+                .with_name(format!(" {name} "))
+                .build()
+                .expect("Valid name rejected");
+            assert_eq!(config.name, name);
+        }
+        let config = ReceiverConfig::builder()
+            .with_name("OTLP_Receiver")
+            .with_lowercase_name()
+            .build()
+            .expect("Valid name rejected");
+        assert_eq!(config.name, "otlp_receiver");
+        // Names are not normalized by `validate`.
+        assert!(ReceiverConfig::new(" receiver").validate().is_err());
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        // A subset of the examples of RFC 7386, appendix A.
+        for (config, patch, expected) in [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (json!(["a", "b"]), json!({"a": "b"}), json!({"a": "b"})),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ] {
+            let mut patched = config.clone();
+            apply_patch(&mut patched, &patch);
+            assert_eq!(patched, expected, "{config} patched with {patch}");
         }
     }
 }
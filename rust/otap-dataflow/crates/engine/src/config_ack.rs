@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Acknowledgement of the configuration updates applied by the nodes.
+//!
+//! Configuration updates are delivered to the nodes via [`ControlMsg::Config`] messages, each
+//! update carrying a version (see [`crate::message::NodeConfigUpdate`]). Once a node has handled
+//! an update, it confirms it with the effect handler's `ack_config` (or reports a failure with
+//! `reject_config`). The outcome can be awaited on the engine side through the
+//! [`ConfigAckWatcher`] returned by the `config_acks` method of the node wrappers.
+//!
+//! For a receiver declaring its configuration type (see
+//! [`crate::config::ReceiverConfig::config_schema`]), the engine rejects the updates that don't
+//! match the type on behalf of the receiver, and delivers the others with their decoded payload
+//! (see [`crate::message::NodeConfigUpdate::decoded`]).
+
+use crate::error::Error;
+use crate::message::ControlMsg;
+use otap_df_channel::error::RecvError;
+use otap_df_config::node::NodeName;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// The outcome of a configuration update handled by a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigAck {
+    /// The node applied the configuration update with the given version.
+    Applied {
+        /// The version of the configuration update.
+        version: u64,
+    },
+
+    /// The node rejected the configuration update with the given version.
+    Rejected {
+        /// The version of the configuration update.
+        version: u64,
+        /// The reason for the rejection.
+        reason: String,
+    },
+}
+
+/// The node side of the configuration acknowledgements, shared by all the clones of an effect
+/// handler.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone)]
+pub(crate) struct ConfigAcks {
+    last_ack: Arc<watch::Sender<Option<ConfigAck>>>,
+}
+
+impl Default for ConfigAcks {
+    fn default() -> Self {
+        let (last_ack, _) = watch::channel(None);
+        ConfigAcks {
+            last_ack: Arc::new(last_ack),
+        }
+    }
+}
+
+impl ConfigAcks {
+    /// Records the outcome of a configuration update.
+    pub(crate) fn record(&self, ack: ConfigAck) {
+        // Unlike `send`, `send_replace` records the outcome even if nobody is watching yet.
+        _ = self.last_ack.send_replace(Some(ack));
+    }
+
+    /// Returns a watcher of the outcomes recorded for the given node.
+    pub(crate) fn watch(&self, node: NodeName) -> ConfigAckWatcher {
+        ConfigAckWatcher {
+            node,
+            last_ack: self.last_ack.subscribe(),
+        }
+    }
+}
+
+/// Waits for a node to acknowledge its configuration updates.
+pub struct ConfigAckWatcher {
+    node: NodeName,
+    last_ack: watch::Receiver<Option<ConfigAck>>,
+}
+
+impl ConfigAckWatcher {
+    /// Returns the outcome of the last configuration update handled by the node, if any.
+    #[must_use]
+    pub fn last_ack(&self) -> Option<ConfigAck> {
+        self.last_ack.borrow().clone()
+    }
+
+    /// Waits until the node has applied the configuration update with the given version (or a
+    /// more recent one).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ConfigRejected`] if the node rejected this version, or an
+    /// [`Error::ChannelRecvError`] if the node terminated without acknowledging it.
+    pub async fn wait_for(&mut self, version: u64) -> Result<(), Error<ControlMsg>> {
+        let ack = self
+            .last_ack
+            .wait_for(|ack| match ack {
+                Some(ConfigAck::Applied { version: applied }) => *applied >= version,
+                Some(ConfigAck::Rejected {
+                    version: rejected, ..
+                }) => *rejected == version,
+                None => false,
+            })
+            .await
+            .map_err(|_| Error::ChannelRecvError(RecvError::Closed))?
+            .clone();
+
+        match ack {
+            Some(ConfigAck::Rejected { version, reason }) => Err(Error::ConfigRejected {
+                node: self.node.clone(),
+                version,
+                reason,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
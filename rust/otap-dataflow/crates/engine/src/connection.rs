@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracking of the per-connection tasks spawned by socket receivers.
+//!
+//! Every effect handler of a receiver owns a [`ConnectionRegistry`] (shared by all its clones). The
+//! tasks handling the accepted connections are spawned through the registry so that, on
+//! `Shutdown`, the receiver can stop accepting new connections and let the in-flight ones complete
+//! within the shutdown deadline instead of abandoning them mid-read.
+//!
+//! The registry can also bound the number of connections served concurrently: the accept loop
+//! reserves a slot (see [`ConnectionRegistry::reserve_slot`]) before accepting a connection, so
+//! that a flood of clients stays in the listen backlog instead of spawning unbounded tasks.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+
+/// A registry of the connection tasks spawned by a receiver.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectionRegistry {
+    state: Arc<Mutex<RegistryState>>,
+    /// The connection slots, if the number of concurrent connections is limited.
+    slots: Option<Arc<Semaphore>>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    /// The connection tasks not yet reaped.
+    connections: JoinSet<()>,
+    /// Set once the registry has been drained, no connection task can be spawned afterward.
+    closed: bool,
+    /// The number of connection tasks which completed or were aborted.
+    closed_connections: u64,
+}
+
+impl ConnectionRegistry {
+    /// Creates a registry limiting the number of concurrent connections to `max_connections`, if
+    /// any.
+    pub(crate) fn with_limit(max_connections: Option<usize>) -> Self {
+        ConnectionRegistry {
+            state: Arc::default(),
+            slots: max_connections.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Waits until the number of concurrent connections is below the limit of the registry, and
+    /// reserves a slot for a new connection. The slot is released when the returned permit is
+    /// dropped, i.e. the permit must be held by the connection task.
+    ///
+    /// Returns `None` immediately if the number of connections is not limited.
+    pub(crate) async fn reserve_slot(&self) -> Option<OwnedSemaphorePermit> {
+        let slots = self.slots.clone()?;
+        // The semaphore is never closed.
+        slots.acquire_owned().await.ok()
+    }
+
+    /// Returns the number of connection tasks still running.
+    pub(crate) fn active_connections(&self) -> usize {
+        let mut state = self.lock();
+        state.reap();
+        state.connections.len()
+    }
+
+    /// Returns the number of connection tasks which completed or were aborted so far.
+    pub(crate) fn closed_connections(&self) -> u64 {
+        let mut state = self.lock();
+        state.reap();
+        state.closed_connections
+    }
+
+    /// Spawns a connection task on the current `LocalSet`.
+    ///
+    /// The task is dropped if the registry has already been drained.
+    pub(crate) fn spawn_local<F>(&self, connection: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        let mut state = self.lock();
+        if state.closed {
+            tracing::debug!("Dropping a connection handler spawned after the shutdown");
+            return;
+        }
+        state.reap();
+        _ = state.connections.spawn_local(connection);
+    }
+
+    /// Spawns a connection task on the Tokio runtime.
+    ///
+    /// The task is dropped if the registry has already been drained.
+    pub(crate) fn spawn<F>(&self, connection: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut state = self.lock();
+        if state.closed {
+            tracing::debug!("Dropping a connection handler spawned after the shutdown");
+            return;
+        }
+        state.reap();
+        _ = state.connections.spawn(connection);
+    }
+
+    /// Closes the registry and waits up to `deadline` for the tracked connection tasks to
+    /// complete. The tasks still running after the deadline are aborted.
+    pub(crate) async fn drain(&self, deadline: Duration) {
+        let mut connections = {
+            let mut state = self.lock();
+            state.closed = true;
+            std::mem::take(&mut state.connections)
+        };
+
+        let mut joined = 0;
+        let drained = tokio::time::timeout(deadline, async {
+            while let Some(result) = connections.join_next().await {
+                log_failure(result);
+                joined += 1;
+            }
+        })
+        .await;
+        if drained.is_err() {
+            tracing::warn!(
+                remaining = connections.len(),
+                "Aborting the connection handlers still running after the shutdown deadline"
+            );
+            joined += connections.len() as u64;
+            connections.abort_all();
+        }
+        self.lock().closed_connections += joined;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RegistryState> {
+        // The state stays consistent even if a thread panicked while holding the lock.
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl RegistryState {
+    /// Removes the completed connection tasks from the registry.
+    fn reap(&mut self) {
+        while let Some(result) = self.connections.try_join_next() {
+            log_failure(result);
+            self.closed_connections += 1;
+        }
+    }
+}
+
+fn log_failure(result: Result<(), tokio::task::JoinError>) {
+    if let Err(error) = result {
+        tracing::warn!(%error, "Connection handler failed");
+    }
+}
@@ -2,10 +2,35 @@
 
 //! Common foundation of all effect handlers.
 
+use crate::ack::{MessageIdGenerator, UNROUTED};
+use crate::buffer_pool::PoolMember;
+use crate::config_ack::{ConfigAck, ConfigAckWatcher, ConfigAcks};
+use crate::connection::ConnectionRegistry;
 use crate::error::Error;
+use crate::flush_ack::{FlushAckWatcher, FlushAcks};
+use crate::health::{HealthReports, HealthStatus};
+use crate::lifecycle::Lifecycle;
+use crate::logging::{LogLevel, LogSink, NodeLogger};
+use crate::message::{ControlMsg, PrioritySender};
+use crate::read_buffer::ReadBufferPool;
+use crate::task::TaskRegistry;
+use crate::telemetry::{MetricsSink, ShutdownReport, TelemetryCounters};
+#[cfg(any(test, feature = "testing"))]
+use crate::testing::fault::{FaultInjector, InjectedFault};
+use crate::timer::NodeTimers;
+use crate::tls::{TlsConfig, TlsListener};
+use crate::unix::UnixListener;
+use otap_df_config::NodeKind;
 use std::borrow::Cow;
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::watch;
+
+/// The size of the accept queue of the TCP listeners created by the nodes, unless specified.
+const DEFAULT_LISTEN_BACKLOG: u32 = 8192;
 
 /// Common implementation of all effect handlers.
 ///
@@ -13,15 +38,245 @@ use tokio::net::TcpListener;
 #[derive(Clone)]
 pub(crate) struct EffectHandlerCore {
     pub(crate) node_name: Cow<'static, str>,
+    /// Generator of the ids used to tag the messages emitted by the node (see [`crate::ack`]).
+    pub(crate) message_ids: Arc<MessageIdGenerator>,
+    /// Registry of the connection tasks spawned by the node (see [`crate::connection`]).
+    pub(crate) connections: ConnectionRegistry,
+    /// Pool of the read buffers of the connections served by the node (see
+    /// [`crate::read_buffer`]).
+    pub(crate) read_buffers: ReadBufferPool,
+    /// Outcomes of the configuration updates handled by the node (see [`crate::config_ack`]).
+    config_acks: ConfigAcks,
+    /// Flushes acknowledged by the node (see [`crate::flush_ack`]).
+    flush_acks: FlushAcks,
+    /// Health statuses reported by the node (see [`crate::health`]).
+    health_reports: HealthReports,
+    /// Lifecycle state of the node (see [`crate::lifecycle`]).
+    pub(crate) lifecycle: Lifecycle,
+    /// Addresses of the listeners and sockets created by the node.
+    bound_addresses: Arc<watch::Sender<Vec<SocketAddr>>>,
+    /// Summary of the run of the node, published once it shut down cleanly.
+    shutdown_report: Arc<watch::Sender<Option<ShutdownReport>>>,
+    /// Internal counters of the node (see [`crate::telemetry`]).
+    pub(crate) telemetry: TelemetryCounters,
+    /// Registry of the subtasks spawned by the node (see [`crate::task`]).
+    pub(crate) tasks: TaskRegistry,
+    /// Periodic timers started by the node (see [`crate::timer`]).
+    pub(crate) timers: NodeTimers,
+    /// Faults injected by the tests (see [`crate::testing::fault`]).
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) faults: Option<FaultInjector>,
+    /// Name of the buffer pool the output channel of the node borrows from (see
+    /// [`crate::buffer_pool`]).
+    pub(crate) buffer_pool_name: Option<String>,
+    /// Membership of the node in its buffer pool, once it joined it.
+    pub(crate) buffer_pool: Option<PoolMember>,
+    /// Sending end of the control channel of the node, for the node to shut itself down.
+    pub(crate) control_sender: Option<PrioritySender<ControlMsg>>,
+    /// Sink of the messages logged by the node (see [`crate::logging`]).
+    logger: NodeLogger,
+}
+
+/// Publishes the summary of the run of a node from its counters (see [`ShutdownReport`]).
+pub(crate) struct ShutdownReporter {
+    telemetry: TelemetryCounters,
+    connections: ConnectionRegistry,
+    report: Arc<watch::Sender<Option<ShutdownReport>>>,
+}
+
+impl ShutdownReporter {
+    /// Publishes the summary of the run of the node.
+    pub(crate) fn publish(&self) {
+        let telemetry = self.telemetry.snapshot();
+        let report = ShutdownReport {
+            messages_emitted: telemetry.messages_sent,
+            messages_dropped: telemetry.messages_dropped,
+            connections_closed: self.connections.closed_connections(),
+        };
+        tracing::debug!(%report, "Receiver shut down");
+        // Unlike `send`, `send_replace` records the report even if nobody is watching yet.
+        _ = self.report.send_replace(Some(report));
+    }
 }
 
 impl EffectHandlerCore {
+    /// Creates a new effect handler core for the given node.
+    pub(crate) fn new(node_name: Cow<'static, str>, node_kind: NodeKind) -> Self {
+        EffectHandlerCore {
+            node_name,
+            message_ids: Arc::new(MessageIdGenerator::new(UNROUTED)),
+            connections: ConnectionRegistry::default(),
+            read_buffers: ReadBufferPool::default(),
+            config_acks: ConfigAcks::default(),
+            flush_acks: FlushAcks::default(),
+            health_reports: HealthReports::default(),
+            lifecycle: Lifecycle::default(),
+            bound_addresses: Arc::new(watch::channel(Vec::new()).0),
+            shutdown_report: Arc::new(watch::channel(None).0),
+            telemetry: TelemetryCounters::default(),
+            tasks: TaskRegistry::default(),
+            timers: NodeTimers::default(),
+            #[cfg(any(test, feature = "testing"))]
+            faults: None,
+            buffer_pool_name: None,
+            buffer_pool: None,
+            control_sender: None,
+            logger: NodeLogger::new(node_kind),
+        }
+    }
+
+    /// Queues a `Shutdown` with the given deadline and reason to the control channel of the node.
+    pub(crate) async fn request_shutdown<PData>(
+        &self,
+        deadline: Duration,
+        reason: String,
+    ) -> Result<(), Error<PData>> {
+        let closed = || Error::ControlChannelClosed {
+            node: self.node_name.clone(),
+        };
+        let Some(control_sender) = &self.control_sender else {
+            return Err(closed());
+        };
+        control_sender
+            .send(ControlMsg::Shutdown { deadline, reason })
+            .await
+            .map_err(|_| closed())
+    }
+
+    /// Returns the fault to inject in the pdata message being sent, if any.
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn injected_fault(&self) -> Option<InjectedFault> {
+        self.faults.as_ref().and_then(FaultInjector::on_send)
+    }
+
     /// Returns the name of the node associated with this effect handler.
     #[must_use]
     pub(crate) fn node_name(&self) -> Cow<'static, str> {
         self.node_name.clone()
     }
 
+    /// Returns the next id to use to tag a message emitted by the node.
+    #[must_use]
+    pub(crate) fn next_message_id(&self) -> u64 {
+        self.message_ids.next_id()
+    }
+
+    /// Sets the Ack/Nack route of the node. Only ids allocated after this call carry the route.
+    pub(crate) fn set_ack_route(&mut self, route: u16) {
+        self.message_ids = Arc::new(MessageIdGenerator::new(route));
+    }
+
+    /// Confirms that the node applied the configuration update with the given version.
+    pub(crate) fn ack_config(&self, version: u64) {
+        self.config_acks.record(ConfigAck::Applied { version });
+    }
+
+    /// Reports that the node failed to apply the configuration update with the given version.
+    pub(crate) fn reject_config(&self, version: u64, reason: String) {
+        self.config_acks
+            .record(ConfigAck::Rejected { version, reason });
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the node.
+    pub(crate) fn config_acks(&self) -> ConfigAckWatcher {
+        self.config_acks.watch(self.node_name())
+    }
+
+    /// Confirms that the node handled the flush with the given id.
+    pub(crate) fn ack_flush(&self, id: u64) {
+        self.flush_acks.ack(id);
+    }
+
+    /// Returns a watcher of the flushes acknowledged by the node.
+    pub(crate) fn flush_acks(&self) -> FlushAckWatcher {
+        self.flush_acks.watch()
+    }
+
+    /// Publishes the health status reported by the node.
+    pub(crate) fn report_health(&self, status: HealthStatus) {
+        self.health_reports.report(status);
+    }
+
+    /// Returns a receiver of the health statuses reported by the node.
+    pub(crate) fn subscribe_health(&self) -> watch::Receiver<HealthStatus> {
+        self.health_reports.subscribe()
+    }
+
+    /// Records an address the node is listening on.
+    fn record_bound_address(&self, addr: SocketAddr) {
+        tracing::info!(node = %self.node_name, %addr, "Listening");
+        // Unlike `send`, `send_modify` records the address even if nobody is watching yet.
+        self.bound_addresses
+            .send_modify(|addresses| addresses.push(addr));
+    }
+
+    /// Returns the addresses of the listeners and sockets created by the node so far.
+    pub(crate) fn bound_addresses(&self) -> Vec<SocketAddr> {
+        self.bound_addresses.borrow().clone()
+    }
+
+    /// Returns a receiver of the addresses of the listeners and sockets created by the node.
+    pub(crate) fn subscribe_bound_addresses(&self) -> watch::Receiver<Vec<SocketAddr>> {
+        self.bound_addresses.subscribe()
+    }
+
+    /// Returns a receiver of the summary of the run of the node, `None` until the node shut down
+    /// cleanly.
+    pub(crate) fn subscribe_shutdown_report(&self) -> watch::Receiver<Option<ShutdownReport>> {
+        self.shutdown_report.subscribe()
+    }
+
+    /// Returns the publisher of the summary of the run of the node, used by its wrapper once the
+    /// node shut down cleanly.
+    pub(crate) fn shutdown_reporter(&self) -> ShutdownReporter {
+        ShutdownReporter {
+            telemetry: self.telemetry.clone(),
+            connections: self.connections.clone(),
+            report: self.shutdown_report.clone(),
+        }
+    }
+
+    /// Limits the number of connections served concurrently by the node (see
+    /// [`ConnectionRegistry::reserve_slot`]). Must be called before the core is cloned.
+    pub(crate) fn set_max_concurrent_connections(&mut self, max_connections: Option<usize>) {
+        self.connections = ConnectionRegistry::with_limit(max_connections);
+    }
+
+    /// Sets the size of the read buffers of the connections served by the node (see
+    /// [`crate::read_buffer`]). Must be called before the core is cloned.
+    pub(crate) fn set_read_buffer_size(&mut self, size: usize) {
+        self.read_buffers = ReadBufferPool::new(size);
+    }
+
+    /// Emits the messages logged by the node to the given sink, tagged with the given pipeline id.
+    pub(crate) fn set_log_sink(
+        &mut self,
+        sink: Arc<dyn LogSink>,
+        pipeline_id: Option<Cow<'static, str>>,
+    ) {
+        self.logger.set_sink(sink, pipeline_id);
+    }
+
+    /// Logs a message tagged with the identity of the node.
+    pub(crate) fn log(&self, level: LogLevel, message: &str) {
+        self.logger.log(self.node_name.clone(), level, message);
+    }
+
+    /// Counts and logs the given restart attempt of the node after it failed with the given error.
+    pub(crate) fn report_restart<PData>(&self, attempt: u32, error: &Error<PData>) {
+        self.telemetry.record_restart(error.error_code());
+        self.log(
+            LogLevel::Warn,
+            &format!("Restarting the node (attempt {attempt}) after it failed: {error}"),
+        );
+    }
+
+    /// Pushes the send counters of the node to the given sink. Must be called before the core is
+    /// cloned.
+    pub(crate) fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.telemetry.set_sink(sink, self.node_name.clone());
+    }
+
     /// Creates a non-blocking TCP listener on the given address with socket options defined by the
     /// pipeline engine implementation. It's important for receiver implementer to create TCP
     /// listeners via this method to ensure the scalability and the serviceability of the pipeline.
@@ -35,6 +290,21 @@ impl EffectHandlerCore {
         &self,
         addr: SocketAddr,
         receiver_name: impl Into<Cow<'static, str>>,
+    ) -> Result<TcpListener, Error<PData>> {
+        self.tcp_listener_with_backlog(addr, DEFAULT_LISTEN_BACKLOG, receiver_name)
+    }
+
+    /// Creates a TCP listener like `tcp_listener`, with an accept queue of the given size. The
+    /// operating system may cap it (e.g. `net.core.somaxconn` on Linux).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if any step in the process fails.
+    pub(crate) fn tcp_listener_with_backlog<PData>(
+        &self,
+        addr: SocketAddr,
+        backlog: u32,
+        receiver_name: impl Into<Cow<'static, str>>,
     ) -> Result<TcpListener, Error<PData>> {
         let node_name: Cow<'static, str> = receiver_name.into();
         // Helper closure to convert errors.
@@ -65,8 +335,96 @@ impl EffectHandlerCore {
         sock.set_reuse_port(true).map_err(err)?;
         sock.set_nonblocking(true).map_err(err)?;
         sock.bind(&addr.into()).map_err(err)?;
-        sock.listen(8192).map_err(err)?;
+        sock.listen(i32::try_from(backlog).unwrap_or(i32::MAX))
+            .map_err(err)?;
+
+        let listener = TcpListener::from_std(sock.into()).map_err(err)?;
+        self.record_bound_address(listener.local_addr().map_err(err)?);
+        Ok(listener)
+    }
+
+    /// Creates a non-blocking UDP socket bound to the given address with socket options defined by
+    /// the pipeline engine implementation. It's important for receiver implementer to create UDP
+    /// sockets via this method to ensure the scalability and the serviceability of the pipeline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if any step in the process fails.
+    pub(crate) fn udp_socket<PData>(
+        &self,
+        addr: SocketAddr,
+        receiver_name: impl Into<Cow<'static, str>>,
+    ) -> Result<UdpSocket, Error<PData>> {
+        let node_name: Cow<'static, str> = receiver_name.into();
+        // Helper closure to convert errors.
+        let err = |error: std::io::Error| Error::IoError {
+            node: node_name.clone(),
+            error,
+        };
+
+        // Create a SO_REUSEADDR + SO_REUSEPORT datagram socket.
+        let sock = socket2::Socket::new(
+            match addr {
+                SocketAddr::V4(_) => socket2::Domain::IPV4,
+                SocketAddr::V6(_) => socket2::Domain::IPV6,
+            },
+            socket2::Type::DGRAM,
+            None,
+        )
+        .map_err(err)?;
+
+        // Same rationale as for the TCP listener, with SO_REUSEPORT the OS distributes incoming
+        // datagrams between the sockets bound to the same address/port (one per core).
+        sock.set_reuse_address(true).map_err(err)?;
+        sock.set_reuse_port(true).map_err(err)?;
+        sock.set_nonblocking(true).map_err(err)?;
+        sock.bind(&addr.into()).map_err(err)?;
+
+        let socket = UdpSocket::from_std(sock.into()).map_err(err)?;
+        self.record_bound_address(socket.local_addr().map_err(err)?);
+        Ok(socket)
+    }
+
+    /// Creates a Unix domain socket listener bound to the given path, replacing a stale socket
+    /// file. The socket file is removed once the listener is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if the listener can't be bound, e.g. if the path is used by
+    /// another file or by a running listener.
+    pub(crate) fn unix_listener<PData>(
+        &self,
+        path: &Path,
+        receiver_name: impl Into<Cow<'static, str>>,
+    ) -> Result<UnixListener, Error<PData>> {
+        let listener = UnixListener::bind(path).map_err(|error| Error::IoError {
+            node: receiver_name.into(),
+            error,
+        })?;
+        tracing::info!(node = %self.node_name, path = %path.display(), "Listening");
+        Ok(listener)
+    }
+
+    /// Creates a TCP listener (see `tcp_listener`) terminating TLS connections with the given
+    /// configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if the listener can't be created or if the certificates and
+    /// keys can't be loaded.
+    pub(crate) fn tls_listener<PData>(
+        &self,
+        addr: SocketAddr,
+        config: &TlsConfig,
+        receiver_name: impl Into<Cow<'static, str>>,
+    ) -> Result<TlsListener, Error<PData>> {
+        let node_name: Cow<'static, str> = receiver_name.into();
+        let acceptor = config.acceptor().map_err(|error| Error::IoError {
+            node: node_name.clone(),
+            error,
+        })?;
+        let listener = self.tcp_listener(addr, node_name)?;
 
-        TcpListener::from_std(sock.into()).map_err(err)
+        Ok(TlsListener::new(listener, acceptor))
     }
 }
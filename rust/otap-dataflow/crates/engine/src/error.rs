@@ -5,18 +5,136 @@
 //! Important note: It is important not to use `!Send` data types in errors (e.g. avoid using Rc) to
 //! ensure these errors can be emitted in both `Send` and `!Send` contexts.
 
+use otap_df_channel::error::{RecvError, SendError};
 use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::time::Duration;
 
 /// All errors that can occur in the pipeline engine infrastructure.
 #[derive(thiserror::Error, Debug)]
 pub enum Error<T> {
     /// A wrapper for the channel errors.
     #[error("A channel error occurred: {0}")]
-    ChannelRecvError(#[from] otap_df_channel::error::RecvError),
+    ChannelRecvError(#[from] RecvError),
 
     /// A wrapper for the channel errors.
     #[error("A channel error occurred: {0}")]
-    ChannelSendError(#[from] otap_df_channel::error::SendError<T>),
+    ChannelSendError(#[from] SendError<T>),
+
+    /// The control channel of a node is closed, i.e. the node can no longer receive control
+    /// messages or failed to send a control message to another node (e.g. a `Flush` forwarded
+    /// downstream or a `Throttle` sent upstream).
+    #[error("A control channel of node {node} is closed")]
+    ControlChannelClosed {
+        /// The name of the node that observed the closed channel.
+        node: Cow<'static, str>,
+    },
+
+    /// The pdata channel a node sends its pdata messages to is closed.
+    #[error("The pdata channel of node {node} is closed")]
+    PdataChannelClosed {
+        /// The name of the node that failed to send the pdata message.
+        node: Cow<'static, str>,
+    },
+
+    /// The pdata channel a node sends a batch of pdata messages to closed before the whole batch
+    /// was sent.
+    #[error("The pdata channel of node {node} closed after {accepted} messages of a batch")]
+    PdataBatchInterrupted {
+        /// The name of the node that failed to send the batch.
+        node: Cow<'static, str>,
+
+        /// The number of messages of the batch accepted before the channel closed.
+        accepted: usize,
+    },
+
+    /// The pdata channel a node sends its pdata messages to is full, and the message could not be
+    /// sent without waiting.
+    #[error("The pdata channel of node {node} is full (capacity {capacity})")]
+    PdataChannelFull {
+        /// The name of the node that failed to send the pdata message.
+        node: Cow<'static, str>,
+
+        /// The capacity of the channel.
+        capacity: usize,
+    },
+
+    /// The PData receiver of a node was already taken, e.g. because the node was wired twice in
+    /// the pipeline.
+    #[error("The pdata receiver of node {node} was already taken")]
+    PdataReceiverAlreadyTaken {
+        /// The name of the node whose PData receiver was already taken.
+        node: Cow<'static, str>,
+    },
+
+    /// The pdata channel a node sends its pdata messages to stayed full until the send timed out
+    /// (see the `send_message_timeout` method of the receiver effect handlers).
+    #[error("The pdata channel of node {node} stayed full for {timeout:?}")]
+    SendTimeout {
+        /// The name of the node that failed to send the pdata message.
+        node: Cow<'static, str>,
+
+        /// The timeout of the send.
+        timeout: Duration,
+
+        /// The pdata message that could not be sent.
+        message: T,
+    },
+
+    /// A node sent a pdata message to an output port it doesn't have.
+    #[error("Node {node} has no output port named {port}")]
+    UnknownOutPort {
+        /// The name of the node that sent the pdata message.
+        node: Cow<'static, str>,
+
+        /// The name of the unknown output port.
+        port: String,
+    },
+
+    /// No receiver is registered to handle the Ack/Nack of the given message id.
+    #[error("No receiver registered to handle the Ack/Nack of message {id}")]
+    UnknownAckRoute {
+        /// The id of the acknowledged message.
+        id: u64,
+    },
+
+    /// A configuration update could not be decoded into the configuration type of a node.
+    #[error("Invalid configuration update: {0}")]
+    InvalidConfig(#[from] serde_json::Error),
+
+    /// A node rejected a configuration update (see [`crate::config_ack`]).
+    #[error("Node {node} rejected the configuration version {version}: {reason}")]
+    ConfigRejected {
+        /// The name of the node that rejected the configuration update.
+        node: Cow<'static, str>,
+
+        /// The version of the rejected configuration update.
+        version: u64,
+
+        /// The reason for the rejection.
+        reason: String,
+    },
+
+    /// A node did not complete within its shutdown deadline after receiving a `Shutdown` message.
+    #[error("Node {node} did not shut down within its deadline of {deadline:?}")]
+    ShutdownTimeout {
+        /// The name of the node that failed to shut down in time.
+        node: Cow<'static, str>,
+
+        /// The deadline carried by the `Shutdown` message.
+        deadline: Duration,
+    },
+
+    /// A node panicked, the panic was caught by its wrapper (see [`crate::shutdown`] for the
+    /// invariants a node must respect for the pipeline to keep running safely).
+    #[error("Node {node} panicked: {payload}")]
+    NodePanicked {
+        /// The name of the node that panicked.
+        node: Cow<'static, str>,
+
+        /// The message of the panic.
+        payload: String,
+    },
 
     /// A wrapper for the IO errors.
     #[error("An IO error occurred in node {node}: {error}")]
@@ -28,6 +146,13 @@ pub enum Error<T> {
         error: std::io::Error,
     },
 
+    /// A pipeline-level error, e.g. an invalid pipeline definition.
+    #[error("A pipeline error occurred: {error}")]
+    PipelineError {
+        /// The error that occurred.
+        error: String,
+    },
+
     /// The specified already exists in the pipeline.
     #[error("The receiver `{receiver}` already exists")]
     ReceiverAlreadyExists {
@@ -82,3 +207,249 @@ pub enum Error<T> {
         error: String,
     },
 }
+
+/// The category of an [`Error`], letting the supervisors of the nodes decide whether to retry or
+/// to escalate without matching on the error variants or messages (see [`Error::error_code`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// A control or pdata channel is closed, i.e. the node on the other side is gone.
+    ChannelClosed,
+    /// A message could not be sent, e.g. because the channel is full.
+    SendFailed,
+    /// A message could not be received.
+    ReceiveFailed,
+    /// A configuration update, a node configuration or the pipeline definition is invalid.
+    ConfigInvalid,
+    /// An IO error occurred, e.g. a socket could not be bound.
+    IoError,
+    /// A node did not complete within its shutdown deadline.
+    Shutdown,
+    /// A node failed, e.g. it reported an error of its own or panicked.
+    NodeFailed,
+}
+
+impl ErrorCode {
+    /// Returns true if the errors with this code are transient, i.e. retrying the operation (or
+    /// restarting the node) may succeed. The other errors are fatal.
+    #[must_use]
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::SendFailed | ErrorCode::ReceiveFailed | ErrorCode::IoError
+        )
+    }
+}
+
+/// Errors returned when receiving a control message whose configuration update is decoded into
+/// the configuration type of a node (see the `recv_typed` method of the receiver control channels).
+#[derive(thiserror::Error, Debug)]
+pub enum TypedRecvError {
+    /// The control channel is closed.
+    #[error("The control channel is closed")]
+    ChannelClosed,
+
+    /// The configuration update does not match the configuration type of the node.
+    #[error("Invalid configuration update: {0}")]
+    InvalidConfig(#[from] serde_json::Error),
+}
+
+/// Errors returned when splitting a byte stream into lines (see [`crate::framing`]).
+#[derive(thiserror::Error, Debug)]
+pub enum FramingError {
+    /// A line exceeds the maximum line length, the rest of the line is skipped.
+    #[error("A line exceeds the maximum of {max} bytes")]
+    LineTooLong {
+        /// The line, truncated to the maximum line length.
+        line: bytes::BytesMut,
+        /// The maximum line length.
+        max: usize,
+    },
+}
+
+/// Errors returned by the outbound connections of exporters (see [`crate::outbound`]).
+#[derive(thiserror::Error, Debug)]
+pub enum ConnectError {
+    /// The endpoint can't be reached, the retry policy of the connection gave up.
+    #[error("The endpoint {addr} is unreachable after {attempts} attempts: {error}")]
+    Unreachable {
+        /// The address of the endpoint.
+        addr: SocketAddr,
+        /// The number of connection (or write) attempts.
+        attempts: u32,
+        /// The last error.
+        error: std::io::Error,
+    },
+}
+
+/// Errors returned when shutting a pipeline down (see the `shutdown_with_timeout` method of the
+/// pipeline handle).
+#[derive(thiserror::Error, Debug)]
+pub enum ShutdownError<T> {
+    /// Some stages of the pipeline didn't complete within the shutdown timeout.
+    #[error("The pipeline stages {stage_names:?} did not complete within the shutdown timeout")]
+    TimedOut {
+        /// The names of the stages which didn't complete.
+        stage_names: Vec<String>,
+    },
+
+    /// A stage of the pipeline failed.
+    #[error("A pipeline stage failed: {0}")]
+    StageFailed(Error<T>),
+}
+
+impl<T> Error<T> {
+    /// Returns the category of this error.
+    #[must_use]
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Error::ChannelRecvError(RecvError::Closed)
+            | Error::ChannelSendError(SendError::Closed(_))
+            | Error::ControlChannelClosed { .. }
+            | Error::PdataChannelClosed { .. }
+            | Error::PdataBatchInterrupted { .. } => ErrorCode::ChannelClosed,
+            Error::ChannelRecvError(RecvError::Empty) => ErrorCode::ReceiveFailed,
+            Error::ChannelSendError(_)
+            | Error::PdataChannelFull { .. }
+            | Error::SendTimeout { .. } => ErrorCode::SendFailed,
+            Error::UnknownOutPort { .. }
+            | Error::UnknownAckRoute { .. }
+            | Error::InvalidConfig(_)
+            | Error::ConfigRejected { .. }
+            | Error::PdataReceiverAlreadyTaken { .. }
+            | Error::PipelineError { .. }
+            | Error::ReceiverAlreadyExists { .. }
+            | Error::ProcessorAlreadyExists { .. }
+            | Error::ExporterAlreadyExists { .. } => ErrorCode::ConfigInvalid,
+            Error::ShutdownTimeout { .. } => ErrorCode::Shutdown,
+            Error::IoError { .. } => ErrorCode::IoError,
+            Error::NodePanicked { .. }
+            | Error::ReceiverError { .. }
+            | Error::ProcessorError { .. }
+            | Error::ExporterError { .. } => ErrorCode::NodeFailed,
+        }
+    }
+
+    /// Converts the error returned when sending a pdata message to the output channel of the
+    /// given node, the capacity of the channel is reported when it is full.
+    pub(crate) fn from_pdata_send_error(
+        node: Cow<'static, str>,
+        capacity: usize,
+        error: SendError<T>,
+    ) -> Self {
+        match error {
+            SendError::Full(_) => Error::PdataChannelFull { node, capacity },
+            SendError::Closed(_) => Error::PdataChannelClosed { node },
+            error @ SendError::Broadcast { .. } => Error::ChannelSendError(error),
+        }
+    }
+
+    /// Converts the error returned when sending a pdata message to the output channel of the
+    /// given node with the given timeout, a full channel meaning that the send timed out.
+    pub(crate) fn from_pdata_send_timeout_error(
+        node: Cow<'static, str>,
+        capacity: usize,
+        timeout: Duration,
+        error: SendError<T>,
+    ) -> Self {
+        match error {
+            SendError::Full(message) => Error::SendTimeout {
+                node,
+                timeout,
+                message,
+            },
+            error => Error::from_pdata_send_error(node, capacity, error),
+        }
+    }
+}
+
+impl<T> From<TypedRecvError> for Error<T> {
+    fn from(error: TypedRecvError) -> Self {
+        match error {
+            TypedRecvError::ChannelClosed => Error::ChannelRecvError(RecvError::Closed),
+            TypedRecvError::InvalidConfig(error) => Error::InvalidConfig(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, ErrorCode};
+    use otap_df_channel::error::{RecvError, SendError};
+    use std::time::Duration;
+
+    #[test]
+    fn test_error_codes() {
+        let cases: [(Error<u32>, ErrorCode, bool); 9] = [
+            (
+                Error::ChannelSendError(SendError::Closed(1)),
+                ErrorCode::ChannelClosed,
+                false,
+            ),
+            (
+                Error::PdataChannelFull {
+                    node: "node".into(),
+                    capacity: 1,
+                },
+                ErrorCode::SendFailed,
+                true,
+            ),
+            (
+                Error::SendTimeout {
+                    node: "node".into(),
+                    timeout: Duration::from_secs(1),
+                    message: 1,
+                },
+                ErrorCode::SendFailed,
+                true,
+            ),
+            (
+                Error::ChannelRecvError(RecvError::Empty),
+                ErrorCode::ReceiveFailed,
+                true,
+            ),
+            (
+                Error::UnknownOutPort {
+                    node: "node".into(),
+                    port: "port".to_owned(),
+                },
+                ErrorCode::ConfigInvalid,
+                false,
+            ),
+            (
+                Error::PdataReceiverAlreadyTaken {
+                    node: "node".into(),
+                },
+                ErrorCode::ConfigInvalid,
+                false,
+            ),
+            (
+                Error::ShutdownTimeout {
+                    node: "node".into(),
+                    deadline: Duration::from_secs(1),
+                },
+                ErrorCode::Shutdown,
+                false,
+            ),
+            (
+                Error::NodePanicked {
+                    node: "node".into(),
+                    payload: "boom".to_owned(),
+                },
+                ErrorCode::NodeFailed,
+                false,
+            ),
+            (
+                Error::IoError {
+                    node: "node".into(),
+                    error: std::io::Error::from(std::io::ErrorKind::AddrInUse),
+                },
+                ErrorCode::IoError,
+                true,
+            ),
+        ];
+        for (error, code, transient) in cases {
+            assert_eq!(error.error_code(), code, "{error}");
+            assert_eq!(code.is_transient(), transient, "{code:?}");
+        }
+    }
+}
@@ -6,13 +6,21 @@
 //! For more details on the `!Send` implementation of an exporter, see [`local::Exporter`].
 //! See [`shared::Exporter`] for the Send implementation.
 
-use crate::config::ExporterConfig;
+use crate::config::{ExporterConfig, TimerConfig};
+use crate::config_ack::ConfigAckWatcher;
 use crate::error::Error;
+use crate::flush_ack::FlushAckWatcher;
+use crate::health::{HealthProbe, NodeHealth, with_health_checks};
 use crate::local::exporter as local;
+use crate::logging::LogSink;
 use crate::message;
-use crate::message::ControlMsg;
-use crate::message::Receiver;
+use crate::message::{ControlMsg, Receiver, Sender};
 use crate::shared::exporter as shared;
+use crate::shutdown::{FORWARDED_CONTROL_CHANNEL_CAPACITY, run_with_shutdown_deadline};
+use otap_df_channel::mpsc;
+use std::borrow::Cow;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
 
 /// A wrapper for the exporter that allows for both `Send` and `!Send` effect handlers.
 ///
@@ -25,6 +33,16 @@ pub enum ExporterWrapper<PData> {
         exporter: Box<dyn local::Exporter<PData>>,
         /// The effect handler instance for the exporter.
         effect_handler: local::EffectHandler<PData>,
+        /// A sender for control messages.
+        control_sender: mpsc::Sender<ControlMsg>,
+        /// A receiver for control messages.
+        control_receiver: mpsc::Receiver<ControlMsg>,
+        /// A receiver for the input pdata messages (set via `connect_input`).
+        pdata_receiver: Option<Receiver<PData>>,
+        /// The liveness probe of the exporter.
+        health: HealthProbe,
+        /// The cadence of the `TimerTick` messages delivered to the exporter.
+        timer: Option<TimerConfig>,
     },
     /// An exporter with a `Send` implementation.
     Shared {
@@ -32,6 +50,16 @@ pub enum ExporterWrapper<PData> {
         exporter: Box<dyn shared::Exporter<PData>>,
         /// The effect handler instance for the exporter.
         effect_handler: shared::EffectHandler<PData>,
+        /// A sender for control messages.
+        control_sender: tokio::sync::mpsc::Sender<ControlMsg>,
+        /// A receiver for control messages.
+        control_receiver: tokio::sync::mpsc::Receiver<ControlMsg>,
+        /// A receiver for the input pdata messages (set via `connect_input`).
+        pdata_receiver: Option<Receiver<PData>>,
+        /// The liveness probe of the exporter.
+        health: HealthProbe,
+        /// The cadence of the `TimerTick` messages delivered to the exporter.
+        timer: Option<TimerConfig>,
     },
 }
 
@@ -42,9 +70,17 @@ impl<PData> ExporterWrapper<PData> {
     where
         E: local::Exporter<PData> + 'static,
     {
+        let (control_sender, control_receiver) =
+            mpsc::Channel::new(config.control_channel.capacity);
+
         ExporterWrapper::Local {
             effect_handler: local::EffectHandler::new(config.name.clone()),
             exporter: Box::new(exporter),
+            control_sender,
+            control_receiver,
+            pdata_receiver: None,
+            health: HealthProbe::new(config.health_check),
+            timer: config.timer,
         }
     }
 
@@ -54,63 +90,317 @@ impl<PData> ExporterWrapper<PData> {
     where
         E: shared::Exporter<PData> + 'static,
     {
+        let (control_sender, control_receiver) =
+            tokio::sync::mpsc::channel(config.control_channel.capacity);
+
         ExporterWrapper::Shared {
             effect_handler: shared::EffectHandler::new(config.name.clone()),
             exporter: Box::new(exporter),
+            control_sender,
+            control_receiver,
+            pdata_receiver: None,
+            health: HealthProbe::new(config.health_check),
+            timer: config.timer,
         }
     }
 
-    /// Starts the exporter and begins exporting incoming data.
-    pub async fn start(
-        self,
-        control_rx: Receiver<ControlMsg>,
-        pdata_rx: Receiver<PData>,
+    /// Returns the name of the exporter.
+    #[must_use]
+    pub fn name(&self) -> Cow<'static, str> {
+        match self {
+            ExporterWrapper::Local { effect_handler, .. } => effect_handler.exporter_name(),
+            ExporterWrapper::Shared { effect_handler, .. } => effect_handler.exporter_name(),
+        }
+    }
+
+    /// Emits the messages logged by the exporter (see the `info_message`, `warn_message` and
+    /// `error_message` methods of its effect handler) to the given sink, tagged with the given
+    /// pipeline id. Without it, the messages are emitted as `tracing` events (see
+    /// [`crate::logging::TracingLogSink`]).
+    pub fn set_log_sink(&mut self, sink: Arc<dyn LogSink>, pipeline_id: Option<Cow<'static, str>>) {
+        match self {
+            ExporterWrapper::Local { effect_handler, .. } => {
+                effect_handler.set_log_sink(sink, pipeline_id);
+            }
+            ExporterWrapper::Shared { effect_handler, .. } => {
+                effect_handler.set_log_sink(sink, pipeline_id);
+            }
+        }
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the exporter (see
+    /// [`crate::config_ack`]).
+    #[must_use]
+    pub fn config_acks(&self) -> ConfigAckWatcher {
+        match self {
+            ExporterWrapper::Local { effect_handler, .. } => effect_handler.config_acks(),
+            ExporterWrapper::Shared { effect_handler, .. } => effect_handler.config_acks(),
+        }
+    }
+
+    /// Returns a watcher used to send flushes to the exporter and to await their acknowledgement
+    /// (see [`crate::flush_ack`]).
+    #[must_use]
+    pub fn flush_acks(&self) -> FlushAckWatcher {
+        match self {
+            ExporterWrapper::Local { effect_handler, .. } => effect_handler.flush_acks(),
+            ExporterWrapper::Shared { effect_handler, .. } => effect_handler.flush_acks(),
+        }
+    }
+
+    /// Returns the current health of the exporter (see [`crate::health`]).
+    #[must_use]
+    pub fn health(&self) -> NodeHealth {
+        self.health_probe().health()
+    }
+
+    /// Returns a probe tracking the health of the exporter once started.
+    #[must_use]
+    pub fn health_probe(&self) -> HealthProbe {
+        match self {
+            ExporterWrapper::Local { health, .. } | ExporterWrapper::Shared { health, .. } => {
+                health.clone()
+            }
+        }
+    }
+
+    /// Returns the control message sender for the exporter.
+    #[must_use]
+    pub fn control_sender(&self) -> Sender<ControlMsg> {
+        match self {
+            ExporterWrapper::Local { control_sender, .. } => Sender::Local(control_sender.clone()),
+            ExporterWrapper::Shared { control_sender, .. } => {
+                Sender::Shared(control_sender.clone())
+            }
+        }
+    }
+
+    /// Connects the input pdata channel of the exporter, typically the pdata receiver taken from
+    /// the upstream node. Any previously connected input is replaced.
+    pub fn connect_input(&mut self, pdata_rx: Receiver<PData>) {
+        match self {
+            ExporterWrapper::Local { pdata_receiver, .. }
+            | ExporterWrapper::Shared { pdata_receiver, .. } => *pdata_receiver = Some(pdata_rx),
+        }
+    }
+
+    /// Connects the control channel of an upstream node (typically a receiver) to which the
+    /// exporter sends its throttle signals and its delivery notifications (see the effect
+    /// handler's `throttle_upstream`, `notify_ack` and `notify_nack`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ExporterError`] if a local control sender is connected to a shared
+    /// exporter.
+    pub fn connect_upstream_control(
+        &mut self,
+        control_sender: Sender<ControlMsg>,
     ) -> Result<(), Error<PData>> {
+        match (self, control_sender) {
+            (ExporterWrapper::Local { effect_handler, .. }, control_sender) => {
+                effect_handler.connect_upstream_control(control_sender);
+            }
+            (ExporterWrapper::Shared { effect_handler, .. }, control_sender) => {
+                match control_sender.into_shared() {
+                    Some(control_sender) => effect_handler.connect_upstream_control(control_sender),
+                    None => {
+                        return Err(Error::ExporterError {
+                            exporter: effect_handler.exporter_name(),
+                            error: "Shared ExporterWrapper requires shared channels".to_owned(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the exporter and begins exporting incoming data.
+    ///
+    /// Once a `Shutdown` control message has been delivered, the exporter is given the shutdown
+    /// deadline to complete, after which it is dropped. The pdata buffered in the input channel
+    /// when the `Shutdown` is delivered is drained to the exporter before it, within the deadline.
+    /// The subtasks spawned by the exporter via `spawn_local_named` or `spawn_named` are aborted
+    /// once the exporter completed.
+    ///
+    /// Exporters are the terminal nodes of the pipeline, so control messages propagating through
+    /// the pipeline (i.e. `Flush`) stop here.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ExporterError`] if no input pdata channel has been connected (see
+    /// `connect_input`) or if a shared exporter is connected to a local channel, and an
+    /// [`Error::ShutdownTimeout`] if the exporter didn't complete within its shutdown deadline.
+    pub async fn start(self) -> Result<(), Error<PData>> {
         match self {
             ExporterWrapper::Local {
                 effect_handler,
                 exporter,
+                control_sender,
+                control_receiver,
+                pdata_receiver,
+                health,
+                timer,
             } => {
-                let message_channel = message::MessageChannel::new(control_rx, pdata_rx);
-                exporter.start(message_channel, effect_handler).await
+                let Some(pdata_rx) = pdata_receiver else {
+                    return Err(Error::ExporterError {
+                        exporter: effect_handler.exporter_name(),
+                        error: "No input pdata channel connected".to_owned(),
+                    });
+                };
+                let (node_control_tx, node_control_rx) =
+                    mpsc::Channel::new(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+                let message_channel =
+                    message::MessageChannel::new(Receiver::Local(node_control_rx), pdata_rx)
+                        .track_health(health.clone())
+                        .track_telemetry(effect_handler.telemetry());
+                let tasks = effect_handler.tasks();
+                let result = with_health_checks(
+                    effect_handler.exporter_name(),
+                    health,
+                    control_sender,
+                    run_with_shutdown_deadline(
+                        effect_handler.exporter_name(),
+                        control_receiver,
+                        node_control_tx,
+                        timer,
+                        None,
+                        effect_handler.telemetry(),
+                        exporter.start(message_channel, effect_handler),
+                    ),
+                )
+                .await;
+                // The subtasks of the exporter never outlive it.
+                tasks.abort_all();
+                result
             }
             ExporterWrapper::Shared {
                 effect_handler,
                 exporter,
-            } => {
-                if let (Receiver::Shared(control_rx), Receiver::Shared(pdata_rx)) =
-                    (control_rx, pdata_rx)
-                {
-                    let message_channel = shared::MessageChannel::new(control_rx, pdata_rx);
-                    exporter.start(message_channel, effect_handler).await
-                } else {
-                    Err(Error::ExporterError {
-                        exporter: effect_handler.exporter_name(),
-                        error: "Shared ExporterWrapper requires shared channels".to_owned(),
-                    })
+                control_sender,
+                control_receiver,
+                pdata_receiver,
+                health,
+                timer,
+            } => match pdata_receiver {
+                Some(Receiver::Shared(pdata_rx)) => {
+                    start_shared(
+                        exporter,
+                        effect_handler,
+                        control_sender,
+                        control_receiver,
+                        pdata_rx,
+                        health,
+                        timer,
+                    )
+                    .await
                 }
-            }
+                Some(Receiver::Local(_)) => Err(Error::ExporterError {
+                    exporter: effect_handler.exporter_name(),
+                    error: "Shared ExporterWrapper requires shared channels".to_owned(),
+                }),
+                None => Err(Error::ExporterError {
+                    exporter: effect_handler.exporter_name(),
+                    error: "No input pdata channel connected".to_owned(),
+                }),
+            },
+        }
+    }
+
+    /// Spawns the exporter on the current runtime, local exporters are spawned on the current
+    /// [`tokio::task::LocalSet`] while shared exporters are spawned on the Tokio thread pool.
+    ///
+    /// Must be called from within a `LocalSet`.
+    pub(crate) fn spawn(self) -> JoinHandle<Result<(), Error<PData>>>
+    where
+        PData: Send + 'static,
+    {
+        match self {
+            ExporterWrapper::Shared {
+                effect_handler,
+                exporter,
+                control_sender,
+                control_receiver,
+                pdata_receiver: Some(Receiver::Shared(pdata_rx)),
+                health,
+                timer,
+            } => tokio::spawn(start_shared(
+                exporter,
+                effect_handler,
+                control_sender,
+                control_receiver,
+                pdata_rx,
+                health,
+                timer,
+            )),
+            // Local exporters, and misconfigured shared exporters (reported by `start`).
+            other => tokio::task::spawn_local(other.start()),
         }
     }
 }
 
+/// Starts a shared exporter, the returned future is `Send` as long as `PData` is `Send`.
+async fn start_shared<PData>(
+    exporter: Box<dyn shared::Exporter<PData>>,
+    effect_handler: shared::EffectHandler<PData>,
+    control_sender: tokio::sync::mpsc::Sender<ControlMsg>,
+    control_receiver: tokio::sync::mpsc::Receiver<ControlMsg>,
+    pdata_rx: tokio::sync::mpsc::Receiver<PData>,
+    health: HealthProbe,
+    timer: Option<TimerConfig>,
+) -> Result<(), Error<PData>> {
+    let (node_control_tx, node_control_rx) =
+        tokio::sync::mpsc::channel(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+    let message_channel = shared::MessageChannel::new(node_control_rx, pdata_rx)
+        .track_health(health.clone())
+        .track_telemetry(effect_handler.telemetry());
+    let tasks = effect_handler.tasks();
+    let result = with_health_checks(
+        effect_handler.exporter_name(),
+        health,
+        control_sender,
+        run_with_shutdown_deadline(
+            effect_handler.exporter_name(),
+            control_receiver,
+            node_control_tx,
+            timer,
+            None,
+            effect_handler.telemetry(),
+            exporter.start(message_channel, effect_handler),
+        ),
+    )
+    .await;
+    // The subtasks of the exporter never outlive it.
+    tasks.abort_all();
+    result
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::config::{ExporterConfig, RetryPolicy};
+    use crate::error::ConnectError;
     use crate::exporter::{Error, ExporterWrapper};
     use crate::local::exporter as local;
     use crate::message;
-    use crate::message::{ControlMsg, Message};
+    use crate::message::{ControlMsg, Message, NodeConfigUpdate};
+    use crate::outbound::OutboundConnection;
     use crate::shared::exporter as shared;
     use crate::testing::exporter::TestContext;
     use crate::testing::exporter::TestRuntime;
-    use crate::testing::{CtrlMsgCounters, TestMsg};
+    use crate::testing::logging::InMemoryLogSink;
+    use crate::testing::{CtrlMsgCounters, TestMsg, setup_test_runtime};
     use async_trait::async_trait;
     use otap_df_channel::error::RecvError;
     use otap_df_channel::mpsc;
     use serde_json::Value;
+    use std::borrow::Cow;
     use std::future::Future;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
-    use tokio::time::sleep;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::time::{sleep, timeout};
 
     /// A test exporter that counts message events.
     /// Works with any type of exporter !Send or Send.
@@ -211,7 +501,7 @@ mod tests {
                 }
 
                 // Send a Config event.
-                ctx.send_config(Value::Null)
+                ctx.send_config(NodeConfigUpdate::for_all(1, Value::Null))
                     .await
                     .expect("Failed to send Config");
 
@@ -238,6 +528,8 @@ mod tests {
                     1, // message
                     1, // config
                     1, // shutdown
+                    0, // pause
+                    0, // resume
                 );
             })
         }
@@ -495,4 +787,330 @@ mod tests {
         // Second recv -> channel considered closed
         assert!(matches!(chan.recv().await, Err(RecvError::Closed)));
     }
+
+    /// Starting an exporter without a connected input must fail instead of panicking.
+    #[test]
+    fn test_exporter_without_input() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let config = ExporterConfig::new("no_input");
+
+        let local_exporter: ExporterWrapper<TestMsg> =
+            ExporterWrapper::local(TestExporter::new(CtrlMsgCounters::new()), &config);
+        let shared_exporter: ExporterWrapper<TestMsg> =
+            ExporterWrapper::shared(TestExporter::new(CtrlMsgCounters::new()), &config);
+
+        rt.block_on(local_tasks.run_until(async move {
+            assert!(matches!(
+                local_exporter.start().await,
+                Err(Error::ExporterError { .. })
+            ));
+            assert!(matches!(
+                shared_exporter.start().await,
+                Err(Error::ExporterError { .. })
+            ));
+        }));
+    }
+
+    #[test]
+    fn test_shared_exporter_rejects_local_upstream_control() {
+        let config = ExporterConfig::new("shared_exporter");
+        let mut exporter: ExporterWrapper<TestMsg> =
+            ExporterWrapper::shared(TestExporter::new(CtrlMsgCounters::new()), &config);
+        let (control_tx, _control_rx) = mpsc::Channel::new(1);
+
+        assert!(matches!(
+            exporter.connect_upstream_control(message::Sender::Local(control_tx)),
+            Err(Error::ExporterError { .. })
+        ));
+    }
+
+    /// A test exporter writing the received pdata to an in-memory sink, and notifying the upstream
+    /// nodes of the delivery of each message (the id being its position in the input). Messages
+    /// with the content `reject` are not written and nacked instead.
+    struct SinkExporter {
+        sink: Arc<Mutex<Vec<TestMsg>>>,
+    }
+
+    #[async_trait(?Send)]
+    impl local::Exporter<TestMsg> for SinkExporter {
+        async fn start(
+            self: Box<Self>,
+            mut msg_chan: message::MessageChannel<TestMsg>,
+            effect_handler: local::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            let mut id = 0;
+            loop {
+                match msg_chan.recv().await? {
+                    Message::Control(ControlMsg::Shutdown { .. }) => break,
+                    Message::PData(msg) if msg.0 == "reject" => {
+                        effect_handler.notify_nack(id, "rejected").await?;
+                        id += 1;
+                    }
+                    Message::PData(msg) => {
+                        self.sink.lock().unwrap().push(msg);
+                        effect_handler.notify_ack(id).await?;
+                        id += 1;
+                    }
+                    Message::Control(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Exporter<TestMsg> for SinkExporter {
+        async fn start(
+            self: Box<Self>,
+            mut msg_chan: shared::MessageChannel<TestMsg>,
+            effect_handler: shared::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            let mut id = 0;
+            loop {
+                match msg_chan.recv().await? {
+                    Message::Control(ControlMsg::Shutdown { .. }) => break,
+                    Message::PData(msg) if msg.0 == "reject" => {
+                        effect_handler.notify_nack(id, "rejected").await?;
+                        id += 1;
+                    }
+                    Message::PData(msg) => {
+                        self.sink.lock().unwrap().push(msg);
+                        effect_handler.notify_ack(id).await?;
+                        id += 1;
+                    }
+                    Message::Control(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Checks that the pdata buffered when the `Shutdown` is delivered reaches the sink, in order,
+    /// before the exporter completes, and that the upstream node is notified of each delivery.
+    fn assert_sink_exporter(
+        exporter: ExporterWrapper<TestMsg>,
+        sink: Arc<Mutex<Vec<TestMsg>>>,
+        (pdata_tx, pdata_rx): (message::Sender<TestMsg>, message::Receiver<TestMsg>),
+        (upstream_tx, mut upstream_rx): (
+            message::Sender<ControlMsg>,
+            message::Receiver<ControlMsg>,
+        ),
+    ) {
+        let (rt, local_tasks) = setup_test_runtime();
+        let mut exporter = exporter;
+        exporter.connect_input(pdata_rx);
+        exporter.connect_upstream_control(upstream_tx).unwrap();
+        let control_tx = exporter.control_sender();
+
+        rt.block_on(local_tasks.run_until(async move {
+            for content in ["a", "b", "reject", "c"] {
+                pdata_tx.send(TestMsg::new(content)).await.unwrap();
+            }
+            control_tx
+                .send(ControlMsg::Shutdown {
+                    deadline: Duration::from_millis(100),
+                    reason: "drain".to_owned(),
+                })
+                .await
+                .unwrap();
+
+            exporter.start().await.unwrap();
+            drop(pdata_tx);
+
+            assert_eq!(
+                *sink.lock().unwrap(),
+                vec![TestMsg::new("a"), TestMsg::new("b"), TestMsg::new("c")]
+            );
+            for expected_id in 0..4 {
+                match upstream_rx.recv().await.unwrap() {
+                    ControlMsg::Ack { id } => assert_eq!(id, expected_id),
+                    ControlMsg::Nack { id, reason } => {
+                        assert_eq!((id, reason.as_str()), (2, "rejected"));
+                        assert_eq!(expected_id, 2);
+                    }
+                    other => panic!("unexpected control message: {other:?}"),
+                }
+            }
+        }));
+    }
+
+    #[test]
+    fn test_sink_exporter_local() {
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let exporter = ExporterWrapper::local(
+            SinkExporter { sink: sink.clone() },
+            &ExporterConfig::new("sink"),
+        );
+        let (pdata_tx, pdata_rx) = mpsc::Channel::new(10);
+        let (upstream_tx, upstream_rx) = mpsc::Channel::new(10);
+
+        assert_sink_exporter(
+            exporter,
+            sink,
+            (
+                message::Sender::Local(pdata_tx),
+                message::Receiver::Local(pdata_rx),
+            ),
+            (
+                message::Sender::Local(upstream_tx),
+                message::Receiver::Local(upstream_rx),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_sink_exporter_shared() {
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let exporter = ExporterWrapper::shared(
+            SinkExporter { sink: sink.clone() },
+            &ExporterConfig::new("sink"),
+        );
+        let (pdata_tx, pdata_rx) = tokio::sync::mpsc::channel(10);
+        let (upstream_tx, upstream_rx) = tokio::sync::mpsc::channel(10);
+
+        assert_sink_exporter(
+            exporter,
+            sink,
+            (
+                message::Sender::Shared(pdata_tx),
+                message::Receiver::Shared(pdata_rx),
+            ),
+            (
+                message::Sender::Shared(upstream_tx),
+                message::Receiver::Shared(upstream_rx),
+            ),
+        );
+    }
+
+    /// Returns a free local address, nothing listens on it.
+    fn free_addr() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("Failed to reserve an address")
+    }
+
+    /// Waits until the given sink holds `count` warnings about failed connection attempts.
+    async fn wait_for_failed_attempts(log_sink: &InMemoryLogSink, count: usize) {
+        loop {
+            let failed = log_sink
+                .records()
+                .iter()
+                .filter(|record| record.message.starts_with("Failed to connect"))
+                .count();
+            if failed >= count {
+                return;
+            }
+            sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    /// Checks that a connection dialed with the given function, by an effect handler logging to
+    /// the given sink, is established at the third attempt once the endpoint starts listening
+    /// after two refused attempts, and that the bytes written to it are received.
+    fn assert_connect_retries<F>(
+        log_sink: Arc<InMemoryLogSink>,
+        connect: impl FnOnce(SocketAddr, RetryPolicy) -> F,
+    ) where
+        F: Future<Output = Result<OutboundConnection, ConnectError>>,
+    {
+        let (rt, local_tasks) = setup_test_runtime();
+        let addr = free_addr();
+        // The jittered backoff puts the third attempt at least 200ms after the second one, the
+        // endpoint starts listening in between.
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+        };
+
+        rt.block_on(local_tasks.run_until(async move {
+            let endpoint = tokio::spawn({
+                let log_sink = log_sink.clone();
+                async move {
+                    timeout(
+                        Duration::from_secs(3),
+                        wait_for_failed_attempts(&log_sink, 2),
+                    )
+                    .await
+                    .expect("Timed out waiting for the second attempt");
+                    let listener = TcpListener::bind(addr)
+                        .await
+                        .expect("Failed to bind the endpoint");
+                    let (mut socket, _) = listener.accept().await.expect("Failed to accept");
+                    let mut received = Vec::new();
+                    _ = socket
+                        .read_to_end(&mut received)
+                        .await
+                        .expect("Failed to read");
+                    received
+                }
+            });
+
+            let mut connection = connect(addr, policy)
+                .await
+                .expect("Failed to connect within the retry policy");
+            assert_eq!(connection.addr(), addr);
+            connection
+                .write_all(b"payload")
+                .await
+                .expect("Failed to write");
+            drop(connection);
+
+            let received = timeout(Duration::from_secs(3), endpoint)
+                .await
+                .expect("Timed out waiting for the endpoint")
+                .expect("Endpoint failed");
+            assert_eq!(received, b"payload");
+
+            // The two refused attempts were logged, the third one succeeded.
+            let failed: Vec<_> = log_sink
+                .records()
+                .into_iter()
+                .filter(|record| record.message.starts_with("Failed to connect"))
+                .map(|record| record.message)
+                .collect();
+            assert_eq!(failed.len(), 2);
+            assert!(failed[0].contains("(attempt 1)"));
+            assert!(failed[1].contains("(attempt 2)"));
+        }));
+    }
+
+    #[test]
+    fn test_connect_retries_local() {
+        let log_sink = Arc::new(InMemoryLogSink::new());
+        let mut effect_handler = local::EffectHandler::<TestMsg>::new(Cow::Borrowed("dialer"));
+        effect_handler.set_log_sink(log_sink.clone(), None);
+        assert_connect_retries(log_sink, |addr, policy| async move {
+            effect_handler.connect(addr, policy).await
+        });
+    }
+
+    #[test]
+    fn test_connect_retries_shared() {
+        let log_sink = Arc::new(InMemoryLogSink::new());
+        let mut effect_handler = shared::EffectHandler::<TestMsg>::new(Cow::Borrowed("dialer"));
+        effect_handler.set_log_sink(log_sink.clone(), None);
+        assert_connect_retries(log_sink, |addr, policy| async move {
+            effect_handler.connect(addr, policy).await
+        });
+    }
+
+    #[test]
+    fn test_connect_unreachable() {
+        let (rt, _) = setup_test_runtime();
+        let addr = free_addr();
+        let effect_handler = shared::EffectHandler::<TestMsg>::new(Cow::Borrowed("dialer"));
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+        };
+
+        let result = rt.block_on(effect_handler.connect(addr, policy));
+        assert!(matches!(
+            result,
+            Err(ConnectError::Unreachable { addr: unreachable, attempts: 2, .. })
+                if unreachable == addr
+        ));
+    }
 }
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Merging of the pdata emitted by several upstream nodes into the input of a node.
+//!
+//! A fan-in (see [`crate::pipeline::PipelineBuilder::fan_in`]) is a task consuming the output
+//! pdata channels of the upstream nodes and forwarding their messages to the input channel of the
+//! downstream node. The upstream channels are polled in a round-robin order so that a busy upstream
+//! can't starve the other ones (see [`MergedReceiver`]), and the input channel of the downstream
+//! node is only closed once all the upstream channels are closed: a failed upstream node doesn't
+//! interrupt the pdata of the other ones. As the fan-in owns the input channel of the downstream
+//! node, it also acts as a bridge: a `Shared` node can be fed by `Local` nodes.
+//!
+//! The messages can be tagged with the name of the upstream node they come from, so that the
+//! downstream node can tell the sources apart (see [`SourceTagged`]).
+
+use crate::error::Error;
+use crate::message::{MergedReceiver, Receiver, SelectableReceiver, Sender};
+use otap_df_channel::error::RecvError;
+use std::borrow::Cow;
+use std::task::{Context, Poll};
+
+/// A pdata message tagged with the name of the node it comes from (see
+/// [`crate::pipeline::PipelineBuilder::fan_in_tagged`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceTagged<PData> {
+    /// The name of the upstream node of the fan-in which emitted the message, empty until the
+    /// message goes through a tagging fan-in.
+    pub source: Cow<'static, str>,
+    /// The message.
+    pub pdata: PData,
+}
+
+impl<PData> SourceTagged<PData> {
+    /// Wraps a message not tagged yet.
+    #[must_use]
+    pub fn new(pdata: PData) -> Self {
+        SourceTagged {
+            source: Cow::Borrowed(""),
+            pdata,
+        }
+    }
+
+    /// Tags the message with the name of the node it comes from.
+    pub(crate) fn tag(&mut self, source: Cow<'static, str>) {
+        self.source = source;
+    }
+}
+
+/// An upstream channel of a fan-in, tagging the messages it delivers if requested.
+pub(crate) struct MergeInput<PData> {
+    pdata_rx: Receiver<PData>,
+    /// The name of the upstream node.
+    source: Cow<'static, str>,
+    tag: Option<fn(&mut PData, Cow<'static, str>)>,
+}
+
+impl<PData> MergeInput<PData> {
+    /// Creates the input of a fan-in consuming the output of the named upstream node.
+    pub(crate) fn new(
+        pdata_rx: Receiver<PData>,
+        source: Cow<'static, str>,
+        tag: Option<fn(&mut PData, Cow<'static, str>)>,
+    ) -> Self {
+        MergeInput {
+            pdata_rx,
+            source,
+            tag,
+        }
+    }
+}
+
+impl<PData> SelectableReceiver for MergeInput<PData> {
+    type Item = PData;
+
+    fn poll_select(&mut self, cx: &mut Context<'_>) -> Poll<Result<PData, RecvError>> {
+        self.pdata_rx.poll_recv(cx).map_ok(|mut pdata| {
+            if let Some(tag) = self.tag {
+                tag(&mut pdata, self.source.clone());
+            }
+            pdata
+        })
+    }
+}
+
+/// Forwards the pdata received from the inputs to `pdata_tx`, until all the inputs are closed.
+pub(crate) async fn run_merge<PData>(
+    inputs: Vec<MergeInput<PData>>,
+    pdata_tx: Sender<PData>,
+) -> Result<(), Error<PData>> {
+    let mut merged = MergedReceiver::new(inputs);
+    while let Some(pdata) = (&mut merged).await {
+        pdata_tx
+            .send(pdata)
+            .await
+            .map_err(Error::ChannelSendError)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MergeInput, SourceTagged, run_merge};
+    use crate::message::{Receiver, Sender};
+    use otap_df_channel::mpsc;
+    use std::borrow::Cow;
+    use tokio::runtime::Builder;
+    use tokio::task::LocalSet;
+
+    #[test]
+    fn test_merge_is_fair_and_waits_for_all_upstreams() {
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        LocalSet::new().block_on(&rt, async {
+            let (busy_tx, busy_rx) = mpsc::Channel::new(16);
+            let (quiet_tx, quiet_rx) = mpsc::Channel::new(16);
+            for i in 0..10 {
+                busy_tx.send(SourceTagged::new(i)).expect("Failed to send");
+            }
+            for i in 0..3 {
+                quiet_tx
+                    .send(SourceTagged::new(100 + i))
+                    .expect("Failed to send");
+            }
+            // The quiet upstream closes first, the merge keeps forwarding the busy one.
+            drop(quiet_tx);
+
+            let tag: fn(&mut SourceTagged<i32>, Cow<'static, str>) = SourceTagged::tag;
+            let inputs = vec![
+                MergeInput::new(Receiver::Local(busy_rx), "busy".into(), Some(tag)),
+                MergeInput::new(Receiver::Local(quiet_rx), "quiet".into(), Some(tag)),
+            ];
+            let (pdata_tx, pdata_rx) = mpsc::Channel::new(32);
+            let merge = tokio::task::spawn_local(run_merge(inputs, Sender::Local(pdata_tx)));
+
+            let mut merged = Vec::new();
+            for _ in 0..13 {
+                merged.push(pdata_rx.recv().await.expect("Merge closed early"));
+            }
+            let sources: Vec<_> = merged.iter().map(|tagged| tagged.source.as_ref()).collect();
+            // The upstreams alternate while both have pdata.
+            assert_eq!(
+                sources[..6],
+                ["busy", "quiet", "busy", "quiet", "busy", "quiet"]
+            );
+            assert!(sources[6..].iter().all(|source| *source == "busy"));
+            assert_eq!(
+                merged[1],
+                SourceTagged {
+                    source: "quiet".into(),
+                    pdata: 100,
+                }
+            );
+
+            // The merge ends once the last upstream closes.
+            assert!(!merge.is_finished());
+            drop(busy_tx);
+            merge
+                .await
+                .expect("Merge task failed")
+                .expect("Merge failed");
+            assert!(pdata_rx.recv().await.is_err());
+        });
+    }
+}
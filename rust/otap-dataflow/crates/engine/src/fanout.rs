@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dispatching of the pdata emitted by a node to several downstream nodes.
+//!
+//! A fan-out (see [`crate::pipeline::PipelineBuilder::fan_out`]) is a task consuming the output
+//! pdata channel of a node and dispatching every message to the input channels of the downstream
+//! nodes according to a [`DispatchPolicy`]. As the fan-out owns the input channel of every
+//! downstream node, it also acts as a bridge: `Shared` nodes can be fed by a `Local` node.
+
+use crate::error::Error;
+use crate::message::{Receiver, Sender};
+use otap_df_channel::error::SendError;
+
+/// How a fan-out dispatches the pdata messages to its downstream nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchPolicy {
+    /// Every downstream node receives a copy of every message. The slowest downstream node paces
+    /// the other ones.
+    Broadcast,
+    /// Every message goes to a single downstream node, in turn. A downstream node whose channel is
+    /// full is skipped, so that a slow node doesn't hold back the other ones. The fan-out only
+    /// waits when all the channels are full.
+    RoundRobin,
+    /// Every message goes to the downstream node with the fewest buffered messages.
+    LeastLoaded,
+}
+
+/// Dispatches the pdata received on `pdata_rx` to the `outputs` according to the policy, until the
+/// upstream node closes its output channel. `clone` copies the messages broadcast to several
+/// outputs.
+pub(crate) async fn run_fanout<PData>(
+    mut pdata_rx: Receiver<PData>,
+    outputs: Vec<Sender<PData>>,
+    policy: DispatchPolicy,
+    clone: fn(&PData) -> PData,
+) -> Result<(), Error<PData>> {
+    let mut next = 0;
+    while let Ok(pdata) = pdata_rx.recv().await {
+        match policy {
+            DispatchPolicy::Broadcast => {
+                let Some((last, others)) = outputs.split_last() else {
+                    continue;
+                };
+                for output in others {
+                    output
+                        .send(clone(&pdata))
+                        .await
+                        .map_err(Error::ChannelSendError)?;
+                }
+                last.send(pdata).await.map_err(Error::ChannelSendError)?;
+            }
+            DispatchPolicy::RoundRobin => {
+                next = send_round_robin(&outputs, next, pdata).await?;
+            }
+            DispatchPolicy::LeastLoaded => {
+                if let Some(output) = outputs.iter().min_by_key(|output| output.len()) {
+                    output.send(pdata).await.map_err(Error::ChannelSendError)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends the message to the first output with some capacity, starting from the `next` one, or
+/// waits for the `next` one if they are all full. Returns the index of the output next in turn.
+async fn send_round_robin<PData>(
+    outputs: &[Sender<PData>],
+    next: usize,
+    mut pdata: PData,
+) -> Result<usize, Error<PData>> {
+    if outputs.is_empty() {
+        return Ok(next);
+    }
+    for offset in 0..outputs.len() {
+        let index = (next + offset) % outputs.len();
+        match outputs[index].try_send(pdata) {
+            Ok(()) => return Ok((index + 1) % outputs.len()),
+            Err(SendError::Full(returned)) => pdata = returned,
+            Err(error) => return Err(Error::ChannelSendError(error)),
+        }
+    }
+    outputs[next]
+        .send(pdata)
+        .await
+        .map_err(Error::ChannelSendError)?;
+    Ok((next + 1) % outputs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DispatchPolicy, run_fanout};
+    use crate::message::{Receiver, Sender};
+    use otap_df_channel::mpsc;
+    use tokio::runtime::Builder;
+    use tokio::task::LocalSet;
+
+    /// Runs a fan-out of the given messages to local outputs of the given capacities, and returns
+    /// the messages left in every output.
+    fn dispatch(policy: DispatchPolicy, messages: usize, capacities: &[usize]) -> Vec<Vec<usize>> {
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        LocalSet::new().block_on(&rt, async {
+            let (pdata_tx, pdata_rx) = mpsc::Channel::new(messages.max(1));
+            let mut outputs = Vec::new();
+            let mut receivers = Vec::new();
+            for &capacity in capacities {
+                let (tx, rx) = mpsc::Channel::new(capacity);
+                outputs.push(Sender::Local(tx));
+                receivers.push(Receiver::Local(rx));
+            }
+            for i in 0..messages {
+                pdata_tx.send(i).expect("Failed to send");
+            }
+            drop(pdata_tx);
+            run_fanout(Receiver::Local(pdata_rx), outputs, policy, usize::clone)
+                .await
+                .expect("Fan-out failed");
+
+            let mut dispatched = Vec::new();
+            for mut receiver in receivers {
+                let mut received = Vec::new();
+                while let Ok(i) = receiver.recv().await {
+                    received.push(i);
+                }
+                dispatched.push(received);
+            }
+            dispatched
+        })
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let dispatched = dispatch(DispatchPolicy::Broadcast, 10, &[10, 10]);
+        assert_eq!(dispatched[0], (0..10).collect::<Vec<_>>());
+        assert_eq!(dispatched[1], (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_round_robin_skips_full_outputs() {
+        // The first output is never drained, the other one takes the messages it can't buffer.
+        let dispatched = dispatch(DispatchPolicy::RoundRobin, 10, &[2, 10]);
+        assert_eq!(dispatched[0], vec![0, 2]);
+        assert_eq!(dispatched[1], vec![1, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_least_loaded() {
+        let dispatched = dispatch(DispatchPolicy::LeastLoaded, 6, &[6, 6, 6]);
+        assert_eq!(dispatched, vec![vec![0, 3], vec![1, 4], vec![2, 5]]);
+    }
+}
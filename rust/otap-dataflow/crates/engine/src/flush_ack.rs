@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Acknowledgement of the `Flush` control messages handled by the nodes.
+//!
+//! Every [`ControlMsg::Flush`] carries a correlation id. Once a node has emitted the pdata it was
+//! holding, it confirms the flush with the effect handler's `ack_flush`. The sender of the flush
+//! can then await its completion through the [`FlushAckWatcher`] returned by the `flush_acks`
+//! method of the node wrappers. The ids of the flushes sent to a node are expected to increase,
+//! an acknowledgement covers all the flushes with a lower or equal id.
+
+use crate::error::Error;
+use crate::message::{ControlMsg, Sender};
+use otap_df_channel::error::RecvError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::watch;
+
+/// The node side of the flush acknowledgements, shared by all the clones of an effect handler.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone)]
+pub(crate) struct FlushAcks {
+    /// The highest flush id acknowledged by the node, 0 if none.
+    last_acked: Arc<watch::Sender<u64>>,
+    /// The id of the next flush sent through a [`FlushAckWatcher`].
+    next_id: Arc<AtomicU64>,
+}
+
+impl Default for FlushAcks {
+    fn default() -> Self {
+        let (last_acked, _) = watch::channel(0);
+        FlushAcks {
+            last_acked: Arc::new(last_acked),
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+}
+
+impl FlushAcks {
+    /// Records the acknowledgement of the flush with the given id.
+    pub(crate) fn ack(&self, id: u64) {
+        self.last_acked
+            .send_modify(|last_acked| *last_acked = (*last_acked).max(id));
+    }
+
+    /// Returns a watcher of the flushes acknowledged by the node.
+    pub(crate) fn watch(&self) -> FlushAckWatcher {
+        FlushAckWatcher {
+            last_acked: self.last_acked.subscribe(),
+            next_id: self.next_id.clone(),
+        }
+    }
+}
+
+/// Sends flushes to a node and waits for their acknowledgement.
+#[derive(Clone)]
+pub struct FlushAckWatcher {
+    last_acked: watch::Receiver<u64>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl FlushAckWatcher {
+    /// Sends a `Flush` control message with a new id to the given control channel of the node,
+    /// and waits for the node to acknowledge it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ChannelSendError`] if the control channel is closed, or an
+    /// [`Error::ChannelRecvError`] if the node terminated without acknowledging the flush.
+    pub async fn flush(
+        &mut self,
+        control_sender: &Sender<ControlMsg>,
+        reason: &str,
+    ) -> Result<(), Error<ControlMsg>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        control_sender
+            .send(ControlMsg::Flush {
+                id,
+                reason: reason.to_owned(),
+            })
+            .await?;
+        self.wait_for(id).await
+    }
+
+    /// Waits until the node has acknowledged the flush with the given id (or a more recent one).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ChannelRecvError`] if the node terminated without acknowledging the
+    /// flush.
+    pub async fn wait_for(&mut self, id: u64) -> Result<(), Error<ControlMsg>> {
+        _ = self
+            .last_acked
+            .wait_for(|last_acked| *last_acked >= id)
+            .await
+            .map_err(|_| Error::ChannelRecvError(RecvError::Closed))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Framing of the byte streams read by the receivers.
+//!
+//! A stream socket doesn't preserve the boundaries of the messages written to it: a `read` may
+//! return part of a message, or several of them, depending on how the stream was segmented. A
+//! [`LengthDelimitedConfig`] maps the framing of a stream into frames, each one prefixed by its
+//! length as a big-endian integer of [`PrefixSize`] bytes, to a [`LengthDelimitedCodec`] of
+//! `tokio_util`. Within a connection handler of `serve_connections`,
+//! [`LengthDelimitedConfig::framed`] wraps the socket into a [`FramedRead`] stream yielding whole
+//! frames, whatever the number of `read` calls needed to receive them.
+//!
+//! The length of a frame is checked against the maximum frame length before the frame is
+//! buffered: a longer frame fails with an [`std::io::ErrorKind::InvalidData`] error, after which
+//! the stream can't be resynchronized and the handler is expected to close the connection.
+//!
+//! For line-oriented protocols (e.g. syslog or logfmt), a [`LinesCodec`] splits a stream into
+//! lines terminated by `\n` or `\r\n`, a last line without terminator being yielded once the
+//! stream ends. Unlike the `LinesCodec` of `tokio_util`, it yields raw bytes rather than UTF-8
+//! strings, and handles a line longer than the maximum line length according to its
+//! [`OverlongLinePolicy`], the stream resuming at the next line either way.
+
+use crate::error::FramingError;
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio_util::codec::{Decoder, FramedRead, LengthDelimitedCodec};
+
+/// The default maximum length of a frame, see [`LengthDelimitedConfig::max_frame_length`].
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// The default maximum length of a line, see [`LinesCodec::with_max_line_length`].
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// The size of the length prefix of the frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefixSize {
+    /// A 1-byte length, for frames of up to 255 bytes.
+    U8,
+    /// A 2-byte length, for frames of up to 64 KiB.
+    U16,
+    /// A 4-byte length, for frames of up to 4 GiB.
+    U32,
+    /// An 8-byte length.
+    U64,
+}
+
+impl PrefixSize {
+    /// Returns the number of bytes of the prefix.
+    #[must_use]
+    pub fn byte_len(self) -> usize {
+        match self {
+            PrefixSize::U8 => 1,
+            PrefixSize::U16 => 2,
+            PrefixSize::U32 => 4,
+            PrefixSize::U64 => 8,
+        }
+    }
+}
+
+/// The framing of a byte stream into length-prefixed frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthDelimitedConfig {
+    /// The size of the length prefix of the frames.
+    pub prefix_size: PrefixSize,
+    /// The maximum length of a frame, excluding its prefix.
+    pub max_frame_length: usize,
+}
+
+impl Default for LengthDelimitedConfig {
+    fn default() -> Self {
+        LengthDelimitedConfig {
+            prefix_size: PrefixSize::U32,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+}
+
+impl LengthDelimitedConfig {
+    /// Returns a codec splitting a byte stream into frames, and encoding frames for such a
+    /// stream, according to this configuration.
+    #[must_use]
+    pub fn codec(&self) -> LengthDelimitedCodec {
+        LengthDelimitedCodec::builder()
+            .length_field_length(self.prefix_size.byte_len())
+            .max_frame_length(self.max_frame_length)
+            .new_codec()
+    }
+
+    /// Wraps the given reader (e.g. an accepted socket) into a stream of the frames read from it.
+    #[must_use]
+    pub fn framed<R: AsyncRead>(&self, reader: R) -> FramedRead<R, LengthDelimitedCodec> {
+        FramedRead::new(reader, self.codec())
+    }
+}
+
+/// What a [`LinesCodec`] does with a line longer than its maximum line length.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlongLinePolicy {
+    /// The line is truncated to the maximum line length and yielded within a
+    /// [`FramingError::LineTooLong`].
+    #[default]
+    Truncate,
+    /// The line is silently dropped.
+    Drop,
+}
+
+/// Splits a byte stream into lines terminated by `\n` or `\r\n`, the terminator being removed
+/// from the lines.
+///
+/// The lines are yielded as `Ok` items, and the overlong lines truncated by the
+/// [`OverlongLinePolicy`] as `Err` items, so that the [`FramedRead`] stream isn't terminated by
+/// them (it ends after the first error of its decoder).
+#[derive(Clone, Copy, Debug)]
+pub struct LinesCodec {
+    max_line_length: usize,
+    overlong_line_policy: OverlongLinePolicy,
+    /// Whether the rest of an overlong line is being skipped.
+    discarding: bool,
+    /// The number of buffered bytes already searched for a newline.
+    searched: usize,
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        LinesCodec {
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            overlong_line_policy: OverlongLinePolicy::default(),
+            discarding: false,
+            searched: 0,
+        }
+    }
+}
+
+impl LinesCodec {
+    /// Creates a codec with the default maximum line length, truncating the overlong lines.
+    #[must_use]
+    pub fn new() -> Self {
+        LinesCodec::default()
+    }
+
+    /// Sets the maximum length of a line, excluding its terminator.
+    #[must_use]
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /// Sets what is done with the lines longer than the maximum line length.
+    #[must_use]
+    pub fn with_overlong_line_policy(mut self, policy: OverlongLinePolicy) -> Self {
+        self.overlong_line_policy = policy;
+        self
+    }
+
+    /// Returns the maximum length of a line, excluding its terminator.
+    #[must_use]
+    pub fn max_line_length(&self) -> usize {
+        self.max_line_length
+    }
+
+    /// Returns what is done with the lines longer than the maximum line length.
+    #[must_use]
+    pub fn overlong_line_policy(&self) -> OverlongLinePolicy {
+        self.overlong_line_policy
+    }
+
+    /// Wraps the given reader (e.g. an accepted socket) into a stream of the lines read from it.
+    #[must_use]
+    pub fn framed<R: AsyncRead>(self, reader: R) -> FramedRead<R, LinesCodec> {
+        FramedRead::new(reader, self)
+    }
+
+    /// Returns the item yielded for the given line, if any.
+    fn checked(&self, mut line: BytesMut) -> Option<Result<BytesMut, FramingError>> {
+        if line.len() <= self.max_line_length {
+            return Some(Ok(line));
+        }
+        match self.overlong_line_policy {
+            OverlongLinePolicy::Truncate => {
+                line.truncate(self.max_line_length);
+                Some(Err(FramingError::LineTooLong {
+                    line,
+                    max: self.max_line_length,
+                }))
+            }
+            OverlongLinePolicy::Drop => None,
+        }
+    }
+}
+
+/// Removes the `\r` ending the given line, if any.
+fn strip_carriage_return(mut line: BytesMut) -> BytesMut {
+    if line.last() == Some(&b'\r') {
+        line.truncate(line.len() - 1);
+    }
+    line
+}
+
+impl Decoder for LinesCodec {
+    type Item = Result<BytesMut, FramingError>;
+    type Error = std::io::Error;
+
+    /// Takes the first line out of the given buffer. An overlong line is reported as soon as
+    /// more than the maximum line length is buffered, and the rest of it skipped.
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let Some(newline) = buf[self.searched..].iter().position(|byte| *byte == b'\n') else {
+                if self.discarding {
+                    buf.clear();
+                    self.searched = 0;
+                    return Ok(None);
+                }
+                // One more byte is allowed for the `\r` of a line of the maximum length.
+                if buf.len().saturating_sub(1) > self.max_line_length {
+                    self.discarding = true;
+                    self.searched = 0;
+                    return Ok(self.checked(buf.split()));
+                }
+                self.searched = buf.len();
+                return Ok(None);
+            };
+            let end = self.searched + newline;
+            self.searched = 0;
+            let mut line = buf.split_to(end + 1);
+            if std::mem::take(&mut self.discarding) {
+                continue;
+            }
+            line.truncate(end);
+            if let Some(item) = self.checked(strip_carriage_return(line)) {
+                return Ok(Some(item));
+            }
+        }
+    }
+
+    /// Yields the remaining lines once the stream ended, the last one even if it has no
+    /// terminator.
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(item) = self.decode(buf)? {
+            return Ok(Some(item));
+        }
+        self.searched = 0;
+        if std::mem::take(&mut self.discarding) || buf.is_empty() {
+            buf.clear();
+            return Ok(None);
+        }
+        Ok(self.checked(strip_carriage_return(buf.split())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LengthDelimitedConfig, LinesCodec, OverlongLinePolicy, PrefixSize};
+    use crate::error::FramingError;
+    use bytes::{Bytes, BytesMut};
+    use futures::StreamExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::time::{Duration, sleep};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    /// Encodes the given frames according to the given configuration.
+    fn encode(config: &LengthDelimitedConfig, frames: &[&'static [u8]]) -> BytesMut {
+        let mut codec = config.codec();
+        let mut bytes = BytesMut::new();
+        for frame in frames {
+            codec
+                .encode(Bytes::from_static(frame), &mut bytes)
+                .expect("Failed to encode");
+        }
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_frame_split_across_reads() {
+        let config = LengthDelimitedConfig {
+            prefix_size: PrefixSize::U16,
+            ..LengthDelimitedConfig::default()
+        };
+        let bytes = encode(&config, &[b"a frame split in two", b"next"]);
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut frames = config.framed(server);
+        let writer = tokio::spawn(async move {
+            // The first read returns the prefix and the beginning of the first frame only.
+            client
+                .write_all(&bytes[..7])
+                .await
+                .expect("Failed to write");
+            sleep(Duration::from_millis(50)).await;
+            client
+                .write_all(&bytes[7..])
+                .await
+                .expect("Failed to write");
+        });
+
+        let frame = frames.next().await.expect("Missing frame");
+        assert_eq!(&frame.expect("Failed to read")[..], b"a frame split in two");
+        let frame = frames.next().await.expect("Missing frame");
+        assert_eq!(&frame.expect("Failed to read")[..], b"next");
+        writer.await.expect("Writer failed");
+        // The stream ended on a frame boundary.
+        assert!(frames.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_frame_too_large_rejected() {
+        let bytes = encode(&LengthDelimitedConfig::default(), &[&[0; 1024]]);
+        let config = LengthDelimitedConfig {
+            max_frame_length: 16,
+            ..LengthDelimitedConfig::default()
+        };
+        assert!(
+            config
+                .codec()
+                .encode(Bytes::from_static(&[0; 17]), &mut BytesMut::new())
+                .is_err()
+        );
+
+        // Only the prefix is sent, the frame is rejected without waiting for its body.
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(&bytes[..4])
+            .await
+            .expect("Failed to write");
+        let mut frames = config.framed(server);
+        let error = frames
+            .next()
+            .await
+            .expect("Missing frame")
+            .expect_err("The frame was not rejected");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // A stream ending in the middle of a frame is truncated.
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(&[0, 0, 0, 8, 1, 2])
+            .await
+            .expect("Failed to write");
+        drop(client);
+        let mut frames = config.framed(server);
+        assert!(matches!(frames.next().await, Some(Err(_))));
+    }
+
+    #[tokio::test]
+    async fn test_lines_split_across_reads() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut lines = LinesCodec::new().framed(server);
+        let writer = tokio::spawn(async move {
+            // The second line is split across the two reads, the last one has no terminator.
+            client.write_all(b"a\r\nb").await.expect("Failed to write");
+            sleep(Duration::from_millis(50)).await;
+            client.write_all(b"\nc").await.expect("Failed to write");
+        });
+
+        for expected in [b"a", b"b", b"c"] {
+            let line = lines.next().await.expect("Missing line");
+            let line = line.expect("Failed to read").expect("Invalid line");
+            assert_eq!(&line[..], &expected[..]);
+        }
+        writer.await.expect("Writer failed");
+        assert!(lines.next().await.is_none());
+    }
+
+    #[test]
+    fn test_overlong_lines() {
+        let mut codec = LinesCodec::new().with_max_line_length(4);
+        assert_eq!(codec.overlong_line_policy(), OverlongLinePolicy::Truncate);
+        let mut buf = BytesMut::from(&b"abcdefgh\nok\n"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf).expect("Failed to decode"),
+            Some(Err(FramingError::LineTooLong { line, max: 4 })) if line == b"abcd"[..]
+        ));
+        assert!(matches!(
+            codec.decode(&mut buf).expect("Failed to decode"),
+            Some(Ok(line)) if line == b"ok"[..]
+        ));
+
+        // The overlong line is reported before its terminator is received, and its rest skipped.
+        let mut codec = codec.with_overlong_line_policy(OverlongLinePolicy::Drop);
+        let mut buf = BytesMut::from(&b"abcdef"[..]);
+        assert!(codec.decode(&mut buf).expect("Failed to decode").is_none());
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b"gh\r\nabcd\r\n");
+        assert!(matches!(
+            codec.decode(&mut buf).expect("Failed to decode"),
+            Some(Ok(line)) if line == b"abcd"[..]
+        ));
+        assert!(
+            codec
+                .decode_eof(&mut buf)
+                .expect("Failed to decode")
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_resumes_after_overlong_line() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"abcdefgh\nok\nlast")
+            .await
+            .expect("Failed to write");
+        drop(client);
+        let mut lines = LinesCodec::new().with_max_line_length(4).framed(server);
+
+        let line = lines.next().await.expect("Missing line");
+        assert!(matches!(
+            line.expect("Failed to read"),
+            Err(FramingError::LineTooLong { max: 4, .. })
+        ));
+        for expected in [&b"ok"[..], &b"last"[..]] {
+            let line = lines.next().await.expect("Missing line");
+            assert_eq!(
+                &line.expect("Failed to read").expect("Invalid line")[..],
+                expected
+            );
+        }
+        assert!(lines.next().await.is_none());
+    }
+}
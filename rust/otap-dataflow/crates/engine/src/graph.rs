@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The wiring topology of a pipeline, as a directed graph of its stages (see
+//! `Pipeline::graph`).
+//!
+//! The graph is accumulated by the `PipelineBuilder` while it connects the stages: a node per
+//! receiver, processor and exporter, and an edge per pdata channel from a stage to the next one.
+//! Bridges and fan-outs are not stages of their own: a bridge marks the edge it carries, and a
+//! fan-out is drawn as an edge to each of its downstream stages. The graph is printed in a
+//! human-readable form via `Display`, and in the Graphviz DOT format via `PipelineGraph::to_dot`
+//! with the `dot` feature.
+
+use otap_df_config::NodeKind;
+use std::borrow::Cow;
+use std::fmt;
+
+/// A stage of a pipeline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphNode {
+    /// The name of the stage.
+    pub name: Cow<'static, str>,
+    /// The kind of the stage.
+    pub kind: NodeKind,
+    /// Whether the stage has a `!Send` implementation.
+    pub is_local: bool,
+}
+
+impl fmt::Display for GraphNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flavor = if self.is_local { "local" } else { "shared" };
+        write!(f, "{} [{flavor} {}]", self.name, kind_name(self.kind))
+    }
+}
+
+/// A pdata channel connecting two stages, identified by their index in
+/// [`PipelineGraph::nodes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphEdge {
+    /// The stage sending the pdata messages.
+    pub from: usize,
+    /// The stage receiving the pdata messages.
+    pub to: usize,
+    /// Whether the messages go through a bridge, from a local stage to a shared stage.
+    pub bridged: bool,
+}
+
+/// The stages of a pipeline and the pdata channels connecting them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+impl PipelineGraph {
+    /// Adds a stage to the graph and returns its index.
+    pub(crate) fn add_node(
+        &mut self,
+        name: Cow<'static, str>,
+        kind: NodeKind,
+        is_local: bool,
+    ) -> usize {
+        self.nodes.push(GraphNode {
+            name,
+            kind,
+            is_local,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Adds a pdata channel between two stages of the graph.
+    pub(crate) fn add_edge(&mut self, from: usize, to: usize, bridged: bool) {
+        self.edges.push(GraphEdge { from, to, bridged });
+    }
+
+    /// Returns the stages of the pipeline, from the receiver to the exporters.
+    #[must_use]
+    pub fn nodes(&self) -> &[GraphNode] {
+        &self.nodes
+    }
+
+    /// Returns the pdata channels connecting the stages.
+    #[must_use]
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges
+    }
+
+    /// Returns the stages directly fed by the stage with the given name.
+    pub fn downstream_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a GraphNode> + 'a {
+        self.edges
+            .iter()
+            .filter(move |edge| self.nodes[edge.from].name == name)
+            .map(|edge| &self.nodes[edge.to])
+    }
+
+    /// Returns the graph in the Graphviz DOT format, e.g. to render it with `dot -Tsvg`.
+    #[cfg(feature = "dot")]
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        use std::fmt::Write;
+
+        let mut dot = String::from("digraph pipeline {\n    rankdir=LR;\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let flavor = if node.is_local { "local" } else { "shared" };
+            _ = writeln!(
+                dot,
+                "    n{index} [label=\"{}\\n{flavor} {}\"];",
+                node.name.replace('\\', "\\\\").replace('"', "\\\""),
+                kind_name(node.kind),
+            );
+        }
+        for edge in &self.edges {
+            let style = if edge.bridged {
+                " [style=dashed, label=\"bridge\"]"
+            } else {
+                ""
+            };
+            _ = writeln!(dot, "    n{} -> n{}{style};", edge.from, edge.to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl fmt::Display for PipelineGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            writeln!(f, "{node}")?;
+        }
+        for edge in &self.edges {
+            write!(
+                f,
+                "{} -> {}",
+                self.nodes[edge.from].name, self.nodes[edge.to].name
+            )?;
+            if edge.bridged {
+                write!(f, " (bridged)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name of a node kind, as printed in the graph.
+fn kind_name(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Receiver => "receiver",
+        NodeKind::Processor => "processor",
+        NodeKind::Exporter => "exporter",
+        NodeKind::ProcessorChain => "processor chain",
+        NodeKind::Connector => "connector",
+    }
+}
+
+#[cfg(all(test, feature = "dot"))]
+mod tests {
+    use super::PipelineGraph;
+    use otap_df_config::NodeKind;
+
+    #[test]
+    fn test_to_dot() {
+        let mut graph = PipelineGraph::default();
+        let receiver = graph.add_node("otlp \"grpc\"".into(), NodeKind::Receiver, true);
+        let exporter = graph.add_node("exporter".into(), NodeKind::Exporter, false);
+        graph.add_edge(receiver, exporter, true);
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph pipeline {\n    rankdir=LR;\n    \
+             n0 [label=\"otlp \\\"grpc\\\"\\nlocal receiver\"];\n    \
+             n1 [label=\"exporter\\nshared exporter\"];\n    \
+             n0 -> n1 [style=dashed, label=\"bridge\"];\n}\n"
+        );
+    }
+}
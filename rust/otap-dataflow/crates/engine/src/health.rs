@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Liveness probing of the nodes.
+//!
+//! When enabled (see [`HealthCheckConfig`]), the node wrappers periodically send a
+//! [`ControlMsg::HealthCheck`] to the node. The check is answered by the control channel of the
+//! node (or its message channel) as soon as the node receives it, i.e. as long as the event loop of
+//! the node keeps draining its control messages. A node that doesn't answer within the configured
+//! timeout is marked as [`NodeState::Stalled`] and a warning is emitted, it is marked as
+//! [`NodeState::Running`] again once it answers. The health of a node can be queried through the
+//! [`HealthProbe`] returned by the `health_probe` method of the node wrappers.
+//!
+//! On top of these probes, a receiver can report its own [`HealthStatus`] (e.g. a degraded
+//! upstream connection) with the effect handler's `report_health`. The reports are published to a
+//! watch channel returned by the `subscribe_health` method of the receiver wrapper, and the
+//! [`PipelineHealth`] of a pipeline aggregates the liveness and the reports of all its stages.
+
+use crate::config::HealthCheckConfig;
+use crate::message::{ControlMsg, ControlSender};
+use std::borrow::Cow;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// The liveness state of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeState {
+    /// The node answers its health checks (or isn't probed).
+    Running,
+    /// The node didn't answer its last health check within the configured timeout.
+    Stalled,
+    /// The node completed.
+    Stopped,
+}
+
+/// The health of a node, as observed by its health checks.
+#[derive(Clone, Copy, Debug)]
+pub struct NodeHealth {
+    /// The last time the node answered a health check (or the creation of the node if it never
+    /// did).
+    pub last_seen: Instant,
+    /// The liveness state of the node.
+    pub state: NodeState,
+}
+
+/// The health of a node, as reported by the node itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// The node works as expected.
+    #[default]
+    Healthy,
+    /// The node works with a reduced service level, for the given reason.
+    Degraded(String),
+    /// The node doesn't work, for the given reason.
+    Unhealthy(String),
+}
+
+impl HealthStatus {
+    /// Orders the statuses from the healthiest to the unhealthiest.
+    fn severity(&self) -> u8 {
+        match self {
+            HealthStatus::Healthy => 0,
+            HealthStatus::Degraded(_) => 1,
+            HealthStatus::Unhealthy(_) => 2,
+        }
+    }
+
+    fn reason(&self) -> Option<&str> {
+        match self {
+            HealthStatus::Healthy => None,
+            HealthStatus::Degraded(reason) | HealthStatus::Unhealthy(reason) => Some(reason),
+        }
+    }
+}
+
+/// A component able to tell its current health.
+pub trait HealthCheck {
+    /// Returns the current health of the component.
+    fn health(&self) -> HealthStatus;
+}
+
+/// The last health status reported by a node.
+impl HealthCheck for watch::Receiver<HealthStatus> {
+    fn health(&self) -> HealthStatus {
+        self.borrow().clone()
+    }
+}
+
+/// The node side of the health reports, shared by all the clones of an effect handler.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone)]
+pub(crate) struct HealthReports {
+    status: Arc<watch::Sender<HealthStatus>>,
+}
+
+impl Default for HealthReports {
+    fn default() -> Self {
+        let (status, _) = watch::channel(HealthStatus::Healthy);
+        HealthReports {
+            status: Arc::new(status),
+        }
+    }
+}
+
+impl HealthReports {
+    /// Publishes the health status reported by the node.
+    pub(crate) fn report(&self, status: HealthStatus) {
+        // Unlike `send`, `send_replace` records the status even if nobody is watching yet.
+        _ = self.status.send_replace(status);
+    }
+
+    /// Returns a receiver of the health statuses reported by the node.
+    pub(crate) fn subscribe(&self) -> watch::Receiver<HealthStatus> {
+        self.status.subscribe()
+    }
+}
+
+/// The health of a node along with the id of the last health check it answered.
+#[derive(Clone, Copy)]
+struct ProbeState {
+    health: NodeHealth,
+    answered: u64,
+}
+
+/// Tracks the health of a node, shared by the node wrapper and the control channel of the node.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone)]
+pub struct HealthProbe {
+    inner: Arc<ProbeInner>,
+}
+
+struct ProbeInner {
+    state: watch::Sender<ProbeState>,
+    config: Option<HealthCheckConfig>,
+}
+
+impl HealthProbe {
+    /// Creates a probe for a node probed according to the given configuration.
+    pub(crate) fn new(config: Option<HealthCheckConfig>) -> Self {
+        let (state, _) = watch::channel(ProbeState {
+            health: NodeHealth {
+                last_seen: Instant::now(),
+                state: NodeState::Running,
+            },
+            answered: 0,
+        });
+        HealthProbe {
+            inner: Arc::new(ProbeInner { state, config }),
+        }
+    }
+
+    /// Returns the current health of the node.
+    #[must_use]
+    pub fn health(&self) -> NodeHealth {
+        self.inner.state.borrow().health
+    }
+
+    /// Records the answer of the node to the health check with the given id.
+    pub(crate) fn answer(&self, id: u64) {
+        self.inner.state.send_modify(|state| {
+            state.answered = state.answered.max(id);
+            state.health.last_seen = Instant::now();
+            if state.health.state == NodeState::Stalled {
+                state.health.state = NodeState::Running;
+            }
+        });
+    }
+
+    fn set_state(&self, node_state: NodeState) {
+        self.inner
+            .state
+            .send_modify(|state| state.health.state = node_state);
+    }
+}
+
+/// The health of a pipeline, aggregated from the health of its stages.
+///
+/// A stage that is stalled or stopped (see [`NodeState`]) is unhealthy, otherwise its health is
+/// the last status it reported (stages that don't report their status are healthy). The pipeline
+/// is as healthy as its unhealthiest stages, the reasons of which are prefixed with the stage name.
+pub struct PipelineHealth {
+    stages: Vec<StageHealth>,
+}
+
+struct StageHealth {
+    name: Cow<'static, str>,
+    probe: HealthProbe,
+    reports: Option<watch::Receiver<HealthStatus>>,
+}
+
+impl PipelineHealth {
+    /// Creates an empty aggregate, i.e. a healthy one.
+    pub(crate) fn new() -> Self {
+        PipelineHealth { stages: Vec::new() }
+    }
+
+    /// Adds a stage to the aggregate, with the receiver of its health reports if it has one.
+    pub(crate) fn add_stage(
+        &mut self,
+        name: Cow<'static, str>,
+        probe: HealthProbe,
+        reports: Option<watch::Receiver<HealthStatus>>,
+    ) {
+        self.stages.push(StageHealth {
+            name,
+            probe,
+            reports,
+        });
+    }
+}
+
+impl StageHealth {
+    fn status(&self) -> HealthStatus {
+        match self.probe.health().state {
+            NodeState::Running => self
+                .reports
+                .as_ref()
+                .map_or(HealthStatus::Healthy, HealthCheck::health),
+            NodeState::Stalled => HealthStatus::Unhealthy("stalled".to_owned()),
+            NodeState::Stopped => HealthStatus::Unhealthy("stopped".to_owned()),
+        }
+    }
+}
+
+impl HealthCheck for PipelineHealth {
+    fn health(&self) -> HealthStatus {
+        let statuses: Vec<_> = self
+            .stages
+            .iter()
+            .map(|stage| (&stage.name, stage.status()))
+            .collect();
+        let severity = statuses
+            .iter()
+            .map(|(_, status)| status.severity())
+            .max()
+            .unwrap_or_default();
+        let reasons = statuses
+            .iter()
+            .filter(|(_, status)| status.severity() == severity)
+            .filter_map(|(name, status)| Some(format!("`{name}`: {}", status.reason()?)))
+            .collect::<Vec<_>>()
+            .join("; ");
+        match severity {
+            0 => HealthStatus::Healthy,
+            1 => HealthStatus::Degraded(reasons),
+            _ => HealthStatus::Unhealthy(reasons),
+        }
+    }
+}
+
+/// Runs the node future to completion while probing the liveness of the node, if the probe has a
+/// health check configuration. The node is marked as stopped once its future completes.
+pub(crate) async fn with_health_checks<T>(
+    node: Cow<'static, str>,
+    probe: HealthProbe,
+    control_sender: impl ControlSender,
+    node_future: impl Future<Output = T>,
+) -> T {
+    let output = match probe.inner.config {
+        Some(config) => {
+            tokio::pin!(node_future);
+            tokio::select! {
+                biased;
+                output = &mut node_future => output,
+                // The monitor only returns once the control channel is closed.
+                () = monitor(&node, config, &probe, control_sender) => node_future.await,
+            }
+        }
+        None => node_future.await,
+    };
+    probe.set_state(NodeState::Stopped);
+    output
+}
+
+/// Sends a health check to the node at every interval and waits for its answer, marking the node
+/// as stalled when the answer takes longer than the configured timeout. Returns once the control
+/// channel is closed.
+async fn monitor(
+    node: &str,
+    config: HealthCheckConfig,
+    probe: &HealthProbe,
+    control_sender: impl ControlSender,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut answers = probe.inner.state.subscribe();
+
+    for id in 1.. {
+        _ = interval.tick().await;
+        // Delivering the check is part of the probe, a stalled node may have a full control
+        // channel.
+        let check = async {
+            control_sender
+                .send_ctrl(ControlMsg::HealthCheck { id })
+                .await
+                .ok()?;
+            answers
+                .wait_for(|state| state.answered >= id)
+                .await
+                .ok()
+                .map(|_| ())
+        };
+        tokio::pin!(check);
+
+        let answered = match tokio::time::timeout(config.timeout, &mut check).await {
+            Ok(answered) => answered,
+            Err(_) => {
+                probe.set_state(NodeState::Stalled);
+                tracing::warn!(
+                    node,
+                    timeout = ?config.timeout,
+                    "Node did not answer its health check, marked as stalled"
+                );
+                let answered = check.await;
+                if answered.is_some() {
+                    tracing::info!(node, "Stalled node answered its health check");
+                }
+                answered
+            }
+        };
+        if answered.is_none() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HealthProbe, NodeState, with_health_checks};
+    use crate::config::HealthCheckConfig;
+    use crate::message::ControlMsg;
+    use crate::testing::{create_not_send_channel, setup_test_runtime};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[test]
+    fn test_stalled_until_answered() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let config = HealthCheckConfig {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_millis(50),
+        };
+        let probe = HealthProbe::new(Some(config));
+        let (control_tx, control_rx) = create_not_send_channel(8);
+
+        rt.block_on(local_tasks.run_until(async move {
+            let node_probe = probe.clone();
+            let node = tokio::task::spawn_local(with_health_checks(
+                "node".into(),
+                probe.clone(),
+                control_tx,
+                async move {
+                    // Answers the first check, then leaves the second one unanswered for a while.
+                    for delay in [Duration::ZERO, Duration::from_millis(200)] {
+                        match control_rx.recv().await {
+                            Ok(ControlMsg::HealthCheck { id }) => {
+                                tokio::time::sleep(delay).await;
+                                node_probe.answer(id);
+                            }
+                            other => panic!("Unexpected control message {other:?}"),
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                },
+            ));
+
+            let wait_for_state = |state: NodeState| {
+                let probe = probe.clone();
+                async move {
+                    timeout(Duration::from_secs(1), async {
+                        while probe.health().state != state {
+                            tokio::time::sleep(Duration::from_millis(5)).await;
+                        }
+                    })
+                    .await
+                    .unwrap_or_else(|_| panic!("Timed out waiting for {state:?}"));
+                }
+            };
+            wait_for_state(NodeState::Stalled).await;
+            let stalled_since = probe.health().last_seen;
+            wait_for_state(NodeState::Running).await;
+            assert!(probe.health().last_seen > stalled_since);
+
+            node.await.expect("Node task panicked");
+            assert_eq!(probe.health().state, NodeState::Stopped);
+        }));
+    }
+}
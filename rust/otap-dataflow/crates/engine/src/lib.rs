@@ -2,16 +2,40 @@
 
 //! Async Pipeline Engine
 
+pub mod ack;
+pub mod buffer_pool;
 pub mod error;
 pub mod exporter;
 pub mod message;
 pub mod processor;
+pub mod read_buffer;
 pub mod receiver;
 
+mod backpressure;
+mod budget;
 pub mod config;
+pub mod config_ack;
+mod connection;
 mod effect_handler;
+pub mod fanin;
+pub mod fanout;
+pub mod flush_ack;
+pub mod framing;
+pub mod graph;
+pub mod health;
+pub mod lifecycle;
 pub mod local;
+pub mod logging;
+pub mod outbound;
 pub mod pipeline;
+pub mod runtime;
 pub mod shared;
+mod shutdown;
+mod spans;
+pub mod task;
+pub mod telemetry;
+pub mod timer;
+pub mod tls;
+pub mod unix;
 
 pub mod testing;
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lifecycle of the nodes.
+//!
+//! The wrapper of a receiver publishes where the receiver is in its lifecycle to a watch channel
+//! returned by `ReceiverWrapper::state_watcher`, e.g. for an operator to wait for a receiver to be
+//! ready before routing traffic to it, or to follow a rolling configuration change:
+//!
+//! - [`LifecycleState::Starting`] until the receiver signals its readiness with the effect
+//!   handler's `notify_ready` (again after each restart of the receiver);
+//! - [`LifecycleState::Ready`] once it did;
+//! - [`LifecycleState::Draining`] once a `Shutdown` was delivered to the receiver;
+//! - [`LifecycleState::Stopped`] once `start` returned, with its result.
+//!
+//! Unlike the liveness of a node (see [`crate::health::NodeState`]), the lifecycle state isn't
+//! driven by the health checks, and only moves backward when the receiver is restarted.
+
+use crate::error::Error;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Where a node is in its lifecycle.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LifecycleState {
+    /// The node is starting and isn't ready to ingest data yet.
+    #[default]
+    Starting,
+    /// The node signaled it is ready to ingest data.
+    Ready,
+    /// The node was asked to shut down and is completing its in-flight work.
+    Draining,
+    /// The node completed, with the given result. The error is rendered as text, as it may carry
+    /// the pdata the node failed to send.
+    Stopped(Result<(), String>),
+}
+
+/// Publisher of the lifecycle state of a node, shared by all the clones of its effect handler.
+#[derive(Clone)]
+pub(crate) struct Lifecycle {
+    state: Arc<watch::Sender<LifecycleState>>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle {
+            state: Arc::new(watch::channel(LifecycleState::Starting).0),
+        }
+    }
+}
+
+impl Lifecycle {
+    /// Marks a starting node as ready. Ignored once the node is draining or stopped.
+    pub(crate) fn notify_ready(&self) {
+        // Unlike `send`, `send_if_modified` updates the state even if nobody is watching yet.
+        _ = self.state.send_if_modified(|state| {
+            let starting = *state == LifecycleState::Starting;
+            if starting {
+                *state = LifecycleState::Ready;
+            }
+            starting
+        });
+    }
+
+    /// Marks a ready node as starting again, e.g. when it is restarted after a failure.
+    pub(crate) fn restarting(&self) {
+        _ = self.state.send_if_modified(|state| {
+            let ready = *state == LifecycleState::Ready;
+            if ready {
+                *state = LifecycleState::Starting;
+            }
+            ready
+        });
+    }
+
+    /// Marks the node as draining, once a `Shutdown` was delivered to it.
+    pub(crate) fn draining(&self) {
+        _ = self.state.send_if_modified(|state| {
+            let running = matches!(state, LifecycleState::Starting | LifecycleState::Ready);
+            if running {
+                *state = LifecycleState::Draining;
+            }
+            running
+        });
+    }
+
+    /// Marks the node as stopped with the given result.
+    pub(crate) fn stopped<PData>(&self, result: &Result<(), Error<PData>>) {
+        let result = match result {
+            Ok(()) => Ok(()),
+            Err(error) => Err(error.to_string()),
+        };
+        _ = self.state.send_replace(LifecycleState::Stopped(result));
+    }
+
+    /// Returns a receiver of the lifecycle states of the node.
+    pub(crate) fn subscribe(&self) -> watch::Receiver<LifecycleState> {
+        self.state.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lifecycle, LifecycleState};
+    use crate::error::Error;
+
+    #[test]
+    fn test_lifecycle_transitions() {
+        let lifecycle = Lifecycle::default();
+        let watcher = lifecycle.subscribe();
+        assert_eq!(*watcher.borrow(), LifecycleState::Starting);
+
+        lifecycle.notify_ready();
+        assert_eq!(*watcher.borrow(), LifecycleState::Ready);
+        lifecycle.restarting();
+        assert_eq!(*watcher.borrow(), LifecycleState::Starting);
+        lifecycle.notify_ready();
+
+        lifecycle.draining();
+        assert_eq!(*watcher.borrow(), LifecycleState::Draining);
+        // A draining node doesn't become ready again.
+        lifecycle.notify_ready();
+        lifecycle.restarting();
+        assert_eq!(*watcher.borrow(), LifecycleState::Draining);
+
+        let error: Error<()> = Error::ReceiverError {
+            receiver: "receiver".into(),
+            error: "boom".to_owned(),
+        };
+        let message = error.to_string();
+        lifecycle.stopped(&Err(error));
+        assert_eq!(*watcher.borrow(), LifecycleState::Stopped(Err(message)));
+        lifecycle.draining();
+        assert!(matches!(*watcher.borrow(), LifecycleState::Stopped(Err(_))));
+    }
+}
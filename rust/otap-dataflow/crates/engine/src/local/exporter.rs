@@ -32,12 +32,25 @@
 //! To ensure scalability, the pipeline engine will start multiple instances of the same pipeline
 //! in parallel on different cores, each with its own exporter instance.
 
+use crate::config::RetryPolicy;
+use crate::config_ack::ConfigAckWatcher;
 use crate::effect_handler::EffectHandlerCore;
-use crate::error::Error;
-use crate::message::MessageChannel;
+use crate::error::{ConnectError, Error};
+use crate::flush_ack::FlushAckWatcher;
+use crate::logging::{LogLevel, LogSink};
+use crate::message::{ControlMsg, MessageChannel, Sender};
+use crate::outbound::OutboundConnection;
+use crate::task::{TaskHandle, TaskRegistry};
+use crate::telemetry::TelemetryCounters;
 use async_trait::async_trait;
+use otap_df_config::NodeKind;
 use std::borrow::Cow;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
 /// A trait for egress exporters (!Send definition).
 #[async_trait( ? Send)]
 pub trait Exporter<PData> {
@@ -87,6 +100,9 @@ pub trait Exporter<PData> {
 pub struct EffectHandler<PData> {
     core: EffectHandlerCore,
 
+    /// The control senders of the upstream nodes to which throttle signals are sent.
+    upstream_control_senders: Vec<Sender<ControlMsg>>,
+
     /// A 0 size type used to parameterize the `EffectHandler` with the type of message the exporter
     /// will consume.
     _pd: PhantomData<PData>,
@@ -98,7 +114,8 @@ impl<PData> EffectHandler<PData> {
     #[must_use]
     pub fn new(name: Cow<'static, str>) -> Self {
         EffectHandler {
-            core: EffectHandlerCore { node_name: name },
+            core: EffectHandlerCore::new(name, NodeKind::Exporter),
+            upstream_control_senders: Vec::new(),
             _pd: PhantomData,
         }
     }
@@ -109,5 +126,174 @@ impl<PData> EffectHandler<PData> {
         self.core.node_name()
     }
 
+    /// Logs an informational message tagged with the identity of the exporter (see
+    /// [`crate::logging`]).
+    pub fn info_message(&self, message: &str) {
+        self.core.log(LogLevel::Info, message);
+    }
+
+    /// Logs a warning tagged with the identity of the exporter (see [`crate::logging`]).
+    pub fn warn_message(&self, message: &str) {
+        self.core.log(LogLevel::Warn, message);
+    }
+
+    /// Logs an error tagged with the identity of the exporter (see [`crate::logging`]).
+    pub fn error_message(&self, message: &str) {
+        self.core.log(LogLevel::Error, message);
+    }
+
+    /// Emits the messages logged by the exporter to the given sink, tagged with the given pipeline
+    /// id.
+    pub(crate) fn set_log_sink(
+        &mut self,
+        sink: Arc<dyn LogSink>,
+        pipeline_id: Option<Cow<'static, str>>,
+    ) {
+        self.core.set_log_sink(sink, pipeline_id);
+    }
+
+    /// Confirms that the configuration update with the given version (see
+    /// [`ControlMsg::Config`]) has been applied by the exporter.
+    pub fn ack_config(&self, version: u64) {
+        self.core.ack_config(version);
+    }
+
+    /// Reports that the exporter failed to apply the configuration update with the given
+    /// version. The engine side observes it as an [`Error::ConfigRejected`].
+    pub fn reject_config(&self, version: u64, reason: impl Into<String>) {
+        self.core.reject_config(version, reason.into());
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the exporter.
+    pub(crate) fn config_acks(&self) -> ConfigAckWatcher {
+        self.core.config_acks()
+    }
+
+    /// Confirms that the exporter emitted the pdata it was holding in response to the flush with
+    /// the given id (see [`ControlMsg::Flush`]).
+    pub fn ack_flush(&self, id: u64) {
+        self.core.ack_flush(id);
+    }
+
+    /// Returns a watcher of the flushes acknowledged by the exporter.
+    pub(crate) fn flush_acks(&self) -> FlushAckWatcher {
+        self.core.flush_acks()
+    }
+
+    /// Returns the internal counters of the exporter (see [`crate::telemetry`]).
+    pub(crate) fn telemetry(&self) -> TelemetryCounters {
+        self.core.telemetry.clone()
+    }
+
+    /// Connects the control channel of an upstream node (typically a receiver) to which the
+    /// throttle signals of this exporter are sent.
+    pub(crate) fn connect_upstream_control(&mut self, control_sender: Sender<ControlMsg>) {
+        self.upstream_control_senders.push(control_sender);
+    }
+
+    /// Requests the upstream nodes to pause ingestion for the given duration by sending them a
+    /// [`ControlMsg::Throttle`]. Exporters are expected to call this method when they can't keep up
+    /// with the incoming pdata, instead of dropping it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of an upstream node is
+    /// closed.
+    pub async fn throttle_upstream(&self, duration: Duration) -> Result<(), Error<PData>> {
+        self.send_upstream(ControlMsg::Throttle { duration }).await
+    }
+
+    /// Notifies the upstream nodes that the pdata with the given id has been delivered, by sending
+    /// them a [`ControlMsg::Ack`]. The upstream nodes ignore the ids they didn't emit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of an upstream node is
+    /// closed.
+    pub async fn notify_ack(&self, id: u64) -> Result<(), Error<PData>> {
+        self.send_upstream(ControlMsg::Ack { id }).await
+    }
+
+    /// Notifies the upstream nodes that the pdata with the given id couldn't be delivered, by
+    /// sending them a [`ControlMsg::Nack`] carrying the reason, so that they can retry it or report
+    /// the failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of an upstream node is
+    /// closed.
+    pub async fn notify_nack(&self, id: u64, reason: &str) -> Result<(), Error<PData>> {
+        self.send_upstream(ControlMsg::Nack {
+            id,
+            reason: reason.to_owned(),
+        })
+        .await
+    }
+
+    /// Sends a control message to every upstream node.
+    async fn send_upstream(&self, msg: ControlMsg) -> Result<(), Error<PData>> {
+        for control_sender in &self.upstream_control_senders {
+            control_sender
+                .send(msg.clone())
+                .await
+                .map_err(|_| Error::ControlChannelClosed {
+                    node: self.exporter_name(),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Creates a non-blocking TCP listener on the given address with socket options defined by the
+    /// pipeline engine implementation, e.g. for exporters serving a pull-based endpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if any step in the process fails.
+    pub fn tcp_listener(&self, addr: SocketAddr) -> Result<TcpListener, Error<PData>> {
+        self.core.tcp_listener(addr, self.exporter_name())
+    }
+
+    /// Connects to the given endpoint, retrying with an exponential backoff and a random jitter
+    /// according to the given policy. The returned connection reconnects to the endpoint when it
+    /// is lost (see [`crate::outbound`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConnectError::Unreachable`] if the endpoint can't be reached within the retry
+    /// policy.
+    pub async fn connect(
+        &self,
+        addr: SocketAddr,
+        policy: RetryPolicy,
+    ) -> Result<OutboundConnection, ConnectError> {
+        OutboundConnection::connect(self.core.clone(), addr, policy).await
+    }
+
+    /// Spawns a named subtask of the exporter on the current `LocalSet`.
+    ///
+    /// The subtask runs within a tracing span carrying the names of the exporter and of the
+    /// subtask. It is tracked by the effect handler: the exporter can await the returned handle
+    /// when it shuts down, and the subtasks still running once the exporter completed are aborted.
+    pub fn spawn_local_named<F>(&self, name: &str, task: F) -> TaskHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.core
+            .tasks
+            .spawn_local(&self.core.node_name, Cow::Owned(name.to_owned()), task)
+    }
+
+    /// Returns the names of the subtasks spawned via `spawn_local_named` that are still running.
+    #[must_use]
+    pub fn running_tasks(&self) -> Vec<Cow<'static, str>> {
+        self.core.tasks.running_tasks()
+    }
+
+    /// Returns the registry of the subtasks spawned by the exporter.
+    pub(crate) fn tasks(&self) -> TaskRegistry {
+        self.core.tasks.clone()
+    }
+
     // More methods will be added in the future as needed.
 }
@@ -31,14 +31,47 @@
 //! To ensure scalability, the pipeline engine will start multiple instances of the same pipeline in
 //! parallel on different cores, each with its own receiver instance.
 
-use crate::effect_handler::EffectHandlerCore;
-use crate::error::Error;
-use crate::message::{ControlMsg, Sender};
+use crate::budget::InflightBudget;
+use crate::buffer_pool::{PoolMember, PoolPermit};
+use crate::config::OverflowPolicy;
+use crate::config_ack::ConfigAckWatcher;
+use crate::effect_handler::{EffectHandlerCore, ShutdownReporter};
+use crate::error::{Error, TypedRecvError};
+use crate::flush_ack::FlushAckWatcher;
+use crate::health::{HealthProbe, HealthStatus};
+use crate::lifecycle::Lifecycle;
+use crate::logging::{LogLevel, LogSink};
+use crate::message::{
+    BudgetedReceiver, ControlMsg, PrioritySender, Receiver as PdataReceiver, Sender,
+    TypedControlMsg,
+};
+use crate::read_buffer::ReadBuffer;
+use crate::spans;
+use crate::task::{TaskHandle, TaskRegistry};
+use crate::telemetry::{MetricsSink, ReceiverMetrics, ShutdownReport, TelemetryCounters};
+#[cfg(any(test, feature = "testing"))]
+use crate::testing::fault::{FaultInjector, InjectedFault};
+use crate::timer::{NodeTimers, TimerId};
+use crate::tls::{TlsConfig, TlsListener};
+use crate::unix::UnixListener;
 use async_trait::async_trait;
-use otap_df_channel::error::RecvError;
+use otap_df_channel::error::{RecvError, SendError};
+use otap_df_config::NodeKind;
+use serde::de::DeserializeOwned;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{OwnedSemaphorePermit, watch};
+use tokio::time::Instant;
+use tracing::Instrument;
+use tracing::span::Id;
 
 /// A trait for ingress receivers (!Send definition).
 ///
@@ -65,6 +98,11 @@ pub trait Receiver<PData> {
     /// Important note: Receivers are expected to process internal control messages in priority over
     /// external data.
     ///
+    /// Receivers observing a `Throttle` control message are expected to pause the ingestion of
+    /// external data for the requested duration before resuming.
+    /// Similarly, receivers should not ingest external data while paused by a `Pause`
+    /// control message (see `EffectHandler::is_paused`).
+    ///
     /// # Parameters
     ///
     /// - `ctrl_chan`: A channel to receive control messages.
@@ -92,32 +130,204 @@ pub trait Receiver<PData> {
 /// values used to control the behavior of a receiver at runtime.
 pub struct ControlChannel {
     rx: crate::message::Receiver<ControlMsg>,
+    /// The ingest state of the receiver, updated on `Pause` and `Resume`.
+    ingest_paused: Arc<AtomicBool>,
+    /// The probe answering the `HealthCheck` messages, if tracked.
+    health: Option<HealthProbe>,
 }
 
 impl ControlChannel {
     /// Creates a new `ControlChannelLocal` with the given receiver.
     #[must_use]
     pub fn new(rx: crate::message::Receiver<ControlMsg>) -> Self {
-        Self { rx }
+        Self {
+            rx,
+            ingest_paused: Arc::new(AtomicBool::new(false)),
+            health: None,
+        }
+    }
+
+    /// Makes this channel maintain the ingest state of a receiver (see the effect handler's
+    /// `is_paused`).
+    pub(crate) fn track_ingest_state(mut self, ingest_paused: Arc<AtomicBool>) -> Self {
+        self.ingest_paused = ingest_paused;
+        self
+    }
+
+    /// Makes this channel answer the health checks of the receiver (see [`crate::health`]).
+    pub(crate) fn track_health(mut self, health: HealthProbe) -> Self {
+        self.health = Some(health);
+        self
     }
 
-    /// Asynchronously receives the next control message.
+    /// Returns true while the receiver is paused, i.e. between a `Pause` and a `Resume` control
+    /// message received from this channel. A paused receiver should not send pdata via its effect
+    /// handler, but must keep receiving control messages in order to be resumed.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.ingest_paused.load(Ordering::Relaxed)
+    }
+
+    /// Asynchronously receives the next control message. The health checks are answered on
+    /// reception and are not returned.
     ///
     /// # Errors
     ///
     /// Returns a [`RecvError`] if the channel is closed.
     pub async fn recv(&mut self) -> Result<ControlMsg, RecvError> {
-        self.rx.recv().await
+        loop {
+            let msg = self.rx.recv().await?;
+            if let Some(msg) = self.observe(msg) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Receives the next control message if one is pending, without waiting. Returns `None` once
+    /// all the pending control messages have been received, e.g. to drain them before handling
+    /// data. The health checks are answered on reception and are not returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecvError`] if the channel is closed.
+    pub fn try_recv(&mut self) -> Result<Option<ControlMsg>, RecvError> {
+        loop {
+            let msg = match self.rx.try_recv() {
+                Ok(msg) => msg,
+                Err(RecvError::Empty) => return Ok(None),
+                Err(error) => return Err(error),
+            };
+            if let Some(msg) = self.observe(msg) {
+                return Ok(Some(msg));
+            }
+        }
+    }
+
+    /// Receives the next control message, waiting at most for the given timeout. Returns `None`
+    /// if no control message was received in time. The health checks are answered on reception
+    /// and are not returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecvError`] if the channel is closed.
+    pub async fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<ControlMsg>, RecvError> {
+        match tokio::time::timeout(timeout, self.recv()).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Waits for the receiver to be resumed, i.e. receives and discards the control messages
+    /// until a `Resume`. Returns immediately if the receiver is not paused.
+    ///
+    /// A `Shutdown` received while paused is returned instead, so that a paused receiver can
+    /// still terminate.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecvError`] if the channel is closed.
+    pub async fn wait_for_resume(&mut self) -> Result<Option<ControlMsg>, RecvError> {
+        while self.is_paused() {
+            let msg = self.recv().await?;
+            if msg.is_shutdown() {
+                return Ok(Some(msg));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the raw receiver of the channel, its ingest state and its health probe, e.g. to
+    /// forward the control messages to a shared receiver (see
+    /// [`crate::receiver::ReceiverWrapper::local_from_shared`]).
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        crate::message::Receiver<ControlMsg>,
+        Arc<AtomicBool>,
+        Option<HealthProbe>,
+    ) {
+        (self.rx, self.ingest_paused, self.health)
+    }
+
+    /// Updates the state of the channel according to a received control message. Returns the
+    /// message unless it is handled by the channel itself (i.e. a health check).
+    fn observe(&self, msg: ControlMsg) -> Option<ControlMsg> {
+        match msg {
+            ControlMsg::Pause => self.ingest_paused.store(true, Ordering::Relaxed),
+            ControlMsg::Resume => self.ingest_paused.store(false, Ordering::Relaxed),
+            ControlMsg::HealthCheck { id } => {
+                if let Some(health) = &self.health {
+                    health.answer(id);
+                }
+                return None;
+            }
+            _ => {}
+        }
+        Some(msg)
+    }
+
+    /// Asynchronously receives the next control message, decoding the payload of a `Reconfigure`
+    /// message into the configuration type `C` of the receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TypedRecvError`] if the channel is closed or if the configuration update does
+    /// not match the configuration type `C`.
+    pub async fn recv_typed<C>(&mut self) -> Result<TypedControlMsg<C>, TypedRecvError>
+    where
+        C: DeserializeOwned + Send,
+    {
+        let msg = self
+            .recv()
+            .await
+            .map_err(|_| TypedRecvError::ChannelClosed)?;
+        Ok(msg.try_into()?)
     }
 }
 
 /// A `!Send` implementation of the EffectHandler.
-#[derive(Clone)]
 pub struct EffectHandler<PData> {
     core: EffectHandlerCore,
 
     /// A sender used to forward messages from the receiver.
     msg_sender: Sender<PData>,
+
+    /// The senders of the named output ports of the receiver (see `send_message_to`).
+    out_ports: Rc<HashMap<String, Sender<PData>>>,
+
+    /// Set while the ingestion is paused (see `is_paused`).
+    ingest_paused: Arc<AtomicBool>,
+
+    /// What `send_message` does when the output channel is full.
+    overflow_policy: OverflowPolicy,
+
+    /// Returns the size in bytes of a pdata message, for the `bytes_sent` metric.
+    pdata_size: Option<fn(&PData) -> usize>,
+
+    /// The budget of the bytes in flight in the output channel (see [`crate::budget`]).
+    inflight_budget: Option<Arc<InflightBudget>>,
+
+    /// Returns the span carried by a pdata message, for the `send_message` span.
+    pdata_trace_context: Option<fn(&PData) -> Option<Id>>,
+}
+
+// Not derived, the handler is cloneable whatever the pdata type.
+impl<PData> Clone for EffectHandler<PData> {
+    fn clone(&self) -> Self {
+        EffectHandler {
+            core: self.core.clone(),
+            msg_sender: self.msg_sender.clone(),
+            out_ports: self.out_ports.clone(),
+            ingest_paused: self.ingest_paused.clone(),
+            overflow_policy: self.overflow_policy,
+            pdata_size: self.pdata_size,
+            inflight_budget: self.inflight_budget.clone(),
+            pdata_trace_context: self.pdata_trace_context,
+        }
+    }
 }
 
 /// Implementation for the `!Send` effect handler.
@@ -126,10 +336,14 @@ impl<PData> EffectHandler<PData> {
     #[must_use]
     pub fn new(receiver_name: Cow<'static, str>, msg_sender: Sender<PData>) -> Self {
         EffectHandler {
-            core: EffectHandlerCore {
-                node_name: receiver_name,
-            },
+            core: EffectHandlerCore::new(receiver_name, NodeKind::Receiver),
             msg_sender,
+            out_ports: Rc::default(),
+            ingest_paused: Arc::new(AtomicBool::new(false)),
+            overflow_policy: OverflowPolicy::Block,
+            pdata_size: None,
+            inflight_budget: None,
+            pdata_trace_context: None,
         }
     }
 
@@ -139,16 +353,614 @@ impl<PData> EffectHandler<PData> {
         self.core.node_name()
     }
 
-    /// Sends a message to the next node(s) in the pipeline.
+    /// Logs an informational message tagged with the identity of the receiver (see
+    /// [`crate::logging`]).
+    pub fn info_message(&self, message: &str) {
+        self.core.log(LogLevel::Info, message);
+    }
+
+    /// Logs a warning tagged with the identity of the receiver (see [`crate::logging`]).
+    pub fn warn_message(&self, message: &str) {
+        self.core.log(LogLevel::Warn, message);
+    }
+
+    /// Logs an error tagged with the identity of the receiver (see [`crate::logging`]).
+    pub fn error_message(&self, message: &str) {
+        self.core.log(LogLevel::Error, message);
+    }
+
+    /// Emits the messages logged by the receiver to the given sink, tagged with the given pipeline
+    /// id.
+    pub(crate) fn set_log_sink(
+        &mut self,
+        sink: Arc<dyn LogSink>,
+        pipeline_id: Option<Cow<'static, str>>,
+    ) {
+        self.core.set_log_sink(sink, pipeline_id);
+    }
+
+    /// Confirms that the configuration update with the given version (see
+    /// [`ControlMsg::Config`]) has been applied by the receiver.
+    pub fn ack_config(&self, version: u64) {
+        self.core.ack_config(version);
+    }
+
+    /// Reports that the receiver failed to apply the configuration update with the given
+    /// version. The engine side observes it as an [`Error::ConfigRejected`].
+    pub fn reject_config(&self, version: u64, reason: impl Into<String>) {
+        self.core.reject_config(version, reason.into());
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the receiver.
+    pub(crate) fn config_acks(&self) -> ConfigAckWatcher {
+        self.core.config_acks()
+    }
+
+    /// Confirms that the receiver emitted the pdata it was holding in response to the flush with
+    /// the given id (see [`ControlMsg::Flush`]).
+    pub fn ack_flush(&self, id: u64) {
+        self.core.ack_flush(id);
+    }
+
+    /// Asks the receiver to shut down, e.g. once it ingested all of its input: a `Shutdown` with
+    /// the given deadline and reason is queued to its own control channel, and handled like one
+    /// sent by the pipeline (the receiver is marked as draining and must complete within the
+    /// deadline). The receiver must keep receiving its control messages to observe it.
     ///
     /// # Errors
     ///
-    /// Returns an [`Error::ChannelSendError`] if the message could not be sent.
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of the receiver is
+    /// closed, or if the handler doesn't belong to a receiver wrapper.
+    pub async fn request_shutdown(
+        &self,
+        deadline: Duration,
+        reason: impl Into<String>,
+    ) -> Result<(), Error<PData>> {
+        self.core.request_shutdown(deadline, reason.into()).await
+    }
+
+    /// Sets the sending end of the control channel of the receiver, used by
+    /// [`EffectHandler::request_shutdown`].
+    pub(crate) fn set_control_sender(&mut self, control_sender: PrioritySender<ControlMsg>) {
+        self.core.control_sender = Some(control_sender);
+    }
+
+    /// Returns a watcher of the flushes acknowledged by the receiver.
+    pub(crate) fn flush_acks(&self) -> FlushAckWatcher {
+        self.core.flush_acks()
+    }
+
+    /// Reports the health of the receiver (e.g. `Degraded` while its upstream source is
+    /// unreachable). The last reported status is observed through the `subscribe_health` method
+    /// of the receiver wrapper.
+    pub fn report_health(&self, status: HealthStatus) {
+        self.core.report_health(status);
+    }
+
+    /// Returns a receiver of the health statuses reported by the receiver.
+    pub(crate) fn subscribe_health(&self) -> watch::Receiver<HealthStatus> {
+        self.core.subscribe_health()
+    }
+
+    /// Signals that the receiver is ready to ingest data, e.g. once its listeners are bound. The
+    /// lifecycle state of the receiver moves from `Starting` to `Ready` (see [`crate::lifecycle`]).
+    pub fn notify_ready(&self) {
+        self.core.lifecycle.notify_ready();
+    }
+
+    /// Returns the publisher of the lifecycle state of the receiver.
+    pub(crate) fn lifecycle(&self) -> Lifecycle {
+        self.core.lifecycle.clone()
+    }
+
+    /// Returns the internal counters of the receiver (see [`crate::telemetry`]).
+    pub(crate) fn telemetry(&self) -> TelemetryCounters {
+        self.core.telemetry.clone()
+    }
+
+    /// Counts and logs the given restart attempt of the receiver after it failed with the given
+    /// error.
+    pub(crate) fn report_restart(&self, attempt: u32, error: &Error<PData>) {
+        self.core.report_restart(attempt, error);
+    }
+
+    /// Returns a new id that the receiver can use to tag the next message it emits.
+    ///
+    /// Downstream nodes can acknowledge (or reject) the tagged message with this id, the
+    /// corresponding Ack (or Nack) control message is then routed back to this receiver (see
+    /// [`crate::ack::AckRouter`]).
+    #[must_use]
+    pub fn next_message_id(&self) -> u64 {
+        self.core.next_message_id()
+    }
+
+    /// Sets the Ack/Nack route used to tag the ids returned by `next_message_id`.
+    pub(crate) fn set_ack_route(&mut self, route: u16) {
+        self.core.set_ack_route(route);
+    }
+
+    /// Limits the number of connections served concurrently by `serve_connections`.
+    pub(crate) fn set_max_concurrent_connections(&mut self, max_connections: Option<usize>) {
+        self.core.set_max_concurrent_connections(max_connections);
+    }
+
+    /// Sets the size of the read buffers handed out by `read_buffer`.
+    pub(crate) fn set_read_buffer_size(&mut self, size: usize) {
+        self.core.set_read_buffer_size(size);
+    }
+
+    /// Pushes the send counters of the receiver to the given sink.
+    pub(crate) fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.core.set_metrics_sink(sink);
+    }
+
+    /// Sets what `send_message` does when the output channel is full.
+    pub(crate) fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    /// Installs the faults injected in `send_message` by the tests (see
+    /// [`crate::testing::fault`]).
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn inject_faults(&mut self, faults: FaultInjector) {
+        self.core.faults = Some(faults);
+    }
+
+    /// Adds the senders of named output ports to the receiver.
+    pub(crate) fn add_out_ports(&mut self, out_ports: HashMap<String, Sender<PData>>) {
+        let mut ports = (*self.out_ports).clone();
+        ports.extend(out_ports);
+        self.out_ports = Rc::new(ports);
+    }
+
+    /// Sets the function returning the size in bytes of a pdata message, for the `bytes_sent`
+    /// metric.
+    pub(crate) fn set_pdata_size(&mut self, pdata_size: fn(&PData) -> usize) {
+        self.pdata_size = Some(pdata_size);
+    }
+
+    /// Returns the size in bytes of a pdata message, or 0 without a pdata size function.
+    fn size_of(&self, data: &PData) -> usize {
+        self.pdata_size.map_or(0, |pdata_size| pdata_size(data))
+    }
+
+    /// Sets the function returning the span carried by a pdata message, which the `send_message`
+    /// span follows from (see [`crate::spans`]).
+    pub(crate) fn set_pdata_trace_context(&mut self, trace_context: fn(&PData) -> Option<Id>) {
+        self.pdata_trace_context = Some(trace_context);
+    }
+
+    /// Limits the number of bytes in flight in the output channel (see [`crate::budget`]).
+    pub(crate) fn set_max_inflight_bytes(&mut self, max_bytes: Option<usize>) {
+        self.inflight_budget = max_bytes.map(|max_bytes| Arc::new(InflightBudget::new(max_bytes)));
+    }
+
+    /// Wraps the receiving side of the output channel to refund the in-flight byte budget.
+    pub(crate) fn budgeted_receiver(
+        &self,
+        receiver: PdataReceiver<PData>,
+    ) -> BudgetedReceiver<PData> {
+        BudgetedReceiver::new(receiver, self.inflight_budget.clone(), self.pdata_size)
+    }
+
+    /// Charges the size of a message to the in-flight byte budget and takes a slot of the buffer
+    /// pool of the receiver (see [`crate::buffer_pool`]), waiting while either is exhausted.
+    async fn acquire_budget(&self, bytes: usize) -> PoolPermit<'_> {
+        if let Some(budget) = &self.inflight_budget {
+            budget.acquire(bytes).await;
+        }
+        match &self.core.buffer_pool {
+            Some(pool) => pool.acquire(|| self.msg_sender.len()).await,
+            None => PoolPermit::unpooled(),
+        }
+    }
+
+    /// Takes a slot of the buffer pool of the receiver without waiting, or returns `None` if the
+    /// pool is exhausted.
+    fn try_acquire_pool_slot(&self) -> Option<PoolPermit<'_>> {
+        match &self.core.buffer_pool {
+            Some(pool) => pool.try_acquire(self.msg_sender.len()),
+            None => Some(PoolPermit::unpooled()),
+        }
+    }
+
+    /// Refunds the size of a message that didn't make it to the output channel.
+    fn release_budget(&self, bytes: usize) {
+        if let Some(budget) = &self.inflight_budget {
+            budget.release(bytes);
+        }
+    }
+
+    /// Sends a message to the output channel without waiting, reporting it as full when the
+    /// in-flight byte budget or the buffer pool is exhausted.
+    fn try_send_budgeted(&self, data: PData, bytes: usize) -> Result<(), SendError<PData>> {
+        let Some(permit) = self.try_acquire_pool_slot() else {
+            return Err(SendError::Full(data));
+        };
+        if let Some(budget) = &self.inflight_budget {
+            if !budget.try_acquire(bytes) {
+                return Err(SendError::Full(data));
+            }
+        }
+        let result = self.msg_sender.try_send(data);
+        permit.complete(self.msg_sender.len());
+        if result.is_err() {
+            self.release_budget(bytes);
+        }
+        result
+    }
+
+    /// Returns the send metrics of the receiver, i.e. the number of messages and bytes sent, and
+    /// the number of send errors.
+    #[must_use]
+    pub fn metrics(&self) -> ReceiverMetrics {
+        self.core.telemetry.receiver_metrics()
+    }
+
+    /// Returns the number of pdata messages dropped so far because the output channel was full
+    /// (see [`OverflowPolicy`]).
+    #[must_use]
+    pub fn dropped_messages(&self) -> u64 {
+        self.core.telemetry.dropped()
+    }
+
+    /// Returns true while the ingestion is paused, i.e. between a `Pause` and a
+    /// `Resume` control message. Receivers are expected to consult this flag (it's cheap)
+    /// before ingesting external data, e.g. to stop accepting new connections.
+    ///
+    /// Note: The flag is updated when the control messages are received from the control channel.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.ingest_paused.load(Ordering::Relaxed)
+    }
+
+    /// Returns the ingest state shared with the control channel of the receiver.
+    pub(crate) fn ingest_state(&self) -> Arc<AtomicBool> {
+        self.ingest_paused.clone()
+    }
+
+    /// Returns true if the output channel of the receiver is `Send` (see
+    /// [`crate::receiver::ReceiverWrapper::local_from_shared`]).
+    pub(crate) fn has_shared_output(&self) -> bool {
+        matches!(self.msg_sender, Sender::Shared(_))
+    }
+
+    /// Converts this handler into a `Send` handler sharing the same state (telemetry, tasks,
+    /// timers, in-flight byte budget, ...) and the same output channels, so that a shared
+    /// receiver can run in a local context. Returns `None` if one of the output channels isn't
+    /// `Send`.
+    pub(crate) fn into_shared(self) -> Option<crate::shared::receiver::EffectHandler<PData>> {
+        let Sender::Shared(msg_sender) = self.msg_sender else {
+            return None;
+        };
+        let out_ports = self
+            .out_ports
+            .iter()
+            .map(|(name, sender)| match sender {
+                Sender::Shared(sender) => Some((name.clone(), sender.clone())),
+                _ => None,
+            })
+            .collect::<Option<HashMap<_, _>>>()?;
+        Some(crate::shared::receiver::EffectHandler::from_parts(
+            self.core,
+            msg_sender,
+            out_ports,
+            self.ingest_paused,
+            self.overflow_policy,
+            self.pdata_size,
+            self.inflight_budget,
+            self.pdata_trace_context,
+        ))
+    }
+
+    /// Returns the number of pdata messages buffered in the output channel of the receiver, and
+    /// the capacity of the channel.
+    pub(crate) fn output_channel_len(&self) -> (usize, usize) {
+        (self.msg_sender.len(), self.msg_sender.capacity())
+    }
+
+    /// Sets the name of the buffer pool the output channel borrows from (see
+    /// [`crate::buffer_pool`]).
+    pub(crate) fn set_buffer_pool_name(&mut self, pool: Option<String>) {
+        self.core.buffer_pool_name = pool;
+    }
+
+    /// Returns the name of the buffer pool the output channel borrows from, if any.
+    pub(crate) fn buffer_pool_name(&self) -> Option<&str> {
+        self.core.buffer_pool_name.as_deref()
+    }
+
+    /// Makes the output channel borrow from a buffer pool, as the given member of the pool.
+    pub(crate) fn set_buffer_pool(&mut self, member: PoolMember) {
+        self.core.buffer_pool = Some(member);
+    }
+
+    /// Returns the membership of the receiver in its buffer pool, if it joined one.
+    pub(crate) fn buffer_pool(&self) -> Option<PoolMember> {
+        self.core.buffer_pool.clone()
+    }
+
+    /// Returns a function returning the number of pdata messages buffered in the output channel
+    /// of the receiver.
+    pub(crate) fn buffered_pdata_probe(&self) -> impl Fn() -> usize + use<PData> {
+        let sender = self.msg_sender.clone();
+        move || sender.len()
+    }
+
+    /// Sends a message to the next node(s) in the pipeline. When the output channel is full, or
+    /// when the in-flight byte budget of the receiver is exhausted (see [`crate::budget`]), or its
+    /// buffer pool (see [`crate::buffer_pool`]), the message is sent according to the overflow
+    /// policy of the receiver (see [`OverflowPolicy`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::PdataChannelClosed`] if the output channel is closed.
     pub async fn send_message(&self, data: PData) -> Result<(), Error<PData>> {
-        self.msg_sender.send(data).await?;
+        let span = spans::send_message_span(&self.core.node_name, || {
+            self.pdata_trace_context
+                .and_then(|trace_context| trace_context(&data))
+        });
+        self.send_to_output(data).instrument(span).await
+    }
+
+    /// Sends a message to the output channel according to the overflow policy of the receiver,
+    /// see `send_message`.
+    async fn send_to_output(&self, data: PData) -> Result<(), Error<PData>> {
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(fault) = self.core.injected_fault() {
+            return self.inject_fault(fault, data);
+        }
+        let telemetry = &self.core.telemetry;
+        let bytes = self.size_of(&data);
+        match self.overflow_policy {
+            OverflowPolicy::Block => {
+                let permit = self.acquire_budget(bytes).await;
+                let sent = self.msg_sender.send(data).await;
+                permit.complete(self.msg_sender.len());
+                if sent.is_err() {
+                    self.release_budget(bytes);
+                }
+                telemetry
+                    .record_send_sized(sent, bytes)
+                    .map_err(|error| self.pdata_send_error(error))?;
+            }
+            OverflowPolicy::DropOldest => self.send_lossy(data)?,
+            OverflowPolicy::DropNewest => match self.try_send_budgeted(data, bytes) {
+                Err(SendError::Full(_)) => telemetry.record_dropped(),
+                result => telemetry
+                    .record_send_sized(result, bytes)
+                    .map_err(|error| self.pdata_send_error(error))?,
+            },
+        }
+        Ok(())
+    }
+
+    /// Sends a message to the next node(s) in the pipeline like `send_message`, but gives up once
+    /// the timeout elapsed while waiting for some capacity in the output channel (or in the
+    /// in-flight byte budget of the receiver). This bounds the latency of a blocking receiver when
+    /// the downstream is stuck, the lossy overflow policies never wait anyway.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::SendTimeout`] carrying the message back if the timeout elapsed, or an
+    /// [`Error::PdataChannelClosed`] if the output channel is closed.
+    pub async fn send_message_timeout(
+        &self,
+        data: PData,
+        timeout: Duration,
+    ) -> Result<(), Error<PData>> {
+        if self.overflow_policy != OverflowPolicy::Block {
+            return self.send_message(data).await;
+        }
+        let span = spans::send_message_span(&self.core.node_name, || {
+            self.pdata_trace_context
+                .and_then(|trace_context| trace_context(&data))
+        });
+        self.send_to_output_timeout(data, timeout)
+            .instrument(span)
+            .await
+    }
+
+    /// Sends a message to the output channel of a blocking receiver, see `send_message_timeout`.
+    async fn send_to_output_timeout(
+        &self,
+        data: PData,
+        timeout: Duration,
+    ) -> Result<(), Error<PData>> {
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(fault) = self.core.injected_fault() {
+            return self.inject_fault(fault, data);
+        }
+        let bytes = self.size_of(&data);
+        let deadline = Instant::now() + timeout;
+        let sent =
+            if let Ok(permit) = tokio::time::timeout(timeout, self.acquire_budget(bytes)).await {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let sent = self.msg_sender.send_timeout(data, remaining).await;
+                permit.complete(self.msg_sender.len());
+                if sent.is_err() {
+                    self.release_budget(bytes);
+                }
+                sent
+            } else {
+                Err(SendError::Full(data))
+            };
+        self.core
+            .telemetry
+            .record_send_sized(sent, bytes)
+            .map_err(|error| {
+                Error::from_pdata_send_timeout_error(
+                    self.receiver_name(),
+                    self.msg_sender.capacity(),
+                    timeout,
+                    error,
+                )
+            })
+    }
+
+    /// Sends a message to the node(s) connected to the given output port of the receiver (see
+    /// the `with_outputs` method of the receiver wrapper), waiting for some capacity when the
+    /// channel of the port is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::UnknownOutPort`] if the receiver has no output port with this name, or
+    /// an [`Error::PdataChannelClosed`] if the channel of the port is closed.
+    pub async fn send_message_to(&self, port: &str, data: PData) -> Result<(), Error<PData>> {
+        let sender = self.out_port(port)?;
+        let bytes = self.size_of(&data);
+        self.core
+            .telemetry
+            .record_send_sized(sender.send(data).await, bytes)
+            .map_err(|error| {
+                Error::from_pdata_send_error(self.receiver_name(), sender.capacity(), error)
+            })
+    }
+
+    /// Returns the sender of the given output port.
+    fn out_port(&self, port: &str) -> Result<&Sender<PData>, Error<PData>> {
+        self.out_ports
+            .get(port)
+            .ok_or_else(|| Error::UnknownOutPort {
+                node: self.receiver_name(),
+                port: port.to_owned(),
+            })
+    }
+
+    /// Sends a batch of messages to the next node(s) in the pipeline, in order. Unlike calling
+    /// `send_message` in a loop, the messages are pushed to the output channel without waiting
+    /// as long as the channel has some capacity, the receiver only waits when the channel is full
+    /// (according to the overflow policy of the receiver, see [`OverflowPolicy`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::PdataBatchInterrupted`] reporting the number of messages accepted by
+    /// the channel if it closed before the whole batch was sent, the remaining messages are
+    /// dropped.
+    pub async fn send_message_batch(
+        &self,
+        batch: impl IntoIterator<Item = PData>,
+    ) -> Result<(), Error<PData>> {
+        let mut accepted = 0;
+        if self.overflow_policy != OverflowPolicy::Block {
+            // The lossy overflow policies never wait.
+            for data in batch {
+                self.send_message(data)
+                    .await
+                    .map_err(|_| self.batch_interrupted(accepted))?;
+                accepted += 1;
+            }
+            return Ok(());
+        }
+        let mut result = Ok(());
+        let mut bytes = 0;
+        for data in batch {
+            let size = self.size_of(&data);
+            let permit = self.acquire_budget(size).await;
+            result = match self.msg_sender.try_send(data) {
+                Err(SendError::Full(data)) => self.msg_sender.send(data).await,
+                result => result,
+            };
+            permit.complete(self.msg_sender.len());
+            if result.is_err() {
+                self.release_budget(size);
+                self.core.telemetry.record_error();
+                break;
+            }
+            accepted += 1;
+            bytes += size;
+        }
+        self.core.telemetry.record_sent_batch(accepted);
+        self.core.telemetry.record_bytes(bytes);
+        result.map_err(|_| self.batch_interrupted(accepted))
+    }
+
+    /// Returns the error reported when the output channel closed in the middle of a batch.
+    fn batch_interrupted(&self, accepted: usize) -> Error<PData> {
+        Error::PdataBatchInterrupted {
+            node: self.receiver_name(),
+            accepted,
+        }
+    }
+
+    /// Sends a message to the next node(s) in the pipeline without waiting, dropping the oldest
+    /// message buffered in the output channel if it is full. The message being sent is dropped
+    /// instead when the in-flight byte budget or the buffer pool of the receiver is exhausted. The
+    /// dropped messages are counted in `dropped_messages`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::PdataChannelClosed`] if the output channel is closed.
+    pub fn send_lossy(&self, data: PData) -> Result<(), Error<PData>> {
+        let telemetry = &self.core.telemetry;
+        let bytes = self.size_of(&data);
+        let Some(permit) = self.try_acquire_pool_slot() else {
+            telemetry.record_dropped();
+            return Ok(());
+        };
+        if let Some(budget) = &self.inflight_budget {
+            if !budget.try_acquire(bytes) {
+                telemetry.record_dropped();
+                return Ok(());
+            }
+        }
+        let sent = self.msg_sender.force_send(data);
+        permit.complete(self.msg_sender.len());
+        if sent.is_err() {
+            self.release_budget(bytes);
+        }
+        if let Some(evicted) = telemetry
+            .record_send_sized(sent, bytes)
+            .map_err(|error| self.pdata_send_error(error))?
+        {
+            self.release_budget(self.size_of(&evicted));
+            telemetry.record_dropped();
+        }
         Ok(())
     }
 
+    /// Converts the error returned when sending a pdata message to the output channel.
+    fn pdata_send_error(&self, error: SendError<PData>) -> Error<PData> {
+        Error::from_pdata_send_error(self.receiver_name(), self.msg_sender.capacity(), error)
+    }
+
+    /// Fails the sending of a message according to an injected fault (see
+    /// [`crate::testing::fault`]). A full channel is handled according to the overflow policy of
+    /// the receiver, except that a blocking receiver gets an [`Error::PdataChannelFull`] instead of
+    /// waiting forever.
+    #[cfg(any(test, feature = "testing"))]
+    fn inject_fault(&self, fault: InjectedFault, data: PData) -> Result<(), Error<PData>> {
+        let error = match fault {
+            InjectedFault::SendFailure => SendError::Closed(data),
+            InjectedFault::ChannelFull if self.overflow_policy == OverflowPolicy::Block => {
+                SendError::Full(data)
+            }
+            InjectedFault::ChannelFull => {
+                self.core.telemetry.record_dropped();
+                return Ok(());
+            }
+        };
+        self.core.telemetry.record_error();
+        Err(self.pdata_send_error(error))
+    }
+
+    /// Sends a message to the next node(s) in the pipeline without waiting. Unlike `send_lossy`,
+    /// the message is returned back to the caller when the output channel is full, letting the
+    /// receiver implement its own shedding logic.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Full`] carrying the message if the output channel is full (or if
+    /// the in-flight byte budget or the buffer pool is exhausted), or a [`SendError::Closed`] if
+    /// the output channel is closed.
+    pub fn try_send_message(&self, data: PData) -> Result<(), SendError<PData>> {
+        let bytes = self.size_of(&data);
+        match self.try_send_budgeted(data, bytes) {
+            Err(SendError::Full(data)) => Err(SendError::Full(data)),
+            result => self.core.telemetry.record_send_sized(result, bytes),
+        }
+    }
+
     /// Creates a non-blocking TCP listener on the given address with socket options defined by the
     /// pipeline engine implementation. It's important for receiver implementer to create TCP
     /// listeners via this method to ensure the scalability and the serviceability of the pipeline.
@@ -160,5 +972,266 @@ impl<PData> EffectHandler<PData> {
         self.core.tcp_listener(addr, self.receiver_name())
     }
 
+    /// Creates a TCP listener like `tcp_listener`, with an accept queue of the given size instead
+    /// of the default one, e.g. for a receiver expecting bursts of connections. The operating
+    /// system may cap the size (e.g. `net.core.somaxconn` on Linux).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if any step in the process fails.
+    pub fn tcp_listener_with_backlog(
+        &self,
+        addr: SocketAddr,
+        backlog: u32,
+    ) -> Result<TcpListener, Error<PData>> {
+        self.core
+            .tcp_listener_with_backlog(addr, backlog, self.receiver_name())
+    }
+
+    /// Creates a TCP listener on the given address (see `tcp_listener`) that terminates TLS with
+    /// the given configuration. The listener only yields connections for which the TLS handshake
+    /// succeeded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if the listener can't be created or if the certificates and
+    /// keys can't be loaded.
+    pub fn tls_listener(
+        &self,
+        addr: SocketAddr,
+        config: TlsConfig,
+    ) -> Result<TlsListener, Error<PData>> {
+        self.core.tls_listener(addr, &config, self.receiver_name())
+    }
+
+    /// Creates a Unix domain socket listener bound to the given path, e.g. for agents sending
+    /// their telemetry over a local socket. A stale socket file is replaced, and the socket file
+    /// is removed once the listener is dropped, i.e. when the receiver shuts down (see
+    /// [`crate::unix`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if the listener can't be bound, e.g. if the path is used by
+    /// another file or by a running listener.
+    pub fn unix_listener(&self, path: &Path) -> Result<UnixListener, Error<PData>> {
+        self.core.unix_listener(path, self.receiver_name())
+    }
+
+    /// Creates a non-blocking UDP socket bound to the given address with socket options defined by
+    /// the pipeline engine implementation. It's important for receiver implementer to create UDP
+    /// sockets via this method to ensure the scalability and the serviceability of the pipeline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if any step in the process fails.
+    pub fn udp_socket(&self, addr: SocketAddr) -> Result<UdpSocket, Error<PData>> {
+        self.core.udp_socket(addr, self.receiver_name())
+    }
+
+    /// Returns the addresses of the listeners and sockets created so far by the receiver via
+    /// `tcp_listener`, `tls_listener` or `udp_socket`, e.g. to find out the ephemeral port bound
+    /// for the address `127.0.0.1:0`.
+    #[must_use]
+    pub fn bound_addresses(&self) -> Vec<SocketAddr> {
+        self.core.bound_addresses()
+    }
+
+    /// Returns a receiver of the addresses of the listeners and sockets created by the receiver.
+    pub(crate) fn subscribe_bound_addresses(&self) -> watch::Receiver<Vec<SocketAddr>> {
+        self.core.subscribe_bound_addresses()
+    }
+
+    /// Spawns a task handling an accepted connection on the current `LocalSet`.
+    ///
+    /// The task is tracked by the effect handler so that it can be awaited when the receiver shuts
+    /// down (see `drain_connections`). Tasks spawned once the connections have been drained are
+    /// dropped.
+    pub fn spawn_connection<F>(&self, connection: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.core.connections.spawn_local(connection);
+    }
+
+    /// Spawns a named subtask of the receiver on the current `LocalSet`.
+    ///
+    /// The subtask runs within a tracing span carrying the names of the receiver and of the
+    /// subtask. It is tracked by the effect handler: the receiver can await the returned handle
+    /// when it shuts down, and the subtasks still running once the receiver completed are aborted.
+    pub fn spawn_local_named<F>(&self, name: &str, task: F) -> TaskHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.core
+            .tasks
+            .spawn_local(&self.core.node_name, Cow::Owned(name.to_owned()), task)
+    }
+
+    /// Runs a blocking function, e.g. decompressing or parsing a payload, on the blocking thread
+    /// pool of Tokio so that it doesn't stall the other tasks of the `LocalSet`.
+    ///
+    /// The function runs within a `blocking_task` tracing span carrying the name of the receiver.
+    /// It is tracked like the subtasks (under the name `blocking`, see `running_tasks`): the
+    /// receiver can await the returned handle, and the blocking tasks still queued once the
+    /// receiver completed are cancelled. A blocking task that already started runs to completion,
+    /// its output is then discarded.
+    pub fn spawn_blocking<F, R>(&self, f: F) -> TaskHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.core.tasks.spawn_blocking(&self.core.node_name, f)
+    }
+
+    /// Returns the names of the subtasks spawned via `spawn_local_named` or `spawn_blocking` that
+    /// are still running.
+    #[must_use]
+    pub fn running_tasks(&self) -> Vec<Cow<'static, str>> {
+        self.core.tasks.running_tasks()
+    }
+
+    /// Returns the registry of the subtasks spawned by the receiver.
+    pub(crate) fn tasks(&self) -> TaskRegistry {
+        self.core.tasks.clone()
+    }
+
+    /// Returns the publisher of the summary of the run of the receiver.
+    pub(crate) fn shutdown_reporter(&self) -> ShutdownReporter {
+        self.core.shutdown_reporter()
+    }
+
+    /// Returns a receiver of the summary of the run of the receiver, `None` until the receiver
+    /// shut down cleanly.
+    pub(crate) fn subscribe_shutdown_report(&self) -> watch::Receiver<Option<ShutdownReport>> {
+        self.core.subscribe_shutdown_report()
+    }
+
+    /// Starts a periodic timer, whose expirations are delivered to the receiver as
+    /// `ControlMsg::NodeTimer` messages carrying the returned id on its control channel, so that
+    /// the receiver doesn't need to poll a sleep next to its control channel. The timer runs until
+    /// it is cancelled or a `Shutdown` is delivered to the receiver.
+    ///
+    /// Like the `TimerTick` messages, the expirations missed while the receiver is busy are
+    /// skipped rather than delivered in a burst.
+    #[must_use]
+    pub fn start_periodic_timer(&self, interval: Duration) -> TimerId {
+        self.core.timers.start(interval)
+    }
+
+    /// Cancels a timer started with `start_periodic_timer`. Returns false if the timer was not
+    /// running.
+    ///
+    /// Note: An expiration already forwarded to the control channel may still be received after
+    /// the cancellation.
+    #[must_use]
+    pub fn cancel_timer(&self, id: TimerId) -> bool {
+        self.core.timers.cancel(id)
+    }
+
+    /// Returns the periodic timers started by the receiver.
+    pub(crate) fn timers(&self) -> NodeTimers {
+        self.core.timers.clone()
+    }
+
+    /// Returns the number of connection tasks spawned via `spawn_connection` that are still
+    /// running.
+    #[must_use]
+    pub fn active_connections(&self) -> usize {
+        self.core.connections.active_connections()
+    }
+
+    /// Waits up to `deadline` for the connection tasks spawned via `spawn_connection` to complete,
+    /// then aborts the ones still running. Receivers are expected to call this method with the
+    /// deadline of the `Shutdown` control message after they stopped accepting new connections.
+    pub async fn drain_connections(&self, deadline: Duration) {
+        self.core.connections.drain(deadline).await;
+    }
+
+    /// Returns the size in bytes of the buffers handed out by `read_buffer` (see
+    /// `ReceiverConfig::read_buffer_size`).
+    #[must_use]
+    pub fn read_buffer_size(&self) -> usize {
+        self.core.read_buffers.buffer_size()
+    }
+
+    /// Takes a read buffer of `read_buffer_size` bytes from the pool of the receiver. Connection
+    /// handlers are expected to take one buffer per connection and read the connection into it;
+    /// the buffer returns to the pool when dropped.
+    #[must_use]
+    pub fn read_buffer(&self) -> ReadBuffer {
+        self.core.read_buffers.acquire()
+    }
+
+    /// Accepts the connections of the given listener until a `Shutdown` control message is
+    /// received, running `handler` in a dedicated task for every accepted connection (see
+    /// `spawn_connection`).
+    ///
+    /// No connection is accepted while the ingestion is paused (see `is_paused`), nor while the
+    /// maximum number of concurrent connections of the receiver is reached (see
+    /// `ReceiverConfig::max_concurrent_connections`), in which case the pending connections stay
+    /// in the listen backlog until a connection handler completes. Every control
+    /// message received in the meantime (including the `Shutdown`) is passed to `on_ctrl_msg`. On
+    /// `Shutdown`, the listener is closed and the in-flight connection handlers are drained (see
+    /// `drain_connections`). The handlers can read their connection into a pooled buffer taken
+    /// with `read_buffer`, or split it into whole frames (see [`crate::framing`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if a connection can't be accepted, or an
+    /// [`Error::ChannelRecvError`] if the control channel is closed.
+    pub async fn serve_connections<F, Fut>(
+        &self,
+        listener: TcpListener,
+        ctrl_chan: &mut ControlChannel,
+        mut on_ctrl_msg: impl FnMut(&ControlMsg),
+        mut handler: F,
+    ) -> Result<(), Error<PData>>
+    where
+        F: FnMut(TcpStream, SocketAddr) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        loop {
+            tokio::select! {
+                biased;
+
+                ctrl_msg = ctrl_chan.recv() => {
+                    let ctrl_msg = ctrl_msg?;
+                    on_ctrl_msg(&ctrl_msg);
+                    if let ControlMsg::Shutdown { deadline, .. } = ctrl_msg {
+                        drop(listener);
+                        self.drain_connections(deadline).await;
+                        return Ok(());
+                    }
+                }
+
+                (slot, accept_result) = self.accept_within_limit(&listener), if !self.is_paused() => {
+                    let (stream, peer_addr) = accept_result.map_err(|error| Error::IoError {
+                        node: self.receiver_name(),
+                        error,
+                    })?;
+                    let connection = handler(stream, peer_addr);
+                    self.spawn_connection(async move {
+                        connection.await;
+                        // Frees the connection slot once the connection is handled.
+                        drop(slot);
+                    });
+                }
+            }
+        }
+    }
+
+    /// Accepts a connection once a connection slot is available.
+    async fn accept_within_limit(
+        &self,
+        listener: &TcpListener,
+    ) -> (
+        Option<OwnedSemaphorePermit>,
+        std::io::Result<(TcpStream, SocketAddr)>,
+    ) {
+        let slot = self.core.connections.reserve_slot().await;
+        (slot, listener.accept().await)
+    }
+
     // More methods will be added in the future as needed.
 }
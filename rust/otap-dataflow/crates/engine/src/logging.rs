@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structured logging of the nodes, tagged with the identity of the node.
+//!
+//! The effect handlers expose `info_message`, `warn_message` and `error_message` methods emitting a
+//! [`LogRecord`] carrying the name and the kind of the node, and the id of its pipeline, to a
+//! pluggable [`LogSink`]. The sink and the pipeline id are configured on the pipeline (see
+//! `PipelineBuilder::log_sink`) or on each node wrapper (see their `set_log_sink` methods), the
+//! records go to the [`TracingLogSink`] otherwise.
+
+use otap_df_config::NodeKind;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+/// The severity of a [`LogRecord`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogLevel {
+    /// An informational message.
+    Info,
+    /// A condition the node recovered from.
+    Warn,
+    /// A failure of the node.
+    Error,
+}
+
+/// A message logged by a node through its effect handler.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRecord {
+    /// The severity of the message.
+    pub level: LogLevel,
+    /// The name of the node that logged the message.
+    pub node: Cow<'static, str>,
+    /// The kind of the node that logged the message.
+    pub node_kind: NodeKind,
+    /// The id of the pipeline of the node, if any.
+    pub pipeline_id: Option<Cow<'static, str>>,
+    /// The message itself.
+    pub message: String,
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pipeline_id) = &self.pipeline_id {
+            write!(f, "[{pipeline_id}] ")?;
+        }
+        write!(f, "{:?} {}: {}", self.node_kind, self.node, self.message)
+    }
+}
+
+/// A destination of the messages logged by the nodes.
+pub trait LogSink: Send + Sync {
+    /// Emits a record.
+    fn log(&self, record: &LogRecord);
+}
+
+/// A [`LogSink`] emitting the records as `tracing` events, the default sink of the nodes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingLogSink;
+
+impl LogSink for TracingLogSink {
+    fn log(&self, record: &LogRecord) {
+        let node = &*record.node;
+        let node_kind = record.node_kind;
+        let pipeline_id = record.pipeline_id.as_deref().unwrap_or_default();
+        let message = &record.message;
+        match record.level {
+            LogLevel::Info => tracing::info!(node, ?node_kind, pipeline_id, "{message}"),
+            LogLevel::Warn => tracing::warn!(node, ?node_kind, pipeline_id, "{message}"),
+            LogLevel::Error => tracing::error!(node, ?node_kind, pipeline_id, "{message}"),
+        }
+    }
+}
+
+/// The sink of a node, along with the identity the records of the node are tagged with.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone)]
+pub(crate) struct NodeLogger {
+    sink: Arc<dyn LogSink>,
+    node_kind: NodeKind,
+    pipeline_id: Option<Cow<'static, str>>,
+}
+
+impl NodeLogger {
+    /// Creates a logger emitting the records of a node of the given kind to the [`TracingLogSink`].
+    pub(crate) fn new(node_kind: NodeKind) -> Self {
+        NodeLogger {
+            sink: Arc::new(TracingLogSink),
+            node_kind,
+            pipeline_id: None,
+        }
+    }
+
+    /// Emits the records to the given sink, tagged with the given pipeline id.
+    pub(crate) fn set_sink(
+        &mut self,
+        sink: Arc<dyn LogSink>,
+        pipeline_id: Option<Cow<'static, str>>,
+    ) {
+        self.sink = sink;
+        self.pipeline_id = pipeline_id;
+    }
+
+    /// Emits a record for the given node.
+    pub(crate) fn log(&self, node: Cow<'static, str>, level: LogLevel, message: &str) {
+        self.sink.log(&LogRecord {
+            level,
+            node,
+            node_kind: self.node_kind,
+            pipeline_id: self.pipeline_id.clone(),
+            message: message.to_owned(),
+        });
+    }
+}
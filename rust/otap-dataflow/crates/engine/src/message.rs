@@ -2,10 +2,24 @@
 
 //! Message definitions for the pipeline engine.
 
+use crate::budget::InflightBudget;
+use crate::config::{OverflowPolicy, apply_patch};
+use crate::error::Error;
+use crate::health::HealthProbe;
+use crate::telemetry::{NodeTelemetry, TelemetryCounters};
+use crate::timer::TimerId;
 use otap_df_channel::error::{RecvError, SendError};
 use otap_df_channel::mpsc;
+use otap_df_config::node::NodeName;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::collections::VecDeque;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
 use std::time::Duration;
+use tokio::sync::Notify;
 use tokio::time::{Instant, Sleep, sleep_until};
 
 /// Represents messages sent to nodes (receivers, processors, exporters, or connectors) within the
@@ -44,146 +58,1437 @@ pub enum ControlMsg {
     /// Indicates a change in the configuration of a node. For example, a config message can
     /// instruct a Filter Processor to include or exclude certain attributes, or notify a Retry
     /// Processor to adjust backoff settings.
+    ///
+    /// The node wrappers only deliver the updates addressed to their node (see
+    /// [`NodeConfigUpdate::target`]). Nodes confirm the updates they applied with the effect
+    /// handler's `ack_config` (see [`crate::config_ack`]). If the receiver declared its
+    /// configuration type (see [`crate::config::ReceiverConfig::config_schema`]), the engine
+    /// decodes the updates addressed to it before delivery (see [`NodeConfigUpdate::decoded`]),
+    /// and rejects the ones that don't match the type without delivering them.
     Config {
-        /// The new configuration.
-        config: serde_json::Value,
+        /// The configuration update.
+        update: NodeConfigUpdate,
+    },
+
+    /// Delivers a configuration update to a node. Unlike `Config`, the payload is meant to be
+    /// decoded into the configuration type of the node (see [`ReconfigurePayload::decode`] and the
+    /// `recv_typed` method of the receiver control channels).
+    ///
+    /// The payload is a JSON merge patch (RFC 7386) against the current configuration of the
+    /// node, so that a single setting can be changed without resending the whole configuration
+    /// (see [`ReconfigurePayload::apply_to`]). A complete configuration is a valid patch replacing
+    /// every setting.
+    Reconfigure {
+        /// The configuration update.
+        payload: ReconfigurePayload,
+    },
+
+    /// Emitted upon timer expiration, used to trigger scheduled tasks (e.g., batch emissions).
+    TimerTick {
+        // TBD
+    },
+
+    /// Emitted upon the expiration of a periodic timer started by the node itself (see the
+    /// `start_periodic_timer` method of the receiver effect handlers).
+    NodeTimer {
+        /// The id of the expired timer.
+        id: TimerId,
+    },
+
+    /// Requests the node to emit all the pdata it buffers internally (e.g. a batch processor),
+    /// without shutting down. Processors forward this message to their downstream nodes once they
+    /// have handled it, so a flush propagates through the whole pipeline.
+    ///
+    /// Nodes confirm the flush with the effect handler's `ack_flush` once the buffered pdata have
+    /// been emitted, which lets the sender await its completion (see [`crate::flush_ack`]).
+    Flush {
+        /// The correlation id of the flush, echoed back by the node when acknowledging it.
+        id: u64,
+        /// The reason for the flush.
+        reason: String,
+    },
+
+    /// Sent upstream by an overwhelmed exporter (see the exporter effect handler's
+    /// `throttle_upstream`) to request the receivers to pause ingestion for the given duration
+    /// before resuming.
+    Throttle {
+        /// How long the ingestion should be paused.
+        duration: Duration,
+    },
+
+    /// Requests a receiver to temporarily stop ingesting external data (e.g. to stop accepting
+    /// connections and to stop sending pdata via its effect handler) until a `Resume` is received.
+    /// A paused receiver keeps draining its control channel. Emitted by the engine when the output
+    /// pdata channel of the receiver stays congested (see [`crate::config::BackpressureConfig`]),
+    /// and usable for manual flow control.
+    Pause,
+
+    /// Requests a receiver paused by a `Pause` to resume the ingestion of external data.
+    Resume,
+
+    /// Delivered to a restarted receiver ahead of any other control message (see the
+    /// `restart_with_retry` method of the receiver wrapper), e.g. to re-establish the state lost
+    /// by the failed instance.
+    Restarting {
+        /// The restart attempt that created this instance of the receiver, starting at 1.
+        attempt: u32,
     },
 
-    /// Emitted upon timer expiration, used to trigger scheduled tasks (e.g., batch emissions).
-    TimerTick {
-        // TBD
-    },
+    /// A liveness probe periodically injected by the node wrappers (see [`crate::health`]). It is
+    /// answered by the control channel of the node as soon as the node receives it, and is never
+    /// returned to the node itself.
+    HealthCheck {
+        /// The correlation id of the health check.
+        id: u64,
+    },
+
+    /// Requests a snapshot of the internal counters of the node (see [`crate::telemetry`]). It is
+    /// answered by the node wrapper on behalf of the node, and is never delivered to the node
+    /// itself.
+    CollectTelemetry {
+        /// The channel on which the snapshot is sent.
+        reply_to: tokio::sync::mpsc::Sender<NodeTelemetry>,
+    },
+
+    /// A graceful shutdown message requiring the node to finish processing messages and release
+    /// resources by a specified deadline. A deadline of 0 indicates an immediate shutdown.
+    Shutdown {
+        /// The deadline for the shutdown.
+        deadline: Duration,
+        /// The reason for the shutdown.
+        reason: String,
+    },
+}
+
+impl ControlMsg {
+    /// Returns the name of the type of this control message, e.g. `"Shutdown"`.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ControlMsg::Ack { .. } => "Ack",
+            ControlMsg::Nack { .. } => "Nack",
+            ControlMsg::Config { .. } => "Config",
+            ControlMsg::Reconfigure { .. } => "Reconfigure",
+            ControlMsg::TimerTick { .. } => "TimerTick",
+            ControlMsg::NodeTimer { .. } => "NodeTimer",
+            ControlMsg::Flush { .. } => "Flush",
+            ControlMsg::Throttle { .. } => "Throttle",
+            ControlMsg::Pause => "Pause",
+            ControlMsg::Resume => "Resume",
+            ControlMsg::Restarting { .. } => "Restarting",
+            ControlMsg::HealthCheck { .. } => "HealthCheck",
+            ControlMsg::CollectTelemetry { .. } => "CollectTelemetry",
+            ControlMsg::Shutdown { .. } => "Shutdown",
+        }
+    }
+
+    /// Checks if this control message is a shutdown message.
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        matches!(self, ControlMsg::Shutdown { .. })
+    }
+
+    /// Checks if this control message is a flush message.
+    #[must_use]
+    pub fn is_flush(&self) -> bool {
+        matches!(self, ControlMsg::Flush { .. })
+    }
+
+    /// Checks if this control message is a pause message.
+    #[must_use]
+    pub fn is_pause(&self) -> bool {
+        matches!(self, ControlMsg::Pause)
+    }
+
+    /// Checks if this control message is a resume message.
+    #[must_use]
+    pub fn is_resume(&self) -> bool {
+        matches!(self, ControlMsg::Resume)
+    }
+
+    /// Checks if this control message must be delivered ahead of the other pending control
+    /// messages (see [`priority_channel`]), i.e. `Shutdown`, `Flush`, `Pause` and `Resume`.
+    /// `Resume` shares the lane of `Pause` so that a `Pause` never overtakes an earlier `Resume`.
+    #[must_use]
+    pub fn is_high_priority(&self) -> bool {
+        matches!(
+            self,
+            ControlMsg::Shutdown { .. }
+                | ControlMsg::Flush { .. }
+                | ControlMsg::Pause
+                | ControlMsg::Resume
+        )
+    }
+
+    /// Checks if this control message is a throttle message.
+    #[must_use]
+    pub fn is_throttle(&self) -> bool {
+        matches!(self, ControlMsg::Throttle { .. })
+    }
+}
+
+/// The node(s) a configuration update is addressed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigTarget {
+    /// Every node of the pipeline.
+    All,
+
+    /// The node with the given name.
+    Node(NodeName),
+}
+
+/// A configuration update carried by a [`ControlMsg::Config`] message.
+#[derive(Debug, Clone)]
+pub struct NodeConfigUpdate {
+    /// The node(s) the update is addressed to.
+    pub target: ConfigTarget,
+    /// The version of the update, echoed back by the node when acknowledging it.
+    pub version: u64,
+    /// The node-specific configuration, opaque to the engine.
+    pub payload: serde_json::Value,
+    /// The payload decoded by the engine into the configuration type of the receiver.
+    decoded: Option<TypedConfig>,
+}
+
+impl NodeConfigUpdate {
+    /// Creates a configuration update addressed to the given node.
+    #[must_use]
+    pub fn for_node(node: impl Into<NodeName>, version: u64, payload: serde_json::Value) -> Self {
+        NodeConfigUpdate {
+            target: ConfigTarget::Node(node.into()),
+            version,
+            payload,
+            decoded: None,
+        }
+    }
+
+    /// Creates a configuration update addressed to every node.
+    #[must_use]
+    pub fn for_all(version: u64, payload: serde_json::Value) -> Self {
+        NodeConfigUpdate {
+            target: ConfigTarget::All,
+            version,
+            payload,
+            decoded: None,
+        }
+    }
+
+    /// Checks if this update is addressed to the given node.
+    #[must_use]
+    pub fn is_addressed_to(&self, node: &str) -> bool {
+        match &self.target {
+            ConfigTarget::All => true,
+            ConfigTarget::Node(target) => target == node,
+        }
+    }
+
+    /// Deserializes the payload into the configuration type `C` of the node.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`serde_json::Error`] if the payload does not match the configuration type.
+    pub fn decode<C: DeserializeOwned>(&self) -> Result<C, serde_json::Error> {
+        C::deserialize(&self.payload)
+    }
+
+    /// Returns the payload already decoded by the engine into the configuration type `C` of the
+    /// receiver, `None` if the receiver didn't declare this type (see
+    /// [`crate::config::ReceiverConfig::config_schema`]).
+    #[must_use]
+    pub fn decoded<C: Any>(&self) -> Option<&C> {
+        self.decoded.as_ref().and_then(TypedConfig::get)
+    }
+
+    /// Attaches the payload decoded by the engine.
+    pub(crate) fn with_decoded(mut self, config: TypedConfig) -> Self {
+        self.decoded = Some(config);
+        self
+    }
+}
+
+/// A configuration decoded by the engine, carried by a [`NodeConfigUpdate`].
+#[derive(Debug, Clone)]
+pub struct TypedConfig(Arc<dyn Any + Send + Sync>);
+
+impl TypedConfig {
+    /// Wraps the given decoded configuration.
+    #[must_use]
+    pub fn new<C: Any + Send + Sync>(config: C) -> Self {
+        TypedConfig(Arc::new(config))
+    }
+
+    /// Returns the configuration if it is of type `C`, `None` otherwise.
+    #[must_use]
+    pub fn get<C: Any>(&self) -> Option<&C> {
+        self.0.downcast_ref()
+    }
+}
+
+/// A configuration update carried by a [`ControlMsg::Reconfigure`] message.
+///
+/// The update travels through the control channels as a [`serde_json::Value`] (the default type
+/// parameter) and is decoded into a strongly-typed configuration `C` by the node receiving it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconfigurePayload<C = serde_json::Value> {
+    /// The configuration update.
+    pub config: C,
+}
+
+impl ReconfigurePayload {
+    /// Creates a new payload from the serialized configuration update.
+    #[must_use]
+    pub fn new(config: serde_json::Value) -> Self {
+        Self { config }
+    }
+
+    /// Decodes the configuration update into the configuration type `C`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`serde_json::Error`] if the update does not match the configuration type.
+    pub fn decode<C: DeserializeOwned>(self) -> Result<ReconfigurePayload<C>, serde_json::Error> {
+        Ok(ReconfigurePayload {
+            config: serde_json::from_value(self.config)?,
+        })
+    }
+
+    /// Applies the configuration update, as a JSON merge patch (see
+    /// [`crate::config::apply_patch`]), to the current configuration of the node. The settings
+    /// not mentioned by the update are left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`serde_json::Error`] if the patched configuration does not match the
+    /// configuration type.
+    pub fn apply_to<C>(&self, current: &C) -> Result<C, serde_json::Error>
+    where
+        C: Serialize + DeserializeOwned,
+    {
+        let mut config = serde_json::to_value(current)?;
+        apply_patch(&mut config, &self.config);
+        serde_json::from_value(config)
+    }
+}
+
+/// A control message whose `Reconfigure` payload has been decoded into the configuration type `C`
+/// of a node (see the `recv_typed` method of the receiver control channels).
+#[derive(Debug, Clone)]
+pub enum TypedControlMsg<C> {
+    /// A configuration update decoded into the configuration type of the node.
+    Reconfigure(ReconfigurePayload<C>),
+
+    /// Any other control message.
+    Other(ControlMsg),
+}
+
+impl<C: DeserializeOwned> TryFrom<ControlMsg> for TypedControlMsg<C> {
+    type Error = serde_json::Error;
+
+    fn try_from(msg: ControlMsg) -> Result<Self, Self::Error> {
+        match msg {
+            ControlMsg::Reconfigure { payload } => {
+                Ok(TypedControlMsg::Reconfigure(payload.decode()?))
+            }
+            msg => Ok(TypedControlMsg::Other(msg)),
+        }
+    }
+}
+
+impl<Data> Message<Data> {
+    /// Create a data message with the given payload.
+    #[must_use]
+    pub fn data_msg(data: Data) -> Self {
+        Message::PData(data)
+    }
+
+    /// Create a ACK control message with the given ID.
+    #[must_use]
+    pub fn ack_ctrl_msg(id: u64) -> Self {
+        Message::Control(ControlMsg::Ack { id })
+    }
+
+    /// Create a NACK control message with the given ID and reason.
+    #[must_use]
+    pub fn nack_ctrl_msg(id: u64, reason: &str) -> Self {
+        Message::Control(ControlMsg::Nack {
+            id,
+            reason: reason.to_owned(),
+        })
+    }
+
+    /// Creates a config control message with the given configuration update.
+    #[must_use]
+    pub fn config_ctrl_msg(update: NodeConfigUpdate) -> Self {
+        Message::Control(ControlMsg::Config { update })
+    }
+
+    /// Creates a timer tick control message.
+    #[must_use]
+    pub fn timer_tick_ctrl_msg() -> Self {
+        Message::Control(ControlMsg::TimerTick {})
+    }
+
+    /// Creates a flush control message with the given correlation id and reason.
+    #[must_use]
+    pub fn flush_ctrl_msg(id: u64, reason: &str) -> Self {
+        Message::Control(ControlMsg::Flush {
+            id,
+            reason: reason.to_owned(),
+        })
+    }
+
+    /// Creates a shutdown control message with the given reason.
+    #[must_use]
+    pub fn shutdown_ctrl_msg(deadline: Duration, reason: &str) -> Self {
+        Message::Control(ControlMsg::Shutdown {
+            deadline,
+            reason: reason.to_owned(),
+        })
+    }
+
+    /// Checks if this message is a data message.
+    #[must_use]
+    pub fn is_data(&self) -> bool {
+        matches!(self, Message::PData(..))
+    }
+
+    /// Checks if this message is a control message.
+    #[must_use]
+    pub fn is_control(&self) -> bool {
+        matches!(self, Message::Control(..))
+    }
+
+    /// Checks if this message is a shutdown control message.
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        matches!(self, Message::Control(ControlMsg::Shutdown { .. }))
+    }
+
+    /// Checks if this message is a flush control message.
+    #[must_use]
+    pub fn is_flush(&self) -> bool {
+        matches!(self, Message::Control(ControlMsg::Flush { .. }))
+    }
+}
+
+/// A generic channel Sender supporting both local and shared semantic (i.e. !Send and Send).
+pub enum Sender<T> {
+    /// Local channel sender.
+    Local(mpsc::Sender<T>),
+    /// Shared channel sender.
+    Shared(tokio::sync::mpsc::Sender<T>),
+    /// Priority channel sender (see [`priority_channel`]), usable in both semantics.
+    Priority(PrioritySender<T>),
+    /// Local ring channel sender (see [`Sender::ring`]).
+    Ring(mpsc::RingSender<T>),
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Sender::Local(sender) => Sender::Local(sender.clone()),
+            Sender::Shared(sender) => Sender::Shared(sender.clone()),
+            Sender::Priority(sender) => Sender::Priority(sender.clone()),
+            Sender::Ring(sender) => Sender::Ring(sender.clone()),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Creates a local ring channel (see [`mpsc::RingChannel`]) with the given capacity. Sending to
+    /// this channel never waits, the oldest buffered message is dropped when the channel is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is zero.
+    #[must_use]
+    pub fn ring(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        let (sender, receiver) = mpsc::RingChannel::new(capacity);
+        (Sender::Ring(sender), Receiver::Local(receiver))
+    }
+
+    /// Sends a message to the channel, waiting for some capacity if the channel is full. A `Ring`
+    /// sender never waits, the oldest buffered message is dropped instead.
+    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        match self {
+            Sender::Local(sender) => sender.send_async(msg).await,
+            Sender::Shared(sender) => sender.send(msg).await.map_err(|e| SendError::Closed(e.0)),
+            Sender::Priority(sender) => sender.send(msg).await,
+            Sender::Ring(sender) => sender.send(msg).map(|_| ()),
+        }
+    }
+
+    /// Sends a message to the channel, waiting at most `timeout` for some capacity if the channel
+    /// is full. A `Ring` sender never waits, the oldest buffered message is dropped instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Full`] with the message if the channel stayed full until the timeout
+    /// elapsed, or a [`SendError::Closed`] if the receiving end of the channel has been dropped.
+    pub async fn send_timeout(&self, msg: T, timeout: Duration) -> Result<(), SendError<T>> {
+        match self {
+            Sender::Local(sender) => sender.send_timeout(msg, timeout).await,
+            Sender::Shared(sender) => sender
+                .send_timeout(msg, timeout)
+                .await
+                .map_err(from_send_timeout_error),
+            Sender::Priority(sender) => sender.send_timeout(msg, timeout).await,
+            Sender::Ring(sender) => sender.send(msg).map(|_| ()),
+        }
+    }
+
+    /// Sends a message to the channel without waiting for some capacity.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Full`] if the channel is full, or a [`SendError::Closed`] if the
+    /// receiving end of the channel has been dropped.
+    pub fn try_send(&self, msg: T) -> Result<(), SendError<T>> {
+        match self {
+            Sender::Local(sender) => sender.send(msg),
+            Sender::Shared(sender) => sender.try_send(msg).map_err(from_try_send_error),
+            Sender::Priority(sender) => sender.try_send(msg),
+            Sender::Ring(sender) => sender.send(msg).map(|_| ()),
+        }
+    }
+
+    /// Sends a message to the channel without waiting for some capacity, evicting the oldest
+    /// buffered message if the channel is full. Returns the evicted message, if any.
+    ///
+    /// Note: Tokio channels can't evict a buffered message, the message being sent is returned
+    /// instead when a `Shared` channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Closed`] if the receiving end of the channel has been dropped.
+    pub fn force_send(&self, msg: T) -> Result<Option<T>, SendError<T>> {
+        match self {
+            Sender::Local(sender) => sender.force_send(msg),
+            Sender::Shared(sender) => match sender.try_send(msg).map_err(from_try_send_error) {
+                Ok(()) => Ok(None),
+                Err(SendError::Full(msg)) => Ok(Some(msg)),
+                Err(error) => Err(error),
+            },
+            Sender::Priority(sender) => sender.force_send(msg),
+            Sender::Ring(sender) => Ok(match sender.send(msg)? {
+                mpsc::RingSendResult::Dropped(dropped) => Some(dropped),
+                mpsc::RingSendResult::Sent => None,
+            }),
+        }
+    }
+
+    /// Returns the number of messages currently buffered in the channel.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match self {
+            Sender::Local(sender) => sender.len(),
+            Sender::Shared(sender) => sender.max_capacity() - sender.capacity(),
+            Sender::Priority(sender) => sender.len(),
+            Sender::Ring(sender) => sender.len(),
+        }
+    }
+
+    /// Returns true if no message is currently buffered in the channel.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of messages the channel can buffer.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        match self {
+            Sender::Local(sender) => sender.capacity(),
+            Sender::Shared(sender) => sender.max_capacity(),
+            Sender::Priority(sender) => sender.capacity(),
+            Sender::Ring(sender) => sender.capacity(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Converts this sender into a sender usable in a `Send` context, or returns `None` for a
+    /// `Local` or `Ring` sender.
+    pub(crate) fn into_shared(self) -> Option<SharedSender<T>> {
+        match self {
+            Sender::Local(_) | Sender::Ring(_) => None,
+            Sender::Shared(sender) => Some(SharedSender::Tokio(sender)),
+            Sender::Priority(sender) => Some(SharedSender::Priority(sender)),
+        }
+    }
+}
+
+/// A sender fanning each message out to multiple downstream channels, e.g. to feed several
+/// processors or exporters from the same node.
+///
+/// Every downstream receives its own clone of the message. Downstreams can be added and removed
+/// at runtime, they are identified by the id returned by [`BroadcastSender::add_downstream`].
+///
+/// Each downstream has its own [`OverflowPolicy`]: the sender waits for the `Block` downstreams
+/// to have some capacity, while a full `DropOldest` or `DropNewest` downstream loses a message
+/// without slowing down the other ones.
+pub struct BroadcastSender<T> {
+    downstreams: Vec<(usize, Sender<T>, OverflowPolicy)>,
+    next_id: usize,
+}
+
+impl<T> Default for BroadcastSender<T> {
+    fn default() -> Self {
+        BroadcastSender {
+            downstreams: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> BroadcastSender<T> {
+    /// Creates a broadcast sender without any downstream.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a downstream channel with the [`OverflowPolicy::Block`] policy and returns its id.
+    pub fn add_downstream(&mut self, sender: Sender<T>) -> usize {
+        self.add_downstream_with_policy(sender, OverflowPolicy::Block)
+    }
+
+    /// Adds a downstream channel with the given overflow policy and returns its id.
+    pub fn add_downstream_with_policy(
+        &mut self,
+        sender: Sender<T>,
+        overflow_policy: OverflowPolicy,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.downstreams.push((id, sender, overflow_policy));
+        id
+    }
+
+    /// Removes the downstream channel with the given id, returning its sender if it was
+    /// registered.
+    pub fn remove_downstream(&mut self, id: usize) -> Option<Sender<T>> {
+        let index = self
+            .downstreams
+            .iter()
+            .position(|(downstream, _, _)| *downstream == id)?;
+        Some(self.downstreams.remove(index).1)
+    }
+
+    /// Returns the number of downstream channels.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.downstreams.len()
+    }
+
+    /// Returns true if no downstream channel is registered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.downstreams.is_empty()
+    }
+}
+
+impl<T: Clone> BroadcastSender<T> {
+    /// Sends a clone of the message to every downstream channel. The message is dropped if no
+    /// downstream is registered.
+    ///
+    /// A failing downstream doesn't prevent the message from being sent to the other ones. A
+    /// message dropped by the overflow policy of a full downstream is not considered a failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ChannelSendError`] wrapping a [`SendError::Broadcast`] that lists the
+    /// ids of the downstreams the message could not be sent to.
+    pub async fn send_message(&self, data: T) -> Result<(), Error<T>> {
+        let Some(((last_id, last, last_policy), others)) = self.downstreams.split_last() else {
+            return Ok(());
+        };
+        let mut failed = Vec::new();
+        let mut unsent = None;
+
+        for (id, sender, overflow_policy) in others {
+            if let Err(error) = send_with_policy(sender, *overflow_policy, data.clone()).await {
+                failed.push(*id);
+                unsent = Some(unsent_msg(error));
+            }
+        }
+        // The last downstream receives the original message.
+        if let Err(error) = send_with_policy(last, *last_policy, data).await {
+            failed.push(*last_id);
+            unsent = Some(unsent_msg(error));
+        }
+
+        match unsent {
+            Some(msg) => Err(Error::ChannelSendError(SendError::Broadcast {
+                msg,
+                failed,
+            })),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The flavors of [`Sender`] usable in a `Send` context.
+pub enum SharedSender<T> {
+    /// Tokio channel sender.
+    Tokio(tokio::sync::mpsc::Sender<T>),
+    /// Priority channel sender.
+    Priority(PrioritySender<T>),
+}
+
+impl<T> Clone for SharedSender<T> {
+    fn clone(&self) -> Self {
+        match self {
+            SharedSender::Tokio(sender) => SharedSender::Tokio(sender.clone()),
+            SharedSender::Priority(sender) => SharedSender::Priority(sender.clone()),
+        }
+    }
+}
+
+impl<T> SharedSender<T> {
+    /// Sends a message to the channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Closed`] if the receiving end of the channel has been dropped.
+    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        match self {
+            SharedSender::Tokio(sender) => {
+                sender.send(msg).await.map_err(|e| SendError::Closed(e.0))
+            }
+            SharedSender::Priority(sender) => sender.send(msg).await,
+        }
+    }
+}
+
+/// A generic channel Receiver supporting both local and shared semantic (i.e. !Send and Send).
+pub enum Receiver<T> {
+    /// Local channel receiver.
+    Local(mpsc::Receiver<T>),
+    /// Shared channel receiver.
+    Shared(tokio::sync::mpsc::Receiver<T>),
+}
+
+impl<T> Receiver<T> {
+    /// Receives a message from the channel.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        match self {
+            Receiver::Local(receiver) => receiver.recv().await,
+            Receiver::Shared(receiver) => receiver.recv().await.ok_or(RecvError::Closed),
+        }
+    }
+
+    /// Tries to receive a message from the channel.
+    pub fn try_recv(&mut self) -> Result<T, RecvError> {
+        match self {
+            Receiver::Local(receiver) => receiver.try_recv(),
+            Receiver::Shared(receiver) => receiver.try_recv().map_err(|_| RecvError::Closed),
+        }
+    }
+
+    /// Polls to receive a message from the channel.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        match self {
+            Receiver::Local(receiver) => receiver.poll_recv(cx),
+            Receiver::Shared(receiver) => receiver
+                .poll_recv(cx)
+                .map(|msg| msg.ok_or(RecvError::Closed)),
+        }
+    }
+
+    /// Converts this receiver into a receiver able to inspect the next message without consuming
+    /// it (see [`PeekableReceiver::peek`]).
+    #[must_use]
+    pub fn peekable(self) -> PeekableReceiver<T> {
+        PeekableReceiver {
+            receiver: self,
+            peeked: None,
+        }
+    }
+
+    /// Converts this receiver into a receiver usable in a `Send` context, or returns `None` for a
+    /// `Local` receiver.
+    #[must_use]
+    pub fn into_shared(self) -> Option<tokio::sync::mpsc::Receiver<T>> {
+        match self {
+            Receiver::Local(_) => None,
+            Receiver::Shared(receiver) => Some(receiver),
+        }
+    }
+}
+
+/// A [`Receiver`] able to inspect the next message without consuming it, e.g. to route it
+/// according to its content (see [`Receiver::peekable`]).
+///
+/// Neither channel implementation supports peeking, the peeked message is removed from the
+/// channel and held by the receiver until the next `recv` or `try_recv` returns it.
+pub struct PeekableReceiver<T> {
+    receiver: Receiver<T>,
+    /// The message returned by the last `peek`, not received yet.
+    peeked: Option<T>,
+}
+
+impl<T> PeekableReceiver<T> {
+    /// Waits for the next message and returns a reference to it, without consuming it: the next
+    /// `recv` or `try_recv` returns the same message.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecvError::Closed`] if the channel is closed and empty.
+    pub async fn peek(&mut self) -> Result<&T, RecvError> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.receiver.recv().await?);
+        }
+        self.peeked.as_ref().ok_or(RecvError::Closed)
+    }
+
+    /// Receives a message from the channel, the peeked one if any.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        match self.peeked.take() {
+            Some(msg) => Ok(msg),
+            None => self.receiver.recv().await,
+        }
+    }
+
+    /// Tries to receive a message from the channel, the peeked one if any.
+    pub fn try_recv(&mut self) -> Result<T, RecvError> {
+        match self.peeked.take() {
+            Some(msg) => Ok(msg),
+            None => self.receiver.try_recv(),
+        }
+    }
+
+    /// Returns the underlying receiver, along with the peeked message if any.
+    #[must_use]
+    pub fn into_inner(self) -> (Receiver<T>, Option<T>) {
+        (self.receiver, self.peeked)
+    }
+}
+
+/// A [`Receiver`] refunding the in-flight byte budget of the upstream receiver when a message is
+/// received (see the `take_budgeted_pdata_receiver` method of the receiver wrapper).
+pub struct BudgetedReceiver<T> {
+    receiver: Receiver<T>,
+    /// The budget of the upstream receiver, if it has one.
+    budget: Option<Arc<InflightBudget>>,
+    /// The function returning the size charged to the budget for a message.
+    pdata_size: Option<fn(&T) -> usize>,
+}
+
+impl<T> BudgetedReceiver<T> {
+    /// Creates a receiver refunding the size of the received messages to the given budget.
+    pub(crate) fn new(
+        receiver: Receiver<T>,
+        budget: Option<Arc<InflightBudget>>,
+        pdata_size: Option<fn(&T) -> usize>,
+    ) -> Self {
+        BudgetedReceiver {
+            receiver,
+            budget,
+            pdata_size,
+        }
+    }
+
+    /// Refunds the size of a received message to the budget.
+    fn refund(&self, msg: &T) {
+        if let (Some(budget), Some(pdata_size)) = (&self.budget, self.pdata_size) {
+            budget.release(pdata_size(msg));
+        }
+    }
+
+    /// Receives a message from the channel.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        let msg = self.receiver.recv().await?;
+        self.refund(&msg);
+        Ok(msg)
+    }
+
+    /// Tries to receive a message from the channel.
+    pub fn try_recv(&mut self) -> Result<T, RecvError> {
+        let msg = self.receiver.try_recv()?;
+        self.refund(&msg);
+        Ok(msg)
+    }
+
+    /// Polls to receive a message from the channel.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let polled = self.receiver.poll_recv(cx);
+        if let Poll::Ready(Ok(msg)) = &polled {
+            self.refund(msg);
+        }
+        polled
+    }
+
+    /// Returns the number of bytes in flight in the channel, 0 if the upstream receiver has no
+    /// budget.
+    #[must_use]
+    pub fn inflight_bytes(&self) -> usize {
+        self.budget
+            .as_ref()
+            .map_or(0, |budget| budget.inflight_bytes())
+    }
+}
+
+/// A receiver multiplexing several upstream channels into one, e.g. to merge the pdata receivers
+/// taken from several receivers (see `ReceiverWrapper::take_pdata_receiver`).
+///
+/// See [`MergedReceiver`] for the polling order, and [`SharedMergingReceiver`] for a `Send`
+/// implementation.
+pub type MergingReceiver<T> = MergedReceiver<Receiver<T>>;
+
+/// A `Send` implementation of the [`MergingReceiver`], merging shared upstream channels.
+pub type SharedMergingReceiver<T> = MergedReceiver<tokio::sync::mpsc::Receiver<T>>;
+
+/// A channel receiver that can be merged with other receivers of the same type by the
+/// [`select_receiver!`](crate::select_receiver) macro.
+pub trait SelectableReceiver {
+    /// The type of the messages received.
+    type Item;
+
+    /// Polls to receive a message from the channel.
+    fn poll_select(&mut self, cx: &mut Context<'_>) -> Poll<Result<Self::Item, RecvError>>;
+}
+
+impl<T> SelectableReceiver for Receiver<T> {
+    type Item = T;
+
+    fn poll_select(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> SelectableReceiver for mpsc::Receiver<T> {
+    type Item = T;
+
+    fn poll_select(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> SelectableReceiver for tokio::sync::mpsc::Receiver<T> {
+    type Item = T;
+
+    fn poll_select(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        self.poll_recv(cx).map(|msg| msg.ok_or(RecvError::Closed))
+    }
+}
+
+/// A receiver merging several upstream channels of the same type, e.g. the one built by the
+/// [`select_receiver!`](crate::select_receiver) macro.
+///
+/// The upstream channels are polled in a round-robin order so that a busy upstream can't starve
+/// the other ones. Closed upstream channels are removed from the set. Awaiting the merged receiver
+/// (or a `&mut` reference to it, to receive several messages) returns the next message received
+/// from any of the upstream channels, or `None` once all of them are closed.
+///
+/// The merged receiver is `Send` if the upstream channels are, i.e. if they are all shared
+/// (`tokio::sync::mpsc::Receiver`).
+pub struct MergedReceiver<R> {
+    receivers: Vec<R>,
+    /// The index of the upstream channel to poll first.
+    next: usize,
+}
+
+impl<R> MergedReceiver<R> {
+    /// Creates a receiver merging the given upstream channels.
+    #[must_use]
+    pub fn new(receivers: Vec<R>) -> Self {
+        MergedReceiver { receivers, next: 0 }
+    }
+
+    /// Adds an upstream channel.
+    pub fn push(&mut self, receiver: R) {
+        self.receivers.push(receiver);
+    }
+
+    /// Returns the number of upstream channels still open.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.receivers.len()
+    }
+
+    /// Returns true if no upstream channel is left.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.receivers.is_empty()
+    }
+}
+
+impl<R: SelectableReceiver> MergedReceiver<R> {
+    /// Receives the next message from any of the upstream channels.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecvError::Closed`] once all the upstream channels are closed.
+    pub async fn recv(&mut self) -> Result<R::Item, RecvError> {
+        std::future::poll_fn(|cx| {
+            poll_round_robin(&mut self.receivers, &mut self.next, cx, R::poll_select)
+        })
+        .await
+    }
+}
+
+impl<R> FromIterator<R> for MergedReceiver<R> {
+    fn from_iter<I: IntoIterator<Item = R>>(receivers: I) -> Self {
+        MergedReceiver::new(receivers.into_iter().collect())
+    }
+}
+
+impl<R: SelectableReceiver + Unpin> Future for MergedReceiver<R> {
+    type Output = Option<R::Item>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_round_robin(&mut this.receivers, &mut this.next, cx, R::poll_select).map(Result::ok)
+    }
+}
+
+/// Merges the given channel receivers into a [`MergedReceiver`], selecting fairly across them.
+///
+/// The receivers must all have the same type:
+/// - local channel receivers (`otap_df_channel::mpsc::Receiver`), for a `!Send` context,
+/// - shared channel receivers (`tokio::sync::mpsc::Receiver`), for a `Send` context,
+/// - or [`Receiver`] values, whose `Local` or `Shared` variant is matched on each poll. The
+///   merged receiver is then `!Send` whatever the variants, use [`Receiver::into_shared`] to merge
+///   them in a `Send` context.
+///
+/// Mixing local and shared channel receivers is rejected at compile time:
+///
+/// ```compile_fail
+/// let (_local_tx, local_rx) = otap_df_channel::mpsc::Channel::<u32>::new(4);
+/// let (_shared_tx, shared_rx) = tokio::sync::mpsc::channel::<u32>(4);
+/// let _merged = otap_df_engine::select_receiver!(local_rx, shared_rx);
+/// ```
+#[macro_export]
+macro_rules! select_receiver {
+    ($($receiver:expr),+ $(,)?) => {
+        $crate::message::MergedReceiver::new(::std::vec![$($receiver),+])
+    };
+}
+
+/// Polls the given receivers starting at `next`, and returns the first message available. `next`
+/// is then moved past the receiver that produced it, the closed receivers are removed.
+fn poll_round_robin<R, T>(
+    receivers: &mut Vec<R>,
+    next: &mut usize,
+    cx: &mut Context<'_>,
+    poll_recv: impl Fn(&mut R, &mut Context<'_>) -> Poll<Result<T, RecvError>>,
+) -> Poll<Result<T, RecvError>> {
+    let mut polled = 0;
+    while polled < receivers.len() {
+        let index = (*next + polled) % receivers.len();
+        match poll_recv(&mut receivers[index], cx) {
+            Poll::Ready(Ok(msg)) => {
+                *next = index + 1;
+                return Poll::Ready(Ok(msg));
+            }
+            Poll::Ready(Err(_)) => {
+                // The receiver at `index` is replaced by the next one, which is polled next.
+                _ = receivers.remove(index);
+                if index < *next {
+                    *next -= 1;
+                }
+            }
+            Poll::Pending => polled += 1,
+        }
+    }
+    if receivers.is_empty() {
+        Poll::Ready(Err(RecvError::Closed))
+    } else {
+        Poll::Pending
+    }
+}
+
+/// Creates a bounded channel delivering the messages for which `is_high_priority` returns true
+/// ahead of the other pending messages, e.g. so that a `Shutdown` doesn't wait behind a backlog of
+/// `TimerTick`s (see [`ControlMsg::is_high_priority`]).
+///
+/// The capacity only bounds the low-priority messages, high-priority messages are expected to be
+/// rare and are never blocked by a backlog. Messages of the same priority are delivered in order.
+///
+/// Both ends of the channel are `Send` as long as `T` is `Send`.
+#[must_use]
+pub fn priority_channel<T>(
+    capacity: usize,
+    is_high_priority: fn(&T) -> bool,
+) -> (PrioritySender<T>, PriorityReceiver<T>) {
+    new_priority_channel(
+        capacity,
+        VecDeque::with_capacity(capacity),
+        is_high_priority,
+    )
+}
 
-    /// A graceful shutdown message requiring the node to finish processing messages and release
-    /// resources by a specified deadline. A deadline of 0 indicates an immediate shutdown.
-    Shutdown {
-        /// The deadline for the shutdown.
-        deadline: Duration,
-        /// The reason for the shutdown.
-        reason: String,
-    },
+/// Creates an unbounded channel delivering the high-priority messages first like
+/// [`priority_channel`]. Sending to the channel never waits, and the capacity reported by its
+/// senders is `usize::MAX`.
+///
+/// Note: Nothing bounds the memory used by the buffered messages.
+#[must_use]
+pub fn unbounded_priority_channel<T>(
+    is_high_priority: fn(&T) -> bool,
+) -> (PrioritySender<T>, PriorityReceiver<T>) {
+    new_priority_channel(usize::MAX, VecDeque::new(), is_high_priority)
 }
 
-impl ControlMsg {
-    /// Checks if this control message is a shutdown message.
-    #[must_use]
-    pub fn is_shutdown(&self) -> bool {
-        matches!(self, ControlMsg::Shutdown { .. })
+fn new_priority_channel<T>(
+    capacity: usize,
+    low: VecDeque<T>,
+    is_high_priority: fn(&T) -> bool,
+) -> (PrioritySender<T>, PriorityReceiver<T>) {
+    let channel = Arc::new(PriorityChannel {
+        state: Mutex::new(PriorityState {
+            high: VecDeque::new(),
+            low,
+            senders: 1,
+            receiver_alive: true,
+        }),
+        capacity,
+        is_high_priority,
+        msg_available: Notify::new(),
+        space_available: Notify::new(),
+    });
+    (
+        PrioritySender {
+            channel: channel.clone(),
+        },
+        PriorityReceiver { channel },
+    )
+}
+
+/// The state shared by both ends of a priority channel: two queues and their notifications.
+struct PriorityChannel<T> {
+    state: Mutex<PriorityState<T>>,
+    capacity: usize,
+    is_high_priority: fn(&T) -> bool,
+    /// Notified when a message is queued or when the last sender is dropped.
+    msg_available: Notify,
+    /// Notified when a low-priority message is dequeued or when the receiver is dropped.
+    space_available: Notify,
+}
+
+struct PriorityState<T> {
+    high: VecDeque<T>,
+    low: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+impl<T> PriorityChannel<T> {
+    fn lock(&self) -> MutexGuard<'_, PriorityState<T>> {
+        // The queues stay consistent even if a thread panicked while holding the lock.
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     }
 }
 
-impl<Data> Message<Data> {
-    /// Create a data message with the given payload.
-    #[must_use]
-    pub fn data_msg(data: Data) -> Self {
-        Message::PData(data)
+/// Sending end of a priority channel (see [`priority_channel`]).
+pub struct PrioritySender<T> {
+    channel: Arc<PriorityChannel<T>>,
+}
+
+impl<T> Clone for PrioritySender<T> {
+    fn clone(&self) -> Self {
+        self.channel.lock().senders += 1;
+        PrioritySender {
+            channel: self.channel.clone(),
+        }
     }
+}
 
-    /// Create a ACK control message with the given ID.
-    #[must_use]
-    pub fn ack_ctrl_msg(id: u64) -> Self {
-        Message::Control(ControlMsg::Ack { id })
+impl<T> Drop for PrioritySender<T> {
+    fn drop(&mut self) {
+        let mut state = self.channel.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.channel.msg_available.notify_one();
+        }
     }
+}
 
-    /// Create a NACK control message with the given ID and reason.
-    #[must_use]
-    pub fn nack_ctrl_msg(id: u64, reason: &str) -> Self {
-        Message::Control(ControlMsg::Nack {
-            id,
-            reason: reason.to_owned(),
-        })
+impl<T> PrioritySender<T> {
+    /// Sends a message to the channel, waiting for some capacity if the message is a low-priority
+    /// message and the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Closed`] if the receiver has been dropped.
+    pub async fn send(&self, mut msg: T) -> Result<(), SendError<T>> {
+        loop {
+            let space_available = self.channel.space_available.notified();
+            tokio::pin!(space_available);
+            // Register for notifications before checking the state to not miss any.
+            _ = space_available.as_mut().enable();
+            match self.try_push(msg) {
+                Ok(result) => return result,
+                Err(unsent) => msg = unsent,
+            }
+            space_available.await;
+        }
     }
 
-    /// Creates a config control message with the given configuration.
-    #[must_use]
-    pub fn config_ctrl_msg(config: serde_json::Value) -> Self {
-        Message::Control(ControlMsg::Config { config })
+    /// Sends a message to the channel, waiting at most `timeout` for some capacity if the message
+    /// is a low-priority message and the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Full`] with the message if the channel stayed full until the timeout
+    /// elapsed, or a [`SendError::Closed`] if the receiver has been dropped.
+    pub async fn send_timeout(&self, mut msg: T, timeout: Duration) -> Result<(), SendError<T>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let space_available = self.channel.space_available.notified();
+            tokio::pin!(space_available);
+            // Register for notifications before checking the state to not miss any.
+            _ = space_available.as_mut().enable();
+            match self.try_push(msg) {
+                Ok(result) => return result,
+                Err(unsent) => msg = unsent,
+            }
+            if tokio::time::timeout_at(deadline, space_available)
+                .await
+                .is_err()
+            {
+                return Err(SendError::Full(msg));
+            }
+        }
     }
 
-    /// Creates a timer tick control message.
+    /// Returns the number of messages currently buffered in the channel.
     #[must_use]
-    pub fn timer_tick_ctrl_msg() -> Self {
-        Message::Control(ControlMsg::TimerTick {})
+    pub fn len(&self) -> usize {
+        let state = self.channel.lock();
+        state.high.len() + state.low.len()
     }
 
-    /// Creates a shutdown control message with the given reason.
+    /// Returns true if no message is currently buffered in the channel.
     #[must_use]
-    pub fn shutdown_ctrl_msg(deadline: Duration, reason: &str) -> Self {
-        Message::Control(ControlMsg::Shutdown {
-            deadline,
-            reason: reason.to_owned(),
-        })
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    /// Checks if this message is a data message.
+    /// Returns the capacity of the channel. Only the low-priority messages are bounded by it, the
+    /// high-priority ones are always accepted.
     #[must_use]
-    pub fn is_data(&self) -> bool {
-        matches!(self, Message::PData(..))
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity
     }
 
-    /// Checks if this message is a control message.
-    #[must_use]
-    pub fn is_control(&self) -> bool {
-        matches!(self, Message::Control(..))
+    /// Sends a message to the channel without waiting, the message is returned back in a
+    /// [`SendError::Full`] if it is a low-priority message and the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Full`] if the channel is full, or a [`SendError::Closed`] if the
+    /// receiver has been dropped.
+    pub fn try_send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.try_push(msg)
+            .unwrap_or_else(|msg| Err(SendError::Full(msg)))
     }
 
-    /// Checks if this message is a shutdown control message.
-    #[must_use]
-    pub fn is_shutdown(&self) -> bool {
-        matches!(self, Message::Control(ControlMsg::Shutdown { .. }))
+    /// Sends a message to the channel without waiting, evicting the oldest low-priority message
+    /// if the channel is full. Returns the evicted message, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SendError::Closed`] if the receiver has been dropped.
+    pub fn force_send(&self, msg: T) -> Result<Option<T>, SendError<T>> {
+        let mut state = self.channel.lock();
+        if !state.receiver_alive {
+            return Err(SendError::Closed(msg));
+        }
+        let mut evicted = None;
+        if (self.channel.is_high_priority)(&msg) {
+            state.high.push_back(msg);
+        } else {
+            if state.low.len() >= self.channel.capacity {
+                evicted = state.low.pop_front();
+            }
+            state.low.push_back(msg);
+        }
+        drop(state);
+        self.channel.msg_available.notify_one();
+        Ok(evicted)
+    }
+
+    /// Queues the message unless the channel is full, in which case the message is returned back.
+    fn try_push(&self, msg: T) -> Result<Result<(), SendError<T>>, T> {
+        let mut state = self.channel.lock();
+        if !state.receiver_alive {
+            return Ok(Err(SendError::Closed(msg)));
+        }
+        if (self.channel.is_high_priority)(&msg) {
+            state.high.push_back(msg);
+        } else if state.low.len() < self.channel.capacity {
+            state.low.push_back(msg);
+        } else {
+            return Err(msg);
+        }
+        drop(state);
+        self.channel.msg_available.notify_one();
+        Ok(Ok(()))
     }
 }
 
-/// A generic channel Sender supporting both local and shared semantic (i.e. !Send and Send).
-pub enum Sender<T> {
-    /// Local channel sender.
-    Local(mpsc::Sender<T>),
-    /// Shared channel sender.
-    Shared(tokio::sync::mpsc::Sender<T>),
+/// Receiving end of a priority channel (see [`priority_channel`]).
+pub struct PriorityReceiver<T> {
+    channel: Arc<PriorityChannel<T>>,
 }
 
-impl<T> Clone for Sender<T> {
-    fn clone(&self) -> Self {
-        match self {
-            Sender::Local(sender) => Sender::Local(sender.clone()),
-            Sender::Shared(sender) => Sender::Shared(sender.clone()),
-        }
+impl<T> Drop for PriorityReceiver<T> {
+    fn drop(&mut self) {
+        self.channel.lock().receiver_alive = false;
+        self.channel.space_available.notify_waiters();
     }
 }
 
-impl<T> Sender<T> {
-    /// Sends a message to the channel.
-    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
-        match self {
-            Sender::Local(sender) => sender.send_async(msg).await,
-            Sender::Shared(sender) => sender.send(msg).await.map_err(|e| SendError::Closed(e.0)),
+impl<T> PriorityReceiver<T> {
+    /// Receives the next message, high-priority messages first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecvError::Closed`] once all the senders have been dropped and the channel is
+    /// empty.
+    pub async fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            {
+                let mut state = self.channel.lock();
+                if let Some(msg) = state.high.pop_front() {
+                    return Ok(msg);
+                }
+                if let Some(msg) = state.low.pop_front() {
+                    drop(state);
+                    self.channel.space_available.notify_waiters();
+                    return Ok(msg);
+                }
+                if state.senders == 0 {
+                    return Err(RecvError::Closed);
+                }
+            }
+            // A single receiver exists, `notify_one` stores a permit if it is not waiting yet.
+            self.channel.msg_available.notified().await;
         }
     }
 }
 
-/// A generic channel Receiver supporting both local and shared semantic (i.e. !Send and Send).
-pub enum Receiver<T> {
-    /// Local channel receiver.
-    Local(mpsc::Receiver<T>),
-    /// Shared channel receiver.
-    Shared(tokio::sync::mpsc::Receiver<T>),
+/// Receiving end of a control channel, implemented by the `!Send` and `Send` channel flavors so
+/// that engine internals can be written once for both while preserving the `Send`-ness of the
+/// resulting futures.
+pub(crate) trait ControlReceiver {
+    /// Receives the next control message, `None` once the channel is closed.
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg>;
 }
 
-impl<T> Receiver<T> {
-    /// Receives a message from the channel.
-    pub async fn recv(&mut self) -> Result<T, RecvError> {
-        match self {
-            Receiver::Local(receiver) => receiver.recv().await,
-            Receiver::Shared(receiver) => receiver.recv().await.ok_or(RecvError::Closed),
-        }
+/// Sending end of a control channel (see [`ControlReceiver`]).
+pub(crate) trait ControlSender {
+    /// Sends a control message, the message is returned back if the channel is closed.
+    async fn send_ctrl(&self, msg: ControlMsg) -> Result<(), ControlMsg>;
+}
+
+impl<R: ControlReceiver> ControlReceiver for &mut R {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        (**self).recv_ctrl().await
     }
+}
 
-    /// Tries to receive a message from the channel.
-    pub fn try_recv(&mut self) -> Result<T, RecvError> {
-        match self {
-            Receiver::Local(receiver) => receiver.try_recv(),
-            Receiver::Shared(receiver) => receiver.try_recv().map_err(|_| RecvError::Closed),
-        }
+impl ControlReceiver for Receiver<ControlMsg> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        self.recv().await.ok()
+    }
+}
+
+impl ControlReceiver for mpsc::Receiver<ControlMsg> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        self.recv().await.ok()
+    }
+}
+
+impl ControlReceiver for PriorityReceiver<ControlMsg> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        self.recv().await.ok()
+    }
+}
+
+impl ControlReceiver for tokio::sync::mpsc::Receiver<ControlMsg> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        self.recv().await
+    }
+}
+
+impl ControlSender for Sender<ControlMsg> {
+    async fn send_ctrl(&self, msg: ControlMsg) -> Result<(), ControlMsg> {
+        self.send(msg).await.map_err(unsent_msg)
+    }
+}
+
+impl ControlSender for SharedSender<ControlMsg> {
+    async fn send_ctrl(&self, msg: ControlMsg) -> Result<(), ControlMsg> {
+        self.send(msg).await.map_err(unsent_msg)
+    }
+}
+
+impl ControlSender for PrioritySender<ControlMsg> {
+    async fn send_ctrl(&self, msg: ControlMsg) -> Result<(), ControlMsg> {
+        self.send(msg).await.map_err(unsent_msg)
+    }
+}
+
+impl ControlSender for mpsc::Sender<ControlMsg> {
+    async fn send_ctrl(&self, msg: ControlMsg) -> Result<(), ControlMsg> {
+        self.send_async(msg).await.map_err(unsent_msg)
+    }
+}
+
+impl ControlSender for tokio::sync::mpsc::Sender<ControlMsg> {
+    async fn send_ctrl(&self, msg: ControlMsg) -> Result<(), ControlMsg> {
+        self.send(msg).await.map_err(|e| e.0)
+    }
+}
+
+/// Returns the message that could not be sent.
+/// Converts the error returned by `try_send` on a Tokio channel.
+pub(crate) fn from_try_send_error<T>(
+    error: tokio::sync::mpsc::error::TrySendError<T>,
+) -> SendError<T> {
+    match error {
+        tokio::sync::mpsc::error::TrySendError::Full(msg) => SendError::Full(msg),
+        tokio::sync::mpsc::error::TrySendError::Closed(msg) => SendError::Closed(msg),
+    }
+}
+
+/// Converts the error returned by a tokio channel on a send with timeout, a timeout is reported as
+/// a full channel.
+pub(crate) fn from_send_timeout_error<T>(
+    error: tokio::sync::mpsc::error::SendTimeoutError<T>,
+) -> SendError<T> {
+    match error {
+        tokio::sync::mpsc::error::SendTimeoutError::Timeout(msg) => SendError::Full(msg),
+        tokio::sync::mpsc::error::SendTimeoutError::Closed(msg) => SendError::Closed(msg),
+    }
+}
+
+/// Sends a message to a downstream of a [`BroadcastSender`] according to its overflow policy.
+/// Only a closed channel is reported as an error, a message dropped by the policy is not.
+async fn send_with_policy<T>(
+    sender: &Sender<T>,
+    overflow_policy: OverflowPolicy,
+    msg: T,
+) -> Result<(), SendError<T>> {
+    match overflow_policy {
+        OverflowPolicy::Block => sender.send(msg).await,
+        OverflowPolicy::DropOldest => sender.force_send(msg).map(|_| ()),
+        OverflowPolicy::DropNewest => match sender.try_send(msg) {
+            Err(SendError::Full(_)) => Ok(()),
+            result => result,
+        },
+    }
+}
+
+fn unsent_msg<T>(error: SendError<T>) -> T {
+    match error {
+        SendError::Full(msg) | SendError::Closed(msg) | SendError::Broadcast { msg, .. } => msg,
     }
 }
 
@@ -203,6 +1508,10 @@ pub struct MessageChannel<PData> {
     shutting_down_deadline: Option<Instant>,
     /// Holds the ControlMsg::Shutdown until after we’ve drained pdata.
     pending_shutdown: Option<ControlMsg>,
+    /// The probe answering the `HealthCheck` messages, if tracked.
+    health: Option<HealthProbe>,
+    /// The counters of the node, incremented for every pdata message delivered to it, if tracked.
+    telemetry: Option<TelemetryCounters>,
 }
 
 impl<PData> MessageChannel<PData> {
@@ -214,9 +1523,24 @@ impl<PData> MessageChannel<PData> {
             pdata_rx: Some(pdata_rx),
             shutting_down_deadline: None,
             pending_shutdown: None,
+            health: None,
+            telemetry: None,
         }
     }
 
+    /// Makes this channel answer the health checks of the node (see [`crate::health`]).
+    pub(crate) fn track_health(mut self, health: HealthProbe) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Makes this channel count the pdata messages delivered to the node (see
+    /// [`crate::telemetry`]).
+    pub(crate) fn track_telemetry(mut self, telemetry: TelemetryCounters) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
     /// Asynchronously receives the next message to process.
     ///
     /// Order of precedence:
@@ -229,6 +1553,8 @@ impl<PData> MessageChannel<PData> {
     /// 3. When the deadline expires (or was `0`): the stored `Shutdown` is returned.
     ///    Subsequent calls return `RecvError::Closed`.
     ///
+    /// The health checks are answered on reception and are not returned.
+    ///
     /// # Errors
     ///
     /// Returns a [`RecvError`] if both channels are closed, or if the
@@ -265,7 +1591,7 @@ impl<PData> MessageChannel<PData> {
 
                     // 1) Any pdata?
                     pdata = self.pdata_rx.as_mut().expect("pdata_rx must exist").recv() => match pdata {
-                        Ok(pdata) => return Ok(Message::PData(pdata)),
+                        Ok(pdata) => return Ok(self.deliver(pdata)),
                         Err(_) => {
                             // pdata channel closed → emit Shutdown
                             let shutdown = self.pending_shutdown
@@ -305,6 +1631,12 @@ impl<PData> MessageChannel<PData> {
                         self.pending_shutdown = Some(ControlMsg::Shutdown { deadline: Duration::ZERO, reason });
                         continue; // re-enter the loop into draining mode
                     }
+                    Ok(ControlMsg::HealthCheck { id }) => {
+                        if let Some(health) = &self.health {
+                            health.answer(id);
+                        }
+                        continue;
+                    }
                     Ok(msg) => return Ok(Message::Control(msg)),
                     Err(e)  => return Err(e),
                 },
@@ -313,7 +1645,7 @@ impl<PData> MessageChannel<PData> {
                 pdata = self.pdata_rx.as_mut().expect("pdata_rx must exist").recv() => {
                     match pdata {
                         Ok(pdata) => {
-                            return Ok(Message::PData(pdata));
+                            return Ok(self.deliver(pdata));
                         }
                         Err(RecvError::Closed) => {
                             // pdata channel closed -> emit Shutdown
@@ -332,9 +1664,308 @@ impl<PData> MessageChannel<PData> {
         }
     }
 
+    fn deliver(&self, pdata: PData) -> Message<PData> {
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record_received();
+        }
+        Message::PData(pdata)
+    }
+
     fn shutdown(&mut self) {
         self.shutting_down_deadline = None;
         drop(self.control_rx.take().expect("control_rx must exist"));
         drop(self.pdata_rx.take().expect("pdata_rx must exist"));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BroadcastSender, MergingReceiver, Receiver, Sender, SharedMergingReceiver};
+    use crate::config::OverflowPolicy;
+    use crate::error::Error;
+    use crate::testing::{TestMsg, create_not_send_channel, setup_test_runtime};
+    use otap_df_channel::error::{RecvError, SendError};
+    use otap_df_channel::mpsc;
+
+    fn downstream() -> (Sender<TestMsg>, Receiver<TestMsg>) {
+        let (tx, rx) = create_not_send_channel(4);
+        (Sender::Local(tx), Receiver::Local(rx))
+    }
+
+    #[test]
+    fn test_broadcast_sender() {
+        let (rt, local_tasks) = setup_test_runtime();
+
+        rt.block_on(local_tasks.run_until(async {
+            let mut broadcast = BroadcastSender::new();
+            let (tx_a, mut rx_a) = downstream();
+            let (tx_b, mut rx_b) = downstream();
+            let (tx_c, rx_c) = downstream();
+            let id_a = broadcast.add_downstream(tx_a);
+            _ = broadcast.add_downstream(tx_b);
+            let id_c = broadcast.add_downstream(tx_c);
+            assert_eq!(broadcast.len(), 3);
+
+            // Every downstream receives a copy of the message.
+            broadcast
+                .send_message(TestMsg::new("to all"))
+                .await
+                .expect("Failed to broadcast");
+            for rx in [&mut rx_a, &mut rx_b] {
+                assert_eq!(rx.recv().await.unwrap(), TestMsg::new("to all"));
+            }
+
+            // A closed downstream is reported, the other ones still receive the message.
+            drop(rx_c);
+            let result = broadcast.send_message(TestMsg::new("one closed")).await;
+            assert!(matches!(
+                result,
+                Err(Error::ChannelSendError(SendError::Broadcast { msg, failed }))
+                    if msg == TestMsg::new("one closed") && failed == vec![id_c]
+            ));
+            for rx in [&mut rx_a, &mut rx_b] {
+                assert_eq!(rx.recv().await.unwrap(), TestMsg::new("one closed"));
+            }
+
+            // A removed downstream doesn't receive the messages anymore.
+            assert!(broadcast.remove_downstream(id_c).is_some());
+            assert!(broadcast.remove_downstream(id_a).is_some());
+            assert!(broadcast.remove_downstream(id_a).is_none());
+            broadcast
+                .send_message(TestMsg::new("to b"))
+                .await
+                .expect("Failed to broadcast");
+            assert_eq!(rx_b.recv().await.unwrap(), TestMsg::new("to b"));
+            assert!(rx_a.try_recv().is_err());
+            assert_eq!(broadcast.len(), 1);
+        }));
+    }
+
+    #[test]
+    fn test_broadcast_sender_overflow_policy() {
+        let (rt, local_tasks) = setup_test_runtime();
+
+        rt.block_on(local_tasks.run_until(async {
+            let mut broadcast = BroadcastSender::new();
+            let (tx_oldest, rx_oldest) = create_not_send_channel(1);
+            let (tx_newest, rx_newest) = create_not_send_channel(1);
+            let (tx_block, mut rx_block) = downstream();
+            _ = broadcast
+                .add_downstream_with_policy(Sender::Local(tx_oldest), OverflowPolicy::DropOldest);
+            _ = broadcast
+                .add_downstream_with_policy(Sender::Local(tx_newest), OverflowPolicy::DropNewest);
+            _ = broadcast.add_downstream(tx_block);
+            let mut rx_oldest = Receiver::Local(rx_oldest);
+            let mut rx_newest = Receiver::Local(rx_newest);
+
+            // The full lossy downstreams don't block the broadcast.
+            for msg in ["first", "second"] {
+                broadcast
+                    .send_message(TestMsg::new(msg))
+                    .await
+                    .expect("Failed to broadcast");
+            }
+            assert_eq!(rx_oldest.recv().await.unwrap(), TestMsg::new("second"));
+            assert_eq!(rx_newest.recv().await.unwrap(), TestMsg::new("first"));
+            assert!(rx_oldest.try_recv().is_err());
+            assert!(rx_newest.try_recv().is_err());
+            for msg in ["first", "second"] {
+                assert_eq!(rx_block.recv().await.unwrap(), TestMsg::new(msg));
+            }
+        }));
+    }
+
+    async fn assert_peek(tx: Sender<TestMsg>, rx: Receiver<TestMsg>) {
+        let mut rx = rx.peekable();
+        tx.send(TestMsg::new("first")).await.unwrap();
+        tx.send(TestMsg::new("second")).await.unwrap();
+
+        // Peeking doesn't consume the message, however many times.
+        assert_eq!(rx.peek().await.unwrap(), &TestMsg::new("first"));
+        assert_eq!(rx.peek().await.unwrap(), &TestMsg::new("first"));
+        assert_eq!(rx.recv().await.unwrap(), TestMsg::new("first"));
+        assert_eq!(rx.peek().await.unwrap(), &TestMsg::new("second"));
+        assert_eq!(rx.try_recv().unwrap(), TestMsg::new("second"));
+
+        drop(tx);
+        assert!(matches!(rx.peek().await, Err(RecvError::Closed)));
+    }
+
+    #[test]
+    fn test_peekable_receiver_local() {
+        let (rt, local_tasks) = setup_test_runtime();
+        rt.block_on(local_tasks.run_until(async {
+            let (tx, rx) = downstream();
+            assert_peek(tx, rx).await;
+        }));
+    }
+
+    #[test]
+    fn test_peekable_receiver_shared() {
+        let (rt, _) = setup_test_runtime();
+        rt.block_on(async {
+            let (tx, rx) = tokio::sync::mpsc::channel(4);
+            assert_peek(Sender::Shared(tx), Receiver::Shared(rx)).await;
+        });
+    }
+
+    #[test]
+    fn test_merging_receiver_round_robin() {
+        let (rt, local_tasks) = setup_test_runtime();
+
+        rt.block_on(local_tasks.run_until(async {
+            let (tx_a, rx_a) = downstream();
+            let (tx_b, rx_b) = downstream();
+            let mut merged: MergingReceiver<TestMsg> = [rx_a, rx_b].into_iter().collect();
+
+            // The busy upstream doesn't starve the other one.
+            for i in 0..4 {
+                tx_a.send(TestMsg::new(format!("a{i}"))).await.unwrap();
+            }
+            tx_b.send(TestMsg::new("b0")).await.unwrap();
+            let mut received = Vec::new();
+            for _ in 0..5 {
+                received.push(merged.recv().await.unwrap().0);
+            }
+            assert_eq!(received, ["a0", "b0", "a1", "a2", "a3"]);
+
+            // Closed upstreams are removed, the merged receiver closes with the last one.
+            drop(tx_a);
+            tx_b.send(TestMsg::new("b1")).await.unwrap();
+            assert_eq!(merged.recv().await.unwrap(), TestMsg::new("b1"));
+            drop(tx_b);
+            assert!(matches!(merged.recv().await, Err(RecvError::Closed)));
+            assert!(merged.is_empty());
+        }));
+    }
+
+    #[test]
+    fn test_shared_merging_receiver_wakes_on_any_upstream() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let (tx_a, rx_a) = tokio::sync::mpsc::channel(4);
+            let (tx_b, rx_b) = tokio::sync::mpsc::channel(4);
+            let mut merged = SharedMergingReceiver::new(vec![rx_a]);
+            merged.push(
+                Receiver::Shared(rx_b)
+                    .into_shared()
+                    .expect("Shared receiver expected"),
+            );
+
+            let recv = tokio::spawn(async move {
+                let msg = merged.recv().await;
+                (msg, merged)
+            });
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            tx_b.send(TestMsg::new("b0")).await.unwrap();
+            let (msg, mut merged) = recv.await.unwrap();
+            assert_eq!(msg.unwrap(), TestMsg::new("b0"));
+
+            drop((tx_a, tx_b));
+            assert!(matches!(merged.recv().await, Err(RecvError::Closed)));
+        });
+    }
+
+    #[test]
+    fn test_select_receiver_local() {
+        let (rt, local_tasks) = setup_test_runtime();
+
+        rt.block_on(local_tasks.run_until(async {
+            let (tx_a, rx_a) = mpsc::Channel::new(4);
+            let (tx_b, rx_b) = mpsc::Channel::new(4);
+            let mut merged = crate::select_receiver!(rx_a, rx_b);
+
+            for i in 0..3 {
+                tx_a.send(TestMsg::new(format!("a{i}"))).unwrap();
+            }
+            tx_b.send(TestMsg::new("b0")).unwrap();
+            let mut received = Vec::new();
+            for _ in 0..4 {
+                received.push((&mut merged).await.unwrap().0);
+            }
+            assert_eq!(received, ["a0", "b0", "a1", "a2"]);
+
+            drop((tx_a, tx_b));
+            assert!((&mut merged).await.is_none());
+            assert!(merged.is_empty());
+        }));
+    }
+
+    #[test]
+    fn test_select_receiver_shared() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let (tx_a, rx_a) = tokio::sync::mpsc::channel(4);
+            let (tx_b, rx_b) = tokio::sync::mpsc::channel(4);
+            let mut merged = crate::select_receiver!(
+                rx_a,
+                Receiver::Shared(rx_b)
+                    .into_shared()
+                    .expect("Shared receiver expected"),
+            );
+
+            // The merged receiver is `Send`, it can be moved to another task.
+            let recv = tokio::spawn(async move {
+                let msg = (&mut merged).await;
+                (msg, merged)
+            });
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            tx_b.send(TestMsg::new("b0")).await.unwrap();
+            let (msg, merged) = recv.await.unwrap();
+            assert_eq!(msg, Some(TestMsg::new("b0")));
+
+            drop((tx_a, tx_b));
+            assert!(merged.await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_select_receiver_enum_variants() {
+        let (rt, local_tasks) = setup_test_runtime();
+
+        rt.block_on(local_tasks.run_until(async {
+            let (tx_local, rx_local) = mpsc::Channel::new(4);
+            let (tx_shared, rx_shared) = tokio::sync::mpsc::channel(4);
+            let mut merged =
+                crate::select_receiver!(Receiver::Local(rx_local), Receiver::Shared(rx_shared));
+
+            tx_shared.send(TestMsg::new("shared")).await.unwrap();
+            tx_local.send(TestMsg::new("local")).unwrap();
+            assert_eq!((&mut merged).await, Some(TestMsg::new("local")));
+            assert_eq!((&mut merged).await, Some(TestMsg::new("shared")));
+
+            drop(tx_local);
+            drop(tx_shared);
+            assert!(merged.await.is_none());
+        }));
+    }
+
+    #[test]
+    fn test_ring_sender_drops_oldest() {
+        let (rt, local_tasks) = setup_test_runtime();
+
+        rt.block_on(local_tasks.run_until(async {
+            let (tx, mut rx) = Sender::ring(2);
+            for i in 0..3 {
+                // Never waits, even once the channel is full.
+                tx.send(TestMsg::new(format!("msg{i}"))).await.unwrap();
+            }
+            assert_eq!(tx.len(), 2);
+            assert_eq!(
+                tx.force_send(TestMsg::new("msg3")).unwrap(),
+                Some(TestMsg::new("msg1"))
+            );
+            assert_eq!(rx.recv().await.unwrap(), TestMsg::new("msg2"));
+            assert_eq!(rx.recv().await.unwrap(), TestMsg::new("msg3"));
+            assert!(tx.into_shared().is_none());
+        }));
+    }
+}
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Outbound TCP connections of exporters, reconnecting when the connection is lost.
+//!
+//! An exporter opens an [`OutboundConnection`] with the `connect` method of its effect handler,
+//! which dials the endpoint according to a [`RetryPolicy`]: the attempts are spaced by an
+//! exponential backoff with a random jitter, so that the exporters of several pipelines don't
+//! hammer a recovering endpoint in lockstep. Once connected, a write failing because the
+//! connection was lost drops the socket, dials the endpoint again and writes the payload over the
+//! new connection, within the same retry policy.
+//!
+//! Protocols starting every connection with a preamble (e.g. the schema of an Arrow IPC stream)
+//! can't resend a payload as is over a new connection, they write through
+//! [`OutboundConnection::write_with`] which tells them whether the payload is the first one of the
+//! connection.
+
+use crate::config::RetryPolicy;
+use crate::effect_handler::EffectHandlerCore;
+use crate::error::ConnectError;
+use crate::logging::LogLevel;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// A TCP connection to an endpoint, dialed again when it is lost.
+///
+/// Note: This implementation is `Send`.
+pub struct OutboundConnection {
+    addr: SocketAddr,
+    policy: RetryPolicy,
+    /// The current socket, `None` once the connection was lost.
+    socket: Option<TcpStream>,
+    /// True once a payload was written to the current socket.
+    written: bool,
+    /// The core of the effect handler of the exporter, logging the lost connections.
+    core: EffectHandlerCore,
+}
+
+impl OutboundConnection {
+    /// Dials the given endpoint according to the retry policy.
+    pub(crate) async fn connect(
+        core: EffectHandlerCore,
+        addr: SocketAddr,
+        policy: RetryPolicy,
+    ) -> Result<Self, ConnectError> {
+        let mut connection = OutboundConnection {
+            addr,
+            policy,
+            socket: None,
+            written: false,
+            core,
+        };
+        connection.socket = Some(connection.dial().await?);
+        Ok(connection)
+    }
+
+    /// Returns the address of the endpoint.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Writes the given bytes to the endpoint, reconnecting if the connection is lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConnectError::Unreachable`] if the retry policy gave up.
+    pub async fn write_all(&mut self, bytes: &[u8]) -> Result<(), ConnectError> {
+        self.write_with(|_| Ok(bytes.to_vec())).await
+    }
+
+    /// Writes the payload returned by the given function to the endpoint, reconnecting if the
+    /// connection is lost. The function is called once per attempt, with `true` if nothing was
+    /// written to the current connection yet, i.e. if the payload must start with the preamble of
+    /// the protocol.
+    ///
+    /// The failed writes are counted against the `max_attempts` of the retry policy, in addition
+    /// to the connection attempts of each reconnection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the payload function, or a [`ConnectError::Unreachable`] (converted to
+    /// the error type of the function) if the retry policy gave up.
+    pub async fn write_with<E>(
+        &mut self,
+        mut payload: impl FnMut(bool) -> Result<Vec<u8>, E>,
+    ) -> Result<(), E>
+    where
+        E: From<ConnectError>,
+    {
+        let mut failures = 0;
+        loop {
+            let socket = match &mut self.socket {
+                Some(socket) => socket,
+                None => {
+                    self.written = false;
+                    self.socket.insert(self.dial().await?)
+                }
+            };
+            let bytes = payload(!self.written)?;
+            let written = match socket.write_all(&bytes).await {
+                Ok(()) => socket.flush().await,
+                Err(error) => Err(error),
+            };
+            match written {
+                Ok(()) => {
+                    self.written = true;
+                    return Ok(());
+                }
+                Err(error) => {
+                    self.socket = None;
+                    failures += 1;
+                    if failures >= self.policy.max_attempts {
+                        return Err(ConnectError::Unreachable {
+                            addr: self.addr,
+                            attempts: failures,
+                            error,
+                        }
+                        .into());
+                    }
+                    self.core.log(
+                        LogLevel::Warn,
+                        &format!("Lost the connection to {}: {error}", self.addr),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Connects to the endpoint, waiting for the jittered backoff of the attempt between two
+    /// attempts. At least one attempt is made.
+    async fn dial(&self) -> Result<TcpStream, ConnectError> {
+        let mut attempt = 0;
+        loop {
+            if attempt > 0 {
+                tokio::time::sleep(jittered(self.policy.backoff(attempt))).await;
+            }
+            attempt += 1;
+            match TcpStream::connect(self.addr).await {
+                Ok(socket) => return Ok(socket),
+                Err(error) if attempt >= self.policy.max_attempts => {
+                    return Err(ConnectError::Unreachable {
+                        addr: self.addr,
+                        attempts: attempt,
+                        error,
+                    });
+                }
+                Err(error) => self.core.log(
+                    LogLevel::Warn,
+                    &format!(
+                        "Failed to connect to {} (attempt {attempt}): {error}",
+                        self.addr
+                    ),
+                ),
+            }
+        }
+    }
+}
+
+/// Returns a random delay between half the given backoff and the backoff itself.
+fn jittered(backoff: Duration) -> Duration {
+    let half_nanos = u64::try_from(backoff.as_nanos() / 2).unwrap_or(u64::MAX);
+    backoff.saturating_sub(Duration::from_nanos(fastrand::u64(0..=half_nanos)))
+}
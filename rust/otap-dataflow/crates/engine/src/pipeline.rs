@@ -1,192 +1,2688 @@
 // SPDX-License-Identifier: Apache-2.0
 
-//! A pipeline is a collection of receivers, processors, and exporters.
+//! A pipeline is a chain of a receiver, zero or more processors, and an exporter. The output of a
+//! stage can also fan out to several chains of processors ending at an exporter (see
+//! [`PipelineBuilder::fan_out`]).
 //!
-//! Important note: This is a work in progress and not yet fully implemented.
+//! Pipelines are created with a [`PipelineBuilder`] which takes care of the pdata channel
+//! hand-offs between consecutive stages, and run with [`Pipeline::run`] which returns a
+//! [`PipelineHandle`] to shut the pipeline down and wait for its completion.
+//!
+//! Important note: This is a work in progress, fan-in is not supported for now.
 
-use crate::config::{ExporterConfig, ProcessorConfig, ReceiverConfig};
-use crate::error::Error;
+use crate::buffer_pool::GlobalBufferPool;
+use crate::config::validate_node_name;
+use crate::error::{Error, ShutdownError};
 use crate::exporter::ExporterWrapper;
+use crate::fanin::{MergeInput, SourceTagged, run_merge};
+use crate::fanout::{DispatchPolicy, run_fanout};
+use crate::graph::PipelineGraph;
+use crate::health::PipelineHealth;
+use crate::health::{HealthProbe, HealthStatus};
+use crate::logging::LogSink;
+use crate::message::{ControlMsg, Receiver, Sender};
 use crate::processor::ProcessorWrapper;
 use crate::receiver::ReceiverWrapper;
+use crate::runtime::PlacedReceiver;
+use crate::telemetry::ShutdownReport;
+use otap_df_channel::error::SendError;
+use otap_df_channel::mpsc;
+use otap_df_config::NodeKind;
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
-use tokio::runtime::Builder;
-use tokio::task::LocalSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
 
-/// A pipeline is a collection of receivers, processors, and exporters.
-pub struct Pipeline<PData> {
-    receivers: HashMap<Cow<'static, str>, ReceiverWrapper<PData>>,
-    processors: HashMap<Cow<'static, str>, ProcessorWrapper<PData>>,
-    exporters: HashMap<Cow<'static, str>, ExporterWrapper<PData>>,
+/// A stage located between the receiver and the exporter of a pipeline.
+enum Stage<PData> {
+    /// A processor.
+    Processor(Box<ProcessorWrapper<PData>>),
+    /// A bridge forwarding the pdata emitted by a local stage to a shared stage.
+    Bridge {
+        /// Capacity of the shared channel feeding the next stage.
+        capacity: usize,
+    },
 }
 
-impl<PData> Pipeline<PData> {
-    /// Adds a receiver to the pipeline.
-    pub fn add_receiver<R>(
-        &mut self,
+/// A node of a pipeline, added by name (see [`PipelineBuilder::add_receiver`]) or appended in
+/// order.
+enum Node<PData> {
+    Receiver(StageReceiver<PData>),
+    Processor(Box<ProcessorWrapper<PData>>),
+    Exporter(Box<ExporterWrapper<PData>>),
+}
+
+impl<PData> Node<PData> {
+    /// Returns the kind of the node, as printed in the errors.
+    fn kind(&self) -> &'static str {
+        match self {
+            Node::Receiver(_) => "receiver",
+            Node::Processor(_) => "processor",
+            Node::Exporter(_) => "exporter",
+        }
+    }
+
+    /// Returns the name of the wrapped stage.
+    fn name(&self) -> Cow<'static, str> {
+        match self {
+            Node::Receiver(receiver) => receiver.name(),
+            Node::Processor(processor) => processor.name(),
+            Node::Exporter(exporter) => exporter.name(),
+        }
+    }
+
+    /// Returns whether the wrapped stage is a `Local` stage.
+    fn is_local(&self) -> bool {
+        match self {
+            Node::Receiver(receiver) => receiver.is_local(),
+            Node::Processor(processor) => matches!(**processor, ProcessorWrapper::Local { .. }),
+            Node::Exporter(exporter) => matches!(**exporter, ExporterWrapper::Local { .. }),
+        }
+    }
+}
+
+/// The receiver of a pipeline, run on the runtime of the pipeline or placed on a scheduler thread
+/// (see [`crate::runtime`]).
+enum StageReceiver<PData> {
+    Wrapper(Box<ReceiverWrapper<PData>>),
+    /// A placed receiver is a `Shared` stage for the rest of the pipeline: its pdata and control
+    /// channels cross threads.
+    Placed(Box<PlacedReceiver<PData>>),
+}
+
+impl<PData> StageReceiver<PData> {
+    fn name(&self) -> Cow<'static, str> {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.name(),
+            StageReceiver::Placed(receiver) => receiver.name(),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        matches!(self, StageReceiver::Wrapper(receiver) if matches!(**receiver, ReceiverWrapper::Local { .. }))
+    }
+
+    fn set_log_sink(&mut self, sink: Arc<dyn LogSink>, pipeline_id: Option<Cow<'static, str>>) {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.set_log_sink(sink, pipeline_id),
+            StageReceiver::Placed(receiver) => receiver.set_log_sink(sink, pipeline_id),
+        }
+    }
+
+    fn has_pdata_receiver(&self) -> bool {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.has_pdata_receiver(),
+            StageReceiver::Placed(receiver) => receiver.has_pdata_receiver(),
+        }
+    }
+
+    fn take_pdata_receiver(&mut self) -> Result<Receiver<PData>, Error<PData>> {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.take_pdata_receiver(),
+            StageReceiver::Placed(receiver) => receiver.take_pdata_receiver(),
+        }
+    }
+
+    fn control_sender(&self) -> Sender<ControlMsg> {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.control_sender(),
+            StageReceiver::Placed(receiver) => receiver.control_sender(),
+        }
+    }
+
+    fn health_probe(&self) -> HealthProbe {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.health_probe(),
+            StageReceiver::Placed(receiver) => receiver.health_probe(),
+        }
+    }
+
+    fn subscribe_health(&self) -> watch::Receiver<HealthStatus> {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.subscribe_health(),
+            StageReceiver::Placed(receiver) => receiver.subscribe_health(),
+        }
+    }
+
+    fn subscribe_shutdown_report(&self) -> watch::Receiver<Option<ShutdownReport>> {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.subscribe_shutdown_report(),
+            StageReceiver::Placed(receiver) => receiver.subscribe_shutdown_report(),
+        }
+    }
+
+    /// Spawns the receiver, a placed receiver being started on its scheduler thread.
+    fn spawn(self) -> JoinHandle<Result<(), Error<PData>>>
+    where
+        PData: Send + 'static,
+    {
+        match self {
+            StageReceiver::Wrapper(receiver) => receiver.spawn(),
+            StageReceiver::Placed(receiver) => tokio::task::spawn_local(receiver.start()),
+        }
+    }
+}
+
+/// A pdata connection between two nodes added by name (see [`PipelineBuilder::connect`]).
+struct Connection {
+    from: Cow<'static, str>,
+    to: Cow<'static, str>,
+    /// Capacity of the bridge between the nodes, if any.
+    bridge: Option<usize>,
+}
+
+/// A fan-out of the pdata output of a node added by name (see [`PipelineBuilder::fan_out`]).
+struct FanOut<PData> {
+    from: Cow<'static, str>,
+    to: Vec<Cow<'static, str>>,
+    policy: DispatchPolicy,
+    clone: fn(&PData) -> PData,
+    /// Capacity of the input channel of every downstream node.
+    capacity: usize,
+}
+
+/// A fan-in of the pdata outputs of nodes added by name into the input of a node (see
+/// [`PipelineBuilder::fan_in`]).
+struct FanIn<PData> {
+    from: Vec<Cow<'static, str>>,
+    to: Cow<'static, str>,
+    /// Capacity of the input channel of the downstream node.
+    capacity: usize,
+    /// Tags the merged pdata with the name of its upstream node, if any (see
+    /// [`PipelineBuilder::fan_in_tagged`]).
+    tag: Option<fn(&mut PData, Cow<'static, str>)>,
+}
+
+/// A stage wired to its input pdata channel.
+enum WiredStage<PData> {
+    /// A processor connected to its input pdata channel.
+    Processor(Box<ProcessorWrapper<PData>>),
+    /// A bridge between a local stage and a shared stage.
+    Bridge {
+        pdata_rx: Receiver<PData>,
+        pdata_tx: tokio::sync::mpsc::Sender<PData>,
+    },
+    /// A fan-out dispatching its input pdata to the input channels of several stages.
+    FanOut {
+        pdata_rx: Receiver<PData>,
+        outputs: Vec<Sender<PData>>,
+        policy: DispatchPolicy,
+        clone: fn(&PData) -> PData,
+    },
+    /// A fan-in merging the output pdata of several stages into the input channel of a stage.
+    FanIn {
+        inputs: Vec<MergeInput<PData>>,
+        pdata_tx: Sender<PData>,
+    },
+}
+
+/// A builder of pipelines: a receiver, followed by zero or more processors, followed by an
+/// exporter. The nodes added by name can also fan out to several chains of processors and
+/// exporters (see [`PipelineBuilder::fan_out`]), and several receivers and chains can fan in to a
+/// processor or an exporter (see [`PipelineBuilder::fan_in`]).
+///
+/// The pdata emitted by a `Local` stage can only be consumed by a `Local` stage. A `Shared` stage
+/// can follow a `Local` stage only if they are separated by an explicit bridge (see
+/// [`PipelineBuilder::bridge`]), a fan-out or a fan-in.
+///
+/// The exporters are connected to the control channel of the receiver they are fed by so that
+/// they can throttle the ingestion (see [`crate::message::ControlMsg::Throttle`]). Behind a fan-in,
+/// that is the receiver feeding its first upstream node.
+///
+/// The wiring of the stages is recorded in the graph of the built pipeline (see
+/// [`Pipeline::graph`]).
+///
+/// The stages can be appended in order (see [`PipelineBuilder::receiver`],
+/// [`PipelineBuilder::processor`] and [`PipelineBuilder::exporter`]), or added by name and
+/// connected explicitly (see [`PipelineBuilder::add_receiver`] and [`PipelineBuilder::connect`]),
+/// in which case the builder orders them along their connections. Both styles can't be mixed in a
+/// pipeline. All the stages share the same pdata type, so the pdata types of connected stages
+/// always line up.
+pub struct PipelineBuilder<PData> {
+    receivers: Vec<ReceiverWrapper<PData>>,
+    stages: Vec<Stage<PData>>,
+    exporters: Vec<ExporterWrapper<PData>>,
+    /// The nodes added by name, and their connections.
+    nodes: Vec<(Cow<'static, str>, Node<PData>)>,
+    connections: Vec<Connection>,
+    fan_outs: Vec<FanOut<PData>>,
+    fan_ins: Vec<FanIn<PData>>,
+    /// The sink of the messages logged by the stages, and the id of the pipeline they are tagged
+    /// with (see [`crate::logging`]).
+    log_sink: Option<(Arc<dyn LogSink>, Cow<'static, str>)>,
+    /// The buffer pools the receivers can borrow from, by name (see [`crate::buffer_pool`]).
+    buffer_pools: HashMap<String, Arc<GlobalBufferPool>>,
+}
+
+impl<PData> Default for PipelineBuilder<PData> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<PData> PipelineBuilder<PData> {
+    /// Creates a new empty pipeline builder.
+    #[must_use]
+    pub fn new() -> Self {
+        PipelineBuilder {
+            receivers: Vec::new(),
+            stages: Vec::new(),
+            exporters: Vec::new(),
+            nodes: Vec::new(),
+            connections: Vec::new(),
+            fan_outs: Vec::new(),
+            fan_ins: Vec::new(),
+            log_sink: None,
+            buffer_pools: HashMap::new(),
+        }
+    }
+
+    /// Sets the receiver of the pipeline.
+    #[must_use]
+    pub fn receiver(mut self, receiver: ReceiverWrapper<PData>) -> Self {
+        self.receivers.push(receiver);
+        self
+    }
+
+    /// Appends a processor to the pipeline.
+    #[must_use]
+    pub fn processor(mut self, processor: ProcessorWrapper<PData>) -> Self {
+        self.stages.push(Stage::Processor(Box::new(processor)));
+        self
+    }
+
+    /// Appends a bridge to the pipeline, allowing the next stage to be a `Shared` stage when the
+    /// previous one is a `Local` stage. The bridge forwards pdata over a shared channel of the
+    /// given capacity.
+    #[must_use]
+    pub fn bridge(mut self, capacity: usize) -> Self {
+        self.stages.push(Stage::Bridge { capacity });
+        self
+    }
+
+    /// Sets the exporter of the pipeline.
+    #[must_use]
+    pub fn exporter(mut self, exporter: ExporterWrapper<PData>) -> Self {
+        self.exporters.push(exporter);
+        self
+    }
+
+    /// Adds a receiver to the pipeline, referred to by the given name in the connections (see
+    /// [`PipelineBuilder::connect`]).
+    #[must_use]
+    pub fn add_receiver(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
         receiver: ReceiverWrapper<PData>,
-        config: &ReceiverConfig,
-    ) -> Result<(), Error<PData>> {
-        let receiver_name = config.name.clone();
-        if self
-            .receivers
-            .insert(config.name.clone(), receiver)
-            .is_some()
-        {
-            return Err(Error::ReceiverAlreadyExists {
-                receiver: receiver_name,
+    ) -> Self {
+        self.nodes.push((
+            name.into(),
+            Node::Receiver(StageReceiver::Wrapper(Box::new(receiver))),
+        ));
+        self
+    }
+
+    /// Adds a `Local` receiver placed on a scheduler thread (see
+    /// [`crate::runtime::CoreScheduler::spawn_local_node`]) to the pipeline, referred to by the
+    /// given name in the connections (see [`PipelineBuilder::connect`]). The receiver starts on
+    /// its scheduler thread when the pipeline runs.
+    ///
+    /// Its pdata are forwarded to a shared channel, the placed receiver is therefore wired as a
+    /// `Shared` stage: it can feed a `Shared` stage without a bridge.
+    #[must_use]
+    pub fn add_placed_receiver(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        receiver: PlacedReceiver<PData>,
+    ) -> Self {
+        self.nodes.push((
+            name.into(),
+            Node::Receiver(StageReceiver::Placed(Box::new(receiver))),
+        ));
+        self
+    }
+
+    /// Adds a processor to the pipeline, referred to by the given name in the connections (see
+    /// [`PipelineBuilder::connect`]).
+    #[must_use]
+    pub fn add_processor(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        processor: ProcessorWrapper<PData>,
+    ) -> Self {
+        self.nodes
+            .push((name.into(), Node::Processor(Box::new(processor))));
+        self
+    }
+
+    /// Adds an exporter to the pipeline, referred to by the given name in the connections (see
+    /// [`PipelineBuilder::connect`]).
+    #[must_use]
+    pub fn add_exporter(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        exporter: ExporterWrapper<PData>,
+    ) -> Self {
+        self.nodes
+            .push((name.into(), Node::Exporter(Box::new(exporter))));
+        self
+    }
+
+    /// Connects the pdata output of the node named `from` to the input of the node named `to`.
+    /// The connections are validated when the pipeline is built.
+    #[must_use]
+    pub fn connect(
+        mut self,
+        from: impl Into<Cow<'static, str>>,
+        to: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.connections.push(Connection {
+            from: from.into(),
+            to: to.into(),
+            bridge: None,
+        });
+        self
+    }
+
+    /// Connects the pdata output of the node named `from` to the input of the node named `to`
+    /// through a bridge of the given capacity (see [`PipelineBuilder::bridge`]), allowing `to` to
+    /// be a `Shared` node when `from` is a `Local` node.
+    #[must_use]
+    pub fn connect_via_bridge(
+        mut self,
+        from: impl Into<Cow<'static, str>>,
+        to: impl Into<Cow<'static, str>>,
+        capacity: usize,
+    ) -> Self {
+        self.connections.push(Connection {
+            from: from.into(),
+            to: to.into(),
+            bridge: Some(capacity),
+        });
+        self
+    }
+
+    /// Dispatches the pdata output of the node named `from` to the nodes named `to`, according to
+    /// the given policy (see [`crate::fanout`]). Every downstream node is fed by a channel of the
+    /// given capacity, shared if the node is a `Shared` node, so no bridge is needed after a
+    /// `Local` node. The fan-out is validated when the pipeline is built.
+    #[must_use]
+    pub fn fan_out<S: Into<Cow<'static, str>>>(
+        mut self,
+        from: impl Into<Cow<'static, str>>,
+        to: impl IntoIterator<Item = S>,
+        policy: DispatchPolicy,
+        capacity: usize,
+    ) -> Self
+    where
+        PData: Clone,
+    {
+        self.fan_outs.push(FanOut {
+            from: from.into(),
+            to: to.into_iter().map(Into::into).collect(),
+            policy,
+            clone: PData::clone,
+            capacity,
+        });
+        self
+    }
+
+    /// Merges the pdata outputs of the nodes named `from` into the input of the node named `to`
+    /// (see [`crate::fanin`]). The upstream nodes are polled in turn so that none of them starves
+    /// the other ones, and the merged input is only closed once all of them are closed. The node
+    /// is fed by a channel of the given capacity, shared if the node is a `Shared` node, so no
+    /// bridge is needed after a `Local` node. The fan-in is validated when the pipeline is built.
+    #[must_use]
+    pub fn fan_in<S: Into<Cow<'static, str>>>(
+        mut self,
+        from: impl IntoIterator<Item = S>,
+        to: impl Into<Cow<'static, str>>,
+        capacity: usize,
+    ) -> Self {
+        self.fan_ins.push(FanIn {
+            from: from.into_iter().map(Into::into).collect(),
+            to: to.into(),
+            capacity,
+            tag: None,
+        });
+        self
+    }
+
+    /// Emits the messages logged by all the stages of the pipeline to the given sink, tagged with
+    /// the given pipeline id (see [`crate::logging`]).
+    #[must_use]
+    pub fn log_sink(
+        mut self,
+        pipeline_id: impl Into<Cow<'static, str>>,
+        sink: Arc<dyn LogSink>,
+    ) -> Self {
+        self.log_sink = Some((sink, pipeline_id.into()));
+        self
+    }
+
+    /// Registers a buffer pool under the given name, bounding the messages buffered in the output
+    /// channels of the receivers naming it in their configuration (see [`crate::buffer_pool`]).
+    #[must_use]
+    pub fn buffer_pool(mut self, name: impl Into<String>, pool: GlobalBufferPool) -> Self {
+        _ = self.buffer_pools.insert(name.into(), Arc::new(pool));
+        self
+    }
+
+    /// Validates the pipeline and connects the pdata channels of consecutive stages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::PipelineError`] if the connections, fan-outs and fan-ins of the nodes
+    /// added by name refer to unknown nodes, form a cycle, leave the output of a node unconsumed or
+    /// a node without input, or feed a node with several inputs outside of a fan-in, if the
+    /// pipeline has no receiver or, when the stages are appended in order, more than one receiver
+    /// or exactly one exporter, if the name of a stage is invalid (see [`validate_node_name`]) or
+    /// shared with another stage, if a `Shared` stage follows a `Local` stage without a bridge, or
+    /// if a receiver names an unknown buffer pool or one that can't reserve its slots.
+    ///
+    /// Note: The receivers placed on a scheduler thread don't borrow from their buffer pool.
+    pub fn build(self) -> Result<Pipeline<PData>, Error<PData>>
+    where
+        PData: 'static,
+    {
+        let PipelineBuilder {
+            receivers,
+            stages,
+            exporters,
+            nodes,
+            connections,
+            fan_outs,
+            fan_ins,
+            log_sink,
+            buffer_pools,
+        } = self;
+
+        let in_order = !receivers.is_empty() || !stages.is_empty() || !exporters.is_empty();
+        let pipeline = if in_order {
+            if !nodes.is_empty()
+                || !connections.is_empty()
+                || !fan_outs.is_empty()
+                || !fan_ins.is_empty()
+            {
+                return Err(Error::PipelineError {
+                    error: "The nodes added by name can't be mixed with the stages appended in \
+                            order"
+                        .to_owned(),
+                });
+            }
+            let (mut nodes, connections) = linear_nodes(receivers, stages, exporters)?;
+            join_buffer_pools(&mut nodes, &buffer_pools)?;
+            wire_nodes(
+                nodes,
+                &connections,
+                Vec::new(),
+                Vec::new(),
+                log_sink.as_ref(),
+            )?
+        } else {
+            let mut nodes = nodes;
+            join_buffer_pools(&mut nodes, &buffer_pools)?;
+            wire_nodes(nodes, &connections, fan_outs, fan_ins, log_sink.as_ref())?
+        };
+        check_stage_names(pipeline.control_senders().into_iter().map(|(name, _)| name))?;
+        Ok(pipeline)
+    }
+}
+
+impl<PData> PipelineBuilder<SourceTagged<PData>> {
+    /// Merges the pdata outputs of the nodes named `from` into the input of the node named `to`
+    /// like [`PipelineBuilder::fan_in`], tagging every message with the name of the upstream node
+    /// it comes from.
+    #[must_use]
+    pub fn fan_in_tagged<S: Into<Cow<'static, str>>>(
+        mut self,
+        from: impl IntoIterator<Item = S>,
+        to: impl Into<Cow<'static, str>>,
+        capacity: usize,
+    ) -> Self {
+        self.fan_ins.push(FanIn {
+            from: from.into_iter().map(Into::into).collect(),
+            to: to.into(),
+            capacity,
+            tag: Some(SourceTagged::tag),
+        });
+        self
+    }
+}
+
+/// Converts the stages appended in order into nodes connected in sequence.
+fn linear_nodes<PData>(
+    mut receivers: Vec<ReceiverWrapper<PData>>,
+    stages: Vec<Stage<PData>>,
+    mut exporters: Vec<ExporterWrapper<PData>>,
+) -> Result<(Vec<(Cow<'static, str>, Node<PData>)>, Vec<Connection>), Error<PData>> {
+    if receivers.len() != 1 {
+        return Err(Error::PipelineError {
+            error: format!(
+                "A pipeline requires exactly one receiver, got {}",
+                receivers.len()
+            ),
+        });
+    }
+    if exporters.len() != 1 {
+        return Err(Error::PipelineError {
+            error: format!(
+                "A pipeline requires exactly one exporter, got {}",
+                exporters.len()
+            ),
+        });
+    }
+
+    let mut nodes: Vec<(Cow<'static, str>, Node<PData>)> = Vec::with_capacity(stages.len() + 2);
+    let mut connections = Vec::with_capacity(stages.len() + 1);
+    let mut append = |node: Node<PData>, bridge: Option<usize>| {
+        let name = node.name();
+        if let Some((from, _)) = nodes.last() {
+            connections.push(Connection {
+                from: from.clone(),
+                to: name.clone(),
+                bridge,
             });
         }
-        Ok(())
+        nodes.push((name, node));
+    };
+    append(
+        Node::Receiver(StageReceiver::Wrapper(Box::new(
+            receivers.pop().expect("one receiver"),
+        ))),
+        None,
+    );
+    let mut bridge = None;
+    for stage in stages {
+        match stage {
+            Stage::Processor(processor) => append(Node::Processor(processor), bridge.take()),
+            Stage::Bridge { capacity } => bridge = Some(capacity),
+        }
     }
+    append(
+        Node::Exporter(Box::new(exporters.pop().expect("one exporter"))),
+        bridge,
+    );
 
-    /// Adds a processor to the pipeline.
-    pub fn add_processor<P>(
-        &mut self,
-        processor: ProcessorWrapper<PData>,
-        config: &ProcessorConfig,
-    ) -> Result<(), Error<PData>> {
-        let processor_name = config.name.clone();
-        if self
-            .processors
-            .insert(config.name.clone(), processor)
-            .is_some()
-        {
-            return Err(Error::ProcessorAlreadyExists {
-                processor: processor_name,
+    // The nodes are named after their stages, report the duplicates as such.
+    check_stage_names(nodes.iter().map(|(name, _)| name.clone()))?;
+    Ok((nodes, connections))
+}
+
+/// Makes the receivers naming a buffer pool in their configuration borrow from it.
+fn join_buffer_pools<PData: 'static>(
+    nodes: &mut [(Cow<'static, str>, Node<PData>)],
+    buffer_pools: &HashMap<String, Arc<GlobalBufferPool>>,
+) -> Result<(), Error<PData>> {
+    for (name, node) in nodes {
+        let Node::Receiver(StageReceiver::Wrapper(receiver)) = node else {
+            continue;
+        };
+        let Some(pool_name) = receiver.buffer_pool_name() else {
+            continue;
+        };
+        let Some(pool) = buffer_pools.get(pool_name) else {
+            return Err(Error::PipelineError {
+                error: format!("The receiver `{name}` names an unknown buffer pool `{pool_name}`"),
             });
+        };
+        receiver.join_buffer_pool(pool)?;
+    }
+    Ok(())
+}
+
+/// The output of a node added by name.
+#[derive(Clone, Copy)]
+enum Output {
+    /// A connection to a node, possibly through a fan-in, and through a bridge of the given
+    /// capacity if any.
+    Node(usize, Option<usize>),
+    /// A fan-out, by index in the fan-outs of the builder.
+    FanOut(usize),
+}
+
+/// The validated topology of the nodes of a pipeline, by index in the nodes of the builder.
+struct Topology {
+    /// The output of every node, none for the exporters.
+    outputs: Vec<Option<Output>>,
+    /// The downstream nodes of every fan-out.
+    fan_out_targets: Vec<Vec<usize>>,
+    /// The fan-in feeding every node, if any.
+    fan_in_of: Vec<Option<usize>>,
+    /// The nodes, every node following its upstream nodes.
+    order: Vec<usize>,
+}
+
+/// Checks the connections, fan-outs and fan-ins of the nodes, and orders the nodes from the
+/// receivers to the exporters.
+///
+/// Every node but the exporters must have exactly one output, every node but the receivers
+/// exactly one input or fan-in, and the connections must not form a cycle.
+fn check_topology<PData>(
+    nodes: &[(Cow<'static, str>, Node<PData>)],
+    connections: &[Connection],
+    fan_outs: &[FanOut<PData>],
+    fan_ins: &[FanIn<PData>],
+) -> Result<Topology, Error<PData>> {
+    let pipeline_error = |error: String| Error::PipelineError { error };
+    let mut index = HashMap::with_capacity(nodes.len());
+    for (i, (name, _)) in nodes.iter().enumerate() {
+        if index.insert(name.clone(), i).is_some() {
+            return Err(pipeline_error(format!("Several nodes are named `{name}`")));
         }
-        Ok(())
     }
 
-    /// Adds an exporter to the pipeline.
-    pub fn add_exporter<E>(
-        &mut self,
-        exporter: ExporterWrapper<PData>,
-        config: &ExporterConfig,
-    ) -> Result<(), Error<PData>> {
-        let exporter_name = config.name.clone();
-        if self
-            .exporters
-            .insert(config.name.clone(), exporter)
-            .is_some()
-        {
-            return Err(Error::ExporterAlreadyExists {
-                exporter: exporter_name,
+    // The output and the downstream nodes of every node, and whether a node has an input.
+    let mut outputs: Vec<Option<Output>> = vec![None; nodes.len()];
+    let mut next: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    let mut has_input = vec![false; nodes.len()];
+    let mut fan_in_of = vec![None; nodes.len()];
+    let mut add_edge = |from: &Cow<'static, str>, to: &Cow<'static, str>| {
+        let edge = format!("{from} -> {to}");
+        let lookup = |name: &Cow<'static, str>| {
+            index.get(name).copied().ok_or_else(|| {
+                pipeline_error(format!(
+                    "The connection `{edge}` refers to the unknown node `{name}`"
+                ))
+            })
+        };
+        let (from_index, to_index) = (lookup(from)?, lookup(to)?);
+        if let Node::Exporter(_) = nodes[from_index].1 {
+            return Err(pipeline_error(format!(
+                "The connection `{edge}` starts from the exporter `{from}`, which has no output"
+            )));
+        }
+        if let Node::Receiver(_) = nodes[to_index].1 {
+            return Err(pipeline_error(format!(
+                "The connection `{edge}` ends at the receiver `{to}`, which has no input"
+            )));
+        }
+        next[from_index].push(to_index);
+        Ok((from_index, to_index))
+    };
+    let second_output = |from: &Cow<'static, str>, to: &Cow<'static, str>| {
+        pipeline_error(format!(
+            "The connection `{from} -> {to}` is a second output of `{from}`, use a fan-out to \
+             feed several nodes"
+        ))
+    };
+    let second_input = |from: &Cow<'static, str>, to: &Cow<'static, str>| {
+        pipeline_error(format!(
+            "The connection `{from} -> {to}` is a second input of `{to}`, use a fan-in to merge \
+             several nodes"
+        ))
+    };
+
+    for Connection { from, to, bridge } in connections {
+        let (from_index, to_index) = add_edge(from, to)?;
+        if outputs[from_index].is_some() {
+            return Err(second_output(from, to));
+        }
+        if std::mem::replace(&mut has_input[to_index], true) {
+            return Err(second_input(from, to));
+        }
+        outputs[from_index] = Some(Output::Node(to_index, *bridge));
+    }
+    let mut fan_out_targets = Vec::with_capacity(fan_outs.len());
+    for (i, FanOut { from, to, .. }) in fan_outs.iter().enumerate() {
+        let Some(&from_index) = index.get(from) else {
+            return Err(pipeline_error(format!(
+                "The fan-out of `{from}` refers to the unknown node `{from}`"
+            )));
+        };
+        if to.is_empty() {
+            return Err(pipeline_error(format!(
+                "The fan-out of `{from}` has no downstream node"
+            )));
+        }
+        if outputs[from_index].is_some() {
+            return Err(pipeline_error(format!(
+                "The fan-out of `{from}` is a second output of `{from}`"
+            )));
+        }
+        outputs[from_index] = Some(Output::FanOut(i));
+        let mut targets = Vec::with_capacity(to.len());
+        for to in to {
+            let (_, to_index) = add_edge(from, to)?;
+            if std::mem::replace(&mut has_input[to_index], true) {
+                return Err(second_input(from, to));
+            }
+            targets.push(to_index);
+        }
+        fan_out_targets.push(targets);
+    }
+    for (i, FanIn { from, to, .. }) in fan_ins.iter().enumerate() {
+        let Some(&to_index) = index.get(to) else {
+            return Err(pipeline_error(format!(
+                "The fan-in of `{to}` refers to the unknown node `{to}`"
+            )));
+        };
+        if from.is_empty() {
+            return Err(pipeline_error(format!(
+                "The fan-in of `{to}` has no upstream node"
+            )));
+        }
+        if std::mem::replace(&mut has_input[to_index], true) {
+            return Err(pipeline_error(format!(
+                "The fan-in of `{to}` is a second input of `{to}`"
+            )));
+        }
+        fan_in_of[to_index] = Some(i);
+        for from in from {
+            let (from_index, _) = add_edge(from, to)?;
+            if outputs[from_index].is_some() {
+                return Err(second_output(from, to));
+            }
+            outputs[from_index] = Some(Output::Node(to_index, None));
+        }
+    }
+
+    // A depth-first walk from every node, a node already on the walked path closes a cycle.
+    let mut visited = vec![false; nodes.len()];
+    let mut on_path = vec![false; nodes.len()];
+    for start in 0..nodes.len() {
+        if std::mem::replace(&mut visited[start], true) {
+            continue;
+        }
+        on_path[start] = true;
+        // The walked path, with the number of downstream nodes walked from every node.
+        let mut path = vec![(start, 0)];
+        while let Some(&(current, walked)) = path.last() {
+            let Some(&downstream) = next[current].get(walked) else {
+                on_path[current] = false;
+                _ = path.pop();
+                continue;
+            };
+            if let Some(last) = path.last_mut() {
+                last.1 += 1;
+            }
+            if on_path[downstream] {
+                return Err(pipeline_error(format!(
+                    "The connection `{} -> {}` creates a cycle",
+                    nodes[current].0, nodes[downstream].0
+                )));
+            }
+            if !std::mem::replace(&mut visited[downstream], true) {
+                on_path[downstream] = true;
+                path.push((downstream, 0));
+            }
+        }
+    }
+
+    for (i, (name, node)) in nodes.iter().enumerate() {
+        if outputs[i].is_none() && !matches!(node, Node::Exporter(_)) {
+            return Err(pipeline_error(format!(
+                "The output of the {} `{name}` is not consumed",
+                node.kind()
+            )));
+        }
+    }
+    if !nodes
+        .iter()
+        .any(|(_, node)| matches!(node, Node::Receiver(_)))
+    {
+        return Err(pipeline_error(
+            "A pipeline requires at least one receiver".to_owned(),
+        ));
+    }
+    // Without cycle, the nodes with an input are all fed by a receiver.
+    for (i, (name, node)) in nodes.iter().enumerate() {
+        if !has_input[i] && !matches!(node, Node::Receiver(_)) {
+            return Err(pipeline_error(format!(
+                "The {} `{name}` has no input",
+                node.kind()
+            )));
+        }
+    }
+
+    // Without cycle, the nodes are all ordered once their upstream nodes are.
+    let mut upstream_count = vec![0; nodes.len()];
+    for &downstream in next.iter().flatten() {
+        upstream_count[downstream] += 1;
+    }
+    let mut order: Vec<_> = (0..nodes.len())
+        .filter(|&i| upstream_count[i] == 0)
+        .collect();
+    let mut ordered = 0;
+    while let Some(&current) = order.get(ordered) {
+        ordered += 1;
+        for &downstream in &next[current] {
+            upstream_count[downstream] -= 1;
+            if upstream_count[downstream] == 0 {
+                order.push(downstream);
+            }
+        }
+    }
+
+    Ok(Topology {
+        outputs,
+        fan_out_targets,
+        fan_in_of,
+        order,
+    })
+}
+
+/// A pdata channel feeding a node while the pipeline is wired.
+struct Input<PData> {
+    pdata_rx: Receiver<PData>,
+    /// The name of the upstream stage.
+    upstream: Cow<'static, str>,
+    upstream_is_local: bool,
+    /// The index of the upstream stage in the graph of the pipeline.
+    upstream_node: usize,
+    /// Whether the pdata goes through a bridge.
+    bridged: bool,
+    /// The index of the receiver the pdata comes from, through the first input of the fan-ins.
+    receiver: usize,
+}
+
+/// Creates the input channel of a stage, local or shared depending on the stage.
+fn input_channel<PData>(is_local: bool, capacity: usize) -> (Sender<PData>, Receiver<PData>) {
+    if is_local {
+        let (pdata_tx, pdata_rx) = mpsc::Channel::new(capacity);
+        (Sender::Local(pdata_tx), Receiver::Local(pdata_rx))
+    } else {
+        let (pdata_tx, pdata_rx) = tokio::sync::mpsc::channel(capacity);
+        (Sender::Shared(pdata_tx), Receiver::Shared(pdata_rx))
+    }
+}
+
+/// Connects the pdata channels of the nodes along their connections, fan-outs and fan-ins, from
+/// the receivers to the exporters.
+///
+/// The exporters are connected to the control channel of the receiver they are fed by, through
+/// the first input of the fan-ins.
+fn wire_nodes<PData>(
+    nodes: Vec<(Cow<'static, str>, Node<PData>)>,
+    connections: &[Connection],
+    fan_outs: Vec<FanOut<PData>>,
+    fan_ins: Vec<FanIn<PData>>,
+    log_sink: Option<&(Arc<dyn LogSink>, Cow<'static, str>)>,
+) -> Result<Pipeline<PData>, Error<PData>> {
+    let topology = check_topology(&nodes, connections, &fan_outs, &fan_ins)?;
+    // Checked before wiring so that no node is half-connected when a receiver was already wired.
+    for (_, node) in &nodes {
+        if let Node::Receiver(receiver) = node {
+            if !receiver.has_pdata_receiver() {
+                return Err(Error::PdataReceiverAlreadyTaken {
+                    node: receiver.name(),
+                });
+            }
+        }
+    }
+    let mut nodes: Vec<_> = nodes.into_iter().map(|(_, node)| Some(node)).collect();
+    let mut inputs: Vec<Vec<Input<PData>>> = nodes.iter().map(|_| Vec::new()).collect();
+    let mut receivers = Vec::new();
+    let mut stages = Vec::new();
+    let mut exporters = Vec::new();
+    let mut graph = PipelineGraph::default();
+
+    for &i in &topology.order {
+        let Some(node) = nodes[i].take() else {
+            continue;
+        };
+        let name = node.name();
+        let is_local = node.is_local();
+        let kind = match node {
+            Node::Receiver(_) => NodeKind::Receiver,
+            Node::Processor(_) => NodeKind::Processor,
+            Node::Exporter(_) => NodeKind::Exporter,
+        };
+        let graph_node = graph.add_node(name.clone(), kind, is_local);
+
+        // The pdata channel feeding the node, merged by a fan-in if several upstream nodes feed it.
+        let mut node_inputs = std::mem::take(&mut inputs[i]);
+        let input = match topology.fan_in_of[i] {
+            Some(fan_in) => {
+                let FanIn { capacity, tag, .. } = fan_ins[fan_in];
+                let receiver = node_inputs.first().map(|input| input.receiver);
+                let (pdata_tx, pdata_rx) = input_channel(is_local, capacity);
+                let mut merged = Vec::with_capacity(node_inputs.len());
+                for input in node_inputs {
+                    // The fan-in bridges the local upstream nodes of a shared node.
+                    let bridged = input.bridged || (input.upstream_is_local && !is_local);
+                    graph.add_edge(input.upstream_node, graph_node, bridged);
+                    merged.push(MergeInput::new(input.pdata_rx, input.upstream, tag));
+                }
+                stages.push(WiredStage::FanIn {
+                    inputs: merged,
+                    pdata_tx,
+                });
+                receiver.map(|receiver| (pdata_rx, receiver))
+            }
+            None => match node_inputs.pop() {
+                Some(input) => {
+                    check_sendability(&input.upstream, input.upstream_is_local, &name, is_local)?;
+                    graph.add_edge(input.upstream_node, graph_node, input.bridged);
+                    Some((input.pdata_rx, input.receiver))
+                }
+                None => None,
+            },
+        };
+        let node_kind = node.kind();
+        let missing_input = || Error::PipelineError {
+            error: format!("The {node_kind} `{name}` has no input"),
+        };
+
+        let (pdata_rx, receiver) = match node {
+            Node::Receiver(mut receiver) => {
+                if let Some((sink, pipeline_id)) = log_sink {
+                    receiver.set_log_sink(sink.clone(), Some(pipeline_id.clone()));
+                }
+                let pdata_rx = receiver.take_pdata_receiver()?;
+                receivers.push(receiver);
+                (pdata_rx, receivers.len() - 1)
+            }
+            Node::Processor(mut processor) => {
+                let (input, receiver) = input.ok_or_else(missing_input)?;
+                if let Some((sink, pipeline_id)) = log_sink {
+                    processor.set_log_sink(sink.clone(), Some(pipeline_id.clone()));
+                }
+                processor.connect_input(input)?;
+                let pdata_rx = processor.take_pdata_receiver();
+                stages.push(WiredStage::Processor(processor));
+                (pdata_rx, receiver)
+            }
+            Node::Exporter(mut exporter) => {
+                let (input, receiver) = input.ok_or_else(missing_input)?;
+                if let Some((sink, pipeline_id)) = log_sink {
+                    exporter.set_log_sink(sink.clone(), Some(pipeline_id.clone()));
+                }
+                exporter.connect_input(input);
+                exporter.connect_upstream_control(receivers[receiver].control_sender())?;
+                exporters.push(*exporter);
+                continue;
+            }
+        };
+
+        let output = Input {
+            pdata_rx,
+            upstream: name,
+            upstream_is_local: is_local,
+            upstream_node: graph_node,
+            bridged: false,
+            receiver,
+        };
+        match topology.outputs[i] {
+            Some(Output::Node(downstream, None)) => inputs[downstream].push(output),
+            Some(Output::Node(downstream, Some(capacity))) => {
+                let (pdata_tx, pdata_rx) = tokio::sync::mpsc::channel(capacity);
+                stages.push(WiredStage::Bridge {
+                    pdata_rx: output.pdata_rx,
+                    pdata_tx,
+                });
+                inputs[downstream].push(Input {
+                    pdata_rx: Receiver::Shared(pdata_rx),
+                    upstream_is_local: false,
+                    bridged: true,
+                    ..output
+                });
+            }
+            Some(Output::FanOut(fan_out)) => {
+                let FanOut {
+                    policy,
+                    clone,
+                    capacity,
+                    ..
+                } = fan_outs[fan_out];
+                let targets = &topology.fan_out_targets[fan_out];
+                let mut outputs = Vec::with_capacity(targets.len());
+                for &target in targets {
+                    // The fan-out feeds every downstream node over a channel matching it, bridging
+                    // the shared ones.
+                    let target_is_local = nodes[target].as_ref().is_none_or(Node::is_local);
+                    let (pdata_tx, pdata_rx) = input_channel(target_is_local, capacity);
+                    outputs.push(pdata_tx);
+                    inputs[target].push(Input {
+                        pdata_rx,
+                        upstream: output.upstream.clone(),
+                        upstream_is_local: false,
+                        upstream_node: graph_node,
+                        bridged: is_local && !target_is_local,
+                        receiver,
+                    });
+                }
+                stages.push(WiredStage::FanOut {
+                    pdata_rx: output.pdata_rx,
+                    outputs,
+                    policy,
+                    clone,
+                });
+            }
+            // Only the exporters have no output.
+            None => {}
+        }
+    }
+
+    Ok(Pipeline {
+        receivers,
+        stages,
+        exporters,
+        graph,
+    })
+}
+
+/// Checks that the names of the stages are valid and unique, as they identify the stages in the
+/// graph, the logs and the metrics of the pipeline.
+fn check_stage_names<PData>(
+    names: impl Iterator<Item = Cow<'static, str>>,
+) -> Result<(), Error<PData>> {
+    let mut seen = HashSet::new();
+    for name in names {
+        validate_node_name(&name).map_err(|error| Error::PipelineError {
+            error: format!("Invalid stage name: {error}"),
+        })?;
+        if !seen.insert(name.clone()) {
+            return Err(Error::PipelineError {
+                error: format!("Several stages are named `{name}`"),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a stage can consume the pdata emitted by its upstream stage.
+fn check_sendability<PData>(
+    upstream: &str,
+    upstream_is_local: bool,
+    stage: &str,
+    stage_is_local: bool,
+) -> Result<(), Error<PData>> {
+    if upstream_is_local && !stage_is_local {
+        return Err(Error::PipelineError {
+            error: format!(
+                "The shared stage `{stage}` follows the local stage `{upstream}` without a bridge"
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// A pipeline whose stages are connected and ready to run (see [`PipelineBuilder`]).
+pub struct Pipeline<PData> {
+    receivers: Vec<StageReceiver<PData>>,
+    stages: Vec<WiredStage<PData>>,
+    exporters: Vec<ExporterWrapper<PData>>,
+    graph: PipelineGraph,
+}
+
+impl<PData> Pipeline<PData> {
+    /// Returns the wiring topology of the pipeline (see [`crate::graph`]).
+    #[must_use]
+    pub fn graph(&self) -> &PipelineGraph {
+        &self.graph
+    }
+
+    /// Returns the name and the control message sender of every stage of the pipeline, from the
+    /// receivers to the exporters.
+    #[must_use]
+    pub fn control_senders(&self) -> Vec<(Cow<'static, str>, Sender<ControlMsg>)> {
+        let mut senders = Vec::new();
+        for receiver in &self.receivers {
+            senders.push((receiver.name(), receiver.control_sender()));
+        }
+        for stage in &self.stages {
+            if let WiredStage::Processor(processor) = stage {
+                senders.push((processor.name(), processor.control_sender()));
+            }
+        }
+        for exporter in &self.exporters {
+            senders.push((exporter.name(), exporter.control_sender()));
+        }
+        senders
+    }
+
+    /// Returns the aggregated health of the stages of the pipeline (see [`PipelineHealth`]), which
+    /// remains usable while and after the pipeline runs.
+    #[must_use]
+    pub fn health_check(&self) -> PipelineHealth {
+        let mut health = PipelineHealth::new();
+        for receiver in &self.receivers {
+            health.add_stage(
+                receiver.name(),
+                receiver.health_probe(),
+                Some(receiver.subscribe_health()),
+            );
+        }
+        for stage in &self.stages {
+            if let WiredStage::Processor(processor) = stage {
+                health.add_stage(processor.name(), processor.health_probe(), None);
+            }
+        }
+        for exporter in &self.exporters {
+            health.add_stage(exporter.name(), exporter.health_probe(), None);
+        }
+        health
+    }
+
+    /// Spawns all the stages of the pipeline and returns a handle to shut them down and to wait
+    /// for their completion.
+    ///
+    /// Local stages are spawned on the current [`tokio::task::LocalSet`], this method must
+    /// therefore be called from within a `LocalSet`. Shared stages are spawned on the Tokio thread
+    /// pool. Stages are started from the exporters to the receivers.
+    ///
+    /// Every stage is joined by its own task, so that a failed stage is logged as soon as it fails
+    /// while the other stages keep running, e.g. the other upstream nodes of a fan-in.
+    #[must_use]
+    pub fn run(self) -> PipelineHandle<PData>
+    where
+        PData: Send + 'static,
+    {
+        let control_senders = self
+            .control_senders()
+            .into_iter()
+            .map(|(_, control_sender)| control_sender)
+            .collect();
+        let Pipeline {
+            receivers,
+            stages,
+            exporters,
+            ..
+        } = self;
+        let shutdown_reports = receivers
+            .iter()
+            .map(StageReceiver::subscribe_shutdown_report)
+            .collect();
+
+        // The joining tasks and the names of their stages, from the exporters to the receivers.
+        let mut joined = Vec::with_capacity(receivers.len() + stages.len() + exporters.len());
+        for exporter in exporters {
+            let name = exporter.name();
+            let task = tokio::task::spawn_local(join(exporter.spawn(), {
+                let name = name.clone();
+                |error| Error::ExporterError {
+                    exporter: name,
+                    error,
+                }
+            }));
+            joined.push((name, task));
+        }
+        for stage in stages.into_iter().rev() {
+            let handle = match stage {
+                WiredStage::Processor(processor) => {
+                    let name = processor.name();
+                    let task = tokio::task::spawn_local(join(processor.spawn(), {
+                        let name = name.clone();
+                        |error| Error::ProcessorError {
+                            processor: name,
+                            error,
+                        }
+                    }));
+                    (name, task)
+                }
+                WiredStage::Bridge { pdata_rx, pdata_tx } => (
+                    Cow::Borrowed("bridge"),
+                    tokio::task::spawn_local(join(
+                        tokio::task::spawn_local(run_bridge(pdata_rx, pdata_tx)),
+                        |error| Error::PipelineError { error },
+                    )),
+                ),
+                WiredStage::FanOut {
+                    pdata_rx,
+                    outputs,
+                    policy,
+                    clone,
+                } => (
+                    Cow::Borrowed("fan_out"),
+                    tokio::task::spawn_local(join(
+                        tokio::task::spawn_local(run_fanout(pdata_rx, outputs, policy, clone)),
+                        |error| Error::PipelineError { error },
+                    )),
+                ),
+                WiredStage::FanIn { inputs, pdata_tx } => (
+                    Cow::Borrowed("fan_in"),
+                    tokio::task::spawn_local(join(
+                        tokio::task::spawn_local(run_merge(inputs, pdata_tx)),
+                        |error| Error::PipelineError { error },
+                    )),
+                ),
+            };
+            joined.push(handle);
+        }
+        for receiver in receivers {
+            let name = receiver.name();
+            let task = tokio::task::spawn_local(join(receiver.spawn(), {
+                let name = name.clone();
+                |error| Error::ReceiverError {
+                    receiver: name,
+                    error,
+                }
+            }));
+            joined.push((name, task));
+        }
+        joined.reverse();
+
+        PipelineHandle {
+            control_senders,
+            shutdown_reports,
+            stages: joined,
+        }
+    }
+}
+
+/// A handle to a running pipeline (see [`Pipeline::run`]).
+pub struct PipelineHandle<PData> {
+    /// The control message senders of the stages, from the receiver to the exporters.
+    control_senders: Vec<Sender<ControlMsg>>,
+    /// The summaries of the runs of the receivers (see [`ShutdownReport`]).
+    shutdown_reports: Vec<watch::Receiver<Option<ShutdownReport>>>,
+    /// The tasks joining the stages and the names of their stages, from the receivers to the
+    /// exporters.
+    stages: Vec<(Cow<'static, str>, JoinHandle<Result<(), Error<PData>>>)>,
+}
+
+impl<PData> PipelineHandle<PData> {
+    /// Requests every stage of the pipeline to shut down within the given deadline (see
+    /// [`ControlMsg::Shutdown`]). The `Shutdown` messages are sent concurrently, so that a stage
+    /// whose control channel is full doesn't delay the others. The stages which already completed
+    /// are skipped.
+    ///
+    /// Must be called from within the [`tokio::task::LocalSet`] of the pipeline.
+    pub async fn shutdown(&self, deadline: Duration) {
+        let mut sends = JoinSet::new();
+        for control_sender in &self.control_senders {
+            let control_sender = control_sender.clone();
+            _ = sends.spawn_local(async move {
+                // The control channel of a completed stage is closed.
+                _ = control_sender
+                    .send(ControlMsg::Shutdown {
+                        deadline,
+                        reason: "Pipeline shutdown".to_owned(),
+                    })
+                    .await;
             });
         }
-        Ok(())
+        while sends.join_next().await.is_some() {}
     }
 
-    /// Connects the receiver's out ports to the downstream nodes.
-    pub fn connect_receiver_out_ports(
-        &mut self,
-        _receiver_name: Rc<str>,
-        _out_ports: HashMap<Rc<str>, HashSet<Rc<str>>>,
-    ) -> Result<(), Error<PData>> {
-        Ok(())
+    /// Requests every stage of the pipeline to shut down within the given timeout (see
+    /// [`PipelineHandle::shutdown`]), and waits up to the timeout for all the stages to complete.
+    ///
+    /// Must be called from within the [`tokio::task::LocalSet`] of the pipeline.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ShutdownError::TimedOut`] listing the stages which didn't complete within the
+    /// timeout, or a [`ShutdownError::StageFailed`] with the first error reported by the stages
+    /// (see [`PipelineHandle::join`]).
+    pub async fn shutdown_with_timeout(
+        self,
+        timeout: Duration,
+    ) -> Result<(), ShutdownError<PData>> {
+        let deadline = Instant::now() + timeout;
+        self.shutdown(timeout).await;
+        let mut result = Ok(());
+        let mut timed_out = Vec::new();
+        for (name, stage) in self.stages {
+            match tokio::time::timeout_at(deadline, join_stage(stage)).await {
+                Ok(stage_result) => result = result.and(stage_result),
+                Err(_) => timed_out.push(name.into_owned()),
+            }
+        }
+        if !timed_out.is_empty() {
+            return Err(ShutdownError::TimedOut {
+                stage_names: timed_out,
+            });
+        }
+        result.map_err(ShutdownError::StageFailed)
+    }
+
+    /// Waits for all the stages of the pipeline to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error reported by the stages, from the receiver to the exporters, or an
+    /// [`Error::PipelineError`] if a task joining a stage failed.
+    pub async fn join(self) -> Result<(), Error<PData>> {
+        let mut result = Ok(());
+        for (_, stage) in self.stages {
+            result = result.and(join_stage(stage).await);
+        }
+        result
+    }
+
+    /// Waits for all the stages of the pipeline to complete (see [`PipelineHandle::join`]), and
+    /// returns the end-of-run summary of the pipeline along with its outcome: the sum of the
+    /// [`ShutdownReport`]s of the receivers which shut down cleanly.
+    pub async fn join_with_report(self) -> (Result<(), Error<PData>>, ShutdownReport) {
+        let shutdown_reports = self.shutdown_reports.clone();
+        let result = self.join().await;
+        let mut report = ShutdownReport::default();
+        for receiver_report in shutdown_reports {
+            if let Some(receiver_report) = *receiver_report.borrow() {
+                report += receiver_report;
+            }
+        }
+        tracing::info!(%report, "Pipeline completed");
+        (result, report)
+    }
+}
+
+/// Waits for the task joining a stage to complete.
+async fn join_stage<PData>(
+    stage: JoinHandle<Result<(), Error<PData>>>,
+) -> Result<(), Error<PData>> {
+    stage.await.unwrap_or_else(|join_error| {
+        Err(Error::PipelineError {
+            error: join_error.to_string(),
+        })
+    })
+}
+
+/// Waits for a stage task to complete, task failures (e.g. panics) are converted with `to_error`.
+/// A failed stage is logged with the code of its error, as a warning if the error is transient.
+async fn join<PData>(
+    handle: JoinHandle<Result<(), Error<PData>>>,
+    to_error: impl FnOnce(String) -> Error<PData>,
+) -> Result<(), Error<PData>> {
+    let result = match handle.await {
+        Ok(result) => result,
+        Err(join_error) => Err(to_error(join_error.to_string())),
+    };
+    if let Err(error) = &result {
+        let code = error.error_code();
+        if code.is_transient() {
+            tracing::warn!(?code, %error, "Pipeline stage failed");
+        } else {
+            tracing::error!(?code, %error, "Pipeline stage failed");
+        }
+    }
+    result
+}
+
+/// Forwards the pdata emitted by a local stage to a shared stage, until the local stage closes its
+/// output channel.
+pub(crate) async fn run_bridge<PData>(
+    mut pdata_rx: Receiver<PData>,
+    pdata_tx: tokio::sync::mpsc::Sender<PData>,
+) -> Result<(), Error<PData>> {
+    while let Ok(pdata) = pdata_rx.recv().await {
+        pdata_tx
+            .send(pdata)
+            .await
+            .map_err(|e| Error::ChannelSendError(SendError::Closed(e.0)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer_pool::GlobalBufferPool;
+    use crate::config::{ExporterConfig, ProcessorConfig, ReceiverConfig};
+    use crate::error::{Error, ShutdownError};
+    use crate::exporter::ExporterWrapper;
+    use crate::fanin::SourceTagged;
+    use crate::fanout::DispatchPolicy;
+    use crate::health::{HealthCheck, HealthStatus};
+    use crate::local::exporter as local_exporter;
+    use crate::local::processor as local_processor;
+    use crate::local::receiver as local_receiver;
+    use crate::message::{ControlMsg, Message, MessageChannel};
+    use crate::pipeline::{Pipeline, PipelineBuilder};
+    use crate::processor::ProcessorWrapper;
+    use crate::receiver::ReceiverWrapper;
+    use crate::runtime::CoreScheduler;
+    use crate::shared::exporter as shared_exporter;
+    use crate::shared::processor as shared_processor;
+    use crate::telemetry::ShutdownReport;
+    use crate::testing::{CtrlMsgCounters, TestMsg};
+    use async_trait::async_trait;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::runtime::Builder;
+    use tokio::sync::oneshot;
+    use tokio::task::LocalSet;
+    use tokio::time::{sleep, timeout};
+
+    /// A receiver emitting a fixed number of messages and then waiting for a shutdown.
+    struct GeneratorReceiver {
+        count: usize,
+    }
+
+    #[async_trait(?Send)]
+    impl local_receiver::Receiver<TestMsg> for GeneratorReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_msg_recv: local_receiver::ControlChannel,
+            effect_handler: local_receiver::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            for i in 0..self.count {
+                effect_handler
+                    .send_message(TestMsg::new(format!("msg {i}")))
+                    .await?;
+            }
+            while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+            Ok(())
+        }
     }
 
-    /// Connects the processor's out ports to the downstream nodes.
-    pub fn connect_processor_out_ports(
-        &mut self,
-        _processor_name: Rc<str>,
-        _out_ports: HashMap<Rc<str>, HashSet<Rc<str>>>,
-    ) -> Result<(), Error<PData>> {
-        Ok(())
+    /// A receiver reporting a degraded health and then waiting for a shutdown.
+    struct DegradedReceiver;
+
+    #[async_trait(?Send)]
+    impl local_receiver::Receiver<TestMsg> for DegradedReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_msg_recv: local_receiver::ControlChannel,
+            effect_handler: local_receiver::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            effect_handler.report_health(HealthStatus::Degraded("source lagging".to_owned()));
+            while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+            Ok(())
+        }
     }
 
-    /// Runs the pipeline.
-    pub fn run(self) -> Result<(), Error<PData>> {
+    /// A processor tagging every message it receives.
+    struct TagProcessor;
+
+    #[async_trait(?Send)]
+    impl local_processor::Processor<TestMsg> for TagProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut local_processor::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            if let Message::PData(data) = msg {
+                effect_handler
+                    .send_message(TestMsg(format!("{} tagged", data.0)))
+                    .await?;
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared_processor::Processor<TestMsg> for TagProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut shared_processor::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            if let Message::PData(data) = msg {
+                effect_handler
+                    .send_message(TestMsg(format!("{} tagged", data.0)))
+                    .await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// An exporter collecting all the messages it receives.
+    struct CollectExporter {
+        collected: Arc<Mutex<Vec<TestMsg>>>,
+    }
+
+    #[async_trait(?Send)]
+    impl local_exporter::Exporter<TestMsg> for CollectExporter {
+        async fn start(
+            self: Box<Self>,
+            mut msg_chan: MessageChannel<TestMsg>,
+            _effect_handler: local_exporter::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            loop {
+                match msg_chan.recv().await? {
+                    Message::PData(data) => self.collected.lock().unwrap().push(data),
+                    Message::Control(ctrl_msg) if ctrl_msg.is_shutdown() => break,
+                    Message::Control(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared_exporter::Exporter<TestMsg> for CollectExporter {
+        async fn start(
+            self: Box<Self>,
+            mut msg_chan: shared_exporter::MessageChannel<TestMsg>,
+            _effect_handler: shared_exporter::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            loop {
+                match msg_chan.recv().await? {
+                    Message::PData(data) => self.collected.lock().unwrap().push(data),
+                    Message::Control(ctrl_msg) if ctrl_msg.is_shutdown() => break,
+                    Message::Control(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// A receiver emitting a message, and another one once it has been throttled.
+    struct ThrottledReceiver {
+        counters: CtrlMsgCounters,
+    }
+
+    #[async_trait(?Send)]
+    impl local_receiver::Receiver<TestMsg> for ThrottledReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_msg_recv: local_receiver::ControlChannel,
+            effect_handler: local_receiver::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            effect_handler
+                .send_message(TestMsg::new("before throttle"))
+                .await?;
+            loop {
+                let ctrl_msg = ctrl_msg_recv.recv().await?;
+                self.counters.update_with(&ctrl_msg);
+                match ctrl_msg {
+                    ControlMsg::Throttle { duration } => {
+                        sleep(duration).await;
+                        effect_handler
+                            .send_message(TestMsg::new("after throttle"))
+                            .await?;
+                    }
+                    ControlMsg::Shutdown { .. } => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// An exporter throttling the ingestion when it receives its first message.
+    struct ThrottlingExporter {
+        collected: Arc<Mutex<Vec<TestMsg>>>,
+    }
+
+    #[async_trait(?Send)]
+    impl local_exporter::Exporter<TestMsg> for ThrottlingExporter {
+        async fn start(
+            self: Box<Self>,
+            mut msg_chan: MessageChannel<TestMsg>,
+            effect_handler: local_exporter::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            loop {
+                match msg_chan.recv().await? {
+                    Message::PData(data) => {
+                        let first = self.collected.lock().unwrap().is_empty();
+                        if first {
+                            effect_handler
+                                .throttle_upstream(Duration::from_millis(50))
+                                .await?;
+                        }
+                        self.collected.lock().unwrap().push(data);
+                    }
+                    Message::Control(ctrl_msg) if ctrl_msg.is_shutdown() => break,
+                    Message::Control(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Runs the pipeline on a multi-threaded runtime until the exporter collected `expected`
+    /// messages, then shuts down every stage.
+    fn run_pipeline(
+        pipeline: Pipeline<TestMsg>,
+        collected: &Arc<Mutex<Vec<TestMsg>>>,
+        expected: usize,
+    ) {
+        let collected = collected.clone();
+        run_pipeline_until(pipeline, move || {
+            collected.lock().unwrap().len() >= expected
+        });
+    }
+
+    /// Runs the pipeline until `done` returns true, and then shuts it down.
+    fn run_pipeline_until(pipeline: Pipeline<TestMsg>, done: impl Fn() -> bool) {
+        let rt = Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        let local_tasks = LocalSet::new();
+
+        local_tasks.block_on(&rt, async move {
+            let handle = pipeline.run();
+
+            timeout(Duration::from_secs(5), async {
+                while !done() {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("Timed out waiting for the exported messages");
+
+            handle.shutdown(Duration::from_millis(100)).await;
+            timeout(Duration::from_secs(5), handle.join())
+                .await
+                .expect("Timed out waiting for the pipeline")
+                .expect("Pipeline failed");
+        });
+    }
+
+    #[test]
+    fn test_local_pipeline() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 3 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .processor(ProcessorWrapper::local(
+                TagProcessor,
+                &ProcessorConfig::new("processor"),
+            ))
+            .exporter(ExporterWrapper::local(
+                CollectExporter {
+                    collected: collected.clone(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        assert_eq!(
+            pipeline
+                .control_senders()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            vec!["receiver", "processor", "exporter"]
+        );
+        run_pipeline(pipeline, &collected, 3);
+
+        let collected = collected.lock().unwrap();
+        assert_eq!(
+            *collected,
+            vec![
+                TestMsg::new("msg 0 tagged"),
+                TestMsg::new("msg 1 tagged"),
+                TestMsg::new("msg 2 tagged"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_connected_pipeline() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        // Nodes are added in any order, and ordered along their connections.
+        let pipeline = PipelineBuilder::new()
+            .add_exporter(
+                "exporter",
+                ExporterWrapper::shared(
+                    CollectExporter {
+                        collected: collected.clone(),
+                    },
+                    &ExporterConfig::new("exporter"),
+                ),
+            )
+            .add_processor(
+                "processor",
+                ProcessorWrapper::local(TagProcessor, &ProcessorConfig::new("processor")),
+            )
+            .add_receiver(
+                "receiver",
+                ReceiverWrapper::local(
+                    GeneratorReceiver { count: 3 },
+                    &ReceiverConfig::new("receiver"),
+                ),
+            )
+            .connect("receiver", "processor")
+            .connect_via_bridge("processor", "exporter", 4)
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        assert_eq!(
+            pipeline.graph().to_string(),
+            "receiver [local receiver]\n\
+             processor [local processor]\n\
+             exporter [shared exporter]\n\
+             receiver -> processor\n\
+             processor -> exporter (bridged)\n"
+        );
+        run_pipeline(pipeline, &collected, 3);
+
+        let collected = collected.lock().unwrap();
+        assert_eq!(
+            *collected,
+            vec![
+                TestMsg::new("msg 0 tagged"),
+                TestMsg::new("msg 1 tagged"),
+                TestMsg::new("msg 2 tagged"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_connections() {
+        let builder = || {
+            PipelineBuilder::new()
+                .add_receiver(
+                    "receiver",
+                    ReceiverWrapper::local(
+                        GeneratorReceiver { count: 0 },
+                        &ReceiverConfig::new("receiver"),
+                    ),
+                )
+                .add_processor(
+                    "first",
+                    ProcessorWrapper::local(TagProcessor, &ProcessorConfig::new("first")),
+                )
+                .add_processor(
+                    "second",
+                    ProcessorWrapper::local(TagProcessor, &ProcessorConfig::new("second")),
+                )
+                .add_exporter(
+                    "exporter",
+                    ExporterWrapper::local(
+                        CollectExporter {
+                            collected: Arc::default(),
+                        },
+                        &ExporterConfig::new("exporter"),
+                    ),
+                )
+        };
+        let error = |builder: PipelineBuilder<TestMsg>| match builder.build() {
+            Err(Error::PipelineError { error }) => error,
+            Err(error) => panic!("Unexpected error: {error}"),
+            Ok(_) => panic!("Invalid pipeline built"),
+        };
+
+        assert_eq!(
+            error(
+                builder()
+                    .connect("receiver", "exporter")
+                    .connect("first", "second")
+                    .connect("second", "first")
+            ),
+            "The connection `second -> first` creates a cycle"
+        );
+        assert_eq!(
+            error(
+                builder()
+                    .connect("receiver", "first")
+                    .connect("first", "exporter")
+            ),
+            "The output of the processor `second` is not consumed"
+        );
+        assert_eq!(
+            error(builder().connect("receiver", "third")),
+            "The connection `receiver -> third` refers to the unknown node `third`"
+        );
+        assert_eq!(
+            error(
+                builder()
+                    .connect("receiver", "first")
+                    .connect("receiver", "second")
+            ),
+            "The connection `receiver -> second` is a second output of `receiver`, use a fan-out to \
+             feed several nodes"
+        );
+        assert_eq!(
+            error(builder().connect("receiver", "first").fan_out(
+                "first",
+                ["second", "exporter"],
+                DispatchPolicy::Broadcast,
+                4
+            )),
+            "The output of the processor `second` is not consumed"
+        );
+        assert_eq!(
+            error(builder().fan_out(
+                "receiver",
+                Vec::<&'static str>::new(),
+                DispatchPolicy::Broadcast,
+                4
+            )),
+            "The fan-out of `receiver` has no downstream node"
+        );
+        assert_eq!(
+            error(
+                builder()
+                    .connect("first", "second")
+                    .connect("second", "exporter")
+                    .fan_out("receiver", ["exporter"], DispatchPolicy::RoundRobin, 4)
+            ),
+            "The connection `receiver -> exporter` is a second input of `exporter`, use a fan-in to \
+             merge several nodes"
+        );
+        assert_eq!(
+            error(builder().fan_in(Vec::<&'static str>::new(), "exporter", 4)),
+            "The fan-in of `exporter` has no upstream node"
+        );
+        assert_eq!(
+            error(
+                builder()
+                    .connect("receiver", "first")
+                    .fan_in(["second"], "first", 4)
+            ),
+            "The fan-in of `first` is a second input of `first`"
+        );
+        assert_eq!(
+            error(
+                builder()
+                    .fan_in(["receiver", "first"], "second", 4)
+                    .connect("second", "exporter")
+            ),
+            "The processor `first` has no input"
+        );
+        assert_eq!(
+            error(builder().connect("exporter", "first")),
+            "The connection `exporter -> first` starts from the exporter `exporter`, which has no \
+             output"
+        );
+    }
+
+    #[test]
+    fn test_bridged_pipeline() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 3 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .bridge(16)
+            .processor(ProcessorWrapper::shared(
+                TagProcessor,
+                &ProcessorConfig::new("processor"),
+            ))
+            .exporter(ExporterWrapper::shared(
+                CollectExporter {
+                    collected: collected.clone(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        run_pipeline(pipeline, &collected, 3);
+
+        assert_eq!(collected.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_pipeline_graph() {
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 0 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .processor(ProcessorWrapper::local(
+                TagProcessor,
+                &ProcessorConfig::new("tagger"),
+            ))
+            .bridge(16)
+            .processor(ProcessorWrapper::shared(
+                TagProcessor,
+                &ProcessorConfig::new("processor"),
+            ))
+            .exporter(ExporterWrapper::shared(
+                CollectExporter {
+                    collected: Arc::default(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        let graph = pipeline.graph();
+        assert_eq!(graph.nodes().len(), 4);
+        assert_eq!(graph.edges().len(), 3);
+        assert_eq!(
+            graph
+                .downstream_of("tagger")
+                .map(|node| node.name.as_ref())
+                .collect::<Vec<_>>(),
+            vec!["processor"]
+        );
+        assert_eq!(
+            graph.to_string(),
+            "receiver [local receiver]\n\
+             tagger [local processor]\n\
+             processor [shared processor]\n\
+             exporter [shared exporter]\n\
+             receiver -> tagger\n\
+             tagger -> processor (bridged)\n\
+             processor -> exporter\n"
+        );
+    }
+
+    #[test]
+    fn test_exporter_throttles_receiver() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let counters = CtrlMsgCounters::new();
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                ThrottledReceiver {
+                    counters: counters.clone(),
+                },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .exporter(ExporterWrapper::local(
+                ThrottlingExporter {
+                    collected: collected.clone(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        run_pipeline(pipeline, &collected, 2);
+
+        assert_eq!(
+            *collected.lock().unwrap(),
+            vec![
+                TestMsg::new("before throttle"),
+                TestMsg::new("after throttle")
+            ]
+        );
+        counters.assert_throttle(1);
+        counters.assert(0, 0, 0, 1, 0, 0);
+    }
+
+    #[test]
+    fn test_shared_stage_after_local_stage_requires_bridge() {
+        let result = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 0 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .processor(ProcessorWrapper::shared(
+                TagProcessor,
+                &ProcessorConfig::new("processor"),
+            ))
+            .exporter(ExporterWrapper::local(
+                CollectExporter {
+                    collected: Arc::default(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build();
+
+        assert!(matches!(result, Err(Error::PipelineError { .. })));
+    }
+
+    #[test]
+    fn test_pipeline_rejects_invalid_stage_names() {
+        let build = |receiver: &str, exporter: &str| {
+            PipelineBuilder::new()
+                .receiver(ReceiverWrapper::local(
+                    GeneratorReceiver { count: 0 },
+                    &ReceiverConfig::new(receiver.to_owned()),
+                ))
+                .exporter(ExporterWrapper::local(
+                    CollectExporter {
+                        collected: Arc::default(),
+                    },
+                    &ExporterConfig::new(exporter.to_owned()),
+                ))
+                .build()
+        };
+
+        for (receiver, exporter) in [
+            (" ", "exporter"),
+            ("otlp receiver", "exporter"),
+            ("node", "node"),
+        ] {
+            assert!(matches!(
+                build(receiver, exporter),
+                Err(Error::PipelineError { .. })
+            ));
+        }
+        assert!(build("receiver", "exporter").is_ok());
+    }
+
+    #[test]
+    fn test_pipeline_requires_an_exporter() {
+        let result = PipelineBuilder::<TestMsg>::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 0 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .build();
+
+        assert!(matches!(result, Err(Error::PipelineError { .. })));
+    }
+
+    #[test]
+    fn test_pipeline_buffer_pools() {
+        let build = |pool: &str| {
+            let mut config = ReceiverConfig::new("receiver");
+            config.output_pdata_channel.pool = Some("receivers".to_owned());
+            PipelineBuilder::new()
+                .buffer_pool(pool, GlobalBufferPool::new(10))
+                .receiver(ReceiverWrapper::local(
+                    GeneratorReceiver { count: 0 },
+                    &config,
+                ))
+                .exporter(ExporterWrapper::local(
+                    CollectExporter {
+                        collected: Arc::default(),
+                    },
+                    &ExporterConfig::new("exporter"),
+                ))
+                .build()
+        };
+
+        assert!(build("receivers").is_ok());
+        assert!(matches!(
+            build("other"),
+            Err(Error::PipelineError { error }) if error.contains("unknown buffer pool")
+        ));
+    }
+
+    #[test]
+    fn test_pipeline_health() {
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                DegradedReceiver,
+                &ReceiverConfig::new("receiver"),
+            ))
+            .processor(ProcessorWrapper::local(
+                TagProcessor,
+                &ProcessorConfig::new("processor"),
+            ))
+            .exporter(ExporterWrapper::local(
+                CollectExporter {
+                    collected: Arc::default(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+        let health = pipeline.health_check();
+        assert_eq!(health.health(), HealthStatus::Healthy);
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        LocalSet::new().block_on(&rt, async move {
+            let handle = pipeline.run();
+
+            timeout(Duration::from_secs(1), async {
+                while health.health() == HealthStatus::Healthy {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("Timed out waiting for the health report");
+            assert_eq!(
+                health.health(),
+                HealthStatus::Degraded("`receiver`: source lagging".to_owned())
+            );
+
+            handle.shutdown(Duration::from_millis(100)).await;
+            timeout(Duration::from_secs(5), handle.join())
+                .await
+                .expect("Timed out waiting for the pipeline")
+                .expect("Pipeline failed");
+            assert_eq!(
+                health.health(),
+                HealthStatus::Unhealthy(
+                    "`receiver`: stopped; `processor`: stopped; `exporter`: stopped".to_owned()
+                )
+            );
+        });
+    }
+
+    /// Adds a receiver emitting `count` messages and an exporter collecting them for every
+    /// collection, the output of the receiver fanning out to the exporters with the given policy.
+    fn fan_out_pipeline(
+        count: usize,
+        collections: &[(&'static str, bool, &Arc<Mutex<Vec<TestMsg>>>)],
+        policy: DispatchPolicy,
+    ) -> Pipeline<TestMsg> {
+        let mut builder = PipelineBuilder::new().add_receiver(
+            "receiver",
+            ReceiverWrapper::local(
+                GeneratorReceiver { count },
+                &ReceiverConfig::new("receiver"),
+            ),
+        );
+        for &(name, is_local, collected) in collections {
+            let exporter = CollectExporter {
+                collected: collected.clone(),
+            };
+            let config = ExporterConfig::new(name);
+            builder = builder.add_exporter(
+                name,
+                if is_local {
+                    ExporterWrapper::local(exporter, &config)
+                } else {
+                    ExporterWrapper::shared(exporter, &config)
+                },
+            );
+        }
+        builder
+            .fan_out(
+                "receiver",
+                collections.iter().map(|&(name, _, _)| name),
+                policy,
+                16,
+            )
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline")
+    }
+
+    #[test]
+    fn test_broadcast_fan_out() {
+        let local = Arc::new(Mutex::new(Vec::new()));
+        let shared = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = fan_out_pipeline(
+            3,
+            &[("local", true, &local), ("shared", false, &shared)],
+            DispatchPolicy::Broadcast,
+        );
+
+        // The fan-out bridges the local receiver and the shared exporter.
+        assert_eq!(
+            pipeline.graph().to_string(),
+            "receiver [local receiver]\n\
+             local [local exporter]\n\
+             shared [shared exporter]\n\
+             receiver -> local\n\
+             receiver -> shared (bridged)\n"
+        );
+        let (done_local, done_shared) = (local.clone(), shared.clone());
+        run_pipeline_until(pipeline, move || {
+            done_local.lock().unwrap().len() >= 3 && done_shared.lock().unwrap().len() >= 3
+        });
+
+        let expected: Vec<_> = (0..3).map(|i| TestMsg::new(format!("msg {i}"))).collect();
+        assert_eq!(*local.lock().unwrap(), expected);
+        assert_eq!(*shared.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_round_robin_fan_out() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = fan_out_pipeline(
+            1000,
+            &[("first", true, &first), ("second", true, &second)],
+            DispatchPolicy::RoundRobin,
+        );
+
+        let (done_first, done_second) = (first.clone(), second.clone());
+        run_pipeline_until(pipeline, move || {
+            done_first.lock().unwrap().len() + done_second.lock().unwrap().len() >= 1000
+        });
+
+        let (first, second) = (first.lock().unwrap().len(), second.lock().unwrap().len());
+        assert_eq!(first + second, 1000);
+        assert!(
+            (400..=600).contains(&first),
+            "Uneven split: {first} / {second}"
+        );
+    }
+
+    /// A receiver emitting a message per frame read from the TCP connections it accepts, and
+    /// acknowledging every frame once emitted.
+    struct TcpFrameReceiver {
+        /// Receives the address the receiver is bound to.
+        bound: oneshot::Sender<SocketAddr>,
+    }
+
+    #[async_trait(?Send)]
+    impl local_receiver::Receiver<SourceTagged<TestMsg>> for TcpFrameReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_msg_recv: local_receiver::ControlChannel,
+            effect_handler: local_receiver::EffectHandler<SourceTagged<TestMsg>>,
+        ) -> Result<(), Error<SourceTagged<TestMsg>>> {
+            let addr = "127.0.0.1:0".parse().expect("Invalid address");
+            let listener = effect_handler.tcp_listener(addr)?;
+            _ = self
+                .bound
+                .send(listener.local_addr().expect("No bound address"));
+            effect_handler
+                .serve_connections(
+                    listener,
+                    &mut ctrl_msg_recv,
+                    |_| {},
+                    |mut socket, _| {
+                        let effect_handler = effect_handler.clone();
+                        async move {
+                            let mut buf = [0u8; 64];
+                            while let Ok(n @ 1..) = socket.read(&mut buf).await {
+                                let frame = String::from_utf8_lossy(&buf[..n]).into_owned();
+                                let tagged = SourceTagged::new(TestMsg(frame));
+                                if effect_handler.send_message(tagged).await.is_err()
+                                    || socket.write_all(b"ack").await.is_err()
+                                {
+                                    break;
+                                }
+                            }
+                        }
+                    },
+                )
+                .await
+        }
+    }
+
+    /// A receiver failing as soon as it starts.
+    struct FailingReceiver;
+
+    #[async_trait(?Send)]
+    impl local_receiver::Receiver<SourceTagged<TestMsg>> for FailingReceiver {
+        async fn start(
+            self: Box<Self>,
+            _ctrl_msg_recv: local_receiver::ControlChannel,
+            _effect_handler: local_receiver::EffectHandler<SourceTagged<TestMsg>>,
+        ) -> Result<(), Error<SourceTagged<TestMsg>>> {
+            Err(Error::ReceiverError {
+                receiver: "failing".into(),
+                error: "Test failure".to_owned(),
+            })
+        }
+    }
+
+    /// A processor recording the source of every message it forwards.
+    struct SourceRecorder {
+        sources: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait(?Send)]
+    impl local_processor::Processor<SourceTagged<TestMsg>> for SourceRecorder {
+        async fn process(
+            &mut self,
+            msg: Message<SourceTagged<TestMsg>>,
+            effect_handler: &mut local_processor::EffectHandler<SourceTagged<TestMsg>>,
+        ) -> Result<(), Error<SourceTagged<TestMsg>>> {
+            if let Message::PData(tagged) = msg {
+                self.sources.lock().unwrap().push(tagged.source.to_string());
+                effect_handler.send_message(tagged).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// An exporter dropping all the messages it receives.
+    struct DiscardExporter;
+
+    #[async_trait(?Send)]
+    impl local_exporter::Exporter<SourceTagged<TestMsg>> for DiscardExporter {
+        async fn start(
+            self: Box<Self>,
+            mut msg_chan: MessageChannel<SourceTagged<TestMsg>>,
+            _effect_handler: local_exporter::EffectHandler<SourceTagged<TestMsg>>,
+        ) -> Result<(), Error<SourceTagged<TestMsg>>> {
+            loop {
+                if let Message::Control(ctrl_msg) = msg_chan.recv().await? {
+                    if ctrl_msg.is_shutdown() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends `count` frames to the address, waiting for the acknowledgment of every frame.
+    async fn send_frames(addr: SocketAddr, count: usize) {
+        let mut stream = TcpStream::connect(addr).await.expect("Failed to connect");
+        let mut ack = [0u8; 3];
+        for i in 0..count {
+            stream
+                .write_all(format!("frame {i}").as_bytes())
+                .await
+                .expect("Failed to send a frame");
+            _ = stream
+                .read_exact(&mut ack)
+                .await
+                .expect("Failed to read the acknowledgment");
+        }
+    }
+
+    /// Waits for the recorder to record the given number of messages.
+    async fn wait_for_sources(sources: &Arc<Mutex<Vec<String>>>, count: usize) {
+        timeout(Duration::from_secs(5), async {
+            while sources.lock().unwrap().len() < count {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timed out waiting for the merged messages");
+    }
+
+    /// Builds a pipeline merging the output of the given receivers into a `SourceRecorder`.
+    fn fan_in_pipeline(
+        receivers: Vec<(&'static str, ReceiverWrapper<SourceTagged<TestMsg>>)>,
+        sources: &Arc<Mutex<Vec<String>>>,
+    ) -> Pipeline<SourceTagged<TestMsg>> {
+        let names: Vec<_> = receivers.iter().map(|(name, _)| *name).collect();
+        let mut builder = PipelineBuilder::new();
+        for (name, receiver) in receivers {
+            builder = builder.add_receiver(name, receiver);
+        }
+        builder
+            .add_processor(
+                "recorder",
+                ProcessorWrapper::local(
+                    SourceRecorder {
+                        sources: sources.clone(),
+                    },
+                    &ProcessorConfig::new("recorder"),
+                ),
+            )
+            .add_exporter(
+                "exporter",
+                ExporterWrapper::local(DiscardExporter, &ExporterConfig::new("exporter")),
+            )
+            .fan_in_tagged(names, "recorder", 16)
+            .connect("recorder", "exporter")
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline")
+    }
+
+    #[test]
+    fn test_tcp_receivers_fan_in() {
+        let sources = Arc::new(Mutex::new(Vec::new()));
+        let (otlp_tx, otlp_rx) = oneshot::channel();
+        let (otap_tx, otap_rx) = oneshot::channel();
+        let pipeline = fan_in_pipeline(
+            vec![
+                (
+                    "otlp",
+                    ReceiverWrapper::local(
+                        TcpFrameReceiver { bound: otlp_tx },
+                        &ReceiverConfig::new("otlp"),
+                    ),
+                ),
+                (
+                    "otap",
+                    ReceiverWrapper::local(
+                        TcpFrameReceiver { bound: otap_tx },
+                        &ReceiverConfig::new("otap"),
+                    ),
+                ),
+            ],
+            &sources,
+        );
+        assert_eq!(
+            pipeline.graph().to_string(),
+            "otlp [local receiver]\n\
+             otap [local receiver]\n\
+             recorder [local processor]\n\
+             exporter [local exporter]\n\
+             otlp -> recorder\n\
+             otap -> recorder\n\
+             recorder -> exporter\n"
+        );
+        let (_, otlp_control) = pipeline
+            .control_senders()
+            .into_iter()
+            .find(|(name, _)| name == "otlp")
+            .expect("No otlp receiver");
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        let recorded = sources.clone();
+        LocalSet::new().block_on(&rt, async move {
+            let handle = pipeline.run();
+            let otlp = otlp_rx.await.expect("The otlp receiver didn't bind");
+            let otap = otap_rx.await.expect("The otap receiver didn't bind");
+
+            _ = tokio::join!(send_frames(otlp, 50), send_frames(otap, 50));
+            wait_for_sources(&recorded, 100).await;
+
+            // The merge goes on with the other receiver once one of them is shut down.
+            otlp_control
+                .send(ControlMsg::Shutdown {
+                    deadline: Duration::from_millis(100),
+                    reason: "Test".to_owned(),
+                })
+                .await
+                .expect("Failed to shut the otlp receiver down");
+            send_frames(otap, 10).await;
+            wait_for_sources(&recorded, 110).await;
+
+            handle.shutdown(Duration::from_millis(100)).await;
+            timeout(Duration::from_secs(5), handle.join())
+                .await
+                .expect("Timed out waiting for the pipeline")
+                .expect("Pipeline failed");
+        });
+
+        let sources = sources.lock().unwrap();
+        let count = |source: &str| sources.iter().filter(|s| *s == source).count();
+        assert_eq!((count("otlp"), count("otap")), (50, 60));
+        // Neither receiver is starved while both emit.
+        assert!(sources[..50].iter().any(|s| s == "otlp"));
+        assert!(sources[..50].iter().any(|s| s == "otap"));
+    }
+
+    #[test]
+    fn test_placed_tcp_receivers_fan_in() {
+        let scheduler = CoreScheduler::new(&[0, 1]).expect("Failed to create the scheduler");
+        let sources = Arc::new(Mutex::new(Vec::new()));
+        let factory_threads = Arc::new(Mutex::new(Vec::new()));
+        let mut bound = Vec::new();
+        let mut builder = PipelineBuilder::new();
+        for (core, name) in [(0, "otlp"), (1, "otap")] {
+            let (bound_tx, bound_rx) = oneshot::channel();
+            bound.push(bound_rx);
+            let factory_threads = factory_threads.clone();
+            let receiver = scheduler
+                .spawn_local_node(core, move || {
+                    factory_threads
+                        .lock()
+                        .unwrap()
+                        .push(std::thread::current().name().map(str::to_owned));
+                    ReceiverWrapper::local(
+                        TcpFrameReceiver { bound: bound_tx },
+                        &ReceiverConfig::new(name),
+                    )
+                })
+                .map_err(|e| e.to_string())
+                .expect("Failed to place the receiver");
+            builder = builder.add_placed_receiver(name, receiver);
+        }
+        let pipeline = builder
+            .add_processor(
+                "recorder",
+                ProcessorWrapper::local(
+                    SourceRecorder {
+                        sources: sources.clone(),
+                    },
+                    &ProcessorConfig::new("recorder"),
+                ),
+            )
+            .add_exporter(
+                "exporter",
+                ExporterWrapper::local(DiscardExporter, &ExporterConfig::new("exporter")),
+            )
+            .fan_in_tagged(["otlp", "otap"], "recorder", 16)
+            .connect("recorder", "exporter")
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+        // Each receiver is built on the scheduler thread of its core.
+        assert_eq!(
+            *factory_threads.lock().unwrap(),
+            vec![
+                Some("otap-core-0".to_owned()),
+                Some("otap-core-1".to_owned())
+            ]
+        );
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        let recorded = sources.clone();
+        LocalSet::new().block_on(&rt, async move {
+            let handle = pipeline.run();
+            let mut addrs = Vec::new();
+            for bound_rx in bound {
+                addrs.push(bound_rx.await.expect("A placed receiver didn't bind"));
+            }
+
+            _ = tokio::join!(send_frames(addrs[0], 50), send_frames(addrs[1], 50));
+            wait_for_sources(&recorded, 100).await;
+
+            handle.shutdown(Duration::from_millis(100)).await;
+            timeout(Duration::from_secs(5), handle.join())
+                .await
+                .expect("Timed out waiting for the pipeline")
+                .expect("Pipeline failed");
+            // The scheduler threads complete with the receivers placed on them.
+            timeout(Duration::from_secs(5), scheduler.join())
+                .await
+                .expect("Timed out waiting for the scheduler threads");
+        });
+
+        let sources = sources.lock().unwrap();
+        let count = |source: &str| sources.iter().filter(|s| *s == source).count();
+        assert_eq!((count("otlp"), count("otap")), (50, 50));
+        assert!(sources[..50].iter().any(|s| s == "otlp"));
+        assert!(sources[..50].iter().any(|s| s == "otap"));
+    }
+
+    #[test]
+    fn test_fan_in_survives_a_failed_upstream() {
+        let sources = Arc::new(Mutex::new(Vec::new()));
+        let (bound_tx, bound_rx) = oneshot::channel();
+        let pipeline = fan_in_pipeline(
+            vec![
+                (
+                    "failing",
+                    ReceiverWrapper::local(FailingReceiver, &ReceiverConfig::new("failing")),
+                ),
+                (
+                    "otap",
+                    ReceiverWrapper::local(
+                        TcpFrameReceiver { bound: bound_tx },
+                        &ReceiverConfig::new("otap"),
+                    ),
+                ),
+            ],
+            &sources,
+        );
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        let recorded = sources.clone();
+        let result = LocalSet::new().block_on(&rt, async move {
+            let handle = pipeline.run();
+            let otap = bound_rx.await.expect("The otap receiver didn't bind");
+
+            send_frames(otap, 10).await;
+            wait_for_sources(&recorded, 10).await;
+
+            handle.shutdown(Duration::from_millis(100)).await;
+            timeout(Duration::from_secs(5), handle.join_with_report())
+                .await
+                .expect("Timed out waiting for the pipeline")
+        });
+
+        // Only the receiver which shut down cleanly is summarized.
+        let (result, report) = result;
+        assert_eq!(
+            report,
+            ShutdownReport {
+                messages_emitted: 10,
+                messages_dropped: 0,
+                connections_closed: 1,
+            }
+        );
+        assert!(matches!(
+            result,
+            Err(Error::ReceiverError { receiver, .. }) if receiver == "failing"
+        ));
+        assert_eq!(*sources.lock().unwrap(), vec!["otap"; 10]);
+    }
+
+    /// An exporter never looking at its input channel, simulating a stage ignoring its shutdown.
+    struct StuckExporter;
+
+    #[async_trait(?Send)]
+    impl local_exporter::Exporter<TestMsg> for StuckExporter {
+        async fn start(
+            self: Box<Self>,
+            _msg_chan: MessageChannel<TestMsg>,
+            _effect_handler: local_exporter::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            std::future::pending().await
+        }
+    }
+
+    /// Runs the pipeline, then shuts it down with `shutdown_with_timeout` and returns the outcome.
+    fn run_and_shutdown_with_timeout(
+        pipeline: Pipeline<TestMsg>,
+        timeout: Duration,
+    ) -> Result<(), ShutdownError<TestMsg>> {
         let rt = Builder::new_current_thread()
             .enable_all()
             .build()
             .expect("Failed to create runtime");
         let local_tasks = LocalSet::new();
 
-        // local_tasks.spawn_local(async move {
-        //     let Pipeline {
-        //         receivers,
-        //         processors,
-        //         exporters,
-        //     } = self;
-
-        // Start exporters first (they need to be ready to receive data)
-        // let mut exporter_handles = HashMap::new();
-        // for (name, exporter) in exporters {
-        //     let handle = spawn_local(async move {
-        //         if let Err(e) = exporter.start().await {
-        //             eprintln!("Exporter '{}' failed: {:?}", name, e);
-        //             return Err(e);
-        //         }
-        //         Ok(())
-        //     });
-        //     exporter_handles.insert(name, handle);
-        // }
-
-        // Start processors next
-        // let mut processor_handles = HashMap::new();
-        // for (name, processor) in processors {
-        //     let handle = spawn_local(async move {
-        //         if let Err(e) = processor.run().await {
-        //             eprintln!("Processor '{}' failed: {:?}", name, e);
-        //             return Err(e);
-        //         }
-        //         Ok(())
-        //     });
-        //     processor_handles.insert(name, handle);
-        // }
-
-        // Start receivers last
-        // let mut receiver_handles = HashMap::new();
-        // for (name, receiver) in receivers {
-        //     let handle = spawn_local(async move {
-        //         if let Err(e) = receiver.start().await {
-        //             eprintln!("Receiver '{}' failed: {:?}", name, e);
-        //             return Err(e);
-        //         }
-        //         Ok(())
-        //     });
-        //     receiver_handles.insert(name, handle);
-        // }
-
-        // Wait for all tasks to complete, gathering any errors
-        // let mut errors = Vec::new();
-
-        // Wait for receivers to complete first
-        // for (name, handle) in receiver_handles {
-        //     if let Err(e) = handle.await.unwrap() {
-        //         errors.push(e);
-        //     }
-        // }
-
-        // Then wait for processors
-        // for (name, handle) in processor_handles {
-        //     if let Err(e) = handle.await.unwrap() {
-        //         errors.push(e);
-        //     }
-        // }
-
-        // Finally wait for exporters
-        // for (name, handle) in exporter_handles {
-        //     if let Err(e) = handle.await.unwrap() {
-        //         errors.push(e);
-        //     }
-        // }
-
-        // Return the first error if any occurred
-        // if let Some(e) = errors.into_iter().next() {
-        //     return Err(e);
-        // }
-
-        //     Ok(())
-        // });
-
-        // Block on the local set to run all tasks to completion
-        rt.block_on(local_tasks);
-
-        Ok(())
+        local_tasks.block_on(&rt, async move {
+            let handle = pipeline.run();
+            // Let the stages start.
+            sleep(Duration::from_millis(50)).await;
+            handle.shutdown_with_timeout(timeout).await
+        })
+    }
+
+    #[test]
+    fn test_shutdown_with_timeout() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 3 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .exporter(ExporterWrapper::local(
+                CollectExporter {
+                    collected: collected.clone(),
+                },
+                &ExporterConfig::new("exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        let result = run_and_shutdown_with_timeout(pipeline, Duration::from_secs(1));
+        assert!(result.is_ok(), "Unexpected result {result:?}");
+        assert_eq!(collected.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_shutdown_with_timeout_lists_stuck_stages() {
+        let pipeline = PipelineBuilder::new()
+            .receiver(ReceiverWrapper::local(
+                GeneratorReceiver { count: 0 },
+                &ReceiverConfig::new("receiver"),
+            ))
+            .exporter(ExporterWrapper::local(
+                StuckExporter,
+                &ExporterConfig::new("stuck_exporter"),
+            ))
+            .build()
+            .map_err(|e| e.to_string())
+            .expect("Failed to build pipeline");
+
+        let result = run_and_shutdown_with_timeout(pipeline, Duration::from_millis(100));
+        assert!(
+            matches!(
+                &result,
+                Err(ShutdownError::TimedOut { stage_names }) if stage_names == &["stuck_exporter"]
+            ),
+            "Unexpected result {result:?}"
+        );
     }
 }
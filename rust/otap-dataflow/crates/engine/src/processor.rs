@@ -6,52 +6,82 @@
 //! For more details on the `!Send` implementation of a processor, see [`local::Processor`].
 //! See [`shared::Processor`] for the Send implementation.
 
-use crate::config::ProcessorConfig;
+use crate::config::{ProcessorConfig, TimerConfig};
+use crate::config_ack::ConfigAckWatcher;
 use crate::error::Error;
+use crate::flush_ack::FlushAckWatcher;
+use crate::health::{HealthProbe, NodeHealth, with_health_checks};
 use crate::local::processor as local;
-use crate::message::{ControlMsg, Message, Receiver, Sender};
+use crate::logging::LogSink;
+use crate::message::{
+    ControlMsg, ControlSender, Message, MessageChannel, Receiver, Sender, SharedSender,
+};
+use crate::shared::exporter::MessageChannel as SharedMessageChannel;
 use crate::shared::processor as shared;
+use crate::shutdown::{FORWARDED_CONTROL_CHANNEL_CAPACITY, run_with_shutdown_deadline};
 use otap_df_channel::mpsc;
+use std::borrow::Cow;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
 
 /// A wrapper for the processor that allows for both `Send` and `!Send` effect handlers.
 ///
 /// Note: This is useful for creating a single interface for the processor regardless of the effect
 /// handler type. This is the only type that the pipeline engine will use in order to be agnostic to
 /// the effect handler type.
-pub enum ProcessorWrapper<PData> {
+///
+/// The processor consumes `PDataIn` messages from its input channel (see `connect_input`) and
+/// produces `PDataOut` messages, the same type by default.
+pub enum ProcessorWrapper<PDataIn, PDataOut = PDataIn> {
     /// A processor with a `!Send` implementation.
     Local {
         /// The processor instance.
-        processor: Box<dyn local::Processor<PData>>,
+        processor: Box<dyn local::Processor<PDataIn, PDataOut>>,
         /// The effect handler for the processor.
-        effect_handler: local::EffectHandler<PData>,
+        effect_handler: local::EffectHandler<PDataOut>,
         /// A sender for control messages.
         control_sender: Sender<ControlMsg>,
         /// A receiver for control messages.
         control_receiver: Receiver<ControlMsg>,
+        /// The receiver of the input pdata messages (see `connect_input`).
+        input_receiver: Option<Receiver<PDataIn>>,
         /// A receiver for pdata messages.
-        pdata_receiver: Option<Receiver<PData>>,
+        pdata_receiver: Option<Receiver<PDataOut>>,
+        /// The liveness probe of the processor.
+        health: HealthProbe,
+        /// The cadence of the `TimerTick` messages delivered to the processor.
+        timer: Option<TimerConfig>,
+        /// The control message senders of the downstream nodes (see `connect_downstream_control`).
+        downstream_control_senders: Vec<Sender<ControlMsg>>,
     },
     /// A processor with a `Send` implementation.
     Shared {
         /// The processor instance.
-        processor: Box<dyn shared::Processor<PData>>,
+        processor: Box<dyn shared::Processor<PDataIn, PDataOut>>,
         /// The effect handler for the processor.
-        effect_handler: shared::EffectHandler<PData>,
+        effect_handler: shared::EffectHandler<PDataOut>,
         /// A sender for control messages.
         control_sender: tokio::sync::mpsc::Sender<ControlMsg>,
         /// A receiver for control messages.
         control_receiver: tokio::sync::mpsc::Receiver<ControlMsg>,
+        /// The receiver of the input pdata messages (see `connect_input`).
+        input_receiver: Option<tokio::sync::mpsc::Receiver<PDataIn>>,
         /// A receiver for pdata messages.
-        pdata_receiver: Option<tokio::sync::mpsc::Receiver<PData>>,
+        pdata_receiver: Option<tokio::sync::mpsc::Receiver<PDataOut>>,
+        /// The liveness probe of the processor.
+        health: HealthProbe,
+        /// The cadence of the `TimerTick` messages delivered to the processor.
+        timer: Option<TimerConfig>,
+        /// The control message senders of the downstream nodes (see `connect_downstream_control`).
+        downstream_control_senders: Vec<SharedSender<ControlMsg>>,
     },
 }
 
-impl<PData> ProcessorWrapper<PData> {
+impl<PDataIn, PDataOut> ProcessorWrapper<PDataIn, PDataOut> {
     /// Creates a new local `ProcessorWrapper` with the given processor and appropriate effect handler.
     pub fn local<P>(processor: P, config: &ProcessorConfig) -> Self
     where
-        P: local::Processor<PData> + 'static,
+        P: local::Processor<PDataIn, PDataOut> + 'static,
     {
         let (control_sender, control_receiver) =
             mpsc::Channel::new(config.control_channel.capacity);
@@ -66,14 +96,18 @@ impl<PData> ProcessorWrapper<PData> {
             ),
             control_sender: Sender::Local(control_sender),
             control_receiver: Receiver::Local(control_receiver),
+            input_receiver: None,
             pdata_receiver: Some(Receiver::Local(pdata_receiver)),
+            health: HealthProbe::new(config.health_check),
+            timer: config.timer,
+            downstream_control_senders: Vec::new(),
         }
     }
 
     /// Creates a new shared `ProcessorWrapper` with the given processor and appropriate effect handler.
     pub fn shared<P>(processor: P, config: &ProcessorConfig) -> Self
     where
-        P: shared::Processor<PData> + 'static,
+        P: shared::Processor<PDataIn, PDataOut> + 'static,
     {
         let (control_sender, control_receiver) =
             tokio::sync::mpsc::channel(config.control_channel.capacity);
@@ -85,12 +119,291 @@ impl<PData> ProcessorWrapper<PData> {
             effect_handler: shared::EffectHandler::new(config.name.clone(), pdata_sender),
             control_sender,
             control_receiver,
+            input_receiver: None,
             pdata_receiver: Some(pdata_receiver),
+            health: HealthProbe::new(config.health_check),
+            timer: config.timer,
+            downstream_control_senders: Vec::new(),
+        }
+    }
+
+    /// Returns the name of the processor.
+    #[must_use]
+    pub fn name(&self) -> Cow<'static, str> {
+        match self {
+            ProcessorWrapper::Local { effect_handler, .. } => effect_handler.processor_name(),
+            ProcessorWrapper::Shared { effect_handler, .. } => effect_handler.processor_name(),
+        }
+    }
+
+    /// Emits the messages logged by the processor (see the `info_message`, `warn_message` and
+    /// `error_message` methods of its effect handler) to the given sink, tagged with the given
+    /// pipeline id. Without it, the messages are emitted as `tracing` events (see
+    /// [`crate::logging::TracingLogSink`]).
+    pub fn set_log_sink(&mut self, sink: Arc<dyn LogSink>, pipeline_id: Option<Cow<'static, str>>) {
+        match self {
+            ProcessorWrapper::Local { effect_handler, .. } => {
+                effect_handler.set_log_sink(sink, pipeline_id);
+            }
+            ProcessorWrapper::Shared { effect_handler, .. } => {
+                effect_handler.set_log_sink(sink, pipeline_id);
+            }
+        }
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the processor (see
+    /// [`crate::config_ack`]).
+    #[must_use]
+    pub fn config_acks(&self) -> ConfigAckWatcher {
+        match self {
+            ProcessorWrapper::Local { effect_handler, .. } => effect_handler.config_acks(),
+            ProcessorWrapper::Shared { effect_handler, .. } => effect_handler.config_acks(),
+        }
+    }
+
+    /// Returns a watcher used to send flushes to the processor and to await their acknowledgement
+    /// (see [`crate::flush_ack`]).
+    #[must_use]
+    pub fn flush_acks(&self) -> FlushAckWatcher {
+        match self {
+            ProcessorWrapper::Local { effect_handler, .. } => effect_handler.flush_acks(),
+            ProcessorWrapper::Shared { effect_handler, .. } => effect_handler.flush_acks(),
+        }
+    }
+
+    /// Returns the current health of the processor (see [`crate::health`]).
+    #[must_use]
+    pub fn health(&self) -> NodeHealth {
+        self.health_probe().health()
+    }
+
+    /// Returns a probe tracking the health of the processor once started.
+    #[must_use]
+    pub fn health_probe(&self) -> HealthProbe {
+        match self {
+            ProcessorWrapper::Local { health, .. } | ProcessorWrapper::Shared { health, .. } => {
+                health.clone()
+            }
+        }
+    }
+
+    /// Returns the control message sender for the processor.
+    #[must_use]
+    pub fn control_sender(&self) -> Sender<ControlMsg> {
+        match self {
+            ProcessorWrapper::Local { control_sender, .. } => control_sender.clone(),
+            ProcessorWrapper::Shared { control_sender, .. } => {
+                Sender::Shared(control_sender.clone())
+            }
+        }
+    }
+
+    /// Connects the control channel of a downstream node. Control messages that must propagate
+    /// through the pipeline (i.e. `Flush`) are forwarded to every connected downstream node once
+    /// this processor has handled them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcessorError`] if a local control sender is connected to a shared
+    /// processor.
+    pub fn connect_downstream_control(
+        &mut self,
+        control_sender: Sender<ControlMsg>,
+    ) -> Result<(), Error<PDataOut>> {
+        match (self, control_sender) {
+            (
+                ProcessorWrapper::Local {
+                    downstream_control_senders,
+                    ..
+                },
+                control_sender,
+            ) => downstream_control_senders.push(control_sender),
+            (
+                ProcessorWrapper::Shared {
+                    effect_handler,
+                    downstream_control_senders,
+                    ..
+                },
+                control_sender,
+            ) => match control_sender.into_shared() {
+                Some(control_sender) => downstream_control_senders.push(control_sender),
+                None => {
+                    return Err(Error::ProcessorError {
+                        processor: effect_handler.processor_name(),
+                        error: "Shared ProcessorWrapper requires shared channels".to_owned(),
+                    });
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Connects the channel the processor consumes its pdata messages from, typically the
+    /// receiver taken from the upstream node (see e.g. `take_pdata_receiver`). Must be called
+    /// before `start`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcessorError`] if a local channel is connected to a shared processor.
+    pub fn connect_input(&mut self, pdata_rx: Receiver<PDataIn>) -> Result<(), Error<PDataOut>> {
+        match (self, pdata_rx) {
+            (ProcessorWrapper::Local { input_receiver, .. }, pdata_rx) => {
+                *input_receiver = Some(pdata_rx);
+            }
+            (ProcessorWrapper::Shared { input_receiver, .. }, Receiver::Shared(pdata_rx)) => {
+                *input_receiver = Some(pdata_rx);
+            }
+            (ProcessorWrapper::Shared { effect_handler, .. }, Receiver::Local(_)) => {
+                return Err(Error::ProcessorError {
+                    processor: effect_handler.processor_name(),
+                    error: "Shared ProcessorWrapper requires shared channels".to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the processor and drives its main loop until a `Shutdown` control message is
+    /// processed or the input pdata channel is closed.
+    ///
+    /// Control messages are prioritized over pdata messages (see [`MessageChannel`]). Every
+    /// message, including the final `Shutdown`, is passed to the processor's `process` method.
+    /// A `Flush` is forwarded to the downstream nodes after the processor has handled it.
+    /// Once a `Shutdown` has been delivered, the processor is given the shutdown deadline to
+    /// complete, after which it is dropped and an [`Error::ShutdownTimeout`] is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcessorError`] if no input channel is connected (see
+    /// `connect_input`).
+    pub async fn start(self) -> Result<(), Error<PDataOut>> {
+        match self {
+            ProcessorWrapper::Local {
+                mut processor,
+                mut effect_handler,
+                control_sender,
+                control_receiver,
+                input_receiver,
+                health,
+                timer,
+                downstream_control_senders,
+                ..
+            } => {
+                let Some(pdata_rx) = input_receiver else {
+                    return Err(no_input_error(effect_handler.processor_name()));
+                };
+                let (node_control_tx, node_control_rx) =
+                    mpsc::Channel::new(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+                let mut message_channel =
+                    MessageChannel::new(Receiver::Local(node_control_rx), pdata_rx)
+                        .track_health(health.clone())
+                        .track_telemetry(effect_handler.telemetry());
+                with_health_checks(
+                    effect_handler.processor_name(),
+                    health,
+                    control_sender,
+                    run_with_shutdown_deadline(
+                        effect_handler.processor_name(),
+                        control_receiver,
+                        node_control_tx,
+                        timer,
+                        None,
+                        effect_handler.telemetry(),
+                        async move {
+                            loop {
+                                let msg = message_channel.recv().await?;
+                                let is_shutdown = msg.is_shutdown();
+                                let flush = match &msg {
+                                    Message::Control(ctrl_msg) if ctrl_msg.is_flush() => {
+                                        Some(ctrl_msg.clone())
+                                    }
+                                    _ => None,
+                                };
+                                processor.process(msg, &mut effect_handler).await?;
+                                if let Some(flush) = flush {
+                                    forward_downstream(
+                                        effect_handler.processor_name(),
+                                        &downstream_control_senders,
+                                        flush,
+                                    )
+                                    .await?;
+                                }
+                                if is_shutdown {
+                                    break;
+                                }
+                            }
+                            Ok(())
+                        },
+                    ),
+                )
+                .await
+            }
+            ProcessorWrapper::Shared {
+                processor,
+                effect_handler,
+                control_sender,
+                control_receiver,
+                input_receiver,
+                health,
+                timer,
+                downstream_control_senders,
+                ..
+            } => {
+                let Some(pdata_rx) = input_receiver else {
+                    return Err(no_input_error(effect_handler.processor_name()));
+                };
+                start_shared(
+                    processor,
+                    effect_handler,
+                    control_sender,
+                    control_receiver,
+                    pdata_rx,
+                    health,
+                    timer,
+                    downstream_control_senders,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Spawns the processor on the current runtime, local processors are spawned on the current
+    /// [`tokio::task::LocalSet`] while shared processors are spawned on the Tokio thread pool.
+    ///
+    /// Must be called from within a `LocalSet`.
+    pub(crate) fn spawn(self) -> JoinHandle<Result<(), Error<PDataOut>>>
+    where
+        PDataIn: Send + 'static,
+        PDataOut: Send + 'static,
+    {
+        match self {
+            ProcessorWrapper::Shared {
+                processor,
+                effect_handler,
+                control_sender,
+                control_receiver,
+                input_receiver: Some(pdata_rx),
+                health,
+                timer,
+                downstream_control_senders,
+                ..
+            } => tokio::spawn(start_shared(
+                processor,
+                effect_handler,
+                control_sender,
+                control_receiver,
+                pdata_rx,
+                health,
+                timer,
+                downstream_control_senders,
+            )),
+            // Local processors, and unconnected shared processors (reported by `start`).
+            processor => tokio::task::spawn_local(processor.start()),
         }
     }
 
     /// Call the processor's `process` method.
-    pub async fn process(&mut self, msg: Message<PData>) -> Result<(), Error<PData>> {
+    pub async fn process(&mut self, msg: Message<PDataIn>) -> Result<(), Error<PDataOut>> {
         match self {
             ProcessorWrapper::Local {
                 effect_handler,
@@ -106,7 +419,7 @@ impl<PData> ProcessorWrapper<PData> {
     }
 
     /// Takes the PData receiver from the wrapper and returns it.
-    pub fn take_pdata_receiver(&mut self) -> Receiver<PData> {
+    pub fn take_pdata_receiver(&mut self) -> Receiver<PDataOut> {
         match self {
             ProcessorWrapper::Local { pdata_receiver, .. } => {
                 pdata_receiver.take().expect("pdata_receiver is None")
@@ -118,16 +431,97 @@ impl<PData> ProcessorWrapper<PData> {
     }
 }
 
+/// Starts a shared processor, the returned future is `Send` as long as the pdata types are `Send`.
+async fn start_shared<PDataIn, PDataOut>(
+    mut processor: Box<dyn shared::Processor<PDataIn, PDataOut>>,
+    mut effect_handler: shared::EffectHandler<PDataOut>,
+    control_sender: tokio::sync::mpsc::Sender<ControlMsg>,
+    control_receiver: tokio::sync::mpsc::Receiver<ControlMsg>,
+    pdata_rx: tokio::sync::mpsc::Receiver<PDataIn>,
+    health: HealthProbe,
+    timer: Option<TimerConfig>,
+    downstream_control_senders: Vec<SharedSender<ControlMsg>>,
+) -> Result<(), Error<PDataOut>> {
+    let (node_control_tx, node_control_rx) =
+        tokio::sync::mpsc::channel(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+    let mut message_channel = SharedMessageChannel::new(node_control_rx, pdata_rx)
+        .track_health(health.clone())
+        .track_telemetry(effect_handler.telemetry());
+    with_health_checks(
+        effect_handler.processor_name(),
+        health,
+        control_sender,
+        run_with_shutdown_deadline(
+            effect_handler.processor_name(),
+            control_receiver,
+            node_control_tx,
+            timer,
+            None,
+            effect_handler.telemetry(),
+            async move {
+                loop {
+                    let msg = message_channel.recv().await?;
+                    let is_shutdown = msg.is_shutdown();
+                    let flush = match &msg {
+                        Message::Control(ctrl_msg) if ctrl_msg.is_flush() => Some(ctrl_msg.clone()),
+                        _ => None,
+                    };
+                    processor.process(msg, &mut effect_handler).await?;
+                    if let Some(flush) = flush {
+                        forward_downstream(
+                            effect_handler.processor_name(),
+                            &downstream_control_senders,
+                            flush,
+                        )
+                        .await?;
+                    }
+                    if is_shutdown {
+                        break;
+                    }
+                }
+                Ok(())
+            },
+        ),
+    )
+    .await
+}
+
+/// Returns the error reported when a processor is started without an input channel.
+fn no_input_error<PData>(processor: Cow<'static, str>) -> Error<PData> {
+    Error::ProcessorError {
+        processor,
+        error: "No input pdata channel connected".to_owned(),
+    }
+}
+
+/// Forwards a control message to all the given downstream nodes.
+async fn forward_downstream<PData, S: ControlSender>(
+    processor: Cow<'static, str>,
+    downstream_control_senders: &[S],
+    ctrl_msg: ControlMsg,
+) -> Result<(), Error<PData>> {
+    for sender in downstream_control_senders {
+        sender
+            .send_ctrl(ctrl_msg.clone())
+            .await
+            .map_err(|_| Error::ControlChannelClosed {
+                node: processor.clone(),
+            })?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::config::ProcessorConfig;
     use crate::local::processor as local;
-    use crate::message::ControlMsg::{Config, Shutdown, TimerTick};
-    use crate::message::Message;
+    use crate::message::ControlMsg::{Config, Flush, Shutdown, TimerTick};
+    use crate::message::{Message, NodeConfigUpdate, Receiver, Sender};
     use crate::processor::{Error, ProcessorWrapper};
     use crate::shared::processor as shared;
     use crate::testing::processor::TestRuntime;
     use crate::testing::processor::{TestContext, ValidateContext};
-    use crate::testing::{CtrlMsgCounters, TestMsg};
+    use crate::testing::{CtrlMsgCounters, TestMsg, create_not_send_channel, setup_test_runtime};
     use async_trait::async_trait;
     use serde_json::Value;
     use std::pin::Pin;
@@ -228,9 +622,12 @@ mod tests {
                 assert_eq!(msgs[0], TestMsg("Hello RECEIVED".to_string()));
 
                 // Process a Config event.
-                ctx.process(Message::config_ctrl_msg(Value::Null))
-                    .await
-                    .expect("Processor failed on Config");
+                ctx.process(Message::config_ctrl_msg(NodeConfigUpdate::for_all(
+                    1,
+                    Value::Null,
+                )))
+                .await
+                .expect("Processor failed on Config");
                 assert!(ctx.drain_pdata().await.is_empty());
 
                 // Process a Shutdown event.
@@ -254,6 +651,8 @@ mod tests {
                     1, // message
                     1, // config
                     1, // shutdown
+                    0, // pause
+                    0, // resume
                 );
             })
         }
@@ -286,4 +685,637 @@ mod tests {
             .run_test(scenario())
             .validate(validation_procedure());
     }
+
+    #[test]
+    fn test_processor_start_local() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let counters = CtrlMsgCounters::new();
+        let test_runtime: TestRuntime<TestMsg> = TestRuntime::new();
+        let mut processor =
+            ProcessorWrapper::local(TestProcessor::new(counters.clone()), test_runtime.config());
+        let control_sender = processor.control_sender();
+        let mut output_rx = processor.take_pdata_receiver();
+        let (input_tx, input_rx) = create_not_send_channel(10);
+
+        rt.block_on(local_tasks.run_until(async move {
+            processor
+                .connect_input(Receiver::Local(input_rx))
+                .expect("Failed to connect input");
+            let handle = tokio::task::spawn_local(processor.start());
+
+            input_tx
+                .send_async(TestMsg::new("Hello"))
+                .await
+                .expect("Failed to send pdata");
+            let output = output_rx.recv().await.expect("No output message");
+            assert_eq!(output, TestMsg::new("Hello RECEIVED"));
+
+            control_sender
+                .send(TimerTick {})
+                .await
+                .expect("Failed to send TimerTick");
+            control_sender
+                .send(Shutdown {
+                    deadline: Duration::from_millis(50),
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+
+            handle
+                .await
+                .expect("Processor task failed")
+                .expect("Processor loop failed");
+        }));
+
+        counters.assert(1, 1, 0, 1, 0, 0);
+    }
+
+    #[test]
+    fn test_processor_start_shared() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let counters = CtrlMsgCounters::new();
+        let test_runtime: TestRuntime<TestMsg> = TestRuntime::new();
+        let mut processor =
+            ProcessorWrapper::shared(TestProcessor::new(counters.clone()), test_runtime.config());
+        let control_sender = processor.control_sender();
+        let mut output_rx = processor.take_pdata_receiver();
+        let (input_tx, input_rx) = tokio::sync::mpsc::channel(10);
+
+        rt.block_on(local_tasks.run_until(async move {
+            processor
+                .connect_input(Receiver::Shared(input_rx))
+                .expect("Failed to connect input");
+            let handle = tokio::task::spawn_local(processor.start());
+
+            input_tx
+                .send(TestMsg::new("Hello"))
+                .await
+                .expect("Failed to send pdata");
+            let output = output_rx.recv().await.expect("No output message");
+            assert_eq!(output, TestMsg::new("Hello RECEIVED"));
+
+            control_sender
+                .send(Shutdown {
+                    deadline: Duration::from_millis(50),
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+
+            handle
+                .await
+                .expect("Processor task failed")
+                .expect("Processor loop failed");
+        }));
+
+        counters.assert(0, 1, 0, 1, 0, 0);
+    }
+
+    #[test]
+    fn test_processor_start_shared_requires_shared_channels() {
+        let (rt, local_tasks) = setup_test_runtime();
+        let test_runtime: TestRuntime<TestMsg> = TestRuntime::new();
+        let mut processor = ProcessorWrapper::shared(
+            TestProcessor::new(CtrlMsgCounters::new()),
+            test_runtime.config(),
+        );
+        let (_input_tx, input_rx) = create_not_send_channel::<TestMsg>(10);
+
+        let result = processor.connect_input(Receiver::Local(input_rx));
+        assert!(matches!(result, Err(Error::ProcessorError { .. })));
+
+        // The processor can't be started without an input channel.
+        let result = rt.block_on(local_tasks.run_until(processor.start()));
+        assert!(matches!(result, Err(Error::ProcessorError { .. })));
+    }
+
+    /// A processor buffering all the pdata it receives until a `Flush` (or `Shutdown`) is received.
+    struct BatchProcessor {
+        ctrl_msg_counters: CtrlMsgCounters,
+        buffer: Vec<TestMsg>,
+    }
+
+    impl BatchProcessor {
+        fn new(ctrl_msg_counters: CtrlMsgCounters) -> Self {
+            BatchProcessor {
+                ctrl_msg_counters,
+                buffer: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl local::Processor<TestMsg> for BatchProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut local::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            match msg {
+                Message::Control(ctrl_msg) => {
+                    self.ctrl_msg_counters.update_with(&ctrl_msg);
+                    if ctrl_msg.is_flush() || ctrl_msg.is_shutdown() {
+                        for data in self.buffer.drain(..) {
+                            effect_handler.send_message(data).await?;
+                        }
+                    }
+                }
+                Message::PData(data) => {
+                    self.ctrl_msg_counters.increment_message();
+                    self.buffer.push(data);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Processor<TestMsg> for BatchProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut shared::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            match msg {
+                Message::Control(ctrl_msg) => {
+                    self.ctrl_msg_counters.update_with(&ctrl_msg);
+                    if ctrl_msg.is_flush() || ctrl_msg.is_shutdown() {
+                        for data in self.buffer.drain(..) {
+                            effect_handler.send_message(data).await?;
+                        }
+                    }
+                }
+                Message::PData(data) => {
+                    self.ctrl_msg_counters.increment_message();
+                    self.buffer.push(data);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Feeds 10 messages to a batch processor, flushes it, and checks that all the messages are
+    /// emitted and that the flush is forwarded downstream.
+    fn run_flush_scenario(
+        mut processor: ProcessorWrapper<TestMsg>,
+        input_tx: Sender<TestMsg>,
+        input_rx: Receiver<TestMsg>,
+        counters: CtrlMsgCounters,
+    ) {
+        let (rt, local_tasks) = setup_test_runtime();
+        let control_sender = processor.control_sender();
+        let mut output_rx = processor.take_pdata_receiver();
+        let (downstream_tx, mut downstream_rx) = match &processor {
+            ProcessorWrapper::Local { .. } => {
+                let (tx, rx) = create_not_send_channel(10);
+                (Sender::Local(tx), Receiver::Local(rx))
+            }
+            ProcessorWrapper::Shared { .. } => {
+                let (tx, rx) = tokio::sync::mpsc::channel(10);
+                (Sender::Shared(tx), Receiver::Shared(rx))
+            }
+        };
+        processor
+            .connect_downstream_control(downstream_tx)
+            .expect("Failed to connect downstream control");
+
+        let scenario_counters = counters.clone();
+        rt.block_on(local_tasks.run_until(async move {
+            processor
+                .connect_input(input_rx)
+                .expect("Failed to connect input");
+            let handle = tokio::task::spawn_local(processor.start());
+
+            for i in 0..10 {
+                input_tx
+                    .send(TestMsg::new(format!("msg {i}")))
+                    .await
+                    .expect("Failed to send pdata");
+            }
+            // Control messages have priority over pdata, wait for all the messages to be buffered.
+            while scenario_counters.get_message_count() < 10 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(output_rx.try_recv().is_err());
+
+            control_sender
+                .send(Flush {
+                    id: 1,
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Flush");
+            for i in 0..10 {
+                let output = output_rx.recv().await.expect("No output message");
+                assert_eq!(output, TestMsg::new(format!("msg {i}")));
+            }
+            let forwarded = downstream_rx.recv().await.expect("Flush not forwarded");
+            assert!(forwarded.is_flush());
+
+            control_sender
+                .send(Shutdown {
+                    deadline: Duration::from_millis(0),
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+            handle
+                .await
+                .expect("Processor task failed")
+                .expect("Processor loop failed");
+        }));
+
+        assert_eq!(counters.get_flush_count(), 1);
+        counters.assert(0, 10, 0, 1, 0, 0);
+    }
+
+    #[test]
+    fn test_processor_flush_local() {
+        let counters = CtrlMsgCounters::new();
+        let test_runtime: TestRuntime<TestMsg> = TestRuntime::new();
+        let processor =
+            ProcessorWrapper::local(BatchProcessor::new(counters.clone()), test_runtime.config());
+        let (input_tx, input_rx) = create_not_send_channel(10);
+
+        run_flush_scenario(
+            processor,
+            Sender::Local(input_tx),
+            Receiver::Local(input_rx),
+            counters,
+        );
+    }
+
+    #[test]
+    fn test_processor_flush_shared() {
+        let counters = CtrlMsgCounters::new();
+        let test_runtime: TestRuntime<TestMsg> = TestRuntime::new();
+        let processor =
+            ProcessorWrapper::shared(BatchProcessor::new(counters.clone()), test_runtime.config());
+        let (input_tx, input_rx) = tokio::sync::mpsc::channel(10);
+
+        run_flush_scenario(
+            processor,
+            Sender::Shared(input_tx),
+            Receiver::Shared(input_rx),
+            counters,
+        );
+    }
+
+    /// Feeds a message to a batch processor and flushes it, while either the downstream control
+    /// channel or the output pdata channel of the processor has been dropped, and returns the
+    /// error the processor fails with.
+    fn run_flush_with_closed_channel(
+        drop_downstream_control: bool,
+        drop_output_pdata: bool,
+    ) -> Error<TestMsg> {
+        let (rt, local_tasks) = setup_test_runtime();
+        let counters = CtrlMsgCounters::new();
+        let mut processor = ProcessorWrapper::local(
+            BatchProcessor::new(counters.clone()),
+            &ProcessorConfig::new("batch_processor"),
+        );
+        let control_sender = processor.control_sender();
+        let output_rx = processor.take_pdata_receiver();
+        let (downstream_tx, downstream_rx) = create_not_send_channel(10);
+        processor
+            .connect_downstream_control(Sender::Local(downstream_tx))
+            .expect("Failed to connect downstream control");
+        let (input_tx, input_rx) = create_not_send_channel(10);
+
+        rt.block_on(local_tasks.run_until(async move {
+            processor
+                .connect_input(Receiver::Local(input_rx))
+                .expect("Failed to connect input");
+            let handle = tokio::task::spawn_local(processor.start());
+            let _downstream_rx = (!drop_downstream_control).then_some(downstream_rx);
+            let _output_rx = (!drop_output_pdata).then_some(output_rx);
+
+            input_tx
+                .send(TestMsg::new("msg"))
+                .expect("Failed to send pdata");
+            while counters.get_message_count() < 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            control_sender
+                .send(Flush {
+                    id: 1,
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Flush");
+            handle
+                .await
+                .expect("Processor task failed")
+                .expect_err("Processor loop succeeded")
+        }))
+    }
+
+    #[test]
+    fn test_closed_downstream_control_channel() {
+        let error = run_flush_with_closed_channel(true, false);
+        assert!(
+            matches!(&error, Error::ControlChannelClosed { node } if node == "batch_processor"),
+            "Unexpected error {error:?}"
+        );
+    }
+
+    #[test]
+    fn test_closed_output_pdata_channel() {
+        let error = run_flush_with_closed_channel(false, true);
+        assert!(
+            matches!(&error, Error::PdataChannelClosed { node } if node == "batch_processor"),
+            "Unexpected error {error:?}"
+        );
+    }
+
+    /// A processor forwarding its pdata messages unchanged.
+    struct PassThroughProcessor;
+
+    #[async_trait(?Send)]
+    impl local::Processor<TestMsg> for PassThroughProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut local::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            if let Message::PData(data) = msg {
+                effect_handler.send_message(data).await?;
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Processor<TestMsg> for PassThroughProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut shared::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            if let Message::PData(data) = msg {
+                effect_handler.send_message(data).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// A processor converting the test messages into their uppercased content.
+    struct UppercaseProcessor;
+
+    #[async_trait(?Send)]
+    impl local::Processor<TestMsg, String> for UppercaseProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut local::EffectHandler<String>,
+        ) -> Result<(), Error<String>> {
+            if let Message::PData(TestMsg(content)) = msg {
+                effect_handler.send_message(content.to_uppercase()).await?;
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Processor<TestMsg, String> for UppercaseProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut shared::EffectHandler<String>,
+        ) -> Result<(), Error<String>> {
+            if let Message::PData(TestMsg(content)) = msg {
+                effect_handler.send_message(content.to_uppercase()).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Connects an input channel to the processor, feeds it the given messages, and returns the
+    /// messages emitted by the processor before it is shut down.
+    fn run_processor<PDataOut: std::fmt::Debug + 'static>(
+        mut processor: ProcessorWrapper<TestMsg, PDataOut>,
+        inputs: &[&str],
+    ) -> Vec<PDataOut> {
+        let (rt, local_tasks) = setup_test_runtime();
+        let control_sender = processor.control_sender();
+        let mut output_rx = processor.take_pdata_receiver();
+        let (input_tx, input_rx) = match &processor {
+            ProcessorWrapper::Local { .. } => {
+                let (tx, rx) = create_not_send_channel(10);
+                (Sender::Local(tx), Receiver::Local(rx))
+            }
+            ProcessorWrapper::Shared { .. } => {
+                let (tx, rx) = tokio::sync::mpsc::channel(10);
+                (Sender::Shared(tx), Receiver::Shared(rx))
+            }
+        };
+        processor
+            .connect_input(input_rx)
+            .expect("Failed to connect input");
+        let inputs: Vec<_> = inputs.iter().map(|input| TestMsg::new(*input)).collect();
+
+        rt.block_on(local_tasks.run_until(async move {
+            let handle = tokio::task::spawn_local(processor.start());
+
+            let mut outputs = Vec::new();
+            for input in inputs {
+                input_tx.send(input).await.expect("Failed to send pdata");
+                outputs.push(output_rx.recv().await.expect("No output message"));
+            }
+
+            control_sender
+                .send(Shutdown {
+                    deadline: Duration::from_millis(50),
+                    reason: "test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+            handle
+                .await
+                .expect("Processor task failed")
+                .expect("Processor loop failed");
+            outputs
+        }))
+    }
+
+    #[test]
+    fn test_pass_through_processor_local() {
+        let processor =
+            ProcessorWrapper::local(PassThroughProcessor, &ProcessorConfig::new("pass_through"));
+        let outputs = run_processor(processor, &["first", "second"]);
+        assert_eq!(outputs, [TestMsg::new("first"), TestMsg::new("second")]);
+    }
+
+    #[test]
+    fn test_pass_through_processor_shared() {
+        let processor =
+            ProcessorWrapper::shared(PassThroughProcessor, &ProcessorConfig::new("pass_through"));
+        let outputs = run_processor(processor, &["first", "second"]);
+        assert_eq!(outputs, [TestMsg::new("first"), TestMsg::new("second")]);
+    }
+
+    #[test]
+    fn test_transforming_processor_local() {
+        let processor =
+            ProcessorWrapper::local(UppercaseProcessor, &ProcessorConfig::new("uppercase"));
+        let outputs = run_processor(processor, &["first", "second"]);
+        assert_eq!(outputs, ["FIRST", "SECOND"]);
+    }
+
+    #[test]
+    fn test_transforming_processor_shared() {
+        let processor =
+            ProcessorWrapper::shared(UppercaseProcessor, &ProcessorConfig::new("uppercase"));
+        let outputs = run_processor(processor, &["first", "second"]);
+        assert_eq!(outputs, ["FIRST", "SECOND"]);
+    }
+
+    /// A processor splitting its pdata messages into their words, sent as a batch.
+    struct SplittingProcessor;
+
+    #[async_trait(?Send)]
+    impl local::Processor<TestMsg> for SplittingProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut local::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            if let Message::PData(TestMsg(content)) = msg {
+                effect_handler
+                    .send_message_batch(content.split(' ').map(TestMsg::new))
+                    .await?;
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Processor<TestMsg> for SplittingProcessor {
+        async fn process(
+            &mut self,
+            msg: Message<TestMsg>,
+            effect_handler: &mut shared::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            if let Message::PData(TestMsg(content)) = msg {
+                let words: Vec<_> = content.split(' ').map(TestMsg::new).collect();
+                effect_handler.send_message_batch(words).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Returns the configuration of a splitting processor whose output channel buffers 2
+    /// messages.
+    fn splitting_config() -> ProcessorConfig {
+        let mut config = ProcessorConfig::new("splitting_processor");
+        config.output_pdata_channel.capacity = 2;
+        config
+    }
+
+    /// Feeds a sentence to a splitting processor, reads the given number of words from its output
+    /// channel then closes it, and returns the words read and the outcome of the processor.
+    fn run_splitting_processor(
+        mut processor: ProcessorWrapper<TestMsg>,
+        read: usize,
+    ) -> (Vec<TestMsg>, Result<(), Error<TestMsg>>) {
+        let (rt, local_tasks) = setup_test_runtime();
+        let control_sender = processor.control_sender();
+        let mut output_rx = processor.take_pdata_receiver();
+        let (input_tx, input_rx) = match &processor {
+            ProcessorWrapper::Local { .. } => {
+                let (tx, rx) = create_not_send_channel(10);
+                (Sender::Local(tx), Receiver::Local(rx))
+            }
+            ProcessorWrapper::Shared { .. } => {
+                let (tx, rx) = tokio::sync::mpsc::channel(10);
+                (Sender::Shared(tx), Receiver::Shared(rx))
+            }
+        };
+        processor
+            .connect_input(input_rx)
+            .expect("Failed to connect input");
+
+        rt.block_on(local_tasks.run_until(async move {
+            let handle = tokio::task::spawn_local(processor.start());
+            input_tx
+                .send(TestMsg::new("one two three four five six"))
+                .await
+                .expect("Failed to send pdata");
+
+            let mut words = Vec::new();
+            for _ in 0..read {
+                words.push(output_rx.recv().await.expect("No output message"));
+            }
+            // Lets the processor fill the output channel again before closing it.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(output_rx);
+
+            _ = control_sender
+                .send(Shutdown {
+                    deadline: Duration::from_millis(50),
+                    reason: "test".to_owned(),
+                })
+                .await;
+            let result = tokio::time::timeout(Duration::from_secs(1), handle)
+                .await
+                .expect("Timed out waiting for the processor")
+                .expect("Processor task failed");
+            (words, result)
+        }))
+    }
+
+    fn assert_send_message_batch(processor: ProcessorWrapper<TestMsg>) {
+        let (words, result) = run_splitting_processor(processor, 6);
+        assert_eq!(
+            words,
+            ["one", "two", "three", "four", "five", "six"].map(TestMsg::new)
+        );
+        assert!(result.is_ok(), "Unexpected result {result:?}");
+    }
+
+    fn assert_send_message_batch_interrupted(processor: ProcessorWrapper<TestMsg>) {
+        // 1 word read and 2 buffered when the output channel closes.
+        let (_, result) = run_splitting_processor(processor, 1);
+        assert!(
+            matches!(
+                &result,
+                Err(Error::PdataBatchInterrupted { node, accepted: 3 })
+                    if node == "splitting_processor"
+            ),
+            "Unexpected result {result:?}"
+        );
+    }
+
+    #[test]
+    fn test_send_message_batch_local() {
+        assert_send_message_batch(ProcessorWrapper::local(
+            SplittingProcessor,
+            &splitting_config(),
+        ));
+    }
+
+    #[test]
+    fn test_send_message_batch_shared() {
+        assert_send_message_batch(ProcessorWrapper::shared(
+            SplittingProcessor,
+            &splitting_config(),
+        ));
+    }
+
+    #[test]
+    fn test_send_message_batch_interrupted_local() {
+        assert_send_message_batch_interrupted(ProcessorWrapper::local(
+            SplittingProcessor,
+            &splitting_config(),
+        ));
+    }
+
+    #[test]
+    fn test_send_message_batch_interrupted_shared() {
+        assert_send_message_batch_interrupted(ProcessorWrapper::shared(
+            SplittingProcessor,
+            &splitting_config(),
+        ));
+    }
 }
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Read buffers of the connections served by socket receivers.
+//!
+//! Every effect handler of a receiver owns a pool of read buffers (shared by all its clones) of
+//! the size configured in [`crate::config::ReceiverConfig::read_buffer_size`]. A connection
+//! handler takes a [`ReadBuffer`] from the pool once (see the `read_buffer` method of the receiver
+//! effect handlers) and reads the whole connection into it, the buffer returns to the pool when
+//! dropped, so that neither the reads nor the subsequent connections allocate.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// The default size of the read buffers, large enough for most of the OTLP requests.
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A pool of read buffers of the same size.
+#[derive(Clone)]
+pub(crate) struct ReadBufferPool {
+    size: usize,
+    /// The buffers returned to the pool, ready to be reused.
+    free: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl Default for ReadBufferPool {
+    fn default() -> Self {
+        ReadBufferPool::new(DEFAULT_READ_BUFFER_SIZE)
+    }
+}
+
+impl ReadBufferPool {
+    /// Creates an empty pool of buffers of the given size.
+    pub(crate) fn new(size: usize) -> Self {
+        ReadBufferPool {
+            size,
+            free: Arc::default(),
+        }
+    }
+
+    /// Returns the size of the buffers of the pool.
+    pub(crate) fn buffer_size(&self) -> usize {
+        self.size
+    }
+
+    /// Takes a buffer from the pool, or allocates one if none is available.
+    pub(crate) fn acquire(&self) -> ReadBuffer {
+        let buf = self
+            .free
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .pop()
+            .unwrap_or_else(|| vec![0; self.size]);
+        ReadBuffer {
+            buf,
+            pool: self.free.clone(),
+        }
+    }
+}
+
+/// A read buffer taken from the pool of a receiver, returned to the pool when dropped.
+///
+/// The buffer dereferences to a byte slice of the configured size. Its content is left as is by
+/// the previous connection, only the bytes read by the current one are meaningful.
+pub struct ReadBuffer {
+    buf: Vec<u8>,
+    pool: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl Deref for ReadBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for ReadBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Drop for ReadBuffer {
+    fn drop(&mut self) {
+        let buf = std::mem::take(&mut self.buf);
+        // The pool stays consistent even if a thread panicked while holding the lock.
+        self.pool
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadBufferPool;
+
+    #[test]
+    fn test_read_buffer_reused() {
+        let pool = ReadBufferPool::new(16);
+        assert_eq!(pool.buffer_size(), 16);
+
+        let mut first = pool.acquire();
+        assert_eq!(first.len(), 16);
+        first[0] = 42;
+        let addr = first.as_ptr();
+        drop(first);
+
+        // The buffer returned to the pool is handed out again.
+        let second = pool.acquire();
+        assert_eq!(second.as_ptr(), addr);
+        assert_eq!(second[0], 42);
+
+        // A buffer is allocated while the pooled one is in use.
+        let third = pool.acquire();
+        assert_ne!(third.as_ptr(), addr);
+        assert_eq!(third.len(), 16);
+    }
+}
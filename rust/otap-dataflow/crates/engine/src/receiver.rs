@@ -6,12 +6,54 @@
 //! For more details on the `!Send` implementation of a receiver, see [`local::Receiver`].
 //! See [`shared::Receiver`] for the Send implementation.
 
-use crate::config::ReceiverConfig;
+use crate::ack::AckRouter;
+use crate::backpressure::with_backpressure;
+use crate::buffer_pool::{GlobalBufferPool, PoolMember, with_buffer_pool};
+use crate::config::{
+    BackpressureConfig, CancellationConfig, ConfigSchema, ControlChannelConfig, ControlChannelKind,
+    DrainPolicy, ReceiverConfig, RetryPolicy, TimerConfig,
+};
+use crate::config_ack::ConfigAckWatcher;
 use crate::error::Error;
+use crate::flush_ack::FlushAckWatcher;
+use crate::health::{HealthProbe, HealthStatus, NodeHealth, with_health_checks};
+use crate::lifecycle::{Lifecycle, LifecycleState};
 use crate::local::receiver as local;
-use crate::message::{ControlMsg, Receiver, Sender};
+use crate::logging::LogSink;
+use crate::message::{
+    BudgetedReceiver, ControlMsg, ControlReceiver, PriorityReceiver, PrioritySender, Receiver,
+    Sender, priority_channel, unbounded_priority_channel,
+};
 use crate::shared::receiver as shared;
+use crate::shutdown::{
+    CancellableControl, FORWARDED_CONTROL_CHANNEL_CAPACITY, drain_output,
+    run_with_shutdown_deadline,
+};
+use crate::spans;
+use crate::telemetry::{ReceiverMetrics, ShutdownReport};
+#[cfg(any(test, feature = "testing"))]
+use crate::testing::fault::FaultInjector;
+use async_trait::async_trait;
 use otap_df_channel::mpsc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// Rebuilds a receiver wrapper from its factory and configuration (see
+/// [`ReceiverWrapper::restart`]).
+type RestartFn<PData> = Rc<dyn Fn() -> ReceiverWrapper<PData>>;
+
+/// Builds a fresh instance of a local receiver.
+type LocalFactory<PData> = Rc<dyn Fn() -> Box<dyn local::Receiver<PData>>>;
+
+/// Builds a fresh instance of a shared receiver.
+type SharedFactory<PData> = Arc<dyn Fn() -> Box<dyn shared::Receiver<PData>> + Send + Sync>;
 
 /// A wrapper for the receiver that allows for both `Send` and `!Send` receivers.
 ///
@@ -25,11 +67,39 @@ pub enum ReceiverWrapper<PData> {
         /// The effect handler for the receiver.
         effect_handler: local::EffectHandler<PData>,
         /// A sender for control messages.
-        control_sender: mpsc::Sender<ControlMsg>,
-        /// A receiver for control messages.
-        control_receiver: mpsc::Receiver<ControlMsg>,
+        control_sender: PrioritySender<ControlMsg>,
+        /// A receiver for control messages, delivering `Shutdown`, `Flush`, `Pause` and `Resume`
+        /// first.
+        control_receiver: PriorityReceiver<ControlMsg>,
+        /// The back-pressure signaling configuration of the receiver.
+        backpressure: Option<BackpressureConfig>,
+        /// The liveness probe of the receiver.
+        health: HealthProbe,
+        /// The cadence of the `TimerTick` messages delivered to the receiver.
+        timer: Option<TimerConfig>,
+        /// What to do with the pdata buffered in the output channel once the receiver completed.
+        drain_policy: DrainPolicy,
         /// A receiver for pdata messages.
         pdata_receiver: Option<Receiver<PData>>,
+        /// Rebuilds the wrapper, if the receiver was created from a factory.
+        restart: Option<RestartFn<PData>>,
+        /// Builds the fresh receiver instances started in place of a failed one, if the receiver
+        /// was created from a factory.
+        factory: Option<LocalFactory<PData>>,
+        /// How the receiver is restarted in place when it fails (see [`ReceiverConfig::restart`]).
+        restart_policy: Option<RetryPolicy>,
+        /// Stops the receiver when its token is cancelled (see [`ReceiverConfig::cancellation`]).
+        cancellation: Option<CancellationConfig>,
+        /// Decodes the configuration updates of the receiver (see
+        /// [`ReceiverConfig::config_schema`]).
+        config_schema: Option<ConfigSchema>,
+        /// The restart attempt that created this instance of the receiver, 0 for the first one.
+        restart_attempt: u32,
+        /// How long `start` waits before starting the receiver, the backoff of a restart.
+        start_delay: Duration,
+        /// The receivers of the named output ports of the receiver (see
+        /// [`ReceiverWrapper::with_outputs`]).
+        out_port_receivers: HashMap<String, Receiver<PData>>,
     },
     /// A receiver with a `Send` implementation.
     Shared {
@@ -38,11 +108,39 @@ pub enum ReceiverWrapper<PData> {
         /// The effect handler for the receiver.
         effect_handler: shared::EffectHandler<PData>,
         /// A sender for control messages.
-        control_sender: tokio::sync::mpsc::Sender<ControlMsg>,
-        /// A receiver for control messages.
-        control_receiver: tokio::sync::mpsc::Receiver<ControlMsg>,
+        control_sender: PrioritySender<ControlMsg>,
+        /// A receiver for control messages, delivering `Shutdown`, `Flush`, `Pause` and `Resume`
+        /// first.
+        control_receiver: PriorityReceiver<ControlMsg>,
+        /// The back-pressure signaling configuration of the receiver.
+        backpressure: Option<BackpressureConfig>,
+        /// The liveness probe of the receiver.
+        health: HealthProbe,
+        /// The cadence of the `TimerTick` messages delivered to the receiver.
+        timer: Option<TimerConfig>,
+        /// What to do with the pdata buffered in the output channel once the receiver completed.
+        drain_policy: DrainPolicy,
         /// A receiver for pdata messages.
         pdata_receiver: Option<tokio::sync::mpsc::Receiver<PData>>,
+        /// Rebuilds the wrapper, if the receiver was created from a factory.
+        restart: Option<RestartFn<PData>>,
+        /// Builds the fresh receiver instances started in place of a failed one, if the receiver
+        /// was created from a factory.
+        factory: Option<SharedFactory<PData>>,
+        /// How the receiver is restarted in place when it fails (see [`ReceiverConfig::restart`]).
+        restart_policy: Option<RetryPolicy>,
+        /// Stops the receiver when its token is cancelled (see [`ReceiverConfig::cancellation`]).
+        cancellation: Option<CancellationConfig>,
+        /// Decodes the configuration updates of the receiver (see
+        /// [`ReceiverConfig::config_schema`]).
+        config_schema: Option<ConfigSchema>,
+        /// The restart attempt that created this instance of the receiver, 0 for the first one.
+        restart_attempt: u32,
+        /// How long `start` waits before starting the receiver, the backoff of a restart.
+        start_delay: Duration,
+        /// The receivers of the named output ports of the receiver (see
+        /// [`ReceiverWrapper::with_outputs`]).
+        out_port_receivers: HashMap<String, Receiver<PData>>,
     },
 }
 
@@ -52,21 +150,122 @@ impl<PData> ReceiverWrapper<PData> {
     where
         R: local::Receiver<PData> + 'static,
     {
-        let (control_sender, control_receiver) =
-            mpsc::Channel::new(config.control_channel.capacity);
+        Self::new_local(Box::new(receiver), config, None, None)
+    }
+
+    /// Creates a new `ReceiverWrapper` with a receiver built by the given factory, and the given
+    /// configuration. Unlike the wrappers created with [`ReceiverWrapper::local`], the wrapper can
+    /// be rebuilt with a fresh receiver instance (see [`ReceiverWrapper::restart`]), and a failed
+    /// receiver is restarted in place according to [`ReceiverConfig::restart`].
+    pub fn local_restartable<F, R>(factory: F, config: &ReceiverConfig) -> Self
+    where
+        F: Fn() -> R + 'static,
+        R: local::Receiver<PData> + 'static,
+        PData: 'static,
+    {
+        let factory: LocalFactory<PData> = Rc::new(move || Box::new(factory()));
+        Self::local_from_factory(factory, config.clone())
+    }
+
+    fn local_from_factory(factory: LocalFactory<PData>, config: ReceiverConfig) -> Self
+    where
+        PData: 'static,
+    {
+        let receiver = factory();
+        let restart_config = config.clone();
+        let restart_factory = factory.clone();
+        let restart: RestartFn<PData> = Rc::new(move || {
+            Self::local_from_factory(restart_factory.clone(), restart_config.clone())
+        });
+        Self::new_local(receiver, &config, Some(restart), Some(factory))
+    }
+
+    /// Creates a new `ReceiverWrapper` running the given shared receiver in a local context, with
+    /// the given configuration. The wrapper is a local one, e.g. to run a receiver implemented
+    /// for both contexts on the `LocalSet` of a pipeline, without implementing
+    /// [`local::Receiver`] as well.
+    ///
+    /// The control messages are forwarded to the shared control channel of the receiver, and its
+    /// effect handler shares the state of the local one. The output channels of the wrapper are
+    /// `Send`, the pdata messages are sent to the downstream node(s) as is.
+    pub fn local_from_shared<R>(receiver: R, config: &ReceiverConfig) -> Self
+    where
+        R: shared::Receiver<PData> + 'static,
+        PData: 'static,
+    {
+        let (pdata_sender, pdata_receiver) =
+            tokio::sync::mpsc::channel(config.output_pdata_channel.capacity);
+        Self::new_local_with_output(
+            Box::new(SharedAsLocal {
+                receiver: Box::new(receiver),
+            }),
+            config,
+            None,
+            None,
+            (
+                Sender::Shared(pdata_sender),
+                Receiver::Shared(pdata_receiver),
+            ),
+        )
+    }
+
+    fn new_local(
+        receiver: Box<dyn local::Receiver<PData>>,
+        config: &ReceiverConfig,
+        restart: Option<RestartFn<PData>>,
+        factory: Option<LocalFactory<PData>>,
+    ) -> Self {
         let (pdata_sender, pdata_receiver) =
             mpsc::Channel::new(config.output_pdata_channel.capacity);
+        Self::new_local_with_output(
+            receiver,
+            config,
+            restart,
+            factory,
+            (Sender::Local(pdata_sender), Receiver::Local(pdata_receiver)),
+        )
+    }
 
-        ReceiverWrapper::Local {
-            effect_handler: local::EffectHandler::new(
-                config.name.clone(),
-                Sender::Local(pdata_sender),
-            ),
-            receiver: Box::new(receiver),
+    fn new_local_with_output(
+        receiver: Box<dyn local::Receiver<PData>>,
+        config: &ReceiverConfig,
+        restart: Option<RestartFn<PData>>,
+        factory: Option<LocalFactory<PData>>,
+        (pdata_sender, pdata_receiver): (Sender<PData>, Receiver<PData>),
+    ) -> Self {
+        let (control_sender, control_receiver) = control_channel(&config.control_channel);
+
+        let mut effect_handler = local::EffectHandler::new(config.name.clone(), pdata_sender);
+        effect_handler.set_max_concurrent_connections(config.max_concurrent_connections);
+        effect_handler.set_read_buffer_size(config.read_buffer_size);
+        if let Some(sink) = &config.metrics_sink {
+            effect_handler.set_metrics_sink(sink.clone());
+        }
+        effect_handler.set_overflow_policy(config.output_pdata_channel.overflow_policy);
+        effect_handler.set_max_inflight_bytes(config.max_inflight_bytes);
+        effect_handler.set_buffer_pool_name(config.output_pdata_channel.pool.clone());
+        effect_handler.set_control_sender(control_sender.clone());
+
+        let wrapper = ReceiverWrapper::Local {
+            effect_handler,
+            receiver,
             control_sender,
             control_receiver,
-            pdata_receiver: Some(Receiver::Local(pdata_receiver)),
-        }
+            backpressure: config.backpressure,
+            health: HealthProbe::new(config.health_check),
+            timer: config.timer,
+            drain_policy: config.drain_policy,
+            pdata_receiver: Some(pdata_receiver),
+            restart,
+            factory,
+            restart_policy: config.restart,
+            cancellation: config.cancellation.clone(),
+            config_schema: config.config_schema.clone(),
+            restart_attempt: 0,
+            start_delay: Duration::ZERO,
+            out_port_receivers: HashMap::new(),
+        };
+        wrapper.with_configured_outputs(config)
     }
 
     /// Creates a new `ReceiverWrapper` with the given receiver and configuration.
@@ -74,366 +273,1188 @@ impl<PData> ReceiverWrapper<PData> {
     where
         R: shared::Receiver<PData> + 'static,
     {
-        let (control_sender, control_receiver) =
-            tokio::sync::mpsc::channel(config.control_channel.capacity);
+        Self::new_shared(Box::new(receiver), config, None, None)
+    }
+
+    /// Creates a new `ReceiverWrapper` with a receiver built by the given factory, and the given
+    /// configuration. Unlike the wrappers created with [`ReceiverWrapper::shared`], the wrapper
+    /// can be rebuilt with a fresh receiver instance (see [`ReceiverWrapper::restart`]), and a
+    /// failed receiver is restarted in place according to [`ReceiverConfig::restart`].
+    pub fn shared_restartable<F, R>(factory: F, config: &ReceiverConfig) -> Self
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: shared::Receiver<PData> + 'static,
+        PData: 'static,
+    {
+        let factory: SharedFactory<PData> = Arc::new(move || Box::new(factory()));
+        Self::shared_from_factory(factory, config.clone())
+    }
+
+    fn shared_from_factory(factory: SharedFactory<PData>, config: ReceiverConfig) -> Self
+    where
+        PData: 'static,
+    {
+        let receiver = factory();
+        let restart_config = config.clone();
+        let restart_factory = factory.clone();
+        let restart: RestartFn<PData> = Rc::new(move || {
+            Self::shared_from_factory(restart_factory.clone(), restart_config.clone())
+        });
+        Self::new_shared(receiver, &config, Some(restart), Some(factory))
+    }
+
+    fn new_shared(
+        receiver: Box<dyn shared::Receiver<PData>>,
+        config: &ReceiverConfig,
+        restart: Option<RestartFn<PData>>,
+        factory: Option<SharedFactory<PData>>,
+    ) -> Self {
+        let (control_sender, control_receiver) = control_channel(&config.control_channel);
         let (pdata_sender, pdata_receiver) =
             tokio::sync::mpsc::channel(config.output_pdata_channel.capacity);
 
-        ReceiverWrapper::Shared {
-            effect_handler: shared::EffectHandler::new(config.name.clone(), pdata_sender),
-            receiver: Box::new(receiver),
+        let mut effect_handler = shared::EffectHandler::new(config.name.clone(), pdata_sender);
+        effect_handler.set_max_concurrent_connections(config.max_concurrent_connections);
+        effect_handler.set_read_buffer_size(config.read_buffer_size);
+        if let Some(sink) = &config.metrics_sink {
+            effect_handler.set_metrics_sink(sink.clone());
+        }
+        effect_handler.set_overflow_policy(config.output_pdata_channel.overflow_policy);
+        effect_handler.set_max_inflight_bytes(config.max_inflight_bytes);
+        effect_handler.set_buffer_pool_name(config.output_pdata_channel.pool.clone());
+        effect_handler.set_control_sender(control_sender.clone());
+
+        let wrapper = ReceiverWrapper::Shared {
+            effect_handler,
+            receiver,
             control_sender,
             control_receiver,
+            backpressure: config.backpressure,
+            health: HealthProbe::new(config.health_check),
+            timer: config.timer,
+            drain_policy: config.drain_policy,
             pdata_receiver: Some(pdata_receiver),
+            restart,
+            factory,
+            restart_policy: config.restart,
+            cancellation: config.cancellation.clone(),
+            config_schema: config.config_schema.clone(),
+            restart_attempt: 0,
+            start_delay: Duration::ZERO,
+            out_port_receivers: HashMap::new(),
+        };
+        wrapper.with_configured_outputs(config)
+    }
+
+    /// Returns the name of the receiver.
+    #[must_use]
+    pub fn name(&self) -> Cow<'static, str> {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.receiver_name(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.receiver_name(),
+        }
+    }
+
+    /// Emits the messages logged by the receiver (see the `info_message`, `warn_message` and
+    /// `error_message` methods of its effect handler) to the given sink, tagged with the given
+    /// pipeline id. Without it, the messages are emitted as `tracing` events (see
+    /// [`crate::logging::TracingLogSink`]).
+    pub fn set_log_sink(&mut self, sink: Arc<dyn LogSink>, pipeline_id: Option<Cow<'static, str>>) {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => {
+                effect_handler.set_log_sink(sink, pipeline_id);
+            }
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.set_log_sink(sink, pipeline_id);
+            }
+        }
+    }
+
+    /// Returns the name of the buffer pool the output pdata channel of the receiver borrows from
+    /// (see the `pool` field of [`PdataChannelConfig`](crate::config::PdataChannelConfig)), if
+    /// any.
+    #[must_use]
+    pub fn buffer_pool_name(&self) -> Option<&str> {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.buffer_pool_name(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.buffer_pool_name(),
+        }
+    }
+
+    /// Makes the output pdata channel of the receiver borrow from the given pool (see
+    /// [`crate::buffer_pool`]), e.g. the pool named in its configuration. The wrappers rebuilt by
+    /// [`ReceiverWrapper::restart`] stay members of the pool. Joining the same pool again has no
+    /// effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::PipelineError`] if the receiver already borrows from another pool, or
+    /// if the pool can't reserve the slots of one more member.
+    pub fn join_buffer_pool(&mut self, pool: &Arc<GlobalBufferPool>) -> Result<(), Error<PData>>
+    where
+        PData: 'static,
+    {
+        let current = match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.buffer_pool(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.buffer_pool(),
+        };
+        if let Some(member) = current {
+            if member.belongs_to(pool) {
+                return Ok(());
+            }
+            return Err(Error::PipelineError {
+                error: format!(
+                    "The receiver `{}` already borrows from another buffer pool",
+                    self.name()
+                ),
+            });
+        }
+        let Some(member) = pool.join() else {
+            return Err(Error::PipelineError {
+                error: format!(
+                    "The buffer pool of {} messages can't reserve {} slots to the receiver `{}`",
+                    pool.capacity(),
+                    pool.reserved_per_member(),
+                    self.name()
+                ),
+            });
+        };
+        self.set_buffer_pool(member);
+        Ok(())
+    }
+
+    /// Makes the output pdata channel of the receiver, and of its rebuilt wrappers, borrow from a
+    /// buffer pool as the given member.
+    fn set_buffer_pool(&mut self, member: PoolMember)
+    where
+        PData: 'static,
+    {
+        let restart = match self {
+            ReceiverWrapper::Local {
+                effect_handler,
+                restart,
+                ..
+            } => {
+                effect_handler.set_buffer_pool(member.clone());
+                restart
+            }
+            ReceiverWrapper::Shared {
+                effect_handler,
+                restart,
+                ..
+            } => {
+                effect_handler.set_buffer_pool(member.clone());
+                restart
+            }
+        };
+        if let Some(rebuild) = restart.take() {
+            *restart = Some(Rc::new(move || {
+                let mut wrapper = rebuild();
+                wrapper.set_buffer_pool(member.clone());
+                wrapper
+            }));
+        }
+    }
+
+    /// Returns the number of pdata messages buffered in the output pdata channel of the receiver,
+    /// and the capacity of the channel.
+    #[must_use]
+    pub fn pdata_channel_len(&self) -> (usize, usize) {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.output_channel_len(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.output_channel_len(),
+        }
+    }
+
+    /// Returns the number of control messages buffered in the control channel of the receiver,
+    /// and the capacity of the channel.
+    #[must_use]
+    pub fn control_channel_len(&self) -> (usize, usize) {
+        match self {
+            ReceiverWrapper::Local { control_sender, .. }
+            | ReceiverWrapper::Shared { control_sender, .. } => {
+                (control_sender.len(), control_sender.capacity())
+            }
+        }
+    }
+
+    /// Sets the function returning the size in bytes of the pdata messages sent by the receiver,
+    /// counted in the `bytes_sent` metric (see [`ReceiverWrapper::metrics`]). Without it, the
+    /// size of the messages isn't counted.
+    pub fn set_pdata_size(&mut self, pdata_size: fn(&PData) -> usize) {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => {
+                effect_handler.set_pdata_size(pdata_size);
+            }
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.set_pdata_size(pdata_size);
+            }
+        }
+    }
+
+    /// Sets the function returning the span carried by a pdata message, e.g. the span of the
+    /// request the message was decoded from. With the `tracing-spans` feature, the `send_message`
+    /// span of the message follows from it.
+    pub fn set_pdata_trace_context(
+        &mut self,
+        trace_context: fn(&PData) -> Option<tracing::span::Id>,
+    ) {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => {
+                effect_handler.set_pdata_trace_context(trace_context);
+            }
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.set_pdata_trace_context(trace_context);
+            }
+        }
+    }
+
+    /// Returns the send metrics of the receiver (see [`ReceiverMetrics`]).
+    #[must_use]
+    pub fn metrics(&self) -> ReceiverMetrics {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.metrics(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.metrics(),
+        }
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the receiver (see
+    /// [`crate::config_ack`]).
+    #[must_use]
+    pub fn config_acks(&self) -> ConfigAckWatcher {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.config_acks(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.config_acks(),
+        }
+    }
+
+    /// Returns a watcher used to send flushes to the receiver and to await their acknowledgement
+    /// (see [`crate::flush_ack`]).
+    #[must_use]
+    pub fn flush_acks(&self) -> FlushAckWatcher {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.flush_acks(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.flush_acks(),
+        }
+    }
+
+    /// Returns the current health of the receiver (see [`crate::health`]).
+    #[must_use]
+    pub fn health(&self) -> NodeHealth {
+        self.health_probe().health()
+    }
+
+    /// Returns a probe tracking the health of the receiver once started.
+    #[must_use]
+    pub fn health_probe(&self) -> HealthProbe {
+        match self {
+            ReceiverWrapper::Local { health, .. } | ReceiverWrapper::Shared { health, .. } => {
+                health.clone()
+            }
+        }
+    }
+
+    /// Returns a receiver of the health statuses reported by the receiver through its effect
+    /// handler (see [`crate::health`]). The receiver is `Healthy` until it reports otherwise.
+    #[must_use]
+    pub fn subscribe_health(&self) -> watch::Receiver<HealthStatus> {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.subscribe_health(),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.subscribe_health(),
+        }
+    }
+
+    /// Returns a receiver of the lifecycle states of the receiver (see [`crate::lifecycle`]),
+    /// `Starting` until the receiver signals its readiness. As `start` consumes the wrapper, it
+    /// must be obtained before starting the receiver.
+    #[must_use]
+    pub fn state_watcher(&self) -> watch::Receiver<LifecycleState> {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.lifecycle().subscribe(),
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.lifecycle().subscribe()
+            }
+        }
+    }
+
+    /// Returns a receiver of the addresses of the listeners and sockets created by the receiver
+    /// through its effect handler (e.g. `tcp_listener`). As `start` consumes the wrapper, it must
+    /// be obtained before starting the receiver, the addresses are then observed as the receiver
+    /// binds them, e.g. to report them or to wait for the receiver to be ready.
+    #[must_use]
+    pub fn subscribe_bound_addresses(&self) -> watch::Receiver<Vec<SocketAddr>> {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => {
+                effect_handler.subscribe_bound_addresses()
+            }
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.subscribe_bound_addresses()
+            }
+        }
+    }
+
+    /// Returns a receiver of the summary of the run of the receiver (see [`ShutdownReport`]),
+    /// `None` until the receiver shut down cleanly. As `start` consumes the wrapper, it must be
+    /// obtained before starting the receiver. No report is published if the receiver failed.
+    #[must_use]
+    pub fn subscribe_shutdown_report(&self) -> watch::Receiver<Option<ShutdownReport>> {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => {
+                effect_handler.subscribe_shutdown_report()
+            }
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.subscribe_shutdown_report()
+            }
+        }
+    }
+
+    /// Installs the faults injected in the `send_message` method of the receiver by the tests
+    /// (see [`crate::testing::fault`]).
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn inject_faults(&mut self, faults: FaultInjector) {
+        match self {
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.inject_faults(faults),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.inject_faults(faults),
         }
     }
 
     /// Returns the control message sender for the receiver.
+    ///
+    /// The control channel of a receiver delivers the high-priority control messages (i.e.
+    /// `Shutdown`, `Flush`, `Pause` and `Resume`) ahead of the pending ones (see [`ControlMsg::is_high_priority`]).
     #[must_use]
     pub fn control_sender(&self) -> Sender<ControlMsg> {
+        Sender::Priority(self.priority_control_sender())
+    }
+
+    /// Returns the control message sender of the receiver, which is `Send` for both kinds of
+    /// receivers.
+    pub(crate) fn priority_control_sender(&self) -> PrioritySender<ControlMsg> {
+        match self {
+            ReceiverWrapper::Local { control_sender, .. }
+            | ReceiverWrapper::Shared { control_sender, .. } => control_sender.clone(),
+        }
+    }
+
+    /// Registers the control channel of this receiver with the given Ack/Nack router so that the
+    /// ids returned by the effect handler's `next_message_id` can be routed back to this receiver.
+    ///
+    /// Returns the route assigned to this receiver, or `None` if the router has no route left.
+    pub fn register_ack_route(&mut self, router: &mut AckRouter) -> Option<u16> {
+        let route = router.register(self.control_sender())?;
         match self {
-            ReceiverWrapper::Local { control_sender, .. } => Sender::Local(control_sender.clone()),
-            ReceiverWrapper::Shared { control_sender, .. } => {
-                Sender::Shared(control_sender.clone())
+            ReceiverWrapper::Local { effect_handler, .. } => effect_handler.set_ack_route(route),
+            ReceiverWrapper::Shared { effect_handler, .. } => effect_handler.set_ack_route(route),
+        }
+        Some(route)
+    }
+
+    /// Returns a fresh instance of this wrapper, with a new receiver built by the factory the
+    /// wrapper was created with, new control and pdata channels, and the same configuration
+    /// (name, capacities, policies). The returned wrapper is ready to be started, e.g. by a
+    /// supervisor once the receiver of this wrapper failed. As `start` consumes the wrapper, the
+    /// fresh instance must be obtained before starting this one.
+    ///
+    /// The new pdata channel must be connected to the downstream node again, and the Ack/Nack
+    /// route registered again if any (see [`ReceiverWrapper::register_ack_route`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ReceiverError`] if the wrapper wasn't created with `local_restartable`
+    /// or `shared_restartable`, the receiver instance can't be re-constructed in that case.
+    pub fn restart(&self) -> Result<Self, Error<PData>> {
+        match self {
+            ReceiverWrapper::Local { restart, .. } | ReceiverWrapper::Shared { restart, .. } => {
+                match restart {
+                    Some(restart) => Ok(restart()),
+                    None => Err(Error::ReceiverError {
+                        receiver: self.name(),
+                        error: "The receiver wasn't created from a factory and can't be restarted"
+                            .to_owned(),
+                    }),
+                }
             }
         }
     }
 
+    /// Returns a fresh instance of this wrapper (see [`ReceiverWrapper::restart`]) governed by the
+    /// given retry policy. The fresh instance is the next restart attempt of the receiver: its
+    /// `start` waits for the backoff of the attempt before starting the receiver, and a
+    /// [`ControlMsg::Restarting`] is the first control message delivered to the receiver.
+    ///
+    /// A supervisor typically obtains the next instance before starting the current one, and
+    /// starts it if the current one failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ReceiverError`] if the wrapper can't be restarted (see
+    /// [`ReceiverWrapper::restart`]) or if the retry policy allows no further attempt.
+    pub fn restart_with_retry(&self, policy: &RetryPolicy) -> Result<Self, Error<PData>> {
+        let attempt = match self {
+            ReceiverWrapper::Local {
+                restart_attempt, ..
+            }
+            | ReceiverWrapper::Shared {
+                restart_attempt, ..
+            } => restart_attempt.saturating_add(1),
+        };
+        if attempt > policy.max_attempts {
+            return Err(Error::ReceiverError {
+                receiver: self.name(),
+                error: format!(
+                    "The receiver was restarted {} times, giving up",
+                    policy.max_attempts
+                ),
+            });
+        }
+
+        let mut restarted = self.restart()?;
+        match &mut restarted {
+            ReceiverWrapper::Local {
+                restart_attempt,
+                start_delay,
+                ..
+            }
+            | ReceiverWrapper::Shared {
+                restart_attempt,
+                start_delay,
+                ..
+            } => {
+                *restart_attempt = attempt;
+                *start_delay = policy.backoff(attempt);
+            }
+        }
+        restarted
+            .control_sender()
+            .try_send(ControlMsg::Restarting { attempt })
+            .map_err(|_| Error::ControlChannelClosed {
+                node: restarted.name(),
+            })?;
+        Ok(restarted)
+    }
+
+    /// Returns how long `start` waits before starting the receiver.
+    fn start_delay(&self) -> Duration {
+        match self {
+            ReceiverWrapper::Local { start_delay, .. }
+            | ReceiverWrapper::Shared { start_delay, .. } => *start_delay,
+        }
+    }
+
     /// Starts the receiver and begins receiver incoming data.
+    ///
+    /// Once a `Shutdown` control message has been delivered, the receiver is given the shutdown
+    /// deadline to complete, after which it is dropped.
+    /// The receiver is also stopped when the cancellation token of its configuration is cancelled
+    /// (see [`ReceiverConfig::cancellation`]): it is delivered a best-effort `Shutdown` with the
+    /// grace period as deadline, and `start` returns `Ok(())` even if the receiver had to be
+    /// dropped at the end of the grace period.
+    /// The subtasks spawned by the receiver via `spawn_local_named` or `spawn_named` are aborted
+    /// once the receiver completed.
+    /// If the receiver fails and the wrapper was created from a factory with a restart policy
+    /// (see [`ReceiverConfig::restart`]), a fresh receiver instance is started in its place after
+    /// the backoff of the attempt, unless the receiver was asked to shut down. Each restart is
+    /// notified to the fresh instance with a `Restarting` control message, logged and counted
+    /// (see [`crate::telemetry::RESTARTS`]).
+    /// The wrapper then waits, according to the drain policy of the receiver (see
+    /// [`DrainPolicy`]), for the downstream node to consume the pdata buffered in the output
+    /// channel, and publishes the summary of the run of the receiver if it completed successfully
+    /// (see [`ReceiverWrapper::subscribe_shutdown_report`]). Where the receiver is in this sequence
+    /// is published to [`ReceiverWrapper::state_watcher`].
+    ///
+    /// With the `tracing-spans` feature, the run of the receiver is covered by a `receiver` span
+    /// named after the receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ShutdownTimeout`] if the receiver didn't complete within its shutdown
+    /// deadline (unless it was stopped by its cancellation token), or the error returned by the
+    /// receiver itself (by its last instance once the restart policy gave up).
     pub async fn start(self) -> Result<(), Error<PData>> {
+        let span = spans::receiver_span(&self.name());
+        self.run().instrument(span).await
+    }
+
+    /// Runs the receiver, see [`ReceiverWrapper::start`].
+    async fn run(self) -> Result<(), Error<PData>> {
+        let start_delay = self.start_delay();
+        if !start_delay.is_zero() {
+            tokio::time::sleep(start_delay).await;
+        }
         match self {
             ReceiverWrapper::Local {
                 effect_handler,
                 receiver,
+                control_sender,
                 control_receiver,
+                backpressure,
+                health,
+                timer,
+                drain_policy,
+                factory,
+                restart_policy,
+                cancellation,
+                config_schema,
                 ..
             } => {
-                let ctrl_msg_chan = local::ControlChannel::new(Receiver::Local(control_receiver));
-                receiver.start(ctrl_msg_chan, effect_handler).await
+                let tasks = effect_handler.tasks();
+                let buffered_pdata = effect_handler.buffered_pdata_probe();
+                let shutdown_reporter = effect_handler.shutdown_reporter();
+                let name = effect_handler.receiver_name();
+                let lifecycle = effect_handler.lifecycle();
+                let result = with_health_checks(
+                    effect_handler.receiver_name(),
+                    health.clone(),
+                    control_sender.clone(),
+                    with_backpressure(
+                        backpressure,
+                        effect_handler.buffered_pdata_probe(),
+                        control_sender,
+                        with_buffer_pool(
+                            effect_handler.buffer_pool(),
+                            effect_handler.buffered_pdata_probe(),
+                            // Boxed, the nested wrappers would otherwise grow the future
+                            // of the receiver past the stack size in debug builds.
+                            Box::pin(run_local(
+                                receiver,
+                                effect_handler,
+                                control_receiver,
+                                health,
+                                timer,
+                                cancellation,
+                                config_schema,
+                                factory.zip(restart_policy),
+                            )),
+                        ),
+                    ),
+                )
+                .await;
+                // The subtasks of the receiver never outlive it.
+                tasks.abort_all();
+                drain_output(&name, drain_policy, buffered_pdata).await;
+                if result.is_ok() {
+                    shutdown_reporter.publish();
+                }
+                lifecycle.stopped(&result);
+                result
             }
             ReceiverWrapper::Shared {
                 effect_handler,
                 receiver,
+                control_sender,
                 control_receiver,
+                backpressure,
+                health,
+                timer,
+                drain_policy,
+                factory,
+                restart_policy,
+                cancellation,
+                config_schema,
                 ..
             } => {
-                let ctrl_msg_chan = shared::ControlChannel::new(control_receiver);
-                receiver.start(ctrl_msg_chan, effect_handler).await
+                start_shared(
+                    receiver,
+                    effect_handler,
+                    control_sender,
+                    control_receiver,
+                    backpressure,
+                    health,
+                    timer,
+                    drain_policy,
+                    cancellation,
+                    config_schema,
+                    factory.zip(restart_policy),
+                )
+                .await
             }
         }
     }
 
-    /// Returns the PData receiver.
-    pub fn take_pdata_receiver(&mut self) -> Receiver<PData> {
-        match self {
-            ReceiverWrapper::Local { pdata_receiver, .. } => {
-                pdata_receiver.take().expect("pdata_receiver is None")
-            }
-            ReceiverWrapper::Shared { pdata_receiver, .. } => {
-                Receiver::Shared(pdata_receiver.take().expect("pdata_receiver is None"))
-            }
-        }
+    /// Spawns the receiver on the current runtime, local receivers are spawned on the current
+    /// [`tokio::task::LocalSet`] while shared receivers are spawned on the Tokio thread pool.
+    ///
+    /// Must be called from within a `LocalSet`.
+    pub(crate) fn spawn(self) -> JoinHandle<Result<(), Error<PData>>>
+    where
+        PData: Send + 'static,
+    {
+        self.spawn_on(&tokio::runtime::Handle::current())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::ReceiverWrapper;
-    use crate::local::receiver as local;
-    use crate::receiver::Error;
-    use crate::shared::receiver as shared;
-    use crate::testing::receiver::{NotSendValidateContext, TestContext, TestRuntime};
-    use crate::testing::{CtrlMsgCounters, TestMsg};
-    use async_trait::async_trait;
-    use serde_json::Value;
-    use std::future::Future;
-    use std::net::SocketAddr;
-    use std::pin::Pin;
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
-    use tokio::net::TcpStream;
-    use tokio::sync::oneshot;
-    use tokio::time::{Duration, sleep, timeout};
-
-    /// A test receiver that counts message events.
-    /// Works with any type of receiver !Send or Send.
-    pub struct TestReceiver {
-        /// Counter for different message types
-        ctrl_msg_counters: CtrlMsgCounters,
-        port_notifier: oneshot::Sender<SocketAddr>,
-    }
-
-    impl TestReceiver {
-        /// Creates a new test node
-        pub fn new(
-            ctrl_msg_counters: CtrlMsgCounters,
-            port_notifier: oneshot::Sender<SocketAddr>,
-        ) -> Self {
-            TestReceiver {
-                ctrl_msg_counters,
-                port_notifier,
-            }
-        }
-    }
-
-    #[async_trait(?Send)]
-    impl local::Receiver<TestMsg> for TestReceiver {
-        async fn start(
-            self: Box<Self>,
-            mut ctrl_msg_recv: local::ControlChannel,
-            effect_handler: local::EffectHandler<TestMsg>,
-        ) -> Result<(), Error<TestMsg>> {
-            // Bind to an ephemeral port.
-            let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
-            let listener = effect_handler.tcp_listener(addr)?;
-            let local_addr = listener.local_addr().unwrap();
-
-            // Notify the test of the actual bound address.
-            let _ = self.port_notifier.send(local_addr);
-
-            loop {
-                tokio::select! {
-                    // Process incoming control messages.
-                    ctrl_msg = ctrl_msg_recv.recv() => {
-                        let ctrl_msg = ctrl_msg?;
-                        self.ctrl_msg_counters.update_with(&ctrl_msg);
-                        if ctrl_msg.is_shutdown() {
-                            break;
-                        }
-                    }
+    /// Starts a shared receiver on the given runtime and returns the handle of its task, e.g. to
+    /// isolate a receiver decoding CPU-heavy payloads (Arrow, Protobuf) on a dedicated
+    /// multi-thread runtime, so that it doesn't starve the other nodes of the pipeline.
+    ///
+    /// The trade-offs of a dedicated runtime:
+    /// - every pdata message crosses runtimes through the output channel, waking the downstream
+    ///   node from another thread pool when it is idle;
+    /// - the timers of the receiver (e.g. `TimerTick` and the shutdown deadline) and its sockets
+    ///   are driven by the given runtime, which must have the time and I/O drivers enabled;
+    /// - the runtime must outlive the receiver, dropping it cancels the receiver task.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ReceiverError`] for a local receiver, which is `!Send` and therefore
+    /// bound to the thread of its `LocalSet`.
+    pub fn start_on(
+        self,
+        handle: tokio::runtime::Handle,
+    ) -> Result<JoinHandle<Result<(), Error<PData>>>, Error<PData>>
+    where
+        PData: Send + 'static,
+    {
+        if let ReceiverWrapper::Local { .. } = self {
+            return Err(Error::ReceiverError {
+                receiver: self.name(),
+                error: "A local receiver can't be started on another runtime".to_owned(),
+            });
+        }
+        Ok(self.spawn_on(&handle))
+    }
 
-                    // Process incoming TCP connections.
-                    accept_result = listener.accept() => {
-                        match accept_result {
-                            Ok((mut socket, peer_addr)) => {
-                                // Clone the effect handler so the spawned task can send messages.
-                                let effect_handler = effect_handler.clone();
-                                // Spawn a task to handle the connection.
-                                // ToDo should be abstract that and expose a method in the effect handler?
-                                _ = tokio::task::spawn_local(async move {
-                                    let mut buf = [0u8; 1024];
-                                    loop {
-                                        match socket.read(&mut buf).await {
-                                            Ok(0) => {
-                                                break;
-                                            },
-                                            Ok(n) => {
-                                                let received = String::from_utf8_lossy(&buf[..n]).to_string();
-                                                // Create a TestMsg from the received data and send it.
-                                                let msg = TestMsg(received);
-                                                if let Err(e) = effect_handler.send_message(msg).await {
-                                                    panic!("Error sending message via effect handler: {e}");
-                                                }
-                                                // Echo back an acknowledgment.
-                                                let _ = socket.write_all(b"ack").await;
-                                            },
-                                            Err(e) => {
-                                                panic!("Error reading from {peer_addr}: {e}");
-                                            }
-                                        }
-                                    }
-                                });
-                            },
-                            Err(e) => {
-                                panic!("Error accepting connection: {e}");
-                            }
-                        }
-                    }
-                    // A timeout branch in case no events occur.
-                    () = sleep(Duration::from_secs(1)) => {
-                        // You could do periodic tasks here.
+    /// Spawns a shared receiver on the given runtime, or a local receiver on the current
+    /// [`tokio::task::LocalSet`].
+    fn spawn_on(self, handle: &tokio::runtime::Handle) -> JoinHandle<Result<(), Error<PData>>>
+    where
+        PData: Send + 'static,
+    {
+        let start_delay = self.start_delay();
+        let span = spans::receiver_span(&self.name());
+        match self {
+            ReceiverWrapper::Shared {
+                effect_handler,
+                receiver,
+                control_sender,
+                control_receiver,
+                backpressure,
+                health,
+                timer,
+                drain_policy,
+                factory,
+                restart_policy,
+                cancellation,
+                config_schema,
+                ..
+            } => handle.spawn(
+                async move {
+                    if !start_delay.is_zero() {
+                        tokio::time::sleep(start_delay).await;
                     }
+                    start_shared(
+                        receiver,
+                        effect_handler,
+                        control_sender,
+                        control_receiver,
+                        backpressure,
+                        health,
+                        timer,
+                        drain_policy,
+                        cancellation,
+                        config_schema,
+                        factory.zip(restart_policy),
+                    )
+                    .await
                 }
+                .instrument(span),
+            ),
+            local @ ReceiverWrapper::Local { .. } => tokio::task::spawn_local(local.start()),
+        }
+    }
 
-                // For this test, exit the loop after 5 timer ticks.
-                if self.ctrl_msg_counters.get_timer_tick_count() >= 5 {
-                    break;
+    /// Adds the given named output ports to the receiver, each one with its own pdata channel of
+    /// the capacity of the output pdata channel. The receiver sends pdata messages to a port with
+    /// the effect handler's `send_message_to`, and the downstream node(s) of a port consume them
+    /// from the receiver returned by [`ReceiverWrapper::take_pdata_receiver_for`]. The default
+    /// output channel (see [`ReceiverWrapper::take_pdata_receiver`]) is left unchanged.
+    ///
+    /// The output ports declared in [`ReceiverConfig::out_ports`] are added when the wrapper is
+    /// created.
+    ///
+    /// Note: The output ports added by this method aren't preserved by
+    /// [`ReceiverWrapper::restart`], unlike the ones declared in the configuration. They must be
+    /// added again to the fresh instance.
+    #[must_use]
+    pub fn with_outputs(mut self, names: &[&str]) -> Self {
+        let (_, capacity) = self.pdata_channel_len();
+        match &mut self {
+            ReceiverWrapper::Local {
+                effect_handler,
+                out_port_receivers,
+                ..
+            } => {
+                // The ports of a shared receiver running in a local context must be `Send`.
+                let shared = effect_handler.has_shared_output();
+                let mut out_ports = HashMap::new();
+                for name in names {
+                    let (sender, receiver) = if shared {
+                        let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+                        (Sender::Shared(sender), Receiver::Shared(receiver))
+                    } else {
+                        let (sender, receiver) = mpsc::Channel::new(capacity);
+                        (Sender::Local(sender), Receiver::Local(receiver))
+                    };
+                    _ = out_ports.insert((*name).to_owned(), sender);
+                    _ = out_port_receivers.insert((*name).to_owned(), receiver);
                 }
+                effect_handler.add_out_ports(out_ports);
             }
+            ReceiverWrapper::Shared {
+                effect_handler,
+                out_port_receivers,
+                ..
+            } => {
+                let mut out_ports = HashMap::new();
+                for name in names {
+                    let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+                    _ = out_ports.insert((*name).to_owned(), sender);
+                    _ = out_port_receivers.insert((*name).to_owned(), Receiver::Shared(receiver));
+                }
+                effect_handler.add_out_ports(out_ports);
+            }
+        }
+        self
+    }
 
-            Ok(())
+    /// Adds the output ports declared in the given configuration.
+    fn with_configured_outputs(self, config: &ReceiverConfig) -> Self {
+        if config.out_ports.is_empty() {
+            return self;
         }
+        let names: Vec<&str> = config.out_ports.iter().map(String::as_str).collect();
+        self.with_outputs(&names)
     }
 
-    #[async_trait]
-    impl shared::Receiver<TestMsg> for TestReceiver {
-        async fn start(
-            self: Box<Self>,
-            mut ctrl_msg_recv: shared::ControlChannel,
-            effect_handler: shared::EffectHandler<TestMsg>,
-        ) -> Result<(), Error<TestMsg>> {
-            // Bind to an ephemeral port.
-            let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
-            let listener = effect_handler.tcp_listener(addr)?;
-            let local_addr = listener.local_addr().unwrap();
+    /// Returns the PData receiver of the given output port (see
+    /// [`ReceiverWrapper::with_outputs`]), or `None` if the receiver has no such port or if its
+    /// PData receiver was already taken.
+    pub fn take_pdata_receiver_for(&mut self, name: &str) -> Option<Receiver<PData>> {
+        match self {
+            ReceiverWrapper::Local {
+                out_port_receivers, ..
+            }
+            | ReceiverWrapper::Shared {
+                out_port_receivers, ..
+            } => out_port_receivers.remove(name),
+        }
+    }
 
-            // Notify the test of the actual bound address.
-            let _ = self.port_notifier.send(local_addr);
+    /// Returns `true` if the PData receiver was not taken yet (see
+    /// [`ReceiverWrapper::take_pdata_receiver`]).
+    #[must_use]
+    pub fn has_pdata_receiver(&self) -> bool {
+        match self {
+            ReceiverWrapper::Local { pdata_receiver, .. } => pdata_receiver.is_some(),
+            ReceiverWrapper::Shared { pdata_receiver, .. } => pdata_receiver.is_some(),
+        }
+    }
 
-            loop {
-                tokio::select! {
-                    // Process incoming control messages.
-                    ctrl_msg = ctrl_msg_recv.recv() => {
-                        let ctrl_msg = ctrl_msg?;
-                        self.ctrl_msg_counters.update_with(&ctrl_msg);
-                        if ctrl_msg.is_shutdown() {
-                            break;
-                        }
-                    }
+    /// Returns the PData receiver, or [`Error::PdataReceiverAlreadyTaken`] if it was already
+    /// taken.
+    ///
+    /// Note: The in-flight byte budget of the receiver (see the `max_inflight_bytes` field of the
+    /// [`ReceiverConfig`]) is only refunded by the receiver returned by
+    /// [`ReceiverWrapper::take_budgeted_pdata_receiver`].
+    pub fn take_pdata_receiver(&mut self) -> Result<Receiver<PData>, Error<PData>> {
+        let receiver = match self {
+            ReceiverWrapper::Local { pdata_receiver, .. } => pdata_receiver.take(),
+            ReceiverWrapper::Shared { pdata_receiver, .. } => {
+                pdata_receiver.take().map(Receiver::Shared)
+            }
+        };
+        receiver.ok_or_else(|| Error::PdataReceiverAlreadyTaken { node: self.name() })
+    }
 
-                    // Process incoming TCP connections.
-                    accept_result = listener.accept() => {
-                        match accept_result {
-                            Ok((mut socket, peer_addr)) => {
-                                // Clone the effect handler so the spawned task can send messages.
-                                let effect_handler = effect_handler.clone();
-                                // Spawn a task to handle the connection.
-                                // ToDo should be abstract that and expose a method in the effect handler?
-                                _ = tokio::task::spawn_local(async move {
-                                    let mut buf = [0u8; 1024];
-                                    loop {
-                                        match socket.read(&mut buf).await {
-                                            Ok(0) => {
-                                                break;
-                                            },
-                                            Ok(n) => {
-                                                let received = String::from_utf8_lossy(&buf[..n]).to_string();
-                                                // Create a TestMsg from the received data and send it.
-                                                let msg = TestMsg(received);
-                                                if let Err(e) = effect_handler.send_message(msg).await {
-                                                    panic!("Error sending message via effect handler: {e}");
-                                                }
-                                                // Echo back an acknowledgment.
-                                                let _ = socket.write_all(b"ack").await;
-                                            },
-                                            Err(e) => {
-                                                panic!("Error reading from {peer_addr}: {e}");
-                                            }
-                                        }
-                                    }
-                                });
-                            },
-                            Err(e) => {
-                                panic!("Error accepting connection: {e}");
-                            }
-                        }
-                    }
-                    // A timeout branch in case no events occur.
-                    () = sleep(Duration::from_secs(1)) => {
-                        // You could do periodic tasks here.
-                    }
-                }
+    /// Returns the PData receiver, refunding the in-flight byte budget of the receiver as the
+    /// messages are received. The pdata size function of the receiver (see
+    /// [`ReceiverWrapper::set_pdata_size`]) must be set beforehand.
+    pub fn take_budgeted_pdata_receiver(
+        &mut self,
+    ) -> Result<BudgetedReceiver<PData>, Error<PData>> {
+        let receiver = self.take_pdata_receiver()?;
+        Ok(match self {
+            ReceiverWrapper::Local { effect_handler, .. } => {
+                effect_handler.budgeted_receiver(receiver)
+            }
+            ReceiverWrapper::Shared { effect_handler, .. } => {
+                effect_handler.budgeted_receiver(receiver)
+            }
+        })
+    }
 
-                // For this test, exit the loop after 5 timer ticks.
-                if self.ctrl_msg_counters.get_timer_tick_count() >= 5 {
-                    break;
+    /// Returns the pdata messages currently buffered in the output channel of the receiver,
+    /// without waiting for new ones, e.g. to check the output of a receiver in a test or to
+    /// collect the pending pdata during a shutdown. Nothing is returned once the PData receiver
+    /// was taken (see [`ReceiverWrapper::take_pdata_receiver`]).
+    ///
+    /// Note: Like [`ReceiverWrapper::take_pdata_receiver`], the in-flight byte budget of the
+    /// receiver isn't refunded.
+    pub fn drain_pdata(&mut self) -> Vec<PData> {
+        let mut drained = Vec::new();
+        match self {
+            ReceiverWrapper::Local {
+                pdata_receiver: Some(pdata_receiver),
+                ..
+            } => {
+                while let Ok(pdata) = pdata_receiver.try_recv() {
+                    drained.push(pdata);
+                }
+            }
+            ReceiverWrapper::Shared {
+                pdata_receiver: Some(pdata_receiver),
+                ..
+            } => {
+                while let Ok(pdata) = pdata_receiver.try_recv() {
+                    drained.push(pdata);
                 }
             }
+            _ => {}
+        }
+        drained
+    }
+}
 
-            Ok(())
+/// Creates the control channel of a receiver, bounded or not depending on its configuration.
+fn control_channel(
+    config: &ControlChannelConfig,
+) -> (PrioritySender<ControlMsg>, PriorityReceiver<ControlMsg>) {
+    match config.kind {
+        ControlChannelKind::Bounded => {
+            priority_channel(config.capacity, ControlMsg::is_high_priority)
         }
+        ControlChannelKind::Unbounded => unbounded_priority_channel(ControlMsg::is_high_priority),
     }
+}
 
-    /// Test closure that simulates a typical receiver scenario.
-    fn scenario(
-        port_rx: oneshot::Receiver<SocketAddr>,
-    ) -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
-        move |ctx| {
-            Box::pin(async move {
-                // Wait for the receiver to send the listening address.
-                let addr: SocketAddr = port_rx.await.expect("Failed to receive listening address");
+/// Runs a shared receiver as a local one (see [`ReceiverWrapper::local_from_shared`]).
+struct SharedAsLocal<PData> {
+    receiver: Box<dyn shared::Receiver<PData>>,
+}
 
-                // Connect to the receiver's socket.
-                let mut stream = TcpStream::connect(addr)
-                    .await
-                    .expect("Failed to connect to receiver");
+#[async_trait(?Send)]
+impl<PData> local::Receiver<PData> for SharedAsLocal<PData> {
+    async fn start(
+        self: Box<Self>,
+        ctrl_chan: local::ControlChannel,
+        effect_handler: local::EffectHandler<PData>,
+    ) -> Result<(), Error<PData>> {
+        let name = effect_handler.receiver_name();
+        let Some(effect_handler) = effect_handler.into_shared() else {
+            return Err(Error::ReceiverError {
+                receiver: name,
+                error: "The output channels of a shared receiver must be Send".to_owned(),
+            });
+        };
 
-                // Send some test data.
-                stream
-                    .write_all(b"Hello from test client")
-                    .await
-                    .expect("Failed to send data");
+        // The shared channel observes the `Pause`, `Resume` and `HealthCheck` messages in place of
+        // the local one.
+        let (mut local_rx, ingest_paused, health) = ctrl_chan.into_parts();
+        let (shared_tx, shared_rx) = tokio::sync::mpsc::channel(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+        let mut shared_chan =
+            shared::ControlChannel::new(shared_rx).track_ingest_state(ingest_paused);
+        if let Some(health) = health {
+            shared_chan = shared_chan.track_health(health);
+        }
 
-                // Optionally, read an echo (acknowledgment) from the receiver.
-                let mut buf = [0u8; 1024];
-                let len = stream
-                    .read(&mut buf)
-                    .await
-                    .expect("Failed to read response");
-                assert_eq!(&buf[..len], b"ack", "Expected acknowledgment from receiver");
-
-                // Send a few TimerTick events from the test.
-                for _ in 0..3 {
-                    ctx.send_timer_tick()
-                        .await
-                        .expect("Failed to send TimerTick");
-                    ctx.sleep(Duration::from_millis(100)).await;
+        let forward = async move {
+            while let Ok(msg) = local_rx.recv().await {
+                if shared_tx.send(msg).await.is_err() {
+                    break;
                 }
+            }
+            // Closes the shared channel once the local one is closed, the receiver completes on
+            // its own.
+            drop(shared_tx);
+            std::future::pending::<()>().await;
+        };
+        tokio::select! {
+            result = self.receiver.start(shared_chan, effect_handler) => result,
+            () = forward => unreachable!("the forwarding of the control messages never completes"),
+        }
+    }
+}
 
-                ctx.send_config(Value::Null)
-                    .await
-                    .expect("Failed to send config");
+/// Starts a shared receiver, the returned future is `Send` as long as `PData` is `Send`.
+async fn start_shared<PData>(
+    receiver: Box<dyn shared::Receiver<PData>>,
+    effect_handler: shared::EffectHandler<PData>,
+    control_sender: PrioritySender<ControlMsg>,
+    control_receiver: PriorityReceiver<ControlMsg>,
+    backpressure: Option<BackpressureConfig>,
+    health: HealthProbe,
+    timer: Option<TimerConfig>,
+    drain_policy: DrainPolicy,
+    cancellation: Option<CancellationConfig>,
+    config_schema: Option<ConfigSchema>,
+    restarts: Option<(SharedFactory<PData>, RetryPolicy)>,
+) -> Result<(), Error<PData>> {
+    let tasks = effect_handler.tasks();
+    let buffered_pdata = effect_handler.buffered_pdata_probe();
+    let shutdown_reporter = effect_handler.shutdown_reporter();
+    let name = effect_handler.receiver_name();
+    let lifecycle = effect_handler.lifecycle();
+    let result = with_health_checks(
+        effect_handler.receiver_name(),
+        health.clone(),
+        control_sender.clone(),
+        with_backpressure(
+            backpressure,
+            effect_handler.buffered_pdata_probe(),
+            control_sender,
+            with_buffer_pool(
+                effect_handler.buffer_pool(),
+                effect_handler.buffered_pdata_probe(),
+                // Boxed, the nested wrappers would otherwise grow the future
+                // of the receiver past the stack size in debug builds.
+                Box::pin(run_shared(
+                    receiver,
+                    effect_handler,
+                    control_receiver,
+                    health,
+                    timer,
+                    cancellation,
+                    config_schema,
+                    restarts,
+                )),
+            ),
+        ),
+    )
+    .await;
+    // The subtasks of the receiver never outlive it.
+    tasks.abort_all();
+    drain_output(&name, drain_policy, buffered_pdata).await;
+    if result.is_ok() {
+        shutdown_reporter.publish();
+    }
+    lifecycle.stopped(&result);
+    result
+}
 
-                // Finally, send a Shutdown event to terminate the receiver.
-                ctx.send_shutdown(Duration::from_millis(200), "Test")
-                    .await
-                    .expect("Failed to send Shutdown");
+/// Runs a local receiver until it completes. A failed receiver is replaced by a fresh instance
+/// built by the given factory, according to the given restart policy (see [`should_restart`]):
+/// the fresh instance receives a `Restarting` control message, and sends to the same pdata
+/// channel(s) as the failed one.
+async fn run_local<PData>(
+    mut receiver: Box<dyn local::Receiver<PData>>,
+    effect_handler: local::EffectHandler<PData>,
+    control_receiver: PriorityReceiver<ControlMsg>,
+    health: HealthProbe,
+    timer: Option<TimerConfig>,
+    cancellation: Option<CancellationConfig>,
+    config_schema: Option<ConfigSchema>,
+    restarts: Option<(LocalFactory<PData>, RetryPolicy)>,
+) -> Result<(), Error<PData>> {
+    let reject_handler = effect_handler.clone();
+    let control_receiver = ConfigDecoder::new(
+        control_receiver,
+        effect_handler.receiver_name(),
+        config_schema,
+        move |version, reason| reject_handler.reject_config(version, reason),
+    );
+    let mut control_receiver = StopObserver::new(
+        CancellableControl::new(control_receiver, cancellation),
+        effect_handler.lifecycle(),
+    );
+    let mut attempt = 0;
+    let result = loop {
+        let (node_control_tx, node_control_rx) =
+            mpsc::Channel::new(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+        if attempt > 0 {
+            // The channel is empty, the message always fits.
+            _ = node_control_tx.send(ControlMsg::Restarting { attempt });
+        }
+        let ctrl_msg_chan = local::ControlChannel::new(Receiver::Local(node_control_rx))
+            .track_ingest_state(effect_handler.ingest_state())
+            .track_health(health.clone());
+        let result = run_with_shutdown_deadline(
+            effect_handler.receiver_name(),
+            &mut control_receiver,
+            node_control_tx,
+            timer,
+            Some(effect_handler.timers()),
+            effect_handler.telemetry(),
+            receiver.start(ctrl_msg_chan, effect_handler.clone()),
+        )
+        .await;
+        let (Err(error), Some((factory, policy))) = (&result, &restarts) else {
+            break result;
+        };
+        attempt += 1;
+        if !should_restart(policy, attempt, control_receiver.stopped) {
+            break result;
+        }
+        effect_handler.report_restart(attempt, error);
+        effect_handler.lifecycle().restarting();
+        // Nothing of the failed instance outlives it.
+        effect_handler.tasks().abort_all();
+        effect_handler.timers().cancel_all();
+        tokio::time::sleep(policy.backoff(attempt)).await;
+        receiver = factory();
+    };
+    cancelled_result(result, control_receiver.control_rx.is_cancelled())
+}
 
-                // Close the TCP connection.
-                let _ = stream.shutdown().await;
-            })
+/// Runs a shared receiver until it completes, see [`run_local`].
+async fn run_shared<PData>(
+    mut receiver: Box<dyn shared::Receiver<PData>>,
+    effect_handler: shared::EffectHandler<PData>,
+    control_receiver: PriorityReceiver<ControlMsg>,
+    health: HealthProbe,
+    timer: Option<TimerConfig>,
+    cancellation: Option<CancellationConfig>,
+    config_schema: Option<ConfigSchema>,
+    restarts: Option<(SharedFactory<PData>, RetryPolicy)>,
+) -> Result<(), Error<PData>> {
+    let reject_handler = effect_handler.clone();
+    let control_receiver = ConfigDecoder::new(
+        control_receiver,
+        effect_handler.receiver_name(),
+        config_schema,
+        move |version, reason| reject_handler.reject_config(version, reason),
+    );
+    let mut control_receiver = StopObserver::new(
+        CancellableControl::new(control_receiver, cancellation),
+        effect_handler.lifecycle(),
+    );
+    let mut attempt = 0;
+    let result = loop {
+        let (node_control_tx, node_control_rx) =
+            tokio::sync::mpsc::channel(FORWARDED_CONTROL_CHANNEL_CAPACITY);
+        if attempt > 0 {
+            // The channel is empty, the message always fits.
+            _ = node_control_tx.try_send(ControlMsg::Restarting { attempt });
         }
+        let ctrl_msg_chan = shared::ControlChannel::new(node_control_rx)
+            .track_ingest_state(effect_handler.ingest_state())
+            .track_health(health.clone());
+        let result = run_with_shutdown_deadline(
+            effect_handler.receiver_name(),
+            &mut control_receiver,
+            node_control_tx,
+            timer,
+            Some(effect_handler.timers()),
+            effect_handler.telemetry(),
+            receiver.start(ctrl_msg_chan, effect_handler.clone()),
+        )
+        .await;
+        let (Err(error), Some((factory, policy))) = (&result, &restarts) else {
+            break result;
+        };
+        attempt += 1;
+        if !should_restart(policy, attempt, control_receiver.stopped) {
+            break result;
+        }
+        effect_handler.report_restart(attempt, error);
+        effect_handler.lifecycle().restarting();
+        // Nothing of the failed instance outlives it.
+        effect_handler.tasks().abort_all();
+        effect_handler.timers().cancel_all();
+        tokio::time::sleep(policy.backoff(attempt)).await;
+        receiver = factory();
+    };
+    cancelled_result(result, control_receiver.control_rx.is_cancelled())
+}
+
+/// Returns the result of a receiver, a receiver stopped by its cancellation token and dropped at
+/// the end of its grace period is considered to have completed.
+fn cancelled_result<PData>(
+    result: Result<(), Error<PData>>,
+    cancelled: bool,
+) -> Result<(), Error<PData>> {
+    match result {
+        Err(Error::ShutdownTimeout { .. }) if cancelled => Ok(()),
+        result => result,
     }
+}
 
-    /// Validation closure that checks the received message and counters (!Send context).
-    fn validation_procedure()
-    -> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
-        |mut ctx| {
-            Box::pin(async move {
-                let received = timeout(Duration::from_secs(3), ctx.recv())
-                    .await
-                    .expect("Timed out waiting for message")
-                    .expect("No message received");
+/// Returns true if a failed receiver is restarted for the given attempt: the restart policy
+/// allows the attempt, and the engine neither asked the receiver to shut down nor closed its
+/// control channel.
+fn should_restart(policy: &RetryPolicy, attempt: u32, stopped: bool) -> bool {
+    !stopped && attempt <= policy.max_attempts
+}
 
-                // Assert that the message received is what the test client sent.
-                assert!(matches!(received, TestMsg(msg) if msg == "Hello from test client"));
-                ctx.counters().assert(3, 0, 1, 1);
-            })
+/// The engine-facing control channel of a receiver, recording whether the engine asked the
+/// receiver to stop (i.e. delivered a `Shutdown` or closed the channel). The receiver is marked as
+/// draining once a `Shutdown` is delivered (see [`crate::lifecycle`]).
+struct StopObserver<R> {
+    control_rx: R,
+    lifecycle: Lifecycle,
+    stopped: bool,
+}
+
+impl<R> StopObserver<R> {
+    fn new(control_rx: R, lifecycle: Lifecycle) -> Self {
+        StopObserver {
+            control_rx,
+            lifecycle,
+            stopped: false,
         }
     }
+}
 
-    /// Test for the receiver in a `!Send` implementation.
-    #[test]
-    fn test_receiver_local() {
-        let test_runtime = TestRuntime::new();
-
-        // Create a oneshot channel to receive the listening address from the receiver.
-        let (port_tx, port_rx) = oneshot::channel();
-        let receiver = ReceiverWrapper::local(
-            TestReceiver::new(test_runtime.counters(), port_tx),
-            test_runtime.config(),
-        );
-
-        test_runtime
-            .set_receiver(receiver)
-            .run_test(scenario(port_rx))
-            .run_validation(validation_procedure());
+impl<R: ControlReceiver> ControlReceiver for StopObserver<R> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        let msg = self.control_rx.recv_ctrl().await;
+        self.stopped |= matches!(msg, None | Some(ControlMsg::Shutdown { .. }));
+        if let Some(ControlMsg::Shutdown { .. }) = msg {
+            self.lifecycle.draining();
+        }
+        msg
     }
+}
 
-    /// Test the receiver with a shared (Send) implementation.
-    #[test]
-    fn test_receiver_shared() {
-        let test_runtime = TestRuntime::new();
+/// The engine-facing control channel of a receiver, decoding the `Config` updates addressed to
+/// the receiver with its configuration schema (see [`ReceiverConfig::config_schema`]). The
+/// decoded updates are delivered with their decoded payload attached, the others are rejected on
+/// behalf of the receiver.
+struct ConfigDecoder<R, F> {
+    control_rx: R,
+    node: Cow<'static, str>,
+    schema: Option<ConfigSchema>,
+    reject: F,
+}
 
-        // Create a oneshot channel to receive the listening address from the receiver.
-        let (port_tx, port_rx) = oneshot::channel();
-        let receiver = ReceiverWrapper::shared(
-            TestReceiver::new(test_runtime.counters(), port_tx),
-            test_runtime.config(),
-        );
+impl<R, F> ConfigDecoder<R, F> {
+    fn new(
+        control_rx: R,
+        node: Cow<'static, str>,
+        schema: Option<ConfigSchema>,
+        reject: F,
+    ) -> Self {
+        ConfigDecoder {
+            control_rx,
+            node,
+            schema,
+            reject,
+        }
+    }
+}
 
-        test_runtime
-            .set_receiver(receiver)
-            .run_test(scenario(port_rx))
-            .run_validation(validation_procedure());
+impl<R: ControlReceiver, F: Fn(u64, String)> ControlReceiver for ConfigDecoder<R, F> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        loop {
+            let msg = self.control_rx.recv_ctrl().await?;
+            let Some(schema) = &self.schema else {
+                return Some(msg);
+            };
+            let update = match msg {
+                ControlMsg::Config { update } if update.is_addressed_to(&self.node) => update,
+                msg => return Some(msg),
+            };
+            match schema.decode(&update.payload) {
+                Ok(config) => {
+                    return Some(ControlMsg::Config {
+                        update: update.with_decoded(config),
+                    });
+                }
+                Err(error) => {
+                    (self.reject)(update.version, format!("Invalid configuration: {error}"))
+                }
+            }
+        }
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Output channels of receivers borrowing from a global buffer pool.
+
+use super::*;
+
+/// A receiver sending `count` messages, counting them in `sent` once sent, then waiting for
+/// the shutdown.
+#[derive(Clone)]
+struct FloodingReceiver {
+    count: usize,
+    sent: Arc<AtomicU64>,
+}
+
+impl_test_receiver!(FloodingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        for i in 0..self.count {
+            effect_handler
+                .send_message(TestMsg::new(format!("message {i}")))
+                .await?;
+            _ = self.sent.fetch_add(1, Ordering::SeqCst);
+        }
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        Ok(())
+    }
+});
+
+fn pooled_config(name: &'static str) -> ReceiverConfig {
+    let mut config = ReceiverConfig::new(name);
+    config.output_pdata_channel.capacity = 100;
+    config.output_pdata_channel.pool = Some("receivers".to_owned());
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+/// Waits until the given counter reaches the expected value.
+async fn wait_for_count(counter: &AtomicU64, expected: u64) {
+    timeout(Duration::from_secs(3), async {
+        while counter.load(Ordering::SeqCst) < expected {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the messages to be sent");
+}
+
+/// Runs a chatty receiver sending 12 messages and a quiet one sending 2 messages on a pool of
+/// 10 messages reserving 2 slots to each receiver, none of them being consumed at first. Checks
+/// that the chatty receiver blocks once it took all the slots but the reserved slots of the
+/// quiet one, although its output channel has room, and that the quiet receiver still sends
+/// its messages.
+fn assert_pool_reservations(
+    make: impl Fn(FloodingReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let pool = Arc::new(GlobalBufferPool::new(10).with_reservation(2));
+    let chatty_sent = Arc::new(AtomicU64::new(0));
+    let quiet_sent = Arc::new(AtomicU64::new(0));
+    let mut chatty = make(
+        FloodingReceiver {
+            count: 12,
+            sent: chatty_sent.clone(),
+        },
+        &pooled_config("chatty_receiver"),
+    );
+    let mut quiet = make(
+        FloodingReceiver {
+            count: 2,
+            sent: quiet_sent.clone(),
+        },
+        &pooled_config("quiet_receiver"),
+    );
+    assert_eq!(chatty.buffer_pool_name(), Some("receivers"));
+    chatty
+        .join_buffer_pool(&pool)
+        .expect("Failed to join the pool");
+    quiet
+        .join_buffer_pool(&pool)
+        .expect("Failed to join the pool");
+    let control_senders = [chatty.control_sender(), quiet.control_sender()];
+    let mut chatty_rx = chatty
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let _quiet_rx = quiet
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handles = [
+            tokio::task::spawn_local(chatty.start()),
+            tokio::task::spawn_local(quiet.start()),
+        ];
+
+        wait_for_count(&chatty_sent, 8).await;
+        wait_for_count(&quiet_sent, 2).await;
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(chatty_sent.load(Ordering::SeqCst), 8);
+        assert_eq!(pool.used(), 10);
+
+        // The slots freed by consuming the output of the chatty receiver are taken again.
+        for i in 0..4 {
+            assert_eq!(
+                chatty_rx.recv().await.expect("Message not received"),
+                TestMsg::new(format!("message {i}"))
+            );
+        }
+        wait_for_count(&chatty_sent, 12).await;
+
+        for control_sender in control_senders {
+            control_sender
+                .send(ControlMsg::Shutdown {
+                    deadline: Duration::from_millis(100),
+                    reason: "Test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+        }
+        for handle in handles {
+            handle
+                .await
+                .expect("Receiver task panicked")
+                .expect("Receiver failed");
+        }
+        // The slots of the completed receivers are returned to the pool.
+        assert_eq!(pool.used(), 0);
+    }));
+}
+
+#[test]
+fn test_pool_reservations_local() {
+    assert_pool_reservations(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_pool_reservations_shared() {
+    assert_pool_reservations(ReceiverWrapper::shared);
+}
+
+#[test]
+fn test_pool_without_room() {
+    let pool = Arc::new(GlobalBufferPool::new(1).with_reservation(1));
+    let flooding = FloodingReceiver {
+        count: 0,
+        sent: Arc::default(),
+    };
+    let mut first = ReceiverWrapper::local(flooding.clone(), &pooled_config("first"));
+    let mut second = ReceiverWrapper::local(flooding, &pooled_config("second"));
+    first
+        .join_buffer_pool(&pool)
+        .expect("Failed to join the pool");
+    assert!(matches!(
+        second.join_buffer_pool(&pool),
+        Err(Error::PipelineError { .. })
+    ));
+}
+
+#[test]
+fn test_pool_joined_twice() {
+    let pool = Arc::new(GlobalBufferPool::new(2).with_reservation(1));
+    let flooding = FloodingReceiver {
+        count: 0,
+        sent: Arc::default(),
+    };
+    let mut first = ReceiverWrapper::shared(flooding.clone(), &pooled_config("first"));
+    let mut second = ReceiverWrapper::local(flooding, &pooled_config("second"));
+    first
+        .join_buffer_pool(&pool)
+        .expect("Failed to join the pool");
+    first
+        .join_buffer_pool(&pool)
+        .expect("Failed to join the pool again");
+    // The second join didn't take the reserved slots left for the second receiver.
+    second
+        .join_buffer_pool(&pool)
+        .expect("Failed to join the pool");
+
+    // A receiver can't borrow from two pools.
+    let other = Arc::new(GlobalBufferPool::new(2));
+    assert!(matches!(
+        first.join_buffer_pool(&other),
+        Err(Error::PipelineError { .. })
+    ));
+    assert!(other.join().is_some());
+    assert!(other.join().is_some());
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Receivers stopped with a cancellation token.
+
+use super::*;
+
+fn cancellable_config(token: &CancellationToken) -> ReceiverConfig {
+    ReceiverConfig::builder()
+        .with_name("cancellable_receiver")
+        .with_drain_policy(DrainPolicy::Immediate)
+        .with_cancellation(token.clone(), Duration::from_millis(200))
+        .build()
+        .expect("Invalid configuration")
+}
+
+/// Cancels the token of a streaming receiver mid-stream, and checks that the receiver exits
+/// within the grace period and closes its pdata channel.
+fn assert_cancelled_mid_stream(
+    new_wrapper: impl FnOnce(&ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let token = CancellationToken::new();
+    let mut receiver = new_wrapper(&cancellable_config(&token));
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        for _ in 0..3 {
+            let msg = timeout(Duration::from_secs(1), pdata_rx.recv())
+                .await
+                .expect("Timed out waiting for a message");
+            assert_eq!(msg.expect("Message not received"), TestMsg::new("tick"));
+        }
+
+        token.cancel();
+        timeout(Duration::from_millis(200), handle)
+            .await
+            .expect("The receiver didn't exit within the grace period")
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+
+        // The messages emitted before the cancellation are still delivered.
+        while let Ok(msg) = pdata_rx.try_recv() {
+            assert_eq!(msg, TestMsg::new("tick"));
+        }
+        assert!(matches!(pdata_rx.recv().await, Err(RecvError::Closed)));
+    }));
+}
+
+#[test]
+fn test_cancellation_local() {
+    assert_cancelled_mid_stream(|config| ReceiverWrapper::local(StreamingReceiver, config));
+}
+
+#[test]
+fn test_cancellation_shared() {
+    assert_cancelled_mid_stream(|config| ReceiverWrapper::shared(StreamingReceiver, config));
+}
+
+#[test]
+fn test_cancellation_drops_stuck_receiver() {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let token = CancellationToken::new();
+    let receiver = ReceiverWrapper::local(StuckReceiver, &cancellable_config(&token));
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        token.cancel();
+
+        // The stuck receiver is dropped after its grace period, without reporting an error.
+        let result = timeout(Duration::from_secs(5), handle)
+            .await
+            .expect("The stuck receiver was not dropped")
+            .expect("Receiver task panicked");
+        assert!(result.is_ok(), "Unexpected result {result:?}");
+    }));
+}
@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The output and control channels of the receiver wrappers.
+
+use super::*;
+
+fn drain_config(drain_policy: DrainPolicy) -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("draining_receiver");
+    config.drain_policy = drain_policy;
+    config
+}
+
+/// Runs the receiver to completion while a slow downstream node consumes its output channel,
+/// and returns the number of messages consumed when the receiver returned.
+fn consumed_on_completion(mut receiver: ReceiverWrapper<TestMsg>) -> usize {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let consumed = Arc::new(AtomicU64::new(0));
+
+    let downstream_consumed = consumed.clone();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let downstream = tokio::task::spawn_local(async move {
+            while pdata_rx.recv().await.is_ok() {
+                _ = downstream_consumed.fetch_add(1, Ordering::Relaxed);
+                sleep(Duration::from_millis(20)).await;
+            }
+        });
+        timeout(Duration::from_secs(3), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver failed");
+        let consumed_on_completion = consumed.load(Ordering::Relaxed);
+        downstream.await.expect("Downstream task panicked");
+        assert_eq!(consumed.load(Ordering::Relaxed), 4);
+        usize::try_from(consumed_on_completion).expect("Invalid count")
+    }))
+}
+
+#[test]
+fn test_drain_flush_local() {
+    let receiver = ReceiverWrapper::local(
+        OverflowingReceiver {
+            dropped: Arc::default(),
+        },
+        &drain_config(DrainPolicy::Flush {
+            timeout: Duration::from_secs(1),
+        }),
+    );
+    assert_eq!(consumed_on_completion(receiver), 4);
+}
+
+#[test]
+fn test_drain_flush_shared() {
+    let receiver = ReceiverWrapper::shared(
+        OverflowingReceiver {
+            dropped: Arc::default(),
+        },
+        &drain_config(DrainPolicy::Flush {
+            timeout: Duration::from_secs(1),
+        }),
+    );
+    assert_eq!(consumed_on_completion(receiver), 4);
+}
+
+#[test]
+fn test_drain_immediate() {
+    let receiver = ReceiverWrapper::local(
+        OverflowingReceiver {
+            dropped: Arc::default(),
+        },
+        &drain_config(DrainPolicy::Immediate),
+    );
+    assert!(consumed_on_completion(receiver) < 4);
+}
+
+#[test]
+fn test_drain_timeout() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut receiver = ReceiverWrapper::shared(
+        OverflowingReceiver {
+            dropped: Arc::default(),
+        },
+        &drain_config(DrainPolicy::Flush {
+            timeout: Duration::from_millis(100),
+        }),
+    );
+    // Nothing consumes the output channel.
+    let _pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let started = Instant::now();
+        timeout(Duration::from_secs(3), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver failed");
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }));
+}
+
+/// Takes the PData receiver of the given receiver twice, the second call reporting the name of
+/// the node instead of panicking.
+fn assert_pdata_receiver_taken_once(mut receiver: ReceiverWrapper<TestMsg>) {
+    assert!(receiver.has_pdata_receiver());
+    assert!(receiver.take_pdata_receiver().is_ok());
+    assert!(!receiver.has_pdata_receiver());
+    match receiver.take_pdata_receiver() {
+        Err(Error::PdataReceiverAlreadyTaken { node }) => {
+            assert_eq!(node, "taken_receiver");
+        }
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+    assert!(matches!(
+        receiver.take_budgeted_pdata_receiver(),
+        Err(Error::PdataReceiverAlreadyTaken { .. })
+    ));
+}
+
+#[test]
+fn test_take_pdata_receiver_twice_local() {
+    assert_pdata_receiver_taken_once(ReceiverWrapper::local(
+        StreamingReceiver,
+        &ReceiverConfig::new("taken_receiver"),
+    ));
+}
+
+#[test]
+fn test_take_pdata_receiver_twice_shared() {
+    assert_pdata_receiver_taken_once(ReceiverWrapper::shared(
+        StreamingReceiver,
+        &ReceiverConfig::new("taken_receiver"),
+    ));
+}
+
+/// Enqueues more control messages than the capacity of a bounded control channel to the
+/// receiver created with the given function, without ever waiting.
+fn assert_unbounded_control_channel(create: impl Fn(&ReceiverConfig) -> ReceiverWrapper<TestMsg>) {
+    let config = |builder: ReceiverConfigBuilder| {
+        builder
+            .with_name("control_receiver")
+            .with_control_channel_capacity(4)
+            .build()
+            .expect("Invalid configuration")
+    };
+    let bounded = create(&config(ReceiverConfig::builder()));
+    let unbounded = create(&config(
+        ReceiverConfig::builder().with_unbounded_control_channel(),
+    ));
+
+    let (bounded_tx, unbounded_tx) = (bounded.control_sender(), unbounded.control_sender());
+    for _ in 0..4 {
+        assert!(bounded_tx.try_send(ControlMsg::TimerTick {}).is_ok());
+    }
+    assert!(matches!(
+        bounded_tx.try_send(ControlMsg::TimerTick {}),
+        Err(SendError::Full(_))
+    ));
+    for _ in 0..100 {
+        assert!(unbounded_tx.try_send(ControlMsg::TimerTick {}).is_ok());
+    }
+    assert_eq!(unbounded.control_channel_len(), (100, usize::MAX));
+}
+
+#[test]
+fn test_unbounded_control_channel_local() {
+    assert_unbounded_control_channel(|config| ReceiverWrapper::local(StreamingReceiver, config));
+}
+
+#[test]
+fn test_unbounded_control_channel_shared() {
+    assert_unbounded_control_channel(|config| ReceiverWrapper::shared(StreamingReceiver, config));
+}
+
+#[test]
+fn test_drain_pdata_local() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut receiver =
+        ReceiverWrapper::local(StreamingReceiver, &ReceiverConfig::new("drained_receiver"));
+    let ReceiverWrapper::Local { effect_handler, .. } = &receiver else {
+        unreachable!("local receiver");
+    };
+    let effect_handler = effect_handler.clone();
+
+    rt.block_on(local_tasks.run_until(async move {
+        assert!(receiver.drain_pdata().is_empty());
+        for i in 0..5 {
+            effect_handler
+                .send_message(TestMsg(format!("msg {i}")))
+                .await
+                .expect("Failed to send a message");
+        }
+        assert_eq!(receiver.drain_pdata().len(), 5);
+        assert!(receiver.drain_pdata().is_empty());
+    }));
+}
+
+#[test]
+fn test_drain_pdata_shared() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut receiver =
+        ReceiverWrapper::shared(StreamingReceiver, &ReceiverConfig::new("drained_receiver"));
+    let ReceiverWrapper::Shared { effect_handler, .. } = &receiver else {
+        unreachable!("shared receiver");
+    };
+    let effect_handler = effect_handler.clone();
+
+    rt.block_on(local_tasks.run_until(async move {
+        for i in 0..5 {
+            effect_handler
+                .send_message(TestMsg(format!("msg {i}")))
+                .await
+                .expect("Failed to send a message");
+        }
+        let drained = receiver.drain_pdata();
+        assert_eq!(
+            drained,
+            (0..5)
+                .map(|i| TestMsg(format!("msg {i}")))
+                .collect::<Vec<_>>()
+        );
+
+        // Nothing is drained once the PData receiver was taken.
+        effect_handler
+            .send_message(TestMsg::new("taken"))
+            .await
+            .expect("Failed to send a message");
+        let _pdata_rx = receiver
+            .take_pdata_receiver()
+            .expect("Failed to take the pdata receiver");
+        assert!(receiver.drain_pdata().is_empty());
+    }));
+}
@@ -0,0 +1,528 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Configuration updates delivered to the receivers.
+
+use super::*;
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct BatchConfig {
+    max_batch_size: usize,
+}
+
+/// A receiver applying the strongly-typed configuration updates it receives.
+struct ReconfigurableReceiver {
+    applied: Arc<Mutex<Vec<BatchConfig>>>,
+}
+
+impl ReconfigurableReceiver {
+    fn apply(&self, msg: TypedControlMsg<BatchConfig>) -> bool {
+        match msg {
+            TypedControlMsg::Reconfigure(payload) => {
+                self.applied.lock().unwrap().push(payload.config);
+                false
+            }
+            TypedControlMsg::Other(msg) => msg.is_shutdown(),
+        }
+    }
+}
+
+impl_test_receiver!(ReconfigurableReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        _effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        while !self.apply(ctrl_msg_recv.recv_typed().await?) {}
+        Ok(())
+    }
+});
+
+/// Sends a well-typed configuration update followed by a malformed one, and checks that the
+/// first one is decoded and the second one is reported as an error instead of panicking.
+fn assert_typed_reconfigure(
+    receiver: ReceiverWrapper<TestMsg>,
+    applied: &Arc<Mutex<Vec<BatchConfig>>>,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        for config in [
+            json!({ "max_batch_size": 512 }),
+            json!({ "max_batch_size": "large" }),
+        ] {
+            control_sender
+                .send(ControlMsg::Reconfigure {
+                    payload: ReconfigurePayload::new(config),
+                })
+                .await
+                .expect("Failed to send Reconfigure");
+        }
+
+        let result = timeout(Duration::from_secs(5), handle)
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver task panicked");
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+    }));
+
+    assert_eq!(
+        *applied.lock().unwrap(),
+        vec![BatchConfig {
+            max_batch_size: 512
+        }]
+    );
+}
+
+#[test]
+fn test_typed_reconfigure_local() {
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    let receiver = ReceiverWrapper::local(
+        ReconfigurableReceiver {
+            applied: applied.clone(),
+        },
+        &ReceiverConfig::new("reconfigurable_receiver"),
+    );
+    assert_typed_reconfigure(receiver, &applied);
+}
+
+#[test]
+fn test_typed_reconfigure_shared() {
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    let receiver = ReceiverWrapper::shared(
+        ReconfigurableReceiver {
+            applied: applied.clone(),
+        },
+        &ReceiverConfig::new("reconfigurable_receiver"),
+    );
+    assert_typed_reconfigure(receiver, &applied);
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct ExportConfig {
+    max_batch_size: usize,
+    timeout_ms: u64,
+    endpoint: Option<String>,
+}
+
+/// A receiver applying the configuration patches it receives to its current configuration,
+/// and emitting the resulting configuration.
+struct PatchedReceiver {
+    config: ExportConfig,
+}
+
+impl PatchedReceiver {
+    fn on_ctrl_msg(&mut self, msg: &ControlMsg) -> Result<Option<TestMsg>, Error<TestMsg>> {
+        let ControlMsg::Reconfigure { payload } = msg else {
+            return Ok(None);
+        };
+        self.config = payload.apply_to(&self.config)?;
+        Ok(Some(TestMsg::new(serde_json::to_string(&self.config)?)))
+    }
+}
+
+impl_test_receiver!(PatchedReceiver {
+    async fn start(
+        mut self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            let msg = ctrl_msg_recv.recv().await?;
+            if let Some(pdata) = self.on_ctrl_msg(&msg)? {
+                effect_handler.send_message(pdata).await?;
+            }
+            if msg.is_shutdown() {
+                return Ok(());
+            }
+        }
+    }
+});
+
+fn patched_receiver() -> PatchedReceiver {
+    PatchedReceiver {
+        config: ExportConfig {
+            max_batch_size: 128,
+            timeout_ms: 1000,
+            endpoint: Some("http://localhost:4317".to_owned()),
+        },
+    }
+}
+
+/// Test closure sending two partial configuration updates.
+fn patch_scenario() -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    |ctx| {
+        Box::pin(async move {
+            for patch in [
+                json!({ "max_batch_size": 512 }),
+                json!({ "timeout_ms": 50, "endpoint": null }),
+            ] {
+                ctx.send_reconfigure(patch)
+                    .await
+                    .expect("Failed to send Reconfigure");
+            }
+            // Shutdown preempts the pending control messages, let the receiver process them.
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+/// Validation closure checking that each patch only changed the settings it mentions.
+fn patch_validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            for expected in [
+                ExportConfig {
+                    max_batch_size: 512,
+                    timeout_ms: 1000,
+                    endpoint: Some("http://localhost:4317".to_owned()),
+                },
+                ExportConfig {
+                    max_batch_size: 512,
+                    timeout_ms: 50,
+                    endpoint: None,
+                },
+            ] {
+                let received = timeout(Duration::from_secs(3), ctx.recv())
+                    .await
+                    .expect("Timed out waiting for message")
+                    .expect("No message received");
+                assert_eq!(
+                    received,
+                    TestMsg::new(serde_json::to_string(&expected).unwrap())
+                );
+            }
+        })
+    }
+}
+
+#[test]
+fn test_reconfigure_patch_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let receiver = ReceiverWrapper::local(patched_receiver(), test_runtime.config());
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(patch_scenario())
+        .run_validation(patch_validation_procedure());
+}
+
+#[test]
+fn test_reconfigure_patch_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let receiver = ReceiverWrapper::shared(patched_receiver(), test_runtime.config());
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(patch_scenario())
+        .run_validation(patch_validation_procedure());
+}
+
+/// A receiver applying the configuration updates addressed to it, rejecting the malformed ones.
+struct ConfigurableReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl ConfigurableReceiver {
+    fn apply(&self, update: &NodeConfigUpdate) -> Result<u64, String> {
+        self.ctrl_msg_counters.increment_config();
+        update
+            .decode::<BatchConfig>()
+            .map(|_config| update.version)
+            .map_err(|error| error.to_string())
+    }
+}
+
+impl_test_receiver!(ConfigurableReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            match ctrl_msg_recv.recv().await? {
+                ControlMsg::Config { update } => match self.apply(&update) {
+                    Ok(version) => effect_handler.ack_config(version),
+                    Err(reason) => effect_handler.reject_config(update.version, reason),
+                },
+                ControlMsg::Shutdown { .. } => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+});
+
+/// Broadcasts configuration updates to two receivers, and checks that the updates addressed to
+/// the first one don't reach the second one and that their outcome is reported back.
+fn assert_config_delivery(
+    receiver_a: ReceiverWrapper<TestMsg>,
+    counters_a: &CtrlMsgCounters,
+    receiver_b: ReceiverWrapper<TestMsg>,
+    counters_b: &CtrlMsgCounters,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_senders = [receiver_a.control_sender(), receiver_b.control_sender()];
+    let mut acks_a = receiver_a.config_acks();
+    let mut acks_b = receiver_b.config_acks();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handles = [
+            tokio::task::spawn_local(receiver_a.start()),
+            tokio::task::spawn_local(receiver_b.start()),
+        ];
+        let broadcast = |update: NodeConfigUpdate| {
+            let control_senders = control_senders.clone();
+            async move {
+                for control_sender in &control_senders {
+                    control_sender
+                        .send(ControlMsg::Config {
+                            update: update.clone(),
+                        })
+                        .await
+                        .expect("Failed to send Config");
+                }
+            }
+        };
+
+        broadcast(NodeConfigUpdate::for_node(
+            "receiver_a",
+            1,
+            json!({ "max_batch_size": 64 }),
+        ))
+        .await;
+        timeout(Duration::from_secs(3), acks_a.wait_for(1))
+            .await
+            .expect("Timed out waiting for the config ack")
+            .expect("Config version 1 not applied");
+
+        broadcast(NodeConfigUpdate::for_node(
+            "receiver_a",
+            2,
+            json!({ "max_batch_size": "large" }),
+        ))
+        .await;
+        let result = timeout(Duration::from_secs(3), acks_a.wait_for(2))
+            .await
+            .expect("Timed out waiting for the config ack");
+        assert!(matches!(
+            result,
+            Err(Error::ConfigRejected { node, version: 2, .. }) if node == "receiver_a"
+        ));
+
+        // The updates are delivered in order, the second receiver only sees the wildcard one.
+        broadcast(NodeConfigUpdate::for_all(
+            3,
+            json!({ "max_batch_size": 128 }),
+        ))
+        .await;
+        timeout(Duration::from_secs(3), acks_b.wait_for(3))
+            .await
+            .expect("Timed out waiting for the config ack")
+            .expect("Config version 3 not applied");
+
+        for control_sender in &control_senders {
+            control_sender
+                .send(ControlMsg::Shutdown {
+                    deadline: Duration::from_millis(100),
+                    reason: "Test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+        }
+        for handle in handles {
+            handle
+                .await
+                .expect("Receiver task panicked")
+                .expect("Receiver failed");
+        }
+    }));
+
+    assert_eq!(counters_a.get_config_count(), 3);
+    assert_eq!(counters_b.get_config_count(), 1);
+}
+
+#[test]
+fn test_config_delivery_local() {
+    let (counters_a, counters_b) = (CtrlMsgCounters::new(), CtrlMsgCounters::new());
+    let receiver_a = ReceiverWrapper::local(
+        ConfigurableReceiver {
+            ctrl_msg_counters: counters_a.clone(),
+        },
+        &ReceiverConfig::new("receiver_a"),
+    );
+    let receiver_b = ReceiverWrapper::local(
+        ConfigurableReceiver {
+            ctrl_msg_counters: counters_b.clone(),
+        },
+        &ReceiverConfig::new("receiver_b"),
+    );
+    assert_config_delivery(receiver_a, &counters_a, receiver_b, &counters_b);
+}
+
+#[test]
+fn test_config_delivery_shared() {
+    let (counters_a, counters_b) = (CtrlMsgCounters::new(), CtrlMsgCounters::new());
+    let receiver_a = ReceiverWrapper::shared(
+        ConfigurableReceiver {
+            ctrl_msg_counters: counters_a.clone(),
+        },
+        &ReceiverConfig::new("receiver_a"),
+    );
+    let receiver_b = ReceiverWrapper::shared(
+        ConfigurableReceiver {
+            ctrl_msg_counters: counters_b.clone(),
+        },
+        &ReceiverConfig::new("receiver_b"),
+    );
+    assert_config_delivery(receiver_a, &counters_a, receiver_b, &counters_b);
+}
+
+/// A receiver emitting the batch size of the decoded configuration updates it receives.
+struct TypedConfigReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl TypedConfigReceiver {
+    /// Returns the message announcing the applied configuration, and its version.
+    fn apply(&self, update: &NodeConfigUpdate) -> (TestMsg, u64) {
+        self.ctrl_msg_counters.increment_config();
+        let config = update
+            .decoded::<BatchConfig>()
+            .expect("Configuration not decoded by the engine");
+        (
+            TestMsg::new(format!("max_batch_size {}", config.max_batch_size)),
+            update.version,
+        )
+    }
+}
+
+impl_test_receiver!(TypedConfigReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            match ctrl_msg_recv.recv().await? {
+                ControlMsg::Config { update } => {
+                    let (msg, version) = self.apply(&update);
+                    effect_handler.send_message(msg).await?;
+                    effect_handler.ack_config(version);
+                }
+                ControlMsg::Shutdown { .. } => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+});
+
+fn typed_config() -> ReceiverConfig {
+    ReceiverConfig::builder()
+        .with_name("typed_receiver")
+        .with_config_schema::<BatchConfig>()
+        .build()
+        .expect("Invalid configuration")
+}
+
+/// Sends a well-typed then a malformed configuration update, and checks that the first one is
+/// delivered decoded while the second one is rejected by the engine without reaching the
+/// receiver.
+fn assert_typed_config(mut receiver: ReceiverWrapper<TestMsg>, counters: &CtrlMsgCounters) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+    let mut acks = receiver.config_acks();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let send_config = |update: NodeConfigUpdate| {
+            let control_sender = control_sender.clone();
+            async move {
+                control_sender
+                    .send(ControlMsg::Config { update })
+                    .await
+                    .expect("Failed to send Config");
+            }
+        };
+
+        send_config(NodeConfigUpdate::for_node(
+            "typed_receiver",
+            1,
+            json!({ "max_batch_size": 64 }),
+        ))
+        .await;
+        timeout(Duration::from_secs(3), acks.wait_for(1))
+            .await
+            .expect("Timed out waiting for the config ack")
+            .expect("Config version 1 not applied");
+        let msg = timeout(Duration::from_secs(3), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for a message")
+            .expect("Message not received");
+        assert_eq!(msg, TestMsg::new("max_batch_size 64"));
+
+        send_config(NodeConfigUpdate::for_all(
+            2,
+            json!({ "max_batch_size": "large" }),
+        ))
+        .await;
+        let result = timeout(Duration::from_secs(3), acks.wait_for(2))
+            .await
+            .expect("Timed out waiting for the config ack");
+        assert!(
+            matches!(
+                &result,
+                Err(Error::ConfigRejected { node, version: 2, reason })
+                    if node == "typed_receiver" && reason.starts_with("Invalid configuration")
+            ),
+            "Unexpected result {result:?}"
+        );
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+
+    // The malformed update never reached the receiver.
+    assert_eq!(counters.get_config_count(), 1);
+}
+
+#[test]
+fn test_typed_config_local() {
+    let counters = CtrlMsgCounters::new();
+    let receiver = ReceiverWrapper::local(
+        TypedConfigReceiver {
+            ctrl_msg_counters: counters.clone(),
+        },
+        &typed_config(),
+    );
+    assert_typed_config(receiver, &counters);
+}
+
+#[test]
+fn test_typed_config_shared() {
+    let counters = CtrlMsgCounters::new();
+    let receiver = ReceiverWrapper::shared(
+        TypedConfigReceiver {
+            ctrl_msg_counters: counters.clone(),
+        },
+        &typed_config(),
+    );
+    assert_typed_config(receiver, &counters);
+}
@@ -0,0 +1,533 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Connections served by the receivers with `serve_connections`.
+
+use super::*;
+
+/// Pauses the ingestion of the receiver, connects a client and checks that no message is
+/// produced until the ingestion is resumed.
+fn assert_no_ingest_while_paused(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let control_sender = receiver.control_sender();
+    let state = receiver.state_watcher();
+    let bound_addresses = receiver.subscribe_bound_addresses();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let addr = ready_address(state, bound_addresses).await;
+
+        control_sender
+            .send(ControlMsg::Pause)
+            .await
+            .expect("Failed to send Pause");
+        sleep(Duration::from_millis(50)).await;
+
+        // The connection lands in the listen backlog of the paused receiver.
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("Failed to connect to receiver");
+        stream
+            .write_all(b"sent while paused")
+            .await
+            .expect("Failed to send data");
+        stream.shutdown().await.expect("Failed to close connection");
+
+        assert!(
+            timeout(Duration::from_millis(200), pdata_rx.recv())
+                .await
+                .is_err(),
+            "A message was produced while the ingestion was paused"
+        );
+
+        control_sender
+            .send(ControlMsg::Resume)
+            .await
+            .expect("Failed to send Resume");
+        let received = timeout(Duration::from_secs(3), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for message")
+            .expect("No message received");
+        assert_eq!(received, TestMsg::new("sent while paused"));
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+}
+
+#[test]
+fn test_no_ingest_while_paused_local() {
+    let receiver =
+        ReceiverWrapper::local(ServingReceiver, &ReceiverConfig::new("pausable_receiver"));
+    assert_no_ingest_while_paused(receiver);
+}
+
+#[test]
+fn test_no_ingest_while_paused_shared() {
+    let receiver =
+        ReceiverWrapper::shared(ServingReceiver, &ReceiverConfig::new("pausable_receiver"));
+    assert_no_ingest_while_paused(receiver);
+}
+
+/// Opens more connections than the receiver accepts concurrently and checks that the extra
+/// connection is only accepted once one of the others is closed.
+fn assert_connection_limit(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let control_sender = receiver.control_sender();
+    let state = receiver.state_watcher();
+    let bound_addresses = receiver.subscribe_bound_addresses();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let addr = ready_address(state, bound_addresses).await;
+
+        let mut clients = Vec::new();
+        for payload in ["first", "second", "third"] {
+            let mut stream = TcpStream::connect(addr)
+                .await
+                .expect("Failed to connect to receiver");
+            stream
+                .write_all(payload.as_bytes())
+                .await
+                .expect("Failed to send data");
+            clients.push(stream);
+            // Let the receiver accept the connection before opening the next one.
+            sleep(Duration::from_millis(50)).await;
+        }
+
+        // The third connection stays in the listen backlog while the first two are open.
+        let mut third = clients.pop().expect("Missing third client");
+        third.shutdown().await.expect("Failed to close connection");
+        assert!(
+            timeout(Duration::from_millis(200), pdata_rx.recv())
+                .await
+                .is_err(),
+            "The third connection was accepted above the limit"
+        );
+
+        let mut first = clients.remove(0);
+        first.shutdown().await.expect("Failed to close connection");
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            received.push(
+                timeout(Duration::from_secs(3), pdata_rx.recv())
+                    .await
+                    .expect("Timed out waiting for message")
+                    .expect("No message received"),
+            );
+        }
+        assert_eq!(received, vec![TestMsg::new("first"), TestMsg::new("third")]);
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+}
+
+/// Connects 4 clients to a receiver whose output channel buffers 2 messages and isn't
+/// consumed, and checks the summary of the run published once the receiver shut down.
+fn assert_shutdown_report(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let control_sender = receiver.control_sender();
+    let state = receiver.state_watcher();
+    let bound_addresses = receiver.subscribe_bound_addresses();
+    let _pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let report_rx = receiver.subscribe_shutdown_report();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let addr = ready_address(state, bound_addresses).await;
+
+        for payload in ["first", "second", "third", "fourth"] {
+            let mut stream = TcpStream::connect(addr)
+                .await
+                .expect("Failed to connect to receiver");
+            stream
+                .write_all(payload.as_bytes())
+                .await
+                .expect("Failed to send data");
+            stream.shutdown().await.expect("Failed to close connection");
+        }
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(*report_rx.borrow(), None);
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+        assert_eq!(
+            *report_rx.borrow(),
+            Some(ShutdownReport {
+                messages_emitted: 2,
+                messages_dropped: 2,
+                connections_closed: 4,
+            })
+        );
+    }));
+}
+
+fn lossy_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("lossy_receiver");
+    config.output_pdata_channel.capacity = 2;
+    config.output_pdata_channel.overflow_policy = OverflowPolicy::DropNewest;
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+#[test]
+fn test_shutdown_report_local() {
+    let receiver = ReceiverWrapper::local(ServingReceiver, &lossy_config());
+    assert_shutdown_report(receiver);
+}
+
+#[test]
+fn test_shutdown_report_shared() {
+    let receiver = ReceiverWrapper::shared(ServingReceiver, &lossy_config());
+    assert_shutdown_report(receiver);
+}
+
+fn limited_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("limited_receiver");
+    config.max_concurrent_connections = Some(2);
+    config
+}
+
+#[test]
+fn test_connection_limit_local() {
+    let receiver = ReceiverWrapper::local(ServingReceiver, &limited_config());
+    assert_connection_limit(receiver);
+}
+
+#[test]
+fn test_connection_limit_shared() {
+    let receiver = ReceiverWrapper::shared(ServingReceiver, &limited_config());
+    assert_connection_limit(receiver);
+}
+
+/// Sends a payload larger than the default buffers of the OS in a single connection and
+/// checks that it is read at once into the pooled buffer of the receiver.
+fn assert_payload_read_at_once(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+    let state = receiver.state_watcher();
+    let bound_addresses = receiver.subscribe_bound_addresses();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let addr = ready_address(state, bound_addresses).await;
+
+        let payload = "x".repeat(50 * 1024);
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("Failed to connect to receiver");
+        stream
+            .write_all(payload.as_bytes())
+            .await
+            .expect("Failed to send data");
+        stream.shutdown().await.expect("Failed to close connection");
+
+        let received = timeout(Duration::from_secs(3), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for message")
+            .expect("No message received");
+        assert_eq!(received, TestMsg(payload));
+        assert!(
+            timeout(Duration::from_millis(100), pdata_rx.recv())
+                .await
+                .is_err(),
+            "The payload was split into several messages"
+        );
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+}
+
+#[test]
+fn test_read_buffer_size_local() {
+    let receiver =
+        ReceiverWrapper::local(ServingReceiver, &ReceiverConfig::new("buffered_receiver"));
+    assert_payload_read_at_once(receiver);
+}
+
+#[test]
+fn test_read_buffer_size_shared() {
+    let receiver =
+        ReceiverWrapper::shared(ServingReceiver, &ReceiverConfig::new("buffered_receiver"));
+    assert_payload_read_at_once(receiver);
+}
+
+/// The framing of the streams read by [`FramingReceiver`].
+fn test_framing() -> LengthDelimitedConfig {
+    LengthDelimitedConfig {
+        max_frame_length: 16,
+        ..LengthDelimitedConfig::default()
+    }
+}
+
+/// A TCP receiver emitting one message per length-prefixed frame of its connections, and
+/// closing a connection on its first invalid frame.
+struct FramingReceiver;
+
+impl_test_receiver!(FramingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = effect_handler.tcp_listener(addr)?;
+        effect_handler.notify_ready();
+
+        effect_handler
+            .serve_connections(
+                listener,
+                &mut ctrl_msg_recv,
+                |_ctrl_msg| {},
+                |socket, _peer_addr| {
+                    let effect_handler = effect_handler.clone();
+                    async move {
+                        let mut frames = test_framing().framed(socket);
+                        while let Some(Ok(frame)) = frames.next().await {
+                            let msg = TestMsg(String::from_utf8_lossy(&frame).into_owned());
+                            effect_handler
+                                .send_message(msg)
+                                .await
+                                .expect("Error sending message via effect handler");
+                        }
+                    }
+                },
+            )
+            .await
+    }
+});
+
+/// Sends two frames split across TCP segments, then a frame longer than the maximum frame
+/// length, and checks that the receiver emits the whole frames then closes the connection.
+fn assert_frames_reassembled(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let control_sender = receiver.control_sender();
+    let state = receiver.state_watcher();
+    let bound_addresses = receiver.subscribe_bound_addresses();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let addr = ready_address(state, bound_addresses).await;
+
+        let mut bytes = BytesMut::new();
+        for frame in ["hello", "world"] {
+            test_framing()
+                .codec()
+                .encode(Bytes::from_static(frame.as_bytes()), &mut bytes)
+                .expect("Failed to encode");
+        }
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("Failed to connect to receiver");
+        stream.set_nodelay(true).expect("Failed to set TCP_NODELAY");
+        for part in [&bytes[..3], &bytes[3..7], &bytes[7..]] {
+            stream.write_all(part).await.expect("Failed to send data");
+            sleep(Duration::from_millis(20)).await;
+        }
+        for expected in ["hello", "world"] {
+            let received = timeout(Duration::from_secs(3), pdata_rx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+            assert_eq!(received, TestMsg::new(expected));
+        }
+
+        // Only the prefix of the over-length frame is sent.
+        let mut oversized = BytesMut::new();
+        LengthDelimitedConfig::default()
+            .codec()
+            .encode(Bytes::from_static(&[0; 64]), &mut oversized)
+            .expect("Failed to encode");
+        stream
+            .write_all(&oversized[..4])
+            .await
+            .expect("Failed to send data");
+        let mut buf = [0u8; 1];
+        let read = timeout(Duration::from_secs(3), stream.read(&mut buf))
+            .await
+            .expect("The connection wasn't closed");
+        assert!(matches!(read, Ok(0) | Err(_)));
+        assert!(
+            timeout(Duration::from_millis(100), pdata_rx.recv())
+                .await
+                .is_err(),
+            "A message was produced for the over-length frame"
+        );
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+}
+
+#[test]
+fn test_framing_local() {
+    assert_frames_reassembled(ReceiverWrapper::local(
+        FramingReceiver,
+        &ReceiverConfig::new("framing_receiver"),
+    ));
+}
+
+#[test]
+fn test_framing_shared() {
+    assert_frames_reassembled(ReceiverWrapper::shared(
+        FramingReceiver,
+        &ReceiverConfig::new("framing_receiver"),
+    ));
+}
+
+/// A TCP receiver logging each accepted connection through its effect handler, and emitting
+/// one message per connection once the client closes it.
+struct LoggingReceiver;
+
+impl_test_receiver!(LoggingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = effect_handler.tcp_listener(addr)?;
+
+        effect_handler
+            .serve_connections(
+                listener,
+                &mut ctrl_msg_recv,
+                |_ctrl_msg| {},
+                |socket, peer_addr| {
+                    let effect_handler = effect_handler.clone();
+                    async move {
+                        effect_handler
+                            .info_message(&format!("Connection accepted from {peer_addr}"));
+                        let msg = read_until_eof(socket).await;
+                        effect_handler
+                            .send_message(msg)
+                            .await
+                            .expect("Error sending message via effect handler");
+                    }
+                },
+            )
+            .await
+    }
+});
+
+/// Connects a client to the receiver and checks that the connection was logged with the
+/// identity of the receiver.
+fn assert_connection_logged(
+    receiver: impl FnOnce(LoggingReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let log_sink = test_runtime.log_sink();
+    let receiver = receiver(LoggingReceiver, test_runtime.config());
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(|ctx| async move {
+            let addr = ctx
+                .bound_address()
+                .await
+                .expect("Receiver terminated before binding its listener");
+            let mut stream = TcpStream::connect(addr)
+                .await
+                .expect("Failed to connect to receiver");
+            stream
+                .write_all(b"logged")
+                .await
+                .expect("Failed to send data");
+            stream.shutdown().await.expect("Failed to close connection");
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+        .run_validation(|mut ctx| async move {
+            let received = timeout(Duration::from_secs(3), ctx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+            assert_eq!(received, TestMsg::new("logged"));
+
+            let records = log_sink.records();
+            assert_eq!(records.len(), 1);
+            let record = &records[0];
+            assert_eq!(record.level, LogLevel::Info);
+            assert_eq!(record.node, "test_receiver");
+            assert_eq!(record.node_kind, NodeKind::Receiver);
+            assert_eq!(record.pipeline_id, None);
+            assert!(
+                record
+                    .message
+                    .starts_with("Connection accepted from 127.0.0.1:")
+            );
+        });
+}
+
+#[test]
+fn test_connection_logged_local() {
+    assert_connection_logged(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_connection_logged_shared() {
+    assert_connection_logged(ReceiverWrapper::shared);
+}
@@ -0,0 +1,557 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Control messages handled by the receivers.
+
+use super::*;
+
+/// A receiver emitting one message per timer tick, unless it is paused.
+struct TickingReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl TickingReceiver {
+    /// Returns the message to emit for the given control message, if any.
+    fn on_ctrl_msg(&self, msg: &ControlMsg, paused: bool) -> Option<TestMsg> {
+        self.ctrl_msg_counters.update_with(msg);
+        let tick = self.ctrl_msg_counters.get_timer_tick_count();
+        (matches!(msg, ControlMsg::TimerTick {}) && !paused)
+            .then(|| TestMsg::new(format!("tick {tick}")))
+    }
+}
+
+impl_test_receiver!(TickingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            let msg = ctrl_msg_recv.recv().await?;
+            if let Some(pdata) = self.on_ctrl_msg(&msg, ctrl_msg_recv.is_paused()) {
+                effect_handler.send_message(pdata).await?;
+            }
+            if msg.is_shutdown() {
+                return Ok(());
+            }
+        }
+    }
+});
+
+/// Test closure sending timer ticks before, while and after pausing the receiver.
+fn pause_scenario() -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    |ctx| {
+        Box::pin(async move {
+            // Pause, Resume and Shutdown preempt the pending control messages, the receiver
+            // processes the ticks sent before each of them first.
+            ctx.send_timer_tick()
+                .await
+                .expect("Failed to send TimerTick");
+            ctx.sleep(Duration::from_millis(100)).await;
+            ctx.send_pause().await.expect("Failed to send Pause");
+            for _ in 0..2 {
+                ctx.send_timer_tick()
+                    .await
+                    .expect("Failed to send TimerTick");
+            }
+            ctx.sleep(Duration::from_millis(100)).await;
+            ctx.send_resume().await.expect("Failed to send Resume");
+            ctx.send_timer_tick()
+                .await
+                .expect("Failed to send TimerTick");
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+/// Validation closure checking that no message was emitted while the receiver was paused.
+fn pause_validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            for expected in ["tick 1", "tick 4"] {
+                let received = timeout(Duration::from_secs(3), ctx.recv())
+                    .await
+                    .expect("Timed out waiting for message")
+                    .expect("No message received");
+                assert_eq!(received, TestMsg::new(expected));
+            }
+            assert!(
+                timeout(Duration::from_millis(100), ctx.recv())
+                    .await
+                    .map_or(true, |received| received.is_err()),
+                "A message was emitted while the receiver was paused"
+            );
+            ctx.counters().assert(4, 0, 0, 1, 1, 1);
+        })
+    }
+}
+
+#[test]
+fn test_pause_resume_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let receiver = ReceiverWrapper::local(
+        TickingReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(pause_scenario())
+        .run_validation(pause_validation_procedure());
+}
+
+#[test]
+fn test_pause_resume_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let receiver = ReceiverWrapper::shared(
+        TickingReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(pause_scenario())
+        .run_validation(pause_validation_procedure());
+}
+
+/// Sends timer ticks to a `TickingReceiver` whose sends fail on the fault injected in the test
+/// runtime, and checks that the receiver surfaces the error through its `start` result.
+fn assert_injected_fault(
+    test_runtime: TestRuntime<TestMsg>,
+    new_receiver: impl FnOnce(TickingReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+    emitted: &'static [&'static str],
+    is_expected_error: fn(&Error<TestMsg>) -> bool,
+) {
+    let faults = test_runtime.faults();
+    let receiver = new_receiver(
+        TickingReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(|ctx| async move {
+            // The receiver fails on an injected fault, the next ticks may not be delivered.
+            for _ in 0..3 {
+                _ = ctx.send_timer_tick().await;
+            }
+        })
+        .run_validation_with_result(|mut ctx, result| async move {
+            let error = result.expect_err("The injected fault was not surfaced");
+            assert!(is_expected_error(&error), "Unexpected error: {error:?}");
+            for expected in emitted {
+                assert_eq!(ctx.recv().await.unwrap(), TestMsg::new(*expected));
+            }
+            assert!(ctx.recv().await.is_err());
+            assert_eq!(faults.get_injected_failure_count(), 1);
+        });
+}
+
+#[test]
+fn test_injected_send_failure_local() {
+    assert_injected_fault(
+        TestRuntime::new().inject_send_failure(1),
+        ReceiverWrapper::local,
+        &["tick 1"],
+        |error| matches!(error, Error::PdataChannelClosed { .. }),
+    );
+}
+
+#[test]
+fn test_injected_send_failure_shared() {
+    assert_injected_fault(
+        TestRuntime::new().inject_send_failure(1),
+        ReceiverWrapper::shared,
+        &["tick 1"],
+        |error| matches!(error, Error::PdataChannelClosed { .. }),
+    );
+}
+
+#[test]
+fn test_injected_channel_full_local() {
+    assert_injected_fault(
+        TestRuntime::new().inject_channel_full(),
+        ReceiverWrapper::local,
+        &[],
+        |error| matches!(error, Error::PdataChannelFull { .. }),
+    );
+}
+
+#[test]
+fn test_injected_channel_full_shared() {
+    assert_injected_fault(
+        TestRuntime::new().inject_channel_full(),
+        ReceiverWrapper::shared,
+        &[],
+        |error| matches!(error, Error::PdataChannelFull { .. }),
+    );
+}
+
+/// A receiver accumulating one message per timer tick, and only emitting them on `Flush`.
+struct BatchingReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl BatchingReceiver {
+    /// Handles a control message, returning the id of the flush to acknowledge along with the
+    /// messages to emit, if any.
+    fn on_ctrl_msg(
+        &self,
+        msg: &ControlMsg,
+        batch: &mut Vec<TestMsg>,
+    ) -> Option<(u64, Vec<TestMsg>)> {
+        self.ctrl_msg_counters.update_with(msg);
+        match msg {
+            ControlMsg::TimerTick {} => {
+                batch.push(TestMsg::new(format!("msg {}", batch.len())));
+                None
+            }
+            ControlMsg::Flush { id, .. } => Some((*id, std::mem::take(batch))),
+            _ => None,
+        }
+    }
+}
+
+impl_test_receiver!(BatchingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let mut batch = Vec::new();
+        loop {
+            let msg = ctrl_msg_recv.recv().await?;
+            if let Some((id, flushed)) = self.on_ctrl_msg(&msg, &mut batch) {
+                for pdata in flushed {
+                    effect_handler.send_message(pdata).await?;
+                    self.ctrl_msg_counters.increment_message();
+                }
+                effect_handler.ack_flush(id);
+            }
+            if msg.is_shutdown() {
+                return Ok(());
+            }
+        }
+    }
+});
+
+/// Test closure accumulating three messages in the receiver, then flushing them.
+fn flush_scenario(
+    counters: CtrlMsgCounters,
+) -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |ctx| {
+        Box::pin(async move {
+            for _ in 0..3 {
+                ctx.send_timer_tick()
+                    .await
+                    .expect("Failed to send TimerTick");
+            }
+            ctx.sleep(Duration::from_millis(100)).await;
+            assert_eq!(counters.get_timer_tick_count(), 3);
+            assert_eq!(counters.get_message_count(), 0, "Emitted before the flush");
+
+            // The flush is acknowledged once the batch has been emitted.
+            timeout(Duration::from_secs(3), ctx.send_flush("checkpoint"))
+                .await
+                .expect("Timed out waiting for the flush ack")
+                .expect("Failed to flush");
+            assert_eq!(counters.get_message_count(), 3);
+
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+/// Validation closure checking that the whole batch was emitted.
+fn flush_validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            for i in 0..3 {
+                let received = timeout(Duration::from_secs(3), ctx.recv())
+                    .await
+                    .expect("Timed out waiting for message")
+                    .expect("No message received");
+                assert_eq!(received, TestMsg::new(format!("msg {i}")));
+            }
+            assert_eq!(ctx.counters().get_flush_count(), 1);
+        })
+    }
+}
+
+#[test]
+fn test_flush_batching_receiver_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let counters = test_runtime.counters();
+    let receiver = ReceiverWrapper::local(
+        BatchingReceiver {
+            ctrl_msg_counters: counters.clone(),
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(flush_scenario(counters))
+        .run_validation(flush_validation_procedure());
+}
+
+#[test]
+fn test_flush_batching_receiver_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let counters = test_runtime.counters();
+    let receiver = ReceiverWrapper::shared(
+        BatchingReceiver {
+            ctrl_msg_counters: counters.clone(),
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(flush_scenario(counters))
+        .run_validation(flush_validation_procedure());
+}
+
+/// Sends a shutdown to a stuck receiver and checks that the shutdown deadline is enforced.
+fn assert_shutdown_timeout(receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(50),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send shutdown");
+
+        let result = timeout(Duration::from_secs(5), handle)
+            .await
+            .expect("The stuck receiver was not dropped")
+            .expect("Receiver task panicked");
+        assert!(matches!(
+            result,
+            Err(Error::ShutdownTimeout { deadline, .. }) if deadline == Duration::from_millis(50)
+        ));
+    }));
+}
+
+#[test]
+fn test_stuck_receiver_shutdown_timeout_local() {
+    let config = ReceiverConfig::new("stuck_receiver");
+    assert_shutdown_timeout(ReceiverWrapper::local(StuckReceiver, &config));
+}
+
+#[test]
+fn test_stuck_receiver_shutdown_timeout_shared() {
+    let config = ReceiverConfig::new("stuck_receiver");
+    assert_shutdown_timeout(ReceiverWrapper::shared(StuckReceiver, &config));
+}
+
+#[test]
+fn test_control_channel_try_recv_local() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (control_tx, control_rx) = create_not_send_channel(4);
+    let mut ctrl_msg_chan = local::ControlChannel::new(Receiver::Local(control_rx));
+
+    rt.block_on(local_tasks.run_until(async move {
+        assert!(matches!(ctrl_msg_chan.try_recv(), Ok(None)));
+        for msg in [
+            ControlMsg::TimerTick {},
+            ControlMsg::HealthCheck { id: 1 },
+            ControlMsg::Pause,
+        ] {
+            control_tx
+                .send(msg)
+                .expect("Failed to send control message");
+        }
+        assert!(matches!(
+            ctrl_msg_chan.try_recv(),
+            Ok(Some(ControlMsg::TimerTick {}))
+        ));
+        // The health check is answered by the channel.
+        assert!(matches!(
+            ctrl_msg_chan.try_recv(),
+            Ok(Some(ControlMsg::Pause))
+        ));
+        assert!(ctrl_msg_chan.is_paused());
+        assert!(matches!(ctrl_msg_chan.try_recv(), Ok(None)));
+
+        let started = Instant::now();
+        assert!(matches!(
+            ctrl_msg_chan.recv_timeout(Duration::from_millis(50)).await,
+            Ok(None)
+        ));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        control_tx
+            .send(ControlMsg::Resume)
+            .expect("Failed to send control message");
+        assert!(matches!(
+            ctrl_msg_chan.recv_timeout(Duration::from_secs(1)).await,
+            Ok(Some(ControlMsg::Resume))
+        ));
+
+        drop(control_tx);
+        assert!(matches!(ctrl_msg_chan.try_recv(), Err(RecvError::Closed)));
+        assert!(matches!(
+            ctrl_msg_chan.recv_timeout(Duration::from_secs(1)).await,
+            Err(RecvError::Closed)
+        ));
+    }));
+}
+
+#[test]
+fn test_control_channel_try_recv_shared() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (control_tx, control_rx) = tokio::sync::mpsc::channel(4);
+    let mut ctrl_msg_chan = shared::ControlChannel::new(control_rx);
+
+    rt.block_on(local_tasks.run_until(async move {
+        assert!(matches!(ctrl_msg_chan.try_recv(), Ok(None)));
+        for msg in [
+            ControlMsg::TimerTick {},
+            ControlMsg::HealthCheck { id: 1 },
+            ControlMsg::Pause,
+        ] {
+            control_tx
+                .send(msg)
+                .await
+                .expect("Failed to send control message");
+        }
+        assert!(matches!(
+            ctrl_msg_chan.try_recv(),
+            Ok(Some(ControlMsg::TimerTick {}))
+        ));
+        // The health check is answered by the channel.
+        assert!(matches!(
+            ctrl_msg_chan.try_recv(),
+            Ok(Some(ControlMsg::Pause))
+        ));
+        assert!(ctrl_msg_chan.is_paused());
+        assert!(matches!(ctrl_msg_chan.try_recv(), Ok(None)));
+
+        let started = Instant::now();
+        assert!(matches!(
+            ctrl_msg_chan.recv_timeout(Duration::from_millis(50)).await,
+            Ok(None)
+        ));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        control_tx
+            .send(ControlMsg::Resume)
+            .await
+            .expect("Failed to send control message");
+        assert!(matches!(
+            ctrl_msg_chan.recv_timeout(Duration::from_secs(1)).await,
+            Ok(Some(ControlMsg::Resume))
+        ));
+
+        drop(control_tx);
+        assert!(matches!(ctrl_msg_chan.try_recv(), Err(RecvError::Closed)));
+        assert!(matches!(
+            ctrl_msg_chan.recv_timeout(Duration::from_secs(1)).await,
+            Err(RecvError::Closed)
+        ));
+    }));
+}
+
+#[test]
+fn test_wait_for_resume_local() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (control_tx, control_rx) = create_not_send_channel(8);
+    let mut ctrl_msg_chan = local::ControlChannel::new(Receiver::Local(control_rx));
+
+    rt.block_on(local_tasks.run_until(async move {
+        // Not paused, nothing to wait for.
+        assert!(matches!(ctrl_msg_chan.wait_for_resume().await, Ok(None)));
+
+        for msg in [
+            ControlMsg::Pause,
+            ControlMsg::TimerTick {},
+            ControlMsg::Resume,
+            ControlMsg::Pause,
+            ControlMsg::Shutdown {
+                deadline: Duration::from_secs(1),
+                reason: "test".to_owned(),
+            },
+        ] {
+            control_tx
+                .send(msg)
+                .expect("Failed to send control message");
+        }
+        assert!(matches!(ctrl_msg_chan.recv().await, Ok(ControlMsg::Pause)));
+        // The timer tick is discarded.
+        assert!(matches!(ctrl_msg_chan.wait_for_resume().await, Ok(None)));
+        assert!(!ctrl_msg_chan.is_paused());
+
+        assert!(matches!(ctrl_msg_chan.recv().await, Ok(ControlMsg::Pause)));
+        assert!(matches!(
+            ctrl_msg_chan.wait_for_resume().await,
+            Ok(Some(ControlMsg::Shutdown { .. }))
+        ));
+
+        drop(control_tx);
+        assert!(matches!(
+            ctrl_msg_chan.wait_for_resume().await,
+            Err(RecvError::Closed)
+        ));
+    }));
+}
+
+#[test]
+fn test_wait_for_resume_shared() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (control_tx, control_rx) = tokio::sync::mpsc::channel(8);
+    let mut ctrl_msg_chan = shared::ControlChannel::new(control_rx);
+
+    rt.block_on(local_tasks.run_until(async move {
+        // Not paused, nothing to wait for.
+        assert!(matches!(ctrl_msg_chan.wait_for_resume().await, Ok(None)));
+
+        for msg in [
+            ControlMsg::Pause,
+            ControlMsg::TimerTick {},
+            ControlMsg::Resume,
+            ControlMsg::Pause,
+            ControlMsg::Shutdown {
+                deadline: Duration::from_secs(1),
+                reason: "test".to_owned(),
+            },
+        ] {
+            control_tx
+                .send(msg)
+                .await
+                .expect("Failed to send control message");
+        }
+        assert!(matches!(ctrl_msg_chan.recv().await, Ok(ControlMsg::Pause)));
+        // The timer tick is discarded.
+        assert!(matches!(ctrl_msg_chan.wait_for_resume().await, Ok(None)));
+        assert!(!ctrl_msg_chan.is_paused());
+
+        assert!(matches!(ctrl_msg_chan.recv().await, Ok(ControlMsg::Pause)));
+        assert!(matches!(
+            ctrl_msg_chan.wait_for_resume().await,
+            Ok(Some(ControlMsg::Shutdown { .. }))
+        ));
+
+        drop(control_tx);
+        assert!(matches!(
+            ctrl_msg_chan.wait_for_resume().await,
+            Err(RecvError::Closed)
+        ));
+    }));
+}
@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Health of the receivers.
+
+use super::*;
+
+/// A test receiver getting stuck for 5 seconds in its event loop on every `TimerTick`.
+struct SleepyReceiver;
+
+const SLEEP_DURATION: Duration = Duration::from_secs(5);
+
+impl_test_receiver!(SleepyReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        _effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            match ctrl_msg_recv.recv().await? {
+                ControlMsg::TimerTick { .. } => sleep(SLEEP_DURATION).await,
+                ControlMsg::Shutdown { .. } => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+});
+
+fn probed_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("sleepy_receiver");
+    config.health_check = Some(HealthCheckConfig {
+        interval: Duration::from_millis(100),
+        timeout: Duration::from_millis(500),
+    });
+    config
+}
+
+/// Checks that the receiver is marked as stalled while it sleeps, back to running once it
+/// wakes up, and stopped once it completes.
+fn assert_health_transitions(receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let control_sender = receiver.control_sender();
+    let probe = receiver.health_probe();
+    assert_eq!(receiver.health().state, NodeState::Running);
+
+    let watched = probe.clone();
+    let wait_for_state = move |state: NodeState, within: Duration| {
+        let probe = watched.clone();
+        async move {
+            timeout(within, async {
+                while probe.health().state != state {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("Timed out waiting for {state:?}"));
+        }
+    };
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+
+        // A few health checks are answered before the receiver gets stuck.
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(probe.health().state, NodeState::Running);
+        let answered_at = probe.health().last_seen;
+
+        control_sender
+            .send(ControlMsg::TimerTick {})
+            .await
+            .expect("Failed to send TimerTick");
+        let stuck_at = Instant::now();
+        wait_for_state(NodeState::Stalled, Duration::from_secs(2)).await;
+        wait_for_state(NodeState::Running, 2 * SLEEP_DURATION).await;
+        assert!(stuck_at.elapsed() >= SLEEP_DURATION - Duration::from_millis(100));
+        assert!(probe.health().last_seen > answered_at);
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+        assert_eq!(probe.health().state, NodeState::Stopped);
+    }));
+}
+
+#[test]
+fn test_health_transitions_local() {
+    assert_health_transitions(ReceiverWrapper::local(SleepyReceiver, &probed_config()));
+}
+
+#[test]
+fn test_health_transitions_shared() {
+    assert_health_transitions(ReceiverWrapper::shared(SleepyReceiver, &probed_config()));
+}
+
+/// A test receiver reporting a degraded health until it gets a `TimerTick`, and an unhealthy
+/// one once shut down.
+struct FlappingReceiver;
+
+impl_test_receiver!(FlappingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        effect_handler.report_health(HealthStatus::Degraded("unreachable".to_owned()));
+        loop {
+            match ctrl_msg_recv.recv().await? {
+                ControlMsg::TimerTick { .. } => {
+                    effect_handler.report_health(HealthStatus::Healthy);
+                }
+                ControlMsg::Shutdown { .. } => {
+                    effect_handler.report_health(HealthStatus::Unhealthy("shut down".into()));
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+});
+
+/// Checks that the health statuses reported by the receiver are observed by the subscribers.
+fn assert_health_reports(receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+    let mut health_rx = receiver.subscribe_health();
+    assert_eq!(health_rx.health(), HealthStatus::Healthy);
+
+    rt.block_on(local_tasks.run_until(async move {
+        let mut wait_for_status = async |expected: HealthStatus| {
+            _ = timeout(
+                Duration::from_secs(1),
+                health_rx.wait_for(|status| *status == expected),
+            )
+            .await
+            .unwrap_or_else(|_| panic!("Timed out waiting for {expected:?}"))
+            .expect("Health reports closed");
+        };
+        let handle = tokio::task::spawn_local(receiver.start());
+        wait_for_status(HealthStatus::Degraded("unreachable".to_owned())).await;
+
+        control_sender
+            .send(ControlMsg::TimerTick {})
+            .await
+            .expect("Failed to send TimerTick");
+        wait_for_status(HealthStatus::Healthy).await;
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+        wait_for_status(HealthStatus::Unhealthy("shut down".to_owned())).await;
+    }));
+}
+
+#[test]
+fn test_health_reports_local() {
+    assert_health_reports(ReceiverWrapper::local(
+        FlappingReceiver,
+        &ReceiverConfig::new("flapping_receiver"),
+    ));
+}
+
+#[test]
+fn test_health_reports_shared() {
+    assert_health_reports(ReceiverWrapper::shared(
+        FlappingReceiver,
+        &ReceiverConfig::new("flapping_receiver"),
+    ));
+}
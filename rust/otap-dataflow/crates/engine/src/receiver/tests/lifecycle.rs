@@ -0,0 +1,322 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lifecycle of the receivers, from their start to their completion.
+
+use super::*;
+
+/// A test receiver signaling its readiness, then failing on its first timer tick, or waiting
+/// for its release once asked to shut down.
+struct LifecycleReceiver {
+    release: Arc<Notify>,
+}
+
+impl LifecycleReceiver {
+    fn fail() -> Result<(), Error<TestMsg>> {
+        Err(Error::ReceiverError {
+            receiver: "lifecycle_receiver".into(),
+            error: "failed on timer tick".to_owned(),
+        })
+    }
+}
+
+impl_test_receiver!(LifecycleReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        effect_handler.notify_ready();
+        loop {
+            match ctrl_msg_recv.recv().await? {
+                ControlMsg::Shutdown { .. } => {
+                    self.release.notified().await;
+                    return Ok(());
+                }
+                ControlMsg::TimerTick {} => return LifecycleReceiver::fail(),
+                _ => {}
+            }
+        }
+    }
+});
+
+/// Checks that the receiver goes through all the lifecycle states on a clean shutdown.
+fn assert_lifecycle(receiver: ReceiverWrapper<TestMsg>, release: Arc<Notify>) {
+    let test_runtime = TestRuntime::new();
+    let state = receiver.state_watcher();
+    assert_eq!(*state.borrow(), LifecycleState::Starting);
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(move |ctx| async move {
+            ctx.wait_ready().await.expect("The receiver isn't ready");
+            ctx.send_shutdown(Duration::from_secs(1), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+            // The receiver completes once released.
+            ctx.wait_for_state(LifecycleState::Draining)
+                .await
+                .expect("The receiver isn't draining");
+            release.notify_one();
+        })
+        .run_validation(move |_ctx| async move {
+            assert_eq!(*state.borrow(), LifecycleState::Stopped(Ok(())));
+        });
+}
+
+#[test]
+fn test_lifecycle_local() {
+    let release = Arc::new(Notify::new());
+    let receiver = ReceiverWrapper::local(
+        LifecycleReceiver {
+            release: release.clone(),
+        },
+        &ReceiverConfig::new("lifecycle_receiver"),
+    );
+    assert_lifecycle(receiver, release);
+}
+
+#[test]
+fn test_lifecycle_shared() {
+    let release = Arc::new(Notify::new());
+    let receiver = ReceiverWrapper::shared(
+        LifecycleReceiver {
+            release: release.clone(),
+        },
+        &ReceiverConfig::new("lifecycle_receiver"),
+    );
+    assert_lifecycle(receiver, release);
+}
+
+/// Checks that a failed receiver ends in the `Stopped` state with its error.
+fn assert_lifecycle_failure(receiver: ReceiverWrapper<TestMsg>) {
+    let test_runtime = TestRuntime::new();
+    let state = receiver.state_watcher();
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(|ctx| async move {
+            ctx.wait_ready().await.expect("The receiver isn't ready");
+            ctx.send_timer_tick()
+                .await
+                .expect("Failed to send TimerTick");
+            // The receiver stops without draining.
+            assert!(ctx.wait_for_state(LifecycleState::Draining).await.is_err());
+        })
+        .run_validation_with_result(move |_ctx, result| async move {
+            let error = result.expect_err("The receiver didn't fail");
+            assert_eq!(
+                *state.borrow(),
+                LifecycleState::Stopped(Err(error.to_string()))
+            );
+        });
+}
+
+#[test]
+fn test_lifecycle_failure_local() {
+    assert_lifecycle_failure(ReceiverWrapper::local(
+        LifecycleReceiver {
+            release: Arc::new(Notify::new()),
+        },
+        &ReceiverConfig::new("lifecycle_receiver"),
+    ));
+}
+
+#[test]
+fn test_lifecycle_failure_shared() {
+    assert_lifecycle_failure(ReceiverWrapper::shared(
+        LifecycleReceiver {
+            release: Arc::new(Notify::new()),
+        },
+        &ReceiverConfig::new("lifecycle_receiver"),
+    ));
+}
+
+/// A test receiver panicking right after sending a message.
+struct PanickingReceiver;
+
+impl_test_receiver!(PanickingReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        effect_handler
+            .send_message(TestMsg::new("before panic"))
+            .await?;
+        panic!("receiver panicked");
+    }
+});
+
+/// Runs the panicking receiver next to a healthy one, and checks that the message sent before
+/// the panic is delivered, that the panic is reported as an error closing the pdata channel of
+/// the receiver, and that the healthy receiver keeps serving its connections.
+fn assert_panic_isolated(
+    mut receiver: ReceiverWrapper<TestMsg>,
+    mut healthy: ReceiverWrapper<TestMsg>,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let control_sender = healthy.control_sender();
+    let state = healthy.state_watcher();
+    let bound_addresses = healthy.subscribe_bound_addresses();
+    let mut healthy_pdata_rx = healthy
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let healthy_handle = tokio::task::spawn_local(healthy.start());
+        let handle = tokio::task::spawn_local(receiver.start());
+
+        let received = timeout(Duration::from_secs(3), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for message")
+            .expect("No message received");
+        assert_eq!(received, TestMsg::new("before panic"));
+        let result = handle
+            .await
+            .expect("The panic escaped the receiver wrapper");
+        assert!(
+            matches!(
+                &result,
+                Err(Error::NodePanicked { node, payload })
+                    if node == "panicking_receiver" && payload == "receiver panicked"
+            ),
+            "Unexpected result {result:?}"
+        );
+        assert!(matches!(pdata_rx.recv().await, Err(RecvError::Closed)));
+
+        let addr = ready_address(state, bound_addresses).await;
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("Failed to connect to receiver");
+        stream
+            .write_all(b"still serving")
+            .await
+            .expect("Failed to send data");
+        stream.shutdown().await.expect("Failed to close connection");
+        let received = timeout(Duration::from_secs(3), healthy_pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for message")
+            .expect("No message received");
+        assert_eq!(received, TestMsg::new("still serving"));
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        healthy_handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+}
+
+#[test]
+fn test_panic_isolated_local() {
+    assert_panic_isolated(
+        ReceiverWrapper::local(
+            PanickingReceiver,
+            &ReceiverConfig::new("panicking_receiver"),
+        ),
+        ReceiverWrapper::local(ServingReceiver, &ReceiverConfig::new("healthy_receiver")),
+    );
+}
+
+#[test]
+fn test_panic_isolated_shared() {
+    assert_panic_isolated(
+        ReceiverWrapper::shared(
+            PanickingReceiver,
+            &ReceiverConfig::new("panicking_receiver"),
+        ),
+        ReceiverWrapper::shared(ServingReceiver, &ReceiverConfig::new("healthy_receiver")),
+    );
+}
+
+#[test]
+fn test_panic_restarts_receiver() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let sink = Arc::new(InMemoryMetricsSink::new());
+    let mut config = flaky_config(1, sink.clone());
+    config.name = "panicking_receiver".into();
+    let mut receiver = ReceiverWrapper::local_restartable(|| PanickingReceiver, &config);
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let result = timeout(Duration::from_secs(1), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver");
+        assert!(
+            matches!(&result, Err(Error::NodePanicked { .. })),
+            "Unexpected result {result:?}"
+        );
+        // The panic of the first instance is handled like any other failure.
+        let labels = [
+            (NODE_LABEL, "panicking_receiver"),
+            (ERROR_CODE_LABEL, "NodeFailed"),
+        ];
+        assert_eq!(sink.labeled_counter_value(RESTARTS, &labels), 1);
+        for _ in 0..2 {
+            assert_eq!(
+                pdata_rx.recv().await.expect("Message not received"),
+                TestMsg::new("before panic")
+            );
+        }
+    }));
+}
+
+/// A receiver shutting itself down once it sent its only message.
+struct SelfStoppingReceiver;
+
+impl_test_receiver!(SelfStoppingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        effect_handler.send_message(TestMsg::new("only")).await?;
+        effect_handler
+            .request_shutdown(Duration::from_secs(1), "Done")
+            .await?;
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        Ok(())
+    }
+});
+
+/// Checks that the `Shutdown` a receiver sends to itself stops it.
+fn assert_self_shutdown(
+    make: impl Fn(SelfStoppingReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let test_runtime = TestRuntime::new();
+    let receiver = make(SelfStoppingReceiver, test_runtime.config());
+    let state = receiver.state_watcher();
+
+    test_runtime
+        .set_receiver(receiver)
+        // The receiver only completes once it received the `Shutdown`.
+        .run_test(|_ctx| async move {})
+        .run_validation(move |mut ctx| async move {
+            assert_eq!(*state.borrow(), LifecycleState::Stopped(Ok(())));
+            assert_eq!(
+                ctx.recv().await.expect("Message not received"),
+                TestMsg::new("only")
+            );
+        });
+}
+
+#[test]
+fn test_self_shutdown_local() {
+    assert_self_shutdown(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_self_shutdown_shared() {
+    assert_self_shutdown(ReceiverWrapper::shared);
+}
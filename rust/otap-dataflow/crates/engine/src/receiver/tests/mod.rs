@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests of the receiver wrappers, each submodule covering a feature with both the local and the
+//! shared receivers. The test receivers and helpers used by several submodules are defined here.
+
+use super::ReceiverWrapper;
+use crate::buffer_pool::GlobalBufferPool;
+use crate::config::{
+    DrainPolicy, HealthCheckConfig, OverflowPolicy, ReceiverConfig, ReceiverConfigBuilder,
+    RetryPolicy, TimerConfig,
+};
+use crate::framing::LengthDelimitedConfig;
+use crate::health::{HealthCheck, HealthStatus, NodeState};
+use crate::lifecycle::LifecycleState;
+use crate::local::receiver as local;
+use crate::logging::LogLevel;
+use crate::message::{
+    ControlMsg, NodeConfigUpdate, Receiver, ReconfigurePayload, Sender, TypedControlMsg,
+};
+use crate::read_buffer::ReadBuffer;
+use crate::receiver::Error;
+use crate::shared::receiver as shared;
+use crate::telemetry::{
+    BYTES_SENT, ERROR_CODE_LABEL, MESSAGES_SENT, MetricsSink, NODE_LABEL, RESTARTS,
+    ReceiverMetrics, SEND_ERRORS, ShutdownReport,
+};
+use crate::testing::metrics::InMemoryMetricsSink;
+use crate::testing::receiver::{
+    NotSendValidateContext, SendValidateContext, TestContext, TestRuntime, wait_for_state,
+};
+use crate::testing::{
+    CtrlMsgCounters, TestMsg, create_not_send_channel, setup_test_runtime,
+    setup_virtual_time_runtime,
+};
+use crate::timer::TimerId;
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use otap_df_channel::error::{RecvError, SendError};
+use otap_df_config::NodeKind;
+use serde_json::{Value, json};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket, UnixStream};
+use tokio::sync::{Notify, oneshot, watch};
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tokio_util::codec::Encoder;
+use tokio_util::sync::CancellationToken;
+
+/// Implements both the local and the shared receiver traits for a test receiver, with the same
+/// trait items: within them, `ControlChannel` and `EffectHandler` name the types of each flavor.
+macro_rules! impl_test_receiver {
+    ($receiver:ident { $($items:tt)* }) => {
+        const _: () = {
+            use crate::local::receiver::{ControlChannel, EffectHandler, Receiver};
+
+            #[async_trait::async_trait(?Send)]
+            impl Receiver<TestMsg> for $receiver {
+                $($items)*
+            }
+        };
+
+        const _: () = {
+            use crate::shared::receiver::{ControlChannel, EffectHandler, Receiver};
+
+            #[async_trait::async_trait]
+            impl Receiver<TestMsg> for $receiver {
+                $($items)*
+            }
+        };
+    };
+}
+
+mod buffer_pool;
+mod cancellation;
+mod channels;
+mod config;
+mod connections;
+mod control;
+mod health;
+mod lifecycle;
+mod overflow;
+mod restart;
+mod runtime;
+mod sending;
+mod sockets;
+#[cfg(feature = "tracing-spans")]
+mod spans;
+mod tasks;
+mod timers;
+
+/// Reads a connection until the client closes it.
+async fn read_until_eof(mut socket: impl AsyncRead + Unpin) -> TestMsg {
+    let mut received = String::new();
+    let _ = socket
+        .read_to_string(&mut received)
+        .await
+        .expect("Error reading from connection");
+    TestMsg(received)
+}
+
+/// A receiver counting the control messages it observes until a `Shutdown`.
+struct CountingReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl_test_receiver!(CountingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        _effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            let ctrl_msg = ctrl_msg_recv.recv().await?;
+            self.ctrl_msg_counters.update_with(&ctrl_msg);
+            if ctrl_msg.is_shutdown() {
+                return Ok(());
+            }
+        }
+    }
+});
+
+/// A TCP receiver serving its connections with `serve_connections`, emitting one message per
+/// connection once the client closes it.
+struct ServingReceiver;
+
+impl_test_receiver!(ServingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = effect_handler.tcp_listener(addr)?;
+        effect_handler.notify_ready();
+
+        effect_handler
+            .serve_connections(
+                listener,
+                &mut ctrl_msg_recv,
+                |_ctrl_msg| {},
+                |socket, _peer_addr| {
+                    let effect_handler = effect_handler.clone();
+                    async move {
+                        let msg = read_into_buffer(socket, effect_handler.read_buffer()).await;
+                        effect_handler
+                            .send_message(msg)
+                            .await
+                            .expect("Error sending message via effect handler");
+                    }
+                },
+            )
+            .await
+    }
+});
+
+/// Reads the connection into the given buffer until the client closes it or the buffer is
+/// full.
+async fn read_into_buffer(mut socket: impl AsyncRead + Unpin, mut buf: ReadBuffer) -> TestMsg {
+    let mut len = 0;
+    while len < buf.len() {
+        match socket
+            .read(&mut buf[len..])
+            .await
+            .expect("Error reading from connection")
+        {
+            0 => break,
+            n => len += n,
+        }
+    }
+    TestMsg(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+/// Waits for a receiver to be ready, and returns the address of its listener.
+async fn ready_address(
+    mut state: watch::Receiver<LifecycleState>,
+    bound_addresses: watch::Receiver<Vec<SocketAddr>>,
+) -> SocketAddr {
+    wait_for_state(&mut state, LifecycleState::Ready)
+        .await
+        .expect("The receiver stopped before being ready");
+    bound_addresses.borrow()[0]
+}
+
+/// A receiver that never looks at its control channel, simulating a misbehaving receiver.
+struct StuckReceiver;
+
+impl_test_receiver!(StuckReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        _effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        std::future::pending().await
+    }
+});
+
+fn flaky_config(max_attempts: u32, sink: Arc<InMemoryMetricsSink>) -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("flaky_receiver");
+    config.drain_policy = DrainPolicy::Immediate;
+    config.metrics_sink = Some(sink as Arc<dyn MetricsSink>);
+    config.restart = Some(RetryPolicy {
+        max_attempts,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+    });
+    config
+}
+
+/// A receiver sending two messages with a timeout to a channel with room for only one, and
+/// reporting the outcome of the second send.
+struct TimeoutReceiver {
+    outcome: tokio::sync::mpsc::UnboundedSender<Error<TestMsg>>,
+}
+
+const SEND_TIMEOUT: Duration = Duration::from_millis(50);
+
+impl_test_receiver!(TimeoutReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        effect_handler
+            .send_message_timeout(TestMsg::new("first"), SEND_TIMEOUT)
+            .await?;
+        if let Err(error) = effect_handler
+            .send_message_timeout(TestMsg::new("second"), SEND_TIMEOUT)
+            .await
+        {
+            _ = self.outcome.send(error);
+        }
+        Ok(())
+    }
+});
+
+/// A test receiver sending 4 messages to its output channel without waiting for them to be
+/// consumed, and reporting the number of messages dropped by its effect handler.
+struct OverflowingReceiver {
+    dropped: Arc<AtomicU64>,
+}
+
+impl_test_receiver!(OverflowingReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        for i in 0..4 {
+            effect_handler
+                .send_message(TestMsg::new(i.to_string()))
+                .await?;
+        }
+        self.dropped
+            .store(effect_handler.dropped_messages(), Ordering::Relaxed);
+        Ok(())
+    }
+});
+
+/// A test receiver emitting a message every 10ms until it is asked to shut down.
+struct StreamingReceiver;
+
+impl_test_receiver!(StreamingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        loop {
+            tokio::select! {
+                msg = ctrl_msg_recv.recv() => {
+                    if let ControlMsg::Shutdown { .. } = msg? {
+                        return Ok(());
+                    }
+                }
+                () = sleep(Duration::from_millis(10)) => {
+                    effect_handler.send_message(TestMsg::new("tick")).await?;
+                }
+            }
+        }
+    }
+});
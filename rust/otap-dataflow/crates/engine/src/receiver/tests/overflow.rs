@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Receivers sending to a full output channel.
+
+use super::*;
+
+fn overflow_config(overflow_policy: OverflowPolicy) -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("overflowing_receiver");
+    config.output_pdata_channel.capacity = 2;
+    config.output_pdata_channel.overflow_policy = overflow_policy;
+    // The output channel is only consumed once the receiver completed.
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+/// Runs the receiver to completion and checks the messages left in its output channel.
+fn assert_overflow(
+    mut receiver: ReceiverWrapper<TestMsg>,
+    dropped: &AtomicU64,
+    expected: [&str; 2],
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        timeout(Duration::from_secs(3), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver failed");
+        for content in expected {
+            assert_eq!(
+                pdata_rx.recv().await.expect("Missing pdata"),
+                TestMsg::new(content)
+            );
+        }
+        assert!(pdata_rx.recv().await.is_err());
+    }));
+
+    assert_eq!(dropped.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_overflow_drop_oldest_local() {
+    let dropped = Arc::new(AtomicU64::new(0));
+    let receiver = ReceiverWrapper::local(
+        OverflowingReceiver {
+            dropped: dropped.clone(),
+        },
+        &overflow_config(OverflowPolicy::DropOldest),
+    );
+    assert_overflow(receiver, &dropped, ["2", "3"]);
+}
+
+#[test]
+fn test_overflow_drop_newest_local() {
+    let dropped = Arc::new(AtomicU64::new(0));
+    let receiver = ReceiverWrapper::local(
+        OverflowingReceiver {
+            dropped: dropped.clone(),
+        },
+        &overflow_config(OverflowPolicy::DropNewest),
+    );
+    assert_overflow(receiver, &dropped, ["0", "1"]);
+}
+
+#[test]
+fn test_overflow_drop_newest_shared() {
+    let dropped = Arc::new(AtomicU64::new(0));
+    let receiver = ReceiverWrapper::shared(
+        OverflowingReceiver {
+            dropped: dropped.clone(),
+        },
+        &overflow_config(OverflowPolicy::DropNewest),
+    );
+    assert_overflow(receiver, &dropped, ["0", "1"]);
+}
+
+#[test]
+fn test_overflow_drop_oldest_shared() {
+    // A Tokio channel can't evict a buffered message, the new messages are dropped instead.
+    let dropped = Arc::new(AtomicU64::new(0));
+    let receiver = ReceiverWrapper::shared(
+        OverflowingReceiver {
+            dropped: dropped.clone(),
+        },
+        &overflow_config(OverflowPolicy::DropOldest),
+    );
+    assert_overflow(receiver, &dropped, ["0", "1"]);
+}
+
+#[test]
+fn test_send_lossy_local() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (pdata_tx, mut pdata_rx) = Sender::ring(2);
+    let effect_handler = local::EffectHandler::new("lossy_receiver".into(), pdata_tx);
+
+    rt.block_on(local_tasks.run_until(async move {
+        for i in 0..4 {
+            effect_handler
+                .send_lossy(TestMsg::new(i.to_string()))
+                .expect("Failed to send pdata");
+        }
+        assert_eq!(effect_handler.dropped_messages(), 2);
+        assert_eq!(pdata_rx.recv().await.unwrap(), TestMsg::new("2"));
+        assert_eq!(pdata_rx.recv().await.unwrap(), TestMsg::new("3"));
+    }));
+}
+
+#[test]
+fn test_try_send_message_local() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (pdata_tx, pdata_rx) = create_not_send_channel(1);
+    let effect_handler =
+        local::EffectHandler::new("non_blocking_receiver".into(), Sender::Local(pdata_tx));
+
+    rt.block_on(local_tasks.run_until(async move {
+        effect_handler
+            .try_send_message(TestMsg::new("accepted"))
+            .expect("Failed to send pdata");
+        match effect_handler.try_send_message(TestMsg::new("rejected")) {
+            Err(SendError::Full(msg)) => assert_eq!(msg, TestMsg::new("rejected")),
+            other => panic!("Expected a full channel, got {other:?}"),
+        }
+        assert_eq!(effect_handler.dropped_messages(), 0);
+        assert_eq!(pdata_rx.recv().await.unwrap(), TestMsg::new("accepted"));
+    }));
+}
+
+#[test]
+fn test_try_send_message_shared() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (pdata_tx, mut pdata_rx) = tokio::sync::mpsc::channel(1);
+    let effect_handler = shared::EffectHandler::new("non_blocking_receiver".into(), pdata_tx);
+
+    rt.block_on(local_tasks.run_until(async move {
+        effect_handler
+            .try_send_message(TestMsg::new("accepted"))
+            .expect("Failed to send pdata");
+        match effect_handler.try_send_message(TestMsg::new("rejected")) {
+            Err(SendError::Full(msg)) => assert_eq!(msg, TestMsg::new("rejected")),
+            other => panic!("Expected a full channel, got {other:?}"),
+        }
+        assert_eq!(pdata_rx.recv().await, Some(TestMsg::new("accepted")));
+    }));
+}
+
+/// Buffers a few control and pdata messages without starting the receiver, and checks the
+/// reported channel depths.
+fn assert_channel_depth(receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+
+    rt.block_on(local_tasks.run_until(async move {
+        assert_eq!(receiver.pdata_channel_len(), (0, 8));
+        assert_eq!(receiver.control_channel_len(), (0, 16));
+        for i in 0..5 {
+            control_sender
+                .send(ControlMsg::TimerTick {})
+                .await
+                .expect("Failed to send TimerTick");
+            let msg = TestMsg::new(i.to_string());
+            match &receiver {
+                ReceiverWrapper::Local { effect_handler, .. } => {
+                    effect_handler.try_send_message(msg)
+                }
+                ReceiverWrapper::Shared { effect_handler, .. } => {
+                    effect_handler.try_send_message(msg)
+                }
+            }
+            .expect("Failed to send pdata");
+        }
+        assert_eq!(receiver.pdata_channel_len(), (5, 8));
+        assert_eq!(receiver.control_channel_len(), (5, 16));
+    }));
+}
+
+fn channel_depth_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("buffering_receiver");
+    config.output_pdata_channel.capacity = 8;
+    config.control_channel.capacity = 16;
+    config
+}
+
+#[test]
+fn test_channel_depth_local() {
+    let receiver = ReceiverWrapper::local(
+        CountingReceiver {
+            ctrl_msg_counters: CtrlMsgCounters::new(),
+        },
+        &channel_depth_config(),
+    );
+    assert_channel_depth(receiver);
+}
+
+#[test]
+fn test_channel_depth_shared() {
+    let receiver = ReceiverWrapper::shared(
+        CountingReceiver {
+            ctrl_msg_counters: CtrlMsgCounters::new(),
+        },
+        &channel_depth_config(),
+    );
+    assert_channel_depth(receiver);
+}
@@ -0,0 +1,342 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Restarts of the failed receivers.
+
+use super::*;
+
+/// A test receiver failing on its first instance, and emitting a message on the next ones.
+struct CrashingReceiver {
+    attempt: usize,
+}
+
+impl CrashingReceiver {
+    fn factory() -> impl Fn() -> CrashingReceiver {
+        let attempts = Arc::new(AtomicU64::new(0));
+        move || CrashingReceiver {
+            attempt: usize::try_from(attempts.fetch_add(1, Ordering::Relaxed))
+                .expect("Invalid attempt"),
+        }
+    }
+
+    fn crash(&self) -> Result<(), Error<TestMsg>> {
+        if self.attempt == 0 {
+            return Err(Error::ReceiverError {
+                receiver: "crashing_receiver".into(),
+                error: "crashed".to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl_test_receiver!(CrashingReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        self.crash()?;
+        effect_handler
+            .send_message(TestMsg::new(format!("attempt {}", self.attempt)))
+            .await
+    }
+});
+
+/// Starts the receiver, which fails, then restarts it and checks that the fresh instance
+/// runs successfully.
+fn assert_restart(receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let mut restarted = receiver.restart().expect("Failed to restart");
+        let result = receiver.start().await;
+        assert!(
+            matches!(&result, Err(Error::ReceiverError { error, .. }) if error == "crashed"),
+            "Unexpected result {result:?}"
+        );
+
+        assert_eq!(restarted.name(), "crashing_receiver");
+        assert_eq!(restarted.pdata_channel_len(), (0, 4));
+        let mut pdata_rx = restarted
+            .take_pdata_receiver()
+            .expect("Failed to take the pdata receiver");
+        timeout(Duration::from_secs(1), restarted.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Restarted receiver failed");
+        let received = pdata_rx.recv().await.expect("Message not received");
+        assert_eq!(received, TestMsg::new("attempt 1"));
+    }));
+}
+
+fn crashing_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("crashing_receiver");
+    config.output_pdata_channel.capacity = 4;
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+#[test]
+fn test_restart_local() {
+    assert_restart(ReceiverWrapper::local_restartable(
+        CrashingReceiver::factory(),
+        &crashing_config(),
+    ));
+}
+
+#[test]
+fn test_restart_shared() {
+    assert_restart(ReceiverWrapper::shared_restartable(
+        CrashingReceiver::factory(),
+        &crashing_config(),
+    ));
+}
+
+#[test]
+fn test_restart_requires_a_factory() {
+    let receiver = ReceiverWrapper::local(CrashingReceiver { attempt: 0 }, &crashing_config());
+    assert!(matches!(
+        receiver.restart(),
+        Err(Error::ReceiverError { .. })
+    ));
+}
+
+/// A test receiver failing on its first two instances, and emitting a message then waiting
+/// for the shutdown on the next ones. Every restarted instance first emits the restart attempt
+/// it was notified of.
+struct FlakyReceiver {
+    instance: u32,
+}
+
+impl FlakyReceiver {
+    fn factory() -> impl Fn() -> FlakyReceiver + Send + Sync {
+        let instances = Arc::new(AtomicU64::new(0));
+        move || FlakyReceiver {
+            instance: u32::try_from(instances.fetch_add(1, Ordering::Relaxed))
+                .expect("Invalid instance"),
+        }
+    }
+
+    /// Returns the message announcing the restart, if the control message is one.
+    fn restarting(msg: ControlMsg) -> Option<TestMsg> {
+        match msg {
+            ControlMsg::Restarting { attempt } => {
+                Some(TestMsg::new(format!("restarting {attempt}")))
+            }
+            _ => None,
+        }
+    }
+
+    fn crashed(&self) -> Result<(), Error<TestMsg>> {
+        if self.instance < 2 {
+            return Err(Error::ReceiverError {
+                receiver: "flaky_receiver".into(),
+                error: format!("instance {} crashed", self.instance),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl_test_receiver!(FlakyReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        if self.instance > 0 {
+            let msg = ctrl_msg_recv.recv().await?;
+            if let Some(restarting) = Self::restarting(msg) {
+                effect_handler.send_message(restarting).await?;
+            }
+        }
+        self.crashed()?;
+        effect_handler.send_message(TestMsg::new("running")).await?;
+        while !matches!(ctrl_msg_recv.recv().await?, ControlMsg::Shutdown { .. }) {}
+        Ok(())
+    }
+});
+
+/// Starts the flaky receiver restarted in place, and checks that its third instance runs
+/// after the backoff of the two restarts, sending to the same pdata channel.
+fn assert_restart_in_place(new_wrapper: impl FnOnce(&ReceiverConfig) -> ReceiverWrapper<TestMsg>) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .expect("Failed to create runtime");
+    let sink = Arc::new(InMemoryMetricsSink::new());
+    let mut receiver = new_wrapper(&flaky_config(3, sink.clone()));
+    let control_sender = receiver.control_sender();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(tokio::task::LocalSet::new().run_until(async move {
+        let started = Instant::now();
+        let handle = tokio::task::spawn_local(receiver.start());
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(pdata_rx.recv().await.expect("Message not received"));
+        }
+        assert_eq!(
+            received,
+            ["restarting 1", "restarting 2", "running"].map(TestMsg::new)
+        );
+        // The backoffs of the first and second restarts.
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+        let labels = [
+            (NODE_LABEL, "flaky_receiver"),
+            (ERROR_CODE_LABEL, "NodeFailed"),
+        ];
+        assert_eq!(sink.labeled_counter_value(RESTARTS, &labels), 2);
+    }));
+}
+
+#[test]
+fn test_restart_in_place_local() {
+    assert_restart_in_place(|config| {
+        ReceiverWrapper::local_restartable(FlakyReceiver::factory(), config)
+    });
+}
+
+#[test]
+fn test_restart_in_place_shared() {
+    assert_restart_in_place(|config| {
+        ReceiverWrapper::shared_restartable(FlakyReceiver::factory(), config)
+    });
+}
+
+#[test]
+fn test_restart_in_place_gives_up() {
+    let (rt, local_tasks) = setup_test_runtime();
+    let sink = Arc::new(InMemoryMetricsSink::new());
+    let receiver = ReceiverWrapper::local_restartable(
+        FlakyReceiver::factory(),
+        &flaky_config(1, sink.clone()),
+    );
+
+    rt.block_on(local_tasks.run_until(async move {
+        let result = timeout(Duration::from_secs(1), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver");
+        // The error of the last instance is returned once the policy gives up.
+        assert!(
+            matches!(&result, Err(Error::ReceiverError { error, .. }) if error == "instance 1 crashed"),
+            "Unexpected result {result:?}"
+        );
+        assert_eq!(sink.counter_value(RESTARTS), 1);
+    }));
+}
+
+/// A test receiver always failing, after emitting the restart attempt it was notified of.
+struct RetriedReceiver;
+
+impl RetriedReceiver {
+    fn attempt(msg: Option<ControlMsg>) -> TestMsg {
+        match msg {
+            Some(ControlMsg::Restarting { attempt }) => TestMsg::new(format!("attempt {attempt}")),
+            _ => TestMsg::new("attempt 0"),
+        }
+    }
+
+    fn crashed() -> Error<TestMsg> {
+        Error::ReceiverError {
+            receiver: "retried_receiver".into(),
+            error: "crashed".to_owned(),
+        }
+    }
+}
+
+impl_test_receiver!(RetriedReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let msg = ctrl_msg_recv
+            .recv_timeout(Duration::from_millis(50))
+            .await?;
+        effect_handler.send_message(Self::attempt(msg)).await?;
+        Err(Self::crashed())
+    }
+});
+
+/// Restarts the failing receiver until the retry policy gives up, and checks the backoff
+/// observed and the attempt delivered to each instance.
+fn assert_restart_with_retry(receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let policy = RetryPolicy {
+        max_attempts: 2,
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_secs(1),
+    };
+
+    rt.block_on(local_tasks.run_until(async move {
+        let mut receiver = receiver;
+        let mut attempts = Vec::new();
+        loop {
+            let next = receiver.restart_with_retry(&policy);
+            let mut pdata_rx = receiver
+                .take_pdata_receiver()
+                .expect("Failed to take the pdata receiver");
+            let started = Instant::now();
+            let result = timeout(Duration::from_secs(5), receiver.start())
+                .await
+                .expect("Timed out waiting for the receiver");
+            assert!(matches!(result, Err(Error::ReceiverError { .. })));
+            let received = pdata_rx.recv().await.expect("Message not received");
+            attempts.push((received, started.elapsed()));
+
+            match next {
+                Ok(next) => receiver = next,
+                Err(error) => {
+                    assert!(matches!(error, Error::ReceiverError { .. }));
+                    break;
+                }
+            }
+        }
+
+        let received: Vec<_> = attempts.iter().map(|(msg, _)| msg.clone()).collect();
+        assert_eq!(
+            received,
+            ["attempt 0", "attempt 1", "attempt 2"].map(TestMsg::new)
+        );
+        assert!(attempts[1].1 >= Duration::from_millis(50));
+        assert!(attempts[2].1 >= Duration::from_millis(100));
+    }));
+}
+
+fn retried_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("retried_receiver");
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+#[test]
+fn test_restart_with_retry_local() {
+    assert_restart_with_retry(ReceiverWrapper::local_restartable(
+        || RetriedReceiver,
+        &retried_config(),
+    ));
+}
+
+#[test]
+fn test_restart_with_retry_shared() {
+    assert_restart_with_retry(ReceiverWrapper::shared_restartable(
+        || RetriedReceiver,
+        &retried_config(),
+    ));
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runtimes running the receivers.
+
+use super::*;
+
+/// A receiver emitting the name of the thread it runs on, and then waiting for a shutdown.
+struct ThreadNameReceiver;
+
+#[async_trait]
+impl shared::Receiver<TestMsg> for ThreadNameReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: shared::ControlChannel,
+        effect_handler: shared::EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let thread_name = std::thread::current().name().map(ToOwned::to_owned);
+        effect_handler
+            .send_message(TestMsg(thread_name.unwrap_or_default()))
+            .await?;
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl local::Receiver<TestMsg> for ThreadNameReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: local::ControlChannel,
+        _effect_handler: local::EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        Ok(())
+    }
+}
+
+/// A shared receiver started on a dedicated runtime runs on the threads of that runtime, while
+/// its messages are consumed from the current runtime.
+#[test]
+fn test_start_on_dedicated_runtime() {
+    let dedicated_rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("dedicated-receiver")
+        .enable_all()
+        .build()
+        .expect("Failed to create the dedicated runtime");
+    let (rt, local_tasks) = setup_test_runtime();
+    let config = ReceiverConfig::new("dedicated_receiver");
+    let mut receiver = ReceiverWrapper::shared(ThreadNameReceiver, &config);
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let control_sender = receiver.control_sender();
+    let handle = receiver
+        .start_on(dedicated_rt.handle().clone())
+        .expect("Failed to start the shared receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let received = timeout(Duration::from_secs(3), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for message")
+            .expect("No message received");
+        assert_eq!(received, TestMsg::new("dedicated-receiver"));
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        timeout(Duration::from_secs(3), handle)
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+
+    // Local receivers are bound to the thread of their `LocalSet`.
+    let local_receiver = ReceiverWrapper::local(ThreadNameReceiver, &config);
+    assert!(matches!(
+        local_receiver.start_on(dedicated_rt.handle().clone()),
+        Err(Error::ReceiverError { .. })
+    ));
+}
+
+/// The capacities chosen for the test runtime apply to the channels of the receiver.
+#[test]
+fn test_runtime_with_capacity() {
+    let test_runtime = TestRuntime::with_capacity(4, 1);
+    assert_eq!(test_runtime.config().control_channel.capacity, 4);
+    assert_eq!(test_runtime.config().output_pdata_channel.capacity, 1);
+
+    let (outcome_tx, mut outcome_rx) = tokio::sync::mpsc::unbounded_channel();
+    let receiver = ReceiverWrapper::local(
+        TimeoutReceiver {
+            outcome: outcome_tx,
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(|_ctx| async {})
+        .run_validation(|mut ctx| async move {
+            // The second message doesn't fit in the output channel.
+            assert!(matches!(
+                outcome_rx.recv().await,
+                Some(Error::SendTimeout { .. })
+            ));
+            assert_eq!(ctx.recv().await.unwrap(), TestMsg::new("first"));
+        });
+}
@@ -0,0 +1,485 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Messages sent by the receivers to their outputs.
+
+use super::*;
+
+/// A test receiver fanning out its messages to its output ports, then sending one to a port it
+/// doesn't have.
+struct FanOutReceiver;
+
+impl FanOutReceiver {
+    fn check_unknown_port(result: Result<(), Error<TestMsg>>) {
+        assert!(
+            matches!(&result, Err(Error::UnknownOutPort { node, port }) if node == "fan_out_receiver" && port == "traces"),
+            "Unexpected result {result:?}"
+        );
+    }
+}
+
+impl_test_receiver!(FanOutReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        for i in 0..2 {
+            effect_handler
+                .send_message_to("metrics", TestMsg::new(format!("metric {i}")))
+                .await?;
+            effect_handler
+                .send_message_to("logs", TestMsg::new(format!("log {i}")))
+                .await?;
+        }
+        Self::check_unknown_port(
+            effect_handler
+                .send_message_to("traces", TestMsg::new("span"))
+                .await,
+        );
+        Ok(())
+    }
+});
+
+/// Checks that each output port only receives the messages sent to it.
+fn assert_fan_out(receiver: ReceiverWrapper<TestMsg>) {
+    assert_routed(receiver.with_outputs(&["metrics", "logs"]));
+}
+
+/// Checks that each output port of a receiver having the `metrics` and `logs` ports only
+/// receives the messages sent to it.
+fn assert_routed(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut metrics_rx = receiver
+        .take_pdata_receiver_for("metrics")
+        .expect("Missing metrics port");
+    let mut logs_rx = receiver
+        .take_pdata_receiver_for("logs")
+        .expect("Missing logs port");
+    assert!(receiver.take_pdata_receiver_for("logs").is_none());
+    assert!(receiver.take_pdata_receiver_for("traces").is_none());
+
+    rt.block_on(local_tasks.run_until(async move {
+        timeout(Duration::from_secs(1), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver failed");
+
+        for (rx, expected) in [
+            (&mut metrics_rx, ["metric 0", "metric 1"]),
+            (&mut logs_rx, ["log 0", "log 1"]),
+        ] {
+            for expected in expected {
+                let received = rx.recv().await.expect("Message not received");
+                assert_eq!(received, TestMsg::new(expected));
+            }
+            assert!(rx.recv().await.is_err());
+        }
+    }));
+}
+
+fn fan_out_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("fan_out_receiver");
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+#[test]
+fn test_out_ports_local() {
+    assert_fan_out(ReceiverWrapper::local(FanOutReceiver, &fan_out_config()));
+}
+
+#[test]
+fn test_out_ports_shared() {
+    assert_fan_out(ReceiverWrapper::shared(FanOutReceiver, &fan_out_config()));
+}
+
+fn configured_ports_config() -> ReceiverConfig {
+    let mut config = fan_out_config();
+    config.out_ports = vec!["metrics".to_owned(), "logs".to_owned()];
+    config
+}
+
+#[test]
+fn test_configured_out_ports_local() {
+    let receiver =
+        ReceiverWrapper::local_restartable(|| FanOutReceiver, &configured_ports_config());
+    // The configured ports are preserved by a restart.
+    assert_routed(receiver.restart().expect("Failed to restart"));
+}
+
+#[test]
+fn test_configured_out_ports_shared() {
+    let receiver =
+        ReceiverWrapper::shared_restartable(|| FanOutReceiver, &configured_ports_config());
+    // The configured ports are preserved by a restart.
+    assert_routed(receiver.restart().expect("Failed to restart"));
+}
+
+/// A test receiver sending a number of messages to its output channel, either in a single
+/// batch or one by one.
+struct BulkReceiver {
+    count: usize,
+    batched: bool,
+}
+
+impl BulkReceiver {
+    fn messages(&self) -> Vec<TestMsg> {
+        (0..self.count).map(|i| TestMsg(i.to_string())).collect()
+    }
+}
+
+impl_test_receiver!(BulkReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        if self.batched {
+            return effect_handler.send_message_batch(self.messages()).await;
+        }
+        for data in self.messages() {
+            effect_handler.send_message(data).await?;
+        }
+        Ok(())
+    }
+});
+
+fn bulk_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("bulk_receiver");
+    config.output_pdata_channel.capacity = 4;
+    config.drain_policy = DrainPolicy::Immediate;
+    config
+}
+
+/// Runs the receiver while a downstream task consumes its output channel, checks that all
+/// the messages are delivered in order, and returns the time taken by the receiver.
+fn time_delivery(mut receiver: ReceiverWrapper<TestMsg>, count: usize) -> Duration {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let downstream = tokio::task::spawn_local(async move {
+            let mut received = 0;
+            while let Ok(TestMsg(data)) = pdata_rx.recv().await {
+                assert_eq!(data, received.to_string());
+                received += 1;
+            }
+            received
+        });
+        let started = Instant::now();
+        timeout(Duration::from_secs(10), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver failed");
+        let elapsed = started.elapsed();
+        assert_eq!(downstream.await.expect("Downstream task panicked"), count);
+        elapsed
+    }))
+}
+
+/// Compares the delivery of 100k messages sent in a batch and one by one. The timings are
+/// only reported (see `RUST_LOG`), asserting on them would make the test flaky.
+#[test]
+fn test_send_message_batch_throughput() {
+    const COUNT: usize = 100_000;
+    let mut config = bulk_config();
+    config.output_pdata_channel.capacity = 256;
+    let new_receiver = |batched| BulkReceiver {
+        count: COUNT,
+        batched,
+    };
+
+    let local_looped = time_delivery(ReceiverWrapper::local(new_receiver(false), &config), COUNT);
+    let local_batched = time_delivery(ReceiverWrapper::local(new_receiver(true), &config), COUNT);
+    let shared_looped = time_delivery(ReceiverWrapper::shared(new_receiver(false), &config), COUNT);
+    let shared_batched = time_delivery(ReceiverWrapper::shared(new_receiver(true), &config), COUNT);
+    tracing::info!(
+        ?local_looped,
+        ?local_batched,
+        ?shared_looped,
+        ?shared_batched,
+        "Delivered {COUNT} messages"
+    );
+}
+
+/// Closes the output channel of the receiver while it waits for some capacity in the middle
+/// of a batch, and checks the number of messages reported as accepted.
+fn assert_batch_interrupted(mut receiver: ReceiverWrapper<TestMsg>) {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        drop(pdata_rx);
+
+        let result = timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver task panicked");
+        assert!(
+            matches!(
+                &result,
+                Err(Error::PdataBatchInterrupted { node, accepted: 4 })
+                    if node == "bulk_receiver"
+            ),
+            "Unexpected result {result:?}"
+        );
+    }));
+}
+
+#[test]
+fn test_send_message_batch_interrupted_local() {
+    assert_batch_interrupted(ReceiverWrapper::local(
+        BulkReceiver {
+            count: 10,
+            batched: true,
+        },
+        &bulk_config(),
+    ));
+}
+
+#[test]
+fn test_send_message_batch_interrupted_shared() {
+    assert_batch_interrupted(ReceiverWrapper::shared(
+        BulkReceiver {
+            count: 10,
+            batched: true,
+        },
+        &bulk_config(),
+    ));
+}
+
+/// A test receiver sending 3 messages, and a 4th one once its output channel is closed, then
+/// reporting its send metrics.
+struct MeteredReceiver {
+    channel_closed: oneshot::Receiver<()>,
+    metrics: oneshot::Sender<ReceiverMetrics>,
+}
+
+impl_test_receiver!(MeteredReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        for data in ["a", "bb", "ccc"] {
+            effect_handler.send_message(TestMsg::new(data)).await?;
+        }
+        self.channel_closed.await.expect("Test ended");
+        assert!(
+            effect_handler
+                .send_message(TestMsg::new("dddd"))
+                .await
+                .is_err()
+        );
+        _ = self.metrics.send(effect_handler.metrics());
+        Ok(())
+    }
+});
+
+fn assert_metrics(
+    new_wrapper: impl FnOnce(MeteredReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (closed_tx, closed_rx) = oneshot::channel();
+    let (metrics_tx, metrics_rx) = oneshot::channel();
+    let sink = Arc::new(InMemoryMetricsSink::new());
+    let mut config = ReceiverConfig::new("metered_receiver");
+    config.drain_policy = DrainPolicy::Immediate;
+    config.metrics_sink = Some(sink.clone() as Arc<dyn MetricsSink>);
+    let mut receiver = new_wrapper(
+        MeteredReceiver {
+            channel_closed: closed_rx,
+            metrics: metrics_tx,
+        },
+        &config,
+    );
+    receiver.set_pdata_size(|msg: &TestMsg| msg.0.len());
+    assert_eq!(receiver.metrics(), ReceiverMetrics::default());
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        for _ in 0..3 {
+            _ = pdata_rx.recv().await.expect("Message not received");
+        }
+        drop(pdata_rx);
+        closed_tx.send(()).expect("Receiver ended");
+
+        let metrics = metrics_rx.await.expect("Metrics not reported");
+        assert_eq!(
+            metrics,
+            ReceiverMetrics {
+                messages_sent: 3,
+                bytes_sent: 6,
+                send_errors: 1,
+            }
+        );
+        assert_eq!(
+            metrics.to_string(),
+            "messages_sent=3 bytes_sent=6 send_errors=1"
+        );
+        // The counters pushed to the sink match the `send_message` calls.
+        let labels = [(NODE_LABEL, "metered_receiver")];
+        assert_eq!(sink.labeled_counter_value(MESSAGES_SENT, &labels), 3);
+        assert_eq!(sink.labeled_counter_value(BYTES_SENT, &labels), 6);
+        assert_eq!(sink.labeled_counter_value(SEND_ERRORS, &labels), 1);
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+    }));
+}
+
+#[test]
+fn test_metrics_local() {
+    assert_metrics(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_metrics_shared() {
+    assert_metrics(ReceiverWrapper::shared);
+}
+
+/// A test receiver sending 3 messages of 1MB, and reporting each completed send.
+struct LargePayloadReceiver {
+    sent: tokio::sync::mpsc::UnboundedSender<usize>,
+}
+
+const MB: usize = 1024 * 1024;
+
+impl_test_receiver!(LargePayloadReceiver {
+    async fn start(
+        self: Box<Self>,
+        _ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        for i in 0..3 {
+            effect_handler
+                .send_message(TestMsg::new("x".repeat(MB)))
+                .await?;
+            _ = self.sent.send(i);
+        }
+        Ok(())
+    }
+});
+
+fn assert_inflight_budget(
+    new_wrapper: impl FnOnce(LargePayloadReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (sent_tx, mut sent_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut config = ReceiverConfig::new("large_payload_receiver");
+    config.drain_policy = DrainPolicy::Immediate;
+    config.max_inflight_bytes = Some(2 * MB);
+    let mut receiver = new_wrapper(LargePayloadReceiver { sent: sent_tx }, &config);
+    receiver.set_pdata_size(|msg: &TestMsg| msg.0.len());
+    let mut pdata_rx = receiver
+        .take_budgeted_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        assert_eq!(sent_rx.recv().await, Some(0));
+        assert_eq!(sent_rx.recv().await, Some(1));
+
+        // The channel has room for the third message, but the byte budget doesn't.
+        assert!(
+            timeout(Duration::from_millis(100), sent_rx.recv())
+                .await
+                .is_err()
+        );
+        assert_eq!(pdata_rx.inflight_bytes(), 2 * MB);
+
+        // Consuming a message refunds its size and unblocks the third send.
+        _ = pdata_rx.recv().await.expect("Message not received");
+        assert_eq!(sent_rx.recv().await, Some(2));
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+
+        for _ in 0..2 {
+            _ = pdata_rx.recv().await.expect("Message not received");
+        }
+        assert_eq!(pdata_rx.inflight_bytes(), 0);
+    }));
+}
+
+#[test]
+fn test_inflight_budget_local() {
+    assert_inflight_budget(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_inflight_budget_shared() {
+    assert_inflight_budget(ReceiverWrapper::shared);
+}
+
+fn assert_send_timeout(
+    new_wrapper: impl FnOnce(TimeoutReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let (outcome_tx, mut outcome_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut config = ReceiverConfig::new("timeout_receiver");
+    config.drain_policy = DrainPolicy::Immediate;
+    config.output_pdata_channel.capacity = 1;
+    let mut receiver = new_wrapper(
+        TimeoutReceiver {
+            outcome: outcome_tx,
+        },
+        &config,
+    );
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+
+        // Nobody consumes the first message, the second send times out.
+        let error = timeout(Duration::from_secs(3), outcome_rx.recv())
+            .await
+            .expect("Timed out waiting for the send timeout")
+            .expect("Receiver terminated without reporting the send timeout");
+        match error {
+            Error::SendTimeout {
+                node,
+                timeout,
+                message,
+            } => {
+                assert_eq!(node, "timeout_receiver");
+                assert_eq!(timeout, SEND_TIMEOUT);
+                assert_eq!(message, TestMsg::new("second"));
+            }
+            error => panic!("Unexpected error: {error}"),
+        }
+        handle
+            .await
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+
+        assert_eq!(
+            pdata_rx.recv().await.expect("Message not received"),
+            TestMsg::new("first")
+        );
+    }));
+}
+
+#[test]
+fn test_send_timeout_local() {
+    assert_send_timeout(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_send_timeout_shared() {
+    assert_send_timeout(ReceiverWrapper::shared);
+}
@@ -0,0 +1,621 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Receivers serving TCP connections, UDP datagrams and Unix domain sockets.
+
+use super::*;
+
+/// A test receiver that counts message events.
+/// Works with any type of receiver !Send or Send.
+pub struct TestReceiver {
+    /// Counter for different message types
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl TestReceiver {
+    /// Creates a new test node
+    pub fn new(ctrl_msg_counters: CtrlMsgCounters) -> Self {
+        TestReceiver { ctrl_msg_counters }
+    }
+}
+
+impl_test_receiver!(TestReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        // Bind to an ephemeral port.
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        // The test waits for the bound address via `TestContext::bound_address`.
+        let listener = effect_handler.tcp_listener(addr)?;
+
+        let counters = self.ctrl_msg_counters;
+        effect_handler
+            .serve_connections(
+                listener,
+                &mut ctrl_msg_recv,
+                |ctrl_msg| counters.update_with(ctrl_msg),
+                |mut socket, peer_addr| {
+                    // Clone the effect handler so the connection task can send messages.
+                    let effect_handler = effect_handler.clone();
+                    async move {
+                        let mut buf = effect_handler.read_buffer();
+                        loop {
+                            match socket.read(&mut buf).await {
+                                Ok(0) => break,
+                                Ok(n) => {
+                                    let received =
+                                        String::from_utf8_lossy(&buf[..n]).to_string();
+                                    // Create a TestMsg from the received data and send it.
+                                    if let Err(e) =
+                                        effect_handler.send_message(TestMsg(received)).await
+                                    {
+                                        panic!("Error sending message via effect handler: {e}");
+                                    }
+                                    // Echo back an acknowledgment.
+                                    let _ = socket.write_all(b"ack").await;
+                                }
+                                Err(e) => panic!("Error reading from {peer_addr}: {e}"),
+                            }
+                        }
+                    }
+                },
+            )
+            .await
+    }
+});
+
+/// A test receiver that reads datagrams from a UDP socket.
+/// Works with any type of receiver !Send or Send.
+pub struct TestUdpReceiver {
+    /// Counter for different message types
+    ctrl_msg_counters: CtrlMsgCounters,
+}
+
+impl TestUdpReceiver {
+    /// Creates a new test node
+    pub fn new(ctrl_msg_counters: CtrlMsgCounters) -> Self {
+        TestUdpReceiver { ctrl_msg_counters }
+    }
+}
+
+impl_test_receiver!(TestUdpReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        // Bind to an ephemeral port.
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        // The test waits for the bound address via `TestContext::bound_address`.
+        let socket = effect_handler.udp_socket(addr)?;
+        assert_eq!(
+            effect_handler.bound_addresses(),
+            [socket.local_addr().unwrap()]
+        );
+
+        let mut buf = [0u8; 1024];
+        loop {
+            tokio::select! {
+                biased;
+
+                // Process incoming control messages.
+                ctrl_msg = ctrl_msg_recv.recv() => {
+                    let ctrl_msg = ctrl_msg?;
+                    self.ctrl_msg_counters.update_with(&ctrl_msg);
+                    if ctrl_msg.is_shutdown() {
+                        break;
+                    }
+                }
+
+                // Process incoming datagrams.
+                recv_result = socket.recv_from(&mut buf) => {
+                    let (n, _peer_addr) = recv_result.expect("Error receiving datagram");
+                    let received = String::from_utf8_lossy(&buf[..n]).to_string();
+                    effect_handler.send_message(TestMsg(received)).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+});
+
+/// A test receiver emitting one message per connection, once the client closed it.
+/// Works with any type of receiver !Send or Send.
+pub struct TestDrainingReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+    port_notifier: oneshot::Sender<SocketAddr>,
+}
+
+impl_test_receiver!(TestDrainingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = effect_handler.tcp_listener(addr)?;
+        let _ = self.port_notifier.send(listener.local_addr().unwrap());
+
+        let deadline = loop {
+            tokio::select! {
+                biased;
+
+                ctrl_msg = ctrl_msg_recv.recv() => {
+                    let ctrl_msg = ctrl_msg?;
+                    self.ctrl_msg_counters.update_with(&ctrl_msg);
+                    if let ControlMsg::Shutdown { deadline, .. } = ctrl_msg {
+                        break deadline;
+                    }
+                }
+
+                accept_result = listener.accept() => {
+                    let (socket, _peer_addr) = accept_result.expect("Error accepting connection");
+                    let connection_handler = effect_handler.clone();
+                    effect_handler.spawn_connection(async move {
+                        let msg = read_until_eof(socket).await;
+                        connection_handler
+                            .send_message(msg)
+                            .await
+                            .expect("Error sending message via effect handler");
+                    });
+                }
+            }
+        };
+
+        // Stop accepting new connections and let the in-flight ones complete.
+        drop(listener);
+        effect_handler.drain_connections(deadline).await;
+        Ok(())
+    }
+});
+
+/// Test closure sending a message in two parts, the second one after the shutdown of the
+/// receiver.
+fn slow_client_scenario(
+    port_rx: oneshot::Receiver<SocketAddr>,
+) -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |ctx| {
+        Box::pin(async move {
+            let addr: SocketAddr = port_rx.await.expect("Failed to receive listening address");
+            let mut stream = TcpStream::connect(addr)
+                .await
+                .expect("Failed to connect to receiver");
+            stream
+                .write_all(b"Hello from ")
+                .await
+                .expect("Failed to send data");
+            ctx.sleep(Duration::from_millis(50)).await;
+
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+            ctx.sleep(Duration::from_millis(50)).await;
+
+            stream
+                .write_all(b"a slow client")
+                .await
+                .expect("Failed to send data");
+            let _ = stream.shutdown().await;
+        })
+    }
+}
+
+/// Validation closure checking that the in-flight message was not lost by the shutdown.
+fn slow_client_validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            let received = timeout(Duration::from_secs(3), ctx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+
+            assert!(matches!(received, TestMsg(msg) if msg == "Hello from a slow client"));
+            ctx.counters().assert(0, 0, 0, 1, 0, 0);
+        })
+    }
+}
+
+/// Test closure that simulates a typical receiver scenario.
+fn scenario() -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |ctx| {
+        Box::pin(async move {
+            // Wait for the receiver to bind its listener.
+            let addr = ctx
+                .bound_address()
+                .await
+                .expect("Receiver terminated before binding its listener");
+
+            // Connect to the receiver's socket.
+            let mut stream = TcpStream::connect(addr)
+                .await
+                .expect("Failed to connect to receiver");
+
+            // Send some test data.
+            stream
+                .write_all(b"Hello from test client")
+                .await
+                .expect("Failed to send data");
+
+            // Optionally, read an echo (acknowledgment) from the receiver.
+            let mut buf = [0u8; 1024];
+            let len = stream
+                .read(&mut buf)
+                .await
+                .expect("Failed to read response");
+            assert_eq!(&buf[..len], b"ack", "Expected acknowledgment from receiver");
+
+            // The wrapper answers on behalf of the receiver, which ignores the request.
+            let telemetry = ctx
+                .collect_telemetry()
+                .await
+                .expect("Failed to collect telemetry");
+            assert_eq!(telemetry.messages_sent, 1);
+            assert_eq!(telemetry.messages_dropped, 0);
+            assert_eq!(telemetry.errors, 0);
+
+            // Send a few TimerTick events from the test.
+            for _ in 0..3 {
+                ctx.send_timer_tick()
+                    .await
+                    .expect("Failed to send TimerTick");
+                ctx.sleep(Duration::from_millis(100)).await;
+            }
+
+            ctx.send_config(NodeConfigUpdate::for_all(1, Value::Null))
+                .await
+                .expect("Failed to send config");
+            // Shutdown preempts the pending control messages, let the receiver process the config.
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            // Finally, send a Shutdown event to terminate the receiver.
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+
+            // Close the TCP connection.
+            let _ = stream.shutdown().await;
+        })
+    }
+}
+
+/// Validation closure that checks the received message and counters (!Send context).
+fn validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            let received = timeout(Duration::from_secs(3), ctx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+
+            // Assert that the message received is what the test client sent.
+            assert!(matches!(received, TestMsg(msg) if msg == "Hello from test client"));
+            ctx.counters().assert(3, 0, 1, 1, 0, 0);
+        })
+    }
+}
+
+/// Validation closure that checks the received message and counters (Send context).
+fn shared_validation_procedure()
+-> impl FnOnce(SendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    |mut ctx| {
+        Box::pin(async move {
+            let received = timeout(Duration::from_secs(3), ctx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+
+            // Assert that the message received is what the test client sent.
+            assert!(matches!(received, TestMsg(msg) if msg == "Hello from test client"));
+            ctx.counters().assert(3, 0, 1, 1, 0, 0);
+        })
+    }
+}
+
+/// Test closure that sends a single datagram to the UDP receiver.
+fn udp_scenario() -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |ctx| {
+        Box::pin(async move {
+            // Wait for the receiver to bind its socket.
+            let addr = ctx
+                .bound_address()
+                .await
+                .expect("Receiver terminated before binding its socket");
+
+            let client = UdpSocket::bind("127.0.0.1:0")
+                .await
+                .expect("Failed to bind client socket");
+            let _ = client
+                .send_to(b"Hello from UDP client", addr)
+                .await
+                .expect("Failed to send datagram");
+
+            // Give the receiver a chance to read the datagram before any control message.
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            ctx.send_timer_tick()
+                .await
+                .expect("Failed to send TimerTick");
+            // Shutdown preempts the pending control messages, let the receiver process the tick.
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            // Finally, send a Shutdown event to terminate the receiver.
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+/// Validation closure that checks the decoded datagram and counters (!Send context).
+fn udp_validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            let received = timeout(Duration::from_secs(3), ctx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+
+            assert!(matches!(received, TestMsg(msg) if msg == "Hello from UDP client"));
+            ctx.counters().assert(1, 0, 0, 1, 0, 0);
+        })
+    }
+}
+
+/// Test for the receiver in a `!Send` implementation.
+#[test]
+fn test_receiver_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let receiver = ReceiverWrapper::local(
+        TestReceiver::new(test_runtime.counters()),
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(scenario())
+        .run_validation(validation_procedure());
+}
+
+/// Test the receiver with a shared (Send) implementation.
+#[test]
+fn test_receiver_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let receiver = ReceiverWrapper::shared(
+        TestReceiver::new(test_runtime.counters()),
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(scenario())
+        .run_validation_shared(shared_validation_procedure());
+}
+
+/// Test the receiver with a shared (Send) implementation run in a local context.
+#[test]
+fn test_receiver_local_from_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let receiver = ReceiverWrapper::local_from_shared(
+        TestReceiver::new(test_runtime.counters()),
+        test_runtime.config(),
+    );
+    assert!(matches!(receiver, ReceiverWrapper::Local { .. }));
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(scenario())
+        .run_validation(validation_procedure());
+}
+
+/// Test for a UDP receiver in a `!Send` implementation.
+#[test]
+fn test_udp_receiver_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let receiver = ReceiverWrapper::local(
+        TestUdpReceiver::new(test_runtime.counters()),
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(udp_scenario())
+        .run_validation(udp_validation_procedure());
+}
+
+/// Test for a UDP receiver with a shared (Send) implementation.
+#[test]
+fn test_udp_receiver_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let receiver = ReceiverWrapper::shared(
+        TestUdpReceiver::new(test_runtime.counters()),
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(udp_scenario())
+        .run_validation(udp_validation_procedure());
+}
+
+/// Test for a UDP receiver with a shared (Send) implementation run in a local context.
+#[test]
+fn test_udp_receiver_local_from_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let receiver = ReceiverWrapper::local_from_shared(
+        TestUdpReceiver::new(test_runtime.counters()),
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(udp_scenario())
+        .run_validation(udp_validation_procedure());
+}
+
+/// Test that a `!Send` receiver drains its in-flight connections on shutdown.
+#[test]
+fn test_connection_draining_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let (port_tx, port_rx) = oneshot::channel();
+    let receiver = ReceiverWrapper::local(
+        TestDrainingReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+            port_notifier: port_tx,
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(slow_client_scenario(port_rx))
+        .run_validation(slow_client_validation_procedure());
+}
+
+/// Test that a shared (Send) receiver drains its in-flight connections on shutdown.
+#[test]
+fn test_connection_draining_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+
+    let (port_tx, port_rx) = oneshot::channel();
+    let receiver = ReceiverWrapper::shared(
+        TestDrainingReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+            port_notifier: port_tx,
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(slow_client_scenario(port_rx))
+        .run_validation(slow_client_validation_procedure());
+}
+
+/// A test receiver reading one message per connection to its Unix domain socket.
+struct TestUnixReceiver {
+    ctrl_msg_counters: CtrlMsgCounters,
+    path: PathBuf,
+    ready: oneshot::Sender<()>,
+}
+
+impl_test_receiver!(TestUnixReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let listener = effect_handler.unix_listener(&self.path)?;
+        let _ = self.ready.send(());
+
+        loop {
+            tokio::select! {
+                biased;
+
+                ctrl_msg = ctrl_msg_recv.recv() => {
+                    let ctrl_msg = ctrl_msg?;
+                    self.ctrl_msg_counters.update_with(&ctrl_msg);
+                    if ctrl_msg.is_shutdown() {
+                        return Ok(());
+                    }
+                }
+
+                accept_result = listener.accept() => {
+                    let (socket, _peer_addr) = accept_result.expect("Error accepting connection");
+                    effect_handler.send_message(read_until_eof(socket).await).await?;
+                }
+            }
+        }
+    }
+});
+
+/// Test closure sending a message over the Unix domain socket of the receiver.
+fn unix_scenario(
+    path: PathBuf,
+    ready: oneshot::Receiver<()>,
+) -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |ctx| {
+        Box::pin(async move {
+            ready.await.expect("Receiver did not bind its socket");
+            let mut stream = UnixStream::connect(&path)
+                .await
+                .expect("Failed to connect to receiver");
+            stream
+                .write_all(b"Hello over a Unix socket")
+                .await
+                .expect("Failed to send data");
+            stream.shutdown().await.expect("Failed to close the stream");
+            // Give the receiver a chance to read the message before the shutdown.
+            ctx.sleep(Duration::from_millis(100)).await;
+
+            ctx.send_shutdown(Duration::from_millis(200), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+/// Validation closure checking the received message, and that the socket file was removed
+/// once the receiver completed.
+fn unix_validation_procedure(
+    path: PathBuf,
+) -> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |mut ctx| {
+        Box::pin(async move {
+            let received = timeout(Duration::from_secs(3), ctx.recv())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("No message received");
+            assert_eq!(received, TestMsg::new("Hello over a Unix socket"));
+            ctx.counters().assert(0, 0, 0, 1, 0, 0);
+            assert!(!path.exists(), "The socket file was not removed");
+        })
+    }
+}
+
+fn run_unix_receiver_test(
+    socket_dir: &Path,
+    new_receiver: impl FnOnce(TestUnixReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let path = socket_dir.join("receiver.sock");
+    // A socket file left behind by a previous run is replaced.
+    drop(std::os::unix::net::UnixListener::bind(&path).expect("Failed to bind"));
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let receiver = new_receiver(
+        TestUnixReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+            path: path.clone(),
+            ready: ready_tx,
+        },
+        test_runtime.config(),
+    );
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(unix_scenario(path.clone(), ready_rx))
+        .run_validation(unix_validation_procedure(path));
+}
+
+#[test]
+fn test_unix_receiver_local() {
+    let socket_dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+    run_unix_receiver_test(socket_dir.path(), ReceiverWrapper::local);
+}
+
+#[test]
+fn test_unix_receiver_shared() {
+    let socket_dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+    run_unix_receiver_test(socket_dir.path(), ReceiverWrapper::shared);
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracing spans covering the lifecycle of the receivers.
+
+use super::*;
+
+/// A span recorded by the [`SpanRecorder`].
+#[derive(Clone, Debug)]
+struct RecordedSpan {
+    name: &'static str,
+    fields: std::collections::HashMap<&'static str, String>,
+    follows_from: Vec<u64>,
+}
+
+/// A tracing subscriber recording the spans created, the id of a span being its index + 1.
+#[derive(Clone, Default)]
+struct SpanRecorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+}
+
+impl SpanRecorder {
+    fn spans_named(&self, name: &str) -> Vec<RecordedSpan> {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| span.name == name)
+            .cloned()
+            .collect()
+    }
+}
+
+struct FieldRecorder<'a>(&'a mut std::collections::HashMap<&'static str, String>);
+
+impl tracing::field::Visit for FieldRecorder<'_> {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        _ = self.0.insert(field.name(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        _ = self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+impl tracing::Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut fields = std::collections::HashMap::new();
+        attrs.record(&mut FieldRecorder(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(RecordedSpan {
+            name: attrs.metadata().name(),
+            fields,
+            follows_from: Vec::new(),
+        });
+        tracing::span::Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let span = &mut spans[span.into_u64() as usize - 1];
+        values.record(&mut FieldRecorder(&mut span.fields));
+    }
+
+    fn record_follows_from(&self, span: &tracing::span::Id, follows: &tracing::span::Id) {
+        self.spans.lock().unwrap()[span.into_u64() as usize - 1]
+            .follows_from
+            .push(follows.into_u64());
+    }
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+/// A test receiver sending a message carrying the id of its trace context, then waiting for
+/// the `Shutdown`.
+struct TracedReceiver {
+    trace_context: u64,
+}
+
+impl_test_receiver!(TracedReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        effect_handler
+            .send_message(TestMsg::new(self.trace_context.to_string()))
+            .await?;
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        Ok(())
+    }
+});
+
+fn assert_lifecycle_spans(
+    new_wrapper: impl FnOnce(TracedReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let recorder = SpanRecorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let (rt, local_tasks) = setup_test_runtime();
+        let request = tracing::info_span!("request");
+        let trace_context = request.id().expect("Span disabled").into_u64();
+        let mut receiver = new_wrapper(
+            TracedReceiver { trace_context },
+            &ReceiverConfig::new("traced_receiver"),
+        );
+        receiver.set_pdata_trace_context(|msg: &TestMsg| {
+            msg.0.parse().ok().map(tracing::span::Id::from_u64)
+        });
+        let mut pdata_rx = receiver
+            .take_pdata_receiver()
+            .expect("Failed to take the pdata receiver");
+        let control_sender = receiver.control_sender();
+
+        rt.block_on(local_tasks.run_until(async move {
+            let handle = tokio::task::spawn_local(receiver.start());
+            _ = pdata_rx.recv().await.expect("Message not received");
+            control_sender
+                .send(ControlMsg::Shutdown {
+                    deadline: Duration::from_millis(100),
+                    reason: "Test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+            handle
+                .await
+                .expect("Receiver task panicked")
+                .expect("Receiver failed");
+        }));
+
+        let receiver_spans = recorder.spans_named("receiver");
+        assert_eq!(receiver_spans.len(), 1);
+        assert_eq!(receiver_spans[0].fields["receiver"], "traced_receiver");
+
+        let send_spans = recorder.spans_named("send_message");
+        assert_eq!(send_spans.len(), 1);
+        assert_eq!(send_spans[0].fields["receiver"], "traced_receiver");
+        assert_eq!(send_spans[0].follows_from, [trace_context]);
+
+        let control_spans = recorder.spans_named("control_msg");
+        assert_eq!(control_spans.len(), 1);
+        assert_eq!(control_spans[0].fields["node"], "traced_receiver");
+        assert_eq!(control_spans[0].fields["message_type"], "Shutdown");
+        assert_eq!(control_spans[0].fields["shutdown_deadline"], "100ms");
+    });
+}
+
+#[test]
+fn test_lifecycle_spans_local() {
+    assert_lifecycle_spans(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_lifecycle_spans_shared() {
+    assert_lifecycle_spans(ReceiverWrapper::shared);
+}
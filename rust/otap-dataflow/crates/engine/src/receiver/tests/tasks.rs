@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tasks spawned by the receivers.
+
+use super::*;
+
+/// A receiver spawning a subtask it awaits on shutdown, and a subtask it leaves running.
+struct SubtaskReceiver {
+    running_tasks: oneshot::Sender<Vec<String>>,
+    leaked_task_alive: oneshot::Sender<()>,
+}
+
+#[async_trait(?Send)]
+impl local::Receiver<TestMsg> for SubtaskReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: local::ControlChannel,
+        effect_handler: local::EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let sender = effect_handler.clone();
+        let worker = effect_handler.spawn_local_named("worker", async move {
+            sender.send_message(TestMsg("from worker".to_owned())).await
+        });
+        let leaked_task_alive = self.leaked_task_alive;
+        let _leaked = effect_handler.spawn_local_named("leaked", async move {
+            let _alive = leaked_task_alive;
+            std::future::pending::<()>().await;
+        });
+        let mut running_tasks: Vec<_> = effect_handler
+            .running_tasks()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        running_tasks.sort();
+        _ = self.running_tasks.send(running_tasks);
+
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        assert_eq!(worker.name(), "worker");
+        worker.await.expect("Worker task failed")
+    }
+}
+
+#[async_trait]
+impl shared::Receiver<TestMsg> for SubtaskReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: shared::ControlChannel,
+        effect_handler: shared::EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let sender = effect_handler.clone();
+        let worker = effect_handler.spawn_named("worker", async move {
+            sender.send_message(TestMsg("from worker".to_owned())).await
+        });
+        let leaked_task_alive = self.leaked_task_alive;
+        let _leaked = effect_handler.spawn_named("leaked", async move {
+            let _alive = leaked_task_alive;
+            std::future::pending::<()>().await;
+        });
+        let mut running_tasks: Vec<_> = effect_handler
+            .running_tasks()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        running_tasks.sort();
+        _ = self.running_tasks.send(running_tasks);
+
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        assert_eq!(worker.name(), "worker");
+        worker.await.expect("Worker task failed")
+    }
+}
+
+/// Checks that the subtasks spawned by the receiver are tracked, and that the subtask left
+/// running is aborted once the receiver completed.
+fn assert_spawn_named(
+    mut receiver: ReceiverWrapper<TestMsg>,
+    running_rx: oneshot::Receiver<Vec<String>>,
+    alive_rx: oneshot::Receiver<()>,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let control_sender = receiver.control_sender();
+
+    rt.block_on(local_tasks.run_until(async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        assert_eq!(
+            running_rx.await.expect("Receiver did not start"),
+            ["leaked", "worker"]
+        );
+        let received = timeout(Duration::from_secs(1), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for the worker")
+            .expect("Worker message not received");
+        assert!(matches!(received, TestMsg(msg) if msg == "from worker"));
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+
+        // The leaked subtask is aborted once the receiver completed.
+        assert!(
+            timeout(Duration::from_secs(1), alive_rx)
+                .await
+                .expect("Leaked subtask still running")
+                .is_err()
+        );
+    }));
+}
+
+#[test]
+fn test_spawn_local_named() {
+    let (running_tx, running_rx) = oneshot::channel();
+    let (alive_tx, alive_rx) = oneshot::channel();
+    let receiver = ReceiverWrapper::local(
+        SubtaskReceiver {
+            running_tasks: running_tx,
+            leaked_task_alive: alive_tx,
+        },
+        &ReceiverConfig::new("subtask_receiver"),
+    );
+    assert_spawn_named(receiver, running_rx, alive_rx);
+}
+
+#[test]
+fn test_spawn_named_shared() {
+    let (running_tx, running_rx) = oneshot::channel();
+    let (alive_tx, alive_rx) = oneshot::channel();
+    let receiver = ReceiverWrapper::shared(
+        SubtaskReceiver {
+            running_tasks: running_tx,
+            leaked_task_alive: alive_tx,
+        },
+        &ReceiverConfig::new("subtask_receiver"),
+    );
+    assert_spawn_named(receiver, running_rx, alive_rx);
+}
+
+/// A receiver decoding a payload in a blocking task, then occupying the only blocking thread
+/// of the runtime and queuing another blocking task behind it.
+struct BlockingReceiver {
+    /// Releases the blocking task occupying the blocking thread.
+    release: std::sync::mpsc::Receiver<()>,
+    /// Set if the queued blocking task ever runs.
+    queued_ran: Arc<AtomicBool>,
+    running_tasks: oneshot::Sender<Vec<String>>,
+}
+
+#[async_trait(?Send)]
+impl local::Receiver<TestMsg> for BlockingReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: local::ControlChannel,
+        effect_handler: local::EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let decoded = effect_handler
+            .spawn_blocking(|| "payload".to_uppercase())
+            .await
+            .expect("Blocking task failed");
+        effect_handler.send_message(TestMsg(decoded)).await?;
+
+        let release = self.release;
+        let _busy = effect_handler.spawn_blocking(move || _ = release.recv());
+        let queued_ran = self.queued_ran;
+        let _queued =
+            effect_handler.spawn_blocking(move || queued_ran.store(true, Ordering::SeqCst));
+        let running_tasks = effect_handler
+            .running_tasks()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        _ = self.running_tasks.send(running_tasks);
+
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        Ok(())
+    }
+}
+
+#[test]
+fn test_spawn_blocking() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .max_blocking_threads(1)
+        .build()
+        .expect("Failed to create runtime");
+    let (release_tx, release_rx) = std::sync::mpsc::channel();
+    let (running_tx, running_rx) = oneshot::channel();
+    let queued_ran = Arc::new(AtomicBool::new(false));
+    let mut receiver = ReceiverWrapper::local(
+        BlockingReceiver {
+            release: release_rx,
+            queued_ran: queued_ran.clone(),
+            running_tasks: running_tx,
+        },
+        &ReceiverConfig::new("blocking_receiver"),
+    );
+    let mut pdata_rx = receiver
+        .take_pdata_receiver()
+        .expect("Failed to take the pdata receiver");
+    let control_sender = receiver.control_sender();
+
+    tokio::task::LocalSet::new().block_on(&rt, async move {
+        let handle = tokio::task::spawn_local(receiver.start());
+        let received = timeout(Duration::from_secs(1), pdata_rx.recv())
+            .await
+            .expect("Timed out waiting for the decoded payload")
+            .expect("Decoded payload not received");
+        assert_eq!(received, TestMsg::new("PAYLOAD"));
+        assert_eq!(
+            running_rx.await.expect("Receiver did not start"),
+            ["blocking", "blocking"]
+        );
+
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+        timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver task panicked")
+            .expect("Receiver failed");
+
+        // The queued blocking task was cancelled with the receiver, it never runs once the
+        // blocking thread is released: a blocking task queued behind it runs first.
+        release_tx
+            .send(())
+            .expect("Failed to release the blocking thread");
+        tokio::task::spawn_blocking(|| {})
+            .await
+            .expect("Blocking task failed");
+    });
+    assert!(!queued_ran.load(Ordering::SeqCst));
+}
@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Timer ticks and node timers of the receivers.
+
+use super::*;
+
+/// Queues a backlog of timer ticks followed by a pause and a shutdown, and checks that the
+/// receiver observes both ahead of the backlog.
+fn assert_shutdown_preempts_timer_ticks(
+    receiver: ReceiverWrapper<TestMsg>,
+    counters: &CtrlMsgCounters,
+) {
+    let (rt, local_tasks) = setup_test_runtime();
+    let control_sender = receiver.control_sender();
+
+    rt.block_on(local_tasks.run_until(async move {
+        for _ in 0..100 {
+            control_sender
+                .send(ControlMsg::TimerTick {})
+                .await
+                .expect("Failed to send TimerTick");
+        }
+        control_sender
+            .send(ControlMsg::Pause)
+            .await
+            .expect("Failed to send Pause");
+        control_sender
+            .send(ControlMsg::Shutdown {
+                deadline: Duration::from_millis(100),
+                reason: "Test".to_owned(),
+            })
+            .await
+            .expect("Failed to send Shutdown");
+
+        timeout(Duration::from_secs(5), receiver.start())
+            .await
+            .expect("Timed out waiting for the receiver")
+            .expect("Receiver failed");
+    }));
+
+    assert_eq!(counters.get_pause_count(), 1);
+    assert_eq!(counters.get_shutdown_count(), 1);
+    assert!(
+        counters.get_timer_tick_count() < 3,
+        "Shutdown observed after {} timer ticks",
+        counters.get_timer_tick_count()
+    );
+}
+
+fn backlog_config() -> ReceiverConfig {
+    let mut config = ReceiverConfig::new("counting_receiver");
+    config.control_channel.capacity = 128;
+    config
+}
+
+#[test]
+fn test_shutdown_preempts_timer_ticks_local() {
+    let counters = CtrlMsgCounters::new();
+    let receiver = ReceiverWrapper::local(
+        CountingReceiver {
+            ctrl_msg_counters: counters.clone(),
+        },
+        &backlog_config(),
+    );
+    assert_shutdown_preempts_timer_ticks(receiver, &counters);
+}
+
+#[test]
+fn test_shutdown_preempts_timer_ticks_shared() {
+    let counters = CtrlMsgCounters::new();
+    let receiver = ReceiverWrapper::shared(
+        CountingReceiver {
+            ctrl_msg_counters: counters.clone(),
+        },
+        &backlog_config(),
+    );
+    assert_shutdown_preempts_timer_ticks(receiver, &counters);
+}
+
+fn timer_config(name: &'static str, interval: Duration) -> ReceiverConfig {
+    let mut config = ReceiverConfig::new(name);
+    config.timer = Some(TimerConfig {
+        interval,
+        jitter: None,
+    });
+    config
+}
+
+#[test]
+fn test_per_node_timer_intervals() {
+    let (rt, local_tasks) = setup_virtual_time_runtime();
+    let fast_counters = CtrlMsgCounters::new();
+    let slow_counters = CtrlMsgCounters::new();
+    let fast = ReceiverWrapper::local(
+        CountingReceiver {
+            ctrl_msg_counters: fast_counters.clone(),
+        },
+        &timer_config("fast_receiver", Duration::from_millis(50)),
+    );
+    let slow = ReceiverWrapper::shared(
+        CountingReceiver {
+            ctrl_msg_counters: slow_counters.clone(),
+        },
+        &timer_config("slow_receiver", Duration::from_millis(500)),
+    );
+
+    rt.block_on(local_tasks.run_until(async move {
+        let senders = [fast.control_sender(), slow.control_sender()];
+        let fast = tokio::task::spawn_local(fast.start());
+        let slow = tokio::task::spawn_local(slow.start());
+
+        // Twenty and a half intervals of the fast receiver.
+        sleep(Duration::from_millis(1025)).await;
+        for sender in senders {
+            sender
+                .send(ControlMsg::Shutdown {
+                    deadline: Duration::from_millis(100),
+                    reason: "Test".to_owned(),
+                })
+                .await
+                .expect("Failed to send Shutdown");
+        }
+        for node in [fast, slow] {
+            timeout(Duration::from_secs(5), node)
+                .await
+                .expect("Timed out waiting for the receiver")
+                .expect("Receiver task panicked")
+                .expect("Receiver failed");
+        }
+    }));
+
+    // The timers stop with the shutdown.
+    let fast_ticks = fast_counters.get_timer_tick_count();
+    let slow_ticks = slow_counters.get_timer_tick_count();
+    assert_eq!(fast_ticks, 20, "{fast_ticks} fast ticks");
+    assert_eq!(slow_ticks, 2, "{slow_ticks} slow ticks");
+    assert_eq!(fast_counters.get_shutdown_count(), 1);
+    assert_eq!(slow_counters.get_shutdown_count(), 1);
+}
+
+/// Advances the virtual clock by ten and a half timer intervals, then shuts the receiver down.
+fn advance_scenario(
+    interval: Duration,
+) -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    move |ctx| {
+        Box::pin(async move {
+            ctx.advance(interval * 21 / 2).await;
+            ctx.send_shutdown(Duration::from_millis(100), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+fn assert_virtual_timer_ticks(
+    new_receiver: impl FnOnce(CountingReceiver, &ReceiverConfig) -> ReceiverWrapper<TestMsg>,
+) {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let interval = Duration::from_secs(60);
+    let mut config = timer_config("counting_receiver", interval);
+    config.drain_policy = DrainPolicy::Immediate;
+    let receiver = new_receiver(
+        CountingReceiver {
+            ctrl_msg_counters: test_runtime.counters(),
+        },
+        &config,
+    );
+
+    let started = std::time::Instant::now();
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(advance_scenario(interval))
+        .run_validation(|ctx| async move {
+            ctx.counters().assert(10, 0, 0, 1, 0, 0);
+        });
+    assert!(started.elapsed() < interval);
+}
+
+#[test]
+fn test_virtual_timer_ticks_local() {
+    assert_virtual_timer_ticks(ReceiverWrapper::local);
+}
+
+#[test]
+fn test_virtual_timer_ticks_shared() {
+    assert_virtual_timer_ticks(ReceiverWrapper::shared);
+}
+
+const NODE_TIMER_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A receiver starting two periodic timers and emitting one message per expiration. The second
+/// timer is cancelled after three expirations.
+struct NodeTimerReceiver;
+
+impl NodeTimerReceiver {
+    /// Returns the message to emit for an expiration of the given timer, and whether the timer
+    /// must be cancelled.
+    fn on_node_timer(
+        id: TimerId,
+        kept: TimerId,
+        cancelled_expirations: &mut usize,
+    ) -> (TestMsg, bool) {
+        if id == kept {
+            return (TestMsg::new("kept"), false);
+        }
+        *cancelled_expirations += 1;
+        (TestMsg::new("cancelled"), *cancelled_expirations == 3)
+    }
+}
+
+impl_test_receiver!(NodeTimerReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: ControlChannel,
+        effect_handler: EffectHandler<TestMsg>,
+    ) -> Result<(), Error<TestMsg>> {
+        let kept = effect_handler.start_periodic_timer(NODE_TIMER_INTERVAL);
+        let cancelled = effect_handler.start_periodic_timer(NODE_TIMER_INTERVAL);
+        let mut cancelled_expirations = 0;
+        loop {
+            match ctrl_msg_recv.recv().await? {
+                ControlMsg::NodeTimer { id } => {
+                    let (msg, cancel) =
+                        Self::on_node_timer(id, kept, &mut cancelled_expirations);
+                    if cancel {
+                        assert!(effect_handler.cancel_timer(cancelled));
+                    }
+                    effect_handler.send_message(msg).await?;
+                }
+                ControlMsg::Shutdown { .. } => {
+                    // The timers are cancelled by the wrapper on shutdown.
+                    assert!(!effect_handler.cancel_timer(kept));
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+});
+
+/// Lets the timers of the receiver expire over 200ms, then shuts the receiver down.
+fn node_timer_scenario() -> impl FnOnce(TestContext) -> Pin<Box<dyn Future<Output = ()>>> {
+    |ctx| {
+        Box::pin(async move {
+            ctx.advance(Duration::from_millis(210)).await;
+            ctx.send_shutdown(Duration::from_millis(100), "Test")
+                .await
+                .expect("Failed to send Shutdown");
+        })
+    }
+}
+
+/// Validation closure counting the expirations of each timer.
+fn node_timer_validation_procedure()
+-> impl FnOnce(NotSendValidateContext<TestMsg>) -> Pin<Box<dyn Future<Output = ()>>> {
+    |mut ctx| {
+        Box::pin(async move {
+            let (mut kept, mut cancelled) = (0, 0);
+            while let Ok(received) = ctx.recv().await {
+                if received == TestMsg::new("kept") {
+                    kept += 1;
+                } else {
+                    cancelled += 1;
+                }
+            }
+            assert_eq!(kept, 10);
+            assert_eq!(cancelled, 3);
+        })
+    }
+}
+
+#[test]
+fn test_node_timers_local() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let receiver = ReceiverWrapper::local(NodeTimerReceiver, test_runtime.config());
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(node_timer_scenario())
+        .run_validation(node_timer_validation_procedure());
+}
+
+#[test]
+fn test_node_timers_shared() {
+    let test_runtime = TestRuntime::new().with_virtual_time();
+    let receiver = ReceiverWrapper::shared(NodeTimerReceiver, test_runtime.config());
+
+    test_runtime
+        .set_receiver(receiver)
+        .run_test(node_timer_scenario())
+        .run_validation(node_timer_validation_procedure());
+}
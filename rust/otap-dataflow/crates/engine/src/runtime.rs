@@ -0,0 +1,481 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Placement of `Local` receivers on dedicated threads, one per CPU core.
+//!
+//! A `Local` receiver is `!Send` so that it can keep its state on a single thread without any
+//! synchronization (thread-per-core). The [`CoreScheduler`] provides these threads: every thread
+//! is pinned to a core where the platform supports it, and runs a current-thread Tokio runtime and
+//! a [`LocalSet`] on which the receivers placed on the core are started.
+//!
+//! As a `Local` receiver can't be moved to another thread once created, it is built on its
+//! scheduler thread by the factory given to [`CoreScheduler::spawn_local_node`]. The returned
+//! [`PlacedReceiver`] is the `Send` handle of the receiver: its pdata are forwarded to a shared
+//! channel by a bridge running next to the receiver, and its control channel is reachable from any
+//! thread. The handle is added to a pipeline with
+//! [`crate::pipeline::PipelineBuilder::add_placed_receiver`], and the receiver starts when the
+//! pipeline runs. The other stages of the pipeline, including the `Shared` ones, keep running on
+//! the runtime of the pipeline.
+//!
+//! The scheduler threads complete once the scheduler is shut down (see [`CoreScheduler::join`])
+//! and all the receivers placed on them completed, e.g. after the pipeline shutdown.
+
+use crate::error::Error;
+use crate::health::{HealthProbe, HealthStatus};
+use crate::logging::LogSink;
+use crate::message::{ControlMsg, PrioritySender, Receiver, Sender};
+use crate::pipeline::run_bridge;
+use crate::receiver::ReceiverWrapper;
+use crate::telemetry::ShutdownReport;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::{JoinSet, LocalSet};
+
+/// A unit of work run on a scheduler thread, returning the future of a node to spawn on the
+/// `LocalSet` of the thread.
+type Job = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+/// The log sink of a pipeline and the id of the pipeline (see [`crate::logging`]).
+type PipelineLogSink = (Arc<dyn LogSink>, Cow<'static, str>);
+
+/// A scheduler thread pinned to a core.
+struct Worker {
+    core: usize,
+    jobs: mpsc::UnboundedSender<Job>,
+    /// Notified when the thread completed.
+    exited: oneshot::Receiver<()>,
+    thread: JoinHandle<()>,
+}
+
+/// A set of threads, one per requested core, running the `Local` receivers placed on them (see
+/// the [module documentation](self)).
+///
+/// Dropping the scheduler without joining it detaches its threads, which complete once the
+/// receivers placed on them completed.
+pub struct CoreScheduler {
+    workers: Vec<Worker>,
+}
+
+impl CoreScheduler {
+    /// Spawns one thread per given core id, each pinned to its core and running a current-thread
+    /// Tokio runtime with all the drivers enabled. A thread which can't be pinned to its core
+    /// (e.g. the core doesn't exist or the platform doesn't support core affinity) runs unpinned,
+    /// and a warning is logged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no core is given, or if a runtime or a thread can't be created.
+    pub fn new(cores: &[usize]) -> std::io::Result<Self> {
+        if cores.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "A core scheduler needs at least one core",
+            ));
+        }
+        let mut workers = Vec::with_capacity(cores.len());
+        for &core in cores {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let (jobs, mut pending_jobs) = mpsc::unbounded_channel::<Job>();
+            let (exited_tx, exited) = oneshot::channel();
+            let thread = std::thread::Builder::new()
+                .name(format!("otap-core-{core}"))
+                .spawn(move || {
+                    if !core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+                        tracing::warn!(core, "Failed to pin the scheduler thread to its core");
+                    }
+                    LocalSet::new().block_on(&runtime, async move {
+                        let mut nodes = JoinSet::new();
+                        while let Some(job) = pending_jobs.recv().await {
+                            _ = nodes.spawn_local(job());
+                        }
+                        while nodes.join_next().await.is_some() {}
+                    });
+                    _ = exited_tx.send(());
+                })?;
+            workers.push(Worker {
+                core,
+                jobs,
+                exited,
+                thread,
+            });
+        }
+        Ok(CoreScheduler { workers })
+    }
+
+    /// Returns the cores of the scheduler threads, in the order they were requested.
+    #[must_use]
+    pub fn cores(&self) -> Vec<usize> {
+        self.workers.iter().map(|worker| worker.core).collect()
+    }
+
+    /// Builds a `Local` receiver with the given factory on the scheduler thread pinned to the
+    /// `core_hint` core, or on the `core_hint % n`-th thread of the `n` scheduler threads if no
+    /// thread is pinned to this core. The receiver starts when the returned handle is started,
+    /// i.e. when the pipeline it was added to runs (see [`PlacedReceiver`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ReceiverError`] if the factory built a `Shared` receiver, which runs on
+    /// the multi-threaded runtime of the pipeline instead, if the factory panicked, or if the
+    /// scheduler thread is gone.
+    pub fn spawn_local_node<PData, F>(
+        &self,
+        core_hint: usize,
+        factory: F,
+    ) -> Result<PlacedReceiver<PData>, Error<PData>>
+    where
+        PData: Send + 'static,
+        F: FnOnce() -> ReceiverWrapper<PData> + Send + 'static,
+    {
+        let worker = self
+            .workers
+            .iter()
+            .find(|worker| worker.core == core_hint)
+            .unwrap_or(&self.workers[core_hint % self.workers.len()]);
+        let core = worker.core;
+        let (ready_tx, ready_rx) = std::sync::mpsc::sync_channel(1);
+        let job: Job = Box::new(move || {
+            Box::pin(async move {
+                let mut receiver = factory();
+                let name = receiver.name();
+                if let ReceiverWrapper::Shared { .. } = receiver {
+                    _ = ready_tx.send(Err(Error::ReceiverError {
+                        receiver: name,
+                        error: "A shared receiver can't be placed on a core".to_owned(),
+                    }));
+                    return;
+                }
+                let pdata_rx = match receiver.take_pdata_receiver() {
+                    Ok(pdata_rx) => pdata_rx,
+                    Err(error) => {
+                        _ = ready_tx.send(Err(error));
+                        return;
+                    }
+                };
+                let (_, capacity) = receiver.pdata_channel_len();
+                let (bridge_tx, bridge_rx) = mpsc::channel(capacity);
+                let (start_tx, start_rx) = oneshot::channel::<Option<PipelineLogSink>>();
+                let (done_tx, done_rx) = oneshot::channel();
+                _ = ready_tx.send(Ok(PlacedReceiver {
+                    name,
+                    core,
+                    control_sender: receiver.priority_control_sender(),
+                    health_probe: receiver.health_probe(),
+                    health: receiver.subscribe_health(),
+                    shutdown_report: receiver.subscribe_shutdown_report(),
+                    pdata_receiver: Some(bridge_rx),
+                    log_sink: None,
+                    start: start_tx,
+                    done: done_rx,
+                }));
+
+                // The receiver is dropped without running if its handle is dropped.
+                let Ok(log_sink) = start_rx.await else {
+                    return;
+                };
+                if let Some((sink, pipeline_id)) = log_sink {
+                    receiver.set_log_sink(sink, Some(pipeline_id));
+                }
+                let bridge = tokio::task::spawn_local(run_bridge(pdata_rx, bridge_tx));
+                let result = receiver.start().await;
+                // The bridge completes once the receiver dropped its output channel.
+                let bridge_result = bridge.await.unwrap_or_else(|join_error| {
+                    Err(Error::PipelineError {
+                        error: join_error.to_string(),
+                    })
+                });
+                _ = done_tx.send(result.and(bridge_result));
+            })
+        });
+
+        let gone = || Error::ReceiverError {
+            receiver: Cow::Borrowed("placed receiver"),
+            error: format!("The scheduler thread of core {core} is gone"),
+        };
+        worker.jobs.send(job).map_err(|_| gone())?;
+        // The factory only builds the receiver, the scheduler thread answers promptly.
+        match ready_rx.recv() {
+            Ok(placed) => placed,
+            Err(_) => Err(Error::ReceiverError {
+                receiver: Cow::Borrowed("placed receiver"),
+                error: format!("The receiver factory panicked on core {core}"),
+            }),
+        }
+    }
+
+    /// Stops accepting receivers and waits for all the scheduler threads to complete, i.e. for
+    /// all the receivers placed on them to complete (see [`crate::pipeline::PipelineHandle`]).
+    pub async fn join(self) {
+        for worker in self.workers {
+            let Worker {
+                jobs,
+                exited,
+                thread,
+                ..
+            } = worker;
+            drop(jobs);
+            // A panicked thread never notifies its completion.
+            _ = exited.await;
+            _ = thread.join();
+        }
+    }
+}
+
+/// The `Send` handle of a `Local` receiver placed on a scheduler thread (see
+/// [`CoreScheduler::spawn_local_node`]).
+///
+/// Once started, the receiver runs on its scheduler thread until it completes, the handle then
+/// resolves to the outcome of the receiver.
+pub struct PlacedReceiver<PData> {
+    name: Cow<'static, str>,
+    core: usize,
+    control_sender: PrioritySender<ControlMsg>,
+    health_probe: HealthProbe,
+    health: watch::Receiver<HealthStatus>,
+    shutdown_report: watch::Receiver<Option<ShutdownReport>>,
+    /// The shared channel the pdata emitted by the receiver are forwarded to.
+    pdata_receiver: Option<mpsc::Receiver<PData>>,
+    log_sink: Option<PipelineLogSink>,
+    /// Starts the receiver, with the log sink of its pipeline if any.
+    start: oneshot::Sender<Option<PipelineLogSink>>,
+    /// The outcome of the receiver.
+    done: oneshot::Receiver<Result<(), Error<PData>>>,
+}
+
+impl<PData> PlacedReceiver<PData> {
+    /// Returns the name of the receiver.
+    #[must_use]
+    pub fn name(&self) -> Cow<'static, str> {
+        self.name.clone()
+    }
+
+    /// Returns the core of the scheduler thread the receiver is placed on.
+    #[must_use]
+    pub fn core(&self) -> usize {
+        self.core
+    }
+
+    /// Returns a sender of control messages to the receiver, usable from any thread.
+    #[must_use]
+    pub fn control_sender(&self) -> Sender<ControlMsg> {
+        Sender::Priority(self.control_sender.clone())
+    }
+
+    /// Returns a probe tracking the health of the receiver once started.
+    #[must_use]
+    pub fn health_probe(&self) -> HealthProbe {
+        self.health_probe.clone()
+    }
+
+    /// Returns a receiver of the health statuses reported by the receiver.
+    #[must_use]
+    pub fn subscribe_health(&self) -> watch::Receiver<HealthStatus> {
+        self.health.clone()
+    }
+
+    /// Returns a receiver of the summary of the run of the receiver (see [`ShutdownReport`]).
+    #[must_use]
+    pub fn subscribe_shutdown_report(&self) -> watch::Receiver<Option<ShutdownReport>> {
+        self.shutdown_report.clone()
+    }
+
+    /// Sets the sink of the messages logged by the receiver, applied when the receiver starts.
+    pub fn set_log_sink(&mut self, sink: Arc<dyn LogSink>, pipeline_id: Option<Cow<'static, str>>) {
+        self.log_sink = pipeline_id.map(|pipeline_id| (sink, pipeline_id));
+    }
+
+    /// Returns `true` if the pdata receiver was not taken yet (see
+    /// [`PlacedReceiver::take_pdata_receiver`]).
+    #[must_use]
+    pub fn has_pdata_receiver(&self) -> bool {
+        self.pdata_receiver.is_some()
+    }
+
+    /// Returns the shared channel the pdata emitted by the receiver are forwarded to, or
+    /// [`Error::PdataReceiverAlreadyTaken`] if it was already taken.
+    pub fn take_pdata_receiver(&mut self) -> Result<Receiver<PData>, Error<PData>> {
+        self.pdata_receiver
+            .take()
+            .map(Receiver::Shared)
+            .ok_or_else(|| Error::PdataReceiverAlreadyTaken {
+                node: self.name.clone(),
+            })
+    }
+
+    /// Starts the receiver on its scheduler thread and waits for its completion.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error returned by the receiver, or an [`Error::ReceiverError`] if the receiver
+    /// panicked or its scheduler thread is gone.
+    pub async fn start(self) -> Result<(), Error<PData>> {
+        let gone = || Error::ReceiverError {
+            receiver: self.name.clone(),
+            error: format!("The receiver stopped unexpectedly on core {}", self.core),
+        };
+        if self.start.send(self.log_sink).is_err() {
+            return Err(gone());
+        }
+        self.done.await.unwrap_or_else(|_| Err(gone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ReceiverConfig;
+    use crate::error::Error;
+    use crate::local::receiver as local;
+    use crate::receiver::ReceiverWrapper;
+    use crate::runtime::CoreScheduler;
+    use crate::shared::receiver as shared;
+    use crate::testing::TestMsg;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::runtime::Builder;
+    use tokio::task::LocalSet;
+    use tokio::time::timeout;
+
+    /// A receiver emitting the name of the thread it runs on, and then waiting for a shutdown.
+    struct ThreadReceiver;
+
+    #[async_trait(?Send)]
+    impl local::Receiver<TestMsg> for ThreadReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_msg_recv: local::ControlChannel,
+            effect_handler: local::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            let thread = std::thread::current().name().unwrap_or_default().to_owned();
+            effect_handler.send_message(TestMsg(thread)).await?;
+            while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl shared::Receiver<TestMsg> for ThreadReceiver {
+        async fn start(
+            self: Box<Self>,
+            mut ctrl_msg_recv: shared::ControlChannel,
+            _effect_handler: shared::EffectHandler<TestMsg>,
+        ) -> Result<(), Error<TestMsg>> {
+            while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_spawn_local_node() {
+        let scheduler = CoreScheduler::new(&[0, 1]).expect("Failed to create the scheduler");
+        assert_eq!(scheduler.cores(), vec![0, 1]);
+        let factory_threads = Arc::new(Mutex::new(Vec::new()));
+        let mut placed = Vec::new();
+        for (core_hint, name) in [(0, "first"), (3, "second")] {
+            let factory_threads = factory_threads.clone();
+            placed.push(
+                scheduler
+                    .spawn_local_node(core_hint, move || {
+                        factory_threads
+                            .lock()
+                            .unwrap()
+                            .push(std::thread::current().name().map(str::to_owned));
+                        ReceiverWrapper::local(ThreadReceiver, &ReceiverConfig::new(name))
+                    })
+                    .map_err(|e| e.to_string())
+                    .expect("Failed to place the receiver"),
+            );
+        }
+        // The core hint falls back to the scheduler threads in a round-robin order.
+        assert_eq!(
+            placed.iter().map(|p| p.core()).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            *factory_threads.lock().unwrap(),
+            vec![
+                Some("otap-core-0".to_owned()),
+                Some("otap-core-1".to_owned())
+            ]
+        );
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        LocalSet::new().block_on(&rt, async move {
+            let mut handles = Vec::new();
+            let mut threads = Vec::new();
+            for mut receiver in placed {
+                let mut pdata_rx = receiver
+                    .take_pdata_receiver()
+                    .expect("Failed to take the pdata receiver");
+                assert!(receiver.take_pdata_receiver().is_err());
+                let control_sender = receiver.control_sender();
+                handles.push((control_sender, tokio::task::spawn_local(receiver.start())));
+                let TestMsg(thread) = timeout(Duration::from_secs(5), pdata_rx.recv())
+                    .await
+                    .expect("Timed out waiting for the receiver")
+                    .expect("The receiver emitted nothing");
+                threads.push(thread);
+            }
+            assert_eq!(threads, vec!["otap-core-0", "otap-core-1"]);
+
+            for (control_sender, handle) in handles {
+                control_sender
+                    .send(crate::message::ControlMsg::Shutdown {
+                        deadline: Duration::from_millis(100),
+                        reason: "Test".to_owned(),
+                    })
+                    .await
+                    .expect("Failed to send the shutdown");
+                let result = timeout(Duration::from_secs(5), handle)
+                    .await
+                    .expect("Timed out waiting for the receiver")
+                    .expect("Receiver task panicked");
+                assert!(result.is_ok(), "Unexpected result {result:?}");
+            }
+            // The scheduler threads complete once their receivers completed.
+            timeout(Duration::from_secs(5), scheduler.join())
+                .await
+                .expect("Timed out waiting for the scheduler threads");
+        });
+    }
+
+    #[test]
+    fn test_spawn_local_node_rejects_shared_receivers() {
+        let scheduler = CoreScheduler::new(&[0]).expect("Failed to create the scheduler");
+        let result = scheduler.spawn_local_node(0, || {
+            ReceiverWrapper::shared(ThreadReceiver, &ReceiverConfig::new("shared"))
+        });
+        assert!(matches!(result, Err(Error::ReceiverError { .. })));
+        assert!(CoreScheduler::new(&[]).is_err());
+    }
+
+    #[test]
+    fn test_dropped_placed_receiver_never_starts() {
+        let scheduler = CoreScheduler::new(&[0]).expect("Failed to create the scheduler");
+        let placed = scheduler
+            .spawn_local_node(0, || {
+                ReceiverWrapper::local(ThreadReceiver, &ReceiverConfig::new("dropped"))
+            })
+            .map_err(|e| e.to_string())
+            .expect("Failed to place the receiver");
+        drop(placed);
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create runtime");
+        rt.block_on(async move {
+            timeout(Duration::from_secs(5), scheduler.join())
+                .await
+                .expect("Timed out waiting for the scheduler threads");
+        });
+    }
+}
@@ -31,20 +31,33 @@
 //! To ensure scalability, the pipeline engine will start multiple instances of the same pipeline
 //! in parallel on different cores, each with its own exporter instance.
 
+use crate::config::RetryPolicy;
+use crate::config_ack::ConfigAckWatcher;
 use crate::effect_handler::EffectHandlerCore;
-use crate::error::Error;
-use crate::message::{ControlMsg, Message};
+use crate::error::{ConnectError, Error};
+use crate::flush_ack::FlushAckWatcher;
+use crate::health::HealthProbe;
+use crate::logging::{LogLevel, LogSink};
+use crate::message::{ControlMsg, Message, SharedSender};
+use crate::outbound::OutboundConnection;
+use crate::task::{TaskHandle, TaskRegistry};
+use crate::telemetry::TelemetryCounters;
 use async_trait::async_trait;
 use otap_df_channel::error::RecvError;
+use otap_df_config::NodeKind;
 use std::borrow::Cow;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::net::TcpListener;
 use tokio::time::{Instant, Sleep, sleep_until};
 
 /// A trait for egress exporters (Send definition).
 #[async_trait]
-pub trait Exporter<PData> {
+pub trait Exporter<PData>: Send {
     /// Similar to local::exporter::Exporter::start, but operates in a Send context.
     async fn start(
         self: Box<Self>,
@@ -69,6 +82,10 @@ pub struct MessageChannel<PData> {
     shutting_down_deadline: Option<Instant>,
     /// Holds the ControlMsg::Shutdown until after we’ve drained pdata.
     pending_shutdown: Option<ControlMsg>,
+    /// The probe answering the `HealthCheck` messages, if tracked.
+    health: Option<HealthProbe>,
+    /// The counters of the node, incremented for every pdata message delivered to it, if tracked.
+    telemetry: Option<TelemetryCounters>,
 }
 
 impl<PData> MessageChannel<PData> {
@@ -83,9 +100,24 @@ impl<PData> MessageChannel<PData> {
             pdata_rx: Some(pdata_rx),
             shutting_down_deadline: None,
             pending_shutdown: None,
+            health: None,
+            telemetry: None,
         }
     }
 
+    /// Makes this channel answer the health checks of the node (see [`crate::health`]).
+    pub(crate) fn track_health(mut self, health: HealthProbe) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Makes this channel count the pdata messages delivered to the node (see
+    /// [`crate::telemetry`]).
+    pub(crate) fn track_telemetry(mut self, telemetry: TelemetryCounters) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
     /// Asynchronously receives the next message to process.
     ///
     /// Order of precedence:
@@ -98,6 +130,8 @@ impl<PData> MessageChannel<PData> {
     /// 3. When the deadline expires (or was `0`): the stored `Shutdown` is returned.
     ///    Subsequent calls return `RecvError::Closed`.
     ///
+    /// The health checks are answered on reception and are not returned.
+    ///
     /// # Errors
     ///
     /// Returns a [`RecvError`] if both channels are closed, or if the
@@ -134,7 +168,7 @@ impl<PData> MessageChannel<PData> {
 
                     // 1) Any pdata?
                     pdata = self.pdata_rx.as_mut().expect("pdata_rx must exist").recv() => match pdata {
-                        Some(pdata) => return Ok(Message::PData(pdata)),
+                        Some(pdata) => return Ok(self.deliver(pdata)),
                         None => {
                             // pdata channel closed → emit Shutdown
                             let shutdown = self.pending_shutdown
@@ -174,6 +208,12 @@ impl<PData> MessageChannel<PData> {
                         self.pending_shutdown = Some(ControlMsg::Shutdown { deadline: Duration::ZERO, reason });
                         continue; // re-enter the loop into draining mode
                     }
+                    Some(ControlMsg::HealthCheck { id }) => {
+                        if let Some(health) = &self.health {
+                            health.answer(id);
+                        }
+                        continue;
+                    }
                     Some(msg) => return Ok(Message::Control(msg)),
                     None  => return Err(RecvError::Closed),
                 },
@@ -182,7 +222,7 @@ impl<PData> MessageChannel<PData> {
                 pdata = self.pdata_rx.as_mut().expect("pdata_rx must exist").recv() => {
                     match pdata {
                         Some(pdata) => {
-                            return Ok(Message::PData(pdata));
+                            return Ok(self.deliver(pdata));
                         }
                         None => {
                             return Err(RecvError::Closed);
@@ -193,6 +233,13 @@ impl<PData> MessageChannel<PData> {
         }
     }
 
+    fn deliver(&self, pdata: PData) -> Message<PData> {
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record_received();
+        }
+        Message::PData(pdata)
+    }
+
     fn shutdown(&mut self) {
         self.shutting_down_deadline = None;
         drop(self.control_rx.take().expect("control_rx must exist"));
@@ -205,6 +252,9 @@ impl<PData> MessageChannel<PData> {
 pub struct EffectHandler<PData> {
     core: EffectHandlerCore,
 
+    /// The control senders of the upstream nodes to which throttle signals are sent.
+    upstream_control_senders: Vec<SharedSender<ControlMsg>>,
+
     /// A 0 size type used to parameterize the `EffectHandler` with the type of message the exporter
     /// will consume.
     _pd: PhantomData<PData>,
@@ -216,7 +266,8 @@ impl<PData> EffectHandler<PData> {
     #[must_use]
     pub fn new(name: Cow<'static, str>) -> Self {
         EffectHandler {
-            core: EffectHandlerCore { node_name: name },
+            core: EffectHandlerCore::new(name, NodeKind::Exporter),
+            upstream_control_senders: Vec::new(),
             _pd: PhantomData,
         }
     }
@@ -227,5 +278,174 @@ impl<PData> EffectHandler<PData> {
         self.core.node_name()
     }
 
+    /// Logs an informational message tagged with the identity of the exporter (see
+    /// [`crate::logging`]).
+    pub fn info_message(&self, message: &str) {
+        self.core.log(LogLevel::Info, message);
+    }
+
+    /// Logs a warning tagged with the identity of the exporter (see [`crate::logging`]).
+    pub fn warn_message(&self, message: &str) {
+        self.core.log(LogLevel::Warn, message);
+    }
+
+    /// Logs an error tagged with the identity of the exporter (see [`crate::logging`]).
+    pub fn error_message(&self, message: &str) {
+        self.core.log(LogLevel::Error, message);
+    }
+
+    /// Emits the messages logged by the exporter to the given sink, tagged with the given pipeline
+    /// id.
+    pub(crate) fn set_log_sink(
+        &mut self,
+        sink: Arc<dyn LogSink>,
+        pipeline_id: Option<Cow<'static, str>>,
+    ) {
+        self.core.set_log_sink(sink, pipeline_id);
+    }
+
+    /// Confirms that the configuration update with the given version (see
+    /// [`ControlMsg::Config`]) has been applied by the exporter.
+    pub fn ack_config(&self, version: u64) {
+        self.core.ack_config(version);
+    }
+
+    /// Reports that the exporter failed to apply the configuration update with the given
+    /// version. The engine side observes it as an [`Error::ConfigRejected`].
+    pub fn reject_config(&self, version: u64, reason: impl Into<String>) {
+        self.core.reject_config(version, reason.into());
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the exporter.
+    pub(crate) fn config_acks(&self) -> ConfigAckWatcher {
+        self.core.config_acks()
+    }
+
+    /// Confirms that the exporter emitted the pdata it was holding in response to the flush with
+    /// the given id (see [`ControlMsg::Flush`]).
+    pub fn ack_flush(&self, id: u64) {
+        self.core.ack_flush(id);
+    }
+
+    /// Returns a watcher of the flushes acknowledged by the exporter.
+    pub(crate) fn flush_acks(&self) -> FlushAckWatcher {
+        self.core.flush_acks()
+    }
+
+    /// Returns the internal counters of the exporter (see [`crate::telemetry`]).
+    pub(crate) fn telemetry(&self) -> TelemetryCounters {
+        self.core.telemetry.clone()
+    }
+
+    /// Connects the control channel of an upstream node (typically a receiver) to which the
+    /// throttle signals of this exporter are sent.
+    pub(crate) fn connect_upstream_control(&mut self, control_sender: SharedSender<ControlMsg>) {
+        self.upstream_control_senders.push(control_sender);
+    }
+
+    /// Requests the upstream nodes to pause ingestion for the given duration by sending them a
+    /// [`ControlMsg::Throttle`]. Exporters are expected to call this method when they can't keep up
+    /// with the incoming pdata, instead of dropping it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of an upstream node is
+    /// closed.
+    pub async fn throttle_upstream(&self, duration: Duration) -> Result<(), Error<PData>> {
+        self.send_upstream(ControlMsg::Throttle { duration }).await
+    }
+
+    /// Notifies the upstream nodes that the pdata with the given id has been delivered, by sending
+    /// them a [`ControlMsg::Ack`]. The upstream nodes ignore the ids they didn't emit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of an upstream node is
+    /// closed.
+    pub async fn notify_ack(&self, id: u64) -> Result<(), Error<PData>> {
+        self.send_upstream(ControlMsg::Ack { id }).await
+    }
+
+    /// Notifies the upstream nodes that the pdata with the given id couldn't be delivered, by
+    /// sending them a [`ControlMsg::Nack`] carrying the reason, so that they can retry it or report
+    /// the failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ControlChannelClosed`] if the control channel of an upstream node is
+    /// closed.
+    pub async fn notify_nack(&self, id: u64, reason: &str) -> Result<(), Error<PData>> {
+        self.send_upstream(ControlMsg::Nack {
+            id,
+            reason: reason.to_owned(),
+        })
+        .await
+    }
+
+    /// Sends a control message to every upstream node.
+    async fn send_upstream(&self, msg: ControlMsg) -> Result<(), Error<PData>> {
+        for control_sender in &self.upstream_control_senders {
+            control_sender
+                .send(msg.clone())
+                .await
+                .map_err(|_| Error::ControlChannelClosed {
+                    node: self.exporter_name(),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Creates a non-blocking TCP listener on the given address with socket options defined by the
+    /// pipeline engine implementation, e.g. for exporters serving a pull-based endpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::IoError`] if any step in the process fails.
+    pub fn tcp_listener(&self, addr: SocketAddr) -> Result<TcpListener, Error<PData>> {
+        self.core.tcp_listener(addr, self.exporter_name())
+    }
+
+    /// Connects to the given endpoint, retrying with an exponential backoff and a random jitter
+    /// according to the given policy. The returned connection reconnects to the endpoint when it
+    /// is lost (see [`crate::outbound`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConnectError::Unreachable`] if the endpoint can't be reached within the retry
+    /// policy.
+    pub async fn connect(
+        &self,
+        addr: SocketAddr,
+        policy: RetryPolicy,
+    ) -> Result<OutboundConnection, ConnectError> {
+        OutboundConnection::connect(self.core.clone(), addr, policy).await
+    }
+
+    /// Spawns a named subtask of the exporter on the Tokio runtime.
+    ///
+    /// The subtask runs within a tracing span carrying the names of the exporter and of the
+    /// subtask. It is tracked by the effect handler: the exporter can await the returned handle
+    /// when it shuts down, and the subtasks still running once the exporter completed are aborted.
+    pub fn spawn_named<F>(&self, name: &str, task: F) -> TaskHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.core
+            .tasks
+            .spawn(&self.core.node_name, Cow::Owned(name.to_owned()), task)
+    }
+
+    /// Returns the names of the subtasks spawned via `spawn_named` that are still running.
+    #[must_use]
+    pub fn running_tasks(&self) -> Vec<Cow<'static, str>> {
+        self.core.tasks.running_tasks()
+    }
+
+    /// Returns the registry of the subtasks spawned by the exporter.
+    pub(crate) fn tasks(&self) -> TaskRegistry {
+        self.core.tasks.clone()
+    }
+
     // More methods will be added in the future as needed.
 }
@@ -30,16 +30,24 @@
 //! To ensure scalability, the pipeline engine will start multiple instances of the same pipeline
 //! in parallel on different cores, each with its own processor instance.
 
+use crate::config_ack::ConfigAckWatcher;
 use crate::effect_handler::EffectHandlerCore;
 use crate::error::Error;
+use crate::flush_ack::FlushAckWatcher;
+use crate::logging::{LogLevel, LogSink};
 use crate::message::Message;
+use crate::telemetry::TelemetryCounters;
 use async_trait::async_trait;
-use otap_df_channel::error::SendError;
+use otap_df_config::NodeKind;
 use std::borrow::Cow;
+use std::sync::Arc;
 
 /// A trait for processors in the pipeline (Send definition).
+///
+/// A processor receives `PDataIn` messages and sends `PDataOut` messages to the next node(s), both
+/// types are the same unless the processor converts the pdata from one type to another.
 #[async_trait]
-pub trait Processor<PData> {
+pub trait Processor<PDataIn, PDataOut = PDataIn>: Send {
     /// Processes a message and optionally produces effects, such as generating new pdata messages.
     ///
     /// This method is called by the pipeline engine for each message that arrives at the processor.
@@ -73,9 +81,9 @@ pub trait Processor<PData> {
     /// Returns an [`Error`] if the processor encounters an unrecoverable error.
     async fn process(
         &mut self,
-        msg: Message<PData>,
-        effect_handler: &mut EffectHandler<PData>,
-    ) -> Result<(), Error<PData>>;
+        msg: Message<PDataIn>,
+        effect_handler: &mut EffectHandler<PDataOut>,
+    ) -> Result<(), Error<PDataOut>>;
 }
 
 /// A `Send` implementation of the EffectHandler.
@@ -93,7 +101,7 @@ impl<PData> EffectHandler<PData> {
     #[must_use]
     pub fn new(name: Cow<'static, str>, msg_sender: tokio::sync::mpsc::Sender<PData>) -> Self {
         EffectHandler {
-            core: EffectHandlerCore { node_name: name },
+            core: EffectHandlerCore::new(name, NodeKind::Processor),
             msg_sender,
         }
     }
@@ -104,16 +112,115 @@ impl<PData> EffectHandler<PData> {
         self.core.node_name()
     }
 
+    /// Logs an informational message tagged with the identity of the processor (see
+    /// [`crate::logging`]).
+    pub fn info_message(&self, message: &str) {
+        self.core.log(LogLevel::Info, message);
+    }
+
+    /// Logs a warning tagged with the identity of the processor (see [`crate::logging`]).
+    pub fn warn_message(&self, message: &str) {
+        self.core.log(LogLevel::Warn, message);
+    }
+
+    /// Logs an error tagged with the identity of the processor (see [`crate::logging`]).
+    pub fn error_message(&self, message: &str) {
+        self.core.log(LogLevel::Error, message);
+    }
+
+    /// Emits the messages logged by the processor to the given sink, tagged with the given pipeline
+    /// id.
+    pub(crate) fn set_log_sink(
+        &mut self,
+        sink: Arc<dyn LogSink>,
+        pipeline_id: Option<Cow<'static, str>>,
+    ) {
+        self.core.set_log_sink(sink, pipeline_id);
+    }
+
+    /// Confirms that the configuration update with the given version (see
+    /// [`crate::message::ControlMsg::Config`]) has been applied by the processor.
+    pub fn ack_config(&self, version: u64) {
+        self.core.ack_config(version);
+    }
+
+    /// Reports that the processor failed to apply the configuration update with the given
+    /// version. The engine side observes it as an [`Error::ConfigRejected`].
+    pub fn reject_config(&self, version: u64, reason: impl Into<String>) {
+        self.core.reject_config(version, reason.into());
+    }
+
+    /// Returns a watcher of the configuration updates acknowledged by the processor.
+    pub(crate) fn config_acks(&self) -> ConfigAckWatcher {
+        self.core.config_acks()
+    }
+
+    /// Confirms that the processor emitted the pdata it was holding in response to the flush with
+    /// the given id (see [`crate::message::ControlMsg::Flush`]).
+    pub fn ack_flush(&self, id: u64) {
+        self.core.ack_flush(id);
+    }
+
+    /// Returns a watcher of the flushes acknowledged by the processor.
+    pub(crate) fn flush_acks(&self) -> FlushAckWatcher {
+        self.core.flush_acks()
+    }
+
+    /// Returns the internal counters of the processor (see [`crate::telemetry`]).
+    pub(crate) fn telemetry(&self) -> TelemetryCounters {
+        self.core.telemetry.clone()
+    }
+
     /// Sends a message to the next node(s) in the pipeline.
     ///
     /// # Errors
     ///
-    /// Returns an [`Error::ChannelSendError`] if the message could not be sent.
+    /// Returns an [`Error::PdataChannelClosed`] if the output channel is closed.
     pub async fn send_message(&self, data: PData) -> Result<(), Error<PData>> {
-        self.msg_sender
-            .send(data)
-            .await
-            .map_err(|e| Error::ChannelSendError(SendError::Closed(e.0)))
+        let sent = self.msg_sender.send(data).await;
+        self.core
+            .telemetry
+            .record_send(sent)
+            .map_err(|_| Error::PdataChannelClosed {
+                node: self.processor_name(),
+            })
+    }
+
+    /// Sends a batch of messages to the next node(s) in the pipeline, in order. Unlike calling
+    /// `send_message` in a loop, the capacity available in the output channel is reserved once
+    /// for as many messages as possible, the processor only waits when the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::PdataBatchInterrupted`] reporting the number of messages accepted by
+    /// the channel if it closed before the whole batch was sent, the remaining messages are
+    /// dropped.
+    pub async fn send_message_batch(
+        &self,
+        batch: impl IntoIterator<Item = PData>,
+    ) -> Result<(), Error<PData>> {
+        let mut batch = batch.into_iter().peekable();
+        let mut accepted = 0;
+        while batch.peek().is_some() {
+            // Reserves the capacity currently available, or waits for a single slot.
+            let count = self.msg_sender.capacity().max(1);
+            let Ok(permits) = self.msg_sender.reserve_many(count).await else {
+                self.core.telemetry.record_error();
+                break;
+            };
+            for (permit, data) in permits.zip(&mut batch) {
+                permit.send(data);
+                accepted += 1;
+            }
+        }
+        self.core.telemetry.record_sent_batch(accepted);
+        if batch.peek().is_some() {
+            return Err(Error::PdataBatchInterrupted {
+                node: self.processor_name(),
+                accepted,
+            });
+        }
+        Ok(())
     }
 
     // More methods will be added in the future as needed.
@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enforcement of the `Shutdown` deadline by the node wrappers.
+//!
+//! A node receives its control messages through a channel owned by the engine. To make sure that a
+//! misbehaving node can't hang the pipeline forever, the wrappers interpose a forwarding loop
+//! between the engine-facing control channel and the node-facing one. Once a `Shutdown` has been
+//! forwarded, the node is given the shutdown deadline (plus a small grace period) to complete,
+//! after which its future is dropped and [`Error::ShutdownTimeout`] is returned.
+//!
+//! A receiver can also be stopped by cancelling a token, which delivers a `Shutdown` to the
+//! receiver in place of the engine (see [`CancellableControl`]).
+//!
+//! Once a receiver completed, its wrapper can also wait for the downstream node to consume the
+//! pdata buffered in the output channel of the receiver (see [`DrainPolicy`]).
+//!
+//! The forwarding loop also produces the `TimerTick` and `NodeTimer` messages of the node (see
+//! [`crate::timer`]) and answers the `CollectTelemetry` requests on behalf of the node (see [`crate::telemetry`]).
+//! The handling of each control message is covered by a `control_msg` span (see [`crate::spans`]).
+//!
+//! A panic of the node is caught by the forwarding loop and returned as an
+//! [`Error::NodePanicked`], like any other failure of the node: the wrapper drops the node and its
+//! pdata sender(s), closing the output channel once the other senders are gone, or restarts the
+//! node if a restart policy is configured. The rest of the pipeline keeps running, which is only
+//! safe if the node respects the following invariants:
+//!
+//! - the state the node shares with other nodes or tasks (e.g. through an `Rc<RefCell<_>>` or a
+//!   `Mutex`) is left consistent at every point the node may panic, a poisoned `Mutex` being
+//!   recovered by the other users (see `PoisonError::into_inner`);
+//! - the node doesn't rely on code running after its last `await` point for the correctness of
+//!   the rest of the pipeline (e.g. to acknowledge a flush or release a permit), only on `Drop`
+//!   implementations, which are run while unwinding;
+//! - the node doesn't panic in the tasks it spawns (these panics are caught by tokio, and never
+//!   reported to the wrapper), nor while a panic is unwinding (which aborts the process).
+
+use crate::config::{CancellationConfig, DrainPolicy, TimerConfig};
+use crate::error::Error;
+use crate::message::{ControlMsg, ControlReceiver, ControlSender};
+use crate::spans;
+use crate::telemetry::TelemetryCounters;
+use crate::timer::{NodeTimers, TickSchedule, next_node_timer, next_tick};
+use futures::FutureExt;
+use otap_df_channel::error::RecvError;
+use std::any::Any;
+use std::borrow::Cow;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Extra time given to a node after its shutdown deadline to process the `Shutdown` message
+/// itself. Nodes relying on a [`crate::message::MessageChannel`] only observe the `Shutdown`
+/// once the deadline has expired (pending pdata are drained first).
+pub(crate) const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Capacity of the node-facing control channel. The engine-facing control channel provides the
+/// configured buffering, this channel only needs to hold the message being forwarded.
+pub(crate) const FORWARDED_CONTROL_CHANNEL_CAPACITY: usize = 1;
+
+/// Interval between two checks of the output channel of a draining receiver.
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Reason of the `Shutdown` delivered to a receiver whose cancellation token was cancelled.
+pub(crate) const CANCELLED_REASON: &str = "Cancellation token cancelled";
+
+/// The engine-facing control channel of a receiver, also delivering a `Shutdown` with the grace
+/// period as deadline once the cancellation token of the receiver is cancelled. The messages sent
+/// by the engine keep being delivered after the cancellation.
+pub(crate) struct CancellableControl<R> {
+    control_rx: R,
+    cancellation: Option<CancellationConfig>,
+    cancelled: bool,
+}
+
+impl<R> CancellableControl<R> {
+    pub(crate) fn new(control_rx: R, cancellation: Option<CancellationConfig>) -> Self {
+        CancellableControl {
+            control_rx,
+            cancellation,
+            cancelled: false,
+        }
+    }
+
+    /// Returns true if the `Shutdown` of the cancellation was delivered.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl<R: ControlReceiver> ControlReceiver for CancellableControl<R> {
+    async fn recv_ctrl(&mut self) -> Option<ControlMsg> {
+        let CancellableControl {
+            control_rx,
+            cancellation,
+            cancelled,
+        } = self;
+        let Some(cancellation) = cancellation.as_ref().filter(|_| !*cancelled) else {
+            return control_rx.recv_ctrl().await;
+        };
+        tokio::select! {
+            biased;
+            () = cancellation.token.cancelled() => {
+                *cancelled = true;
+                Some(ControlMsg::Shutdown {
+                    deadline: cancellation.grace_period,
+                    reason: CANCELLED_REASON.to_owned(),
+                })
+            }
+            msg = control_rx.recv_ctrl() => msg,
+        }
+    }
+}
+
+/// Drives `node_future` to completion while forwarding control messages from `control_rx` to
+/// `node_control_tx`. The configuration updates that are not addressed to the node are dropped.
+/// If a timer configuration is given, `TimerTick` messages are forwarded according to its cadence
+/// until a `Shutdown` has been forwarded. Likewise, a `NodeTimer` message is forwarded on every
+/// expiration of the `node_timers` started by the node, and these timers are cancelled once a
+/// `Shutdown` has been forwarded. The `CollectTelemetry` requests are answered with a
+/// snapshot of the `telemetry` counters and are not forwarded.
+///
+/// If the engine-facing control channel is closed, the node-facing one is closed as well and the
+/// node is expected to complete, a node failing on its closed control channel is reported as an
+/// [`Error::ControlChannelClosed`].
+///
+/// After a `Shutdown` message has been forwarded, the node future is bounded by the shutdown
+/// deadline (plus [`SHUTDOWN_GRACE_PERIOD`]). On expiry, the node future is dropped and an
+/// [`Error::ShutdownTimeout`] is returned.
+///
+/// A panic of the node future is returned as an [`Error::NodePanicked`] (see the invariants in
+/// the module documentation).
+pub(crate) async fn run_with_shutdown_deadline<PData, Rx, Tx, Fut>(
+    node: Cow<'static, str>,
+    mut control_rx: Rx,
+    node_control_tx: Tx,
+    timer: Option<TimerConfig>,
+    node_timers: Option<NodeTimers>,
+    telemetry: TelemetryCounters,
+    node_future: Fut,
+) -> Result<(), Error<PData>>
+where
+    Rx: ControlReceiver,
+    Tx: ControlSender,
+    Fut: Future<Output = Result<(), Error<PData>>>,
+{
+    let node_future = catch_panic(node.clone(), node_future);
+    tokio::pin!(node_future);
+    let mut tick_schedule = timer.map(TickSchedule::new);
+
+    loop {
+        let msg = tokio::select! {
+            biased;
+            result = &mut node_future => return result,
+            msg = control_rx.recv_ctrl() => msg,
+            () = next_tick(tick_schedule.as_mut()) => Some(ControlMsg::TimerTick {}),
+            id = next_node_timer(node_timers.as_ref()) => Some(ControlMsg::NodeTimer { id }),
+        };
+
+        let Some(msg) = msg else {
+            // The engine-facing control channel is closed, close the node-facing one as well and
+            // let the node complete on its own terms.
+            drop(node_control_tx);
+            return node_future.await.map_err(|error| match error {
+                Error::ChannelRecvError(RecvError::Closed) => Error::ControlChannelClosed { node },
+                error => error,
+            });
+        };
+
+        let span = spans::control_msg_span(&node, &msg);
+        let deadline = match &msg {
+            ControlMsg::Shutdown { deadline, .. } => Some(*deadline),
+            ControlMsg::Config { update } if !update.is_addressed_to(&node) => continue,
+            ControlMsg::CollectTelemetry { reply_to } => {
+                // The requester is gone or not keeping up, the snapshot is simply dropped.
+                _ = span.in_scope(|| reply_to.try_send(telemetry.snapshot()));
+                continue;
+            }
+            _ => None,
+        };
+
+        if let (Some(_), Some(node_timers)) = (deadline, &node_timers) {
+            // No timer expiration is delivered after the `Shutdown`.
+            node_timers.cancel_all();
+        }
+
+        // The node future must keep being polled while the message is forwarded, otherwise a node
+        // that isn't draining its control channel yet would never make room for it.
+        tokio::select! {
+            biased;
+            result = &mut node_future => return result,
+            sent = node_control_tx.send_ctrl(msg).instrument(span.clone()) => {
+                if sent.is_err() {
+                    // The node dropped its control channel, nothing left to forward.
+                    return node_future.await;
+                }
+            }
+        }
+
+        if let Some(deadline) = deadline {
+            let bounded = tokio::time::timeout(deadline + SHUTDOWN_GRACE_PERIOD, node_future);
+            return match bounded.instrument(span).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::ShutdownTimeout { node, deadline }),
+            };
+        }
+    }
+}
+
+/// Drives the given node future to completion, converting its panic into an
+/// [`Error::NodePanicked`].
+async fn catch_panic<PData>(
+    node: Cow<'static, str>,
+    node_future: impl Future<Output = Result<(), Error<PData>>>,
+) -> Result<(), Error<PData>> {
+    // The node is dropped right after the panic, its state isn't observed anymore.
+    match AssertUnwindSafe(node_future).catch_unwind().await {
+        Ok(result) => result,
+        Err(payload) => {
+            let payload = panic_message(payload.as_ref());
+            tracing::error!(node = %node, payload, "Node panicked");
+            Err(Error::NodePanicked { node, payload })
+        }
+    }
+}
+
+/// Returns the message of a panic, i.e. its payload if it is a string.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+/// Waits, according to the drain policy, until no pdata message is buffered in the output channel
+/// of the given receiver, as reported by `buffered_pdata`. A warning is emitted if the timeout
+/// expires first.
+pub(crate) async fn drain_output(
+    node: &str,
+    policy: DrainPolicy,
+    buffered_pdata: impl Fn() -> usize,
+) {
+    let DrainPolicy::Flush { timeout } = policy else {
+        return;
+    };
+    let drained = tokio::time::timeout(timeout, async {
+        while buffered_pdata() > 0 {
+            tokio::time::sleep(DRAIN_CHECK_INTERVAL).await;
+        }
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!(
+            node,
+            remaining = buffered_pdata(),
+            "Output channel not drained within the drain timeout"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracing spans around the lifecycle of the nodes, to debug pipeline stalls.
+//!
+//! With the `tracing-spans` feature, the node wrappers create:
+//! - a `receiver` span covering the whole run of a receiver,
+//! - a `control_msg` span covering the handling of each control message by a node wrapper, with
+//!   the type of the message and the deadline of a `Shutdown`,
+//! - a `send_message` span covering each pdata message sent by a receiver, following from the
+//!   span carried by the message if any (see the `set_pdata_trace_context` method of the receiver
+//!   wrapper).
+//!
+//! Without the feature, these functions return disabled spans and instrumenting a future with them
+//! is a no-op.
+
+use crate::message::ControlMsg;
+use tracing::Span;
+use tracing::span::Id;
+
+/// Returns the span covering the run of the given receiver.
+#[cfg(feature = "tracing-spans")]
+pub(crate) fn receiver_span(receiver: &str) -> Span {
+    tracing::info_span!("receiver", receiver)
+}
+
+/// Returns the span covering the run of the given receiver.
+#[cfg(not(feature = "tracing-spans"))]
+pub(crate) fn receiver_span(_receiver: &str) -> Span {
+    Span::none()
+}
+
+/// Returns the span covering the handling of a control message by the wrapper of the given node.
+#[cfg(feature = "tracing-spans")]
+pub(crate) fn control_msg_span(node: &str, msg: &ControlMsg) -> Span {
+    let span = tracing::info_span!(
+        "control_msg",
+        node,
+        message_type = msg.kind(),
+        shutdown_deadline = tracing::field::Empty
+    );
+    if let ControlMsg::Shutdown { deadline, .. } = msg {
+        _ = span.record("shutdown_deadline", tracing::field::debug(deadline));
+    }
+    span
+}
+
+/// Returns the span covering the handling of a control message by the wrapper of the given node.
+#[cfg(not(feature = "tracing-spans"))]
+pub(crate) fn control_msg_span(_node: &str, _msg: &ControlMsg) -> Span {
+    Span::none()
+}
+
+/// Returns the span covering a pdata message sent by the given receiver, following from the span
+/// returned by `trace_context`, if any.
+#[cfg(feature = "tracing-spans")]
+pub(crate) fn send_message_span(
+    receiver: &str,
+    trace_context: impl FnOnce() -> Option<Id>,
+) -> Span {
+    let span = tracing::debug_span!("send_message", receiver);
+    if let Some(context) = trace_context() {
+        _ = span.follows_from(context);
+    }
+    span
+}
+
+/// Returns the span covering a pdata message sent by the given receiver, following from the span
+/// returned by `trace_context`, if any.
+#[cfg(not(feature = "tracing-spans"))]
+pub(crate) fn send_message_span(
+    _receiver: &str,
+    _trace_context: impl FnOnce() -> Option<Id>,
+) -> Span {
+    Span::none()
+}
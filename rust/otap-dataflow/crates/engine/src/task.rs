@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracking of the named subtasks spawned by the nodes.
+//!
+//! Nodes that need background work (e.g. a periodic scraper next to their main loop) spawn it
+//! through their effect handler (see the local receiver's `spawn_local_named` and the shared
+//! receiver's `spawn_named`) instead of calling `tokio::task::spawn_local` or `tokio::spawn`
+//! directly, which also rules out a shared receiver calling `spawn_local` outside of a `LocalSet`. The subtask runs within a tracing span carrying the names
+//! of the node and of the subtask, and is registered in the [`TaskRegistry`] of the node. The node
+//! can await the returned [`TaskHandle`] during its shutdown, and the subtasks still running once
+//! the node completed are aborted by the node wrapper so that they never outlive their node.
+//!
+//! Local receivers can also offload CPU-heavy work (e.g. decompression, Arrow schema parsing) to
+//! the blocking thread pool of Tokio (see the local receiver's `spawn_blocking`). A blocking task
+//! can't be interrupted once started: aborting it only cancels it if it is still queued, and
+//! otherwise discards its output.
+
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+use tracing::Instrument;
+
+/// The name under which the blocking tasks of a node are registered.
+pub(crate) const BLOCKING_TASK_NAME: &str = "blocking";
+
+/// A handle to a subtask spawned by a node. Awaiting it returns the output of the subtask, or a
+/// [`JoinError`] if the subtask panicked or was aborted.
+#[derive(Debug)]
+pub struct TaskHandle<T> {
+    name: Cow<'static, str>,
+    handle: JoinHandle<T>,
+}
+
+impl<T> TaskHandle<T> {
+    /// Returns the name of the subtask.
+    #[must_use]
+    pub fn name(&self) -> Cow<'static, str> {
+        self.name.clone()
+    }
+
+    /// Returns true if the subtask completed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Aborts the subtask.
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle).poll(cx)
+    }
+}
+
+/// The registry of the subtasks spawned by a node, shared by all the clones of its effect handler.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone, Default)]
+pub(crate) struct TaskRegistry {
+    tasks: Arc<Mutex<Vec<(Cow<'static, str>, AbortHandle)>>>,
+}
+
+impl TaskRegistry {
+    /// Spawns a subtask of the given node on the current `LocalSet`, within a tracing span
+    /// carrying the names of the node and of the subtask.
+    pub(crate) fn spawn_local<F>(
+        &self,
+        node: &str,
+        name: Cow<'static, str>,
+        task: F,
+    ) -> TaskHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let span = tracing::info_span!("subtask", node, task = %name);
+        let handle = tokio::task::spawn_local(task.instrument(span));
+        self.register(name, handle)
+    }
+
+    /// Spawns a subtask of the given node on the Tokio runtime, within a tracing span carrying the
+    /// names of the node and of the subtask.
+    pub(crate) fn spawn<F>(
+        &self,
+        node: &str,
+        name: Cow<'static, str>,
+        task: F,
+    ) -> TaskHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let span = tracing::info_span!("subtask", node, task = %name);
+        let handle = tokio::spawn(task.instrument(span));
+        self.register(name, handle)
+    }
+
+    /// Runs a blocking function of the given node on the blocking thread pool of Tokio, within a
+    /// tracing span carrying the name of the node.
+    pub(crate) fn spawn_blocking<F, R>(&self, node: &str, f: F) -> TaskHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let span = tracing::info_span!("blocking_task", node);
+        let handle = tokio::task::spawn_blocking(move || span.in_scope(f));
+        self.register(Cow::Borrowed(BLOCKING_TASK_NAME), handle)
+    }
+
+    fn register<T>(&self, name: Cow<'static, str>, handle: JoinHandle<T>) -> TaskHandle<T> {
+        let mut tasks = self.lock();
+        tasks.retain(|(_, task)| !task.is_finished());
+        tasks.push((name.clone(), handle.abort_handle()));
+        TaskHandle { name, handle }
+    }
+
+    /// Returns the names of the subtasks still running.
+    pub(crate) fn running_tasks(&self) -> Vec<Cow<'static, str>> {
+        let mut tasks = self.lock();
+        tasks.retain(|(_, task)| !task.is_finished());
+        tasks.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Aborts the subtasks still running.
+    pub(crate) fn abort_all(&self) {
+        for (name, task) in self.lock().drain(..) {
+            if !task.is_finished() {
+                tracing::debug!(task = %name, "Aborting a subtask that outlived its node");
+                task.abort();
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(Cow<'static, str>, AbortHandle)>> {
+        // The registry stays consistent even if a thread panicked while holding the lock.
+        self.tasks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
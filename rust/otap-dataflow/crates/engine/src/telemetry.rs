@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pull-based collection of the internal counters of the nodes.
+//!
+//! The effect handlers count the pdata messages they send (and drop), and the message channels
+//! count the pdata messages they deliver to the nodes. A [`ControlMsg::CollectTelemetry`] sent to
+//! a node is answered by its node wrapper with a [`NodeTelemetry`] snapshot of these counters,
+//! whether or not the node itself handles control messages.
+//!
+//! The send counters of a receiver can also be pushed to a [`MetricsSink`] as they are updated
+//! (see the `metrics_sink` field of the [`ReceiverConfig`](crate::config::ReceiverConfig)).
+//!
+//! Once a receiver shut down cleanly, its wrapper publishes a [`ShutdownReport`] summarizing the
+//! pdata emitted and dropped by the receiver over its run.
+//!
+//! [`ControlMsg::CollectTelemetry`]: crate::message::ControlMsg::CollectTelemetry
+
+use crate::error::ErrorCode;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::AddAssign;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of the internal counters of a node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeTelemetry {
+    /// The number of pdata messages delivered to the node (always 0 for receivers).
+    pub messages_received: u64,
+    /// The number of pdata messages sent by the node through its effect handler.
+    pub messages_sent: u64,
+    /// The size in bytes of the pdata messages sent by the node (only counted by the receivers
+    /// with a pdata size function, see the `set_pdata_size` method of the receiver wrapper).
+    pub bytes_sent: u64,
+    /// The number of pdata messages dropped because the output channel was full (see
+    /// [`crate::config::OverflowPolicy`]).
+    pub messages_dropped: u64,
+    /// The number of pdata messages the effect handler failed to send.
+    pub errors: u64,
+}
+
+/// The send metrics of a receiver (see the `metrics` method of the receiver effect handlers).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReceiverMetrics {
+    /// The number of pdata messages sent by the receiver.
+    pub messages_sent: u64,
+    /// The size in bytes of the pdata messages sent by the receiver, 0 if the receiver has no
+    /// pdata size function.
+    pub bytes_sent: u64,
+    /// The number of pdata messages the receiver failed to send.
+    pub send_errors: u64,
+}
+
+impl fmt::Display for ReceiverMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "messages_sent={} bytes_sent={} send_errors={}",
+            self.messages_sent, self.bytes_sent, self.send_errors
+        )
+    }
+}
+
+/// The summary of the run of a receiver which shut down cleanly (see the
+/// `subscribe_shutdown_report` method of the receiver wrapper). The reports of several receivers
+/// add up, e.g. for the end-of-run summary of a pipeline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// The number of pdata messages sent by the receiver.
+    pub messages_emitted: u64,
+    /// The number of pdata messages dropped by the receiver (see
+    /// [`crate::config::OverflowPolicy`]).
+    pub messages_dropped: u64,
+    /// The number of connections served by the receiver (see the `spawn_connection` method of
+    /// the receiver effect handlers) which were closed, either by the peer or on shutdown.
+    pub connections_closed: u64,
+}
+
+impl AddAssign for ShutdownReport {
+    fn add_assign(&mut self, other: Self) {
+        self.messages_emitted += other.messages_emitted;
+        self.messages_dropped += other.messages_dropped;
+        self.connections_closed += other.connections_closed;
+    }
+}
+
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "messages_emitted={} messages_dropped={} connections_closed={}",
+            self.messages_emitted, self.messages_dropped, self.connections_closed
+        )
+    }
+}
+
+/// Name of the counter of the pdata messages sent by a receiver.
+pub const MESSAGES_SENT: &str = "messages_sent";
+/// Name of the counter of the pdata messages a receiver failed to send.
+pub const SEND_ERRORS: &str = "send_errors";
+/// Name of the counter of the size in bytes of the pdata messages sent by a receiver.
+pub const BYTES_SENT: &str = "bytes_sent";
+/// Name of the counter of the restarts of a failed receiver (see the `restart` field of the
+/// [`ReceiverConfig`](crate::config::ReceiverConfig)).
+pub const RESTARTS: &str = "restarts";
+/// Name of the label identifying the receiver a counter belongs to.
+pub const NODE_LABEL: &str = "node";
+/// Name of the label carrying the code of the error that triggered a restart (see
+/// [`ErrorCode`]).
+pub const ERROR_CODE_LABEL: &str = "error_code";
+
+/// A destination of the counters of the receivers, e.g. a metrics exporter.
+///
+/// The counters ([`MESSAGES_SENT`], [`SEND_ERRORS`] and [`BYTES_SENT`]) are pushed as increments
+/// when a receiver sends pdata messages, labeled with the name of the receiver ([`NODE_LABEL`]).
+/// The [`RESTARTS`] counter is pushed when a failed receiver is restarted, additionally labeled
+/// with the code of the error that triggered the restart ([`ERROR_CODE_LABEL`]).
+pub trait MetricsSink: Send + Sync {
+    /// Increments the counter with the given name and labels by `value`.
+    fn counter(&self, name: &str, value: u64, labels: &[(&str, &str)]);
+}
+
+/// A [`MetricsSink`] discarding all the counters, used by the nodes without a configured sink.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopMetricsSink;
+
+impl MetricsSink for NoopMetricsSink {
+    fn counter(&self, _name: &str, _value: u64, _labels: &[(&str, &str)]) {}
+}
+
+/// The counters of a node, shared by all the clones of its effect handler and by its message
+/// channel.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone)]
+pub(crate) struct TelemetryCounters {
+    inner: Arc<Counters>,
+    /// The sink the send counters are pushed to.
+    sink: Arc<dyn MetricsSink>,
+    /// The name of the node, used to label the counters pushed to the sink.
+    node: Cow<'static, str>,
+}
+
+impl Default for TelemetryCounters {
+    fn default() -> Self {
+        TelemetryCounters {
+            inner: Arc::default(),
+            sink: Arc::new(NoopMetricsSink),
+            node: Cow::Borrowed(""),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    messages_received: AtomicU64,
+    messages_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    messages_dropped: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl TelemetryCounters {
+    /// Pushes the send counters of the given node to the given sink. Must be called before the
+    /// counters are cloned.
+    pub(crate) fn set_sink(&mut self, sink: Arc<dyn MetricsSink>, node: Cow<'static, str>) {
+        self.sink = sink;
+        self.node = node;
+    }
+
+    /// Increments the counter with the given name in the sink.
+    fn push(&self, name: &str, value: u64) {
+        self.sink.counter(name, value, &[(NODE_LABEL, &self.node)]);
+    }
+
+    /// Counts a pdata message delivered to the node.
+    pub(crate) fn record_received(&self) {
+        _ = self.inner.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a pdata message sent by the node.
+    pub(crate) fn record_sent(&self) {
+        _ = self.inner.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.push(MESSAGES_SENT, 1);
+    }
+
+    /// Counts a batch of pdata messages sent by the node.
+    pub(crate) fn record_sent_batch(&self, count: usize) {
+        _ = self
+            .inner
+            .messages_sent
+            .fetch_add(count as u64, Ordering::Relaxed);
+        self.push(MESSAGES_SENT, count as u64);
+    }
+
+    /// Counts the size of a pdata message sent by the node.
+    pub(crate) fn record_bytes(&self, bytes: usize) {
+        _ = self
+            .inner
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if bytes > 0 {
+            self.push(BYTES_SENT, bytes as u64);
+        }
+    }
+
+    /// Counts a pdata message dropped by the node.
+    pub(crate) fn record_dropped(&self) {
+        _ = self.inner.messages_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a pdata message the node failed to send.
+    pub(crate) fn record_error(&self) {
+        _ = self.inner.errors.fetch_add(1, Ordering::Relaxed);
+        self.push(SEND_ERRORS, 1);
+    }
+
+    /// Counts a restart of the node after it failed with an error of the given code.
+    pub(crate) fn record_restart(&self, code: ErrorCode) {
+        let code = format!("{code:?}");
+        self.sink.counter(
+            RESTARTS,
+            1,
+            &[(NODE_LABEL, &self.node), (ERROR_CODE_LABEL, &code)],
+        );
+    }
+
+    /// Counts the outcome of sending a pdata message, and returns it.
+    pub(crate) fn record_send<T, E>(&self, result: Result<T, E>) -> Result<T, E> {
+        match &result {
+            Ok(_) => self.record_sent(),
+            Err(_) => self.record_error(),
+        }
+        result
+    }
+
+    /// Counts the outcome of sending a pdata message of the given size, and returns it.
+    pub(crate) fn record_send_sized<T, E>(
+        &self,
+        result: Result<T, E>,
+        bytes: usize,
+    ) -> Result<T, E> {
+        if result.is_ok() {
+            self.record_bytes(bytes);
+        }
+        self.record_send(result)
+    }
+
+    /// Returns the number of pdata messages dropped so far.
+    #[must_use]
+    pub(crate) fn dropped(&self) -> u64 {
+        self.inner.messages_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns a snapshot of the counters.
+    #[must_use]
+    pub(crate) fn snapshot(&self) -> NodeTelemetry {
+        NodeTelemetry {
+            messages_received: self.inner.messages_received.load(Ordering::Relaxed),
+            messages_sent: self.inner.messages_sent.load(Ordering::Relaxed),
+            bytes_sent: self.inner.bytes_sent.load(Ordering::Relaxed),
+            messages_dropped: self.dropped(),
+            errors: self.inner.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the send metrics of a receiver.
+    #[must_use]
+    pub(crate) fn receiver_metrics(&self) -> ReceiverMetrics {
+        ReceiverMetrics {
+            messages_sent: self.inner.messages_sent.load(Ordering::Relaxed),
+            bytes_sent: self.inner.bytes_sent.load(Ordering::Relaxed),
+            send_errors: self.inner.errors.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -7,10 +7,9 @@
 
 use crate::config::ExporterConfig;
 use crate::exporter::ExporterWrapper;
-use crate::message::{ControlMsg, Receiver, Sender};
+use crate::message::{ControlMsg, NodeConfigUpdate, Receiver, Sender};
 use crate::testing::{CtrlMsgCounters, create_not_send_channel, setup_test_runtime};
 use otap_df_channel::error::SendError;
-use serde_json::Value;
 use std::fmt::Debug;
 use std::future::Future;
 use std::marker::PhantomData;
@@ -66,13 +65,27 @@ impl<PData> TestContext<PData> {
         self.control_tx.send(ControlMsg::TimerTick {}).await
     }
 
-    /// Sends a config control message.
+    /// Sends a config control message carrying the given configuration update.
     ///
     /// # Errors
     ///
     /// Returns an error if the message could not be sent.
-    pub async fn send_config(&self, config: Value) -> Result<(), SendError<ControlMsg>> {
-        self.control_tx.send(ControlMsg::Config { config }).await
+    pub async fn send_config(&self, update: NodeConfigUpdate) -> Result<(), SendError<ControlMsg>> {
+        self.control_tx.send(ControlMsg::Config { update }).await
+    }
+
+    /// Sends a flush control message with the given correlation id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent.
+    pub async fn send_flush(&self, id: u64, reason: &str) -> Result<(), SendError<ControlMsg>> {
+        self.control_tx
+            .send(ControlMsg::Flush {
+                id,
+                reason: reason.to_owned(),
+            })
+            .await
     }
 
     /// Sends a shutdown control message.
@@ -159,7 +172,7 @@ pub struct ValidationPhase<PData> {
 }
 
 impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
-    /// Creates a new test runtime with channels of the specified capacity.
+    /// Creates a new test runtime with channels of the default capacities.
     pub fn new() -> Self {
         let config = ExporterConfig::new("test_exporter");
         let (rt, local_tasks) = setup_test_runtime();
@@ -174,6 +187,16 @@ impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
         }
     }
 
+    /// Creates a new test runtime whose exporter configuration has a control channel and an input
+    /// pdata channel of the given capacities.
+    #[must_use]
+    pub fn with_capacity(control: usize, pdata: usize) -> Self {
+        let mut test_runtime = Self::new();
+        test_runtime.config.control_channel.capacity = control;
+        test_runtime.config.input_pdata_channel.capacity = pdata;
+        test_runtime
+    }
+
     /// Returns the current exporter configuration.
     pub fn config(&self) -> &ExporterConfig {
         &self.config
@@ -185,39 +208,24 @@ impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
     }
 
     /// Sets the exporter for the test runtime and returns the test phase.
-    pub fn set_exporter(self, exporter: ExporterWrapper<PData>) -> TestPhase<PData> {
-        let (control_tx, control_rx, pdata_tx, pdata_rx) = match &exporter {
+    pub fn set_exporter(self, mut exporter: ExporterWrapper<PData>) -> TestPhase<PData> {
+        let control_tx = exporter.control_sender();
+        let (pdata_tx, pdata_rx) = match &exporter {
             ExporterWrapper::Local { .. } => {
-                let (control_tx, control_rx) =
-                    create_not_send_channel(self.config.control_channel.capacity);
                 let (pdata_tx, pdata_rx) =
-                    create_not_send_channel(self.config.control_channel.capacity);
-                (
-                    Sender::Local(control_tx),
-                    Receiver::Local(control_rx),
-                    Sender::Local(pdata_tx),
-                    Receiver::Local(pdata_rx),
-                )
+                    create_not_send_channel(self.config.input_pdata_channel.capacity);
+                (Sender::Local(pdata_tx), Receiver::Local(pdata_rx))
             }
             ExporterWrapper::Shared { .. } => {
-                let (control_tx, control_rx) =
-                    tokio::sync::mpsc::channel(self.config.control_channel.capacity);
                 let (pdata_tx, pdata_rx) =
-                    tokio::sync::mpsc::channel(self.config.control_channel.capacity);
-                (
-                    Sender::Shared(control_tx),
-                    Receiver::Shared(control_rx),
-                    Sender::Shared(pdata_tx),
-                    Receiver::Shared(pdata_rx),
-                )
+                    tokio::sync::mpsc::channel(self.config.input_pdata_channel.capacity);
+                (Sender::Shared(pdata_tx), Receiver::Shared(pdata_rx))
             }
         };
+        exporter.connect_input(pdata_rx);
 
         let run_exporter_handle = self.local_tasks.spawn_local(async move {
-            exporter
-                .start(control_rx, pdata_rx)
-                .await
-                .expect("Exporter event loop failed");
+            exporter.start().await.expect("Exporter event loop failed");
         });
         TestPhase {
             rt: self.rt,
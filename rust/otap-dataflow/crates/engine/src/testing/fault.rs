@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fault injection in the effect handlers of the nodes under test.
+//!
+//! A [`FaultInjector`] installed in the effect handler of a receiver (see the
+//! `inject_send_failure` and `inject_channel_full` methods of the receiver
+//! [`TestRuntime`](super::receiver::TestRuntime)) makes its `send_message` method fail
+//! deterministically, so that the error paths of the receiver can be exercised without a
+//! misbehaving downstream node.
+//!
+//! Only available in the tests of this crate and with the `testing` feature, so that the send path
+//! of the production effect handlers doesn't check for injected faults.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Value of the send countdown when no send failure is injected.
+const NO_SEND_FAILURE: usize = usize::MAX;
+
+/// A fault injected in a `send_message` call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InjectedFault {
+    /// The output channel is reported as closed.
+    SendFailure,
+    /// The output channel is reported as full.
+    ChannelFull,
+}
+
+/// The faults injected in the effect handler of a node, and the count of the faults triggered so
+/// far. Cloning the injector shares its state.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone, Debug)]
+pub struct FaultInjector {
+    /// Number of sends to let through before the injected send failure, or `NO_SEND_FAILURE`.
+    sends_before_failure: Arc<AtomicUsize>,
+    channel_full: Arc<AtomicBool>,
+    injected_failure_count: Arc<AtomicUsize>,
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        FaultInjector {
+            sends_before_failure: Arc::new(AtomicUsize::new(NO_SEND_FAILURE)),
+            channel_full: Arc::new(AtomicBool::new(false)),
+            injected_failure_count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl FaultInjector {
+    /// Creates an injector without any fault.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the send following the next `after_n` successful sends fail as if the output channel
+    /// was closed. The failure is injected once.
+    pub fn fail_send_after(&self, after_n: usize) {
+        self.sends_before_failure.store(after_n, Ordering::Relaxed);
+    }
+
+    /// Makes every send behave as if the output channel was full, until cleared.
+    pub fn set_channel_full(&self, channel_full: bool) {
+        self.channel_full.store(channel_full, Ordering::Relaxed);
+    }
+
+    /// Returns the number of sends that failed because of an injected fault.
+    #[must_use]
+    pub fn get_injected_failure_count(&self) -> usize {
+        self.injected_failure_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the fault to inject in the current send, if any.
+    pub(crate) fn on_send(&self) -> Option<InjectedFault> {
+        let countdown = self.sends_before_failure.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |n| match n {
+                NO_SEND_FAILURE => None,
+                0 => Some(NO_SEND_FAILURE),
+                n => Some(n - 1),
+            },
+        );
+        let fault = if countdown == Ok(0) {
+            InjectedFault::SendFailure
+        } else if self.channel_full.load(Ordering::Relaxed) {
+            InjectedFault::ChannelFull
+        } else {
+            return None;
+        };
+        _ = self.injected_failure_count.fetch_add(1, Ordering::Relaxed);
+        Some(fault)
+    }
+}
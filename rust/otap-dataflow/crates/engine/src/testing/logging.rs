@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! An in-memory [`LogSink`] collecting the records logged by the nodes under test.
+
+use crate::logging::{LogRecord, LogSink};
+use std::sync::Mutex;
+
+/// A [`LogSink`] collecting the records in memory, in the order they were logged.
+///
+/// Note: This implementation is `Send`.
+#[derive(Debug, Default)]
+pub struct InMemoryLogSink {
+    records: Mutex<Vec<LogRecord>>,
+}
+
+impl InMemoryLogSink {
+    /// Creates an empty sink.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the records logged so far.
+    #[must_use]
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().expect("Log sink lock poisoned").clone()
+    }
+}
+
+impl LogSink for InMemoryLogSink {
+    fn log(&self, record: &LogRecord) {
+        self.records
+            .lock()
+            .expect("Log sink lock poisoned")
+            .push(record.clone());
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! An in-memory [`MetricsSink`] recording the counters pushed by the nodes under test.
+
+use crate::telemetry::MetricsSink;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A [`MetricsSink`] accumulating the counters in memory, keyed by name and labels.
+///
+/// Note: This implementation is `Send`.
+#[derive(Debug, Default)]
+pub struct InMemoryMetricsSink {
+    counters: Mutex<HashMap<(String, Vec<(String, String)>), u64>>,
+}
+
+impl InMemoryMetricsSink {
+    /// Creates an empty sink.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the counter with the given name, summed over all its labels.
+    #[must_use]
+    pub fn counter_value(&self, name: &str) -> u64 {
+        self.counters
+            .lock()
+            .expect("Metrics sink lock poisoned")
+            .iter()
+            .filter(|((counter, _), _)| counter == name)
+            .map(|(_, value)| value)
+            .sum()
+    }
+
+    /// Returns the value of the counter with the given name and labels.
+    #[must_use]
+    pub fn labeled_counter_value(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        let key = Self::key(name, labels);
+        self.counters
+            .lock()
+            .expect("Metrics sink lock poisoned")
+            .get(&key)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn key(name: &str, labels: &[(&str, &str)]) -> (String, Vec<(String, String)>) {
+        let mut labels: Vec<_> = labels
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect();
+        labels.sort();
+        (name.to_owned(), labels)
+    }
+}
+
+impl MetricsSink for InMemoryMetricsSink {
+    fn counter(&self, name: &str, value: u64, labels: &[(&str, &str)]) {
+        *self
+            .counters
+            .lock()
+            .expect("Metrics sink lock poisoned")
+            .entry(Self::key(name, labels))
+            .or_default() += value;
+    }
+}
@@ -9,6 +9,10 @@
 //! - Counter mechanisms for tracking control message processing
 //! - Utilities for setting up single-threaded async test runtimes
 //! - Channel creation helpers for connecting components
+//! - Fault injection in the effect handlers, to exercise the error paths of the components (with
+//!   the `testing` feature)
+//! - An in-memory metrics sink recording the counters pushed by the components
+//! - An in-memory log sink collecting the messages logged by the components
 //!
 //! The specialized testing utilities for receivers, processors, and exporters are in their respective
 //! submodules.
@@ -21,6 +25,10 @@ use tokio::runtime::Builder;
 use tokio::task::LocalSet;
 
 pub mod exporter;
+#[cfg(any(test, feature = "testing"))]
+pub mod fault;
+pub mod logging;
+pub mod metrics;
 pub mod processor;
 pub mod receiver;
 
@@ -43,7 +51,14 @@ pub struct CtrlMsgCounters {
     timer_tick_count: Arc<AtomicUsize>,
     message_count: Arc<AtomicUsize>,
     config_count: Arc<AtomicUsize>,
+    reconfigure_count: Arc<AtomicUsize>,
     shutdown_count: Arc<AtomicUsize>,
+    ack_count: Arc<AtomicUsize>,
+    nack_count: Arc<AtomicUsize>,
+    flush_count: Arc<AtomicUsize>,
+    throttle_count: Arc<AtomicUsize>,
+    pause_count: Arc<AtomicUsize>,
+    resume_count: Arc<AtomicUsize>,
 }
 
 impl CtrlMsgCounters {
@@ -53,17 +68,37 @@ impl CtrlMsgCounters {
             timer_tick_count: Arc::new(AtomicUsize::new(0)),
             message_count: Arc::new(AtomicUsize::new(0)),
             config_count: Arc::new(AtomicUsize::new(0)),
+            reconfigure_count: Arc::new(AtomicUsize::new(0)),
             shutdown_count: Arc::new(AtomicUsize::new(0)),
+            ack_count: Arc::new(AtomicUsize::new(0)),
+            nack_count: Arc::new(AtomicUsize::new(0)),
+            flush_count: Arc::new(AtomicUsize::new(0)),
+            throttle_count: Arc::new(AtomicUsize::new(0)),
+            pause_count: Arc::new(AtomicUsize::new(0)),
+            resume_count: Arc::new(AtomicUsize::new(0)),
         }
     }
 
     /// Handles incoming control messages and increments the appropriate counter.
     pub fn update_with(&self, msg: &ControlMsg) {
         match msg {
-            ControlMsg::TimerTick { .. } => self.increment_timer_tick(),
+            ControlMsg::TimerTick { .. } | ControlMsg::NodeTimer { .. } => {
+                self.increment_timer_tick();
+            }
             ControlMsg::Config { .. } => self.increment_config(),
+            ControlMsg::Reconfigure { .. } => self.increment_reconfigure(),
             ControlMsg::Shutdown { .. } => self.increment_shutdown(),
-            _ => {}
+            ControlMsg::Ack { .. } => self.increment_ack(),
+            ControlMsg::Nack { .. } => self.increment_nack(),
+            ControlMsg::Flush { .. } => self.increment_flush(),
+            ControlMsg::Throttle { .. } => self.increment_throttle(),
+            ControlMsg::Pause => self.increment_pause(),
+            ControlMsg::Resume => self.increment_resume(),
+            // Only delivered to restarted receivers, not counted.
+            ControlMsg::Restarting { .. } => {}
+            // Answered by the control channels and the node wrappers, never delivered to the
+            // nodes.
+            ControlMsg::HealthCheck { .. } | ControlMsg::CollectTelemetry { .. } => {}
         }
     }
 
@@ -88,6 +123,13 @@ impl CtrlMsgCounters {
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
 
+    /// Increments the reconfigure count.
+    pub fn increment_reconfigure(&self) {
+        _ = self
+            .reconfigure_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// Increments the shutdown count.
     pub fn increment_shutdown(&self) {
         _ = self
@@ -95,6 +137,48 @@ impl CtrlMsgCounters {
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
 
+    /// Increments the ack count.
+    pub fn increment_ack(&self) {
+        _ = self
+            .ack_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Increments the nack count.
+    pub fn increment_nack(&self) {
+        _ = self
+            .nack_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Increments the flush count.
+    pub fn increment_flush(&self) {
+        _ = self
+            .flush_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Increments the throttle count.
+    pub fn increment_throttle(&self) {
+        _ = self
+            .throttle_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Increments the pause count.
+    pub fn increment_pause(&self) {
+        _ = self
+            .pause_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Increments the resume count.
+    pub fn increment_resume(&self) {
+        _ = self
+            .resume_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// Gets the current timer tick count.
     pub fn get_timer_tick_count(&self) -> usize {
         self.timer_tick_count
@@ -112,12 +196,56 @@ impl CtrlMsgCounters {
         self.config_count.load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Gets the current reconfigure count.
+    #[must_use]
+    pub fn get_reconfigure_count(&self) -> usize {
+        self.reconfigure_count
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
     /// Gets the current shutdown count.
     pub fn get_shutdown_count(&self) -> usize {
         self.shutdown_count
             .load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Gets the current ack count.
+    #[must_use]
+    pub fn get_ack_count(&self) -> usize {
+        self.ack_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Gets the current nack count.
+    #[must_use]
+    pub fn get_nack_count(&self) -> usize {
+        self.nack_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Gets the current flush count.
+    #[must_use]
+    pub fn get_flush_count(&self) -> usize {
+        self.flush_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Gets the current throttle count.
+    #[must_use]
+    pub fn get_throttle_count(&self) -> usize {
+        self.throttle_count
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Gets the current pause count.
+    #[must_use]
+    pub fn get_pause_count(&self) -> usize {
+        self.pause_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Gets the current resume count.
+    #[must_use]
+    pub fn get_resume_count(&self) -> usize {
+        self.resume_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
     /// Asserts that the current counters match the expected values.
     pub fn assert(
         &self,
@@ -125,6 +253,8 @@ impl CtrlMsgCounters {
         message_count: usize,
         config_count: usize,
         shutdown_count: usize,
+        pause_count: usize,
+        resume_count: usize,
     ) {
         assert_eq!(
             self.get_timer_tick_count(),
@@ -146,6 +276,21 @@ impl CtrlMsgCounters {
             shutdown_count,
             "Shutdown count mismatch"
         );
+        assert_eq!(self.get_pause_count(), pause_count, "Pause count mismatch");
+        assert_eq!(
+            self.get_resume_count(),
+            resume_count,
+            "Resume count mismatch"
+        );
+    }
+
+    /// Asserts that the current throttle count matches the expected value.
+    pub fn assert_throttle(&self, throttle_count: usize) {
+        assert_eq!(
+            self.get_throttle_count(),
+            throttle_count,
+            "Throttle count mismatch"
+        );
     }
 }
 
@@ -159,6 +304,21 @@ pub fn setup_test_runtime() -> (tokio::runtime::Runtime, LocalSet) {
     (rt, local_tasks)
 }
 
+/// Creates a single-threaded runtime with a virtual clock and a local task set for testing
+/// components: time only advances when the runtime is idle, so that sleeps, timeouts and timers
+/// complete deterministically and without wall-clock delay (see `TestRuntime::with_virtual_time`
+/// in the [`receiver`] module).
+#[cfg(any(test, feature = "testing"))]
+pub fn setup_virtual_time_runtime() -> (tokio::runtime::Runtime, LocalSet) {
+    let rt = Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .expect("Failed to create new runtime");
+    let local_tasks = LocalSet::new();
+    (rt, local_tasks)
+}
+
 /// Helper to create `!Send` MPSC channels with a specific capacity.
 ///
 /// This function creates a sender-receiver pair with the given capacity.
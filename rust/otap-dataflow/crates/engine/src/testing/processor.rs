@@ -110,7 +110,7 @@ pub struct ValidationPhase {
 }
 
 impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
-    /// Creates a new test runtime with channels of the specified capacity.
+    /// Creates a new test runtime with channels of the default capacities.
     pub fn new() -> Self {
         let config = ProcessorConfig::new("test_processor");
         let (rt, local_tasks) = setup_test_runtime();
@@ -124,6 +124,17 @@ impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
         }
     }
 
+    /// Creates a new test runtime whose processor configuration has a control channel, and input
+    /// and output pdata channels, of the given capacities.
+    #[must_use]
+    pub fn with_capacity(control: usize, pdata: usize) -> Self {
+        let mut test_runtime = Self::new();
+        test_runtime.config.control_channel.capacity = control;
+        test_runtime.config.input_pdata_channel.capacity = pdata;
+        test_runtime.config.output_pdata_channel.capacity = pdata;
+        test_runtime
+    }
+
     /// Returns the current receiver configuration.
     pub fn config(&self) -> &ProcessorConfig {
         &self.config
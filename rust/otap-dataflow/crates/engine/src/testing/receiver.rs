@@ -5,16 +5,25 @@
 //! These utilities are designed to make testing receivers simpler by abstracting away common
 //! setup and lifecycle management.
 
-use crate::config::ReceiverConfig;
+use crate::config::{DrainPolicy, ReceiverConfig};
 use crate::error::Error;
-use crate::message::{ControlMsg, Receiver, Sender};
+use crate::flush_ack::FlushAckWatcher;
+use crate::lifecycle::LifecycleState;
+use crate::message::{ControlMsg, NodeConfigUpdate, Receiver, ReconfigurePayload, Sender};
 use crate::receiver::ReceiverWrapper;
+use crate::telemetry::NodeTelemetry;
+#[cfg(any(test, feature = "testing"))]
+use crate::testing::fault::FaultInjector;
+use crate::testing::logging::InMemoryLogSink;
 use crate::testing::{CtrlMsgCounters, setup_test_runtime};
 use otap_df_channel::error::RecvError;
 use serde_json::Value;
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 use tokio::task::LocalSet;
 use tokio::time::sleep;
 
@@ -22,6 +31,12 @@ use tokio::time::sleep;
 pub struct TestContext {
     /// Sender for control messages
     control_sender: Sender<ControlMsg>,
+    /// Watcher of the flushes acknowledged by the receiver
+    flush_acks: FlushAckWatcher,
+    /// Receiver of the addresses bound by the receiver
+    bound_addresses: watch::Receiver<Vec<SocketAddr>>,
+    /// Receiver of the lifecycle states of the receiver
+    lifecycle: watch::Receiver<LifecycleState>,
 }
 
 /// Context used during the validation phase of a test (!Send context).
@@ -49,18 +64,103 @@ impl TestContext {
             .map_err(Error::ChannelSendError)
     }
 
-    /// Sends a config control message.
+    /// Sends a config control message carrying the given configuration update.
     ///
     /// # Errors
     ///
     /// Returns an error if the message could not be sent.
-    pub async fn send_config(&self, config: Value) -> Result<(), Error<ControlMsg>> {
+    pub async fn send_config(&self, update: NodeConfigUpdate) -> Result<(), Error<ControlMsg>> {
         self.control_sender
-            .send(ControlMsg::Config { config })
+            .send(ControlMsg::Config { update })
             .await
             .map_err(Error::ChannelSendError)
     }
 
+    /// Sends a reconfigure control message carrying the given configuration update, a JSON merge
+    /// patch against the current configuration of the receiver (see
+    /// [`ReconfigurePayload::apply_to`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent.
+    pub async fn send_reconfigure(&self, config: Value) -> Result<(), Error<ControlMsg>> {
+        self.control_sender
+            .send(ControlMsg::Reconfigure {
+                payload: ReconfigurePayload::new(config),
+            })
+            .await
+            .map_err(Error::ChannelSendError)
+    }
+
+    /// Sends a flush control message and waits for the receiver to acknowledge it (see the
+    /// effect handler's `ack_flush`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent or if the receiver terminated without
+    /// acknowledging the flush.
+    pub async fn send_flush(&self, reason: &str) -> Result<(), Error<ControlMsg>> {
+        self.flush_acks
+            .clone()
+            .flush(&self.control_sender, reason)
+            .await
+    }
+
+    /// Sends a pause control message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent.
+    pub async fn send_pause(&self) -> Result<(), Error<ControlMsg>> {
+        self.control_sender
+            .send(ControlMsg::Pause)
+            .await
+            .map_err(Error::ChannelSendError)
+    }
+
+    /// Sends a throttle control message, asking the receiver to stop ingesting for the given
+    /// duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent.
+    pub async fn send_throttle(&self, duration: Duration) -> Result<(), Error<ControlMsg>> {
+        self.control_sender
+            .send(ControlMsg::Throttle { duration })
+            .await
+            .map_err(Error::ChannelSendError)
+    }
+
+    /// Sends a resume control message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent.
+    pub async fn send_resume(&self) -> Result<(), Error<ControlMsg>> {
+        self.control_sender
+            .send(ControlMsg::Resume)
+            .await
+            .map_err(Error::ChannelSendError)
+    }
+
+    /// Requests a snapshot of the internal counters of the receiver (see [`crate::telemetry`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be sent or if the receiver terminated without
+    /// answering the request.
+    pub async fn collect_telemetry(&self) -> Result<NodeTelemetry, Error<ControlMsg>> {
+        let (reply_to, mut reply_rx) = tokio::sync::mpsc::channel(1);
+        self.control_sender
+            .send(ControlMsg::CollectTelemetry { reply_to })
+            .await
+            .map_err(Error::ChannelSendError)?;
+        reply_rx
+            .recv()
+            .await
+            .ok_or(Error::ChannelRecvError(RecvError::Closed))
+    }
+
     /// Sends a shutdown control message.
     ///
     /// # Errors
@@ -80,10 +180,87 @@ impl TestContext {
             .map_err(Error::ChannelSendError)
     }
 
+    /// Waits for the receiver to bind its first listener or socket (see the effect handler's
+    /// `bound_addresses`), and returns its address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiver terminated without binding any address.
+    pub async fn bound_address(&self) -> Result<SocketAddr, Error<ControlMsg>> {
+        let mut bound_addresses = self.bound_addresses.clone();
+        let addresses = bound_addresses
+            .wait_for(|addresses| !addresses.is_empty())
+            .await
+            .map_err(|_| Error::ChannelRecvError(RecvError::Closed))?;
+        Ok(addresses[0])
+    }
+
+    /// Waits for the receiver to reach the given lifecycle state (see [`wait_for_state`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiver stopped without reaching the state.
+    pub async fn wait_for_state(&self, state: LifecycleState) -> Result<(), Error<ControlMsg>> {
+        wait_for_state(&mut self.lifecycle.clone(), state).await
+    }
+
+    /// Waits for the receiver to signal its readiness with the effect handler's `notify_ready`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiver stopped without being ready.
+    pub async fn wait_ready(&self) -> Result<(), Error<ControlMsg>> {
+        self.wait_for_state(LifecycleState::Ready).await
+    }
+
     /// Sleeps for the specified duration.
+    ///
+    /// Under virtual time (see `TestRuntime::with_virtual_time`), the clock is advanced
+    /// instantly once the runtime is idle.
     pub async fn sleep(&self, duration: Duration) {
         sleep(duration).await;
     }
+
+    /// Advances the virtual clock by the given duration, firing in order the timers due in the
+    /// meantime (e.g. the timer ticks of the receiver) without any wall-clock delay.
+    ///
+    /// Note: A timer due exactly at the end of the advance may only fire once it returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the test runtime does not use virtual time (see
+    /// [`TestRuntime::with_virtual_time`]).
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn advance(&self, duration: Duration) {
+        // Panics if the clock is not paused.
+        tokio::time::advance(Duration::ZERO).await;
+        // Unlike a single `tokio::time::advance`, sleeping lets the runtime jump from one due timer
+        // to the next, so that a timer re-armed in between (e.g. the tick schedule) fires too.
+        sleep(duration).await;
+    }
+}
+
+/// Waits for a receiver to reach the given lifecycle state, observed through the receiver of its
+/// states (see [`ReceiverWrapper::state_watcher`]), e.g. for the tests driving the receiver
+/// without the test runtime.
+///
+/// # Errors
+///
+/// Returns an error if the receiver stopped without reaching the state.
+pub async fn wait_for_state(
+    lifecycle: &mut watch::Receiver<LifecycleState>,
+    state: LifecycleState,
+) -> Result<(), Error<ControlMsg>> {
+    let stopped = |current: &LifecycleState| matches!(current, LifecycleState::Stopped(_));
+    let reached = lifecycle
+        .wait_for(|current| *current == state || stopped(current))
+        .await
+        .map_err(|_| Error::ChannelRecvError(RecvError::Closed))?;
+    if *reached == state {
+        Ok(())
+    } else {
+        Err(Error::ChannelRecvError(RecvError::Closed))
+    }
 }
 
 impl<PData> NotSendValidateContext<PData> {
@@ -129,6 +306,13 @@ pub struct TestRuntime<PData> {
     /// Message counter for tracking processed messages
     counter: CtrlMsgCounters,
 
+    /// Faults injected in the effect handler of the receiver
+    #[cfg(any(test, feature = "testing"))]
+    faults: FaultInjector,
+
+    /// Sink of the messages logged by the receiver
+    log_sink: Arc<InMemoryLogSink>,
+
     _pd: PhantomData<PData>,
 }
 
@@ -140,6 +324,9 @@ pub struct TestPhase<PData> {
     local_tasks: LocalSet,
 
     control_sender: Sender<ControlMsg>,
+    flush_acks: FlushAckWatcher,
+    bound_addresses: watch::Receiver<Vec<SocketAddr>>,
+    lifecycle: watch::Receiver<LifecycleState>,
     receiver: ReceiverWrapper<PData>,
     counters: CtrlMsgCounters,
 }
@@ -156,16 +343,18 @@ pub struct ValidationPhase<PData> {
     pdata_receiver: Receiver<PData>,
 
     /// Join handle for the running the receiver task
-    run_receiver_handle: tokio::task::JoinHandle<()>,
+    run_receiver_handle: tokio::task::JoinHandle<Result<(), Error<PData>>>,
 
     /// Join handle for the running the test task
     run_test_handle: tokio::task::JoinHandle<()>,
 }
 
 impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
-    /// Creates a new test runtime with channels of the specified capacity.
+    /// Creates a new test runtime with channels of the default capacities.
     pub fn new() -> Self {
-        let config = ReceiverConfig::new("test_receiver");
+        let mut config = ReceiverConfig::new("test_receiver");
+        // The pdata are only consumed by the validation phase, once the receiver completed.
+        config.drain_policy = DrainPolicy::Immediate;
         let (rt, local_tasks) = setup_test_runtime();
 
         Self {
@@ -173,10 +362,70 @@ impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
             rt,
             local_tasks,
             counter: CtrlMsgCounters::new(),
+            #[cfg(any(test, feature = "testing"))]
+            faults: FaultInjector::new(),
+            log_sink: Arc::new(InMemoryLogSink::new()),
             _pd: PhantomData,
         }
     }
 
+    /// Creates a new test runtime whose receiver configuration has a control channel and an output
+    /// pdata channel of the given capacities, e.g. to test a receiver against a full channel.
+    #[must_use]
+    pub fn with_capacity(control: usize, pdata: usize) -> Self {
+        let mut test_runtime = Self::new();
+        test_runtime.config.control_channel.capacity = control;
+        test_runtime.config.output_pdata_channel.capacity = pdata;
+        test_runtime
+    }
+
+    /// Makes the test runtime use a virtual clock: time only advances when the runtime is idle or
+    /// on [`TestContext::advance`], so that sleeps, timeouts and timer ticks complete
+    /// deterministically and without wall-clock delay.
+    ///
+    /// Note: An idle runtime jumps to the next pending timer once the I/O events already reported
+    /// by the OS were processed. The tests talking to the sockets of a receiver should wait for
+    /// its replies (or for its bound address, see [`TestContext::bound_address`]) rather than for
+    /// some delay, as a peer outside of the test process may answer after the timer fired.
+    #[cfg(any(test, feature = "testing"))]
+    #[must_use]
+    pub fn with_virtual_time(self) -> Self {
+        let _guard = self.rt.enter();
+        tokio::time::pause();
+        self
+    }
+
+    /// Makes the `send_message` method of the receiver fail as if its output channel was closed,
+    /// once `after_n` messages have been sent (see [`FaultInjector::fail_send_after`]).
+    #[cfg(any(test, feature = "testing"))]
+    #[must_use]
+    pub fn inject_send_failure(self, after_n: usize) -> Self {
+        self.faults.fail_send_after(after_n);
+        self
+    }
+
+    /// Makes the `send_message` method of the receiver behave as if its output channel was full
+    /// (see [`FaultInjector::set_channel_full`]). The fault can be cleared via [`Self::faults`].
+    #[cfg(any(test, feature = "testing"))]
+    #[must_use]
+    pub fn inject_channel_full(self) -> Self {
+        self.faults.set_channel_full(true);
+        self
+    }
+
+    /// Returns the faults injected in the effect handler of the receiver, e.g. to count the
+    /// injected failures.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn faults(&self) -> FaultInjector {
+        self.faults.clone()
+    }
+
+    /// Returns the sink collecting the messages logged by the receiver (see
+    /// [`crate::logging`]).
+    pub fn log_sink(&self) -> Arc<InMemoryLogSink> {
+        self.log_sink.clone()
+    }
+
     /// Returns the current receiver configuration.
     pub fn config(&self) -> &ReceiverConfig {
         &self.config
@@ -188,13 +437,22 @@ impl<PData: Clone + Debug + 'static> TestRuntime<PData> {
     }
 
     /// Sets the receiver for the test runtime and returns a test phase.
-    pub fn set_receiver(self, receiver: ReceiverWrapper<PData>) -> TestPhase<PData> {
+    pub fn set_receiver(self, mut receiver: ReceiverWrapper<PData>) -> TestPhase<PData> {
+        #[cfg(any(test, feature = "testing"))]
+        receiver.inject_faults(self.faults);
+        receiver.set_log_sink(self.log_sink, None);
         let control_sender = receiver.control_sender();
+        let flush_acks = receiver.flush_acks();
+        let bound_addresses = receiver.subscribe_bound_addresses();
+        let lifecycle = receiver.state_watcher();
         TestPhase {
             rt: self.rt,
             local_tasks: self.local_tasks,
             receiver,
             control_sender,
+            flush_acks,
+            bound_addresses,
+            lifecycle,
             counters: self.counter,
         }
     }
@@ -207,16 +465,19 @@ impl<PData: Debug + 'static> TestPhase<PData> {
         F: FnOnce(TestContext) -> Fut + 'static,
         Fut: Future<Output = ()> + 'static,
     {
-        let pdata_receiver = self.receiver.take_pdata_receiver();
-        let run_receiver_handle = self.local_tasks.spawn_local(async move {
-            self.receiver
-                .start()
-                .await
-                .expect("Receiver event loop failed");
-        });
+        let pdata_receiver = self
+            .receiver
+            .take_pdata_receiver()
+            .expect("Failed to take the pdata receiver");
+        let run_receiver_handle = self
+            .local_tasks
+            .spawn_local(async move { self.receiver.start().await });
 
         let context = TestContext {
             control_sender: self.control_sender,
+            flush_acks: self.flush_acks,
+            bound_addresses: self.bound_addresses,
+            lifecycle: self.lifecycle,
         };
         let run_test_handle = self.local_tasks.spawn_local(async move {
             f(context).await;
@@ -232,9 +493,9 @@ impl<PData: Debug + 'static> TestPhase<PData> {
     }
 }
 
-impl<PData> ValidationPhase<PData> {
+impl<PData: Debug> ValidationPhase<PData> {
     /// Runs all spawned tasks to completion and executes the provided future to validate test
-    /// expectations.
+    /// expectations. The receiver is expected to complete successfully.
     ///
     /// # Type Parameters
     ///
@@ -249,6 +510,60 @@ impl<PData> ValidationPhase<PData> {
     where
         F: FnOnce(NotSendValidateContext<PData>) -> Fut,
         Fut: Future<Output = T>,
+    {
+        self.run_validation_with_result(|context, result| {
+            result.expect("Receiver event loop failed");
+            future_fn(context)
+        })
+    }
+
+    /// Runs all spawned tasks to completion and executes the future returned by the provided
+    /// function on the Tokio runtime, to validate test expectations from a `Send` context. The
+    /// receiver is expected to complete successfully.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the receiver is not a shared receiver, whose output channel is `Send`.
+    pub fn run_validation_shared<F, Fut, T>(self, future_fn: F) -> T
+    where
+        PData: Send + 'static,
+        F: FnOnce(SendValidateContext<PData>) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let context = SendValidateContext {
+            pdata_receiver: self
+                .pdata_receiver
+                .into_shared()
+                .expect("A shared receiver is required to validate from a Send context"),
+            counters: self.counters,
+        };
+
+        // First run all the spawned tasks to completion
+        self.rt.block_on(self.local_tasks);
+
+        self.rt
+            .block_on(self.run_receiver_handle)
+            .expect("Receiver task failed")
+            .expect("Receiver event loop failed");
+
+        self.rt
+            .block_on(self.run_test_handle)
+            .expect("Test task failed");
+
+        // Then run the validation future on the runtime, outside of the local task set
+        self.rt
+            .block_on(async move { tokio::spawn(future_fn(context)).await })
+            .expect("Validation task failed")
+    }
+
+    /// Runs all spawned tasks to completion and executes the provided future to validate test
+    /// expectations, given the result returned by the receiver (e.g. to check the error surfaced
+    /// by a receiver failing on an injected fault, see `TestRuntime::inject_send_failure`).
+    pub fn run_validation_with_result<F, Fut, T>(self, future_fn: F) -> T
+    where
+        F: FnOnce(NotSendValidateContext<PData>, Result<(), Error<PData>>) -> Fut,
+        Fut: Future<Output = T>,
     {
         let context = NotSendValidateContext {
             pdata_receiver: self.pdata_receiver,
@@ -258,7 +573,8 @@ impl<PData> ValidationPhase<PData> {
         // First run all the spawned tasks to completion
         self.rt.block_on(self.local_tasks);
 
-        self.rt
+        let result = self
+            .rt
             .block_on(self.run_receiver_handle)
             .expect("Receiver task failed");
 
@@ -267,6 +583,6 @@ impl<PData> ValidationPhase<PData> {
             .expect("Test task failed");
 
         // Then run the validation future with the test context
-        self.rt.block_on(future_fn(context))
+        self.rt.block_on(future_fn(context, result))
     }
 }
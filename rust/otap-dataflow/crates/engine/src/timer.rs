@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-node scheduling of the `TimerTick` and `NodeTimer` control messages.
+//!
+//! Each node configured with a [`TimerConfig`] gets its own tick schedule. Receivers can also start
+//! their own periodic timers at runtime (see the `start_periodic_timer` method of the receiver
+//! effect handlers), whose expirations are delivered as `NodeTimer` messages carrying the
+//! [`TimerId`] of the timer.
+//!
+//! Both are produced by the control forwarding loop of the node wrapper, so they are only emitted
+//! when the node is ready to receive a control message (ticks never pile up in the control channel
+//! of a busy node) and they stop as soon as a `Shutdown` has been delivered.
+
+use crate::config::TimerConfig;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{Instant, sleep_until};
+
+/// The tick schedule of a node.
+pub(crate) struct TickSchedule {
+    config: TimerConfig,
+    next_tick: Instant,
+}
+
+impl TickSchedule {
+    /// Creates a schedule whose first tick is due after one interval (plus jitter).
+    pub(crate) fn new(config: TimerConfig) -> Self {
+        TickSchedule {
+            next_tick: Instant::now() + next_delay(&config),
+            config,
+        }
+    }
+
+    /// Waits for the next tick.
+    ///
+    /// Cancel safety: the schedule is only advanced once the tick is due, a cancelled call can be
+    /// retried without skipping a tick.
+    pub(crate) async fn tick(&mut self) {
+        sleep_until(self.next_tick).await;
+        // Ticks missed while the node was busy are skipped rather than delivered in a burst.
+        self.next_tick = Instant::now() + next_delay(&self.config);
+    }
+}
+
+/// Waits for the next tick of the given schedule, forever if there is no schedule.
+pub(crate) async fn next_tick(schedule: Option<&mut TickSchedule>) {
+    match schedule {
+        Some(schedule) => schedule.tick().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Returns the interval of the configuration plus a random jitter.
+fn next_delay(config: &TimerConfig) -> Duration {
+    match config.jitter {
+        Some(jitter) if !jitter.is_zero() => {
+            let jitter_nanos = u64::try_from(jitter.as_nanos()).unwrap_or(u64::MAX);
+            config.interval + Duration::from_nanos(fastrand::u64(0..=jitter_nanos))
+        }
+        _ => config.interval,
+    }
+}
+
+/// The identifier of a periodic timer started by a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+/// A periodic timer started by a node.
+struct NodeTimer {
+    id: TimerId,
+    interval: Duration,
+    next_expiration: Instant,
+}
+
+#[derive(Default)]
+struct NodeTimersState {
+    next_id: u64,
+    timers: Vec<NodeTimer>,
+}
+
+/// The periodic timers started by a node, shared by its effect handler and its wrapper.
+///
+/// Note: This implementation is `Send`.
+#[derive(Clone, Default)]
+pub(crate) struct NodeTimers {
+    state: Arc<Mutex<NodeTimersState>>,
+    /// Notified when a timer is started or cancelled.
+    changed: Arc<Notify>,
+}
+
+impl NodeTimers {
+    /// Starts a timer expiring after every interval, and returns its id.
+    pub(crate) fn start(&self, interval: Duration) -> TimerId {
+        let mut state = self.lock();
+        let id = TimerId(state.next_id);
+        state.next_id += 1;
+        state.timers.push(NodeTimer {
+            id,
+            interval,
+            next_expiration: Instant::now() + interval,
+        });
+        drop(state);
+        self.changed.notify_waiters();
+        id
+    }
+
+    /// Cancels the given timer. Returns false if the timer was not running.
+    pub(crate) fn cancel(&self, id: TimerId) -> bool {
+        let mut state = self.lock();
+        let Some(index) = state.timers.iter().position(|timer| timer.id == id) else {
+            return false;
+        };
+        _ = state.timers.remove(index);
+        drop(state);
+        self.changed.notify_waiters();
+        true
+    }
+
+    /// Cancels all the timers.
+    pub(crate) fn cancel_all(&self) {
+        self.lock().timers.clear();
+        self.changed.notify_waiters();
+    }
+
+    /// Waits for the next expiration of a timer, and returns its id.
+    ///
+    /// Cancel safety: a timer is only re-armed once it expired, a cancelled call can be retried
+    /// without skipping an expiration.
+    pub(crate) async fn next_expiration(&self) -> TimerId {
+        loop {
+            // Created before reading the timers, so that no change is missed.
+            let changed = self.changed.notified();
+            let next = self
+                .lock()
+                .timers
+                .iter()
+                .min_by_key(|timer| timer.next_expiration)
+                .map(|timer| (timer.id, timer.next_expiration));
+            let Some((id, deadline)) = next else {
+                changed.await;
+                continue;
+            };
+            tokio::select! {
+                () = sleep_until(deadline) => {}
+                () = changed => continue,
+            }
+            let mut state = self.lock();
+            if let Some(timer) = state.timers.iter_mut().find(|timer| timer.id == id) {
+                // Expirations missed while the node was busy are skipped rather than delivered in
+                // a burst.
+                timer.next_expiration = Instant::now() + timer.interval;
+                return id;
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, NodeTimersState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Waits for the next expiration of the given node timers, forever if there are none.
+pub(crate) async fn next_node_timer(timers: Option<&NodeTimers>) -> TimerId {
+    match timers {
+        Some(timers) => timers.next_expiration().await,
+        None => std::future::pending().await,
+    }
+}
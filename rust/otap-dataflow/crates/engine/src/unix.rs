@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Unix domain socket support for the receivers.
+//!
+//! A [`UnixListener`] is created by the receiver effect handlers (see `unix_listener`) and owns
+//! its socket file: the file is removed once the listener is dropped, so that the path can be
+//! bound again, e.g. by a restarted receiver. A socket file left behind by a crashed process is
+//! removed when the path is bound again, and the socket file is only accessible to the owner and
+//! the group of the process (see [`SOCKET_FILE_MODE`]), e.g. to share it with a sidecar agent.
+
+use std::io;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tokio::net::UnixStream;
+use tokio::net::unix::SocketAddr;
+
+/// The permissions of the socket files created by the listeners: read and write for the owner and
+/// the group of the process.
+pub const SOCKET_FILE_MODE: u32 = 0o660;
+
+/// A Unix domain socket listener removing its socket file when dropped.
+pub struct UnixListener {
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+}
+
+impl UnixListener {
+    /// Binds a new listener to the given path, removing a stale socket file first.
+    ///
+    /// Fails if the path exists and is not a socket file, or is a socket file still in use.
+    pub(crate) fn bind(path: &Path) -> io::Result<Self> {
+        remove_stale_socket(path)?;
+        let listener = UnixListener {
+            listener: tokio::net::UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+        };
+        // The socket file is removed when the listener is dropped, including on failure.
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(SOCKET_FILE_MODE))?;
+        Ok(listener)
+    }
+
+    /// Accepts a new incoming connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the listener fails to accept connections.
+    pub async fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        self.listener.accept().await
+    }
+
+    /// Returns the path of the socket file this listener is bound to.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Removes the socket file at the given path if no process listens on it anymore.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket file", path.display()),
+        ));
+    }
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another listener", path.display()),
+        )),
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+            tracing::debug!(path = %path.display(), "Removing a stale socket file");
+            std::fs::remove_file(path)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            tracing::debug!(path = %self.path.display(), %error, "Failed to remove the socket file");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SOCKET_FILE_MODE, UnixListener};
+    use std::io;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test]
+    async fn test_bind() {
+        let dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+        let path = dir.path().join("receiver.sock");
+
+        // A socket file left behind by a listener which didn't clean up is replaced.
+        drop(std::os::unix::net::UnixListener::bind(&path).expect("Failed to bind"));
+        assert!(path.exists());
+        let listener = UnixListener::bind(&path).expect("Stale socket file not replaced");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, SOCKET_FILE_MODE);
+
+        // A socket file in use is left untouched.
+        let error = UnixListener::bind(&path)
+            .err()
+            .expect("Socket file in use replaced");
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        drop(listener);
+        assert!(!path.exists());
+
+        // Other files are never removed.
+        std::fs::write(&path, "data").unwrap();
+        let error = UnixListener::bind(&path)
+            .err()
+            .expect("Regular file replaced");
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,374 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implementation of the Arrow IPC exporter node.
+//!
+//! The exporter sends the record batches it receives to a TCP endpoint, encoded in the Arrow IPC
+//! stream format (see [`crate::arrow_ipc_receiver`]). A connection carries a single stream: the
+//! schema of the first batch, the batches themselves and, once the exporter shuts down, the
+//! end-of-stream marker. A batch with a different schema ends the current stream, and the next
+//! batches are sent over a new connection.
+//!
+//! The encoded batches are buffered until they are flushed to the connection, according to the
+//! flush policy of the exporter (see [`FlushPolicy`]), on `Flush` and on `Shutdown`. The
+//! connection is managed by the effect handler (see [`otap_df_engine::outbound`]): when it is lost,
+//! the exporter reconnects with a jittered exponential backoff and starts a new stream with the
+//! batches that weren't flushed yet.
+
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use otap_df_engine::config::RetryPolicy;
+use otap_df_engine::error::{ConnectError, Error};
+use otap_df_engine::message::{ControlMsg, Message};
+use otap_df_engine::outbound::OutboundConnection;
+use otap_df_engine::shared::exporter as shared;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// The default reconnection policy, see [`ArrowIpcExporterConfig::reconnect`].
+pub const DEFAULT_RECONNECT_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 10,
+    initial_backoff: Duration::from_millis(100),
+    max_backoff: Duration::from_secs(10),
+};
+
+/// When the encoded batches are written to the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Every batch is written as soon as it is received.
+    PerBatch,
+    /// The batches are written once at least the given number of encoded bytes is buffered.
+    AfterBytes(usize),
+}
+
+/// The configuration of an [`ArrowIpcExporter`].
+#[derive(Clone, Debug)]
+pub struct ArrowIpcExporterConfig {
+    /// The address of the endpoint the batches are sent to.
+    pub endpoint: SocketAddr,
+    /// When the encoded batches are written to the connection.
+    pub flush: FlushPolicy,
+    /// How many times, and how often, the exporter tries to (re)connect to the endpoint before
+    /// failing.
+    pub reconnect: RetryPolicy,
+}
+
+impl ArrowIpcExporterConfig {
+    /// Creates a configuration sending the batches to the given endpoint, flushing every batch
+    /// and reconnecting with the default policy.
+    #[must_use]
+    pub fn new(endpoint: SocketAddr) -> Self {
+        ArrowIpcExporterConfig {
+            endpoint,
+            flush: FlushPolicy::PerBatch,
+            reconnect: DEFAULT_RECONNECT_POLICY,
+        }
+    }
+
+    /// Sets when the encoded batches are written to the connection.
+    #[must_use]
+    pub fn with_flush(mut self, flush: FlushPolicy) -> Self {
+        self.flush = flush;
+        self
+    }
+
+    /// Sets the reconnection policy.
+    #[must_use]
+    pub fn with_reconnect(mut self, reconnect: RetryPolicy) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+}
+
+/// An Exporter that sends record batches to a TCP endpoint as an Arrow IPC stream.
+pub struct ArrowIpcExporter {
+    config: ArrowIpcExporterConfig,
+}
+
+impl ArrowIpcExporter {
+    /// Creates a new Arrow IPC exporter with the given configuration.
+    #[must_use]
+    pub fn new(config: ArrowIpcExporterConfig) -> Self {
+        ArrowIpcExporter { config }
+    }
+}
+
+#[async_trait]
+impl shared::Exporter<RecordBatch> for ArrowIpcExporter {
+    async fn start(
+        self: Box<Self>,
+        mut msg_chan: shared::MessageChannel<RecordBatch>,
+        effect_handler: shared::EffectHandler<RecordBatch>,
+    ) -> Result<(), Error<RecordBatch>> {
+        let mut stream = IpcStream::new(self.config, &effect_handler);
+        let export_error = |error: ExportError| Error::ExporterError {
+            exporter: effect_handler.exporter_name(),
+            error: error.to_string(),
+        };
+
+        loop {
+            match msg_chan.recv().await? {
+                Message::PData(batch) => stream.write(batch).await.map_err(export_error)?,
+                Message::Control(ControlMsg::Flush { id, .. }) => {
+                    stream.flush(false).await.map_err(export_error)?;
+                    effect_handler.ack_flush(id);
+                }
+                Message::Control(ControlMsg::Shutdown { .. }) => {
+                    stream.finish().await.map_err(export_error)?;
+                    break;
+                }
+                Message::Control(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The errors failing the exporter.
+#[derive(thiserror::Error, Debug)]
+enum ExportError {
+    /// A batch can't be encoded.
+    #[error("Encoding error: {0}")]
+    Encode(#[from] ArrowError),
+
+    /// The endpoint is unreachable, the reconnection policy gave up.
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+}
+
+/// The Arrow IPC stream currently sent to the endpoint.
+struct Stream {
+    /// The schema of the stream.
+    schema: SchemaRef,
+    /// Encodes the stream, the encoded bytes are buffered until they are written to the
+    /// connection.
+    writer: StreamWriter<Vec<u8>>,
+}
+
+/// The Arrow IPC streams sent to the endpoint, one per connection.
+struct IpcStream<'a> {
+    config: ArrowIpcExporterConfig,
+    effect_handler: &'a shared::EffectHandler<RecordBatch>,
+    /// The connection carrying the current stream, reconnecting when it is lost.
+    connection: Option<OutboundConnection>,
+    stream: Option<Stream>,
+    /// The batches encoded since the last successful flush, sent again over a new connection if
+    /// the current one is lost.
+    unflushed: Vec<RecordBatch>,
+}
+
+impl<'a> IpcStream<'a> {
+    fn new(
+        config: ArrowIpcExporterConfig,
+        effect_handler: &'a shared::EffectHandler<RecordBatch>,
+    ) -> Self {
+        IpcStream {
+            config,
+            effect_handler,
+            connection: None,
+            stream: None,
+            unflushed: Vec::new(),
+        }
+    }
+
+    /// Encodes the given batch and flushes the stream according to the flush policy.
+    async fn write(&mut self, batch: RecordBatch) -> Result<(), ExportError> {
+        if let Some(stream) = &self.stream {
+            if stream.schema != batch.schema() {
+                // The schema of a stream is fixed, the batch starts a new one.
+                self.finish().await?;
+            }
+        }
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let schema = batch.schema();
+                let writer = StreamWriter::try_new(Vec::new(), &schema)?;
+                self.stream.insert(Stream { schema, writer })
+            }
+        };
+        stream.writer.write(&batch)?;
+        self.unflushed.push(batch);
+        let flush = match self.config.flush {
+            FlushPolicy::PerBatch => true,
+            FlushPolicy::AfterBytes(threshold) => stream.writer.get_ref().len() >= threshold,
+        };
+        if flush {
+            self.flush(false).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes the encoded batches to the connection, followed by the end-of-stream marker if
+    /// `end` is true. A connection is opened for the first flush of the stream.
+    async fn flush(&mut self, end: bool) -> Result<(), ExportError> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+        if self.unflushed.is_empty() && !end {
+            return Ok(());
+        }
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => {
+                let connection = self
+                    .effect_handler
+                    .connect(self.config.endpoint, self.config.reconnect)
+                    .await?;
+                self.connection.insert(connection)
+            }
+        };
+        let unflushed = &self.unflushed;
+        connection
+            .write_with(|fresh| {
+                if fresh {
+                    // A new connection starts a new stream with the batches that weren't flushed
+                    // yet.
+                    stream.writer = StreamWriter::try_new(Vec::new(), &stream.schema)?;
+                    for batch in unflushed {
+                        stream.writer.write(batch)?;
+                    }
+                }
+                if end {
+                    stream.writer.finish()?;
+                }
+                Ok::<_, ExportError>(std::mem::take(stream.writer.get_mut()))
+            })
+            .await?;
+        self.unflushed.clear();
+        Ok(())
+    }
+
+    /// Flushes the pending batches and ends the stream with the end-of-stream marker, the next
+    /// batches are sent over a new connection.
+    async fn finish(&mut self) -> Result<(), ExportError> {
+        self.flush(true).await?;
+        self.stream = None;
+        self.connection = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arrow_ipc_exporter::{ArrowIpcExporter, ArrowIpcExporterConfig, FlushPolicy};
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::ipc::reader::StreamReader;
+    use arrow::record_batch::RecordBatch;
+    use otap_df_engine::config::RetryPolicy;
+    use otap_df_engine::exporter::ExporterWrapper;
+    use otap_df_engine::testing::exporter::TestRuntime;
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+    use tokio::time::{Duration, sleep, timeout};
+
+    /// Returns a record batch with the given ids.
+    fn batch(ids: &[i64]) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        let names: Vec<String> = ids.iter().map(|id| format!("name-{id}")).collect();
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int64Array::from(ids.to_vec())),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .expect("Failed to build the record batch")
+    }
+
+    /// Returns a free local address, nothing listens on it.
+    fn free_addr() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("Failed to reserve an address")
+    }
+
+    /// Accepts a single connection on the given address once the delay elapsed, and returns the
+    /// batches of the Arrow IPC stream read from it.
+    fn spawn_endpoint(addr: SocketAddr, delay: Duration) -> oneshot::Receiver<Vec<RecordBatch>> {
+        let (tx, rx) = oneshot::channel();
+        _ = tokio::spawn(async move {
+            sleep(delay).await;
+            let listener = TcpListener::bind(addr)
+                .await
+                .expect("Failed to bind the endpoint");
+            let (mut socket, _) = listener.accept().await.expect("Failed to accept");
+            let mut bytes = Vec::new();
+            _ = socket
+                .read_to_end(&mut bytes)
+                .await
+                .expect("Failed to read the stream");
+            let batches = StreamReader::try_new(Cursor::new(bytes), None)
+                .expect("Invalid stream")
+                .collect::<Result<Vec<_>, _>>()
+                .expect("Invalid batch");
+            _ = tx.send(batches);
+        });
+        rx
+    }
+
+    /// Sends the given batches to an exporter with the given configuration, whose endpoint starts
+    /// listening after the given delay, and checks that the endpoint receives them.
+    fn assert_batches_exported(config: ArrowIpcExporterConfig, delay: Duration) {
+        let test_runtime = TestRuntime::new();
+        let addr = config.endpoint;
+        let exporter =
+            ExporterWrapper::shared(ArrowIpcExporter::new(config), test_runtime.config());
+        let batches = vec![batch(&[1, 2, 3]), batch(&[4, 5])];
+        let expected = batches.clone();
+        let (received_tx, received_rx) = oneshot::channel();
+
+        test_runtime
+            .set_exporter(exporter)
+            .run_test(move |ctx| async move {
+                _ = received_tx.send(spawn_endpoint(addr, delay));
+                for batch in batches {
+                    ctx.send_pdata(batch).await.expect("Failed to send pdata");
+                }
+                ctx.send_shutdown(Duration::from_millis(200), "Test")
+                    .await
+                    .expect("Failed to send Shutdown");
+            })
+            .run_validation(|_ctx| async move {
+                let received = received_rx.await.expect("Endpoint not started");
+                let received = timeout(Duration::from_secs(3), received)
+                    .await
+                    .expect("Timed out waiting for the stream")
+                    .expect("Endpoint failed");
+                assert_eq!(received, expected);
+            });
+    }
+
+    #[test]
+    fn test_arrow_ipc_exporter_per_batch() {
+        assert_batches_exported(ArrowIpcExporterConfig::new(free_addr()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_arrow_ipc_exporter_after_bytes() {
+        let config =
+            ArrowIpcExporterConfig::new(free_addr()).with_flush(FlushPolicy::AfterBytes(1 << 20));
+        assert_batches_exported(config, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_arrow_ipc_exporter_reconnects() {
+        let config = ArrowIpcExporterConfig::new(free_addr()).with_reconnect(RetryPolicy {
+            max_attempts: 20,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        });
+        assert_batches_exported(config, Duration::from_millis(200));
+    }
+}
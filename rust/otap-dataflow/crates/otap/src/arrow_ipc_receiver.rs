@@ -0,0 +1,461 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implementation of the Arrow IPC receiver node.
+//!
+//! The receiver accepts TCP connections carrying record batches encoded in the Arrow IPC stream
+//! format (see <https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format>). Each
+//! connection is an independent stream: a schema message followed by dictionary batches and
+//! record batches, each one framed as an encapsulated message, and an optional end-of-stream
+//! marker. The decoded record batches are sent downstream as is.
+//!
+//! A connection is closed, and the error logged, when a frame exceeds the maximum frame size,
+//! when no bytes are received within the read timeout, or when a message can't be decoded.
+
+use arrow::buffer::Buffer;
+use arrow::error::ArrowError;
+use arrow::ipc::reader::StreamDecoder;
+use arrow::ipc::root_as_message;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use otap_df_engine::error::Error;
+use otap_df_engine::shared::receiver as shared;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The continuation token preceding the metadata length of an encapsulated message.
+const CONTINUATION_MARKER: u32 = 0xFFFF_FFFF;
+
+/// The length of the prefix of an encapsulated message, i.e. the continuation token and the
+/// metadata length.
+const PREFIX_LEN: usize = 8;
+
+/// The default maximum size of a frame, see [`ArrowIpcReceiverConfig::max_frame_size`].
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// The default read timeout, see [`ArrowIpcReceiverConfig::read_timeout`].
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The configuration of an [`ArrowIpcReceiver`].
+#[derive(Clone, Debug)]
+pub struct ArrowIpcReceiverConfig {
+    /// The address the receiver listens on.
+    pub listening_addr: SocketAddr,
+    /// The maximum size in bytes of a frame, i.e. of the metadata and the body of an encapsulated
+    /// message. The connection sending a larger frame is closed before the frame is buffered.
+    pub max_frame_size: usize,
+    /// How long the receiver waits for the next bytes of a connection before closing it, idle
+    /// connections included.
+    pub read_timeout: Duration,
+    /// The size of the accept queue of the listener, the one defined by the pipeline engine if
+    /// `None`.
+    pub accept_backlog: Option<u32>,
+}
+
+impl ArrowIpcReceiverConfig {
+    /// Creates a configuration listening on the given address, with the default frame size and
+    /// read timeout.
+    #[must_use]
+    pub fn new(listening_addr: SocketAddr) -> Self {
+        ArrowIpcReceiverConfig {
+            listening_addr,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            accept_backlog: None,
+        }
+    }
+
+    /// Sets the maximum size in bytes of a frame.
+    #[must_use]
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Sets how long the receiver waits for the next bytes of a connection.
+    #[must_use]
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    /// Sets the size of the accept queue of the listener.
+    #[must_use]
+    pub fn with_accept_backlog(mut self, accept_backlog: u32) -> Self {
+        self.accept_backlog = Some(accept_backlog);
+        self
+    }
+}
+
+/// A Receiver that decodes the Arrow IPC streams sent over TCP connections.
+pub struct ArrowIpcReceiver {
+    config: ArrowIpcReceiverConfig,
+}
+
+impl ArrowIpcReceiver {
+    /// Creates a new Arrow IPC receiver with the given configuration.
+    #[must_use]
+    pub fn new(config: ArrowIpcReceiverConfig) -> Self {
+        ArrowIpcReceiver { config }
+    }
+}
+
+#[async_trait]
+impl shared::Receiver<RecordBatch> for ArrowIpcReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: shared::ControlChannel,
+        effect_handler: shared::EffectHandler<RecordBatch>,
+    ) -> Result<(), Error<RecordBatch>> {
+        let config = self.config;
+        let listener = match config.accept_backlog {
+            Some(backlog) => {
+                effect_handler.tcp_listener_with_backlog(config.listening_addr, backlog)?
+            }
+            None => effect_handler.tcp_listener(config.listening_addr)?,
+        };
+
+        // Stops accepting connections and drains the in-flight streams on `Shutdown`.
+        effect_handler
+            .serve_connections(
+                listener,
+                &mut ctrl_msg_recv,
+                |_| {},
+                |socket, peer_addr| {
+                    let effect_handler = effect_handler.clone();
+                    let mut stream = FrameReader::new(socket, &config);
+                    async move {
+                        if let Err(error) = forward_stream(&mut stream, &effect_handler).await {
+                            effect_handler.warn_message(&format!(
+                                "Closing the Arrow IPC stream of {peer_addr}: {error}"
+                            ));
+                        }
+                    }
+                },
+            )
+            .await
+    }
+}
+
+/// The errors closing an Arrow IPC stream.
+#[derive(thiserror::Error, Debug)]
+enum StreamError {
+    /// The connection failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// No bytes were received within the read timeout.
+    #[error("No data received for {0:?}")]
+    ReadTimeout(Duration),
+
+    /// A frame exceeds the maximum frame size.
+    #[error("Frame of {size} bytes exceeds the maximum frame size of {max} bytes")]
+    FrameTooLarge {
+        /// The size of the frame.
+        size: usize,
+        /// The maximum frame size.
+        max: usize,
+    },
+
+    /// The metadata of a message is invalid.
+    #[error("Invalid message metadata: {0}")]
+    InvalidMessage(String),
+
+    /// A message can't be decoded.
+    #[error("Decoding error: {0}")]
+    Decode(#[from] ArrowError),
+
+    /// A record batch can't be sent downstream.
+    #[error("Send error: {0}")]
+    Send(String),
+}
+
+/// Decodes an Arrow IPC stream and sends its record batches downstream, until the end of the
+/// stream.
+async fn forward_stream<S: AsyncRead + Unpin>(
+    stream: &mut FrameReader<S>,
+    effect_handler: &shared::EffectHandler<RecordBatch>,
+) -> Result<(), StreamError> {
+    let mut decoder = StreamDecoder::new();
+    while let Some(frame) = stream.next_frame().await? {
+        let mut buffer = Buffer::from_vec(frame);
+        while !buffer.is_empty() {
+            if let Some(batch) = decoder.decode(&mut buffer)? {
+                effect_handler
+                    .send_message(batch)
+                    .await
+                    .map_err(|error| StreamError::Send(error.to_string()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits the bytes of an Arrow IPC stream into frames, each one holding an encapsulated message.
+struct FrameReader<S> {
+    socket: S,
+    max_frame_size: usize,
+    read_timeout: Duration,
+}
+
+impl<S: AsyncRead + Unpin> FrameReader<S> {
+    fn new(socket: S, config: &ArrowIpcReceiverConfig) -> Self {
+        FrameReader {
+            socket,
+            max_frame_size: config.max_frame_size,
+            read_timeout: config.read_timeout,
+        }
+    }
+
+    /// Reads the next encapsulated message, normalized with a continuation token (older writers
+    /// omit it). Returns `None` at the end of the stream, i.e. on the end-of-stream marker or
+    /// once the peer closed the connection between two messages.
+    async fn next_frame(&mut self) -> Result<Option<Vec<u8>>, StreamError> {
+        let mut word = [0u8; 4];
+        match self.read_exact(&mut word).await {
+            Err(StreamError::Io(error)) if error.kind() == ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            result => result?,
+        }
+        let mut metadata_len = u32::from_le_bytes(word);
+        if metadata_len == CONTINUATION_MARKER {
+            self.read_exact(&mut word).await?;
+            metadata_len = u32::from_le_bytes(word);
+        }
+        if metadata_len == 0 {
+            // The end-of-stream marker.
+            return Ok(None);
+        }
+        let metadata_len = usize::try_from(metadata_len).unwrap_or(usize::MAX);
+        self.check_frame_size(metadata_len)?;
+
+        let mut frame = Vec::with_capacity(PREFIX_LEN + metadata_len);
+        frame.extend_from_slice(&CONTINUATION_MARKER.to_le_bytes());
+        frame.extend_from_slice(&word);
+        frame.resize(PREFIX_LEN + metadata_len, 0);
+        self.read_exact(&mut frame[PREFIX_LEN..]).await?;
+
+        let body_len = root_as_message(&frame[PREFIX_LEN..])
+            .map_err(|error| StreamError::InvalidMessage(error.to_string()))?
+            .bodyLength();
+        let body_len = usize::try_from(body_len)
+            .map_err(|_| StreamError::InvalidMessage(format!("Negative body length {body_len}")))?;
+        self.check_frame_size(metadata_len.saturating_add(body_len))?;
+
+        let body_start = frame.len();
+        frame.resize(body_start + body_len, 0);
+        self.read_exact(&mut frame[body_start..]).await?;
+        Ok(Some(frame))
+    }
+
+    /// Fills the given buffer, waiting at most for the read timeout between two reads.
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), StreamError> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let read =
+                tokio::time::timeout(self.read_timeout, self.socket.read(&mut buf[filled..]))
+                    .await
+                    .map_err(|_| StreamError::ReadTimeout(self.read_timeout))??;
+            if read == 0 {
+                return Err(StreamError::Io(ErrorKind::UnexpectedEof.into()));
+            }
+            filled += read;
+        }
+        Ok(())
+    }
+
+    /// Rejects the frames larger than the maximum frame size.
+    fn check_frame_size(&self, size: usize) -> Result<(), StreamError> {
+        if size > self.max_frame_size {
+            return Err(StreamError::FrameTooLarge {
+                size,
+                max: self.max_frame_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arrow_ipc_receiver::{ArrowIpcReceiver, ArrowIpcReceiverConfig};
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::ipc::writer::StreamWriter;
+    use arrow::record_batch::RecordBatch;
+    use otap_df_engine::logging::LogLevel;
+    use otap_df_engine::receiver::ReceiverWrapper;
+    use otap_df_engine::testing::receiver::TestRuntime;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::{Duration, timeout};
+
+    /// Returns a record batch with the given ids.
+    fn batch(ids: &[i64]) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        let names: Vec<String> = ids.iter().map(|id| format!("name-{id}")).collect();
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int64Array::from(ids.to_vec())),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .expect("Failed to build the record batch")
+    }
+
+    /// Encodes the given batches as an Arrow IPC stream.
+    fn ipc_stream(batches: &[RecordBatch]) -> Vec<u8> {
+        let mut writer = StreamWriter::try_new(Vec::new(), &batches[0].schema())
+            .expect("Failed to create the stream writer");
+        for batch in batches {
+            writer.write(batch).expect("Failed to write the batch");
+        }
+        writer.into_inner().expect("Failed to finish the stream")
+    }
+
+    fn config() -> ArrowIpcReceiverConfig {
+        let addr = "127.0.0.1:0".parse().expect("Invalid address");
+        ArrowIpcReceiverConfig::new(addr).with_accept_backlog(16)
+    }
+
+    #[test]
+    fn test_arrow_ipc_receiver() {
+        let test_runtime = TestRuntime::new();
+        let receiver =
+            ReceiverWrapper::shared(ArrowIpcReceiver::new(config()), test_runtime.config());
+        let batches = vec![batch(&[1, 2, 3]), batch(&[4, 5])];
+        let stream = ipc_stream(&batches);
+
+        test_runtime
+            .set_receiver(receiver)
+            .run_test(|ctx| async move {
+                let addr = ctx
+                    .bound_address()
+                    .await
+                    .expect("Receiver terminated before binding its listener");
+                let mut socket = TcpStream::connect(addr)
+                    .await
+                    .expect("Failed to connect to receiver");
+                // Split the stream across writes to exercise the framing.
+                let (head, tail) = stream.split_at(stream.len() / 3);
+                socket.write_all(head).await.expect("Failed to send data");
+                ctx.sleep(Duration::from_millis(50)).await;
+                socket.write_all(tail).await.expect("Failed to send data");
+                socket.shutdown().await.expect("Failed to close connection");
+                ctx.sleep(Duration::from_millis(100)).await;
+
+                ctx.send_shutdown(Duration::from_millis(200), "Test")
+                    .await
+                    .expect("Failed to send Shutdown");
+            })
+            .run_validation(|mut ctx| async move {
+                for expected in batches {
+                    let received = timeout(Duration::from_secs(3), ctx.recv())
+                        .await
+                        .expect("Timed out waiting for a batch")
+                        .expect("No batch received");
+                    assert_eq!(received, expected);
+                }
+            });
+    }
+
+    #[test]
+    fn test_arrow_ipc_receiver_frame_too_large() {
+        let test_runtime = TestRuntime::new();
+        let log_sink = test_runtime.log_sink();
+        let receiver = ReceiverWrapper::shared(
+            ArrowIpcReceiver::new(config().with_max_frame_size(64)),
+            test_runtime.config(),
+        );
+        let stream = ipc_stream(&[batch(&[1])]);
+
+        test_runtime
+            .set_receiver(receiver)
+            .run_test(|ctx| async move {
+                let addr = ctx
+                    .bound_address()
+                    .await
+                    .expect("Receiver terminated before binding its listener");
+                let mut socket = TcpStream::connect(addr)
+                    .await
+                    .expect("Failed to connect to receiver");
+                socket
+                    .write_all(&stream)
+                    .await
+                    .expect("Failed to send data");
+
+                // The receiver closes the connection on the first frame.
+                let mut buf = [0u8; 16];
+                let read = timeout(Duration::from_secs(3), socket.read(&mut buf))
+                    .await
+                    .expect("Timed out waiting for the connection to close");
+                assert!(matches!(read, Ok(0) | Err(_)));
+
+                ctx.send_shutdown(Duration::from_millis(200), "Test")
+                    .await
+                    .expect("Failed to send Shutdown");
+            })
+            .run_validation(|mut ctx| async move {
+                let received = timeout(Duration::from_millis(100), ctx.recv()).await;
+                assert!(!matches!(received, Ok(Ok(_))), "No batch expected");
+                let records = log_sink.records();
+                assert_eq!(records.len(), 1);
+                assert_eq!(records[0].level, LogLevel::Warn);
+                assert!(
+                    records[0]
+                        .message
+                        .contains("exceeds the maximum frame size of 64 bytes")
+                );
+            });
+    }
+
+    #[test]
+    fn test_arrow_ipc_receiver_read_timeout() {
+        let test_runtime = TestRuntime::new();
+        let log_sink = test_runtime.log_sink();
+        let receiver = ReceiverWrapper::shared(
+            ArrowIpcReceiver::new(config().with_read_timeout(Duration::from_millis(100))),
+            test_runtime.config(),
+        );
+        let stream = ipc_stream(&[batch(&[1])]);
+
+        test_runtime
+            .set_receiver(receiver)
+            .run_test(|ctx| async move {
+                let addr = ctx
+                    .bound_address()
+                    .await
+                    .expect("Receiver terminated before binding its listener");
+                let mut socket = TcpStream::connect(addr)
+                    .await
+                    .expect("Failed to connect to receiver");
+                // Stall in the middle of the schema message.
+                socket
+                    .write_all(&stream[..12])
+                    .await
+                    .expect("Failed to send data");
+
+                let mut buf = [0u8; 16];
+                let read = timeout(Duration::from_secs(3), socket.read(&mut buf))
+                    .await
+                    .expect("Timed out waiting for the connection to close");
+                assert!(matches!(read, Ok(0) | Err(_)));
+
+                ctx.send_shutdown(Duration::from_millis(200), "Test")
+                    .await
+                    .expect("Failed to send Shutdown");
+            })
+            .run_validation(|_ctx| async move {
+                let records = log_sink.records();
+                assert_eq!(records.len(), 1);
+                assert!(records[0].message.contains("No data received for 100ms"));
+            });
+    }
+}
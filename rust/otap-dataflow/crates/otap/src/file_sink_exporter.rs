@@ -0,0 +1,432 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implementation of the file sink exporter node.
+//!
+//! The exporter writes the record batches it receives to Parquet files in a directory, e.g. to
+//! persist the data flowing through a pipeline while debugging it. The current file is closed and
+//! a new one is opened according to the rotation policy of the exporter (see [`RotatePolicy`]),
+//! and whenever a batch has a different schema than the batches of the current file. Each file is
+//! named after the prefix of the exporter, the time it was opened (in milliseconds since the Unix
+//! epoch) and its sequence number, e.g. `batches-1700000000000-000000.parquet`.
+//!
+//! On `Flush`, the buffered rows are written to the current file as a row group. On `Shutdown`,
+//! the current file is flushed and closed. The files are written synchronously, as the exporter
+//! isn't meant for production traffic.
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use otap_df_engine::error::Error;
+use otap_df_engine::message::{ControlMsg, Message};
+use otap_df_engine::shared::exporter as shared;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use std::fs::File;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The default rotation policy, see [`FileSinkConfig::rotate`].
+pub const DEFAULT_ROTATE_POLICY: RotatePolicy = RotatePolicy::BySize(128 * 1024 * 1024);
+
+/// The default prefix of the file names, see [`FileSinkConfig::prefix`].
+pub const DEFAULT_FILE_PREFIX: &str = "batches";
+
+/// When the current file is closed and a new one is opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotatePolicy {
+    /// Once the current file reaches the given size in bytes, including the rows buffered for
+    /// its next row group.
+    BySize(u64),
+    /// Once the current file has been open for the given duration. The age of the file is
+    /// checked on every batch and on every `TimerTick`, a file rotates while idle only if the
+    /// exporter is configured with a timer.
+    ByTime(Duration),
+    /// Once the given number of batches has been written to the current file.
+    ByCount(usize),
+}
+
+/// The configuration of a [`FileSinkExporter`].
+#[derive(Clone, Debug)]
+pub struct FileSinkConfig {
+    /// The directory the files are written to, created if needed.
+    pub directory: PathBuf,
+    /// The prefix of the file names.
+    pub prefix: String,
+    /// When the current file is closed and a new one is opened.
+    pub rotate: RotatePolicy,
+}
+
+impl FileSinkConfig {
+    /// Creates a configuration writing the files to the given directory, with the default prefix
+    /// and rotation policy.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FileSinkConfig {
+            directory: directory.into(),
+            prefix: DEFAULT_FILE_PREFIX.to_owned(),
+            rotate: DEFAULT_ROTATE_POLICY,
+        }
+    }
+
+    /// Sets the prefix of the file names.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the rotation policy.
+    #[must_use]
+    pub fn with_rotate(mut self, rotate: RotatePolicy) -> Self {
+        self.rotate = rotate;
+        self
+    }
+}
+
+/// An exporter that writes record batches to rotated Parquet files.
+pub struct FileSinkExporter<PData> {
+    config: FileSinkConfig,
+    _pd: PhantomData<fn(PData)>,
+}
+
+impl<PData> FileSinkExporter<PData> {
+    /// Creates a new file sink exporter with the given configuration.
+    #[must_use]
+    pub fn new(config: FileSinkConfig) -> Self {
+        FileSinkExporter {
+            config,
+            _pd: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<PData> shared::Exporter<PData> for FileSinkExporter<PData>
+where
+    PData: Into<RecordBatch> + Send + 'static,
+{
+    async fn start(
+        self: Box<Self>,
+        mut msg_chan: shared::MessageChannel<PData>,
+        effect_handler: shared::EffectHandler<PData>,
+    ) -> Result<(), Error<PData>> {
+        let mut sink = FileSink::new(self.config);
+        // The effect handler is only `Sync` if the pdata are, the name is captured instead.
+        let exporter = effect_handler.exporter_name();
+        let export_error = |error: SinkError| Error::ExporterError {
+            exporter: exporter.clone(),
+            error: error.to_string(),
+        };
+
+        loop {
+            match msg_chan.recv().await? {
+                Message::PData(pdata) => sink.write(pdata.into()).map_err(export_error)?,
+                Message::Control(ControlMsg::TimerTick {}) => {
+                    sink.rotate_if_expired().map_err(export_error)?;
+                }
+                Message::Control(ControlMsg::Flush { id, .. }) => {
+                    sink.flush().map_err(export_error)?;
+                    effect_handler.ack_flush(id);
+                }
+                Message::Control(ControlMsg::Shutdown { .. }) => {
+                    sink.close().map_err(export_error)?;
+                    break;
+                }
+                Message::Control(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The errors failing the exporter.
+#[derive(thiserror::Error, Debug)]
+enum SinkError {
+    /// A file can't be created.
+    #[error("Failed to create {path}: {error}")]
+    Create {
+        /// The path of the file.
+        path: PathBuf,
+        /// The cause of the failure.
+        error: std::io::Error,
+    },
+
+    /// A batch can't be encoded or written.
+    #[error("Parquet error: {0}")]
+    Write(#[from] ParquetError),
+}
+
+/// An open Parquet file.
+struct OpenFile {
+    writer: ArrowWriter<File>,
+    /// The schema of the batches of the file.
+    schema: SchemaRef,
+    opened_at: Instant,
+    batches: usize,
+}
+
+impl OpenFile {
+    /// Returns true if the file must be closed according to the given rotation policy.
+    fn is_full(&self, rotate: RotatePolicy) -> bool {
+        match rotate {
+            RotatePolicy::BySize(bytes) => {
+                let size = self.writer.bytes_written() + self.writer.in_progress_size();
+                u64::try_from(size).unwrap_or(u64::MAX) >= bytes
+            }
+            RotatePolicy::ByTime(duration) => self.opened_at.elapsed() >= duration,
+            RotatePolicy::ByCount(batches) => self.batches >= batches,
+        }
+    }
+}
+
+/// The sequence of files written by the exporter.
+struct FileSink {
+    config: FileSinkConfig,
+    current: Option<OpenFile>,
+    /// The sequence number of the next file.
+    next_seq: u64,
+}
+
+impl FileSink {
+    fn new(config: FileSinkConfig) -> Self {
+        FileSink {
+            config,
+            current: None,
+            next_seq: 0,
+        }
+    }
+
+    /// Writes the given batch to the current file, opening a new one if needed, and closes the
+    /// file once it is full.
+    fn write(&mut self, batch: RecordBatch) -> Result<(), SinkError> {
+        self.rotate_if_expired()?;
+        if let Some(file) = &self.current {
+            if file.schema != batch.schema() {
+                // The schema of a file is fixed, the batch starts a new one.
+                self.close()?;
+            }
+        }
+        let mut file = match self.current.take() {
+            Some(file) => file,
+            None => self.open(batch.schema())?,
+        };
+        file.writer.write(&batch)?;
+        file.batches += 1;
+        if file.is_full(self.config.rotate) {
+            _ = file.writer.close()?;
+        } else {
+            self.current = Some(file);
+        }
+        Ok(())
+    }
+
+    /// Closes the current file if it has been open for longer than the rotation period.
+    fn rotate_if_expired(&mut self) -> Result<(), SinkError> {
+        let expired = match (&self.current, self.config.rotate) {
+            (Some(file), RotatePolicy::ByTime(duration)) => file.opened_at.elapsed() >= duration,
+            _ => false,
+        };
+        if expired { self.close() } else { Ok(()) }
+    }
+
+    /// Writes the buffered rows to the current file.
+    fn flush(&mut self) -> Result<(), SinkError> {
+        if let Some(file) = &mut self.current {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes and closes the current file, the next batch opens a new one.
+    fn close(&mut self) -> Result<(), SinkError> {
+        if let Some(file) = self.current.take() {
+            _ = file.writer.close()?;
+        }
+        Ok(())
+    }
+
+    /// Opens the next file of the sequence for batches of the given schema.
+    fn open(&mut self, schema: SchemaRef) -> Result<OpenFile, SinkError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self.config.directory.join(format!(
+            "{}-{timestamp}-{:06}.parquet",
+            self.config.prefix, self.next_seq
+        ));
+        let create_error = |error| SinkError::Create {
+            path: path.clone(),
+            error,
+        };
+        std::fs::create_dir_all(&self.config.directory).map_err(create_error)?;
+        let file = File::create(&path).map_err(create_error)?;
+        self.next_seq += 1;
+        Ok(OpenFile {
+            writer: ArrowWriter::try_new(file, schema.clone(), None)?,
+            schema,
+            opened_at: Instant::now(),
+            batches: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_sink_exporter::{
+        DEFAULT_ROTATE_POLICY, FileSink, FileSinkConfig, FileSinkExporter, RotatePolicy,
+    };
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use otap_df_engine::exporter::ExporterWrapper;
+    use otap_df_engine::testing::exporter::TestRuntime;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Returns a record batch with the given ids.
+    fn batch(ids: &[i64]) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        let names: Vec<String> = ids.iter().map(|id| format!("name-{id}")).collect();
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int64Array::from(ids.to_vec())),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .expect("Failed to build the record batch")
+    }
+
+    /// Returns the files of the given directory, in the order they were written.
+    fn written_files(directory: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(directory)
+            .expect("Failed to list the files")
+            .map(|entry| entry.expect("Failed to list the files").path())
+            .collect();
+        // The sequence number orders the files written within the same millisecond.
+        files.sort_by_key(|path| {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("");
+            name.rsplit('-').next().unwrap_or("").to_owned()
+        });
+        files
+    }
+
+    /// Returns the ids of the rows of the given Parquet file.
+    fn read_ids(path: &Path) -> Vec<i64> {
+        let file = File::open(path).expect("Failed to open the file");
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .and_then(|builder| builder.build())
+            .expect("Invalid Parquet file");
+        let mut ids = Vec::new();
+        for batch in reader {
+            let batch = batch.expect("Invalid batch");
+            let column = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .expect("Invalid id column");
+            ids.extend(column.values().iter().copied());
+        }
+        ids
+    }
+
+    /// Returns the ids of the rows of each file written to the given directory, checking the
+    /// names of the files.
+    fn read_files(directory: &Path) -> Vec<Vec<i64>> {
+        let files = written_files(directory);
+        for file in &files {
+            let name = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            assert!(name.starts_with("test-") && name.ends_with(".parquet"));
+        }
+        files.iter().map(|file| read_ids(file)).collect()
+    }
+
+    /// Sends the given batches to an exporter with the given rotation policy, and returns the ids
+    /// of the rows of each written file.
+    fn export(rotate: RotatePolicy, batches: Vec<RecordBatch>) -> Vec<Vec<i64>> {
+        let directory = tempfile::tempdir().expect("Failed to create a temporary directory");
+        let config = FileSinkConfig::new(directory.path())
+            .with_prefix("test")
+            .with_rotate(rotate);
+        let test_runtime = TestRuntime::new();
+        let exporter = ExporterWrapper::shared(
+            FileSinkExporter::<RecordBatch>::new(config),
+            test_runtime.config(),
+        );
+
+        test_runtime
+            .set_exporter(exporter)
+            .run_test(move |ctx| async move {
+                for batch in batches {
+                    ctx.send_pdata(batch).await.expect("Failed to send pdata");
+                }
+                ctx.send_shutdown(Duration::from_millis(200), "Test")
+                    .await
+                    .expect("Failed to send Shutdown");
+            })
+            .run_validation(|_ctx| async move {});
+
+        read_files(directory.path())
+    }
+
+    #[test]
+    fn test_file_sink_rotates_by_count() {
+        let batches = vec![batch(&[1, 2]), batch(&[3]), batch(&[4, 5]), batch(&[6])];
+        let files = export(RotatePolicy::ByCount(2), batches);
+        assert_eq!(files, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+    }
+
+    #[test]
+    fn test_file_sink_rotates_by_size() {
+        let batches = vec![batch(&[1, 2]), batch(&[3]), batch(&[4])];
+        // Every batch fills a file.
+        let files = export(RotatePolicy::BySize(1), batches);
+        assert_eq!(files, vec![vec![1, 2], vec![3], vec![4]]);
+    }
+
+    #[test]
+    fn test_file_sink_rotates_by_time() {
+        let directory = tempfile::tempdir().expect("Failed to create a temporary directory");
+        let config = FileSinkConfig::new(directory.path())
+            .with_prefix("test")
+            .with_rotate(RotatePolicy::ByTime(Duration::from_millis(50)));
+        let mut sink = FileSink::new(config);
+
+        sink.write(batch(&[1])).expect("Failed to write");
+        std::thread::sleep(Duration::from_millis(100));
+        // The expired file is closed before the batch is written.
+        sink.write(batch(&[2])).expect("Failed to write");
+        std::thread::sleep(Duration::from_millis(100));
+        // The expired file is closed while idle, on a timer tick.
+        sink.rotate_if_expired().expect("Failed to rotate");
+        assert_eq!(read_files(directory.path()), vec![vec![1], vec![2]]);
+
+        sink.write(batch(&[3])).expect("Failed to write");
+        sink.close().expect("Failed to close");
+        assert_eq!(
+            read_files(directory.path()),
+            vec![vec![1], vec![2], vec![3]]
+        );
+    }
+
+    #[test]
+    fn test_file_sink_closes_file_on_shutdown() {
+        let batches = vec![batch(&[1, 2]), batch(&[3])];
+        let files = export(DEFAULT_ROTATE_POLICY, batches);
+        assert_eq!(files, vec![vec![1, 2, 3]]);
+    }
+}
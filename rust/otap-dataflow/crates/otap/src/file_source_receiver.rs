@@ -0,0 +1,435 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implementation of the file source receiver node.
+//!
+//! The receiver replays the Parquet files of a directory, e.g. the files written by the file sink
+//! exporter (see [`crate::file_sink_exporter`]) to debug a pipeline with the data it once
+//! processed. The files are read in the order of their names, and every row group of a file is
+//! sent downstream as a record batch.
+//!
+//! The replay is as fast as possible by default. With a positive replay speed (see
+//! [`FileSourceConfig::replay_speed`]), the receiver sleeps before each file for the time elapsed
+//! between the openings of the file and of the previous one, as recorded in their names by the
+//! file sink exporter (e.g. `batches-1700000000000-000000.parquet`), divided by the replay speed.
+//! The control messages received meanwhile don't cut the delay short, only a `Shutdown` does.
+//!
+//! The pacing is per file, as the file names are the only timing recorded: the row groups of a
+//! file are sent back to back, and the files named otherwise are replayed without delay.
+//!
+//! Once all the files are replayed, the receiver shuts itself down by sending a `Shutdown` to its
+//! own control channel. The files are read synchronously, as the receiver isn't meant for
+//! production traffic.
+
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use otap_df_engine::error::Error;
+use otap_df_engine::message::ControlMsg;
+use otap_df_engine::shared::receiver as shared;
+use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ParquetRecordBatchReaderBuilder};
+use parquet::errors::ParquetError;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::{Instant, timeout_at};
+
+/// The deadline of the `Shutdown` the receiver sends to itself once all the files are replayed.
+pub const REPLAY_DONE_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(1);
+
+/// The configuration of a [`FileSourceReceiver`].
+#[derive(Clone, Debug)]
+pub struct FileSourceConfig {
+    /// The directory the Parquet files are read from.
+    pub directory: PathBuf,
+    /// The speed of the replay relative to the original timing of the files, e.g. `1.0` to
+    /// replay the files at their original pace or `2.0` twice as fast. The files are replayed as
+    /// fast as possible if the speed isn't positive.
+    pub replay_speed: f64,
+}
+
+impl FileSourceConfig {
+    /// Creates a configuration replaying the files of the given directory as fast as possible.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FileSourceConfig {
+            directory: directory.into(),
+            replay_speed: 0.0,
+        }
+    }
+
+    /// Sets the speed of the replay relative to the original timing of the files.
+    #[must_use]
+    pub fn with_replay_speed(mut self, replay_speed: f64) -> Self {
+        self.replay_speed = replay_speed;
+        self
+    }
+}
+
+/// A receiver that replays the row groups of Parquet files as record batches.
+pub struct FileSourceReceiver {
+    config: FileSourceConfig,
+}
+
+impl FileSourceReceiver {
+    /// Creates a new file source receiver with the given configuration.
+    #[must_use]
+    pub fn new(config: FileSourceConfig) -> Self {
+        FileSourceReceiver { config }
+    }
+}
+
+#[async_trait]
+impl shared::Receiver<RecordBatch> for FileSourceReceiver {
+    async fn start(
+        self: Box<Self>,
+        mut ctrl_msg_recv: shared::ControlChannel,
+        effect_handler: shared::EffectHandler<RecordBatch>,
+    ) -> Result<(), Error<RecordBatch>> {
+        let receiver = effect_handler.receiver_name();
+        let source_error = |error: SourceError| Error::ReceiverError {
+            receiver: receiver.clone(),
+            error: error.to_string(),
+        };
+        let files = list_files(&self.config.directory).map_err(source_error)?;
+        effect_handler.notify_ready();
+
+        let mut previous_timestamp = None;
+        for path in files {
+            let timestamp = file_timestamp(&path);
+            let delay = match (previous_timestamp, timestamp) {
+                (Some(previous), Some(current)) => {
+                    replay_delay(current.saturating_sub(previous), self.config.replay_speed)
+                }
+                _ => Duration::ZERO,
+            };
+            previous_timestamp = timestamp;
+            if delay > Duration::ZERO && wait_for_delay(&mut ctrl_msg_recv, delay).await? {
+                return Ok(());
+            }
+
+            let file = RowGroupReader::open(path).map_err(source_error)?;
+            for row_group in 0..file.row_groups() {
+                // Handles the pending control messages, and waits while the receiver is paused.
+                while let Some(msg) = ctrl_msg_recv.try_recv()? {
+                    if msg.is_shutdown() {
+                        return Ok(());
+                    }
+                }
+                if let Some(ControlMsg::Shutdown { .. }) = ctrl_msg_recv.wait_for_resume().await? {
+                    return Ok(());
+                }
+                if let Some(batch) = file.read(row_group).map_err(source_error)? {
+                    effect_handler.send_message(batch).await?;
+                }
+            }
+        }
+
+        effect_handler
+            .request_shutdown(REPLAY_DONE_SHUTDOWN_DEADLINE, "All the files were replayed")
+            .await?;
+        while !ctrl_msg_recv.recv().await?.is_shutdown() {}
+        Ok(())
+    }
+}
+
+/// Waits for the given delay while handling the control messages received in the meantime,
+/// which don't cut the delay short. Returns `true` if a `Shutdown` was received.
+async fn wait_for_delay(
+    ctrl_msg_recv: &mut shared::ControlChannel,
+    delay: Duration,
+) -> Result<bool, Error<RecordBatch>> {
+    // A saturated delay has no deadline, the receiver then only waits for a `Shutdown`.
+    let deadline = Instant::now().checked_add(delay);
+    loop {
+        let msg = match deadline {
+            Some(deadline) => match timeout_at(deadline, ctrl_msg_recv.recv()).await {
+                Ok(msg) => msg?,
+                Err(_) => return Ok(false),
+            },
+            None => ctrl_msg_recv.recv().await?,
+        };
+        if msg.is_shutdown() {
+            return Ok(true);
+        }
+    }
+}
+
+/// The errors failing the receiver.
+#[derive(thiserror::Error, Debug)]
+enum SourceError {
+    /// A file or the directory can't be read.
+    #[error("Failed to read {path}: {error}")]
+    Read {
+        /// The path of the file or directory.
+        path: PathBuf,
+        /// The cause of the failure.
+        error: std::io::Error,
+    },
+
+    /// A file can't be decoded.
+    #[error("Parquet error in {path}: {error}")]
+    Decode {
+        /// The path of the file.
+        path: PathBuf,
+        /// The cause of the failure.
+        error: ParquetError,
+    },
+}
+
+/// Returns the Parquet files of the given directory, sorted by name.
+fn list_files(directory: &Path) -> Result<Vec<PathBuf>, SourceError> {
+    let read_error = |error| SourceError::Read {
+        path: directory.to_owned(),
+        error,
+    };
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "parquet") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the time the given file was opened at, in milliseconds since the Unix epoch, if it is
+/// named like the files of the file sink exporter, i.e. `<prefix>-<timestamp>-<sequence>`.
+fn file_timestamp(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut parts = stem.rsplit('-');
+    let _sequence = parts.next()?;
+    parts.next()?.parse().ok()
+}
+
+/// Returns how long to wait before replaying a file opened `elapsed_ms` milliseconds after the
+/// previous one, at the given replay speed. A speed that isn't positive (including NaN) replays
+/// the files without delay, a delay too long to be represented is saturated.
+fn replay_delay(elapsed_ms: u64, replay_speed: f64) -> Duration {
+    if replay_speed > 0.0 {
+        Duration::try_from_secs_f64(elapsed_ms as f64 / 1000.0 / replay_speed)
+            .unwrap_or(Duration::MAX)
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// A Parquet file read one row group at a time.
+struct RowGroupReader {
+    path: PathBuf,
+    file: File,
+    metadata: ArrowReaderMetadata,
+}
+
+impl RowGroupReader {
+    fn open(path: PathBuf) -> Result<Self, SourceError> {
+        let file = File::open(&path).map_err(|error| SourceError::Read {
+            path: path.clone(),
+            error,
+        })?;
+        match ArrowReaderMetadata::load(&file, Default::default()) {
+            Ok(metadata) => Ok(RowGroupReader {
+                path,
+                file,
+                metadata,
+            }),
+            Err(error) => Err(SourceError::Decode { path, error }),
+        }
+    }
+
+    /// Returns the number of row groups of the file.
+    fn row_groups(&self) -> usize {
+        self.metadata.metadata().num_row_groups()
+    }
+
+    /// Reads the given row group as a single record batch, `None` if it has no rows.
+    fn read(&self, row_group: usize) -> Result<Option<RecordBatch>, SourceError> {
+        let rows = self.metadata.metadata().row_group(row_group).num_rows();
+        let rows = usize::try_from(rows).unwrap_or_default();
+        if rows == 0 {
+            return Ok(None);
+        }
+        let file = self.file.try_clone().map_err(|error| SourceError::Read {
+            path: self.path.clone(),
+            error,
+        })?;
+        let decode_error = |error| SourceError::Decode {
+            path: self.path.clone(),
+            error,
+        };
+        let mut reader =
+            ParquetRecordBatchReaderBuilder::new_with_metadata(file, self.metadata.clone())
+                .with_row_groups(vec![row_group])
+                .with_batch_size(rows)
+                .build()
+                .map_err(decode_error)?;
+        reader
+            .next()
+            .transpose()
+            .map_err(|error| decode_error(error.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_source_receiver::{FileSourceConfig, FileSourceReceiver, replay_delay};
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use otap_df_engine::receiver::ReceiverWrapper;
+    use otap_df_engine::testing::receiver::TestRuntime;
+    use parquet::arrow::ArrowWriter;
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::time::timeout;
+
+    /// Returns a record batch with the given ids.
+    fn batch(ids: &[i64]) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        let names: Vec<String> = ids.iter().map(|id| format!("name-{id}")).collect();
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int64Array::from(ids.to_vec())),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .expect("Failed to build the record batch")
+    }
+
+    /// Writes the given batches to a Parquet file, each batch as a row group.
+    fn write_file(directory: &Path, name: &str, batches: &[RecordBatch]) {
+        let file = File::create(directory.join(name)).expect("Failed to create the file");
+        let mut writer = ArrowWriter::try_new(file, batches[0].schema(), None)
+            .expect("Failed to create the writer");
+        for batch in batches {
+            writer.write(batch).expect("Failed to write the batch");
+            writer.flush().expect("Failed to flush the row group");
+        }
+        _ = writer.close().expect("Failed to close the file");
+    }
+
+    /// Replays the files of the given directory at the given speed, and returns the received
+    /// batches.
+    fn replay(directory: &Path, replay_speed: f64) -> Vec<RecordBatch> {
+        let config = FileSourceConfig::new(directory).with_replay_speed(replay_speed);
+        let test_runtime = TestRuntime::new();
+        let receiver =
+            ReceiverWrapper::shared(FileSourceReceiver::new(config), test_runtime.config());
+
+        // The receiver shuts itself down once the files are replayed.
+        test_runtime
+            .set_receiver(receiver)
+            .run_test(|_ctx| async move {})
+            .run_validation(|mut ctx| async move {
+                let mut batches = Vec::new();
+                while let Ok(Ok(batch)) = timeout(Duration::from_millis(100), ctx.recv()).await {
+                    batches.push(batch);
+                }
+                batches
+            })
+    }
+
+    #[test]
+    fn test_file_source_replays_row_groups() {
+        let directory = tempfile::tempdir().expect("Failed to create a temporary directory");
+        write_file(
+            directory.path(),
+            "batches-1000-000001.parquet",
+            &[batch(&[4]), batch(&[5, 6])],
+        );
+        write_file(
+            directory.path(),
+            "batches-1000-000000.parquet",
+            &[batch(&[1, 2]), batch(&[3])],
+        );
+        std::fs::write(directory.path().join("notes.txt"), "not a Parquet file")
+            .expect("Failed to write the file");
+
+        let batches = replay(directory.path(), 0.0);
+        assert_eq!(
+            batches,
+            vec![batch(&[1, 2]), batch(&[3]), batch(&[4]), batch(&[5, 6])]
+        );
+    }
+
+    #[test]
+    fn test_file_source_replay_speed() {
+        let directory = tempfile::tempdir().expect("Failed to create a temporary directory");
+        write_file(
+            directory.path(),
+            "batches-1000-000000.parquet",
+            &[batch(&[1])],
+        );
+        write_file(
+            directory.path(),
+            "batches-1400-000001.parquet",
+            &[batch(&[2])],
+        );
+
+        // The 400ms between the files are replayed in 200ms.
+        let start = Instant::now();
+        let batches = replay(directory.path(), 2.0);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(batches, vec![batch(&[1]), batch(&[2])]);
+    }
+
+    #[test]
+    fn test_file_source_delay_not_cut_short() {
+        let directory = tempfile::tempdir().expect("Failed to create a temporary directory");
+        write_file(
+            directory.path(),
+            "batches-1000-000000.parquet",
+            &[batch(&[1])],
+        );
+        write_file(
+            directory.path(),
+            "batches-1400-000001.parquet",
+            &[batch(&[2])],
+        );
+
+        // The timer ticks received during the 400ms before the second file don't end the wait.
+        let config = FileSourceConfig::new(directory.path()).with_replay_speed(1.0);
+        let test_runtime = TestRuntime::new();
+        let receiver =
+            ReceiverWrapper::shared(FileSourceReceiver::new(config), test_runtime.config());
+        let start = Instant::now();
+        let (batches, last_received) = test_runtime
+            .set_receiver(receiver)
+            .run_test(|ctx| async move {
+                for _ in 0..4 {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    ctx.send_timer_tick()
+                        .await
+                        .expect("Failed to send the timer tick");
+                }
+            })
+            .run_validation(move |mut ctx| async move {
+                let mut batches = Vec::new();
+                let mut last_received = Duration::ZERO;
+                while let Ok(Ok(batch)) = timeout(Duration::from_millis(500), ctx.recv()).await {
+                    batches.push(batch);
+                    last_received = start.elapsed();
+                }
+                (batches, last_received)
+            });
+        assert!(last_received >= Duration::from_millis(400));
+        assert_eq!(batches, vec![batch(&[1]), batch(&[2])]);
+    }
+
+    #[test]
+    fn test_replay_delay() {
+        assert_eq!(replay_delay(400, 2.0), Duration::from_millis(200));
+        assert_eq!(replay_delay(400, 0.0), Duration::ZERO);
+        assert_eq!(replay_delay(400, -1.0), Duration::ZERO);
+        assert_eq!(replay_delay(400, f64::NAN), Duration::ZERO);
+        assert_eq!(replay_delay(400, f64::INFINITY), Duration::ZERO);
+        // Too slow a replay saturates the delay instead of overflowing.
+        assert_eq!(replay_delay(400, f64::MIN_POSITIVE), Duration::MAX);
+        assert_eq!(replay_delay(u64::MAX, 1e-30), Duration::MAX);
+    }
+}
@@ -1,3 +1,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Implementation of the OTAP nodes (receiver, exporter, processor).
+
+pub mod arrow_ipc_exporter;
+pub mod arrow_ipc_receiver;
+pub mod file_sink_exporter;
+pub mod file_source_receiver;
+pub mod pdata;
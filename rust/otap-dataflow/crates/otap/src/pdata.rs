@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The pdata exchanged by the OTAP nodes, i.e. OpenTelemetry Arrow record batches.
+//!
+//! The engine is generic over the pdata of the pipelines (e.g. `ReceiverWrapper<PData>`), the
+//! [`OtelPData`] trait narrows it down to the types convertible to and from an
+//! [`OtelArrowBatch`], so that the Arrow-specific code has a concrete type to work with while the
+//! pipelines keep their own pdata type.
+
+use arrow::record_batch::RecordBatch;
+
+/// An OpenTelemetry Arrow record batch.
+pub type OtelArrowBatch = RecordBatch;
+
+/// The pdata of the pipelines made of OTAP nodes, convertible to and from an [`OtelArrowBatch`].
+///
+/// Implemented for every type with the required conversions, including [`OtelArrowBatch`] itself.
+pub trait OtelPData: Into<OtelArrowBatch> + From<OtelArrowBatch> + Send + 'static {}
+
+impl<T> OtelPData for T where T: Into<OtelArrowBatch> + From<OtelArrowBatch> + Send + 'static {}
+
+#[cfg(test)]
+mod tests {
+    use super::{OtelArrowBatch, OtelPData};
+    use arrow::array::{ArrayRef, UInt32Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    /// A custom pdata wrapping an [`OtelArrowBatch`].
+    struct TaggedBatch {
+        batch: OtelArrowBatch,
+    }
+
+    impl From<OtelArrowBatch> for TaggedBatch {
+        fn from(batch: OtelArrowBatch) -> Self {
+            TaggedBatch { batch }
+        }
+    }
+
+    impl From<TaggedBatch> for OtelArrowBatch {
+        fn from(pdata: TaggedBatch) -> Self {
+            pdata.batch
+        }
+    }
+
+    /// Converts the given batch to the given pdata and back.
+    fn round_trip<PData: OtelPData>(batch: OtelArrowBatch) -> OtelArrowBatch {
+        PData::from(batch).into()
+    }
+
+    #[test]
+    fn test_otel_pdata_round_trip() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::UInt32, false)]));
+        let ids: ArrayRef = Arc::new(UInt32Array::from(vec![1, 2, 3]));
+        let batch = OtelArrowBatch::try_new(schema, vec![ids]).expect("Invalid record batch");
+
+        assert_eq!(round_trip::<OtelArrowBatch>(batch.clone()), batch);
+        assert_eq!(round_trip::<TaggedBatch>(batch.clone()), batch);
+    }
+}
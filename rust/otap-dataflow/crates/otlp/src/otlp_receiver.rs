@@ -23,6 +23,7 @@ use otap_df_engine::error::Error;
 use otap_df_engine::message::ControlMsg;
 use otap_df_engine::shared::receiver as shared;
 use std::net::SocketAddr;
+use tokio::time::Instant;
 use tonic::codegen::tokio_stream::wrappers::TcpListenerStream;
 use tonic::transport::Server;
 
@@ -55,6 +56,8 @@ impl shared::Receiver<OTLPData> for OTLPReceiver {
         // create listener on addr provided from config
         let listener = effect_handler.tcp_listener(self.listening_addr)?;
         let mut listener_stream = TcpListenerStream::new(listener);
+        // The server isn't polled until this instant, see the Throttle control message.
+        let mut throttled_until = Instant::now();
 
         //start event loop
         loop {
@@ -87,6 +90,7 @@ impl shared::Receiver<OTLPData> for OTLPReceiver {
                     .accept_compressed(encoding);
             }
 
+            let throttled = Instant::now() < throttled_until;
             tokio::select! {
                 biased; //prioritize ctrl_msg over all other blocks
                 // Process internal event
@@ -96,6 +100,11 @@ impl shared::Receiver<OTLPData> for OTLPReceiver {
                             // ToDo: add proper deadline function
                             break;
                         },
+                        Ok(ControlMsg::Throttle {duration}) => {
+                            // Stop serving requests while the downstream nodes catch up, the
+                            // control messages are still received in the meantime
+                            throttled_until = Instant::now() + duration;
+                        },
                         Err(e) => {
                             return Err(Error::ChannelRecvError(e));
                         }
@@ -104,13 +113,15 @@ impl shared::Receiver<OTLPData> for OTLPReceiver {
                         }
                     }
                 }
-                // Poll the grpc server
+                // Serve again once the throttle window ends
+                () = tokio::time::sleep_until(throttled_until), if throttled => {}
+                // Poll the grpc server, unless the ingestion is paused or throttled
                 result = Server::builder()
                 .add_service(logs_service_server)
                 .add_service(metrics_service_server)
                 .add_service(trace_service_server)
                 .add_service(profiles_service_server)
-                .serve_with_incoming(&mut listener_stream)=> {
+                .serve_with_incoming(&mut listener_stream), if !effect_handler.is_paused() && !throttled => {
                     if let Err(error) = result {
                         // Report receiver error
                         return Err(Error::ReceiverError{receiver: effect_handler.receiver_name(), error: error.to_string()});
@@ -140,6 +151,7 @@ mod tests {
     use std::future::Future;
     use std::net::SocketAddr;
     use std::pin::Pin;
+    use std::time::Instant;
     use tokio::time::{Duration, timeout};
 
     /// Test closure that simulates a typical receiver scenario.
@@ -259,4 +271,28 @@ mod tests {
             .run_test(scenario(grpc_endpoint))
             .run_validation(validation_procedure());
     }
+
+    #[test]
+    fn test_otlp_receiver_shutdown_while_throttled() {
+        let test_runtime = TestRuntime::new();
+        let grpc_port = portpicker::pick_unused_port().expect("No free ports");
+        let addr: SocketAddr = format!("127.0.0.1:{grpc_port}").parse().unwrap();
+        let receiver =
+            ReceiverWrapper::shared(OTLPReceiver::new(addr, None), test_runtime.config());
+
+        // The Shutdown is handled without waiting for the end of the throttle window.
+        let start = Instant::now();
+        test_runtime
+            .set_receiver(receiver)
+            .run_test(|ctx| async move {
+                ctx.send_throttle(Duration::from_secs(60))
+                    .await
+                    .expect("Failed to send Throttle");
+                ctx.send_shutdown(Duration::from_millis(0), "Test")
+                    .await
+                    .expect("Failed to send Shutdown");
+            })
+            .run_validation(|_ctx| async {});
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
 }